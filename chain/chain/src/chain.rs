@@ -9,13 +9,15 @@ use log::{debug, error, info, warn};
 use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::SeedableRng;
+use rayon::prelude::*;
 
 use crate::error::{Error, ErrorKind, LogTransientStorageError};
 use crate::lightclient::get_epoch_block_producers_view;
+use crate::optimistic::OptimisticApplyResults;
 use crate::store::{ChainStore, ChainStoreAccess, ChainStoreUpdate, GCMode};
 use crate::types::{
     AcceptedBlock, ApplyTransactionResult, Block, BlockEconomicsConfig, BlockHeader,
-    BlockHeaderInfo, BlockStatus, ChainGenesis, Provenance, RuntimeAdapter,
+    BlockHeaderInfo, BlockStatus, ChainGenesis, Provenance, ReorgEvent, RuntimeAdapter,
 };
 use crate::validate::{
     validate_challenge, validate_chunk_proofs, validate_chunk_with_chunk_extra,
@@ -23,6 +25,7 @@ use crate::validate::{
 };
 use crate::{byzantine_assert, create_light_client_block_view, Doomslug};
 use crate::{metrics, DoomslugThresholdMode};
+use near_chain_configs::TrustedCheckpoint;
 use near_primitives::block::{genesis_chunks, Tip};
 use near_primitives::challenge::{
     BlockDoubleSign, Challenge, ChallengeBody, ChallengesResult, ChunkProofs, ChunkState,
@@ -34,22 +37,24 @@ use near_primitives::merkle::{
 };
 use near_primitives::receipt::Receipt;
 use near_primitives::sharding::{
-    ChunkHash, ChunkHashHeight, ReceiptList, ReceiptProof, ShardChunk, ShardChunkHeader, ShardInfo,
-    ShardProof, StateSyncInfo,
+    ChunkHash, ChunkHashHeight, ReceiptList, ReceiptProof, ShardChunk, ShardChunkHeader,
+    ShardChunkHeaderInner, ShardInfo, ShardProof, StateSyncInfo,
 };
 use near_primitives::syncing::{
     get_num_state_parts, ReceiptProofResponse, ReceiptResponse, RootProof,
     ShardStateSyncResponseHeader, ShardStateSyncResponseHeaderV1, ShardStateSyncResponseHeaderV2,
     StateHeaderKey, StatePartKey,
 };
-use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
+use near_primitives::transaction::{ExecutionOutcomeWithIdAndProof, ExecutionStatus};
 use near_primitives::types::{
-    AccountId, Balance, BlockExtra, BlockHeight, BlockHeightDelta, ChunkExtra, EpochId, MerkleHash,
-    NumBlocks, ShardId, ValidatorStake,
+    AccountId, Balance, BlockExtra, BlockFeeInfo, BlockHeight, BlockHeightDelta, ChunkExtra,
+    EpochId, MerkleHash, NumBlocks, ShardId, StateChangeValue, StateChangesRequest, ValidatorStake,
+    WatchedAccountChange, WatchedAccountChangeKind,
 };
 use near_primitives::unwrap_or_return;
+use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::{
-    ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
+    DebugBlockView, ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
     FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus, LightClientBlockView,
     SignedTransactionView,
 };
@@ -62,7 +67,7 @@ use delay_detector::DelayDetector;
 pub const MAX_ORPHAN_SIZE: usize = 1024;
 
 /// Maximum age of orhpan to store in the chain.
-const MAX_ORPHAN_AGE_SECS: u64 = 300;
+pub const MAX_ORPHAN_AGE: TimeDuration = TimeDuration::from_secs(300);
 
 /// Refuse blocks more than this many block intervals in the future (as in bitcoin).
 const ACCEPTABLE_TIME_DIFFERENCE: i64 = 12 * 10;
@@ -76,6 +81,10 @@ const NEAR_BASE: Balance = 1_000_000_000_000_000_000_000_000;
 /// Number of epochs for which we keep store data
 pub const NUM_EPOCHS_TO_KEEP_STORE_DATA: u64 = 5;
 
+/// Fewest epochs for which store data can be kept, state sync and catchups read the data of the
+/// previous epochs.
+pub const MIN_EPOCHS_TO_KEEP_STORE_DATA: u64 = 3;
+
 /// Maximum number of height to go through at each step when cleaning forks during garbage collection.
 const GC_FORK_CLEAN_STEP: u64 = 1000;
 
@@ -84,26 +93,60 @@ enum ApplyChunksMode {
     NextEpoch,
 }
 
+/// What is read from the store to apply the chunk of a shard, before the chunks of a block are
+/// applied in parallel. The updates of a block are listed in shard order, which is also the order
+/// their results and errors are looked at.
+enum ShardUpdate {
+    /// The block includes a new chunk for the shard.
+    NewChunk {
+        chunk: ShardChunk,
+        chunk_inner: ShardChunkHeaderInner,
+        height_included: BlockHeight,
+        receipts: Vec<Receipt>,
+    },
+    /// The chunk for the shard is missing, the state is carried over from the previous block.
+    OldChunk { prev_extra: ChunkExtra },
+}
+
 pub struct Orphan {
     block: Block,
     provenance: Provenance,
     added: Instant,
 }
 
+/// Orphans evicted from the pool when one was added.
+#[derive(Default)]
+struct Evicted {
+    /// Older than the maximum age.
+    by_age: usize,
+    /// The highest ones over the capacity.
+    by_capacity: usize,
+}
+
+/// Blocks waiting for their previous block. When the pool is full, the highest blocks are
+/// evicted first: they are the furthest from being processed and the cheapest to flood with.
 pub struct OrphanBlockPool {
     orphans: HashMap<CryptoHash, Orphan>,
     height_idx: HashMap<BlockHeight, Vec<CryptoHash>>,
     prev_hash_idx: HashMap<CryptoHash, Vec<CryptoHash>>,
     evicted: usize,
+    max_size: usize,
+    max_age: TimeDuration,
 }
 
 impl OrphanBlockPool {
     pub fn new() -> OrphanBlockPool {
+        OrphanBlockPool::with_limits(MAX_ORPHAN_SIZE, MAX_ORPHAN_AGE)
+    }
+
+    pub fn with_limits(max_size: usize, max_age: TimeDuration) -> OrphanBlockPool {
         OrphanBlockPool {
             orphans: HashMap::default(),
             height_idx: HashMap::default(),
             prev_hash_idx: HashMap::default(),
             evicted: 0,
+            max_size,
+            max_age,
         }
     }
 
@@ -115,61 +158,117 @@ impl OrphanBlockPool {
         self.evicted
     }
 
-    fn add(&mut self, orphan: Orphan) {
-        let height_hashes =
-            self.height_idx.entry(orphan.block.header().height()).or_insert_with(|| vec![]);
-        height_hashes.push(*orphan.block.hash());
-        let prev_hash_entries =
-            self.prev_hash_idx.entry(*orphan.block.header().prev_hash()).or_insert_with(|| vec![]);
-        prev_hash_entries.push(*orphan.block.hash());
-        self.orphans.insert(*orphan.block.hash(), orphan);
-
-        if self.orphans.len() > MAX_ORPHAN_SIZE {
-            let old_len = self.orphans.len();
-
-            self.orphans.retain(|_, ref mut x| {
-                x.added.elapsed() < TimeDuration::from_secs(MAX_ORPHAN_AGE_SECS)
-            });
-            let mut heights = self.height_idx.keys().cloned().collect::<Vec<u64>>();
+    fn add(&mut self, orphan: Orphan) -> Evicted {
+        let hash = *orphan.block.hash();
+        if self.orphans.contains_key(&hash) {
+            return Evicted::default();
+        }
+        self.height_idx.entry(orphan.block.header().height()).or_insert_with(|| vec![]).push(hash);
+        self.prev_hash_idx
+            .entry(*orphan.block.header().prev_hash())
+            .or_insert_with(|| vec![])
+            .push(hash);
+        self.orphans.insert(hash, orphan);
+
+        let mut evicted = Evicted::default();
+        let max_age = self.max_age;
+        let expired: Vec<_> = self
+            .orphans
+            .iter()
+            .filter(|(_, orphan)| orphan.added.elapsed() >= max_age)
+            .map(|(hash, _)| *hash)
+            .collect();
+        for hash in expired {
+            self.remove(&hash);
+            evicted.by_age += 1;
+        }
+        if self.orphans.len() > self.max_size {
+            let mut heights: Vec<_> = self.height_idx.keys().cloned().collect();
             heights.sort_unstable();
-            let mut removed_hashes: HashSet<CryptoHash> = HashSet::default();
-            for h in heights.iter().rev() {
-                if let Some(hash) = self.height_idx.remove(h) {
-                    for h in hash {
-                        let _ = self.orphans.remove(&h);
-                        removed_hashes.insert(h);
+            for height in heights.into_iter().rev() {
+                for hash in self.height_idx[&height].clone() {
+                    if self.orphans.len() <= self.max_size {
+                        break;
                     }
+                    self.remove(&hash);
+                    evicted.by_capacity += 1;
                 }
-                if self.orphans.len() < MAX_ORPHAN_SIZE {
+                if self.orphans.len() <= self.max_size {
                     break;
                 }
             }
-            self.height_idx.retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(&x)));
-            self.prev_hash_idx
-                .retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(&x)));
-
-            self.evicted += old_len - self.orphans.len();
         }
+        self.evicted += evicted.by_age + evicted.by_capacity;
+        evicted
+    }
+
+    /// Removes the orphan and its entries in the indexes.
+    fn remove(&mut self, hash: &CryptoHash) -> Option<Orphan> {
+        let orphan = self.orphans.remove(hash)?;
+        remove_from_idx(&mut self.height_idx, orphan.block.header().height(), hash);
+        remove_from_idx(&mut self.prev_hash_idx, *orphan.block.header().prev_hash(), hash);
+        Some(orphan)
     }
 
     pub fn contains(&self, hash: &CryptoHash) -> bool {
         self.orphans.contains_key(hash)
     }
 
-    pub fn remove_by_prev_hash(&mut self, prev_hash: CryptoHash) -> Option<Vec<Orphan>> {
-        let mut removed_hashes: HashSet<CryptoHash> = HashSet::default();
-        let ret = self.prev_hash_idx.remove(&prev_hash).map(|hs| {
-            hs.iter()
-                .filter_map(|h| {
-                    removed_hashes.insert(h.clone());
-                    self.orphans.remove(h)
-                })
-                .collect()
-        });
+    /// The first block missing before the given one, following the orphans it descends from.
+    fn missing_ancestor(&self, hash: &CryptoHash) -> CryptoHash {
+        let mut hash = *hash;
+        while let Some(orphan) = self.orphans.get(&hash) {
+            hash = *orphan.block.header().prev_hash();
+        }
+        hash
+    }
 
-        self.height_idx.retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(&x)));
+    /// Blocks in the pool, ordered by height.
+    fn debug_blocks(&self) -> Vec<DebugBlockView> {
+        let mut blocks: Vec<_> = self
+            .orphans
+            .values()
+            .map(|orphan| DebugBlockView {
+                hash: *orphan.block.hash(),
+                height: orphan.block.header().height(),
+                prev_hash: *orphan.block.header().prev_hash(),
+                age_ms: orphan.added.elapsed().as_millis() as u64,
+            })
+            .collect();
+        blocks.sort_by_key(|block| (block.height, block.hash));
+        blocks
+    }
 
-        ret
+    /// Blocks in the pool on top of the given block.
+    fn blocks_by_prev_hash(&self, prev_hash: &CryptoHash) -> Vec<Block> {
+        self.prev_hash_idx
+            .get(prev_hash)
+            .map(|hashes| {
+                hashes
+                    .iter()
+                    .filter_map(|hash| self.orphans.get(hash))
+                    .map(|orphan| orphan.block.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn remove_by_prev_hash(&mut self, prev_hash: CryptoHash) -> Option<Vec<Orphan>> {
+        let hashes = self.prev_hash_idx.get(&prev_hash)?.clone();
+        Some(hashes.iter().filter_map(|hash| self.remove(hash)).collect())
+    }
+}
+
+fn remove_from_idx<K: std::hash::Hash + Eq>(
+    idx: &mut HashMap<K, Vec<CryptoHash>>,
+    key: K,
+    hash: &CryptoHash,
+) {
+    if let Some(hashes) = idx.get_mut(&key) {
+        hashes.retain(|h| h != hash);
+        if hashes.is_empty() {
+            idx.remove(&key);
+        }
     }
 }
 
@@ -186,6 +285,11 @@ pub struct Chain {
     /// Block economics, relevant to changes when new block must be produced.
     pub block_economics_config: BlockEconomicsConfig,
     pub doomslug_threshold_mode: DoomslugThresholdMode,
+    /// Block up to which the signatures of the headers and chunks are not checked.
+    trusted_checkpoint: Option<TrustedCheckpoint>,
+    /// Chunks of the blocks with missing chunks applied ahead, if optimistic block application
+    /// is enabled.
+    optimistic_apply_results: Option<OptimisticApplyResults>,
 }
 
 impl Chain {
@@ -222,6 +326,8 @@ impl Chain {
             epoch_length: chain_genesis.epoch_length,
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
+            trusted_checkpoint: None,
+            optimistic_apply_results: None,
         })
     }
 
@@ -334,6 +440,8 @@ impl Chain {
             epoch_length: chain_genesis.epoch_length,
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
+            trusted_checkpoint: None,
+            optimistic_apply_results: None,
         })
     }
 
@@ -393,7 +501,7 @@ impl Chain {
 
     pub fn save_block(&mut self, block: &Block) -> Result<(), Error> {
         if let Err(e) =
-            Chain::check_block_validity(self.runtime_adapter.as_ref(), &self.genesis, block)
+            Chain::check_block_validity(self.runtime_adapter.as_ref(), &self.genesis, block, true)
         {
             byzantine_assert!(false);
             return Err(e.into());
@@ -410,13 +518,49 @@ impl Chain {
     }
 
     pub fn save_orphan(&mut self, block: &Block) {
-        self.orphans.add(Orphan {
+        self.add_orphan(Orphan {
             block: block.clone(),
             provenance: Provenance::NONE,
             added: Instant::now(),
         });
     }
 
+    /// Sets how many orphans are kept and for how long.
+    pub fn set_orphan_pool_limits(&mut self, max_size: usize, max_age: TimeDuration) {
+        self.orphans.max_size = max_size;
+        self.orphans.max_age = max_age;
+    }
+
+    /// Sets the block up to which the signatures of the headers and chunks are not checked.
+    pub fn set_trusted_checkpoint(&mut self, trusted_checkpoint: Option<TrustedCheckpoint>) {
+        self.trusted_checkpoint = trusted_checkpoint;
+    }
+
+    /// Sets whether the chunks of the blocks with missing chunks are applied as they arrive.
+    pub fn set_optimistic_block_application(&mut self, enabled: bool) {
+        self.optimistic_apply_results =
+            if enabled { Some(OptimisticApplyResults::new()) } else { None };
+    }
+
+    /// Whether the chunk of the shard in the block was applied ahead, and the result is kept
+    /// until the block is processed.
+    pub fn has_optimistic_apply_result(
+        &mut self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+    ) -> bool {
+        self.optimistic_apply_results
+            .as_mut()
+            .map_or(false, |results| results.contains(block_hash, shard_id))
+    }
+
+    fn add_orphan(&mut self, orphan: Orphan) {
+        let evicted = self.orphans.add(orphan);
+        near_metrics::inc_counter_by(&metrics::ORPHAN_BLOCKS_EXPIRED, evicted.by_age as i64);
+        near_metrics::inc_counter_by(&metrics::ORPHAN_BLOCKS_EVICTED, evicted.by_capacity as i64);
+        near_metrics::set_gauge(&metrics::ORPHAN_BLOCKS, self.orphans.len() as i64);
+    }
+
     fn save_block_height_processed(&mut self, block_height: BlockHeight) -> Result<(), Error> {
         let mut chain_store_update = ChainStoreUpdate::new(&mut self.store);
         if !chain_store_update.is_height_processed(block_height)? {
@@ -505,10 +649,48 @@ impl Chain {
         &mut self,
         tries: ShardTries,
         gc_blocks_limit: NumBlocks,
+    ) -> Result<(), Error> {
+        self.clear_data_within(tries, gc_blocks_limit, None)
+    }
+
+    /// Same as `clear_data`, but stops after the height being cleared once `time_limit` is over.
+    /// Forks are cleared for at most half of the time, so that they don't hold back the canonical
+    /// chain, which is cleared for the rest of it. Each of them clears at least one height.
+    /// Heights left to clear are reported as the garbage collection debt.
+    pub fn clear_data_within(
+        &mut self,
+        tries: ShardTries,
+        gc_blocks_limit: NumBlocks,
+        time_limit: Option<TimeDuration>,
     ) -> Result<(), Error> {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("GC".into());
 
+        let start = Instant::now();
+        let forks_deadline = time_limit.map(|time_limit| start + time_limit / 2);
+        let deadline = time_limit.map(|time_limit| start + time_limit);
+        let result = self.clear_data_until(tries, gc_blocks_limit, forks_deadline, deadline);
+        near_metrics::set_gauge(&metrics::GC_DEBT_BLOCKS, self.gc_debt()? as i64);
+        result
+    }
+
+    /// Number of heights below the garbage collection stop height that are not cleared yet.
+    pub fn gc_debt(&self) -> Result<NumBlocks, Error> {
+        let gc_stop_height =
+            self.runtime_adapter.get_gc_stop_height(&self.store.head()?.last_block_hash);
+        Ok(gc_stop_height.saturating_sub(self.store.tail()? + 1))
+    }
+
+    fn clear_data_until(
+        &mut self,
+        tries: ShardTries,
+        gc_blocks_limit: NumBlocks,
+        forks_deadline: Option<Instant>,
+        deadline: Option<Instant>,
+    ) -> Result<(), Error> {
+        let is_over = |deadline: Option<Instant>| {
+            deadline.map_or(false, |deadline| Instant::now() >= deadline)
+        };
         let head = self.store.head()?;
         let tail = self.store.tail()?;
         let gc_stop_height = self.runtime_adapter.get_gc_stop_height(&head.last_block_hash);
@@ -543,6 +725,9 @@ impl Chain {
             let mut chain_store_update = self.store.store_update();
             chain_store_update.update_fork_tail(height);
             chain_store_update.commit()?;
+            if is_over(forks_deadline) {
+                break;
+            }
         }
 
         // Canonical Chain Clearing
@@ -577,6 +762,9 @@ impl Chain {
             }
             chain_store_update.update_tail(height);
             chain_store_update.commit()?;
+            if is_over(deadline) {
+                return Ok(());
+            }
         }
         Ok(())
     }
@@ -623,17 +811,110 @@ impl Chain {
         Ok(())
     }
 
+    /// Rewinds the head by up to `num_blocks` blocks to recover from a locally corrupted chain or
+    /// state. The blocks above the new head are removed with the state they produced, while their
+    /// headers, chunks and epoch data are kept, so syncing applies them again. Returns the new
+    /// head.
+    ///
+    /// A running node doesn't go below the last final block, which it may have already approved
+    /// blocks on top of and served to light clients. A stopped node can go `past_final`, down to
+    /// the tail: the final head then moves back to the last final block of the new head.
+    pub fn undo_blocks(
+        &mut self,
+        tries: ShardTries,
+        num_blocks: NumBlocks,
+        past_final: bool,
+    ) -> Result<Tip, Error> {
+        let head = self.store.head()?;
+        let final_head = self.store.final_head()?;
+        let lowest_height = if past_final { self.store.tail()? } else { final_head.height };
+        let target_height = std::cmp::max(head.height.saturating_sub(num_blocks), lowest_height);
+        let mut target = self.get_block_header(&head.last_block_hash)?.clone();
+        while target.height() > target_height {
+            target = self.get_previous_header(&target)?.clone();
+        }
+
+        // No block is above the head. Blocks are removed from the highest, so that only blocks
+        // without children are removed.
+        for height in (target.height() + 1..=head.height).rev() {
+            let blocks_at_height = match self.store.get_all_block_hashes_by_height(height) {
+                Ok(blocks) => blocks.values().flatten().cloned().collect::<Vec<_>>(),
+                Err(_) => continue,
+            };
+            for block_hash in blocks_at_height {
+                let mut chain_store_update = self.store.store_update();
+                if *chain_store_update.get_block_refcount(&block_hash)? != 0 {
+                    return Err(ErrorKind::Other(format!(
+                        "Block {} at {} has children that are not removed",
+                        block_hash, height
+                    ))
+                    .into());
+                }
+                chain_store_update.clear_block_data(block_hash, GCMode::Undo(tries.clone()))?;
+                chain_store_update.commit()?;
+            }
+        }
+
+        let new_head = Tip::from_header(&target);
+        let watched_accounts_head = self.store.get_watched_accounts_head()?;
+        let mut chain_store_update = self.store.store_update();
+        // Moves the header head too and drops the heights above it from the canonical chain.
+        chain_store_update.save_head(&new_head)?;
+        // The ordinals of the removed blocks are written again with their headers when the blocks
+        // are, the block merkle tree of each header stays with the header.
+        let head_ordinal = chain_store_update.get_block_merkle_tree(&head.last_block_hash)?.size();
+        let new_head_ordinal = chain_store_update.get_block_merkle_tree(target.hash())?.size();
+        chain_store_update.clear_block_ordinals(new_head_ordinal + 1, head_ordinal);
+        if target.height() < final_head.height {
+            let new_final_header = if target.last_final_block() == &CryptoHash::default() {
+                self.genesis.header()
+            } else {
+                chain_store_update.get_block_header(target.last_final_block())?
+            };
+            let new_final_head = Tip::from_header(new_final_header);
+            chain_store_update.save_final_head(&new_final_head)?;
+            // Only final blocks are indexed, the removed ones are indexed again once final.
+            if watched_accounts_head.map_or(false, |height| height > new_final_head.height) {
+                chain_store_update.save_watched_account_changes(new_final_head.height, vec![])?;
+            }
+        }
+        chain_store_update.commit()?;
+        // Tells the view clients, which have their own caches, to drop them.
+        self.store.save_undo()?;
+        near_metrics::set_gauge(&metrics::BLOCK_HEIGHT_HEAD, new_head.height as i64);
+        info!(target: "chain", "Undo blocks: head moved from {} [{}] to {} [{}]", head.height, head.last_block_hash, new_head.height, new_head.last_block_hash);
+        Ok(new_head)
+    }
+
+    /// Clears the caches of the chain store if blocks were undone by another `Chain` on the same
+    /// database since the last call.
+    pub fn clear_caches_if_undone(&mut self) -> Result<(), Error> {
+        self.store.clear_caches_if_undone()
+    }
+
     /// Do Basic validation of a block upon receiving it. Check that header is valid
     /// and block is well-formed (various roots match).
     pub fn validate_block(&mut self, block: &Block) -> Result<(), Error> {
         self.process_block_header(&block.header(), |_| {})?;
-        Self::check_block_validity(self.runtime_adapter.as_ref(), &self.genesis_block(), block)
+        let trusted = is_trusted_by_checkpoint(
+            &mut self.store,
+            self.trusted_checkpoint.as_ref(),
+            block.header(),
+        );
+        Self::check_block_validity(
+            self.runtime_adapter.as_ref(),
+            &self.genesis_block(),
+            block,
+            !trusted,
+        )
     }
 
+    /// Checks the block against its header, and the signatures of its chunks if `check_signatures`.
     fn check_block_validity(
         runtime_adapter: &dyn RuntimeAdapter,
         genesis_block: &Block,
         block: &Block,
+        check_signatures: bool,
     ) -> Result<(), Error> {
         for (shard_id, chunk_header) in block.chunks().iter().enumerate() {
             if chunk_header.height_created() == genesis_block.header().height() {
@@ -645,7 +926,7 @@ impl Chain {
                 {
                     return Err(ErrorKind::InvalidChunk.into());
                 }
-            } else {
+            } else if check_signatures {
                 if !runtime_adapter.verify_chunk_header_signature(&chunk_header.clone())? {
                     byzantine_assert!(false);
                     return Err(ErrorKind::InvalidChunk.into());
@@ -782,6 +1063,9 @@ impl Chain {
 
                 chain_update.validate_header(header, &Provenance::SYNC, on_challenge)?;
                 chain_update.chain_store_update.save_block_header(header.clone())?;
+                // Header sync resumes from the header head after a restart, so it moves with
+                // every header saved rather than once the whole batch is.
+                chain_update.update_header_head_if_not_challenged(header)?;
                 chain_update.commit()?;
 
                 // Add validator proposals for given header.
@@ -848,6 +1132,32 @@ impl Chain {
         }
     }
 
+    /// Describes the switch of the head from the old head to the new one on another fork.
+    pub fn get_reorg_event(
+        &mut self,
+        old_head: &CryptoHash,
+        new_head: &CryptoHash,
+    ) -> Result<ReorgEvent, Error> {
+        let mut old_header = self.get_block_header(old_head)?.clone();
+        let mut new_header = self.get_block_header(new_head)?.clone();
+        let mut abandoned_blocks = vec![];
+        while old_header.hash() != new_header.hash() {
+            if old_header.height() >= new_header.height() {
+                abandoned_blocks.push(*old_header.hash());
+                old_header = self.get_block_header(old_header.prev_hash())?.clone();
+            } else {
+                new_header = self.get_block_header(new_header.prev_hash())?.clone();
+            }
+        }
+        Ok(ReorgEvent {
+            old_head: *old_head,
+            new_head: *new_head,
+            common_ancestor: *old_header.hash(),
+            depth: abandoned_blocks.len() as NumBlocks,
+            abandoned_blocks,
+        })
+    }
+
     pub fn reset_data_pre_state_sync(&mut self, sync_hash: CryptoHash) -> Result<(), Error> {
         let head = self.head()?;
         // Get header we were syncing into.
@@ -1057,7 +1367,7 @@ impl Chain {
                             let block_hash = *block.hash();
                             let orphan = Orphan { block, provenance, added: Instant::now() };
 
-                            self.orphans.add(orphan);
+                            self.add_orphan(orphan);
 
                             debug!(
                                 target: "chain",
@@ -1189,6 +1499,25 @@ impl Chain {
         }
     }
 
+    /// Applies the chunks that arrived of the blocks on top of the given block still missing
+    /// chunks, if optimistic block application is enabled. Only blocks waiting in the pool of
+    /// blocks with missing chunks are applied ahead: a block with all its chunks is applied when
+    /// it's processed, not speculatively before its approvals, and its state is saved then.
+    pub fn apply_chunks_optimistically(
+        &mut self,
+        me: &Option<AccountId>,
+        prev_hash: &CryptoHash,
+    ) -> Result<(), Error> {
+        if self.optimistic_apply_results.is_none() {
+            return Ok(());
+        }
+        for block in self.blocks_with_missing_chunks.blocks_by_prev_hash(prev_hash) {
+            // Nothing is committed, the results are saved with the block.
+            self.chain_update().apply_chunks_optimistically(me, &block)?;
+        }
+        Ok(())
+    }
+
     /// Check for orphans, once a block is successfully added.
     pub fn check_orphans<F, F2, F3>(
         &mut self,
@@ -1240,6 +1569,7 @@ impl Chain {
             queue_idx += 1;
         }
 
+        near_metrics::set_gauge(&metrics::ORPHAN_BLOCKS, self.orphans.len() as i64);
         if queue.len() > 1 {
             debug!(
                 target: "chain",
@@ -1934,7 +2264,7 @@ impl Chain {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(FinalExecutionOutcomeWithReceiptView { final_outcome, receipts })
+        Ok(FinalExecutionOutcomeWithReceiptView::new(final_outcome, receipts))
     }
 
     /// Find a validator to forward transactions to
@@ -2003,6 +2333,133 @@ impl Chain {
         }
         Ok(res)
     }
+
+    /// Indexes the changes of the watched accounts in the final blocks after the last indexed
+    /// one. If no block was indexed yet, starts from the current final block.
+    pub fn index_watched_accounts(&mut self, watched_accounts: &[AccountId]) -> Result<(), Error> {
+        let final_head = self.final_head()?;
+        let last_indexed_height = self.store.get_watched_accounts_head()?;
+        let mut block_hashes = vec![];
+        let mut block_hash = final_head.last_block_hash;
+        loop {
+            let header = match self.get_block(&block_hash) {
+                Ok(block) => block.header(),
+                // Reached genesis, garbage collected blocks or blocks skipped by state sync.
+                Err(_) => break,
+            };
+            match last_indexed_height {
+                Some(height) if header.height() <= height => break,
+                None if !block_hashes.is_empty() => break,
+                _ => {}
+            }
+            block_hashes.push((block_hash, header.height()));
+            block_hash = *header.prev_hash();
+        }
+        for (block_hash, block_height) in block_hashes.into_iter().rev() {
+            let changes = self.get_watched_account_changes_in_block(
+                &block_hash,
+                block_height,
+                watched_accounts,
+            )?;
+            let mut chain_store_update = self.store.store_update();
+            chain_store_update.save_watched_account_changes(block_height, changes)?;
+            chain_store_update.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Transactions and receipts sent by or to the watched accounts, and their balances after
+    /// they changed in the block.
+    fn get_watched_account_changes_in_block(
+        &mut self,
+        block_hash: &CryptoHash,
+        block_height: BlockHeight,
+        watched_accounts: &[AccountId],
+    ) -> Result<Vec<(AccountId, WatchedAccountChange)>, Error> {
+        let mut kinds = vec![];
+        let mut outcomes =
+            self.get_block_execution_outcomes(block_hash)?.into_iter().collect::<Vec<_>>();
+        outcomes.sort_by_key(|(shard_id, _)| *shard_id);
+        for outcome in outcomes.into_iter().flat_map(|(_, outcomes)| outcomes) {
+            let id = outcome.outcome_with_id.id;
+            let success = match outcome.outcome_with_id.outcome.status {
+                ExecutionStatus::Unknown | ExecutionStatus::Failure(_) => false,
+                ExecutionStatus::SuccessValue(_) | ExecutionStatus::SuccessReceiptId(_) => true,
+            };
+            if let Some(transaction) = self.store.get_transaction(&id)?.cloned() {
+                let transaction = transaction.transaction;
+                let account_ids =
+                    vec![transaction.signer_id.clone(), transaction.receiver_id.clone()];
+                let kind = WatchedAccountChangeKind::Transaction {
+                    hash: id,
+                    signer_id: transaction.signer_id,
+                    receiver_id: transaction.receiver_id,
+                    success,
+                };
+                kinds.push((account_ids, kind));
+            } else if let Some(receipt) = self.store.get_receipt(&id)?.cloned() {
+                let account_ids = vec![receipt.predecessor_id.clone(), receipt.receiver_id.clone()];
+                let kind = WatchedAccountChangeKind::Receipt {
+                    receipt_id: id,
+                    predecessor_id: receipt.predecessor_id,
+                    receiver_id: receipt.receiver_id,
+                    success,
+                };
+                kinds.push((account_ids, kind));
+            }
+        }
+
+        let state_changes = self.store.get_state_changes(
+            block_hash,
+            &StateChangesRequest::AccountChanges { account_ids: watched_accounts.to_vec() },
+        )?;
+        let mut balances = HashMap::new();
+        for state_change in state_changes {
+            match state_change.value {
+                StateChangeValue::AccountUpdate { account_id, account } => {
+                    balances.insert(account_id, (account.amount, account.locked));
+                }
+                StateChangeValue::AccountDeletion { account_id } => {
+                    balances.insert(account_id, (0, 0));
+                }
+                _ => {}
+            }
+        }
+
+        let mut changes = vec![];
+        for account_id in watched_accounts {
+            let mut index = 0;
+            for (account_ids, kind) in kinds.iter() {
+                if account_ids.contains(account_id) {
+                    changes.push((
+                        account_id.clone(),
+                        WatchedAccountChange {
+                            block_hash: *block_hash,
+                            block_height,
+                            index,
+                            kind: kind.clone(),
+                        },
+                    ));
+                    index += 1;
+                }
+            }
+            if let Some((amount, locked)) = balances.get(account_id) {
+                changes.push((
+                    account_id.clone(),
+                    WatchedAccountChange {
+                        block_hash: *block_hash,
+                        block_height,
+                        index,
+                        kind: WatchedAccountChangeKind::Balance {
+                            amount: *amount,
+                            locked: *locked,
+                        },
+                    },
+                ));
+            }
+        }
+        Ok(changes)
+    }
 }
 
 /// Implement block merkle proof retrieval.
@@ -2032,6 +2489,8 @@ impl Chain {
             &self.block_economics_config,
             self.doomslug_threshold_mode,
             &self.genesis,
+            self.trusted_checkpoint.as_ref(),
+            self.optimistic_apply_results.as_mut(),
         )
     }
 
@@ -2289,6 +2748,12 @@ impl Chain {
         self.store.get_block_extra(block_hash)
     }
 
+    /// Get gas price and gas usage of the block.
+    #[inline]
+    pub fn get_block_fee_info(&mut self, block_hash: &CryptoHash) -> Result<&BlockFeeInfo, Error> {
+        self.store.get_block_fee_info(block_hash)
+    }
+
     /// Get chunk extra that was computed after applying chunk with given hash.
     #[inline]
     pub fn get_chunk_extra(
@@ -2366,6 +2831,22 @@ impl Chain {
         self.orphans.len_evicted()
     }
 
+    /// Returns the first block missing before the given block, following the orphans it descends
+    /// from: the block to request so that they can be processed.
+    pub fn orphan_missing_ancestor(&self, hash: &CryptoHash) -> CryptoHash {
+        self.orphans.missing_ancestor(hash)
+    }
+
+    /// Returns the blocks currently in the orphan pool.
+    pub fn debug_orphans(&self) -> Vec<DebugBlockView> {
+        self.orphans.debug_blocks()
+    }
+
+    /// Returns the blocks currently waiting for chunks.
+    pub fn debug_blocks_with_missing_chunks(&self) -> Vec<DebugBlockView> {
+        self.blocks_with_missing_chunks.debug_blocks()
+    }
+
     /// Check if hash is for a known orphan.
     #[inline]
     pub fn is_orphan(&self, hash: &CryptoHash) -> bool {
@@ -2415,6 +2896,26 @@ impl Chain {
     }
 }
 
+/// Whether the header is the trusted checkpoint block or one of its ancestors, whose signatures are
+/// not checked. The height index follows the header chain through `prev_hash`, so once it holds
+/// the checkpoint block, the headers it holds below it are the ancestors of the checkpoint. Until
+/// the header of the checkpoint block is known, every header is checked.
+fn is_trusted_by_checkpoint<S: ChainStoreAccess>(
+    chain_store: &mut S,
+    trusted_checkpoint: Option<&TrustedCheckpoint>,
+    header: &BlockHeader,
+) -> bool {
+    let checkpoint = match trusted_checkpoint {
+        Some(checkpoint) if header.height() <= checkpoint.height => checkpoint,
+        _ => return false,
+    };
+    if header.hash() == &checkpoint.block_hash {
+        return true;
+    }
+    chain_store.get_block_hash_by_height(checkpoint.height).ok() == Some(checkpoint.block_hash)
+        && chain_store.get_block_hash_by_height(header.height()).ok() == Some(*header.hash())
+}
+
 /// Chain update helper, contains information that is needed to process block
 /// and decide to accept it or reject it.
 /// If rejected nothing will be updated in underlying storage.
@@ -2428,6 +2929,8 @@ pub struct ChainUpdate<'a> {
     block_economics_config: &'a BlockEconomicsConfig,
     doomslug_threshold_mode: DoomslugThresholdMode,
     genesis: &'a Block,
+    trusted_checkpoint: Option<&'a TrustedCheckpoint>,
+    optimistic_apply_results: Option<&'a mut OptimisticApplyResults>,
 }
 
 impl<'a> ChainUpdate<'a> {
@@ -2440,6 +2943,8 @@ impl<'a> ChainUpdate<'a> {
         block_economics_config: &'a BlockEconomicsConfig,
         doomslug_threshold_mode: DoomslugThresholdMode,
         genesis: &'a Block,
+        trusted_checkpoint: Option<&'a TrustedCheckpoint>,
+        optimistic_apply_results: Option<&'a mut OptimisticApplyResults>,
     ) -> Self {
         let chain_store_update: ChainStoreUpdate<'_> = store.store_update();
         ChainUpdate {
@@ -2451,6 +2956,8 @@ impl<'a> ChainUpdate<'a> {
             block_economics_config,
             doomslug_threshold_mode,
             genesis,
+            trusted_checkpoint,
+            optimistic_apply_results,
         }
     }
 
@@ -2574,13 +3081,26 @@ impl<'a> ChainUpdate<'a> {
         if !self.care_about_any_shard_or_part(me, *block.header().prev_hash())? {
             return Ok(());
         }
+        for (shard_id, receipt_proofs) in self.collect_incoming_receipt_proofs(block)? {
+            self.chain_store_update.save_incoming_receipt(&block.hash(), shard_id, receipt_proofs);
+        }
+
+        Ok(())
+    }
+
+    /// Receipts to every shard from the new chunks of the block, in the order they are applied.
+    /// Fails if a partial chunk of the block is missing.
+    fn collect_incoming_receipt_proofs(
+        &mut self,
+        block: &Block,
+    ) -> Result<HashMap<ShardId, Vec<ReceiptProof>>, Error> {
         let height = block.header().height();
         let mut receipt_proofs_by_shard_id = HashMap::new();
 
         for chunk_header in block.chunks().iter() {
             if chunk_header.height_included() == height {
                 let partial_encoded_chunk =
-                    self.chain_store_update.get_partial_chunk(&chunk_header.chunk_hash()).unwrap();
+                    self.chain_store_update.get_partial_chunk(&chunk_header.chunk_hash())?;
                 for receipt in partial_encoded_chunk.receipts().iter() {
                     let ReceiptProof(_, shard_proof) = receipt;
                     let ShardProof { from_shard_id: _, to_shard_id, proof: _ } = shard_proof;
@@ -2592,15 +3112,14 @@ impl<'a> ChainUpdate<'a> {
             }
         }
 
-        for (shard_id, mut receipt_proofs) in receipt_proofs_by_shard_id {
+        for receipt_proofs in receipt_proofs_by_shard_id.values_mut() {
             let mut slice = [0u8; 32];
             slice.copy_from_slice(block.hash().as_ref());
             let mut rng: StdRng = SeedableRng::from_seed(slice);
             receipt_proofs.shuffle(&mut rng);
-            self.chain_store_update.save_incoming_receipt(&block.hash(), shard_id, receipt_proofs);
         }
 
-        Ok(())
+        Ok(receipt_proofs_by_shard_id)
     }
 
     pub fn create_chunk_state_challenge(
@@ -2679,6 +3198,11 @@ impl<'a> ChainUpdate<'a> {
         )?;
         self.chain_store_update.save_block_extra(&block.hash(), BlockExtra { challenges_result });
 
+        let protocol_version =
+            self.runtime_adapter.get_epoch_protocol_version(block.header().epoch_id())?;
+        let gas_limit = self.block_economics_config.gas_limit(protocol_version);
+
+        let mut shard_updates = vec![];
         for (shard_id, (chunk_header, prev_chunk_header)) in
             (block.chunks().iter().zip(prev_block.chunks().iter())).enumerate()
         {
@@ -2722,6 +3246,7 @@ impl<'a> ChainUpdate<'a> {
                         &prev_chunk_extra,
                         prev_chunk_header,
                         chunk_header,
+                        gas_limit,
                     )
                     .map_err(|e| {
                         debug!(target: "chain", "Failed to validate chunk extra: {:?}", e);
@@ -2761,28 +3286,66 @@ impl<'a> ChainUpdate<'a> {
                     }
 
                     let chunk_inner = chunk.cloned_header().take_inner();
-                    let gas_limit = chunk_inner.gas_limit;
+                    shard_updates.push((
+                        shard_id,
+                        ShardUpdate::NewChunk {
+                            chunk,
+                            chunk_inner,
+                            height_included: chunk_header.height_included(),
+                            receipts,
+                        },
+                    ));
+                } else {
+                    let prev_extra = self
+                        .chain_store_update
+                        .get_chunk_extra(&prev_block.hash(), shard_id)?
+                        .clone();
+                    shard_updates.push((shard_id, ShardUpdate::OldChunk { prev_extra }));
+                }
+            }
+        }
 
-                    // Apply transactions and receipts.
-                    let apply_result = self
-                        .runtime_adapter
-                        .apply_transactions(
-                            shard_id,
-                            &chunk_inner.prev_state_root,
-                            chunk_header.height_included(),
-                            block.header().raw_timestamp(),
-                            &chunk_header.prev_block_hash(),
-                            &block.hash(),
-                            &receipts,
-                            chunk.transactions(),
-                            &chunk_inner.validator_proposals,
-                            prev_block.header().gas_price(),
-                            gas_limit,
-                            &block.header().challenges_result(),
-                            *block.header().random_value(),
-                        )
-                        .map_err(|e| ErrorKind::Other(e.to_string()))?;
+        // Chunks applied while the block waited for its other chunks are not applied again.
+        let optimistic_results = shard_updates
+            .iter()
+            .map(|(shard_id, shard_update)| {
+                match (shard_update, self.optimistic_apply_results.as_mut()) {
+                    (ShardUpdate::NewChunk { receipts, .. }, Some(optimistic_apply_results)) => {
+                        let result =
+                            optimistic_apply_results.take(block.hash(), *shard_id, receipts);
+                        if result.is_some() {
+                            near_metrics::inc_counter(&metrics::OPTIMISTIC_CHUNKS_USED);
+                        }
+                        result
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Chunks of different shards don't depend on each other, so they are applied in parallel
+        // and only their results are saved one shard after the other. The results are collected in
+        // shard order before looking at errors, so that when several shards fail the error of the
+        // lowest shard is returned no matter which one finished first, and nothing is saved.
+        let runtime_adapter = &*self.runtime_adapter;
+        let apply_results = shard_updates
+            .par_iter()
+            .zip(optimistic_results.into_par_iter())
+            .map(|((shard_id, shard_update), optimistic_result)| match optimistic_result {
+                Some(apply_result) => Ok(apply_result),
+                None => {
+                    apply_shard_update(runtime_adapter, block, prev_block, *shard_id, shard_update)
+                }
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| ErrorKind::Other(e.to_string()))?;
 
+        for ((shard_id, shard_update), apply_result) in shard_updates.into_iter().zip(apply_results)
+        {
+            match shard_update {
+                ShardUpdate::NewChunk { chunk, chunk_inner, .. } => {
                     let (outcome_root, outcome_paths) =
                         ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
 
@@ -2796,7 +3359,7 @@ impl<'a> ChainUpdate<'a> {
                             outcome_root,
                             apply_result.validator_proposals,
                             apply_result.total_gas_burnt,
-                            gas_limit,
+                            chunk_inner.gas_limit,
                             apply_result.total_balance_burnt,
                         ),
                     );
@@ -2805,6 +3368,12 @@ impl<'a> ChainUpdate<'a> {
                         shard_id,
                         apply_result.receipt_result,
                     );
+                    self.chain_store_update.save_idempotency_keys(
+                        &block.hash(),
+                        block.header().height(),
+                        chunk.transactions(),
+                        &apply_result.outcomes,
+                    );
                     // Save receipt and transaction results.
                     self.chain_store_update.save_outcomes_with_proofs(
                         &block.hash(),
@@ -2812,31 +3381,8 @@ impl<'a> ChainUpdate<'a> {
                         apply_result.outcomes,
                         outcome_paths,
                     );
-                } else {
-                    let mut new_extra = self
-                        .chain_store_update
-                        .get_chunk_extra(&prev_block.hash(), shard_id)?
-                        .clone();
-
-                    let apply_result = self
-                        .runtime_adapter
-                        .apply_transactions(
-                            shard_id,
-                            &new_extra.state_root,
-                            block.header().height(),
-                            block.header().raw_timestamp(),
-                            &prev_block.hash(),
-                            &block.hash(),
-                            &[],
-                            &[],
-                            &new_extra.validator_proposals,
-                            block.header().gas_price(),
-                            new_extra.gas_limit,
-                            &block.header().challenges_result(),
-                            *block.header().random_value(),
-                        )
-                        .map_err(|e| ErrorKind::Other(e.to_string()))?;
-
+                }
+                ShardUpdate::OldChunk { prev_extra: mut new_extra } => {
                     self.chain_store_update.save_trie_changes(apply_result.trie_changes);
                     new_extra.state_root = apply_result.new_root;
 
@@ -2848,6 +3394,102 @@ impl<'a> ChainUpdate<'a> {
         Ok(())
     }
 
+    /// Applies the chunks of the shards this node tracks in the block waiting for its other
+    /// chunks, that arrived with the receipts of the block. The results are kept until the block
+    /// is processed, nothing is saved to the store.
+    fn apply_chunks_optimistically(
+        &mut self,
+        me: &Option<AccountId>,
+        block: &Block,
+    ) -> Result<(), Error> {
+        if self.optimistic_apply_results.is_none()
+            || !self.care_about_any_shard_or_part(me, *block.header().prev_hash())?
+        {
+            return Ok(());
+        }
+        let receipt_proofs_by_shard_id = match self.collect_incoming_receipt_proofs(block) {
+            Ok(receipt_proofs_by_shard_id) => receipt_proofs_by_shard_id,
+            // Receipts of the block to the shard can't be known before all chunks arrive.
+            Err(_) => return Ok(()),
+        };
+        let prev_block = self.chain_store_update.get_block(block.header().prev_hash())?.clone();
+
+        let mut shard_updates = vec![];
+        for (shard_id, (chunk_header, prev_chunk_header)) in
+            block.chunks().iter().zip(prev_block.chunks().iter()).enumerate()
+        {
+            let shard_id = shard_id as ShardId;
+            if chunk_header.height_included() != block.header().height()
+                || !self.runtime_adapter.cares_about_shard(
+                    me.as_ref(),
+                    block.header().prev_hash(),
+                    shard_id,
+                    true,
+                )
+                || self
+                    .optimistic_apply_results
+                    .as_mut()
+                    .map_or(true, |results| results.contains(block.hash(), shard_id))
+            {
+                continue;
+            }
+            let chunk = match self.chain_store_update.get_chunk_clone_from_header(chunk_header) {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            };
+            // Same receipts as `get_incoming_receipts_for_shard` returns once the block and its
+            // receipts are saved.
+            let mut receipt_proof_response = vec![ReceiptProofResponse(
+                *block.hash(),
+                receipt_proofs_by_shard_id.get(&shard_id).cloned().unwrap_or_default(),
+            )];
+            receipt_proof_response.extend(
+                self.chain_store_update.get_incoming_receipts_for_shard(
+                    shard_id,
+                    *block.header().prev_hash(),
+                    prev_chunk_header.height_included(),
+                )?,
+            );
+            let receipts = collect_receipts_from_response(&receipt_proof_response);
+            let chunk_inner = chunk.cloned_header().take_inner();
+            shard_updates.push((
+                shard_id,
+                ShardUpdate::NewChunk {
+                    chunk,
+                    chunk_inner,
+                    height_included: chunk_header.height_included(),
+                    receipts,
+                },
+            ));
+        }
+
+        let runtime_adapter = &*self.runtime_adapter;
+        let apply_results = shard_updates
+            .par_iter()
+            .map(|(shard_id, shard_update)| {
+                apply_shard_update(runtime_adapter, block, &prev_block, *shard_id, shard_update)
+            })
+            .collect::<Vec<_>>();
+        for ((shard_id, shard_update), apply_result) in shard_updates.into_iter().zip(apply_results)
+        {
+            // The chunk is applied again, and fails the same way, once the block is processed.
+            if let (ShardUpdate::NewChunk { receipts, .. }, Ok(apply_result)) =
+                (shard_update, apply_result)
+            {
+                near_metrics::inc_counter(&metrics::OPTIMISTIC_CHUNKS_APPLIED);
+                if let Some(optimistic_apply_results) = self.optimistic_apply_results.as_mut() {
+                    optimistic_apply_results.insert(
+                        *block.hash(),
+                        shard_id,
+                        &receipts,
+                        apply_result,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Runs the block processing, including validation and finding a place for the new block in the chain.
     /// Returns new head if chain head updated, as well as a boolean indicating if we need to start
     ///    fetching state for the next epoch.
@@ -2937,21 +3579,27 @@ impl<'a> ChainUpdate<'a> {
         // Check the header is valid before we proceed with the full block.
         self.process_header_for_block(block.header(), provenance, on_challenge)?;
 
-        self.runtime_adapter.verify_block_vrf(
-            &block.header().epoch_id(),
-            block.header().height(),
-            &prev_random_value,
-            block.vrf_value(),
-            block.vrf_proof(),
-        )?;
+        let trusted = self.is_trusted_by_checkpoint(block.header());
+        if !trusted {
+            self.runtime_adapter.verify_block_vrf(
+                &block.header().epoch_id(),
+                block.header().height(),
+                &prev_random_value,
+                block.vrf_value(),
+                block.vrf_proof(),
+            )?;
+        }
 
         if block.header().random_value() != &hash(block.vrf_value().0.as_ref()) {
             return Err(ErrorKind::InvalidRandomnessBeaconOutput.into());
         }
 
-        if let Err(e) =
-            Chain::check_block_validity(self.runtime_adapter.as_ref(), self.genesis, block)
-        {
+        if let Err(e) = Chain::check_block_validity(
+            self.runtime_adapter.as_ref(),
+            self.genesis,
+            block,
+            !trusted,
+        ) {
             byzantine_assert!(false);
             return Err(e.into());
         }
@@ -2968,6 +3616,12 @@ impl<'a> ChainUpdate<'a> {
             return Err(ErrorKind::InvalidGasPrice.into());
         }
 
+        let max_block_size = self.block_economics_config.max_block_size(protocol_version);
+        if block.try_to_vec()?.len() as u64 > max_block_size {
+            byzantine_assert!(false);
+            return Err(ErrorKind::InvalidBlockSize.into());
+        }
+
         let prev_block = self.chain_store_update.get_block(&prev_hash)?.clone();
 
         self.ping_missing_chunks(me, prev_hash, &block)?;
@@ -3103,6 +3757,36 @@ impl<'a> ChainUpdate<'a> {
         Ok(())
     }
 
+    fn is_trusted_by_checkpoint(&mut self, header: &BlockHeader) -> bool {
+        is_trusted_by_checkpoint(&mut self.chain_store_update, self.trusted_checkpoint, header)
+    }
+
+    /// Whether the signatures of the header are not checked. Fails if the header is at the height
+    /// of the trusted checkpoint but is not the checkpoint block, or is above it but doesn't
+    /// descend from the checkpoint block.
+    fn check_trusted_checkpoint(&mut self, header: &BlockHeader) -> Result<bool, Error> {
+        let checkpoint = match self.trusted_checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(false),
+        };
+        if header.height() == checkpoint.height
+            && (header.hash() != &checkpoint.block_hash
+                || header.prev_state_root() != &checkpoint.state_root)
+        {
+            return Err(ErrorKind::InvalidCheckpointBlock.into());
+        }
+        if header.height() > checkpoint.height {
+            // The headers above the first one past the checkpoint were checked the same way.
+            let prev_header = self.get_previous_header(header)?;
+            if prev_header.height() <= checkpoint.height
+                && prev_header.hash() != &checkpoint.block_hash
+            {
+                return Err(ErrorKind::InvalidCheckpointBlock.into());
+            }
+        }
+        Ok(self.is_trusted_by_checkpoint(header))
+    }
+
     fn validate_header<F>(
         &mut self,
         header: &BlockHeader,
@@ -3117,8 +3801,10 @@ impl<'a> ChainUpdate<'a> {
             return Err(ErrorKind::InvalidBlockFutureTime(header.timestamp()).into());
         }
 
+        let trusted = self.check_trusted_checkpoint(header)?;
+
         // First I/O cost, delay as much as possible.
-        if !self.runtime_adapter.verify_header_signature(header)? {
+        if !trusted && !self.runtime_adapter.verify_header_signature(header)? {
             return Err(ErrorKind::InvalidSignature.into());
         }
 
@@ -3161,6 +3847,16 @@ impl<'a> ChainUpdate<'a> {
             return Err(ErrorKind::InvalidEpochHash.into());
         }
 
+        // Don't go past the start of an epoch that this release doesn't implement, so the node
+        // keeps serving the chain it has instead of applying blocks with the wrong rules.
+        let protocol_version =
+            self.runtime_adapter.get_epoch_protocol_version(header.epoch_id())?;
+        if protocol_version > PROTOCOL_VERSION {
+            return Err(
+                ErrorKind::UnsupportedProtocolVersion(protocol_version, PROTOCOL_VERSION).into()
+            );
+        }
+
         if header.epoch_id() == prev_header.epoch_id() {
             if header.next_bp_hash() != prev_header.next_bp_hash() {
                 return Err(ErrorKind::InvalidNextBPHash.into());
@@ -3198,12 +3894,14 @@ impl<'a> ChainUpdate<'a> {
         // producer, confirmation signatures and finality info.
         if *provenance != Provenance::PRODUCED {
             // first verify aggregated signature
-            if !self.runtime_adapter.verify_approval(
-                prev_header.hash(),
-                prev_header.height(),
-                header.height(),
-                &header.approvals(),
-            )? {
+            if !trusted
+                && !self.runtime_adapter.verify_approval(
+                    prev_header.hash(),
+                    prev_header.height(),
+                    header.height(),
+                    &header.approvals(),
+                )?
+            {
                 return Err(ErrorKind::InvalidApprovals.into());
             };
 
@@ -3482,6 +4180,12 @@ impl<'a> ChainUpdate<'a> {
         let (outcome_root, outcome_proofs) =
             ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
 
+        self.chain_store_update.save_idempotency_keys(
+            block_header.hash(),
+            block_header.height(),
+            chunk.transactions(),
+            &apply_result.outcomes,
+        );
         self.chain_store_update.save_chunk(chunk);
 
         self.chain_store_update.save_trie_changes(apply_result.trie_changes);
@@ -3632,3 +4336,48 @@ pub fn collect_receipts_from_response(
         receipt_proof_response.iter().flat_map(|ReceiptProofResponse(_, proofs)| proofs),
     )
 }
+
+/// Applies the new chunk of the shard in the block, or carries the state of the shard over to the
+/// block if its chunk is missing.
+fn apply_shard_update(
+    runtime_adapter: &dyn RuntimeAdapter,
+    block: &Block,
+    prev_block: &Block,
+    shard_id: ShardId,
+    shard_update: &ShardUpdate,
+) -> Result<ApplyTransactionResult, Error> {
+    match shard_update {
+        // Apply transactions and receipts.
+        ShardUpdate::NewChunk { chunk, chunk_inner, height_included, receipts } => runtime_adapter
+            .apply_transactions(
+                shard_id,
+                &chunk_inner.prev_state_root,
+                *height_included,
+                block.header().raw_timestamp(),
+                &chunk_inner.prev_block_hash,
+                &block.hash(),
+                receipts,
+                chunk.transactions(),
+                &chunk_inner.validator_proposals,
+                prev_block.header().gas_price(),
+                chunk_inner.gas_limit,
+                &block.header().challenges_result(),
+                *block.header().random_value(),
+            ),
+        ShardUpdate::OldChunk { prev_extra } => runtime_adapter.apply_transactions(
+            shard_id,
+            &prev_extra.state_root,
+            block.header().height(),
+            block.header().raw_timestamp(),
+            &prev_block.hash(),
+            &block.hash(),
+            &[],
+            &[],
+            &prev_extra.validator_proposals,
+            block.header().gas_price(),
+            prev_extra.gas_limit,
+            &block.header().challenges_result(),
+            *block.header().random_value(),
+        ),
+    }
+}
@@ -12,6 +12,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::serialize::to_base;
 use near_primitives::sharding::{ChunkHash, ShardChunkHeader};
 use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::version::ProtocolVersion;
 
 #[derive(Debug)]
 pub struct Error {
@@ -128,6 +129,9 @@ pub enum ErrorKind {
     /// Invalid Gas Limit
     #[fail(display = "Invalid Gas Price")]
     InvalidGasPrice,
+    /// Block exceeds max block size of its protocol version
+    #[fail(display = "Invalid Block Size")]
+    InvalidBlockSize,
     /// Invalid Gas Used
     #[fail(display = "Invalid Gas Used")]
     InvalidGasUsed,
@@ -146,6 +150,9 @@ pub enum ErrorKind {
     /// Invalid block merkle root.
     #[fail(display = "Invalid Block Merkle Root")]
     InvalidBlockMerkleRoot,
+    /// Block at the height of the trusted checkpoint is not the checkpoint block.
+    #[fail(display = "Block doesn't match the trusted checkpoint")]
+    InvalidCheckpointBlock,
     /// Someone is not a validator. Usually happens in signature verification
     #[fail(display = "Not A Validator")]
     NotAValidator,
@@ -155,6 +162,12 @@ pub enum ErrorKind {
     /// Epoch out of bounds. Usually if received block is too far in the future or alternative fork.
     #[fail(display = "Epoch Out Of Bounds")]
     EpochOutOfBounds,
+    /// Protocol version of the epoch is newer than the latest version supported by this release.
+    #[fail(
+        display = "Protocol version {} is not supported by this release, which supports versions up to {}. Upgrade to a release that supports protocol version {}",
+        _0, _1, _0
+    )]
+    UnsupportedProtocolVersion(ProtocolVersion, ProtocolVersion),
     /// A challenged block is on the chain that was attempted to become the head
     #[fail(display = "Challenged block on chain")]
     ChallengedBlockOnChain,
@@ -230,6 +243,7 @@ impl Error {
             | ErrorKind::ValidatorError(_)
             // TODO: can be either way?
             | ErrorKind::EpochOutOfBounds
+            | ErrorKind::UnsupportedProtocolVersion(_, _)
             | ErrorKind::ChallengedBlockOnChain
             | ErrorKind::StorageError(_)
             | ErrorKind::GCError(_)
@@ -263,12 +277,14 @@ impl Error {
             | ErrorKind::InvalidApprovals
             | ErrorKind::InvalidGasLimit
             | ErrorKind::InvalidGasPrice
+            | ErrorKind::InvalidBlockSize
             | ErrorKind::InvalidGasUsed
             | ErrorKind::InvalidBalanceBurnt
             | ErrorKind::InvalidShardId(_)
             | ErrorKind::InvalidStateRequest(_)
             | ErrorKind::InvalidRandomnessBeaconOutput
             | ErrorKind::InvalidBlockMerkleRoot
+            | ErrorKind::InvalidCheckpointBlock
             | ErrorKind::NotAValidator
             | ErrorKind::InvalidChallengeRoot => true,
         }
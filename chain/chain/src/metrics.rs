@@ -25,4 +25,28 @@ lazy_static! {
         "near_validator_active_total",
         "The total number of validators active after last block"
     );
+    pub static ref ORPHAN_BLOCKS: near_metrics::Result<IntGauge> =
+        try_create_int_gauge("near_orphan_blocks", "Number of blocks in the orphan pool");
+    pub static ref ORPHAN_BLOCKS_EXPIRED: near_metrics::Result<IntCounter> = try_create_int_counter(
+        "near_orphan_blocks_expired_total",
+        "Number of orphans dropped from the pool for waiting too long"
+    );
+    pub static ref ORPHAN_BLOCKS_EVICTED: near_metrics::Result<IntCounter> = try_create_int_counter(
+        "near_orphan_blocks_evicted_total",
+        "Number of orphans dropped from the full pool"
+    );
+    pub static ref GC_DEBT_BLOCKS: near_metrics::Result<IntGauge> = try_create_int_gauge(
+        "near_gc_debt_blocks",
+        "Number of heights below the garbage collection stop height that are not collected yet"
+    );
+    pub static ref OPTIMISTIC_CHUNKS_APPLIED: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_optimistic_chunks_applied_total",
+            "Number of chunks applied before their block could be processed"
+        );
+    pub static ref OPTIMISTIC_CHUNKS_USED: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_optimistic_chunks_used_total",
+            "Number of chunks applied ahead whose results were saved with their block"
+        );
 }
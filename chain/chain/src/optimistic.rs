@@ -0,0 +1,133 @@
+//! Results of the chunks applied before their block is processed.
+//!
+//! A block waits in the pool of blocks with missing chunks until the chunks of all the shards the
+//! node tracks arrive, and it becomes the head, readable at `optimistic` finality, only after all
+//! of them are applied. With optimistic block application, the chunk of a shard is applied as
+//! soon as it and the receipts of the block are available, and the result is kept here until the
+//! block is processed, which then only applies the chunks that arrived last.
+//!
+//! A result is saved to the store only with its block, and only if the block is applied to the
+//! same receipts. Results of blocks that are never processed, e.g. forks dropped on a reorg, are
+//! evicted by the results of the newer blocks.
+use borsh::BorshSerialize;
+use cached::{Cached, SizedCache};
+
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::receipt::Receipt;
+use near_primitives::types::ShardId;
+
+use crate::types::ApplyTransactionResult;
+
+/// Number of applied chunks kept, a few blocks worth of chunks of every shard.
+const OPTIMISTIC_RESULTS_CACHE_SIZE: usize = 64;
+
+pub struct OptimisticApplyResults {
+    /// Result of the chunk of the block and shard, with the hash of the receipts it applied.
+    results: SizedCache<(CryptoHash, ShardId), (CryptoHash, ApplyTransactionResult)>,
+}
+
+impl OptimisticApplyResults {
+    pub fn new() -> Self {
+        OptimisticApplyResults { results: SizedCache::with_size(OPTIMISTIC_RESULTS_CACHE_SIZE) }
+    }
+
+    pub fn contains(&mut self, block_hash: &CryptoHash, shard_id: ShardId) -> bool {
+        self.results.cache_get(&(*block_hash, shard_id)).is_some()
+    }
+
+    pub fn insert(
+        &mut self,
+        block_hash: CryptoHash,
+        shard_id: ShardId,
+        receipts: &[Receipt],
+        result: ApplyTransactionResult,
+    ) {
+        self.results.cache_set((block_hash, shard_id), (receipts_hash(receipts), result));
+    }
+
+    /// Takes the result of the chunk of the block, if it was applied to the given receipts.
+    pub fn take(
+        &mut self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        receipts: &[Receipt],
+    ) -> Option<ApplyTransactionResult> {
+        let (applied_receipts_hash, result) =
+            self.results.cache_remove(&(*block_hash, shard_id))?;
+        if applied_receipts_hash == receipts_hash(receipts) {
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+fn receipts_hash(receipts: &[Receipt]) -> CryptoHash {
+    hash(&receipts.try_to_vec().expect("Failed to serialize"))
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, PublicKey};
+    use near_primitives::receipt::{ActionReceipt, ReceiptEnum};
+    use near_store::test_utils::create_test_store;
+    use near_store::{ShardTries, Trie, TrieChanges, WrappedTrieChanges};
+
+    use super::*;
+
+    fn apply_result(block_hash: CryptoHash) -> ApplyTransactionResult {
+        let tries = ShardTries::new(create_test_store(), 1);
+        ApplyTransactionResult {
+            trie_changes: WrappedTrieChanges::new(
+                tries,
+                0,
+                TrieChanges::empty(Trie::empty_root()),
+                vec![],
+                block_hash,
+            ),
+            new_root: Trie::empty_root(),
+            outcomes: vec![],
+            receipt_result: Default::default(),
+            validator_proposals: vec![],
+            total_gas_burnt: 0,
+            total_balance_burnt: 0,
+            proof: None,
+        }
+    }
+
+    fn receipt(receiver_id: &str) -> Receipt {
+        Receipt {
+            predecessor_id: "alice.near".to_string(),
+            receiver_id: receiver_id.to_string(),
+            receipt_id: CryptoHash::default(),
+            receipt: ReceiptEnum::Action(ActionReceipt {
+                signer_id: "alice.near".to_string(),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: 0,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions: vec![],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_optimistic_apply_results() {
+        let mut results = OptimisticApplyResults::new();
+        let block_hash = hash(&[1]);
+        let receipts = vec![receipt("bob.near")];
+
+        results.insert(block_hash, 0, &receipts, apply_result(block_hash));
+        assert!(results.contains(&block_hash, 0));
+        assert!(!results.contains(&block_hash, 1));
+        assert!(results.take(&hash(&[2]), 0, &receipts).is_none());
+        assert!(results.take(&block_hash, 0, &receipts).is_some());
+        // A result is used once.
+        assert!(!results.contains(&block_hash, 0));
+
+        // The block is applied to other receipts than the chunk was.
+        results.insert(block_hash, 0, &receipts, apply_result(block_hash));
+        assert!(results.take(&block_hash, 0, &[receipt("carol.near")]).is_none());
+        assert!(!results.contains(&block_hash, 0));
+    }
+}
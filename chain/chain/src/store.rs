@@ -27,23 +27,29 @@ use near_primitives::transaction::{
 };
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::{
-    AccountId, BlockExtra, BlockHeight, ChunkExtra, EpochId, GCCount, NumBlocks, ShardId,
-    StateChanges, StateChangesExt, StateChangesKinds, StateChangesKindsExt, StateChangesRequest,
+    AccountId, BlockExtra, BlockFeeInfo, BlockHeight, ChunkExtra, EpochId, GCCount,
+    IdempotencyKeyExecution, NumBlocks, ShardId, StateChanges, StateChangesExt, StateChangesKinds,
+    StateChangesKindsExt, StateChangesRequest, WatchedAccountChange,
+};
+use near_primitives::utils::{
+    get_block_shard_id, get_idempotency_key_id, get_watched_account_change_id,
+    get_watched_account_changes_prefix, index_to_bytes, to_timestamp,
 };
-use near_primitives::utils::{get_block_shard_id, index_to_bytes, to_timestamp};
 use near_primitives::views::LightClientBlockView;
 use near_store::{
-    read_with_cache, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockHeight, ColBlockInfo,
-    ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight, ColBlockRefCount,
-    ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra, ColChunkHashesByHeight,
-    ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks, ColGCCount, ColIncomingReceipts,
+    read_with_cache, ColBlock, ColBlockExtra, ColBlockFeeInfo, ColBlockHeader, ColBlockHeight,
+    ColBlockInfo, ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight,
+    ColBlockRefCount, ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra,
+    ColChunkHashesByHeight, ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks,
+    ColGCCount, ColIdempotencyKeys, ColIdempotencyKeysByBlock, ColIncomingReceipts,
     ColInvalidChunks, ColLastBlockWithNewChunk, ColNextBlockHashes, ColNextBlockWithNewChunk,
     ColOutcomeIds, ColOutgoingReceipts, ColPartialChunks, ColProcessedBlockHeights,
     ColReceiptIdToShardId, ColReceipts, ColState, ColStateChanges, ColStateDlInfos,
-    ColStateHeaders, ColStateParts, ColTransactionResult, ColTransactions, ColTrieChanges, DBCol,
-    KeyForStateChanges, ShardTries, Store, StoreUpdate, TrieChanges, WrappedTrieChanges,
-    CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
-    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC, TAIL_KEY,
+    ColStateHeaders, ColStateParts, ColTransactionResult, ColTransactions, ColTrieChanges,
+    ColWatchedAccountChanges, DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate,
+    TrieChanges, WrappedTrieChanges, CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY,
+    HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC,
+    TAIL_KEY, UNDO_COUNT_KEY, WATCHED_ACCOUNTS_HEAD_KEY,
 };
 
 use crate::error::{Error, ErrorKind};
@@ -65,7 +71,12 @@ const CHUNK_CACHE_SIZE: usize = 1;
 pub enum GCMode {
     Fork(ShardTries),
     Canonical(ShardTries),
-    StateSync { clear_block_info: bool },
+    /// Block above the head that a rollback removes, its epoch data is kept for when it's applied
+    /// again.
+    Undo(ShardTries),
+    StateSync {
+        clear_block_info: bool,
+    },
 }
 
 fn get_height_shard_id(height: BlockHeight, shard_id: ShardId) -> Vec<u8> {
@@ -132,6 +143,8 @@ pub trait ChainStoreAccess {
     fn get_previous_header(&mut self, header: &BlockHeader) -> Result<&BlockHeader, Error>;
     /// GEt block extra for given block.
     fn get_block_extra(&mut self, block_hash: &CryptoHash) -> Result<&BlockExtra, Error>;
+    /// Get gas price and gas usage of given block.
+    fn get_block_fee_info(&mut self, block_hash: &CryptoHash) -> Result<&BlockFeeInfo, Error>;
     /// Get chunk extra info for given block hash + shard id.
     fn get_chunk_extra(
         &mut self,
@@ -236,6 +249,12 @@ pub trait ChainStoreAccess {
         &self,
         id: &CryptoHash,
     ) -> Result<Vec<ExecutionOutcomeWithIdAndProof>, Error>;
+    /// Executions of transactions of given signer with given idempotency key.
+    fn get_idempotency_key_executions(
+        &self,
+        account_id: &AccountId,
+        key: &[u8],
+    ) -> Result<Vec<IdempotencyKeyExecution>, Error>;
     /// Returns whether the block with the given hash was challenged
     fn is_block_challenged(&mut self, hash: &CryptoHash) -> Result<bool, Error>;
 
@@ -334,6 +353,8 @@ pub struct ChainStore {
     partial_chunks: SizedCache<Vec<u8>, PartialEncodedChunk>,
     /// Cache with block extra.
     block_extras: SizedCache<Vec<u8>, BlockExtra>,
+    /// Cache with block fee info.
+    block_fee_infos: SizedCache<Vec<u8>, BlockFeeInfo>,
     /// Cache with chunk extra.
     chunk_extras: SizedCache<Vec<u8>, ChunkExtra>,
     /// Cache with height to hash on the main chain.
@@ -375,6 +396,8 @@ pub struct ChainStore {
     block_ordinal_to_hash: SizedCache<Vec<u8>, CryptoHash>,
     /// Processed block heights.
     processed_block_heights: SizedCache<Vec<u8>, ()>,
+    /// Number of times blocks were undone when the caches were last cleared.
+    undo_count: u64,
 }
 
 pub fn option_to_not_found<T>(res: io::Result<Option<T>>, field_name: &str) -> Result<T, Error> {
@@ -397,6 +420,7 @@ impl ChainStore {
             chunks: SizedCache::with_size(CHUNK_CACHE_SIZE),
             partial_chunks: SizedCache::with_size(CHUNK_CACHE_SIZE),
             block_extras: SizedCache::with_size(CACHE_SIZE),
+            block_fee_infos: SizedCache::with_size(CACHE_SIZE),
             chunk_extras: SizedCache::with_size(CACHE_SIZE),
             height: SizedCache::with_size(CACHE_SIZE),
             block_hash_per_height: SizedCache::with_size(CACHE_SIZE),
@@ -417,9 +441,42 @@ impl ChainStore {
             block_merkle_tree: SizedCache::with_size(CACHE_SIZE),
             block_ordinal_to_hash: SizedCache::with_size(CACHE_SIZE),
             processed_block_heights: SizedCache::with_size(CACHE_SIZE),
+            undo_count: 0,
         }
     }
 
+    /// Drops all cached data, which may hold blocks that are no longer in the store.
+    pub fn clear_caches(&mut self) {
+        let undo_count = self.undo_count;
+        *self = ChainStore::new(self.store.clone(), self.genesis_height);
+        self.undo_count = undo_count;
+    }
+
+    /// Records that blocks were undone and clears the caches. Other chain stores on the same
+    /// database clear theirs on the next `clear_caches_if_undone`.
+    pub fn save_undo(&mut self) -> Result<(), Error> {
+        let undo_count =
+            self.store.get_ser::<u64>(ColBlockMisc, UNDO_COUNT_KEY)?.unwrap_or_default() + 1;
+        let mut store_update = self.store.store_update();
+        store_update.set_ser(ColBlockMisc, UNDO_COUNT_KEY, &undo_count)?;
+        store_update.commit()?;
+        self.clear_caches();
+        self.undo_count = undo_count;
+        Ok(())
+    }
+
+    /// Clears the caches if blocks were undone through another chain store on the same
+    /// database, e.g. by the client while this one belongs to a view client.
+    pub fn clear_caches_if_undone(&mut self) -> Result<(), Error> {
+        let undo_count =
+            self.store.get_ser::<u64>(ColBlockMisc, UNDO_COUNT_KEY)?.unwrap_or_default();
+        if undo_count != self.undo_count {
+            self.clear_caches();
+            self.undo_count = undo_count;
+        }
+        Ok(())
+    }
+
     pub fn owned_store(&self) -> Arc<Store> {
         self.store.clone()
     }
@@ -526,6 +583,40 @@ impl ChainStore {
         }
     }
 
+    /// A transaction with an idempotency key is rejected if a transaction of the same signer with
+    /// the same key was already executed on the chain ending with `prev_block_header`.
+    pub fn check_idempotency_key(
+        &mut self,
+        prev_block_header: &BlockHeader,
+        transaction: &SignedTransaction,
+    ) -> Result<(), InvalidTxError> {
+        let key = match transaction.transaction.idempotency_key() {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let executions = self
+            .get_idempotency_key_executions(&transaction.transaction.signer_id, key)
+            .map_err(|_| InvalidTxError::InvalidChain)?;
+        for execution in executions {
+            if execution.block_height > prev_block_header.height() {
+                continue;
+            }
+            // Executions on forks are only garbage collected later.
+            let on_chain = match self
+                .get_header_on_chain_by_height(prev_block_header.hash(), execution.block_height)
+            {
+                Ok(header) => header.hash() == &execution.block_hash,
+                Err(_) => false,
+            };
+            if on_chain {
+                return Err(InvalidTxError::DuplicateIdempotencyKey {
+                    transaction_hash: execution.transaction_hash,
+                });
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_block_height(&mut self, hash: &CryptoHash) -> Result<BlockHeight, Error> {
         if hash == &CryptoHash::default() {
             Ok(self.genesis_height)
@@ -533,6 +624,44 @@ impl ChainStore {
             Ok(self.get_block_header(hash)?.height())
         }
     }
+
+    /// Height of the last final block whose changes of the watched accounts were indexed.
+    pub fn get_watched_accounts_head(&self) -> Result<Option<BlockHeight>, Error> {
+        Ok(self.store.get_ser(ColBlockMisc, WATCHED_ACCOUNTS_HEAD_KEY)?)
+    }
+
+    /// Up to `limit` changes of the watched account, starting from the change with the given
+    /// block height and index.
+    pub fn get_watched_account_changes(
+        &self,
+        account_id: &AccountId,
+        from_block_height: BlockHeight,
+        from_index: u32,
+        limit: usize,
+    ) -> Result<Vec<WatchedAccountChange>, Error> {
+        let head = match self.get_watched_accounts_head()? {
+            Some(head) => head,
+            None => return Ok(vec![]),
+        };
+        let mut changes = vec![];
+        for block_height in std::cmp::max(from_block_height, self.tail()?)..=head {
+            if changes.len() >= limit {
+                break;
+            }
+            let prefix = get_watched_account_changes_prefix(block_height, account_id);
+            let from = get_watched_account_change_id(block_height, account_id, from_index);
+            for (key, value) in self.store.iter_prefix(ColWatchedAccountChanges, &prefix) {
+                if block_height == from_block_height && key.as_ref() < from.as_slice() {
+                    continue;
+                }
+                if changes.len() >= limit {
+                    break;
+                }
+                changes.push(WatchedAccountChange::try_from_slice(value.as_ref())?);
+            }
+        }
+        Ok(changes)
+    }
 }
 
 impl ChainStoreAccess for ChainStore {
@@ -681,6 +810,19 @@ impl ChainStoreAccess for ChainStore {
         )
     }
 
+    /// Gas price and gas usage of the block.
+    fn get_block_fee_info(&mut self, block_hash: &CryptoHash) -> Result<&BlockFeeInfo, Error> {
+        option_to_not_found(
+            read_with_cache(
+                &*self.store,
+                ColBlockFeeInfo,
+                &mut self.block_fee_infos,
+                block_hash.as_ref(),
+            ),
+            &format!("BLOCK FEE INFO: {}", block_hash),
+        )
+    }
+
     /// Information from applying chunk.
     fn get_chunk_extra(
         &mut self,
@@ -855,6 +997,17 @@ impl ChainStoreAccess for ChainStore {
         Ok(self.store.get_ser(ColTransactionResult, id.as_ref())?.unwrap_or_else(|| vec![]))
     }
 
+    fn get_idempotency_key_executions(
+        &self,
+        account_id: &AccountId,
+        key: &[u8],
+    ) -> Result<Vec<IdempotencyKeyExecution>, Error> {
+        Ok(self
+            .store
+            .get_ser(ColIdempotencyKeys, &get_idempotency_key_id(account_id, key))?
+            .unwrap_or_else(|| vec![]))
+    }
+
     fn get_blocks_to_catchup(&self, hash: &CryptoHash) -> Result<Vec<CryptoHash>, Error> {
         Ok(self.store.get_ser(ColBlocksToCatchup, hash.as_ref())?.unwrap_or_else(|| vec![]))
     }
@@ -1079,6 +1232,22 @@ impl ChainStoreAccess for ChainStore {
                 }
                 changes
             }
+            StateChangesRequest::AllChanges { account_ids } => {
+                let requests = vec![
+                    StateChangesRequest::AccountChanges { account_ids: account_ids.clone() },
+                    StateChangesRequest::AllAccessKeyChanges { account_ids: account_ids.clone() },
+                    StateChangesRequest::ContractCodeChanges { account_ids: account_ids.clone() },
+                    StateChangesRequest::DataChanges {
+                        account_ids: account_ids.clone(),
+                        key_prefix: Vec::<u8>::new().into(),
+                    },
+                ];
+                let mut changes = StateChanges::new();
+                for request in requests.iter() {
+                    changes.extend(self.get_state_changes(block_hash, request)?);
+                }
+                changes
+            }
         })
     }
 
@@ -1134,6 +1303,7 @@ struct ChainStoreCacheUpdate {
     blocks: HashMap<CryptoHash, Block>,
     headers: HashMap<CryptoHash, BlockHeader>,
     block_extras: HashMap<CryptoHash, BlockExtra>,
+    block_fee_infos: HashMap<CryptoHash, BlockFeeInfo>,
     chunk_extras: HashMap<(CryptoHash, ShardId), ChunkExtra>,
     chunks: HashMap<ChunkHash, ShardChunk>,
     partial_chunks: HashMap<ChunkHash, PartialEncodedChunk>,
@@ -1148,6 +1318,8 @@ struct ChainStoreCacheUpdate {
     incoming_receipts: HashMap<(CryptoHash, ShardId), Vec<ReceiptProof>>,
     outcomes: HashMap<CryptoHash, Vec<ExecutionOutcomeWithIdAndProof>>,
     outcome_ids: HashMap<(CryptoHash, ShardId), Vec<CryptoHash>>,
+    idempotency_keys: HashMap<Vec<u8>, Vec<IdempotencyKeyExecution>>,
+    idempotency_keys_by_block: HashMap<CryptoHash, Vec<Vec<u8>>>,
     invalid_chunks: HashMap<ChunkHash, EncodedShardChunk>,
     receipt_id_to_shard_id: HashMap<CryptoHash, ShardId>,
     next_block_with_new_chunk: HashMap<(CryptoHash, ShardId), CryptoHash>,
@@ -1351,6 +1523,14 @@ impl<'a> ChainStoreAccess for ChainStoreUpdate<'a> {
         }
     }
 
+    fn get_block_fee_info(&mut self, block_hash: &CryptoHash) -> Result<&BlockFeeInfo, Error> {
+        if let Some(fee_info) = self.chain_store_cache_update.block_fee_infos.get(block_hash) {
+            Ok(fee_info)
+        } else {
+            self.chain_store.get_block_fee_info(block_hash)
+        }
+    }
+
     /// Get state root hash after applying header with given hash.
     fn get_chunk_extra(
         &mut self,
@@ -1489,6 +1669,14 @@ impl<'a> ChainStoreAccess for ChainStoreUpdate<'a> {
         self.chain_store.get_outcomes_by_id(id)
     }
 
+    fn get_idempotency_key_executions(
+        &self,
+        account_id: &AccountId,
+        key: &[u8],
+    ) -> Result<Vec<IdempotencyKeyExecution>, Error> {
+        self.chain_store.get_idempotency_key_executions(account_id, key)
+    }
+
     fn get_chunk(&mut self, chunk_hash: &ChunkHash) -> Result<&ShardChunk, Error> {
         if let Some(chunk) = self.chain_store_cache_update.chunks.get(chunk_hash) {
             Ok(chunk)
@@ -1771,8 +1959,9 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
-    /// Save block.
+    /// Save block along with its fee info.
     pub fn save_block(&mut self, block: Block) {
+        self.chain_store_cache_update.block_fee_infos.insert(*block.hash(), block.fee_info());
         self.chain_store_cache_update.blocks.insert(*block.hash(), block);
     }
 
@@ -1902,6 +2091,67 @@ impl<'a> ChainStoreUpdate<'a> {
         self.chain_store_cache_update.outcome_ids.insert((*block_hash, shard_id), outcome_ids);
     }
 
+    /// Indexes executed transactions with idempotency keys by their signer and key.
+    pub fn save_idempotency_keys(
+        &mut self,
+        block_hash: &CryptoHash,
+        block_height: BlockHeight,
+        transactions: &[SignedTransaction],
+        outcomes: &[ExecutionOutcomeWithId],
+    ) {
+        let executed: HashSet<_> = outcomes.iter().map(|outcome| outcome.id).collect();
+        for transaction in transactions {
+            let key = match transaction.transaction.idempotency_key() {
+                Some(key) if executed.contains(&transaction.get_hash()) => key,
+                _ => continue,
+            };
+            let id = get_idempotency_key_id(&transaction.transaction.signer_id, key);
+            self.chain_store_cache_update
+                .idempotency_keys
+                .entry(id.clone())
+                .or_insert_with(Vec::new)
+                .push(IdempotencyKeyExecution {
+                    transaction_hash: transaction.get_hash(),
+                    block_hash: *block_hash,
+                    block_height,
+                });
+            self.chain_store_cache_update
+                .idempotency_keys_by_block
+                .entry(*block_hash)
+                .or_insert_with(Vec::new)
+                .push(id);
+        }
+    }
+
+    /// Saves the changes of the watched accounts in the final block at the given height and marks
+    /// it as indexed.
+    pub fn save_watched_account_changes(
+        &mut self,
+        block_height: BlockHeight,
+        changes: Vec<(AccountId, WatchedAccountChange)>,
+    ) -> Result<(), Error> {
+        let mut store_update = self.store().store_update();
+        for (account_id, change) in changes {
+            store_update.set_ser(
+                ColWatchedAccountChanges,
+                &get_watched_account_change_id(change.block_height, &account_id, change.index),
+                &change,
+            )?;
+        }
+        store_update.set_ser(ColBlockMisc, WATCHED_ACCOUNTS_HEAD_KEY, &block_height)?;
+        self.merge(store_update);
+        Ok(())
+    }
+
+    /// Removes the blocks with the given ordinals, inclusive, from the block ordinal index.
+    pub fn clear_block_ordinals(&mut self, from_ordinal: NumBlocks, to_ordinal: NumBlocks) {
+        let mut store_update = self.store().store_update();
+        for block_ordinal in from_ordinal..=to_ordinal {
+            store_update.delete(ColBlockOrdinal, &index_to_bytes(block_ordinal));
+        }
+        self.merge(store_update);
+    }
+
     pub fn save_trie_changes(&mut self, trie_changes: WrappedTrieChanges) {
         self.trie_changes.push(trie_changes);
     }
@@ -2050,7 +2300,7 @@ impl<'a> ChainStoreUpdate<'a> {
 
         // 1. Apply revert insertions or deletions from ColTrieChanges for Trie
         match gc_mode.clone() {
-            GCMode::Fork(tries) => {
+            GCMode::Fork(tries) | GCMode::Undo(tries) => {
                 // If the block is on a fork, we delete the state that's the result of applying this block
                 for shard_id in 0..header.chunk_mask().len() as ShardId {
                     self.store()
@@ -2137,6 +2387,7 @@ impl<'a> ChainStoreUpdate<'a> {
         let block_hash_vec: Vec<u8> = block_hash.as_ref().into();
         self.gc_col(ColBlock, &block_hash_vec);
         self.gc_col(ColBlockExtra, &block_hash_vec);
+        self.gc_col(ColBlockFeeInfo, &block_hash_vec);
         self.gc_col(ColNextBlockHashes, &block_hash_vec);
         self.gc_col(ColChallengedBlocks, &block_hash_vec);
         self.gc_col(ColBlocksToCatchup, &block_hash_vec);
@@ -2152,8 +2403,9 @@ impl<'a> ChainStoreUpdate<'a> {
         }
         self.gc_col(ColBlockRefCount, &block_hash_vec);
         self.gc_outcomes(&block)?;
+        self.gc_idempotency_keys(&block_hash)?;
         match gc_mode {
-            GCMode::StateSync { clear_block_info: false } => {}
+            GCMode::StateSync { clear_block_info: false } | GCMode::Undo(_) => {}
             _ => self.gc_col(ColBlockInfo, &block_hash_vec),
         }
         self.gc_col(ColStateDlInfos, &block_hash_vec);
@@ -2166,6 +2418,23 @@ impl<'a> ChainStoreUpdate<'a> {
                 // 5. Forks only clearing
                 self.dec_block_refcount(block.header().prev_hash())?;
             }
+            GCMode::Undo(_) => {
+                // 5a. Rollback clearing, the previous block may become the head
+                if self
+                    .chain_store
+                    .get_watched_accounts_head()?
+                    .map_or(false, |watched_head| height <= watched_head)
+                {
+                    // The block was final and indexed.
+                    self.gc_watched_account_changes(height);
+                }
+                let prev_hash = *block.header().prev_hash();
+                self.dec_block_refcount(&prev_hash)?;
+                if self.get_next_block_hash(&prev_hash).ok() == Some(&block_hash) {
+                    let prev_hash_vec: Vec<u8> = prev_hash.as_ref().into();
+                    self.gc_col(ColNextBlockHashes, &prev_hash_vec);
+                }
+            }
             GCMode::Canonical(_) => {
                 // 6. Canonical Chain only clearing
                 // Delete chunks and chunk-indexed data
@@ -2176,12 +2445,18 @@ impl<'a> ChainStoreUpdate<'a> {
                     }
                 }
                 self.clear_chunk_data(min_chunk_height)?;
+                // Only final blocks are indexed, so the changes at the height are all on this block
+                self.gc_watched_account_changes(height);
             }
             GCMode::StateSync { .. } => {
                 // 7. State Sync clearing
                 // Chunks deleted separately
             }
         };
+        if let GCMode::Fork(_) | GCMode::Undo(_) = gc_mode {
+            // Forks are not part of the history kept by archival nodes
+            store_update.discard_garbage();
+        }
         self.merge(store_update);
         Ok(())
     }
@@ -2300,6 +2575,43 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Removes the changes of the watched accounts at the height. They are keyed by the height
+    /// first, so only the keys at the height are read.
+    fn gc_watched_account_changes(&mut self, height: BlockHeight) {
+        let keys: Vec<Vec<u8>> = self
+            .chain_store
+            .store()
+            .iter_prefix(ColWatchedAccountChanges, &height.to_be_bytes())
+            .map(|(key, _)| key.into())
+            .collect();
+        for key in keys {
+            self.gc_col(ColWatchedAccountChanges, &key);
+        }
+    }
+
+    /// Removes executions in the block from the idempotency keys index.
+    fn gc_idempotency_keys(&mut self, block_hash: &CryptoHash) -> Result<(), Error> {
+        let ids: Vec<Vec<u8>> =
+            match self.store().get_ser(ColIdempotencyKeysByBlock, block_hash.as_ref())? {
+                Some(ids) => ids,
+                None => return Ok(()),
+            };
+        let mut store_update = self.store().store_update();
+        for id in ids {
+            let mut executions: Vec<IdempotencyKeyExecution> =
+                self.store().get_ser(ColIdempotencyKeys, &id)?.unwrap_or_else(|| vec![]);
+            executions.retain(|execution| &execution.block_hash != block_hash);
+            if executions.is_empty() {
+                self.gc_col(ColIdempotencyKeys, &id);
+            } else {
+                store_update.set_ser(ColIdempotencyKeys, &id, &executions)?;
+            }
+        }
+        self.gc_col(ColIdempotencyKeysByBlock, &block_hash.as_ref().into());
+        self.merge(store_update);
+        Ok(())
+    }
+
     fn gc_col(&mut self, col: DBCol, key: &Vec<u8>) {
         assert!(SHOULD_COL_GC[col as usize]);
         let mut store_update = self.store().store_update();
@@ -2330,6 +2642,10 @@ impl<'a> ChainStoreUpdate<'a> {
                 store_update.delete(col, key);
                 self.chain_store.block_extras.cache_remove(key);
             }
+            DBCol::ColBlockFeeInfo => {
+                store_update.delete(col, key);
+                self.chain_store.block_fee_infos.cache_remove(key);
+            }
             DBCol::ColNextBlockHashes => {
                 store_update.delete(col, key);
                 self.chain_store.next_block_hashes.cache_remove(key);
@@ -2340,7 +2656,7 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::ColBlocksToCatchup => {
                 store_update.delete(col, key);
             }
-            DBCol::ColStateChanges => {
+            DBCol::ColStateChanges | DBCol::ColWatchedAccountChanges => {
                 store_update.delete(col, key);
             }
             DBCol::ColBlockRefCount => {
@@ -2396,6 +2712,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::ColOutcomeIds => {
                 store_update.delete(col, key);
             }
+            DBCol::ColIdempotencyKeys | DBCol::ColIdempotencyKeysByBlock => {
+                store_update.delete(col, key);
+            }
             DBCol::ColStateDlInfos => {
                 store_update.delete(col, key);
             }
@@ -2426,7 +2745,8 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::ColEpochStart
             | DBCol::ColBlockOrdinal
             | DBCol::_ColTransactionRefCount
-            | DBCol::ColCachedContractCode => {
+            | DBCol::ColCachedContractCode
+            | DBCol::ColPeerStats => {
                 unreachable!();
             }
         }
@@ -2497,6 +2817,9 @@ impl<'a> ChainStoreUpdate<'a> {
         for (block_hash, block_extra) in self.chain_store_cache_update.block_extras.iter() {
             store_update.set_ser(ColBlockExtra, block_hash.as_ref(), block_extra)?;
         }
+        for (block_hash, fee_info) in self.chain_store_cache_update.block_fee_infos.iter() {
+            store_update.set_ser(ColBlockFeeInfo, block_hash.as_ref(), fee_info)?;
+        }
         for ((height, shard_id), chunk_hash) in
             self.chain_store_cache_update.chunk_hash_per_height_shard.iter()
         {
@@ -2588,6 +2911,21 @@ impl<'a> ChainStoreUpdate<'a> {
             existing_outcomes.extend_from_slice(outcomes);
             store_update.set_ser(ColTransactionResult, hash.as_ref(), &existing_outcomes)?;
         }
+        for (id, executions) in self.chain_store_cache_update.idempotency_keys.iter() {
+            let mut existing_executions: Vec<IdempotencyKeyExecution> =
+                self.chain_store.store.get_ser(ColIdempotencyKeys, id)?.unwrap_or_else(|| vec![]);
+            existing_executions.extend_from_slice(executions);
+            store_update.set_ser(ColIdempotencyKeys, id, &existing_executions)?;
+        }
+        for (block_hash, ids) in self.chain_store_cache_update.idempotency_keys_by_block.iter() {
+            let mut existing_ids: Vec<Vec<u8>> = self
+                .chain_store
+                .store
+                .get_ser(ColIdempotencyKeysByBlock, block_hash.as_ref())?
+                .unwrap_or_else(|| vec![]);
+            existing_ids.extend_from_slice(ids);
+            store_update.set_ser(ColIdempotencyKeysByBlock, block_hash.as_ref(), &existing_ids)?;
+        }
         for ((block_hash, shard_id), ids) in self.chain_store_cache_update.outcome_ids.iter() {
             store_update.set_ser(
                 ColOutcomeIds,
@@ -2737,6 +3075,7 @@ impl<'a> ChainStoreUpdate<'a> {
             blocks,
             headers,
             block_extras,
+            block_fee_infos,
             chunk_extras,
             chunks,
             partial_chunks,
@@ -2770,6 +3109,9 @@ impl<'a> ChainStoreUpdate<'a> {
         for (hash, block_extra) in block_extras {
             self.chain_store.block_extras.cache_set(hash.into(), block_extra);
         }
+        for (hash, fee_info) in block_fee_infos {
+            self.chain_store.block_fee_infos.cache_set(hash.into(), fee_info);
+        }
         for ((block_hash, shard_id), chunk_extra) in chunk_extras {
             let key = get_block_shard_id(&block_hash, shard_id);
             self.chain_store.chunk_extras.cache_set(key, chunk_extra);
@@ -2864,18 +3206,24 @@ impl<'a> ChainStoreUpdate<'a> {
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use borsh::BorshSerialize;
     use cached::Cached;
     use strum::IntoEnumIterator;
 
-    use near_crypto::KeyType;
+    use near_crypto::{InMemorySigner, KeyType};
     use near_primitives::block::{Block, Tip};
     #[cfg(feature = "expensive_tests")]
     use near_primitives::epoch_manager::BlockInfo;
     use near_primitives::errors::InvalidTxError;
     use near_primitives::hash::hash;
-    use near_primitives::types::{BlockHeight, EpochId, GCCount, NumBlocks};
+    use near_primitives::transaction::{
+        Action, ExecutionOutcomeWithId, IdempotencyKeyAction, SignedTransaction,
+    };
+    use near_primitives::types::{
+        BlockHeight, EpochId, GCCount, NumBlocks, WatchedAccountChange, WatchedAccountChangeKind,
+    };
     use near_primitives::utils::index_to_bytes;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
     use near_store::test_utils::create_test_store;
@@ -3062,6 +3410,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_duplicate_idempotency_key() {
+        let mut chain = get_chain();
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer =
+            Arc::new(InMemoryValidatorSigner::from_seed("test1", KeyType::ED25519, "test1"));
+        let fork_signer =
+            Arc::new(InMemoryValidatorSigner::from_seed("test2", KeyType::ED25519, "test2"));
+        let mut blocks = vec![];
+        let mut prev_block = genesis.clone();
+        let mut store_update = chain.mut_store().store_update();
+        for i in 1..5 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            prev_block = block.clone();
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update
+                .update_height_if_not_challenged(block.header().height(), *block.hash())
+                .unwrap();
+            blocks.push(block);
+        }
+        let fork_block = Block::empty_with_height(&blocks[0], 2, &*fork_signer.clone());
+        store_update.save_block_header(fork_block.header().clone()).unwrap();
+        store_update.commit().unwrap();
+
+        let tx_signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+        let transaction = |nonce| {
+            SignedTransaction::from_actions(
+                nonce,
+                "test1".to_string(),
+                "test2".to_string(),
+                &tx_signer,
+                vec![Action::IdempotencyKey(IdempotencyKeyAction { key: b"key".to_vec() })],
+                *genesis.hash(),
+            )
+        };
+        let executed = transaction(1);
+        let outcomes =
+            vec![ExecutionOutcomeWithId { id: executed.get_hash(), outcome: Default::default() }];
+        let head = blocks.last().unwrap().header().clone();
+
+        // Execution on a fork doesn't prevent the key from being used.
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_idempotency_keys(fork_block.hash(), 2, &[executed.clone()], &outcomes);
+        store_update.commit().unwrap();
+        assert!(chain.mut_store().check_idempotency_key(&head, &transaction(2)).is_ok());
+
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_idempotency_keys(blocks[1].hash(), 2, &[executed.clone()], &outcomes);
+        store_update.commit().unwrap();
+        assert_eq!(
+            chain.mut_store().check_idempotency_key(&head, &transaction(2)),
+            Err(InvalidTxError::DuplicateIdempotencyKey { transaction_hash: executed.get_hash() })
+        );
+        // The key is not used yet on the chain ending before the execution.
+        assert!(chain
+            .mut_store()
+            .check_idempotency_key(&blocks[0].header().clone(), &transaction(2))
+            .is_ok());
+        // Transactions without the key are not affected.
+        let without_key = SignedTransaction::send_money(
+            2,
+            "test1".to_string(),
+            "test2".to_string(),
+            &tx_signer,
+            1,
+            *genesis.hash(),
+        );
+        assert!(chain.mut_store().check_idempotency_key(&head, &without_key).is_ok());
+    }
+
     #[test]
     fn test_cache_invalidation() {
         let mut chain = get_chain();
@@ -3157,6 +3575,7 @@ mod tests {
             DBCol::ColChallengedBlocks,
             DBCol::ColStateDlInfos,
             DBCol::ColBlockExtra,
+            DBCol::ColBlockFeeInfo,
             DBCol::ColBlockPerHeight,
             DBCol::ColNextBlockHashes,
             DBCol::ColNextBlockWithNewChunk,
@@ -3197,6 +3616,120 @@ mod tests {
         }
     }
 
+    /// A garbage collection step out of time still clears a canonical height after the forks,
+    /// the heights left are counted as the debt.
+    #[test]
+    fn test_clear_data_within_time_limit() {
+        let mut chain = get_chain_with_epoch_length(1);
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer =
+            Arc::new(InMemoryValidatorSigner::from_seed("test1", KeyType::ED25519, "test1"));
+        let mut prev_block = genesis.clone();
+        let mut blocks = vec![prev_block.clone()];
+        for i in 1..15 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            blocks.push(block.clone());
+            let mut store_update = chain.mut_store().store_update();
+            store_update.save_block(block.clone());
+            store_update.inc_block_refcount(block.header().prev_hash()).unwrap();
+            store_update.save_head(&Tip::from_header(block.header())).unwrap();
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update
+                .chain_store_cache_update
+                .height_to_hashes
+                .insert(i, Some(*block.header().hash()));
+            store_update.save_next_block_hash(&prev_block.hash(), *block.hash());
+            store_update.commit().unwrap();
+
+            prev_block = block.clone();
+        }
+
+        chain.epoch_length = 1;
+        let trie = chain.runtime_adapter.get_tries();
+        let tail = chain.store().tail().unwrap();
+        let debt = chain.gc_debt().unwrap();
+        assert!(debt > 1);
+        assert!(chain.clear_data_within(trie.clone(), 100, Some(Duration::from_secs(0))).is_ok());
+        assert_eq!(chain.store().tail().unwrap(), tail + 1);
+        assert_eq!(chain.gc_debt().unwrap(), debt - 1);
+        assert!(chain.get_block(&blocks[tail as usize + 2].hash()).is_ok());
+
+        assert!(chain.clear_data_within(trie, 100, None).is_ok());
+        assert_eq!(chain.gc_debt().unwrap(), 0);
+        assert!(chain.get_block(&blocks[tail as usize + 2].hash()).is_err());
+        assert!(chain.get_block(&blocks[14].hash()).is_ok());
+    }
+
+    #[test]
+    fn test_watched_account_changes() {
+        let mut chain = get_chain_with_epoch_length(1);
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer =
+            Arc::new(InMemoryValidatorSigner::from_seed("test1", KeyType::ED25519, "test1"));
+        let change = |block_hash, block_height, index| WatchedAccountChange {
+            block_hash,
+            block_height,
+            index,
+            kind: WatchedAccountChangeKind::Balance { amount: block_height as u128, locked: 0 },
+        };
+        let mut prev_block = genesis.clone();
+        for i in 1..15 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            let mut store_update = chain.mut_store().store_update();
+            store_update.save_block(block.clone());
+            store_update.inc_block_refcount(block.header().prev_hash()).unwrap();
+            store_update.save_head(&Tip::from_header(block.header())).unwrap();
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update
+                .chain_store_cache_update
+                .height_to_hashes
+                .insert(i, Some(*block.header().hash()));
+            store_update.save_next_block_hash(&prev_block.hash(), *block.hash());
+            store_update
+                .save_watched_account_changes(
+                    i,
+                    vec![
+                        ("alice".to_string(), change(*block.hash(), i, 0)),
+                        ("alice".to_string(), change(*block.hash(), i, 1)),
+                        ("bob".to_string(), change(*block.hash(), i, 0)),
+                    ],
+                )
+                .unwrap();
+            store_update.commit().unwrap();
+            prev_block = block.clone();
+        }
+
+        let changes = |chain: &Chain, from_block_height, from_index, limit| {
+            chain
+                .store()
+                .get_watched_account_changes(
+                    &"alice".to_string(),
+                    from_block_height,
+                    from_index,
+                    limit,
+                )
+                .unwrap()
+                .into_iter()
+                .map(|change| (change.block_height, change.index))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(changes(&chain, 9, 1, 3), vec![(9, 1), (10, 0), (10, 1)]);
+        assert_eq!(changes(&chain, 14, 0, 3), vec![(14, 0), (14, 1)]);
+
+        chain.epoch_length = 1;
+        let trie = chain.runtime_adapter.get_tries();
+        assert!(chain.clear_data(trie, 100).is_ok());
+
+        // The changes at the garbage collected heights are removed with the blocks.
+        assert_eq!(changes(&chain, 0, 0, 2), vec![(8, 0), (8, 1)]);
+        assert!(chain
+            .store()
+            .store
+            .iter_prefix(DBCol::ColWatchedAccountChanges, &7u64.to_be_bytes())
+            .next()
+            .is_none());
+    }
+
     #[test]
     fn test_clear_old_data_fixed_height() {
         let mut chain = get_chain();
@@ -9,6 +9,7 @@ use log::debug;
 use num_rational::Rational;
 use serde::Serialize;
 
+use near_chain_configs::{GenesisConfig, ProtocolConfigView, DEFAULT_MAX_BLOCK_SIZE};
 use near_crypto::{KeyType, PublicKey, SecretKey, Signature};
 use near_pool::types::PoolIterator;
 use near_primitives::account::{AccessKey, Account};
@@ -29,8 +30,8 @@ use near_primitives::types::{
 use near_primitives::validator_signer::InMemoryValidatorSigner;
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 use near_primitives::views::{
-    AccessKeyInfoView, AccessKeyList, CallResult, EpochValidatorInfo, QueryRequest, QueryResponse,
-    QueryResponseKind, ViewStateResult,
+    AccessKeyInfoView, AccessKeyList, AccountList, CallResult, ContractMetadataView,
+    EpochValidatorInfo, QueryRequest, QueryResponse, QueryResponseKind, ViewStateResult,
 };
 use near_store::test_utils::create_test_store;
 use near_store::{
@@ -333,6 +334,10 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(validators.iter().map(|x| (x.clone(), false)).collect())
     }
 
+    fn get_epoch_chunk_producers(&self, epoch_id: &EpochId) -> Result<Vec<ValidatorStake>, Error> {
+        Ok(self.validators[self.get_valset_for_epoch(epoch_id)?].clone())
+    }
+
     fn get_epoch_block_approvers_ordered(
         &self,
         parent_hash: &CryptoHash,
@@ -478,6 +483,10 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(None)
     }
 
+    fn verify_transaction_signature(&self, _transaction: &SignedTransaction) -> bool {
+        true
+    }
+
     fn prepare_transactions(
         &self,
         _gas_price: Balance,
@@ -699,7 +708,7 @@ impl RuntimeAdapter for KeyValueRuntime {
 
     fn query(
         &self,
-        _shard_id: ShardId,
+        shard_id: ShardId,
         state_root: &StateRoot,
         block_height: BlockHeight,
         _block_timestamp: u64,
@@ -724,12 +733,14 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_height,
                 block_hash: *block_hash,
             }),
-            QueryRequest::ViewAccessKeyList { .. } => Ok(QueryResponse {
+            QueryRequest::ViewAccessKeyList { .. }
+            | QueryRequest::ViewAccessKeyListPaginated { .. } => Ok(QueryResponse {
                 kind: QueryResponseKind::AccessKeyList(AccessKeyList {
                     keys: vec![AccessKeyInfoView {
                         public_key: PublicKey::empty(KeyType::ED25519),
                         access_key: AccessKey::full_access().into(),
                     }],
+                    next_cursor: None,
                 }),
                 block_height,
                 block_hash: *block_hash,
@@ -739,14 +750,17 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_height,
                 block_hash: *block_hash,
             }),
-            QueryRequest::ViewState { .. } => Ok(QueryResponse {
-                kind: QueryResponseKind::ViewState(ViewStateResult {
-                    values: Default::default(),
-                    proof: vec![],
-                }),
-                block_height,
-                block_hash: *block_hash,
-            }),
+            QueryRequest::ViewState { .. } | QueryRequest::ViewStatePaginated { .. } => {
+                Ok(QueryResponse {
+                    kind: QueryResponseKind::ViewState(ViewStateResult {
+                        values: Default::default(),
+                        proof: vec![],
+                        next_cursor: None,
+                    }),
+                    block_height,
+                    block_hash: *block_hash,
+                })
+            }
             QueryRequest::CallFunction { .. } => Ok(QueryResponse {
                 kind: QueryResponseKind::CallResult(CallResult {
                     result: Default::default(),
@@ -755,6 +769,39 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_height,
                 block_hash: *block_hash,
             }),
+            QueryRequest::ViewContractMetadata { .. } => Ok(QueryResponse {
+                kind: QueryResponseKind::ContractMetadata(ContractMetadataView { methods: vec![] }),
+                block_height,
+                block_hash: *block_hash,
+            }),
+            QueryRequest::ViewAccountsByPrefix { prefix, cursor, limit } => {
+                let mut accounts: Vec<AccountId> =
+                    self.state.read().unwrap().get(&state_root).map_or_else(Vec::new, |state| {
+                        state
+                            .amounts
+                            .keys()
+                            .filter(|account_id| {
+                                account_id.starts_with(prefix.as_str())
+                                    && self.account_id_to_shard_id(account_id) == shard_id
+                                    && cursor.as_ref().map_or(true, |cursor| *account_id > cursor)
+                            })
+                            .cloned()
+                            .collect()
+                    });
+                accounts.sort();
+                let limit = limit.map_or(accounts.len(), |limit| limit as usize);
+                let next_cursor = if accounts.len() > limit {
+                    accounts.truncate(limit);
+                    accounts.last().cloned()
+                } else {
+                    None
+                };
+                Ok(QueryResponse {
+                    kind: QueryResponseKind::AccountList(AccountList { accounts, next_cursor }),
+                    block_height,
+                    block_hash: *block_hash,
+                })
+            }
         }
     }
 
@@ -891,6 +938,15 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(PROTOCOL_VERSION)
     }
 
+    fn get_protocol_config(&self, _epoch_id: &EpochId) -> Result<ProtocolConfigView, Error> {
+        let genesis_config = GenesisConfig {
+            protocol_version: PROTOCOL_VERSION,
+            epoch_length: self.epoch_length,
+            ..GenesisConfig::default()
+        };
+        Ok(ProtocolConfigView::from(&genesis_config))
+    }
+
     fn get_validator_info(&self, _block_hash: &CryptoHash) -> Result<EpochValidatorInfo, Error> {
         Ok(EpochValidatorInfo {
             current_validators: vec![],
@@ -899,6 +955,7 @@ impl RuntimeAdapter for KeyValueRuntime {
             next_fishermen: vec![],
             current_proposals: vec![],
             prev_epoch_kickout: vec![],
+            current_epoch_kickout: None,
             epoch_start_height: 0,
         })
     }
@@ -976,6 +1033,8 @@ pub fn setup_with_tx_validity_period(
             time: Utc::now(),
             height: 0,
             gas_limit: 1_000_000,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            block_limits_upgrades: vec![],
             min_gas_price: 100,
             max_gas_price: 1_000_000_000,
             total_supply: 1_000_000_000,
@@ -1016,6 +1075,8 @@ pub fn setup_with_validators(
             time: Utc::now(),
             height: 0,
             gas_limit: 1_000_000,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            block_limits_upgrades: vec![],
             min_gas_price: 100,
             max_gas_price: 1_000_000_000,
             total_supply: 1_000_000_000,
@@ -1135,6 +1196,8 @@ impl ChainGenesis {
             time: Utc::now(),
             height: 0,
             gas_limit: 1_000_000,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            block_limits_upgrades: vec![],
             min_gas_price: 0,
             max_gas_price: 1_000_000_000,
             total_supply: 1_000_000_000,
@@ -28,7 +28,7 @@ use near_store::{PartialStorage, ShardTries, Store, Trie, WrappedTrieChanges};
 
 use crate::error::Error;
 use chrono::{DateTime, Utc};
-use near_chain_configs::GenesisConfig;
+use near_chain_configs::{BlockLimitsUpgrade, GenesisConfig, ProtocolConfigView};
 use num_rational::Rational;
 
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -71,6 +71,25 @@ pub struct AcceptedBlock {
     pub provenance: Provenance,
 }
 
+/// Switch of the head to a block on another fork than the previous head.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgEvent {
+    pub old_head: CryptoHash,
+    pub new_head: CryptoHash,
+    /// Last block of the previous fork that is still on the canonical chain.
+    pub common_ancestor: CryptoHash,
+    /// Number of blocks that left the canonical chain.
+    pub depth: NumBlocks,
+    /// Blocks that left the canonical chain, from the old head down.
+    pub abandoned_blocks: Vec<CryptoHash>,
+}
+
+/// Receives the reorgs of the canonical chain, e.g. for indexers to invalidate the data of the
+/// abandoned blocks.
+pub trait ReorgAdapter: Sync + Send {
+    fn on_reorg(&self, reorg: &ReorgEvent);
+}
+
 /// Map of shard to list of receipts to send to it.
 pub type ReceiptResult = HashMap<ShardId, Vec<Receipt>>;
 
@@ -138,6 +157,9 @@ pub struct BlockEconomicsConfig {
     gas_price_adjustment_rate: Rational,
     min_gas_price: Balance,
     max_gas_price: Balance,
+    gas_limit: Gas,
+    max_block_size: u64,
+    block_limits_upgrades: Vec<BlockLimitsUpgrade>,
     genesis_protocol_version: ProtocolVersion,
 }
 
@@ -170,6 +192,29 @@ impl BlockEconomicsConfig {
     pub fn gas_price_adjustment_rate(&self, _protocol_version: ProtocolVersion) -> Rational {
         self.gas_price_adjustment_rate
     }
+
+    /// Latest limits upgrade that is enabled at the given protocol version.
+    fn block_limits_upgrade(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Option<&BlockLimitsUpgrade> {
+        self.block_limits_upgrades
+            .iter()
+            .rev()
+            .find(|upgrade| upgrade.protocol_version <= protocol_version)
+    }
+
+    /// Gas limit that every chunk produced at the given protocol version must have.
+    pub fn gas_limit(&self, protocol_version: ProtocolVersion) -> Gas {
+        self.block_limits_upgrade(protocol_version)
+            .map_or(self.gas_limit, |upgrade| upgrade.gas_limit)
+    }
+
+    /// Max size of a borsh-serialized block produced at the given protocol version.
+    pub fn max_block_size(&self, protocol_version: ProtocolVersion) -> u64 {
+        self.block_limits_upgrade(protocol_version)
+            .map_or(self.max_block_size, |upgrade| upgrade.max_block_size)
+    }
 }
 
 impl From<&ChainGenesis> for BlockEconomicsConfig {
@@ -178,6 +223,9 @@ impl From<&ChainGenesis> for BlockEconomicsConfig {
             gas_price_adjustment_rate: chain_genesis.gas_price_adjustment_rate,
             min_gas_price: chain_genesis.min_gas_price,
             max_gas_price: chain_genesis.max_gas_price,
+            gas_limit: chain_genesis.gas_limit,
+            max_block_size: chain_genesis.max_block_size,
+            block_limits_upgrades: chain_genesis.block_limits_upgrades.clone(),
             genesis_protocol_version: chain_genesis.protocol_version,
         }
     }
@@ -189,6 +237,8 @@ pub struct ChainGenesis {
     pub time: DateTime<Utc>,
     pub height: BlockHeight,
     pub gas_limit: Gas,
+    pub max_block_size: u64,
+    pub block_limits_upgrades: Vec<BlockLimitsUpgrade>,
     pub min_gas_price: Balance,
     pub max_gas_price: Balance,
     pub total_supply: Balance,
@@ -208,6 +258,8 @@ where
             time: genesis_config.genesis_time,
             height: genesis_config.genesis_height,
             gas_limit: genesis_config.gas_limit,
+            max_block_size: genesis_config.max_block_size,
+            block_limits_upgrades: genesis_config.block_limits_upgrades.clone(),
             min_gas_price: genesis_config.min_gas_price,
             max_gas_price: genesis_config.max_gas_price,
             total_supply: genesis_config.total_supply,
@@ -259,6 +311,10 @@ pub trait RuntimeAdapter: Send + Sync {
         current_protocol_version: ProtocolVersion,
     ) -> Result<Option<InvalidTxError>, Error>;
 
+    /// Verifies the signature of the transaction. Valid signatures are remembered, so that
+    /// `validate_tx` and applying the chunk with the transaction don't verify them again.
+    fn verify_transaction_signature(&self, transaction: &SignedTransaction) -> bool;
+
     /// Returns an ordered list of valid transactions from the pool up the given limits.
     /// Pulls transactions from the given pool iterators one by one. Validates each transaction
     /// against the given `chain_validate` closure and runtime's transaction verifier.
@@ -343,6 +399,10 @@ pub trait RuntimeAdapter: Send + Sync {
         parent_hash: &CryptoHash,
     ) -> Result<Vec<ApprovalStake>, Error>;
 
+    /// Chunk producers of the epoch, including the chunk-only producers that don't produce
+    /// blocks.
+    fn get_epoch_chunk_producers(&self, epoch_id: &EpochId) -> Result<Vec<ValidatorStake>, Error>;
+
     /// Block producers for given height for the main block. Return error if outside of known boundaries.
     fn get_block_producer(
         &self,
@@ -438,6 +498,9 @@ pub trait RuntimeAdapter: Send + Sync {
     /// Epoch active protocol version.
     fn get_epoch_protocol_version(&self, epoch_id: &EpochId) -> Result<ProtocolVersion, Error>;
 
+    /// Protocol parameters in effect in the given epoch.
+    fn get_protocol_config(&self, epoch_id: &EpochId) -> Result<ProtocolConfigView, Error>;
+
     /// Add proposals for validators.
     fn add_validator_proposals(&self, block_header_info: BlockHeaderInfo) -> Result<(), Error>;
 
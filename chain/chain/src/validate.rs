@@ -14,16 +14,13 @@ use near_primitives::sharding::{
     ShardChunk, ShardChunkHeader, ShardChunkHeaderV1, ShardChunkHeaderV2,
 };
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, ChunkExtra, EpochId, Nonce};
+use near_primitives::types::{AccountId, ChunkExtra, EpochId, Gas, Nonce};
 use near_store::PartialStorage;
 
 use crate::byzantine_assert;
 use crate::types::ApplyTransactionResult;
 use crate::{ChainStore, Error, ErrorKind, RuntimeAdapter};
 
-/// Gas limit cannot be adjusted for more than 0.1% at a time.
-const GAS_LIMIT_ADJUSTMENT_FACTOR: u64 = 1000;
-
 /// Verifies that chunk's proofs in the header match the body.
 pub fn validate_chunk_proofs(chunk: &ShardChunk, runtime_adapter: &dyn RuntimeAdapter) -> bool {
     let correct_chunk_hash = match chunk {
@@ -115,7 +112,8 @@ pub fn validate_transactions_order(transactions: &[SignedTransaction]) -> bool {
     true
 }
 
-/// Validate that all next chunk information matches previous chunk extra.
+/// Validate that all next chunk information matches previous chunk extra and that the chunk has
+/// the gas limit of the protocol version of the block it is included in.
 pub fn validate_chunk_with_chunk_extra(
     chain_store: &mut ChainStore,
     runtime_adapter: &dyn RuntimeAdapter,
@@ -123,6 +121,7 @@ pub fn validate_chunk_with_chunk_extra(
     prev_chunk_extra: &ChunkExtra,
     prev_chunk_header: &ShardChunkHeader,
     chunk_header: &ShardChunkHeader,
+    gas_limit: Gas,
 ) -> Result<(), Error> {
     if prev_chunk_extra.state_root != chunk_header.prev_state_root() {
         return Err(ErrorKind::InvalidStateRoot.into());
@@ -136,7 +135,7 @@ pub fn validate_chunk_with_chunk_extra(
         return Err(ErrorKind::InvalidValidatorProposals.into());
     }
 
-    if prev_chunk_extra.gas_limit != chunk_header.gas_limit() {
+    if chunk_header.gas_limit() != gas_limit {
         return Err(ErrorKind::InvalidGasLimit.into());
     }
 
@@ -160,13 +159,6 @@ pub fn validate_chunk_with_chunk_extra(
         return Err(ErrorKind::InvalidReceiptsProof.into());
     }
 
-    let prev_gas_limit = prev_chunk_extra.gas_limit;
-    if chunk_header.gas_limit() < prev_gas_limit - prev_gas_limit / GAS_LIMIT_ADJUSTMENT_FACTOR
-        || chunk_header.gas_limit() > prev_gas_limit + prev_gas_limit / GAS_LIMIT_ADJUSTMENT_FACTOR
-    {
-        return Err(ErrorKind::InvalidGasLimit.into());
-    }
-
     Ok(())
 }
 
@@ -1,7 +1,14 @@
+use std::time::Duration;
+
 use near_chain::test_utils::setup;
-use near_chain::{Block, ChainStoreAccess, ErrorKind, Provenance};
+use near_chain::{
+    Block, Chain, ChainStore, ChainStoreAccess, ErrorKind, Provenance, RuntimeAdapter,
+};
+use near_chain_configs::TrustedCheckpoint;
+use near_crypto::KeyType;
 use near_logger_utils::init_test_logger;
 use near_primitives::hash::CryptoHash;
+use near_primitives::validator_signer::InMemoryValidatorSigner;
 use near_primitives::version::PROTOCOL_VERSION;
 use num_rational::Rational;
 
@@ -39,6 +46,7 @@ fn build_chain_with_orhpans() {
     }
     let last_block = &blocks[blocks.len() - 1];
     let block = Block::produce(
+        PROTOCOL_VERSION,
         PROTOCOL_VERSION,
         &last_block.header(),
         10,
@@ -116,6 +124,37 @@ fn build_chain_with_orhpans() {
     );
 }
 
+#[test]
+fn orphan_pool_limits() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    chain.set_orphan_pool_limits(2, Duration::from_secs(300));
+    let mut blocks = vec![chain.get_block(&chain.genesis().hash().clone()).unwrap().clone()];
+    for i in 1..6 {
+        let block = Block::empty(&blocks[i - 1], &*signer);
+        blocks.push(block);
+    }
+    for i in (3..6).rev() {
+        let err = chain
+            .process_block(&None, blocks[i].clone(), Provenance::NONE, |_| {}, |_| {}, |_| {})
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Orphan);
+    }
+    // The highest orphan is evicted.
+    assert_eq!(chain.orphans_len(), 2);
+    assert_eq!(chain.orphans_evicted_len(), 1);
+    assert!(!chain.is_orphan(blocks[5].hash()));
+    assert_eq!(chain.orphan_missing_ancestor(blocks[4].hash()), *blocks[2].hash());
+
+    for i in 1..3 {
+        chain
+            .process_block(&None, blocks[i].clone(), Provenance::NONE, |_| {}, |_| {}, |_| {})
+            .unwrap();
+    }
+    assert_eq!(chain.orphans_len(), 0);
+    assert_eq!(chain.head().unwrap().height, 4);
+}
+
 #[test]
 fn build_chain_with_skips_and_forks() {
     init_test_logger();
@@ -237,3 +276,138 @@ fn next_blocks() {
     assert_eq!(chain.mut_store().get_next_block_hash(&b1_hash).unwrap(), &b3_hash);
     assert_eq!(chain.mut_store().get_next_block_hash(&b3_hash).unwrap(), &b4_hash);
 }
+
+#[test]
+fn trusted_checkpoint() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let genesis = chain.get_block(&chain.genesis().hash().clone()).unwrap().clone();
+    let b1 = Block::empty(&genesis, &*signer);
+    let b2 = Block::empty(&b1, &*signer);
+    let b3 = Block::empty(&b2, &*signer);
+    let b4 = Block::empty(&b3, &*signer);
+    // Signing again keeps the hash of the block.
+    let other = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "other");
+    let mut forged_b2 = b2.clone();
+    forged_b2.mut_header().resign(&other);
+    chain.set_trusted_checkpoint(Some(TrustedCheckpoint {
+        height: 3,
+        block_hash: *b3.hash(),
+        state_root: *b3.header().prev_state_root(),
+    }));
+    let process = |chain: &mut Chain, block: &Block| {
+        chain
+            .process_block(&None, block.clone(), Provenance::PRODUCED, |_| {}, |_| {}, |_| {})
+            .map(|_| ())
+            .map_err(|err| err.kind())
+    };
+
+    assert!(process(&mut chain, &b1).is_ok());
+    // The ancestors of the checkpoint are only known once its header is.
+    assert_eq!(process(&mut chain, &forged_b2), Err(ErrorKind::InvalidSignature));
+    chain.sync_block_headers(vec![b2.header().clone(), b3.header().clone()], |_| {}).unwrap();
+
+    // Blocks below the checkpoint that are not its ancestors are checked.
+    let mut fork = Block::empty_with_height(&genesis, 2, &*signer);
+    fork.mut_header().resign(&other);
+    assert_eq!(process(&mut chain, &fork), Err(ErrorKind::InvalidSignature));
+
+    assert!(process(&mut chain, &forged_b2).is_ok());
+
+    // Blocks above the checkpoint must descend from it, whether their chain has a block at its
+    // height or not.
+    let fork_at_checkpoint = Block::empty_with_height(&b1, 3, &*signer);
+    assert_eq!(process(&mut chain, &fork_at_checkpoint), Err(ErrorKind::InvalidCheckpointBlock));
+    let fork_past_checkpoint = Block::empty_with_height(&b2, 4, &*signer);
+    assert_eq!(process(&mut chain, &fork_past_checkpoint), Err(ErrorKind::InvalidCheckpointBlock));
+
+    assert!(process(&mut chain, &b3).is_ok());
+    assert!(process(&mut chain, &b4).is_ok());
+    assert_eq!(chain.head().unwrap().height, 4);
+}
+
+#[test]
+fn undo_blocks() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let mut blocks = vec![chain.get_block(&chain.genesis().hash().clone()).unwrap().clone()];
+    for i in 1..7 {
+        let block = Block::empty(&blocks[i - 1], &*signer);
+        blocks.push(block.clone());
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+    let tries = chain.runtime_adapter.get_tries();
+    let final_height = chain.final_head().unwrap().height;
+    assert!(final_height < 5);
+    // A chain store on the same database, like the view client's, caches the last block.
+    let mut view_store = ChainStore::new(chain.store().owned_store(), 0);
+    assert!(view_store.get_block(blocks[6].hash()).is_ok());
+
+    let tip = chain.undo_blocks(tries.clone(), 1, false).unwrap();
+    assert_eq!(tip.height, 5);
+    assert_eq!(chain.head().unwrap(), tip);
+    assert_eq!(chain.header_head().unwrap(), tip);
+    assert!(chain.block_exists(blocks[5].hash()).unwrap());
+    for block in &blocks[6..] {
+        assert!(!chain.block_exists(block.hash()).unwrap());
+        assert!(chain.get_block(block.hash()).is_err());
+        assert!(chain.get_block_header(block.hash()).is_ok());
+    }
+    view_store.clear_caches_if_undone().unwrap();
+    assert!(view_store.get_block(blocks[6].hash()).is_err());
+    assert_eq!(view_store.head().unwrap(), tip);
+
+    // The head doesn't go below the last final block.
+    let tip = chain.undo_blocks(tries.clone(), 100, false).unwrap();
+    assert_eq!(tip.height, final_height);
+
+    // The removed blocks are applied again.
+    for block in blocks[final_height as usize + 1..].iter().cloned() {
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[6].hash());
+    let final_head = chain.final_head().unwrap();
+    assert_eq!(final_head.height, final_height);
+
+    // A stopped node goes past the last final block, which moves back with the head.
+    let tip = chain.undo_blocks(tries, 4, true).unwrap();
+    assert_eq!(tip.height, 2);
+    assert!(tip.height < final_height);
+    assert_eq!(chain.head().unwrap(), tip);
+    assert_eq!(chain.header_head().unwrap(), tip);
+    assert_eq!(chain.final_head().unwrap().last_block_hash, *blocks[2].last_final_block());
+    for block in &blocks[3..] {
+        assert!(!chain.block_exists(block.hash()).unwrap());
+    }
+    assert_eq!(chain.mut_store().get_block_hash_from_ordinal(2).unwrap(), blocks[2].hash());
+    assert!(chain.mut_store().get_block_hash_from_ordinal(3).is_err());
+
+    for block in blocks[3..].iter().cloned() {
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[6].hash());
+    assert_eq!(chain.final_head().unwrap(), final_head);
+    assert_eq!(chain.mut_store().get_block_hash_from_ordinal(6).unwrap(), blocks[6].hash());
+}
+
+#[test]
+fn reorg_event() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let genesis = chain.get_block(&chain.genesis().hash().clone()).unwrap().clone();
+    let b1 = Block::empty(&genesis, &*signer);
+    let b2 = Block::empty(&b1, &*signer);
+    let b3 = Block::empty(&b2, &*signer);
+    let c4 = Block::empty_with_height(&b1, 4, &*signer);
+    for block in vec![b1.clone(), b2.clone(), b3.clone(), c4.clone()] {
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, *c4.hash());
+
+    let reorg = chain.get_reorg_event(b3.hash(), c4.hash()).unwrap();
+    assert_eq!(reorg.old_head, *b3.hash());
+    assert_eq!(reorg.new_head, *c4.hash());
+    assert_eq!(reorg.common_ancestor, *b1.hash());
+    assert_eq!(reorg.depth, 2);
+    assert_eq!(reorg.abandoned_blocks, vec![*b3.hash(), *b2.hash()]);
+}
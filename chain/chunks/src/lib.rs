@@ -40,10 +40,12 @@ use near_primitives::version::ProtocolVersion;
 use near_primitives::{checked_feature, unwrap_or_return};
 
 use crate::chunk_cache::{EncodedChunksCache, EncodedChunksCacheEntry};
+use crate::part_requests::{FetchFrom, PartRequests};
 pub use crate::types::Error;
 use rand::Rng;
 
 mod chunk_cache;
+mod part_requests;
 pub mod test_utils;
 mod types;
 
@@ -51,6 +53,9 @@ const CHUNK_PRODUCER_BLACKLIST_SIZE: usize = 100;
 pub const CHUNK_REQUEST_RETRY_MS: u64 = 100;
 pub const CHUNK_REQUEST_SWITCH_TO_OTHERS_MS: u64 = 400;
 pub const CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS: u64 = 3_000;
+/// Time after which the owned parts of a chunk still missing are forwarded again, shortly before
+/// the block producer stops waiting for the chunks (2s by default).
+pub const CHUNK_REQUEST_REDUNDANT_FORWARD_MS: u64 = 1_500;
 const CHUNK_REQUEST_RETRY_MAX_MS: u64 = 100_000;
 const CHUNK_FORWARD_CACHE_SIZE: usize = 1000;
 const ACCEPTING_SEAL_PERIOD_MS: i64 = 30_000;
@@ -95,6 +100,8 @@ struct RequestPool {
     switch_to_full_fetch_duration: Duration,
     max_duration: Duration,
     requests: HashMap<ChunkHash, ChunkRequestInfo>,
+    /// Parts in flight of the requested chunks.
+    part_requests: HashMap<ChunkHash, PartRequests>,
 }
 
 impl RequestPool {
@@ -110,6 +117,7 @@ impl RequestPool {
             switch_to_full_fetch_duration,
             max_duration,
             requests: HashMap::default(),
+            part_requests: HashMap::default(),
         }
     }
     pub fn contains_key(&self, chunk_hash: &ChunkHash) -> bool {
@@ -117,11 +125,17 @@ impl RequestPool {
     }
 
     pub fn insert(&mut self, chunk_hash: ChunkHash, chunk_request: ChunkRequestInfo) {
+        self.part_requests.insert(chunk_hash.clone(), PartRequests::default());
         self.requests.insert(chunk_hash, chunk_request);
     }
 
     pub fn remove(&mut self, chunk_hash: &ChunkHash) {
         self.requests.remove(chunk_hash);
+        self.part_requests.remove(chunk_hash);
+    }
+
+    fn part_requests(&mut self, chunk_hash: &ChunkHash) -> &mut PartRequests {
+        self.part_requests.entry(chunk_hash.clone()).or_default()
     }
 
     pub fn fetch(&mut self) -> Vec<(ChunkHash, ChunkRequestInfo)> {
@@ -139,7 +153,7 @@ impl RequestPool {
             }
         }
         for chunk_hash in removed_requests {
-            self.requests.remove(&chunk_hash);
+            self.remove(&chunk_hash);
         }
         requests
     }
@@ -385,6 +399,9 @@ pub struct ShardsManager {
     requested_partial_encoded_chunks: RequestPool,
     stored_partial_encoded_chunks: HashMap<BlockHeight, HashMap<ShardId, PartialEncodedChunkV2>>,
     chunk_forwards_cache: SizedCache<ChunkHash, HashMap<u64, PartialEncodedChunkPart>>,
+    /// Missing chunks whose owned parts were already forwarded again.
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    redundantly_forwarded_chunks: SizedCache<ChunkHash, ()>,
 
     seals_mgr: SealsManager,
 }
@@ -409,6 +426,8 @@ impl ShardsManager {
             ),
             stored_partial_encoded_chunks: HashMap::new(),
             chunk_forwards_cache: SizedCache::with_size(CHUNK_FORWARD_CACHE_SIZE),
+            #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+            redundantly_forwarded_chunks: SizedCache::with_size(CHUNK_FORWARD_CACHE_SIZE),
             seals_mgr: SealsManager::new(me, runtime_adapter),
         }
     }
@@ -420,6 +439,11 @@ impl ShardsManager {
         );
     }
 
+    /// Number of chunks requested from the peers and not received yet.
+    pub fn num_requested_chunks(&self) -> usize {
+        self.requested_partial_encoded_chunks.requests.len()
+    }
+
     pub fn get_pool_iterator(&mut self, shard_id: ShardId) -> Option<PoolIteratorWrapper<'_>> {
         self.tx_pools.get_mut(&shard_id).map(|pool| pool.pool_iterator())
     }
@@ -474,6 +498,7 @@ impl ShardsManager {
 
         let seal = self.seals_mgr.get_seal(chunk_hash, parent_hash, height, shard_id)?;
 
+        let mut missing_part_ords = vec![];
         for part_ord in 0..self.runtime_adapter.num_total_parts() {
             let part_ord = part_ord as u64;
             if cache_entry.map_or(false, |cache_entry| cache_entry.parts.contains_key(&part_ord)) {
@@ -491,21 +516,39 @@ impl ShardsManager {
             };
 
             if need_to_fetch_part {
-                let fetch_from = if request_from_archival {
+                missing_part_ords.push(part_ord);
+            }
+        }
+
+        // Parts still in flight are not requested again until they time out, and then from
+        // another peer, so that a slow part owner doesn't hold up the chunk.
+        let now = Instant::now();
+        let due_parts = self
+            .requested_partial_encoded_chunks
+            .part_requests(chunk_hash)
+            .due(&missing_part_ords, now);
+
+        for (part_ord, fetch_from) in due_parts {
+            let fetch_from = if fetch_from == FetchFrom::Other {
+                self.get_random_target_tracking_shard(
+                    &parent_hash,
+                    shard_id,
+                    request_from_archival,
+                )?
+            } else if request_from_archival {
+                shard_representative_target.clone()
+            } else {
+                let part_owner = self.runtime_adapter.get_part_owner(&parent_hash, part_ord)?;
+
+                if Some(&part_owner) == self.me.as_ref() {
+                    // If missing own part, request it from the chunk producer / node tracking shard
                     shard_representative_target.clone()
                 } else {
-                    let part_owner = self.runtime_adapter.get_part_owner(&parent_hash, part_ord)?;
-
-                    if Some(&part_owner) == self.me.as_ref() {
-                        // If missing own part, request it from the chunk producer / node tracking shard
-                        shard_representative_target.clone()
-                    } else {
-                        AccountIdOrPeerTrackingShard::from_account(shard_id, part_owner)
-                    }
-                };
+                    AccountIdOrPeerTrackingShard::from_account(shard_id, part_owner)
+                }
+            };
 
-                bp_to_parts.entry(fetch_from).or_insert_with(|| vec![]).push(part_ord);
-            }
+            bp_to_parts.entry(fetch_from).or_insert_with(|| vec![]).push(part_ord);
         }
 
         let shards_to_fetch_receipts =
@@ -516,7 +559,9 @@ impl ShardsManager {
         // We need to send such a message to the original chunk producer if we do not have the receipts
         //     for some subset of shards, even if we don't need to request any parts from the original
         //     chunk producer.
-        if !shards_to_fetch_receipts.is_empty() {
+        if !shards_to_fetch_receipts.is_empty()
+            && self.requested_partial_encoded_chunks.part_requests(chunk_hash).receipts_due(now)
+        {
             bp_to_parts.entry(shard_representative_target.clone()).or_insert_with(|| vec![]);
         }
 
@@ -700,6 +745,17 @@ impl ShardsManager {
                     error!(target: "chunks", "Error during requesting partial encoded chunk: {}", err);
                 }
             }
+
+            #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+            {
+                if chunk_request.added.elapsed()
+                    > Duration::from_millis(CHUNK_REQUEST_REDUNDANT_FORWARD_MS)
+                {
+                    if let Err(err) = self.forward_parts_of_missing_chunk(&chunk_hash) {
+                        debug!(target: "chunks", "Error during forwarding the parts of missing chunk {:?}: {}", chunk_hash.0, err);
+                    }
+                }
+            }
         }
     }
 
@@ -1328,49 +1384,125 @@ impl ShardsManager {
         &mut self,
         partial_encoded_chunk: PartialEncodedChunkV2,
     ) -> Result<(), Error> {
-        let me = match &self.me {
-            Some(me) => me,
+        let owned_parts = self.owned_parts(
+            &partial_encoded_chunk.header.prev_block_hash(),
+            partial_encoded_chunk.parts,
+        );
+        if owned_parts.is_empty() {
+            return Ok(());
+        }
+        self.forward_parts(&partial_encoded_chunk.header, owned_parts, None)
+    }
+
+    /// Forwards again the parts owned by `self.me` of a chunk still missing shortly before the
+    /// block producer stops waiting for it, in case the first forwards were lost. The parts go to
+    /// the validators tracking the shard and to the producer of the block that is to include the
+    /// chunk. Every chunk is forwarded this way once.
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    fn forward_parts_of_missing_chunk(&mut self, chunk_hash: &ChunkHash) -> Result<(), Error> {
+        if self.redundantly_forwarded_chunks.cache_get(chunk_hash).is_some() {
+            return Ok(());
+        }
+        let (header, parts): (_, Vec<_>) = match self.encoded_chunks.get(chunk_hash) {
+            Some(entry) => (entry.header.clone(), entry.parts.values().cloned().collect()),
             None => return Ok(()),
         };
-        let parent_hash = partial_encoded_chunk.header.prev_block_hash();
+        let parent_hash = header.prev_block_hash();
         let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&parent_hash)?;
-        let shard_id = partial_encoded_chunk.header.shard_id();
-        let owned_parts: Vec<_> = partial_encoded_chunk
-            .parts
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&epoch_id)?;
+        if !checked_feature!(
+            "protocol_feature_forward_chunk_parts",
+            ForwardChunkParts,
+            protocol_version
+        ) {
+            return Ok(());
+        }
+        // Parts received later are forwarded on arrival, and again by the next call.
+        let owned_parts = self.owned_parts(&parent_hash, parts);
+        if owned_parts.is_empty() {
+            return Ok(());
+        }
+        let block_producer =
+            self.runtime_adapter.get_block_producer(&epoch_id, header.height_created())?;
+        self.redundantly_forwarded_chunks.cache_set(chunk_hash.clone(), ());
+        self.forward_parts(&header, owned_parts, Some(block_producer))
+    }
+
+    /// Parts owned by `self.me` among the given parts of a chunk.
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    fn owned_parts(
+        &self,
+        parent_hash: &CryptoHash,
+        parts: Vec<PartialEncodedChunkPart>,
+    ) -> Vec<PartialEncodedChunkPart> {
+        let me = match &self.me {
+            Some(me) => me,
+            None => return vec![],
+        };
+        parts
             .into_iter()
             .filter(|part| {
                 self.runtime_adapter
-                    .get_part_owner(&parent_hash, part.part_ord)
+                    .get_part_owner(parent_hash, part.part_ord)
                     .map_or(false, |owner| &owner == me)
             })
-            .collect();
-
-        if owned_parts.is_empty() {
-            return Ok(());
-        }
+            .collect()
+    }
 
-        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
-            &partial_encoded_chunk.header,
-            owned_parts,
-        );
+    /// Sends the parts of the chunk to the validators tracking its shard and to the extra
+    /// recipient, if any, once to every account.
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    fn forward_parts(
+        &self,
+        header: &ShardChunkHeader,
+        parts: Vec<PartialEncodedChunkPart>,
+        extra_recipient: Option<AccountId>,
+    ) -> Result<(), Error> {
+        let me = match &self.me {
+            Some(me) => me,
+            None => return Ok(()),
+        };
+        let parent_hash = header.prev_block_hash();
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&parent_hash)?;
+        let shard_id = header.shard_id();
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(header, parts);
 
-        let block_producers =
-            self.runtime_adapter.get_epoch_block_producers_ordered(&epoch_id, &parent_hash)?;
-        for (bp, _) in block_producers {
+        // Chunk-only producers track the shards of their chunks without producing blocks.
+        let block_producers = self
+            .runtime_adapter
+            .get_epoch_block_producers_ordered(&epoch_id, &parent_hash)?
+            .into_iter()
+            .map(|(bp, _)| bp.account_id);
+        let chunk_producers = self
+            .runtime_adapter
+            .get_epoch_chunk_producers(&epoch_id)?
+            .into_iter()
+            .map(|cp| cp.account_id);
+        let mut recipients = HashSet::new();
+        if let Some(account_id) = extra_recipient {
+            if me != &account_id {
+                recipients.insert(account_id.clone());
+                self.network_adapter.do_send(NetworkRequests::PartialEncodedChunkForward {
+                    account_id,
+                    forward: forward.clone(),
+                });
+            }
+        }
+        for account_id in block_producers.chain(chunk_producers) {
             // no need to send anything to myself
-            if me == &bp.account_id {
+            if me == &account_id || !recipients.insert(account_id.clone()) {
                 continue;
             }
 
             let cares_about_shard = self.cares_about_shard_this_or_next_epoch(
-                Some(&bp.account_id),
+                Some(&account_id),
                 &parent_hash,
                 shard_id,
                 false,
             );
             if cares_about_shard {
                 self.network_adapter.do_send(NetworkRequests::PartialEncodedChunkForward {
-                    account_id: bp.account_id,
+                    account_id,
                     forward: forward.clone(),
                 });
             }
@@ -1699,6 +1831,7 @@ impl ShardsManager {
 
 #[cfg(test)]
 mod test {
+    use crate::part_requests::PART_REQUEST_TIMEOUT;
     use crate::test_utils::{ChunkForwardingTestFixture, SealsManagerTestFixture};
     use crate::{
         ChunkRequestInfo, ProcessPartialEncodedChunkResult, Seal, SealsManager, ShardsManager,
@@ -1706,14 +1839,17 @@ mod test {
     };
     use near_chain::test_utils::KeyValueRuntime;
     use near_network::test_utils::MockNetworkAdapter;
-    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
-    use near_network::types::PartialEncodedChunkForwardMsg;
     use near_primitives::hash::{hash, CryptoHash};
     use near_primitives::sharding::{ChunkHash, PartialEncodedChunkV2};
     use near_primitives::version::PROTOCOL_VERSION;
     use near_store::test_utils::create_test_store;
     use std::sync::Arc;
     use std::time::{Duration, Instant};
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    use {
+        crate::CHUNK_REQUEST_REDUNDANT_FORWARD_MS,
+        near_network::types::PartialEncodedChunkForwardMsg, std::collections::HashSet,
+    };
 
     use near_network::NetworkRequests;
     use near_primitives::block::Tip;
@@ -1764,6 +1900,45 @@ mod test {
         };
     }
 
+    /// parts in flight should only be requested again once they time out
+    #[test]
+    fn test_request_parts_in_flight() {
+        let runtime_adapter = Arc::new(KeyValueRuntime::new(create_test_store()));
+        let network_adapter = Arc::new(MockNetworkAdapter::default());
+        let mut shards_manager =
+            ShardsManager::new(Some("test".to_string()), runtime_adapter, network_adapter.clone());
+        let chunk_hash = ChunkHash(hash(&[1]));
+        shards_manager.requested_partial_encoded_chunks.insert(
+            chunk_hash.clone(),
+            ChunkRequestInfo {
+                height: 0,
+                parent_hash: Default::default(),
+                shard_id: 0,
+                added: Instant::now(),
+                last_requested: Instant::now(),
+            },
+        );
+        let mut request = || {
+            shards_manager
+                .request_partial_encoded_chunk(
+                    0,
+                    &Default::default(),
+                    0,
+                    &chunk_hash,
+                    false,
+                    false,
+                    false,
+                )
+                .unwrap();
+            network_adapter.requests.write().unwrap().drain(..).count()
+        };
+
+        assert_eq!(request(), 1);
+        assert_eq!(request(), 0);
+        std::thread::sleep(PART_REQUEST_TIMEOUT);
+        assert!(request() > 0);
+    }
+
     #[cfg(feature = "expensive_tests")]
     #[test]
     fn test_seal_removal() {
@@ -1996,6 +2171,92 @@ mod test {
         assert!(requests_count > 0);
     }
 
+    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+    #[test]
+    fn test_forward_parts_of_missing_chunk() {
+        use near_chain::RuntimeAdapter;
+
+        // When a chunk is still missing shortly before the block timeout, its part owner should
+        // forward the parts again, to the shard trackers and the block producer, and only once.
+        let mut fixture = ChunkForwardingTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_chunk_part_owner.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+        );
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
+        let result = shards_manager
+            .process_partial_encoded_chunk(
+                partial_encoded_chunk,
+                &mut fixture.chain_store,
+                &mut fixture.rs,
+                PROTOCOL_VERSION,
+            )
+            .unwrap();
+        match result {
+            ProcessPartialEncodedChunkResult::NeedMorePartsOrReceipts(_) => shards_manager
+                .request_chunk_single(fixture.mock_chunk_header.clone(), None, PROTOCOL_VERSION),
+
+            _ => panic!("Expected to need more parts!"),
+        }
+        let forwarded_to = |fixture: &ChunkForwardingTestFixture| -> Vec<String> {
+            fixture
+                .mock_network
+                .requests
+                .write()
+                .unwrap()
+                .drain(..)
+                .filter_map(|r| match r {
+                    NetworkRequests::PartialEncodedChunkForward { account_id, .. } => {
+                        Some(account_id)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let head = Tip {
+            height: 0,
+            last_block_hash: Default::default(),
+            prev_block_hash: Default::default(),
+            epoch_id: Default::default(),
+            next_epoch_id: Default::default(),
+        };
+        forwarded_to(&fixture);
+
+        // Not close to the block timeout yet.
+        std::thread::sleep(Duration::from_millis(2 * CHUNK_REQUEST_RETRY_MS));
+        shards_manager.resend_chunk_requests(&head);
+        assert!(forwarded_to(&fixture).is_empty());
+
+        let chunk_hash = fixture.mock_chunk_header.chunk_hash();
+        shards_manager
+            .requested_partial_encoded_chunks
+            .requests
+            .get_mut(&chunk_hash)
+            .unwrap()
+            .added = Instant::now() - Duration::from_millis(CHUNK_REQUEST_REDUNDANT_FORWARD_MS);
+        std::thread::sleep(Duration::from_millis(2 * CHUNK_REQUEST_RETRY_MS));
+        shards_manager.resend_chunk_requests(&head);
+        let recipients = forwarded_to(&fixture);
+        assert!(recipients.contains(&fixture.mock_shard_tracker));
+        let epoch_id = fixture
+            .mock_runtime
+            .get_epoch_id_from_prev_block(&fixture.mock_chunk_header.prev_block_hash())
+            .unwrap();
+        let block_producer = fixture
+            .mock_runtime
+            .get_block_producer(&epoch_id, fixture.mock_chunk_header.height_created())
+            .unwrap();
+        assert!(
+            recipients.contains(&block_producer) || block_producer == fixture.mock_chunk_part_owner
+        );
+        assert_eq!(recipients.iter().collect::<HashSet<_>>().len(), recipients.len());
+
+        std::thread::sleep(Duration::from_millis(2 * CHUNK_REQUEST_RETRY_MS));
+        shards_manager.resend_chunk_requests(&head);
+        assert!(forwarded_to(&fixture).is_empty());
+    }
+
     #[cfg(feature = "protocol_feature_forward_chunk_parts")]
     #[test]
     fn test_receive_forward_before_header() {
@@ -0,0 +1,121 @@
+//! Requests of the parts of a missing chunk that are in flight.
+//!
+//! The parts of a chunk are requested from their owners all at once, each owner getting the
+//! parts it owns. A part that doesn't arrive within `PART_REQUEST_TIMEOUT` is requested again from
+//! another peer tracking the shard. Once most of the requested parts have arrived, the block waits
+//! on the slowest few owners, so the parts of that tail are requested elsewhere as soon as
+//! `TAIL_REQUEST_TIMEOUT` is over.
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// How long a part is waited for before it is requested from another peer.
+pub const PART_REQUEST_TIMEOUT: Duration = Duration::from_millis(400);
+/// How long the parts of the tail are waited for.
+pub const TAIL_REQUEST_TIMEOUT: Duration = Duration::from_millis(200);
+/// Missing parts are the tail once at most one in `TAIL_DIVISOR` requested parts is missing.
+const TAIL_DIVISOR: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchFrom {
+    /// The owner of the part, or the chunk producer for parts this node owns.
+    Owner,
+    /// Any other peer tracking the shard.
+    Other,
+}
+
+/// Parts of a chunk requested and not received yet.
+#[derive(Debug, Default)]
+pub struct PartRequests {
+    /// When each part was last requested, by part ordinal.
+    sent: HashMap<u64, Instant>,
+    /// Parts requested since the chunk was, received or not.
+    requested: usize,
+    /// When the receipts were last requested.
+    receipts_sent: Option<Instant>,
+}
+
+impl PartRequests {
+    /// Where to request the missing parts from now, parts still in flight are left out.
+    pub fn due(&mut self, missing: &[u64], now: Instant) -> Vec<(u64, FetchFrom)> {
+        let missing_set: HashSet<_> = missing.iter().collect();
+        self.sent.retain(|part_ord, _| missing_set.contains(part_ord));
+        let timeout = if self.sent.len() * TAIL_DIVISOR <= self.requested {
+            TAIL_REQUEST_TIMEOUT
+        } else {
+            PART_REQUEST_TIMEOUT
+        };
+        let mut due = vec![];
+        for &part_ord in missing {
+            let fetch_from = match self.sent.get(&part_ord) {
+                None => FetchFrom::Owner,
+                Some(sent) if now.saturating_duration_since(*sent) >= timeout => FetchFrom::Other,
+                Some(_) => continue,
+            };
+            if fetch_from == FetchFrom::Owner {
+                self.requested += 1;
+            }
+            self.sent.insert(part_ord, now);
+            due.push((part_ord, fetch_from));
+        }
+        due
+    }
+
+    /// Whether to request the receipts again, nothing tells when they arrive so they are asked
+    /// for every `PART_REQUEST_TIMEOUT` until the chunk is complete.
+    pub fn receipts_due(&mut self, now: Instant) -> bool {
+        match self.receipts_sent {
+            Some(sent) if now.saturating_duration_since(sent) < PART_REQUEST_TIMEOUT => false,
+            _ => {
+                self.receipts_sent = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_requests() {
+        let mut requests = PartRequests::default();
+        let start = Instant::now();
+        let millis = |millis| start + Duration::from_millis(millis);
+        let missing: Vec<u64> = (0..8).collect();
+
+        let due = requests.due(&missing, start);
+        assert_eq!(
+            due,
+            missing.iter().map(|&part_ord| (part_ord, FetchFrom::Owner)).collect::<Vec<_>>()
+        );
+        // In flight.
+        assert_eq!(requests.due(&missing, millis(100)), vec![]);
+        // Timed out.
+        assert_eq!(
+            requests.due(&[0, 1, 2, 3], millis(400)),
+            vec![
+                (0, FetchFrom::Other),
+                (1, FetchFrom::Other),
+                (2, FetchFrom::Other),
+                (3, FetchFrom::Other)
+            ]
+        );
+
+        // Parts that were not needed before go to their owners.
+        assert_eq!(requests.due(&[0, 1, 8], millis(500)), vec![(8, FetchFrom::Owner)]);
+        // Three of nine requested parts missing is not the tail yet.
+        assert_eq!(requests.due(&[0, 1, 8], millis(700)), vec![]);
+        // Two is, and they waited long enough.
+        assert_eq!(
+            requests.due(&[1, 8], millis(700)),
+            vec![(1, FetchFrom::Other), (8, FetchFrom::Other)]
+        );
+        assert_eq!(requests.due(&[1, 8], millis(800)), vec![]);
+        assert_eq!(requests.due(&[1], millis(900)), vec![(1, FetchFrom::Other)]);
+
+        assert!(requests.receipts_due(start));
+        assert!(!requests.receipts_due(millis(399)));
+        assert!(requests.receipts_due(millis(400)));
+    }
+}
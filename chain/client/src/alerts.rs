@@ -0,0 +1,200 @@
+//! Detection of critical events that are reported to the webhooks configured in
+//! `telemetry.alerts`.
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use actix::Addr;
+use sysinfo::{DiskExt, System, SystemExt};
+
+use near_primitives::types::{BlockHeight, NumBlocks};
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
+use near_telemetry::{alert, Alert, AlertKind, TelemetryActor};
+
+/// Alert when the validator produced less than this percent of the expected blocks.
+const KICKOUT_RISK_PERCENT: u64 = 95;
+/// Don't judge the validator until it was expected to produce this many blocks in the epoch.
+const KICKOUT_RISK_MIN_EXPECTED_BLOCKS: NumBlocks = 10;
+/// Alert when less than this percent of the disk with the database is available.
+const DISK_SPACE_LOW_PERCENT: u64 = 5;
+/// Alert when the head doesn't move for this long while peers are ahead.
+const SYNC_STALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Tracks the state of the node between checks and sends alerts to the telemetry actor.
+pub struct AlertMonitor {
+    telemetry_actor: Addr<TelemetryActor>,
+    node_id: String,
+    chain_id: String,
+    sys: System,
+    /// Height of the head and the time when it was first seen.
+    last_head: Option<(BlockHeight, Instant)>,
+    /// Protocol version of the last reported upgrade, to report every upgrade once.
+    last_protocol_upgrade: Option<ProtocolVersion>,
+}
+
+impl AlertMonitor {
+    pub fn new(telemetry_actor: Addr<TelemetryActor>, node_id: String, chain_id: String) -> Self {
+        Self {
+            telemetry_actor,
+            node_id,
+            chain_id,
+            sys: System::new(),
+            last_head: None,
+            last_protocol_upgrade: None,
+        }
+    }
+
+    fn alert(&self, kind: AlertKind, message: String) {
+        alert(
+            &self.telemetry_actor,
+            Alert::new(kind, message, self.node_id.clone(), self.chain_id.clone()),
+        );
+    }
+
+    pub fn fork_detected(
+        &self,
+        height: BlockHeight,
+        prev_head_height: BlockHeight,
+        num_abandoned_blocks: NumBlocks,
+    ) {
+        self.alert(
+            AlertKind::ForkDetected,
+            format!(
+                "Head switched to a different fork at #{} from #{}, abandoning {} blocks",
+                height, prev_head_height, num_abandoned_blocks
+            ),
+        );
+    }
+
+    pub fn check_kickout_risk(
+        &self,
+        num_produced_blocks: NumBlocks,
+        num_expected_blocks: NumBlocks,
+    ) {
+        if is_kickout_risk(num_produced_blocks, num_expected_blocks) {
+            self.alert(
+                AlertKind::KickoutRisk,
+                format!(
+                    "Produced {} out of {} expected blocks in the current epoch",
+                    num_produced_blocks, num_expected_blocks
+                ),
+            );
+        }
+    }
+
+    pub fn check_protocol_upgrade(
+        &mut self,
+        current_protocol_version: ProtocolVersion,
+        next_protocol_version: ProtocolVersion,
+    ) {
+        if next_protocol_version <= current_protocol_version
+            || self.last_protocol_upgrade == Some(next_protocol_version)
+        {
+            return;
+        }
+        self.last_protocol_upgrade = Some(next_protocol_version);
+        let mut message = format!(
+            "Protocol version changes from {} to {} in the next epoch",
+            current_protocol_version, next_protocol_version
+        );
+        if next_protocol_version > PROTOCOL_VERSION {
+            message.push_str(&format!(
+                ", the node supports only {} and must be upgraded",
+                PROTOCOL_VERSION
+            ));
+        }
+        self.alert(AlertKind::ProtocolUpgrade, message);
+    }
+
+    pub fn check_disk_space(&mut self, path: &Path) {
+        self.sys.refresh_disks_list();
+        // The disk with the longest mount point that contains the path.
+        let disk = self
+            .sys
+            .get_disks()
+            .iter()
+            .filter(|disk| path.starts_with(disk.get_mount_point()))
+            .max_by_key(|disk| disk.get_mount_point().as_os_str().len());
+        if let Some(disk) = disk {
+            if is_disk_space_low(disk.get_available_space(), disk.get_total_space()) {
+                self.alert(
+                    AlertKind::DiskSpaceLow,
+                    format!(
+                        "Only {} MB out of {} MB is available on {}",
+                        disk.get_available_space() / 1_000_000,
+                        disk.get_total_space() / 1_000_000,
+                        disk.get_mount_point().display()
+                    ),
+                );
+            }
+        }
+    }
+
+    pub fn check_sync_progress(
+        &mut self,
+        head_height: BlockHeight,
+        highest_peer_height: BlockHeight,
+    ) {
+        let now = Instant::now();
+        match self.last_head {
+            Some((height, _)) if height == head_height => {}
+            _ => self.last_head = Some((head_height, now)),
+        }
+        let stalled_since = self.last_head.map(|(_, since)| since).unwrap_or(now);
+        if is_sync_stalled(head_height, highest_peer_height, now.duration_since(stalled_since)) {
+            self.alert(
+                AlertKind::SyncStalled,
+                format!(
+                    "Head is stuck at #{} for {} seconds, peers are at #{}",
+                    head_height,
+                    now.duration_since(stalled_since).as_secs(),
+                    highest_peer_height
+                ),
+            );
+        }
+    }
+}
+
+fn is_kickout_risk(num_produced_blocks: NumBlocks, num_expected_blocks: NumBlocks) -> bool {
+    num_expected_blocks >= KICKOUT_RISK_MIN_EXPECTED_BLOCKS
+        && num_produced_blocks * 100 < num_expected_blocks * KICKOUT_RISK_PERCENT
+}
+
+fn is_disk_space_low(available_space: u64, total_space: u64) -> bool {
+    total_space > 0 && available_space * 100 < total_space * DISK_SPACE_LOW_PERCENT
+}
+
+fn is_sync_stalled(
+    head_height: BlockHeight,
+    highest_peer_height: BlockHeight,
+    head_age: Duration,
+) -> bool {
+    highest_peer_height > head_height && head_age >= SYNC_STALL_TIMEOUT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_kickout_risk() {
+        assert!(!is_kickout_risk(0, KICKOUT_RISK_MIN_EXPECTED_BLOCKS - 1));
+        assert!(is_kickout_risk(0, KICKOUT_RISK_MIN_EXPECTED_BLOCKS));
+        assert!(is_kickout_risk(94, 100));
+        assert!(!is_kickout_risk(95, 100));
+        assert!(!is_kickout_risk(100, 100));
+    }
+
+    #[test]
+    fn test_is_disk_space_low() {
+        assert!(!is_disk_space_low(0, 0));
+        assert!(is_disk_space_low(4, 100));
+        assert!(!is_disk_space_low(5, 100));
+    }
+
+    #[test]
+    fn test_is_sync_stalled() {
+        assert!(!is_sync_stalled(10, 20, SYNC_STALL_TIMEOUT - Duration::from_secs(1)));
+        assert!(is_sync_stalled(10, 20, SYNC_STALL_TIMEOUT));
+        assert!(!is_sync_stalled(20, 20, SYNC_STALL_TIMEOUT));
+    }
+}
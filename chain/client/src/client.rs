@@ -6,6 +6,7 @@ use std::iter;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+use borsh::BorshSerialize;
 use cached::{Cached, SizedCache};
 use chrono::Utc;
 use log::{debug, error, info, warn};
@@ -31,20 +32,28 @@ use near_primitives::sharding::{
 };
 use near_primitives::syncing::ReceiptResponse;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, ApprovalStake, BlockHeight, ChunkExtra, EpochId, ShardId};
+use near_primitives::types::{
+    AccountId, ApprovalStake, BlockHeight, ChunkExtra, EpochId, NumBlocks, ShardId,
+};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::to_timestamp;
 use near_primitives::validator_signer::ValidatorSigner;
 
+use crate::external_state::ExternalStateSync;
 use crate::metrics;
 use crate::sync::{BlockSync, HeaderSync, StateSync, StateSyncResult};
 use crate::types::{Error, ShardSyncDownload};
 use crate::SyncStatus;
 use near_primitives::block_header::ApprovalType;
+use near_primitives::upgrade_schedule::get_protocol_version;
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 
+#[cfg(feature = "protocol_feature_pq_crypto")]
+use near_crypto::HybridSecretKey;
 #[cfg(feature = "protocol_feature_forward_chunk_parts")]
 use near_network::types::PartialEncodedChunkForwardMsg;
+#[cfg(feature = "protocol_feature_pq_crypto")]
+use near_primitives::checked_feature;
 #[cfg(feature = "protocol_feature_forward_chunk_parts")]
 use near_primitives::sharding::PartialEncodedChunkV2;
 
@@ -78,6 +87,8 @@ pub struct Client {
     pub block_sync: BlockSync,
     /// Keeps track of syncing state.
     pub state_sync: StateSync,
+    /// External storage that state sync and the catchups download state from, next to the peers.
+    external_state_sync: Option<ExternalStateSync>,
     /// List of currently accumulated challenges.
     pub challenges: HashMap<CryptoHash, Challenge>,
     /// A ReedSolomon instance to reconstruct shard.
@@ -87,6 +98,8 @@ pub struct Client {
     /// Last time the head was updated, or our head was rebroadcasted. Used to re-broadcast the head
     /// again to prevent network from stalling if a large percentage of the network missed a block
     last_time_head_progress_made: Instant,
+    /// Whether garbage collection is paused by the operator.
+    pub gc_paused: bool,
 }
 
 impl Client {
@@ -103,7 +116,22 @@ impl Client {
         } else {
             DoomslugThresholdMode::NoApprovals
         };
-        let chain = Chain::new(runtime_adapter.clone(), &chain_genesis, doomslug_threshold_mode)?;
+        let mut chain =
+            Chain::new(runtime_adapter.clone(), &chain_genesis, doomslug_threshold_mode)?;
+        chain.set_orphan_pool_limits(config.max_orphans, config.max_orphan_age);
+        chain.set_trusted_checkpoint(config.trusted_checkpoint.clone());
+        chain.set_optimistic_block_application(config.optimistic_block_application);
+        // Epochs older than `OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION` are still processed when
+        // syncing the history, so only a newer protocol version of the head epoch is rejected.
+        let head_protocol_version =
+            runtime_adapter.get_epoch_protocol_version(&chain.head()?.epoch_id)?;
+        if head_protocol_version > PROTOCOL_VERSION {
+            return Err(near_chain::Error::from(ErrorKind::UnsupportedProtocolVersion(
+                head_protocol_version,
+                PROTOCOL_VERSION,
+            ))
+            .into());
+        }
         let shards_mgr = ShardsManager::new(
             validator_signer.as_ref().map(|x| x.validator_id().clone()),
             runtime_adapter.clone(),
@@ -119,7 +147,7 @@ impl Client {
         );
         let block_sync =
             BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
-        let state_sync = StateSync::new(network_adapter.clone());
+        let state_sync = StateSync::new(network_adapter.clone(), None);
         let num_block_producer_seats = config.num_block_producer_seats as usize;
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
@@ -152,13 +180,22 @@ impl Client {
             header_sync,
             block_sync,
             state_sync,
+            external_state_sync: None,
             challenges: Default::default(),
             rs: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: SizedCache::with_size(NUM_REBROADCAST_BLOCKS),
             last_time_head_progress_made: Instant::now(),
+            gc_paused: false,
         })
     }
 
+    /// Lets state sync and the catchups started from now on download state from external storage.
+    pub fn set_external_state_sync(&mut self, external_state_sync: ExternalStateSync) {
+        self.state_sync =
+            StateSync::new(self.network_adapter.clone(), Some(external_state_sync.clone()));
+        self.external_state_sync = Some(external_state_sync);
+    }
+
     // Checks if it's been at least `stall_timeout` since the last time the head was updated, or
     // this method was called. If yes, rebroadcasts the current head.
     pub fn check_head_progress_stalled(&mut self, stall_timeout: Duration) -> Result<(), Error> {
@@ -389,6 +426,7 @@ impl Client {
             self.chain.block_economics_config.gas_price_adjustment_rate(protocol_version);
         let min_gas_price = self.chain.block_economics_config.min_gas_price(protocol_version);
         let max_gas_price = self.chain.block_economics_config.max_gas_price(protocol_version);
+        let max_block_size = self.chain.block_economics_config.max_block_size(protocol_version);
 
         let next_bp_hash = if prev_epoch_id != epoch_id {
             Chain::compute_bp_hash(&*self.runtime_adapter, next_epoch_id.clone(), &prev_hash)?
@@ -427,9 +465,15 @@ impl Client {
         // TODO(2445): Enable challenges when they are working correctly.
         // let challenges = self.challenges.drain().map(|(_, challenge)| challenge).collect();
         let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&next_epoch_id)?;
+        let latest_protocol_version = get_protocol_version(
+            protocol_version,
+            self.config.protocol_upgrade_voting_schedule,
+            Utc::now(),
+        );
 
         let block = Block::produce(
             protocol_version,
+            latest_protocol_version,
             &prev_header,
             next_height,
             chunks,
@@ -447,12 +491,21 @@ impl Client {
             block_merkle_root,
         );
 
+        let block_size = block.try_to_vec().expect("Failed to serialize").len() as u64;
+        if block_size > max_block_size {
+            return Err(Error::BlockProducer(format!(
+                "Block at height {} has size {} which exceeds max block size {}",
+                next_height, block_size, max_block_size
+            )));
+        }
+
         // Update latest known even before returning block out, to prevent race conditions.
         self.chain.mut_store().save_latest_known(LatestKnown {
             height: next_height,
             seen: to_timestamp(Utc::now()),
         })?;
 
+        near_metrics::inc_counter(&metrics::BLOCK_PRODUCED_TOTAL);
         Ok(Some(block))
     }
 
@@ -503,6 +556,13 @@ impl Client {
             .map_err(|err| Error::ChunkProducer(format!("No chunk extra available: {}", err)))?
             .clone();
 
+        // Gas limit is defined by the protocol version of the block that will include the chunk.
+        let next_epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&prev_block_hash)?;
+        let gas_limit = self
+            .chain
+            .block_economics_config
+            .gas_limit(self.runtime_adapter.get_epoch_protocol_version(&next_epoch_id)?);
+
         let prev_block_header = self.chain.get_block_header(&prev_block_hash)?.clone();
         let transactions = self.prepare_transactions(shard_id, &chunk_extra, &prev_block_header)?;
         let num_filtered_transactions = transactions.len();
@@ -537,7 +597,7 @@ impl Client {
             next_height,
             shard_id,
             chunk_extra.gas_used,
-            chunk_extra.gas_limit,
+            gas_limit,
             chunk_extra.balance_burnt,
             chunk_extra.validator_proposals,
             transactions,
@@ -560,7 +620,7 @@ impl Client {
             encoded_chunk.chunk_hash().0,
         );
 
-        near_metrics::inc_counter(&metrics::BLOCK_PRODUCED_TOTAL);
+        near_metrics::inc_counter(&metrics::CHUNK_PRODUCED_TOTAL);
         Ok(Some((encoded_chunk, merkle_paths, outgoing_receipts)))
     }
 
@@ -579,21 +639,32 @@ impl Client {
 
         let transactions = if let Some(mut iter) = shards_mgr.get_pool_iterator(shard_id) {
             let transaction_validity_period = chain.transaction_validity_period;
+            // Idempotency keys of the transactions already included into the chunk.
+            let mut idempotency_keys = HashSet::new();
             runtime_adapter.prepare_transactions(
                 prev_block_header.gas_price(),
-                chunk_extra.gas_limit,
+                chain.block_economics_config.gas_limit(protocol_version),
                 shard_id,
                 chunk_extra.state_root.clone(),
                 &mut iter,
                 &mut |tx: &SignedTransaction| -> bool {
-                    chain
-                        .mut_store()
+                    let store = chain.mut_store();
+                    if store
                         .check_transaction_validity_period(
                             &prev_block_header,
                             &tx.transaction.block_hash,
                             transaction_validity_period,
                         )
-                        .is_ok()
+                        .is_err()
+                        || store.check_idempotency_key(&prev_block_header, tx).is_err()
+                    {
+                        return false;
+                    }
+                    match tx.transaction.idempotency_key() {
+                        Some(key) => idempotency_keys
+                            .insert((tx.transaction.signer_id.clone(), key.to_vec())),
+                        None => true,
+                    }
                 },
                 protocol_version,
             )?
@@ -839,6 +910,15 @@ impl Client {
         Ok(())
     }
 
+    /// Rewinds the head by up to `num_blocks` blocks, but not below the last final block, which
+    /// sync then applies again. See `Chain::undo_blocks`.
+    pub fn undo_blocks(&mut self, num_blocks: NumBlocks) -> Result<Tip, Error> {
+        let tries = self.runtime_adapter.get_tries();
+        let head = self.chain.undo_blocks(tries, num_blocks, false)?;
+        self.check_and_update_doomslug_tip()?;
+        Ok(head)
+    }
+
     /// Checks if the latest hash known to Doomslug matches the current head, and updates it if not.
     pub fn check_and_update_doomslug_tip(&mut self) -> Result<(), Error> {
         let tip = self.chain.head()?;
@@ -870,6 +950,8 @@ impl Client {
         approval: Approval,
     ) -> Result<(), Error> {
         let next_epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(parent_hash)?;
+        #[cfg(feature = "protocol_feature_pq_crypto")]
+        self.measure_hybrid_approval_signature(&next_epoch_id, &approval)?;
         let next_block_producer =
             self.runtime_adapter.get_block_producer(&next_epoch_id, approval.target_height)?;
         if Some(&next_block_producer) == self.validator_signer.as_ref().map(|x| x.validator_id()) {
@@ -883,6 +965,40 @@ impl Client {
         Ok(())
     }
 
+    /// Signs the approval with a hybrid post-quantum signature and verifies it, recording time and
+    /// size in metrics. The signature is not sent, so approvals on the wire stay the same.
+    #[cfg(feature = "protocol_feature_pq_crypto")]
+    fn measure_hybrid_approval_signature(
+        &self,
+        epoch_id: &EpochId,
+        approval: &Approval,
+    ) -> Result<(), Error> {
+        lazy_static! {
+            static ref HYBRID_SECRET_KEY: HybridSecretKey = HybridSecretKey::from_random();
+        }
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(epoch_id)?;
+        if !checked_feature!(
+            "protocol_feature_pq_crypto",
+            HybridApprovalSignature,
+            protocol_version
+        ) {
+            return Ok(());
+        }
+        let data = Approval::get_data_for_sig(&approval.inner, approval.target_height);
+        let timer = near_metrics::start_timer(&metrics::HYBRID_APPROVAL_SIGN_TIME);
+        let signature = HYBRID_SECRET_KEY.sign(&data);
+        near_metrics::stop_timer(timer);
+        let timer = near_metrics::start_timer(&metrics::HYBRID_APPROVAL_VERIFY_TIME);
+        let is_valid = signature.verify(&data, HYBRID_SECRET_KEY.public_key());
+        near_metrics::stop_timer(timer);
+        debug_assert!(is_valid);
+        near_metrics::set_gauge(
+            &metrics::HYBRID_APPROVAL_SIGNATURE_SIZE,
+            signature.try_to_vec().expect("Failed to serialize").len() as i64,
+        );
+        Ok(())
+    }
+
     /// Gets called when block got accepted.
     /// Send updates over network, update tx pool and notify ourselves if it's time to produce next block.
     /// Blocks are passed in no particular order.
@@ -925,17 +1041,25 @@ impl Client {
 
         if status.is_new_head() {
             self.shards_mgr.update_largest_seen_height(block.header().height());
-            if !self.config.archive {
+            // Archival nodes with a cold store collect garbage too, the old data is moved to the
+            // cold store instead of being removed.
+            if (!self.config.archive || self.config.cold_store_path.is_some()) && !self.gc_paused {
                 let timer = near_metrics::start_timer(&metrics::GC_TIME);
-                if let Err(err) = self
-                    .chain
-                    .clear_data(self.runtime_adapter.get_tries(), self.config.gc_blocks_limit)
-                {
+                if let Err(err) = self.chain.clear_data_within(
+                    self.runtime_adapter.get_tries(),
+                    self.config.gc_blocks_limit,
+                    Some(self.config.gc_step_time_limit),
+                ) {
                     error!(target: "client", "Can't clear old data, {:?}", err);
                     debug_assert!(false);
                 };
                 near_metrics::stop_timer(timer);
             }
+            if !self.config.watched_accounts.is_empty() {
+                if let Err(err) = self.chain.index_watched_accounts(&self.config.watched_accounts) {
+                    error!(target: "client", "Can't index watched accounts, {:?}", err);
+                }
+            }
         }
 
         if let Some(validator_signer) = self.validator_signer.clone() {
@@ -1088,6 +1212,13 @@ impl Client {
         }, |missing_chunks| blocks_missing_chunks.write().unwrap().push(missing_chunks), |challenge| challenges.write().unwrap().push(challenge));
         self.send_challenges(challenges);
 
+        // The blocks still missing chunks apply the chunks that arrived so far.
+        if let Err(err) =
+            self.chain.apply_chunks_optimistically(&me.cloned(), &last_accepted_block_hash)
+        {
+            debug!(target: "client", "Failed to apply chunks optimistically: {}", err);
+        }
+
         self.shards_mgr.request_chunks(
             blocks_missing_chunks.write().unwrap().drain(..).flatten(),
             &self
@@ -1101,6 +1232,11 @@ impl Client {
         unwrapped_accepted_blocks
     }
 
+    /// Number of blocks for which approvals were received before the block itself.
+    pub fn num_pending_approvals(&self) -> usize {
+        self.pending_approvals.cache_size()
+    }
+
     pub fn is_validator(&self, epoch_id: &EpochId, block_hash: &CryptoHash) -> bool {
         match self.validator_signer.as_ref() {
             None => false,
@@ -1119,6 +1255,26 @@ impl Client {
         }
     }
 
+    /// Whether this node produces blocks in the epoch. Chunk-only producers are validators that
+    /// neither produce nor approve blocks.
+    pub fn is_block_producer(&self, epoch_id: &EpochId, block_hash: &CryptoHash) -> bool {
+        match self.validator_signer.as_ref() {
+            None => false,
+            Some(signer) => {
+                match self.runtime_adapter.get_epoch_block_producers_ordered(epoch_id, block_hash) {
+                    Ok(block_producers) => {
+                        block_producers.iter().any(|(validator_stake, is_slashed)| {
+                            !is_slashed
+                                && &validator_stake.account_id == signer.validator_id()
+                                && validator_stake.public_key == signer.public_key()
+                        })
+                    }
+                    Err(_) => false,
+                }
+            }
+        }
+    }
+
     fn handle_process_approval_error(
         &mut self,
         approval: &Approval,
@@ -1377,6 +1533,10 @@ impl Client {
             debug!(target: "client", "Invalid tx: expired or from a different fork -- {:?}", tx);
             return Ok(NetworkClientResponses::InvalidTx(e));
         }
+        if let Err(e) = self.chain.mut_store().check_idempotency_key(&cur_block_header, tx) {
+            debug!(target: "client", "Invalid tx: idempotency key was already used -- {:?}", tx);
+            return Ok(NetworkClientResponses::InvalidTx(e));
+        }
         let gas_price = cur_block_header.gas_price();
         let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
 
@@ -1493,11 +1653,12 @@ impl Client {
         for (sync_hash, state_sync_info) in self.chain.store().iterate_state_sync_infos() {
             assert_eq!(sync_hash, state_sync_info.epoch_tail_hash);
             let network_adapter1 = self.network_adapter.clone();
+            let external_state_sync = self.external_state_sync.clone();
 
-            let (state_sync, new_shard_sync) = self
-                .catchup_state_syncs
-                .entry(sync_hash)
-                .or_insert_with(|| (StateSync::new(network_adapter1), HashMap::new()));
+            let (state_sync, new_shard_sync) =
+                self.catchup_state_syncs.entry(sync_hash).or_insert_with(|| {
+                    (StateSync::new(network_adapter1, external_state_sync), HashMap::new())
+                });
 
             debug!(
                 target: "client",
@@ -1,11 +1,14 @@
 //! Client actor orchestrates Client and facilitates network connection.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler};
+use actix::{
+    Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message, ResponseFuture, SyncArbiter,
+};
+use cached::{Cached, SizedCache};
 use chrono::Duration as OldDuration;
 use chrono::{DateTime, Utc};
 use log::{debug, error, info, trace, warn};
@@ -13,12 +16,12 @@ use log::{debug, error, info, trace, warn};
 #[cfg(feature = "delay_detector")]
 use delay_detector::DelayDetector;
 use near_chain::test_utils::format_hash;
-use near_chain::types::AcceptedBlock;
+use near_chain::types::{AcceptedBlock, BlockStatus};
 #[cfg(feature = "adversarial")]
 use near_chain::StoreValidator;
 use near_chain::{
-    byzantine_assert, Block, BlockHeader, ChainGenesis, ChainStoreAccess, Provenance,
-    RuntimeAdapter,
+    byzantine_assert, Block, BlockHeader, ChainGenesis, ChainStoreAccess, Provenance, ReorgAdapter,
+    ReorgEvent, RuntimeAdapter,
 };
 use near_chain_configs::ClientConfig;
 #[cfg(feature = "adversarial")]
@@ -30,26 +33,43 @@ use near_network::recorder::MetricRecorder;
 use near_network::types::NetworkAdversarialMessage;
 use near_network::types::{NetworkInfo, ReasonForBan};
 use near_network::{
-    NetworkAdapter, NetworkClientMessages, NetworkClientResponses, NetworkRequests,
+    Misbehavior, NetworkAdapter, NetworkClientMessages, NetworkClientResponses, NetworkRequests,
+    NetworkResponses,
 };
+use near_primitives::block::Tip;
+use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{BlockHeight, EpochId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::from_timestamp;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::PROTOCOL_VERSION;
-use near_primitives::views::ValidatorInfo;
+use near_primitives::views::{
+    DebugStatusView, KnownPeerView, PeerAccessView, PeerReputationView, RoutingInfoView,
+    ValidatorInfo,
+};
 #[cfg(feature = "adversarial")]
 use near_store::ColBlock;
 use near_telemetry::TelemetryActor;
 
+use crate::alerts::AlertMonitor;
 use crate::client::Client;
+use crate::deprecation::deprecated_usage;
+use crate::external_state::ExternalStateSync;
+use crate::header_verifier::{HeaderVerifier, VerifiedBlockHeaders, VerifyBlockHeaders};
 use crate::info::{InfoHelper, ValidatorInfoHelper};
+use crate::metrics;
 use crate::sync::{highest_height_peer, StateSync, StateSyncResult};
+use crate::tx_signature_verifier::{
+    TransactionSignatureVerifier, VerifyTransactionSignature, TX_SIGNATURE_VERIFIER_THREADS,
+};
 use crate::types::{
-    Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload, ShardSyncStatus, Status,
-    StatusSyncInfo, SyncStatus,
+    CheckReadiness, Error, GetDebugStatus, GetNetworkInfo, GetPeerAccess, GetPeerReputation,
+    GetPeerStore, GetRoutingInfo, NetworkInfoResponse, SetGCPaused, ShardSyncDownload,
+    ShardSyncStatus, Status, StatusSyncInfo, SubscribeReorgs, SyncStatus, UndoBlocks,
+    UpdatePeerAccess,
 };
 #[cfg(feature = "adversarial")]
 use crate::AdversarialControls;
@@ -63,6 +83,11 @@ const BLOCK_HORIZON: u64 = 500;
 /// `max_block_production_time` times this multiplier is how long we wait before rebroadcasting
 /// the current `head`
 const HEAD_STALL_MULTIPLIER: u32 = 4;
+/// The missing ancestor of orphans is requested at most this often, however many of its
+/// descendants arrive.
+const ORPHAN_ANCESTOR_REQUEST_PERIOD: Duration = Duration::from_secs(2);
+/// Missing ancestors remembered as requested.
+const ORPHAN_ANCESTOR_REQUESTS_CACHE_SIZE: usize = 128;
 
 pub struct ClientActor {
     /// Adversarial controls
@@ -79,6 +104,8 @@ pub struct ClientActor {
     last_validator_announce_time: Option<Instant>,
     /// Info helper.
     info_helper: InfoHelper,
+    /// Detects critical events for the alert webhooks.
+    alert_monitor: AlertMonitor,
 
     /// Last time handle_block_production method was called
     block_production_next_attempt: DateTime<Utc>,
@@ -86,6 +113,14 @@ pub struct ClientActor {
     doomslug_timer_next_attempt: DateTime<Utc>,
     chunk_request_retry_next_attempt: DateTime<Utc>,
     sync_started: bool,
+    /// When the missing ancestors of orphans were last requested.
+    orphan_ancestor_requests: SizedCache<CryptoHash, Instant>,
+    /// Checks the received block headers before they are processed, started with the actor.
+    header_verifier: Option<Addr<HeaderVerifier>>,
+    /// Verifies the signatures of the received transactions, started with the actor.
+    tx_signature_verifier: Option<Addr<TransactionSignatureVerifier>>,
+    /// Notified of every switch of the head to another fork.
+    reorg_adapters: Vec<Arc<dyn ReorgAdapter>>,
 }
 
 /// Blocks the program until given genesis time arrives.
@@ -124,6 +159,11 @@ impl ClientActor {
         if let Some(vs) = &validator_signer {
             info!(target: "client", "Starting validator node: {}", vs.validator_id());
         }
+        let alert_monitor = AlertMonitor::new(
+            telemetry_actor.clone(),
+            node_id.to_string(),
+            config.chain_id.clone(),
+        );
         let info_helper = InfoHelper::new(telemetry_actor, &config, validator_signer.clone());
         let client = Client::new(
             config,
@@ -149,17 +189,25 @@ impl ClientActor {
                 received_bytes_per_sec: 0,
                 sent_bytes_per_sec: 0,
                 known_producers: vec![],
+                peer_protocol_versions: BTreeMap::new(),
+                peer_clock_skews: HashMap::new(),
+                peer_transports: HashMap::new(),
                 #[cfg(feature = "metric_recorder")]
                 metric_recorder: MetricRecorder::default(),
                 peer_counter: 0,
             },
             last_validator_announce_time: None,
             info_helper,
+            alert_monitor,
             block_production_next_attempt: now,
             block_production_started: false,
             doomslug_timer_next_attempt: now,
             chunk_request_retry_next_attempt: now,
             sync_started: false,
+            orphan_ancestor_requests: SizedCache::with_size(ORPHAN_ANCESTOR_REQUESTS_CACHE_SIZE),
+            header_verifier: None,
+            tx_signature_verifier: None,
+            reorg_adapters: vec![],
         })
     }
 }
@@ -168,6 +216,29 @@ impl Actor for ClientActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        let runtime_adapter = self.client.runtime_adapter.clone();
+        let network_adapter = self.network_adapter.clone();
+        let client = ctx.address().recipient();
+        let trusted_checkpoint = self.client.config.trusted_checkpoint.clone();
+        self.header_verifier = Some(SyncArbiter::start(1, move || {
+            HeaderVerifier::new(
+                runtime_adapter.clone(),
+                network_adapter.clone(),
+                client.clone(),
+                trusted_checkpoint.clone(),
+            )
+        }));
+        let runtime_adapter = self.client.runtime_adapter.clone();
+        self.tx_signature_verifier =
+            Some(SyncArbiter::start(TX_SIGNATURE_VERIFIER_THREADS, move || {
+                TransactionSignatureVerifier::new(runtime_adapter.clone())
+            }));
+        if let Some(location) = &self.client.config.state_sync_external {
+            info!(target: "client", "Downloading state from {:?} during state sync", location);
+            let external_state_sync = ExternalStateSync::new(location, ctx.address().recipient());
+            self.client.set_external_state_sync(external_state_sync);
+        }
+
         // Start syncing job.
         self.start_sync(ctx);
 
@@ -187,10 +258,64 @@ impl Actor for ClientActor {
     }
 }
 
-impl Handler<NetworkClientMessages> for ClientActor {
+/// Transaction whose signature was checked by the `TransactionSignatureVerifier`.
+struct VerifiedTransaction {
+    transaction: SignedTransaction,
+    is_forwarded: bool,
+    check_only: bool,
+}
+
+impl Message for VerifiedTransaction {
     type Result = NetworkClientResponses;
+}
+
+impl Handler<VerifiedTransaction> for ClientActor {
+    type Result = NetworkClientResponses;
+
+    fn handle(&mut self, msg: VerifiedTransaction, _ctx: &mut Context<Self>) -> Self::Result {
+        self.client.process_tx(msg.transaction, msg.is_forwarded, msg.check_only)
+    }
+}
+
+impl Handler<NetworkClientMessages> for ClientActor {
+    type Result = ResponseFuture<NetworkClientResponses>;
 
     fn handle(&mut self, msg: NetworkClientMessages, ctx: &mut Context<Self>) -> Self::Result {
+        match (msg, &self.tx_signature_verifier) {
+            (
+                NetworkClientMessages::Transaction { transaction, is_forwarded, check_only },
+                Some(verifier),
+            ) => {
+                // The signature is verified on the verifier threads, the client actor then only
+                // checks the transaction against the state with the signature already cached.
+                let verifier = verifier.clone();
+                let client = ctx.address();
+                Box::pin(async move {
+                    let is_valid =
+                        verifier.send(VerifyTransactionSignature(transaction.clone())).await;
+                    if let Ok(false) = is_valid {
+                        return NetworkClientResponses::InvalidTx(InvalidTxError::InvalidSignature);
+                    }
+                    client
+                        .send(VerifiedTransaction { transaction, is_forwarded, check_only })
+                        .await
+                        .unwrap_or(NetworkClientResponses::NoResponse)
+                })
+            }
+            (msg, _) => {
+                let response = self.handle_client_message(msg, ctx);
+                Box::pin(async move { response })
+            }
+        }
+    }
+}
+
+impl ClientActor {
+    fn handle_client_message(
+        &mut self,
+        msg: NetworkClientMessages,
+        ctx: &mut Context<Self>,
+    ) -> NetworkClientResponses {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new(format!("NetworkClientMessage {}", msg.as_ref()).into());
         self.check_triggers(ctx);
@@ -333,11 +458,29 @@ impl Handler<NetworkClientMessages> for ClientActor {
                 }
             }
             NetworkClientMessages::BlockHeaders(headers, peer_id) => {
-                if self.receive_headers(headers, peer_id) {
-                    NetworkClientResponses::NoResponse
-                } else {
-                    warn!(target: "client", "Banning node for sending invalid block headers");
-                    NetworkClientResponses::Ban { ban_reason: ReasonForBan::BadBlockHeader }
+                if let Err(err) =
+                    self.client.header_sync.received_headers(&self.client.chain, &headers, &peer_id)
+                {
+                    error!(target: "client", "Failed to request the next block headers: {}", err);
+                }
+                let header_verifier = self.header_verifier.as_ref();
+                match (header_verifier, self.client.chain.header_head()) {
+                    (Some(header_verifier), Ok(header_head)) => {
+                        header_verifier.do_send(VerifyBlockHeaders {
+                            headers,
+                            peer_id,
+                            last_known_hash: header_head.last_block_hash,
+                        });
+                        NetworkClientResponses::NoResponse
+                    }
+                    _ => {
+                        if self.receive_headers(headers, peer_id) {
+                            NetworkClientResponses::NoResponse
+                        } else {
+                            warn!(target: "client", "Banning node for sending invalid block headers");
+                            NetworkClientResponses::Ban { ban_reason: ReasonForBan::BadBlockHeader }
+                        }
+                    }
                 }
             }
             NetworkClientMessages::BlockApproval(approval, peer_id) => {
@@ -585,10 +728,45 @@ impl Handler<Status> for ClientActor {
                 syncing: self.client.sync_status.is_syncing(),
             },
             validator_account_id,
+            deprecated_usage: deprecated_usage(),
         })
     }
 }
 
+impl Handler<CheckReadiness> for ClientActor {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: CheckReadiness, ctx: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new("client check readiness".into());
+        self.check_triggers(ctx);
+
+        let head = self
+            .client
+            .chain
+            .head()
+            .map_err(|err| format!("Failed to read the head from the database: {}", err))?;
+        if self.network_info.num_active_peers < msg.min_peers {
+            return Err(format!(
+                "Connected to {} peers, {} required.",
+                self.network_info.num_active_peers, msg.min_peers
+            ));
+        }
+        let highest_peer_height = self
+            .network_info
+            .highest_height_peers
+            .iter()
+            .map(|peer| peer.chain_info.height)
+            .max()
+            .unwrap_or(head.height);
+        let blocks_behind = highest_peer_height.saturating_sub(head.height);
+        if blocks_behind > msg.max_blocks_behind {
+            return Err(format!("Head is {} blocks behind the peers.", blocks_behind));
+        }
+        Ok(())
+    }
+}
+
 impl Handler<GetNetworkInfo> for ClientActor {
     type Result = Result<NetworkInfoResponse, String>;
 
@@ -610,12 +788,200 @@ impl Handler<GetNetworkInfo> for ClientActor {
             sent_bytes_per_sec: self.network_info.sent_bytes_per_sec,
             received_bytes_per_sec: self.network_info.received_bytes_per_sec,
             known_producers: self.network_info.known_producers.clone(),
+            peer_protocol_versions: self.network_info.peer_protocol_versions.clone(),
             #[cfg(feature = "metric_recorder")]
             metric_recorder: self.network_info.metric_recorder.clone(),
         })
     }
 }
 
+impl Handler<GetRoutingInfo> for ClientActor {
+    type Result = Result<RoutingInfoView, String>;
+
+    fn handle(&mut self, _: GetRoutingInfo, ctx: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new("client get routing info".into());
+        self.check_triggers(ctx);
+
+        let head = self.client.chain.head().map_err(|err| err.to_string())?;
+        let header = self
+            .client
+            .chain
+            .get_block_header(&head.last_block_hash)
+            .map_err(|err| err.to_string())?;
+        let latest_block_time = from_timestamp(header.raw_timestamp());
+        let me = self.client.validator_signer.as_ref().map(|vs| vs.validator_id());
+        let runtime_adapter = &self.client.runtime_adapter;
+        let num_shards = runtime_adapter.num_shards();
+        let tracked_shards = (0..num_shards)
+            .filter(|&shard_id| {
+                runtime_adapter.cares_about_shard(me, &head.last_block_hash, shard_id, true)
+            })
+            .collect();
+
+        Ok(RoutingInfoView {
+            chain_id: self.client.config.chain_id.clone(),
+            num_shards,
+            tracked_shards,
+            latest_block_hash: head.last_block_hash,
+            latest_block_height: head.height,
+            latest_block_time,
+            syncing: self.client.sync_status.is_syncing(),
+            sync_status: self.client.sync_status.as_variant_name().to_string(),
+        })
+    }
+}
+
+impl Handler<GetDebugStatus> for ClientActor {
+    type Result = Result<DebugStatusView, String>;
+
+    fn handle(&mut self, _: GetDebugStatus, ctx: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new("client get debug status".into());
+        self.check_triggers(ctx);
+
+        let head = self.client.chain.head().map_err(|err| err.to_string())?;
+        let (sync_current_height, sync_highest_height) = match self.client.sync_status {
+            SyncStatus::HeaderSync { current_height, highest_height }
+            | SyncStatus::BodySync { current_height, highest_height } => {
+                (Some(current_height), Some(highest_height))
+            }
+            _ => (None, None),
+        };
+        let mut state_sync_shards: Vec<_> = match &self.client.sync_status {
+            SyncStatus::StateSync(_, shards) => shards
+                .iter()
+                .map(|(shard_id, download)| (*shard_id, format!("{:?}", download.status)))
+                .collect(),
+            _ => vec![],
+        };
+        state_sync_shards.sort();
+        let mut catchup_blocks: Vec<_> = self.client.catchup_state_syncs.keys().cloned().collect();
+        catchup_blocks.sort();
+
+        Ok(DebugStatusView {
+            sync_status: self.client.sync_status.as_variant_name().to_string(),
+            sync_current_height,
+            sync_highest_height,
+            state_sync_shards,
+            head_hash: head.last_block_hash,
+            head_height: head.height,
+            orphans: self.client.chain.debug_orphans(),
+            orphans_evicted: self.client.chain.orphans_evicted_len() as u64,
+            blocks_with_missing_chunks: self.client.chain.debug_blocks_with_missing_chunks(),
+            num_requested_chunks: self.client.shards_mgr.num_requested_chunks() as u64,
+            catchup_blocks,
+            num_pending_approvals: self.client.num_pending_approvals() as u64,
+            num_challenges: self.client.challenges.len() as u64,
+        })
+    }
+}
+
+impl Handler<GetPeerStore> for ClientActor {
+    type Result = ResponseFuture<Result<Vec<KnownPeerView>, String>>;
+
+    fn handle(&mut self, _: GetPeerStore, _: &mut Context<Self>) -> Self::Result {
+        let response = self.network_adapter.send(NetworkRequests::FetchPeerStore);
+        Box::pin(async move {
+            match response.await {
+                Ok(NetworkResponses::PeerStore(peers)) => Ok(peers),
+                Ok(_) => Err("Peer store is not available".to_string()),
+                Err(err) => Err(err.to_string()),
+            }
+        })
+    }
+}
+
+impl Handler<GetPeerReputation> for ClientActor {
+    type Result = ResponseFuture<Result<Vec<PeerReputationView>, String>>;
+
+    fn handle(&mut self, _: GetPeerReputation, _: &mut Context<Self>) -> Self::Result {
+        let response = self.network_adapter.send(NetworkRequests::FetchPeerReputation);
+        Box::pin(async move {
+            match response.await {
+                Ok(NetworkResponses::PeerReputation(peers)) => Ok(peers),
+                Ok(_) => Err("Peer reputation is not available".to_string()),
+                Err(err) => Err(err.to_string()),
+            }
+        })
+    }
+}
+
+impl Handler<GetPeerAccess> for ClientActor {
+    type Result = ResponseFuture<Result<PeerAccessView, String>>;
+
+    fn handle(&mut self, _: GetPeerAccess, _: &mut Context<Self>) -> Self::Result {
+        let response = self.network_adapter.send(NetworkRequests::FetchPeerAccess);
+        Box::pin(async move {
+            match response.await {
+                Ok(NetworkResponses::PeerAccess(access)) => Ok(access),
+                Ok(_) => Err("Peer access is not available".to_string()),
+                Err(err) => Err(err.to_string()),
+            }
+        })
+    }
+}
+
+impl Handler<UpdatePeerAccess> for ClientActor {
+    type Result = ResponseFuture<Result<PeerAccessView, String>>;
+
+    fn handle(&mut self, msg: UpdatePeerAccess, _: &mut Context<Self>) -> Self::Result {
+        let response = self.network_adapter.send(NetworkRequests::UpdatePeerAccess(msg.0));
+        Box::pin(async move {
+            match response.await {
+                Ok(NetworkResponses::PeerAccess(access)) => Ok(access),
+                Ok(_) => Err("Peer access is not available".to_string()),
+                Err(err) => Err(err.to_string()),
+            }
+        })
+    }
+}
+
+impl Handler<SetGCPaused> for ClientActor {
+    type Result = Result<bool, String>;
+
+    fn handle(&mut self, msg: SetGCPaused, _: &mut Context<Self>) -> Self::Result {
+        if self.client.gc_paused != msg.0 {
+            info!(target: "client", "Garbage collection {}", if msg.0 { "paused" } else { "resumed" });
+        }
+        self.client.gc_paused = msg.0;
+        near_metrics::set_gauge(&metrics::GC_PAUSED, msg.0 as i64);
+        Ok(self.client.gc_paused)
+    }
+}
+
+impl Handler<UndoBlocks> for ClientActor {
+    type Result = Result<Tip, String>;
+
+    fn handle(&mut self, msg: UndoBlocks, _: &mut Context<Self>) -> Self::Result {
+        warn!(target: "client", "Undoing up to {} blocks", msg.0);
+        self.client.undo_blocks(msg.0).map_err(|err| err.to_string())
+    }
+}
+
+impl Handler<SubscribeReorgs> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeReorgs, _: &mut Context<Self>) {
+        self.reorg_adapters.push(msg.0);
+    }
+}
+
+impl Handler<VerifiedBlockHeaders> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: VerifiedBlockHeaders, _: &mut Context<Self>) {
+        let VerifiedBlockHeaders { headers, peer_id } = msg;
+        if !self.receive_headers(headers, peer_id.clone()) {
+            warn!(target: "client", "Banning node for sending invalid block headers");
+            self.network_adapter.do_send(NetworkRequests::BanPeer {
+                peer_id,
+                ban_reason: ReasonForBan::BadBlockHeader,
+            });
+        }
+    }
+}
+
 impl ClientActor {
     fn sign_announce_account(&self, epoch_id: &EpochId) -> Result<Signature, ()> {
         if let Some(validator_signer) = self.client.validator_signer.as_ref() {
@@ -803,6 +1169,9 @@ impl ClientActor {
         let _ = self.client.check_and_update_doomslug_tip();
 
         let approvals = self.client.doomslug.process_timer(Instant::now());
+        if !approvals.is_empty() {
+            self.log_peer_clock_skews();
+        }
 
         // Important to save the largest approval target height before sending approvals, so
         // that if the node crashes in the meantime, we cannot get slashed on recovery
@@ -813,8 +1182,8 @@ impl ClientActor {
         match chain_store_update.commit() {
             Ok(_) => {
                 let head = unwrap_or_return!(self.client.chain.head());
-                if self.client.is_validator(&head.epoch_id, &head.last_block_hash)
-                    || self.client.is_validator(&head.next_epoch_id, &head.last_block_hash)
+                if self.client.is_block_producer(&head.epoch_id, &head.last_block_hash)
+                    || self.client.is_block_producer(&head.next_epoch_id, &head.last_block_hash)
                 {
                     for approval in approvals {
                         if let Err(e) =
@@ -829,6 +1198,19 @@ impl ClientActor {
         };
     }
 
+    /// Logs how far the clocks of the peers are from ours. Approvals and blocks of peers with
+    /// skewed clocks arrive early or late compared to the doomslug timers.
+    fn log_peer_clock_skews(&self) {
+        let mut skews: Vec<i64> = self.network_info.peer_clock_skews.values().cloned().collect();
+        if skews.is_empty() {
+            return;
+        }
+        skews.sort();
+        let median = skews[skews.len() / 2];
+        let largest = skews.iter().cloned().max_by_key(|skew| skew.abs()).unwrap_or_default();
+        debug!(target: "client", "Doomslug timer, peer clock skew median {}ms, largest {}ms over {} peers", median, largest, skews.len());
+    }
+
     /// Produce block if we are block producer for given `next_height` height.
     /// Can return error, should be called with `produce_block` to handle errors and reschedule.
     fn produce_block(&mut self, next_height: BlockHeight) -> Result<(), Error> {
@@ -878,9 +1260,50 @@ impl ClientActor {
         }
     }
 
+    /// Reports the switch of the head to another fork to the log, the metrics, the alerts and
+    /// the subscribed adapters. Abandoned blocks are counted by their producers.
+    fn on_reorg(&mut self, reorg: ReorgEvent) {
+        info!(target: "client", "Reorg from {} to {}, common ancestor {}, depth {}, abandoned blocks {:?}", reorg.old_head, reorg.new_head, reorg.common_ancestor, reorg.depth, reorg.abandoned_blocks);
+        near_metrics::inc_counter(&metrics::REORGS_TOTAL);
+        near_metrics::observe(&metrics::REORG_DEPTH, reorg.depth as f64);
+        for block_hash in reorg.abandoned_blocks.iter() {
+            let chain = &mut self.client.chain;
+            let runtime_adapter = &self.client.runtime_adapter;
+            let producer = chain.get_block_header(block_hash).and_then(|header| {
+                runtime_adapter.get_block_producer(header.epoch_id(), header.height())
+            });
+            if let Ok(producer) = producer {
+                near_metrics::inc_counter_vec(
+                    &metrics::ABANDONED_BLOCKS_TOTAL,
+                    &[producer.as_str()],
+                );
+            }
+        }
+
+        let old_head_height =
+            self.client.chain.get_block_header(&reorg.old_head).map(|header| header.height());
+        let new_head_height =
+            self.client.chain.get_block_header(&reorg.new_head).map(|header| header.height());
+        if let (Ok(new_head_height), Ok(old_head_height)) = (new_head_height, old_head_height) {
+            self.alert_monitor.fork_detected(new_head_height, old_head_height, reorg.depth);
+        }
+
+        for adapter in self.reorg_adapters.iter() {
+            adapter.on_reorg(&reorg);
+        }
+    }
+
     /// Process all blocks that were accepted by calling other relevant services.
     fn process_accepted_blocks(&mut self, accepted_blocks: Vec<AcceptedBlock>) {
         for accepted_block in accepted_blocks {
+            if let BlockStatus::Reorg(prev_head) = &accepted_block.status {
+                match self.client.chain.get_reorg_event(prev_head, &accepted_block.hash) {
+                    Ok(reorg) => self.on_reorg(reorg),
+                    Err(err) => {
+                        error!(target: "client", "Failed to trace the reorg to {}: {}", accepted_block.hash, err)
+                    }
+                }
+            }
             self.client.on_block_accepted(
                 accepted_block.hash,
                 accepted_block.status,
@@ -955,6 +1378,10 @@ impl ClientActor {
             Ok(_) => {}
             Err(ref err) if err.is_bad_data() => {
                 warn!(target: "client", "receive bad block: {}", err);
+                self.network_adapter.do_send(NetworkRequests::ReportMisbehavior {
+                    peer_id,
+                    misbehavior: Misbehavior::InvalidBlock,
+                });
             }
             Err(ref err) if err.is_error() => {
                 if self.client.sync_status.is_syncing() {
@@ -966,11 +1393,7 @@ impl ClientActor {
                 }
             }
             Err(e) => match e.kind() {
-                near_chain::ErrorKind::Orphan => {
-                    if !self.client.chain.is_orphan(&prev_hash) {
-                        self.request_block_by_hash(prev_hash, peer_id)
-                    }
-                }
+                near_chain::ErrorKind::Orphan => self.request_orphan_ancestor(prev_hash, peer_id),
                 near_chain::ErrorKind::ChunksMissing(missing_chunks) => {
                     debug!(
                         target: "client",
@@ -996,6 +1419,9 @@ impl ClientActor {
                         protocol_version,
                     );
                 }
+                near_chain::ErrorKind::UnsupportedProtocolVersion(_, _) => {
+                    error!(target: "client", "Block {} refused by chain: {}", hash, e.kind());
+                }
                 _ => {
                     debug!(target: "client", "Process block: block {} refused by chain: {}", hash, e.kind());
                 }
@@ -1022,6 +1448,22 @@ impl ClientActor {
         }
     }
 
+    /// Requests the first missing block before an orphan from the peer that sent it, unless it
+    /// was just requested for another orphan.
+    fn request_orphan_ancestor(&mut self, prev_hash: CryptoHash, peer_id: PeerId) {
+        let ancestor = self.client.chain.orphan_missing_ancestor(&prev_hash);
+        let now = Instant::now();
+        if let Some(requested) = self.orphan_ancestor_requests.cache_get(&ancestor) {
+            if now.saturating_duration_since(*requested) < ORPHAN_ANCESTOR_REQUEST_PERIOD {
+                return;
+            }
+        }
+        self.orphan_ancestor_requests.cache_set(ancestor, now);
+        debug!(target: "client", "Requesting missing ancestor {} of orphan with parent {} from {}", ancestor, prev_hash, peer_id);
+        near_metrics::inc_counter(&metrics::ORPHAN_ANCESTOR_REQUESTS_TOTAL);
+        self.request_block_by_hash(ancestor, peer_id)
+    }
+
     fn request_block_by_hash(&mut self, hash: CryptoHash, peer_id: PeerId) {
         match self.client.chain.block_exists(&hash) {
             Ok(false) => {
@@ -1346,6 +1788,44 @@ impl ClientActor {
         });
     }
 
+    /// Checks the state of the node for the alert webhooks.
+    fn check_alerts(&mut self, head: &Tip) {
+        let highest_peer_height = self
+            .network_info
+            .highest_height_peers
+            .iter()
+            .map(|peer| peer.chain_info.height)
+            .max()
+            .unwrap_or_default();
+        self.alert_monitor.check_sync_progress(head.height, highest_peer_height);
+        if let Some(db) = self.client.chain.store().store().get_rocksdb() {
+            self.alert_monitor.check_disk_space(db.path());
+        }
+        if self.client.sync_status.is_syncing() {
+            return;
+        }
+        let runtime_adapter = &self.client.runtime_adapter;
+        if let (Ok(current_protocol_version), Ok(next_protocol_version)) = (
+            runtime_adapter.get_epoch_protocol_version(&head.epoch_id),
+            runtime_adapter.get_epoch_protocol_version(&head.next_epoch_id),
+        ) {
+            self.alert_monitor
+                .check_protocol_upgrade(current_protocol_version, next_protocol_version);
+        }
+        let account_id = match &self.client.validator_signer {
+            Some(validator_signer) => validator_signer.validator_id(),
+            None => return,
+        };
+        if let Ok(validator_info) = runtime_adapter.get_validator_info(&head.last_block_hash) {
+            if let Some(info) =
+                validator_info.current_validators.iter().find(|info| &info.account_id == account_id)
+            {
+                self.alert_monitor
+                    .check_kickout_risk(info.num_produced_blocks, info.num_expected_blocks);
+            }
+        }
+    }
+
     /// Periodically log summary.
     fn log_summary(&self, ctx: &mut Context<Self>) {
         ctx.run_later(self.client.config.log_summary_period, move |act, ctx| {
@@ -1387,6 +1867,7 @@ impl ClientActor {
                 &act.network_info,
                 validator_info,
             );
+            act.check_alerts(&head);
 
             act.log_summary(ctx);
         });
@@ -0,0 +1,68 @@
+//! Tracks usage of the interfaces listed in `near_primitives::version::DEPRECATIONS`.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use log::warn;
+
+use near_primitives::version::{find_deprecation, DeprecatedSurface, Deprecation};
+use near_primitives::views::DeprecatedUsageView;
+
+use crate::metrics;
+
+lazy_static! {
+    static ref DEPRECATED_USAGE: Mutex<HashMap<(DeprecatedSurface, &'static str), u64>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Records that a deprecated interface was used. Warns in the log on the first use only, every
+/// use is counted in the metrics and in the `status` RPC. Names that are not in the deprecation
+/// table are ignored.
+pub fn report_deprecated_usage(surface: DeprecatedSurface, name: &str) {
+    let deprecation = match find_deprecation(surface, name) {
+        Some(deprecation) => deprecation,
+        None => return,
+    };
+    let count = {
+        let mut usage = DEPRECATED_USAGE.lock().expect("Lock is not poisoned");
+        let count = usage.entry((surface, deprecation.name)).or_insert(0);
+        *count += 1;
+        *count
+    };
+    near_metrics::inc_counter_vec(
+        &metrics::DEPRECATED_USAGE_TOTAL,
+        &[surface.as_str(), deprecation.name],
+    );
+    if count == 1 {
+        warn!(
+            target: "deprecation",
+            "Deprecated {} {} is used, it will be removed in protocol version {}, use {} instead",
+            surface.as_str(),
+            deprecation.name,
+            deprecation.removed_in_version,
+            deprecation.replacement
+        );
+    }
+}
+
+/// Deprecated interfaces used since the node started.
+pub fn deprecated_usage() -> Vec<DeprecatedUsageView> {
+    let usage = DEPRECATED_USAGE.lock().expect("Lock is not poisoned");
+    let mut result: Vec<_> = usage
+        .iter()
+        .filter_map(|((surface, name), count)| {
+            find_deprecation(*surface, name).map(|deprecation| usage_view(deprecation, *count))
+        })
+        .collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
+fn usage_view(deprecation: &Deprecation, count: u64) -> DeprecatedUsageView {
+    DeprecatedUsageView {
+        surface: deprecation.surface,
+        name: deprecation.name.to_string(),
+        removed_in_version: deprecation.removed_in_version,
+        replacement: deprecation.replacement.to_string(),
+        count,
+    }
+}
@@ -0,0 +1,231 @@
+//! State headers and parts downloaded from external storage instead of the peers.
+//!
+//! Nodes that dump their state publish, for every shard and sync hash, what a peer would send in
+//! a `StateResponse`:
+//!
+//! * `<sync_hash>/<shard_id>/header` is the borsh encoded `ShardStateSyncResponseHeader`,
+//! * `<sync_hash>/<shard_id>/part_<part_id>_of_<num_parts>` is the part as is.
+//!
+//! Downloaded objects are handed to the client as state responses, so they go through the same
+//! checks against the sync block and the state root as the ones of the peers, and a broken or
+//! missing object is requested again from a peer or the storage.
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::Recipient;
+use actix_web::client::Client;
+use borsh::BorshDeserialize;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use log::{debug, warn};
+
+use near_chain_configs::ExternalStorageLocation;
+use near_network::types::{StateResponseInfo, StateResponseInfoV2};
+use near_network::NetworkClientMessages;
+use near_primitives::hash::CryptoHash;
+use near_primitives::syncing::{
+    ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
+    ShardStateSyncResponseV2,
+};
+use near_primitives::types::ShardId;
+
+/// Timeout of a download from an HTTP mirror.
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest object downloaded from an HTTP mirror, parts are about a megabyte.
+const MAX_OBJECT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Storage that holds state dumps.
+pub trait ExternalStateSource: Send + Sync {
+    /// Downloads the object at the given path.
+    fn fetch(&self, path: String) -> LocalBoxFuture<'static, Result<Vec<u8>, String>>;
+}
+
+/// Directory on the local filesystem.
+pub struct FilesystemSource {
+    root_dir: PathBuf,
+}
+
+impl FilesystemSource {
+    pub fn new(root_dir: PathBuf) -> Self {
+        FilesystemSource { root_dir }
+    }
+}
+
+impl ExternalStateSource for FilesystemSource {
+    fn fetch(&self, path: String) -> LocalBoxFuture<'static, Result<Vec<u8>, String>> {
+        let path = self.root_dir.join(path);
+        async move { tokio::fs::read(&path).await.map_err(|err| format!("{:?}: {}", path, err)) }
+            .boxed_local()
+    }
+}
+
+/// HTTP mirror or the public endpoint of a bucket.
+pub struct HttpSource {
+    url: String,
+}
+
+impl HttpSource {
+    pub fn new(url: String) -> Self {
+        HttpSource { url: url.trim_end_matches('/').to_string() }
+    }
+}
+
+impl ExternalStateSource for HttpSource {
+    fn fetch(&self, path: String) -> LocalBoxFuture<'static, Result<Vec<u8>, String>> {
+        let url = format!("{}/{}", self.url, path);
+        async move {
+            let mut response = Client::default()
+                .get(&url)
+                .timeout(HTTP_REQUEST_TIMEOUT)
+                .send()
+                .await
+                .map_err(|err| format!("{}: {}", url, err))?;
+            if !response.status().is_success() {
+                return Err(format!("{}: {}", url, response.status()));
+            }
+            let body = response
+                .body()
+                .limit(MAX_OBJECT_SIZE)
+                .await
+                .map_err(|err| format!("{}: {}", url, err))?;
+            Ok(body.to_vec())
+        }
+        .boxed_local()
+    }
+}
+
+pub fn state_header_path(sync_hash: &CryptoHash, shard_id: ShardId) -> String {
+    format!("{}/{}/header", sync_hash, shard_id)
+}
+
+pub fn state_part_path(
+    sync_hash: &CryptoHash,
+    shard_id: ShardId,
+    part_id: u64,
+    num_parts: u64,
+) -> String {
+    format!("{}/{}/part_{}_of_{}", sync_hash, shard_id, part_id, num_parts)
+}
+
+/// Downloads state from external storage for state sync and the catchups.
+#[derive(Clone)]
+pub struct ExternalStateSync {
+    source: Arc<dyn ExternalStateSource>,
+    client: Recipient<NetworkClientMessages>,
+}
+
+impl ExternalStateSync {
+    pub fn new(
+        location: &ExternalStorageLocation,
+        client: Recipient<NetworkClientMessages>,
+    ) -> Self {
+        let source: Arc<dyn ExternalStateSource> = match location {
+            ExternalStorageLocation::Filesystem { root_dir } => {
+                Arc::new(FilesystemSource::new(root_dir.clone()))
+            }
+            ExternalStorageLocation::Http { url } => Arc::new(HttpSource::new(url.clone())),
+        };
+        ExternalStateSync { source, client }
+    }
+
+    /// Downloads the state header of the shard, `run_me` is set again if it fails.
+    pub fn request_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        run_me: Arc<AtomicBool>,
+    ) {
+        let client = self.client.clone();
+        let fetch = self.source.fetch(state_header_path(&sync_hash, shard_id));
+        actix::spawn(async move {
+            let header = fetch.await.and_then(|data| {
+                ShardStateSyncResponseHeader::try_from_slice(&data).map_err(|err| err.to_string())
+            });
+            let state_response = match header {
+                Ok(ShardStateSyncResponseHeader::V1(header)) => {
+                    ShardStateSyncResponse::V1(ShardStateSyncResponseV1 {
+                        header: Some(header),
+                        part: None,
+                    })
+                }
+                Ok(ShardStateSyncResponseHeader::V2(header)) => {
+                    ShardStateSyncResponse::V2(ShardStateSyncResponseV2 {
+                        header: Some(header),
+                        part: None,
+                    })
+                }
+                Err(err) => {
+                    warn!(target: "sync", "Failed to download the state header of shard {} at {}: {}", shard_id, sync_hash, err);
+                    run_me.store(true, Ordering::SeqCst);
+                    return;
+                }
+            };
+            debug!(target: "sync", "Downloaded the state header of shard {} at {}", shard_id, sync_hash);
+            send_state_response(&client, shard_id, sync_hash, state_response);
+        });
+    }
+
+    /// Downloads a state part of the shard, `run_me` is set again if it fails.
+    pub fn request_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        part_id: u64,
+        num_parts: u64,
+        run_me: Arc<AtomicBool>,
+    ) {
+        let client = self.client.clone();
+        let fetch = self.source.fetch(state_part_path(&sync_hash, shard_id, part_id, num_parts));
+        actix::spawn(async move {
+            match fetch.await {
+                Ok(data) => {
+                    let state_response = ShardStateSyncResponse::V2(ShardStateSyncResponseV2 {
+                        header: None,
+                        part: Some((part_id, data)),
+                    });
+                    send_state_response(&client, shard_id, sync_hash, state_response);
+                }
+                Err(err) => {
+                    warn!(target: "sync", "Failed to download state part {} of shard {} at {}: {}", part_id, shard_id, sync_hash, err);
+                    run_me.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+    }
+}
+
+fn send_state_response(
+    client: &Recipient<NetworkClientMessages>,
+    shard_id: ShardId,
+    sync_hash: CryptoHash,
+    state_response: ShardStateSyncResponse,
+) {
+    let _ = client.do_send(NetworkClientMessages::StateResponse(StateResponseInfo::V2(
+        StateResponseInfoV2 { shard_id, sync_hash, state_response },
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::hash::hash;
+
+    use super::*;
+
+    #[test]
+    fn test_filesystem_source() {
+        let dir = tempfile::Builder::new().prefix("external_state").tempdir().unwrap();
+        let sync_hash = hash(&[1]);
+        let path = state_part_path(&sync_hash, 0, 1, 3);
+        assert_eq!(path, format!("{}/0/part_1_of_3", sync_hash));
+        std::fs::create_dir_all(dir.path().join(format!("{}/0", sync_hash))).unwrap();
+        std::fs::write(dir.path().join(&path), b"part").unwrap();
+
+        let source = FilesystemSource::new(dir.path().to_path_buf());
+        actix::System::new("test").block_on(async move {
+            assert_eq!(source.fetch(path).await.unwrap(), b"part".to_vec());
+            assert!(source.fetch(state_header_path(&sync_hash, 0)).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,229 @@
+//! Checks of the block headers received during header sync that don't need the chain.
+//!
+//! Header sync asks for the next batch of headers before the received one is validated, so
+//! batches arrive faster than the client actor saves them. Every batch first goes through the
+//! `HeaderVerifier` on its own thread: the headers must form a chain, and the signatures of the
+//! headers in the epochs already known, except the trusted checkpoint block and its ancestors, are
+//! checked in parallel.
+//! The peer that sent a batch failing these checks is banned without the batch ever reaching the
+//! client actor, which validates the rest against the chain.
+use std::sync::Arc;
+
+use actix::{Actor, Handler, Message, Recipient, SyncContext};
+use log::warn;
+use rayon::prelude::*;
+
+use near_chain::RuntimeAdapter;
+use near_chain_configs::TrustedCheckpoint;
+use near_network::types::ReasonForBan;
+use near_network::{NetworkAdapter, NetworkRequests};
+use near_primitives::block_header::BlockHeader;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+
+/// Headers received from a peer, to check before the client processes them.
+pub struct VerifyBlockHeaders {
+    pub headers: Vec<BlockHeader>,
+    pub peer_id: PeerId,
+    /// Block known to the chain, the block producers are looked up as of it.
+    pub last_known_hash: CryptoHash,
+}
+
+impl Message for VerifyBlockHeaders {
+    type Result = ();
+}
+
+/// Headers that passed the checks of the verifier, sorted by height.
+pub struct VerifiedBlockHeaders {
+    pub headers: Vec<BlockHeader>,
+    pub peer_id: PeerId,
+}
+
+impl Message for VerifiedBlockHeaders {
+    type Result = ();
+}
+
+pub struct HeaderVerifier {
+    runtime_adapter: Arc<dyn RuntimeAdapter>,
+    network_adapter: Arc<dyn NetworkAdapter>,
+    client: Recipient<VerifiedBlockHeaders>,
+    trusted_checkpoint: Option<TrustedCheckpoint>,
+}
+
+impl HeaderVerifier {
+    pub fn new(
+        runtime_adapter: Arc<dyn RuntimeAdapter>,
+        network_adapter: Arc<dyn NetworkAdapter>,
+        client: Recipient<VerifiedBlockHeaders>,
+        trusted_checkpoint: Option<TrustedCheckpoint>,
+    ) -> Self {
+        HeaderVerifier { runtime_adapter, network_adapter, client, trusted_checkpoint }
+    }
+}
+
+impl Actor for HeaderVerifier {
+    type Context = SyncContext<Self>;
+}
+
+impl Handler<VerifyBlockHeaders> for HeaderVerifier {
+    type Result = ();
+
+    fn handle(&mut self, msg: VerifyBlockHeaders, _ctx: &mut Self::Context) {
+        let VerifyBlockHeaders { mut headers, peer_id, last_known_hash } = msg;
+        match verify_headers(
+            &*self.runtime_adapter,
+            &mut headers,
+            &last_known_hash,
+            self.trusted_checkpoint.as_ref(),
+        ) {
+            Ok(()) => {
+                let _ = self.client.do_send(VerifiedBlockHeaders { headers, peer_id });
+            }
+            Err(err) => {
+                warn!(target: "sync", "Banning {} for sending invalid block headers: {}", peer_id, err);
+                self.network_adapter.do_send(NetworkRequests::BanPeer {
+                    peer_id,
+                    ban_reason: ReasonForBan::BadBlockHeader,
+                });
+            }
+        }
+    }
+}
+
+/// Sorts the headers by height and checks that they form a chain signed by their block
+/// producers. Signatures of headers in epochs not known yet are left to the chain, and the ones
+/// of the trusted checkpoint block and its ancestors in the batch are not checked. Headers above
+/// the checkpoint in a batch that skips it are rejected.
+fn verify_headers(
+    runtime_adapter: &dyn RuntimeAdapter,
+    headers: &mut Vec<BlockHeader>,
+    last_known_hash: &CryptoHash,
+    trusted_checkpoint: Option<&TrustedCheckpoint>,
+) -> Result<(), String> {
+    headers.sort_by_key(|header| header.height());
+    for pair in headers.windows(2) {
+        if pair[1].prev_hash() != pair[0].hash() {
+            return Err(format!(
+                "header {} at {} doesn't follow {}",
+                pair[1].hash(),
+                pair[1].height(),
+                pair[0].hash()
+            ));
+        }
+    }
+    // The headers form a chain, so the ones up to the checkpoint block are its ancestors.
+    let num_trusted = match trusted_checkpoint {
+        Some(checkpoint) => {
+            match headers.iter().position(|header| header.hash() == &checkpoint.block_hash) {
+                Some(position) => position + 1,
+                None => {
+                    let below = headers.first().map_or(false, |h| h.height() <= checkpoint.height);
+                    let above = headers.last().map_or(false, |h| h.height() > checkpoint.height);
+                    if below && above {
+                        return Err(format!(
+                            "headers skip the checkpoint {}",
+                            checkpoint.block_hash
+                        ));
+                    }
+                    0
+                }
+            }
+        }
+        None => 0,
+    };
+    headers[num_trusted..].par_iter().try_for_each(|header| {
+        if !runtime_adapter.epoch_exists(header.epoch_id()) {
+            return Ok(());
+        }
+        let block_producer = runtime_adapter
+            .get_block_producer(header.epoch_id(), header.height())
+            .and_then(|account_id| {
+                runtime_adapter.get_validator_by_account_id(
+                    header.epoch_id(),
+                    last_known_hash,
+                    &account_id,
+                )
+            });
+        match block_producer {
+            Ok((validator, _)) => {
+                if header.signature().verify(header.hash().as_ref(), &validator.public_key) {
+                    Ok(())
+                } else {
+                    Err(format!("invalid signature of header {}", header.hash()))
+                }
+            }
+            Err(_) => Ok(()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use near_chain::test_utils::setup;
+    use near_chain::Block;
+    use near_crypto::KeyType;
+    use near_primitives::validator_signer::InMemoryValidatorSigner;
+
+    use super::*;
+
+    #[test]
+    fn test_verify_headers() {
+        let (chain, runtime, signer) = setup();
+        let genesis = chain.genesis_block().clone();
+        let mut blocks = vec![Block::empty(&genesis, &*signer)];
+        for _ in 0..3 {
+            let block = Block::empty(blocks.last().unwrap(), &*signer);
+            blocks.push(block);
+        }
+        let headers: Vec<_> = blocks.iter().map(|block| block.header().clone()).collect();
+        let genesis_hash = *genesis.hash();
+
+        let mut shuffled = vec![headers[2].clone(), headers[0].clone(), headers[3].clone()];
+        shuffled.insert(1, headers[1].clone());
+        assert!(verify_headers(&*runtime, &mut shuffled, &genesis_hash, None).is_ok());
+        assert_eq!(shuffled, headers);
+
+        let mut gap = vec![headers[0].clone(), headers[2].clone()];
+        assert!(verify_headers(&*runtime, &mut gap, &genesis_hash, None).is_err());
+
+        // Signing again keeps the hash of the header.
+        let other = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "other");
+        let mut forged = blocks[1].clone();
+        forged.mut_header().resign(&other);
+        let mut forged_headers = headers.clone();
+        forged_headers[1] = forged.header().clone();
+        assert!(verify_headers(&*runtime, &mut forged_headers, &genesis_hash, None).is_err());
+
+        // Only the checkpoint block and its ancestors are trusted.
+        let checkpoint = |block: &Block| TrustedCheckpoint {
+            height: block.header().height(),
+            block_hash: *block.hash(),
+            state_root: *block.header().prev_state_root(),
+        };
+        assert!(verify_headers(
+            &*runtime,
+            &mut forged_headers,
+            &genesis_hash,
+            Some(&checkpoint(&blocks[2]))
+        )
+        .is_ok());
+        assert!(verify_headers(
+            &*runtime,
+            &mut forged_headers,
+            &genesis_hash,
+            Some(&checkpoint(&blocks[0]))
+        )
+        .is_err());
+
+        // A fork past the height of the checkpoint must contain it.
+        let fork = Block::empty_with_height(&blocks[0], blocks[2].header().height() + 1, &*signer);
+        let mut fork_headers = vec![headers[0].clone(), fork.header().clone()];
+        assert!(verify_headers(
+            &*runtime,
+            &mut fork_headers,
+            &genesis_hash,
+            Some(&checkpoint(&blocks[2]))
+        )
+        .is_err());
+    }
+}
@@ -3,22 +3,33 @@ extern crate lazy_static;
 
 pub use crate::client::Client;
 pub use crate::client_actor::{start_client, ClientActor};
+pub use crate::deprecation::{deprecated_usage, report_deprecated_usage};
 pub use crate::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetReceipt, GetStateChanges, GetStateChangesInBlock,
-    GetValidatorInfo, GetValidatorOrdered, Query, Status, StatusResponse, SyncStatus, TxStatus,
-    TxStatusError,
+    CheckReadiness, Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunk, GetChunkError, GetChunkWithProofs, GetDebugStatus,
+    GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetFeeHistory,
+    GetGasPrice, GetGasPriceHistory, GetIdempotencyKey, GetNetworkInfo, GetNextLightClientBlock,
+    GetPeerAccess, GetPeerReputation, GetPeerStore, GetProtocolConfig, GetProtocolFeatures,
+    GetReceipt, GetReceiptError, GetReceiptWithOutcome, GetRoutingInfo, GetStateChanges,
+    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, GetWatchedAccountChanges, Query,
+    SetGCPaused, Status, StatusResponse, SubscribeReorgs, SyncStatus, TxStatus, TxStatusError,
+    UndoBlocks, UpdatePeerAccess,
 };
 #[cfg(feature = "adversarial")]
 pub use crate::view_client::AdversarialControls;
 pub use crate::view_client::{start_view_client, ViewClientActor};
 
+mod alerts;
 mod client;
 mod client_actor;
+mod deprecation;
+pub mod external_state;
+mod header_verifier;
 mod info;
 mod metrics;
+mod state_part_limits;
 pub mod sync;
 pub mod test_utils;
+mod tx_signature_verifier;
 mod types;
 mod view_client;
@@ -1,6 +1,6 @@
 use near_metrics::{
-    try_create_histogram, try_create_int_counter, try_create_int_gauge, Histogram, IntCounter,
-    IntGauge,
+    try_create_histogram, try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge,
+    Histogram, IntCounter, IntCounterVec, IntGauge,
 };
 
 lazy_static! {
@@ -8,6 +8,10 @@ lazy_static! {
         "near_block_produced_total",
         "Total number of blocks produced since starting this node"
     );
+    pub static ref CHUNK_PRODUCED_TOTAL: near_metrics::Result<IntCounter> = try_create_int_counter(
+        "near_chunk_produced_total",
+        "Total number of chunks produced since starting this node"
+    );
     pub static ref IS_VALIDATOR: near_metrics::Result<IntGauge> =
         try_create_int_gauge("near_is_validator", "Bool to denote if it is currently validating");
     pub static ref RECEIVED_BYTES_PER_SECOND: near_metrics::Result<IntGauge> = try_create_int_gauge(
@@ -26,4 +30,55 @@ lazy_static! {
         try_create_int_gauge("near_memory_usage_bytes", "Amount of RAM memory usage");
     pub static ref GC_TIME: near_metrics::Result<Histogram> =
         try_create_histogram("near_gc_time", "Time taken to do garbage collection");
+    pub static ref GC_PAUSED: near_metrics::Result<IntGauge> =
+        try_create_int_gauge("near_gc_paused", "Bool to denote if garbage collection is paused");
+    pub static ref DEPRECATED_USAGE_TOTAL: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_deprecated_usage_total",
+            "Number of times deprecated interfaces were used, by kind and name",
+            &["surface", "name"]
+        );
+    pub static ref STATE_PART_REQUESTS_TOTAL: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_state_part_requests_total",
+            "Number of state part requests served, by whether the part was cached or generated, \
+             or dropped because the requester was throttled or too many parts were being generated",
+            &["result"]
+        );
+}
+
+#[cfg(feature = "protocol_feature_pq_crypto")]
+lazy_static! {
+    pub static ref HYBRID_APPROVAL_SIGN_TIME: near_metrics::Result<Histogram> =
+        try_create_histogram(
+            "near_hybrid_approval_sign_time",
+            "Time taken to sign an approval with the experimental hybrid signature"
+        );
+    pub static ref HYBRID_APPROVAL_VERIFY_TIME: near_metrics::Result<Histogram> =
+        try_create_histogram(
+            "near_hybrid_approval_verify_time",
+            "Time taken to verify the experimental hybrid signature of an approval"
+        );
+    pub static ref HYBRID_APPROVAL_SIGNATURE_SIZE: near_metrics::Result<IntGauge> =
+        try_create_int_gauge(
+            "near_hybrid_approval_signature_size_bytes",
+            "Serialized size of the experimental hybrid signature of an approval"
+        );
+    pub static ref ORPHAN_ANCESTOR_REQUESTS_TOTAL: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_orphan_ancestor_requests_total",
+            "Number of requests for the missing ancestors of orphan blocks"
+        );
+    pub static ref REORGS_TOTAL: near_metrics::Result<IntCounter> = try_create_int_counter(
+        "near_reorgs_total",
+        "Number of times the head switched to a block on another fork"
+    );
+    pub static ref REORG_DEPTH: near_metrics::Result<Histogram> =
+        try_create_histogram("near_reorg_depth", "Number of blocks abandoned by a reorg");
+    pub static ref ABANDONED_BLOCKS_TOTAL: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_abandoned_blocks_total",
+            "Number of blocks that left the canonical chain in a reorg, by their producer",
+            &["producer"]
+        );
 }
@@ -0,0 +1,161 @@
+//! Limits on the state parts served to the nodes syncing state.
+//!
+//! A state part is read from the trie every time it is generated, which takes long enough that a
+//! few requesters asking for parts in a loop keep all the view client threads busy. The parts
+//! generated recently are kept in a cache shared by the view client threads, as the nodes syncing
+//! at the same time ask for the same parts of the same sync hash. At most
+//! `MAX_CONCURRENT_PART_GENERATIONS` parts are generated at once, and every requester gets a bucket
+//! of `REQUESTER_BURST` requests refilled at `REQUESTER_PARTS_PER_SECOND`. Requests over these
+//! limits get no response, so the requester asks another peer once its request times out.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use cached::{Cached, SizedCache};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::types::ShardId;
+
+/// Number of generated parts kept, parts are up to a few megabytes.
+const PART_CACHE_SIZE: usize = 64;
+/// Number of parts generated at the same time across the view client threads.
+const MAX_CONCURRENT_PART_GENERATIONS: usize = 2;
+/// Number of requests a requester can send at once.
+const REQUESTER_BURST: f64 = 32.0;
+/// Number of requests a requester can send per second after the burst.
+const REQUESTER_PARTS_PER_SECOND: f64 = 4.0;
+/// Number of requesters throttled, the least recent ones are forgotten.
+const MAX_REQUESTERS: usize = 1024;
+
+const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
+
+/// Sync hash, shard and part ordinal of a state part.
+pub type StatePartKey = (CryptoHash, ShardId, u64);
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(now: Instant) -> Self {
+        Self { tokens: REQUESTER_BURST, last_refill: now }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * REQUESTER_PARTS_PER_SECOND).min(REQUESTER_BURST);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub struct StatePartLimits {
+    parts: Mutex<SizedCache<StatePartKey, Vec<u8>>>,
+    generations: AtomicUsize,
+    requesters: Mutex<SizedCache<PeerId, TokenBucket>>,
+}
+
+/// Slot of a part being generated, freed when dropped.
+pub struct GenerationSlot<'a> {
+    generations: &'a AtomicUsize,
+}
+
+impl Drop for GenerationSlot<'_> {
+    fn drop(&mut self) {
+        self.generations.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl StatePartLimits {
+    pub fn new() -> Self {
+        StatePartLimits {
+            parts: Mutex::new(SizedCache::with_size(PART_CACHE_SIZE)),
+            generations: AtomicUsize::new(0),
+            requesters: Mutex::new(SizedCache::with_size(MAX_REQUESTERS)),
+        }
+    }
+
+    /// Takes a request of the requester from its bucket, returns false if it is empty.
+    pub fn allow_request(&self, requester: &PeerId, now: Instant) -> bool {
+        let mut requesters = self.requesters.lock().expect(POISONED_LOCK_ERR);
+        if let Some(bucket) = requesters.cache_get_mut(requester) {
+            return bucket.try_take(now);
+        }
+        let mut bucket = TokenBucket::new(now);
+        let allowed = bucket.try_take(now);
+        requesters.cache_set(requester.clone(), bucket);
+        allowed
+    }
+
+    pub fn get_part(&self, key: &StatePartKey) -> Option<Vec<u8>> {
+        self.parts.lock().expect(POISONED_LOCK_ERR).cache_get(key).cloned()
+    }
+
+    pub fn insert_part(&self, key: StatePartKey, part: Vec<u8>) {
+        self.parts.lock().expect(POISONED_LOCK_ERR).cache_set(key, part);
+    }
+
+    /// Takes a slot to generate a part, returns None if too many parts are being generated.
+    pub fn start_generation(&self) -> Option<GenerationSlot> {
+        let slot = GenerationSlot { generations: &self.generations };
+        if self.generations.fetch_add(1, Ordering::SeqCst) < MAX_CONCURRENT_PART_GENERATIONS {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::hash::hash;
+
+    use super::*;
+
+    fn peer_id(seed: &str) -> PeerId {
+        SecretKey::from_seed(KeyType::ED25519, seed).public_key().into()
+    }
+
+    #[test]
+    fn test_state_part_limits() {
+        let limits = StatePartLimits::new();
+        let (requester, other) = (peer_id("requester"), peer_id("other"));
+        let start = Instant::now();
+        let millis = |millis| start + Duration::from_millis(millis);
+
+        for _ in 0..32 {
+            assert!(limits.allow_request(&requester, start));
+        }
+        assert!(!limits.allow_request(&requester, start));
+        assert!(limits.allow_request(&other, start));
+        // Refilled at four requests per second.
+        assert!(!limits.allow_request(&requester, millis(200)));
+        assert!(limits.allow_request(&requester, millis(400)));
+        assert!(!limits.allow_request(&requester, millis(400)));
+        assert!(limits.allow_request(&requester, millis(1000)));
+        assert!(limits.allow_request(&requester, millis(1000)));
+
+        let key = (hash(&[1]), 0, 3);
+        assert_eq!(limits.get_part(&key), None);
+        limits.insert_part(key, vec![1, 2, 3]);
+        assert_eq!(limits.get_part(&key), Some(vec![1, 2, 3]));
+        assert_eq!(limits.get_part(&(hash(&[1]), 1, 3)), None);
+
+        let first = limits.start_generation();
+        let second = limits.start_generation();
+        assert!(first.is_some() && second.is_some());
+        assert!(limits.start_generation().is_none());
+        drop(first);
+        assert!(limits.start_generation().is_some());
+    }
+}
@@ -16,11 +16,14 @@ use near_chain::{Chain, RuntimeAdapter};
 use near_network::types::{AccountOrPeerIdOrHash, NetworkResponses, ReasonForBan};
 use near_network::{FullPeerInfo, NetworkAdapter, NetworkRequests};
 use near_primitives::block::Tip;
+use near_primitives::block_header::BlockHeader;
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 use near_primitives::syncing::get_num_state_parts;
 use near_primitives::types::{AccountId, BlockHeight, BlockHeightDelta, ShardId};
 use near_primitives::utils::to_timestamp;
 
+use crate::external_state::ExternalStateSync;
 use crate::types::{DownloadStatus, ShardSyncDownload, ShardSyncStatus, SyncStatus};
 use cached::{Cached, SizedCache};
 
@@ -30,6 +33,10 @@ pub const MAX_BLOCK_HEADERS: u64 = 512;
 /// Maximum number of block header hashes to send as part of a locator.
 pub const MAX_BLOCK_HEADER_HASHES: usize = 20;
 
+/// Maximum number of batches of headers requested ahead of the header head, the next batch is
+/// requested as soon as a full one arrives while the received ones are still validated.
+pub const MAX_HEADER_BATCHES_AHEAD: u64 = 4;
+
 const BLOCK_REQUEST_TIMEOUT: i64 = 2;
 
 /// Sync state download timeout in seconds.
@@ -243,6 +250,42 @@ impl HeaderSync {
         None
     }
 
+    /// Requests the batch of headers following a full batch received from the syncing peer right
+    /// away, before the received one is validated, as long as the header head is at most
+    /// `MAX_HEADER_BATCHES_AHEAD` batches behind.
+    pub fn received_headers(
+        &mut self,
+        chain: &Chain,
+        headers: &[BlockHeader],
+        peer_id: &PeerId,
+    ) -> Result<(), near_chain::Error> {
+        let peer = match &self.syncing_peer {
+            Some(peer) if &peer.peer_info.id == peer_id => peer.clone(),
+            _ => return Ok(()),
+        };
+        let last = match headers.iter().max_by_key(|header| header.height()) {
+            Some(last) if headers.len() as u64 >= MAX_BLOCK_HEADERS => last,
+            _ => return Ok(()),
+        };
+        let header_head = chain.header_head()?;
+        if last.height() >= peer.chain_info.height
+            || last.height() > header_head.height + MAX_BLOCK_HEADERS * MAX_HEADER_BATCHES_AHEAD
+        {
+            return Ok(());
+        }
+        // The peer answers from the first hash it knows, the last locator is the fallback if it
+        // switched to another fork meanwhile.
+        let mut hashes = vec![*last.hash()];
+        hashes.extend(self.history_locator.iter().map(|(_, hash)| *hash));
+        hashes.truncate(MAX_BLOCK_HEADER_HASHES);
+        debug!(target: "sync", "Sync: request headers: asking {} for headers after {} at {}", peer_id, last.hash(), last.height());
+        self.network_adapter
+            .do_send(NetworkRequests::BlockHeadersRequest { hashes, peer_id: peer_id.clone() });
+        // All headers are received once the requested batch is.
+        self.prev_header_sync.2 = last.height();
+        Ok(())
+    }
+
     fn get_locator(&mut self, chain: &mut Chain) -> Result<Vec<CryptoHash>, near_chain::Error> {
         let tip = chain.header_head()?;
         let genesis_height = chain.genesis().height();
@@ -479,9 +522,18 @@ impl PendingRequestStatus {
     }
 }
 
+/// Where state is downloaded from.
+#[derive(Clone)]
+enum StateSource {
+    Peer(AccountOrPeerIdOrHash),
+    External(ExternalStateSync),
+}
+
 /// Helper to track state sync.
 pub struct StateSync {
     network_adapter: Arc<dyn NetworkAdapter>,
+    /// External storage state is downloaded from next to the peers.
+    external: Option<ExternalStateSync>,
 
     state_sync_time: HashMap<ShardId, DateTime<Utc>>,
     last_time_block_requested: Option<DateTime<Utc>>,
@@ -492,9 +544,13 @@ pub struct StateSync {
 }
 
 impl StateSync {
-    pub fn new(network_adapter: Arc<dyn NetworkAdapter>) -> Self {
+    pub fn new(
+        network_adapter: Arc<dyn NetworkAdapter>,
+        external: Option<ExternalStateSync>,
+    ) -> Self {
         StateSync {
             network_adapter,
+            external,
             state_sync_time: Default::default(),
             last_time_block_requested: None,
             last_part_id_requested: Default::default(),
@@ -838,7 +894,13 @@ impl StateSync {
             highest_height_peers,
         )?;
 
-        if possible_targets.is_empty() {
+        // The external storage takes its share of the requests as one more peer would.
+        let sources: Vec<_> = possible_targets
+            .into_iter()
+            .map(StateSource::Peer)
+            .chain(self.external.clone().map(StateSource::External))
+            .collect();
+        if sources.is_empty() {
             return Ok(shard_sync_download);
         }
 
@@ -847,12 +909,20 @@ impl StateSync {
 
         match shard_sync_download.status {
             ShardSyncStatus::StateDownloadHeader => {
-                let target = possible_targets.choose(&mut thread_rng()).cloned().unwrap();
+                let source = sources.choose(&mut thread_rng()).cloned().unwrap();
                 assert!(new_shard_sync_download.downloads[0].run_me.load(Ordering::SeqCst));
                 new_shard_sync_download.downloads[0].run_me.store(false, Ordering::SeqCst);
                 new_shard_sync_download.downloads[0].state_requests_count += 1;
-                new_shard_sync_download.downloads[0].last_target = Some(target.clone());
                 let run_me = new_shard_sync_download.downloads[0].run_me.clone();
+                let target = match source {
+                    StateSource::Peer(target) => target,
+                    StateSource::External(external) => {
+                        new_shard_sync_download.downloads[0].last_target = None;
+                        external.request_header(shard_id, sync_hash, run_me);
+                        return Ok(new_shard_sync_download);
+                    }
+                };
+                new_shard_sync_download.downloads[0].last_target = Some(target.clone());
                 actix::spawn(
                     self.network_adapter
                         .send(NetworkRequests::StateRequestHeader { shard_id, sync_hash, target })
@@ -866,25 +936,39 @@ impl StateSync {
                 );
             }
             ShardSyncStatus::StateDownloadParts => {
-                let possible_targets_sampler =
-                    SamplerLimited::new(possible_targets, MAX_STATE_PART_REQUEST);
+                let num_parts = new_shard_sync_download.downloads.len() as u64;
+                let sources_sampler = SamplerLimited::new(sources, MAX_STATE_PART_REQUEST);
 
                 // Iterate over all parts that needs to be requested (i.e. download.run_me is true).
                 // Parts are ordered such that its index match its part_id.
                 // Finally, for every part that needs to be requested it is selected one peer (target) randomly
                 // to request the part from
-                for ((part_id, download), target) in new_shard_sync_download
+                for ((part_id, download), source) in new_shard_sync_download
                     .downloads
                     .iter_mut()
                     .enumerate()
                     .filter(|(_, download)| download.run_me.load(Ordering::SeqCst))
-                    .zip(possible_targets_sampler)
+                    .zip(sources_sampler)
                 {
-                    self.sent_request_part(target.clone(), part_id as u64, shard_id, sync_hash);
                     download.run_me.store(false, Ordering::SeqCst);
                     download.state_requests_count += 1;
-                    download.last_target = Some(target.clone());
                     let run_me = download.run_me.clone();
+                    let target = match source {
+                        StateSource::Peer(target) => target,
+                        StateSource::External(external) => {
+                            download.last_target = None;
+                            external.request_part(
+                                shard_id,
+                                sync_hash,
+                                part_id as u64,
+                                num_parts,
+                                run_me,
+                            );
+                            continue;
+                        }
+                    };
+                    self.sent_request_part(target.clone(), part_id as u64, shard_id, sync_hash);
+                    download.last_target = Some(target.clone());
 
                     actix::spawn(
                         self.network_adapter
@@ -1124,6 +1208,61 @@ mod test {
         );
     }
 
+    /// Checks that the next batch of headers is requested from the syncing peer as soon as a full
+    /// batch arrives, before it is processed.
+    #[test]
+    fn test_pipelined_header_requests() {
+        let mock_adapter = Arc::new(MockNetworkAdapter::default());
+        let mut header_sync = HeaderSync::new(
+            mock_adapter.clone(),
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(2),
+            TimeDuration::from_secs(120),
+            1_000_000_000,
+        );
+        let (mut chain, _, signer) = setup();
+        let mut blocks = vec![chain.genesis_block().clone()];
+        for _ in 0..MAX_BLOCK_HEADERS + 10 {
+            let block = Block::empty(blocks.last().unwrap(), &*signer);
+            blocks.push(block);
+        }
+        let peer = FullPeerInfo {
+            peer_info: PeerInfo::random(),
+            chain_info: PeerChainInfoV2 {
+                genesis_id: GenesisId {
+                    chain_id: "unittest".to_string(),
+                    hash: *chain.genesis().hash(),
+                },
+                height: blocks.last().unwrap().header().height(),
+                tracked_shards: vec![],
+                archival: false,
+            },
+            edge_info: EdgeInfo::default(),
+        };
+        let mut sync_status = SyncStatus::NoSync;
+        header_sync.run(&mut sync_status, &mut chain, 0, &vec![peer.clone()]).unwrap();
+        assert!(mock_adapter.pop().is_some());
+
+        let headers: Vec<_> = blocks[1..=MAX_BLOCK_HEADERS as usize]
+            .iter()
+            .map(|block| block.header().clone())
+            .collect();
+        // Only full batches from the syncing peer are followed up.
+        header_sync.received_headers(&chain, &headers, &PeerInfo::random().id).unwrap();
+        header_sync.received_headers(&chain, &headers[1..], &peer.peer_info.id).unwrap();
+        assert!(mock_adapter.pop().is_none());
+
+        header_sync.received_headers(&chain, &headers, &peer.peer_info.id).unwrap();
+        match mock_adapter.pop() {
+            Some(NetworkRequests::BlockHeadersRequest { hashes, peer_id }) => {
+                assert_eq!(hashes[0], *headers.last().unwrap().hash());
+                assert_eq!(hashes[1], *chain.genesis().hash());
+                assert_eq!(peer_id, peer.peer_info.id);
+            }
+            request => panic!("unexpected request {:?}", request),
+        }
+    }
+
     /// Sets up `HeaderSync` with particular tolerance for slowness, and makes sure that a peer that
     /// sends headers below the threshold gets banned, and the peer that sends them faster doesn't get
     /// banned.
@@ -1205,6 +1344,7 @@ mod test {
                     )
                 };
             let block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header(),
                 current_height,
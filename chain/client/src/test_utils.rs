@@ -1,6 +1,6 @@
 use log::info;
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::DerefMut;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
@@ -15,7 +15,7 @@ use near_chain::test_utils::KeyValueRuntime;
 use near_chain::{
     Chain, ChainGenesis, ChainStoreAccess, DoomslugThresholdMode, Provenance, RuntimeAdapter,
 };
-use near_chain_configs::ClientConfig;
+use near_chain_configs::{ClientConfig, DEFAULT_MAX_BLOCK_SIZE};
 use near_crypto::{InMemorySigner, KeyType, PublicKey};
 #[cfg(feature = "metric_recorder")]
 use near_network::recorder::MetricRecorder;
@@ -83,6 +83,8 @@ pub fn setup(
         time: genesis_time,
         height: 0,
         gas_limit: 1_000_000,
+        max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+        block_limits_upgrades: vec![],
         min_gas_price: 100,
         max_gas_price: 1_000_000_000,
         total_supply: 3_000_000_000_000_000_000_000_000_000_000_000,
@@ -474,6 +476,9 @@ pub fn setup_mock_all_validators(
                             sent_bytes_per_sec: 0,
                             received_bytes_per_sec: 0,
                             known_producers: vec![],
+                            peer_protocol_versions: BTreeMap::new(),
+                            peer_clock_skews: HashMap::new(),
+                            peer_transports: HashMap::new(),
                             #[cfg(feature = "metric_recorder")]
                             metric_recorder: MetricRecorder::default(),
                             peer_counter: 0,
@@ -689,6 +694,7 @@ pub fn setup_mock_all_validators(
                                                 shard_id: *shard_id,
                                                 sync_hash: *sync_hash,
                                                 part_id: *part_id,
+                                                requester: my_key_pair.id.clone(),
                                             })
                                             .then(move |response| {
                                                 let response = response.unwrap();
@@ -803,9 +809,15 @@ pub fn setup_mock_all_validators(
                         }
                         NetworkRequests::ForwardTx(_, _)
                         | NetworkRequests::Sync { .. }
+                        | NetworkRequests::SyncAccountsData { .. }
                         | NetworkRequests::FetchRoutingTable
                         | NetworkRequests::PingTo(_, _)
                         | NetworkRequests::FetchPingPongInfo
+                        | NetworkRequests::FetchPeerStore
+                        | NetworkRequests::FetchPeerReputation
+                        | NetworkRequests::FetchPeerAccess
+                        | NetworkRequests::UpdatePeerAccess(_)
+                        | NetworkRequests::ReportMisbehavior { .. }
                         | NetworkRequests::BanPeer { .. }
                         | NetworkRequests::TxStatus(_, _, _)
                         | NetworkRequests::Query { .. }
@@ -1183,6 +1195,7 @@ pub fn create_chunk(
         client.chain.mut_store().get_block_merkle_tree(&last_block.hash()).unwrap().clone();
     block_merkle_tree.insert(*last_block.hash());
     let block = Block::produce(
+        PROTOCOL_VERSION,
         PROTOCOL_VERSION,
         &last_block.header(),
         2,
@@ -0,0 +1,44 @@
+//! Verification of the signatures of the transactions submitted to the node, off the client actor.
+//!
+//! Transactions from the RPC and the peers first go through the `TransactionSignatureVerifier`
+//! threads. The valid signatures are remembered by the runtime, so that the checks of the
+//! transaction against the state on the client actor, and the chunk production later, skip the
+//! ed25519 verification.
+use std::sync::Arc;
+
+use actix::{Actor, Handler, Message, SyncContext};
+
+use near_chain::RuntimeAdapter;
+use near_primitives::transaction::SignedTransaction;
+
+/// Number of threads verifying the signatures of the transactions.
+pub const TX_SIGNATURE_VERIFIER_THREADS: usize = 4;
+
+/// Transaction to verify the signature of. Responds whether the signature is valid.
+pub struct VerifyTransactionSignature(pub SignedTransaction);
+
+impl Message for VerifyTransactionSignature {
+    type Result = bool;
+}
+
+pub struct TransactionSignatureVerifier {
+    runtime_adapter: Arc<dyn RuntimeAdapter>,
+}
+
+impl TransactionSignatureVerifier {
+    pub fn new(runtime_adapter: Arc<dyn RuntimeAdapter>) -> Self {
+        TransactionSignatureVerifier { runtime_adapter }
+    }
+}
+
+impl Actor for TransactionSignatureVerifier {
+    type Context = SyncContext<Self>;
+}
+
+impl Handler<VerifyTransactionSignature> for TransactionSignatureVerifier {
+    type Result = bool;
+
+    fn handle(&mut self, msg: VerifyTransactionSignature, _ctx: &mut Self::Context) -> bool {
+        self.runtime_adapter.verify_transaction_signature(&msg.0)
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(feature = "metric_recorder")]
 use near_network::recorder::MetricRecorder;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -8,21 +8,28 @@ use actix::Message;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use near_chain::ReorgAdapter;
+use near_chain_configs::ProtocolConfigView;
 use near_network::types::{AccountOrPeerIdOrHash, KnownProducer};
-use near_network::PeerInfo;
+use near_network::{PeerAccessChange, PeerInfo};
+use near_primitives::block::Tip;
 use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockReference, MaybeBlockId, ShardId, TransactionOrReceiptId,
+    AccountId, BlockHeight, BlockHeightDelta, BlockReference, EpochReference, MaybeBlockId,
+    NumBlocks, ShardId, TransactionOrReceiptId,
 };
 use near_primitives::utils::generate_random_string;
+use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView, StateChangesRequestView,
-    StateChangesView, ValidatorStakeView,
+    BlockView, ChunkView, ChunkWithProofsView, DebugStatusView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FeeHistoryView, FinalExecutionOutcomeViewEnum, GasPriceView,
+    IdempotencyKeyView, KnownPeerView, LightClientBlockLiteView, LightClientBlockView,
+    PeerAccessView, PeerReputationView, ProtocolFeaturesView, QueryRequest, QueryResponse,
+    ReceiptView, ReceiptWithOutcomeView, RoutingInfoView, StateChangesKindsView,
+    StateChangesRequestView, StateChangesView, ValidatorStakeView, WatchedAccountChangesView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
 
@@ -156,8 +163,32 @@ impl GetBlock {
     }
 }
 
+#[derive(Debug)]
+pub enum GetBlockError {
+    /// The node doesn't know about the block.
+    UnknownBlock(String),
+    /// The block was processed by the node, but has been garbage collected since.
+    GarbageCollectedBlock(String),
+    /// There are no fully synchronized blocks yet.
+    NotSyncedYet,
+    InternalError(String),
+}
+
+impl From<GetBlockError> for String {
+    fn from(error: GetBlockError) -> Self {
+        match error {
+            GetBlockError::UnknownBlock(err) => err,
+            GetBlockError::GarbageCollectedBlock(err) => {
+                format!("Block has been garbage collected: {}", err)
+            }
+            GetBlockError::NotSyncedYet => "There are no fully synchronized blocks yet".to_string(),
+            GetBlockError::InternalError(err) => err,
+        }
+    }
+}
+
 impl Message for GetBlock {
-    type Result = Result<BlockView, String>;
+    type Result = Result<BlockView, GetBlockError>;
 }
 
 /// Get block with the block merkle tree. Used for testing
@@ -180,8 +211,56 @@ pub enum GetChunk {
     ChunkHash(ChunkHash),
 }
 
+#[derive(Debug)]
+pub enum GetChunkError {
+    /// The node doesn't know about the block of the chunk.
+    UnknownBlock(String),
+    /// The block of the chunk has been garbage collected.
+    GarbageCollectedBlock(String),
+    InvalidShardId(ShardId),
+    /// The node doesn't know about the chunk or it has been garbage collected.
+    UnknownChunk(ChunkHash),
+    InternalError(String),
+}
+
+impl From<GetBlockError> for GetChunkError {
+    fn from(error: GetBlockError) -> Self {
+        match error {
+            GetBlockError::UnknownBlock(err) => GetChunkError::UnknownBlock(err),
+            GetBlockError::GarbageCollectedBlock(err) => GetChunkError::GarbageCollectedBlock(err),
+            GetBlockError::NotSyncedYet => {
+                GetChunkError::InternalError(GetBlockError::NotSyncedYet.into())
+            }
+            GetBlockError::InternalError(err) => GetChunkError::InternalError(err),
+        }
+    }
+}
+
+impl From<GetChunkError> for String {
+    fn from(error: GetChunkError) -> Self {
+        match error {
+            GetChunkError::UnknownBlock(err) => err,
+            GetChunkError::GarbageCollectedBlock(err) => {
+                format!("Block has been garbage collected: {}", err)
+            }
+            GetChunkError::InvalidShardId(shard_id) => format!("Invalid shard id {}", shard_id),
+            GetChunkError::UnknownChunk(chunk_hash) => {
+                format!("Chunk {} is unknown", chunk_hash.0)
+            }
+            GetChunkError::InternalError(err) => err,
+        }
+    }
+}
+
 impl Message for GetChunk {
-    type Result = Result<ChunkView, String>;
+    type Result = Result<ChunkView, GetChunkError>;
+}
+
+/// Actor message requesting a chunk with the Merkle proofs of its contents.
+pub struct GetChunkWithProofs(pub GetChunk);
+
+impl Message for GetChunkWithProofs {
+    type Result = Result<ChunkWithProofsView, GetChunkError>;
 }
 
 /// Queries client for given path / data.
@@ -210,6 +289,18 @@ impl Message for Status {
     type Result = Result<StatusResponse, String>;
 }
 
+/// Checks whether the node is ready to serve requests, the error explains why it's not.
+pub struct CheckReadiness {
+    /// How far the head can be behind the highest height reported by the peers.
+    pub max_blocks_behind: BlockHeightDelta,
+    /// Minimum number of active peers.
+    pub min_peers: usize,
+}
+
+impl Message for CheckReadiness {
+    type Result = Result<(), String>;
+}
+
 pub struct GetNextLightClientBlock {
     pub last_block_hash: CryptoHash,
 }
@@ -218,6 +309,69 @@ impl Message for GetNextLightClientBlock {
     type Result = Result<Option<LightClientBlockView>, String>;
 }
 
+/// Actor message requesting the shards tracked by the node and its head, see `RoutingInfoView`.
+pub struct GetRoutingInfo {}
+
+impl Message for GetRoutingInfo {
+    type Result = Result<RoutingInfoView, String>;
+}
+
+/// Actor message requesting the sync status and the pools of the chain, see `DebugStatusView`.
+pub struct GetDebugStatus {}
+
+impl Message for GetDebugStatus {
+    type Result = Result<DebugStatusView, String>;
+}
+
+/// Actor message requesting the peers known to the node, forwarded to the network.
+pub struct GetPeerStore {}
+
+impl Message for GetPeerStore {
+    type Result = Result<Vec<KnownPeerView>, String>;
+}
+
+/// Actor message requesting the misbehavior scores of peers, forwarded to the network.
+pub struct GetPeerReputation {}
+
+impl Message for GetPeerReputation {
+    type Result = Result<Vec<PeerReputationView>, String>;
+}
+
+pub struct GetPeerAccess {}
+
+impl Message for GetPeerAccess {
+    type Result = Result<PeerAccessView, String>;
+}
+
+/// Changes the rules which peers may connect, returns the rules after the change.
+pub struct UpdatePeerAccess(pub PeerAccessChange);
+
+impl Message for UpdatePeerAccess {
+    type Result = Result<PeerAccessView, String>;
+}
+
+/// Pauses or resumes garbage collection, returns whether it is paused.
+pub struct SetGCPaused(pub bool);
+
+impl Message for SetGCPaused {
+    type Result = Result<bool, String>;
+}
+
+/// Rewinds the head by up to the given number of blocks, but not below the last final block,
+/// returns the new head. Only a stopped node can rewind past the final block.
+pub struct UndoBlocks(pub NumBlocks);
+
+impl Message for UndoBlocks {
+    type Result = Result<Tip, String>;
+}
+
+/// Registers an adapter notified of every switch of the head to another fork.
+pub struct SubscribeReorgs(pub Arc<dyn ReorgAdapter>);
+
+impl Message for SubscribeReorgs {
+    type Result = ();
+}
+
 pub struct GetNetworkInfo {}
 
 impl Message for GetNetworkInfo {
@@ -232,6 +386,63 @@ impl Message for GetGasPrice {
     type Result = Result<GasPriceView, String>;
 }
 
+/// Gas prices and gas usage of up to `block_count` blocks ending with the given block.
+pub struct GetFeeHistory {
+    pub block_count: NumBlocks,
+    pub block_id: MaybeBlockId,
+}
+
+impl Message for GetFeeHistory {
+    type Result = Result<FeeHistoryView, String>;
+}
+
+/// Gas prices and gas usage of the blocks on the canonical chain in the range of heights,
+/// `to_height` defaults to the head.
+pub struct GetGasPriceHistory {
+    pub from_height: BlockHeight,
+    pub to_height: Option<BlockHeight>,
+}
+
+impl Message for GetGasPriceHistory {
+    type Result = Result<FeeHistoryView, String>;
+}
+
+/// Executions of transactions of the account with the given idempotency key.
+pub struct GetIdempotencyKey {
+    pub account_id: AccountId,
+    pub key: Vec<u8>,
+}
+
+impl Message for GetIdempotencyKey {
+    type Result = Result<IdempotencyKeyView, String>;
+}
+
+/// Up to `limit` indexed changes of the watched account, starting from the given position.
+pub struct GetWatchedAccountChanges {
+    pub account_id: AccountId,
+    pub from_block_height: BlockHeight,
+    pub from_index: u32,
+    pub limit: u64,
+}
+
+impl Message for GetWatchedAccountChanges {
+    type Result = Result<WatchedAccountChangesView, String>;
+}
+
+/// Protocol features compiled into the node and whether they are enabled in the epoch of the head.
+pub struct GetProtocolFeatures {}
+
+impl Message for GetProtocolFeatures {
+    type Result = Result<ProtocolFeaturesView, String>;
+}
+
+/// Protocol parameters in effect at the given block.
+pub struct GetProtocolConfig(pub BlockReference);
+
+impl Message for GetProtocolConfig {
+    type Result = Result<ProtocolConfigView, GetBlockError>;
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NetworkInfoResponse {
     pub active_peers: Vec<PeerInfo>,
@@ -241,6 +452,8 @@ pub struct NetworkInfoResponse {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
+    /// Number of active peers by the protocol version they advertised.
+    pub peer_protocol_versions: BTreeMap<ProtocolVersion, usize>,
     #[cfg(feature = "metric_recorder")]
     pub metric_recorder: MetricRecorder,
 }
@@ -280,7 +493,7 @@ impl Message for TxStatus {
 }
 
 pub struct GetValidatorInfo {
-    pub block_id: MaybeBlockId,
+    pub epoch_reference: EpochReference,
 }
 
 impl Message for GetValidatorInfo {
@@ -354,3 +567,30 @@ pub struct GetReceipt {
 impl Message for GetReceipt {
     type Result = Result<Option<ReceiptView>, String>;
 }
+
+/// Actor message requesting a receipt with the block, chunk and outcome of its execution.
+pub struct GetReceiptWithOutcome {
+    pub receipt_id: CryptoHash,
+}
+
+#[derive(Debug)]
+pub enum GetReceiptError {
+    /// The node doesn't track the shard of the receipt or the receipt has been garbage collected.
+    UnknownReceipt(CryptoHash),
+    InternalError(String),
+}
+
+impl From<GetReceiptError> for String {
+    fn from(error: GetReceiptError) -> Self {
+        match error {
+            GetReceiptError::UnknownReceipt(receipt_id) => {
+                format!("Receipt {} is unknown", receipt_id)
+            }
+            GetReceiptError::InternalError(err) => err,
+        }
+    }
+}
+
+impl Message for GetReceiptWithOutcome {
+    type Result = Result<ReceiptWithOutcomeView, GetReceiptError>;
+}
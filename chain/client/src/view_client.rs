@@ -15,7 +15,7 @@ use near_chain::{
     get_epoch_block_producers_view, Chain, ChainGenesis, ChainStoreAccess, DoomslugThresholdMode,
     ErrorKind, RuntimeAdapter,
 };
-use near_chain_configs::ClientConfig;
+use near_chain_configs::{ClientConfig, ProtocolConfigView};
 #[cfg(feature = "adversarial")]
 use near_network::types::NetworkAdversarialMessage;
 use near_network::types::{
@@ -27,36 +27,48 @@ use near_primitives::block::{Block, BlockHeader, GenesisId, Tip};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
-use near_primitives::sharding::ShardChunk;
+use near_primitives::serialize::to_base64;
+use near_primitives::sharding::{ChunkHash, ShardChunk, ShardChunkHeader};
 use near_primitives::syncing::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
     ShardStateSyncResponseV2,
 };
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockId, BlockReference, Finality, MaybeBlockId, ShardId,
-    TransactionOrReceiptId,
+    AccountId, BlockHeight, BlockId, BlockReference, EpochId, EpochReference, Finality,
+    MaybeBlockId, NumBlocks, ShardId, TransactionOrReceiptId,
 };
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
-    LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView, ValidatorStakeView,
+    AccountList, BlockFeeView, BlockView, ChunkProofsView, ChunkView, ChunkWithProofsView,
+    EpochValidatorInfo, ExecutionOutcomeWithIdView, FeeHistoryView, FinalExecutionOutcomeView,
+    FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView, IdempotencyKeyView,
+    LightClientBlockView, ProtocolFeaturesView, QueryRequest, QueryResponse, QueryResponseKind,
+    ReceiptView, ReceiptWithOutcomeView, StateChangesKindsView, StateChangesView,
+    ValidatorStakeView, WatchedAccountChangesView, MAX_ACCOUNTS_PER_QUERY,
 };
 
+use crate::metrics;
+use crate::state_part_limits::StatePartLimits;
 use crate::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree,
-    GetExecutionOutcome, GetExecutionOutcomesForBlock, GetGasPrice, GetReceipt, Query, TxStatus,
+    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree,
+    GetExecutionOutcome, GetExecutionOutcomesForBlock, GetFeeHistory, GetGasPrice,
+    GetGasPriceHistory, GetIdempotencyKey, GetProtocolConfig, GetProtocolFeatures, GetReceipt,
+    GetReceiptError, GetReceiptWithOutcome, GetWatchedAccountChanges, Query, TxStatus,
     TxStatusError,
 };
 use crate::{
-    sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
+    sync, GetChunk, GetChunkError, GetChunkWithProofs, GetExecutionOutcomeResponse,
+    GetNextLightClientBlock, GetStateChanges, GetStateChangesInBlock, GetValidatorInfo,
+    GetValidatorOrdered,
 };
 
 /// Max number of queries that we keep.
 const QUERY_REQUEST_LIMIT: usize = 500;
 /// Waiting time between requests, in ms
 const REQUEST_WAIT_TIME: u64 = 1000;
+/// Max number of blocks returned by a single fee history request.
+const MAX_FEE_HISTORY_BLOCKS: NumBlocks = 1000;
+/// Max number of changes returned by a single watched account changes request.
+const MAX_WATCHED_ACCOUNT_CHANGES: u64 = 1000;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
@@ -94,6 +106,7 @@ pub struct ViewClientActor {
     network_adapter: Arc<dyn NetworkAdapter>,
     pub config: ClientConfig,
     request_manager: Arc<RwLock<ViewClientRequestManager>>,
+    state_part_limits: Arc<StatePartLimits>,
 }
 
 impl ViewClientRequestManager {
@@ -116,6 +129,7 @@ impl ViewClientActor {
         network_adapter: Arc<dyn NetworkAdapter>,
         config: ClientConfig,
         request_manager: Arc<RwLock<ViewClientRequestManager>>,
+        state_part_limits: Arc<StatePartLimits>,
         #[cfg(feature = "adversarial")] adv: Arc<RwLock<AdversarialControls>>,
     ) -> Result<Self, Error> {
         // TODO: should we create shared ChainStore that is passed to both Client and ViewClient?
@@ -133,6 +147,7 @@ impl ViewClientActor {
             network_adapter,
             config,
             request_manager,
+            state_part_limits,
         })
     }
 
@@ -147,6 +162,30 @@ impl ViewClientActor {
         }
     }
 
+    /// Returns the last block of a finished epoch or the head for the current one. The epoch is
+    /// searched for from the head one epoch at a time.
+    fn get_epoch_last_block_hash(
+        &mut self,
+        epoch_id: &EpochId,
+    ) -> Result<CryptoHash, near_chain::Error> {
+        let unknown_epoch = || -> near_chain::Error {
+            ErrorKind::DBNotFoundErr(format!("Epoch {:?}", epoch_id)).into()
+        };
+        if !self.runtime_adapter.epoch_exists(epoch_id) {
+            return Err(unknown_epoch());
+        }
+        let mut block_hash = self.chain.head()?.last_block_hash;
+        while self.chain.get_block_header(&block_hash)?.epoch_id() != epoch_id {
+            let epoch_start_height = self.runtime_adapter.get_epoch_start_height(&block_hash)?;
+            block_hash = *self.chain.get_header_by_height(epoch_start_height)?.prev_hash();
+            if block_hash == CryptoHash::default() {
+                // The next epoch is known before it starts.
+                return Err(unknown_epoch());
+            }
+        }
+        Ok(block_hash)
+    }
+
     fn need_request<K: Hash + Eq + Clone>(key: K, cache: &mut SizedCache<K, Instant>) -> bool {
         let now = Instant::now();
         let need_request = match cache.cache_get(&key) {
@@ -183,6 +222,102 @@ impl ViewClientActor {
         }
     }
 
+    fn get_block_by_id(&mut self, block_id: &BlockId) -> Result<Block, GetBlockError> {
+        match block_id {
+            BlockId::Height(height) => self.chain.get_block_by_height(*height).map(Clone::clone),
+            BlockId::Hash(hash) => self.chain.get_block(hash).map(Clone::clone),
+        }
+        .map_err(|err| self.get_block_error(Some(block_id), err))
+    }
+
+    /// Tells blocks the node doesn't know about from the garbage collected ones. Headers are kept
+    /// when the blocks are garbage collected, so the height of blocks requested by hash is known.
+    fn get_block_error(
+        &mut self,
+        block_id: Option<&BlockId>,
+        err: near_chain::Error,
+    ) -> GetBlockError {
+        if let ErrorKind::DBNotFoundErr(_) = err.kind() {
+            let height = match block_id {
+                Some(BlockId::Height(height)) => Some(*height),
+                Some(BlockId::Hash(hash)) => {
+                    self.chain.get_block_header(hash).ok().map(|header| header.height())
+                }
+                None => None,
+            };
+            match (height, self.chain.store().tail()) {
+                (Some(height), Ok(tail)) if height < tail => {
+                    GetBlockError::GarbageCollectedBlock(err.to_string())
+                }
+                _ => GetBlockError::UnknownBlock(err.to_string()),
+            }
+        } else {
+            GetBlockError::InternalError(err.to_string())
+        }
+    }
+
+    fn get_chunk_header(
+        block: &Block,
+        shard_id: ShardId,
+    ) -> Result<(ChunkHash, Option<ShardChunkHeader>), GetChunkError> {
+        let chunk_header = block
+            .chunks()
+            .get(shard_id as usize)
+            .ok_or(GetChunkError::InvalidShardId(shard_id))?
+            .clone();
+        Ok((chunk_header.chunk_hash(), Some(chunk_header)))
+    }
+
+    /// Returns the chunk and, if the chunk was requested by block, the block.
+    fn get_chunk(&mut self, msg: GetChunk) -> Result<(ShardChunk, Option<Block>), GetChunkError> {
+        let (chunk_hash, chunk_header, block) = match msg {
+            GetChunk::ChunkHash(chunk_hash) => (chunk_hash, None, None),
+            GetChunk::BlockHash(block_hash, shard_id) => {
+                let block = self.get_block_by_id(&BlockId::Hash(block_hash))?;
+                let (chunk_hash, chunk_header) = Self::get_chunk_header(&block, shard_id)?;
+                (chunk_hash, chunk_header, Some(block))
+            }
+            GetChunk::Height(height, shard_id) => {
+                let block = self.get_block_by_id(&BlockId::Height(height))?;
+                let (chunk_hash, chunk_header) = Self::get_chunk_header(&block, shard_id)?;
+                (chunk_hash, chunk_header, Some(block))
+            }
+        };
+        let chunk = self.chain.get_chunk(&chunk_hash).map(Clone::clone).map_err(|err| {
+            if let ErrorKind::ChunkMissing(_) = err.kind() {
+                GetChunkError::UnknownChunk(chunk_hash.clone())
+            } else {
+                GetChunkError::InternalError(err.to_string())
+            }
+        })?;
+        let chunk = match chunk_header {
+            Some(chunk_header) => {
+                ShardChunk::with_header(chunk, chunk_header).ok_or_else(|| {
+                    GetChunkError::InternalError(format!(
+                        "Mismatched versions for chunk with hash {}",
+                        chunk_hash.0
+                    ))
+                })?
+            }
+            None => chunk,
+        };
+        Ok((chunk, block))
+    }
+
+    fn get_chunk_author(&self, chunk: &ShardChunk) -> Result<AccountId, GetChunkError> {
+        let chunk_inner = chunk.cloned_header().take_inner();
+        self.runtime_adapter
+            .get_epoch_id_from_prev_block(&chunk_inner.prev_block_hash)
+            .and_then(|epoch_id| {
+                self.runtime_adapter.get_chunk_producer(
+                    &epoch_id,
+                    chunk_inner.height_created,
+                    chunk_inner.shard_id,
+                )
+            })
+            .map_err(|err| GetChunkError::InternalError(err.to_string()))
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<Option<QueryResponse>, String> {
         {
             let mut request_manager = self.request_manager.write().expect(POISONED_LOCK_ERR);
@@ -220,9 +355,15 @@ impl ViewClientActor {
         let account_id = match &msg.request {
             QueryRequest::ViewAccount { account_id, .. } => account_id,
             QueryRequest::ViewState { account_id, .. } => account_id,
+            QueryRequest::ViewStatePaginated { account_id, .. } => account_id,
             QueryRequest::ViewAccessKey { account_id, .. } => account_id,
-            QueryRequest::ViewAccessKeyList { account_id, .. } => account_id,
+            QueryRequest::ViewAccessKeyList { account_id } => account_id,
+            QueryRequest::ViewAccessKeyListPaginated { account_id, .. } => account_id,
             QueryRequest::CallFunction { account_id, .. } => account_id,
+            QueryRequest::ViewContractMetadata { account_id } => account_id,
+            QueryRequest::ViewAccountsByPrefix { limit, .. } => {
+                return self.query_all_shards_accounts(&header, &msg.request, *limit).map(Some);
+            }
         };
         let shard_id = self.runtime_adapter.account_id_to_shard_id(account_id);
 
@@ -271,6 +412,63 @@ impl ViewClientActor {
         }
     }
 
+    /// Accounts are spread across the shards by hash, so listing them queries every shard and
+    /// merges the sorted pages. Only works on nodes that track all the shards.
+    fn query_all_shards_accounts(
+        &self,
+        header: &BlockHeader,
+        request: &QueryRequest,
+        limit: Option<u32>,
+    ) -> Result<QueryResponse, String> {
+        let limit =
+            std::cmp::min(limit.unwrap_or(MAX_ACCOUNTS_PER_QUERY), MAX_ACCOUNTS_PER_QUERY) as usize;
+        let mut accounts = vec![];
+        let mut has_more = false;
+        for shard_id in 0..self.runtime_adapter.num_shards() {
+            let chunk_extra =
+                self.chain.get_chunk_extra(header.hash(), shard_id).map_err(|_| {
+                    format!(
+                        "Node doesn't track shard {}, can't list accounts of all shards",
+                        shard_id
+                    )
+                })?;
+            let response = self
+                .runtime_adapter
+                .query(
+                    shard_id,
+                    &chunk_extra.state_root,
+                    header.height(),
+                    header.raw_timestamp(),
+                    header.hash(),
+                    header.epoch_id(),
+                    request,
+                )
+                .map_err(|e| e.to_string())?;
+            match response.kind {
+                QueryResponseKind::AccountList(account_list) => {
+                    has_more |= account_list.next_cursor.is_some();
+                    accounts.extend(account_list.accounts);
+                }
+                // Errors don't depend on the shard.
+                QueryResponseKind::Error(_) => return Ok(response),
+                _ => return Err("Invalid type of response".to_string()),
+            }
+        }
+        // A shard with more accounts returned `limit` of them, so the first `limit` merged
+        // accounts are the first ones overall.
+        accounts.sort();
+        if accounts.len() > limit {
+            accounts.truncate(limit);
+            has_more = true;
+        }
+        let next_cursor = if has_more { accounts.last().cloned() } else { None };
+        Ok(QueryResponse {
+            kind: QueryResponseKind::AccountList(AccountList { accounts, next_cursor }),
+            block_height: header.height(),
+            block_hash: *header.hash(),
+        })
+    }
+
     fn request_receipt_outcome(
         &mut self,
         receipt_id: CryptoHash,
@@ -424,6 +622,13 @@ impl ViewClientActor {
             .map_err(|e| e.into())
     }
 
+    /// Drops the cached chain data if the client undid blocks since the last request.
+    fn check_undone_blocks(&mut self) {
+        if let Err(err) = self.chain.clear_caches_if_undone() {
+            error!(target: "view_client", "Failed to check for undone blocks: {}", err);
+        }
+    }
+
     fn get_height(&self, head: &Tip) -> BlockHeight {
         #[cfg(feature = "adversarial")]
         {
@@ -445,44 +650,46 @@ impl Handler<Query> for ViewClientActor {
     type Result = Result<Option<QueryResponse>, String>;
 
     fn handle(&mut self, msg: Query, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.handle_query(msg)
     }
 }
 
 /// Handles retrieving block from the chain.
 impl Handler<GetBlock> for ViewClientActor {
-    type Result = Result<BlockView, String>;
+    type Result = Result<BlockView, GetBlockError>;
 
     fn handle(&mut self, msg: GetBlock, _: &mut Self::Context) -> Self::Result {
-        match msg.0 {
+        self.check_undone_blocks();
+        let block = match msg.0 {
             BlockReference::Finality(finality) => {
-                let block_hash =
-                    self.get_block_hash_by_finality(&finality).map_err(|e| e.to_string())?;
-                self.chain.get_block(&block_hash).map(Clone::clone)
-            }
-            BlockReference::BlockId(BlockId::Height(height)) => {
-                self.chain.get_block_by_height(height).map(Clone::clone)
-            }
-            BlockReference::BlockId(BlockId::Hash(hash)) => {
-                self.chain.get_block(&hash).map(Clone::clone)
+                let block_hash = self
+                    .get_block_hash_by_finality(&finality)
+                    .map_err(|err| GetBlockError::InternalError(err.to_string()))?;
+                self.chain
+                    .get_block(&block_hash)
+                    .map(Clone::clone)
+                    .map_err(|err| self.get_block_error(None, err))?
             }
+            BlockReference::BlockId(block_id) => self.get_block_by_id(&block_id)?,
             BlockReference::SyncCheckpoint(sync_checkpoint) => {
-                if let Some(block_hash) = self
-                    .get_block_hash_by_sync_checkpoint(&sync_checkpoint)
-                    .map_err(|e| e.to_string())?
+                if let Some(block_hash) =
+                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint)
+                        .map_err(|err| GetBlockError::InternalError(err.to_string()))?
                 {
-                    self.chain.get_block(&block_hash).map(Clone::clone)
+                    self.chain
+                        .get_block(&block_hash)
+                        .map(Clone::clone)
+                        .map_err(|err| self.get_block_error(None, err))?
                 } else {
-                    return Err("There are no fully synchronized blocks yet".into());
+                    return Err(GetBlockError::NotSyncedYet);
                 }
             }
-        }
-        .and_then(|block| {
-            self.runtime_adapter
-                .get_block_producer(&block.header().epoch_id(), block.header().height())
-                .map(|author| BlockView::from_author_block(author, block))
-        })
-        .map_err(|err| err.to_string())
+        };
+        self.runtime_adapter
+            .get_block_producer(&block.header().epoch_id(), block.header().height())
+            .map(|author| BlockView::from_author_block(author, block))
+            .map_err(|err| GetBlockError::InternalError(err.to_string()))
     }
 }
 
@@ -490,6 +697,7 @@ impl Handler<GetBlockWithMerkleTree> for ViewClientActor {
     type Result = Result<(BlockView, PartialMerkleTree), String>;
 
     fn handle(&mut self, msg: GetBlockWithMerkleTree, ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         let block_view = self.handle(GetBlock(msg.0), ctx)?;
         self.chain
             .mut_store()
@@ -500,49 +708,37 @@ impl Handler<GetBlockWithMerkleTree> for ViewClientActor {
 }
 
 impl Handler<GetChunk> for ViewClientActor {
-    type Result = Result<ChunkView, String>;
+    type Result = Result<ChunkView, GetChunkError>;
 
     fn handle(&mut self, msg: GetChunk, _: &mut Self::Context) -> Self::Result {
-        let get_chunk_from_block = |block: Result<Block, near_chain::Error>,
-                                    shard_id: ShardId,
-                                    chain: &mut Chain|
-         -> Result<ShardChunk, near_chain::Error> {
-            let block = block?;
-            let chunk_header = block
-                .chunks()
-                .get(shard_id as usize)
-                .ok_or_else(|| near_chain::Error::from(ErrorKind::InvalidShardId(shard_id)))?
-                .clone();
-            let chunk_hash = chunk_header.chunk_hash();
-            chain.get_chunk(&chunk_hash).and_then(|chunk| {
-                ShardChunk::with_header(chunk.clone(), chunk_header).ok_or(near_chain::Error::from(
-                    ErrorKind::Other(format!(
-                        "Mismatched versions for chunk with hash {}",
-                        chunk_hash.0
-                    )),
-                ))
-            })
+        self.check_undone_blocks();
+        let (chunk, _) = self.get_chunk(msg)?;
+        let author = self.get_chunk_author(&chunk)?;
+        Ok(ChunkView::from_author_chunk(author, chunk))
+    }
+}
+
+impl Handler<GetChunkWithProofs> for ViewClientActor {
+    type Result = Result<ChunkWithProofsView, GetChunkError>;
+
+    fn handle(&mut self, msg: GetChunkWithProofs, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let (chunk, block) = self.get_chunk(msg.0)?;
+        let author = self.get_chunk_author(&chunk)?;
+        let proofs = ChunkProofsView {
+            transactions: merklize(chunk.transactions()).1,
+            outgoing_receipts: merklize(
+                &self.runtime_adapter.build_receipts_hashes(chunk.receipts()),
+            )
+            .1,
+            chunk_header: block.and_then(|block| {
+                Block::compute_chunk_headers_root(block.chunks().iter())
+                    .1
+                    .get(chunk.shard_id() as usize)
+                    .cloned()
+            }),
         };
-        match msg {
-            GetChunk::ChunkHash(chunk_hash) => self.chain.get_chunk(&chunk_hash).map(Clone::clone),
-            GetChunk::BlockHash(block_hash, shard_id) => {
-                let block = self.chain.get_block(&block_hash).map(Clone::clone);
-                get_chunk_from_block(block, shard_id, &mut self.chain)
-            }
-            GetChunk::Height(height, shard_id) => {
-                let block = self.chain.get_block_by_height(height).map(Clone::clone);
-                get_chunk_from_block(block, shard_id, &mut self.chain)
-            }
-        }
-        .and_then(|chunk| {
-            let chunk_inner = chunk.cloned_header().take_inner();
-            let epoch_id =
-                self.runtime_adapter.get_epoch_id_from_prev_block(&chunk_inner.prev_block_hash)?;
-            self.runtime_adapter
-                .get_chunk_producer(&epoch_id, chunk_inner.height_created, chunk_inner.shard_id)
-                .map(|author| ChunkView::from_author_chunk(author, chunk))
-        })
-        .map_err(|err| err.to_string())
+        Ok(ChunkWithProofsView { chunk: ChunkView::from_author_chunk(author, chunk), proofs })
     }
 }
 
@@ -550,6 +746,7 @@ impl Handler<TxStatus> for ViewClientActor {
     type Result = Result<Option<FinalExecutionOutcomeViewEnum>, TxStatusError>;
 
     fn handle(&mut self, msg: TxStatus, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.get_tx_status(msg.tx_hash, msg.signer_account_id, msg.fetch_receipt)
     }
 }
@@ -558,7 +755,13 @@ impl Handler<GetValidatorInfo> for ViewClientActor {
     type Result = Result<EpochValidatorInfo, String>;
 
     fn handle(&mut self, msg: GetValidatorInfo, _: &mut Self::Context) -> Self::Result {
-        self.maybe_block_id_to_block_hash(msg.block_id)
+        self.check_undone_blocks();
+        let block_hash = match msg.epoch_reference {
+            EpochReference::EpochId(epoch_id) => self.get_epoch_last_block_hash(&epoch_id),
+            EpochReference::BlockId(block_id) => self.maybe_block_id_to_block_hash(Some(block_id)),
+            EpochReference::Latest => self.maybe_block_id_to_block_hash(None),
+        };
+        block_hash
             .and_then(|block_hash| self.runtime_adapter.get_validator_info(&block_hash))
             .map_err(|err| err.to_string())
     }
@@ -568,6 +771,7 @@ impl Handler<GetValidatorOrdered> for ViewClientActor {
     type Result = Result<Vec<ValidatorStakeView>, String>;
 
     fn handle(&mut self, msg: GetValidatorOrdered, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.maybe_block_id_to_block_hash(msg.block_id)
             .and_then(|block_hash| self.chain.get_block_header(&block_hash).map(|h| h.clone()))
             .and_then(|header| {
@@ -585,6 +789,7 @@ impl Handler<GetStateChangesInBlock> for ViewClientActor {
     type Result = Result<StateChangesKindsView, String>;
 
     fn handle(&mut self, msg: GetStateChangesInBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.chain
             .store()
             .get_state_changes_in_block(&msg.block_hash)
@@ -598,6 +803,7 @@ impl Handler<GetStateChanges> for ViewClientActor {
     type Result = Result<StateChangesView, String>;
 
     fn handle(&mut self, msg: GetStateChanges, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.chain
             .store()
             .get_state_changes(&msg.block_hash, &msg.state_changes_request.into())
@@ -618,6 +824,7 @@ impl Handler<GetNextLightClientBlock> for ViewClientActor {
     type Result = Result<Option<LightClientBlockView>, String>;
 
     fn handle(&mut self, request: GetNextLightClientBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         let last_block_header =
             self.chain.get_block_header(&request.last_block_hash).map_err(|err| err.to_string())?;
         let last_epoch_id = last_block_header.epoch_id().clone();
@@ -661,6 +868,7 @@ impl Handler<GetExecutionOutcome> for ViewClientActor {
     type Result = Result<GetExecutionOutcomeResponse, String>;
 
     fn handle(&mut self, msg: GetExecutionOutcome, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         let (id, target_shard_id) = match msg.id {
             TransactionOrReceiptId::Transaction { transaction_hash, sender_id } => {
                 (transaction_hash, self.runtime_adapter.account_id_to_shard_id(&sender_id))
@@ -729,6 +937,7 @@ impl Handler<GetExecutionOutcomesForBlock> for ViewClientActor {
     type Result = Result<HashMap<ShardId, Vec<ExecutionOutcomeWithIdView>>, String>;
 
     fn handle(&mut self, msg: GetExecutionOutcomesForBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         Ok(self
             .chain
             .get_block_execution_outcomes(&msg.block_hash)
@@ -743,6 +952,7 @@ impl Handler<GetReceipt> for ViewClientActor {
     type Result = Result<Option<ReceiptView>, String>;
 
     fn handle(&mut self, msg: GetReceipt, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         Ok(self
             .chain
             .mut_store()
@@ -752,10 +962,54 @@ impl Handler<GetReceipt> for ViewClientActor {
     }
 }
 
+impl Handler<GetReceiptWithOutcome> for ViewClientActor {
+    type Result = Result<ReceiptWithOutcomeView, GetReceiptError>;
+
+    fn handle(&mut self, msg: GetReceiptWithOutcome, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let receipt = self
+            .chain
+            .mut_store()
+            .get_receipt(&msg.receipt_id)
+            .map_err(|e| GetReceiptError::InternalError(e.to_string()))?
+            .cloned()
+            .ok_or(GetReceiptError::UnknownReceipt(msg.receipt_id))?;
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(&receipt.receiver_id);
+        let outcome = match self.chain.get_execution_outcome(&msg.receipt_id) {
+            Ok(outcome) => Some(outcome),
+            Err(e) => match e.kind() {
+                ErrorKind::DBNotFoundErr(_) => None,
+                _ => return Err(GetReceiptError::InternalError(e.to_string())),
+            },
+        };
+        let (block_hash, block_height, chunk_hash) = match &outcome {
+            Some(outcome) => {
+                let block = self
+                    .chain
+                    .get_block(&outcome.block_hash)
+                    .map_err(|e| GetReceiptError::InternalError(e.to_string()))?;
+                let chunk_hash =
+                    block.chunks().get(shard_id as usize).map(|chunk| chunk.chunk_hash().0);
+                (Some(outcome.block_hash), Some(block.header().height()), chunk_hash)
+            }
+            None => (None, None, None),
+        };
+        Ok(ReceiptWithOutcomeView {
+            receipt: receipt.into(),
+            shard_id,
+            block_hash,
+            block_height,
+            chunk_hash,
+            outcome: outcome.map(Into::into),
+        })
+    }
+}
+
 impl Handler<GetBlockProof> for ViewClientActor {
     type Result = Result<GetBlockProofResponse, String>;
 
     fn handle(&mut self, msg: GetBlockProof, _: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         self.chain.check_block_final_and_canonical(&msg.block_hash).map_err(|e| e.to_string())?;
         self.chain
             .check_block_final_and_canonical(&msg.head_block_hash)
@@ -774,6 +1028,7 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
     type Result = NetworkViewClientResponses;
 
     fn handle(&mut self, msg: NetworkViewClientMessages, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         match msg {
             #[cfg(feature = "adversarial")]
             NetworkViewClientMessages::Adversarial(adversarial_msg) => {
@@ -1007,18 +1262,55 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                     }
                 }
             }
-            NetworkViewClientMessages::StateRequestPart { shard_id, sync_hash, part_id } => {
+            NetworkViewClientMessages::StateRequestPart {
+                shard_id,
+                sync_hash,
+                part_id,
+                requester,
+            } => {
+                if !self.state_part_limits.allow_request(&requester, Instant::now()) {
+                    debug!(target: "sync", "Throttling state part requests of {}", requester);
+                    near_metrics::inc_counter_vec(
+                        &metrics::STATE_PART_REQUESTS_TOTAL,
+                        &["throttled"],
+                    );
+                    return NetworkViewClientResponses::NoResponse;
+                }
                 trace!(target: "sync", "Computing state request part {} {} {}", shard_id, sync_hash, part_id);
                 let state_response = match self.chain.check_sync_hash_validity(&sync_hash) {
                     Ok(true) => {
-                        let part = match self
-                            .chain
-                            .get_state_response_part(shard_id, part_id, sync_hash)
-                        {
-                            Ok(part) => Some((part_id, part)),
-                            Err(e) => {
-                                error!(target: "sync", "Cannot build sync part #{:?} (get_state_response_part): {}", part_id, e);
-                                None
+                        let key = (sync_hash, shard_id, part_id);
+                        let part = if let Some(part) = self.state_part_limits.get_part(&key) {
+                            near_metrics::inc_counter_vec(
+                                &metrics::STATE_PART_REQUESTS_TOTAL,
+                                &["cached"],
+                            );
+                            Some((part_id, part))
+                        } else {
+                            let _slot = match self.state_part_limits.start_generation() {
+                                Some(slot) => slot,
+                                None => {
+                                    debug!(target: "sync", "Too many state parts are being generated, dropping request of part {} of shard {}", part_id, shard_id);
+                                    near_metrics::inc_counter_vec(
+                                        &metrics::STATE_PART_REQUESTS_TOTAL,
+                                        &["busy"],
+                                    );
+                                    return NetworkViewClientResponses::NoResponse;
+                                }
+                            };
+                            match self.chain.get_state_response_part(shard_id, part_id, sync_hash) {
+                                Ok(part) => {
+                                    near_metrics::inc_counter_vec(
+                                        &metrics::STATE_PART_REQUESTS_TOTAL,
+                                        &["generated"],
+                                    );
+                                    self.state_part_limits.insert_part(key, part.clone());
+                                    Some((part_id, part))
+                                }
+                                Err(e) => {
+                                    error!(target: "sync", "Cannot build sync part #{:?} (get_state_response_part): {}", part_id, e);
+                                    None
+                                }
                             }
                         };
 
@@ -1091,6 +1383,7 @@ impl Handler<GetGasPrice> for ViewClientActor {
     type Result = Result<GasPriceView, String>;
 
     fn handle(&mut self, msg: GetGasPrice, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
         let header = self
             .maybe_block_id_to_block_hash(msg.block_id)
             .and_then(|block_hash| self.chain.get_block_header(&block_hash));
@@ -1098,6 +1391,162 @@ impl Handler<GetGasPrice> for ViewClientActor {
     }
 }
 
+impl Handler<GetFeeHistory> for ViewClientActor {
+    type Result = Result<FeeHistoryView, String>;
+
+    fn handle(&mut self, msg: GetFeeHistory, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let mut block_hash =
+            self.maybe_block_id_to_block_hash(msg.block_id).map_err(|e| e.to_string())?;
+        let block_count = std::cmp::min(msg.block_count, MAX_FEE_HISTORY_BLOCKS);
+        let mut blocks = vec![];
+        while (blocks.len() as NumBlocks) < block_count {
+            let fee_info = match self.chain.get_block_fee_info(&block_hash) {
+                Ok(fee_info) => fee_info.clone(),
+                // Reached genesis, garbage collected blocks or blocks processed before the
+                // fee info column was introduced.
+                Err(e) => match e.kind() {
+                    ErrorKind::DBNotFoundErr(_) => break,
+                    _ => return Err(e.to_string()),
+                },
+            };
+            blocks.push(BlockFeeView::new(block_hash, &fee_info));
+            block_hash =
+                *self.chain.get_block_header(&block_hash).map_err(|e| e.to_string())?.prev_hash();
+        }
+        blocks.reverse();
+        Ok(FeeHistoryView { blocks })
+    }
+}
+
+impl Handler<GetGasPriceHistory> for ViewClientActor {
+    type Result = Result<FeeHistoryView, String>;
+
+    fn handle(&mut self, msg: GetGasPriceHistory, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let to_height = match msg.to_height {
+            Some(to_height) => to_height,
+            None => self.chain.head().map_err(|e| e.to_string())?.height,
+        };
+        if to_height < msg.from_height {
+            return Err(format!("Invalid range of heights {}..={}", msg.from_height, to_height));
+        }
+        if to_height - msg.from_height >= MAX_FEE_HISTORY_BLOCKS {
+            return Err(format!("Range of heights is longer than {}", MAX_FEE_HISTORY_BLOCKS));
+        }
+        let mut blocks = vec![];
+        for height in msg.from_height..=to_height {
+            let block_hash = match self.chain.mut_store().get_block_hash_by_height(height) {
+                Ok(block_hash) => block_hash,
+                // Skipped heights and heights after the head.
+                Err(e) => match e.kind() {
+                    ErrorKind::DBNotFoundErr(_) => continue,
+                    _ => return Err(e.to_string()),
+                },
+            };
+            match self.chain.get_block_fee_info(&block_hash) {
+                Ok(fee_info) => blocks.push(BlockFeeView::new(block_hash, fee_info)),
+                // Garbage collected blocks or blocks processed before the fee info column was
+                // introduced.
+                Err(e) => match e.kind() {
+                    ErrorKind::DBNotFoundErr(_) => continue,
+                    _ => return Err(e.to_string()),
+                },
+            }
+        }
+        Ok(FeeHistoryView { blocks })
+    }
+}
+
+impl Handler<GetIdempotencyKey> for ViewClientActor {
+    type Result = Result<IdempotencyKeyView, String>;
+
+    fn handle(&mut self, msg: GetIdempotencyKey, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let mut executions = self
+            .chain
+            .store()
+            .get_idempotency_key_executions(&msg.account_id, &msg.key)
+            .map_err(|e| e.to_string())?;
+        // Blocks on forks are only garbage collected later, skip executions in them.
+        executions.retain(|execution| {
+            match self.chain.mut_store().get_block_hash_by_height(execution.block_height) {
+                Ok(block_hash) => block_hash == execution.block_hash,
+                Err(_) => false,
+            }
+        });
+        executions.sort_by_key(|execution| execution.block_height);
+        Ok(IdempotencyKeyView { account_id: msg.account_id, key: to_base64(&msg.key), executions })
+    }
+}
+
+impl Handler<GetWatchedAccountChanges> for ViewClientActor {
+    type Result = Result<WatchedAccountChangesView, String>;
+
+    fn handle(&mut self, msg: GetWatchedAccountChanges, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        if !self.config.watched_accounts.contains(&msg.account_id) {
+            return Err(format!("Account {} is not watched by this node", msg.account_id));
+        }
+        let limit = std::cmp::min(msg.limit, MAX_WATCHED_ACCOUNT_CHANGES);
+        let changes = self
+            .chain
+            .store()
+            .get_watched_account_changes(
+                &msg.account_id,
+                msg.from_block_height,
+                msg.from_index,
+                limit as usize,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(WatchedAccountChangesView { account_id: msg.account_id, changes })
+    }
+}
+
+impl Handler<GetProtocolConfig> for ViewClientActor {
+    type Result = Result<ProtocolConfigView, GetBlockError>;
+
+    fn handle(&mut self, msg: GetProtocolConfig, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let block_id = match msg.0 {
+            BlockReference::BlockId(block_id) => block_id,
+            BlockReference::Finality(finality) => BlockId::Hash(
+                self.get_block_hash_by_finality(&finality)
+                    .map_err(|err| GetBlockError::InternalError(err.to_string()))?,
+            ),
+            BlockReference::SyncCheckpoint(sync_checkpoint) => BlockId::Hash(
+                self.get_block_hash_by_sync_checkpoint(&sync_checkpoint)
+                    .map_err(|err| GetBlockError::InternalError(err.to_string()))?
+                    .ok_or(GetBlockError::NotSyncedYet)?,
+            ),
+        };
+        // Headers are kept when the blocks are garbage collected, so old blocks can be used too.
+        let epoch_id = match &block_id {
+            BlockId::Height(height) => self.chain.get_header_by_height(*height),
+            BlockId::Hash(hash) => self.chain.get_block_header(hash),
+        }
+        .map(|header| header.epoch_id().clone())
+        .map_err(|err| self.get_block_error(Some(&block_id), err))?;
+        self.runtime_adapter
+            .get_protocol_config(&epoch_id)
+            .map_err(|err| GetBlockError::InternalError(err.to_string()))
+    }
+}
+
+impl Handler<GetProtocolFeatures> for ViewClientActor {
+    type Result = Result<ProtocolFeaturesView, String>;
+
+    fn handle(&mut self, _msg: GetProtocolFeatures, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_undone_blocks();
+        let head = self.chain.head().map_err(|e| e.to_string())?;
+        let epoch_protocol_version = self
+            .runtime_adapter
+            .get_epoch_protocol_version(&head.epoch_id)
+            .map_err(|e| e.to_string())?;
+        Ok(ProtocolFeaturesView::new(epoch_protocol_version))
+    }
+}
+
 /// Starts the View Client in a new arbiter (thread).
 pub fn start_view_client(
     validator_account_id: Option<AccountId>,
@@ -1108,6 +1557,7 @@ pub fn start_view_client(
     #[cfg(feature = "adversarial")] adv: Arc<RwLock<AdversarialControls>>,
 ) -> Addr<ViewClientActor> {
     let request_manager = Arc::new(RwLock::new(ViewClientRequestManager::new()));
+    let state_part_limits = Arc::new(StatePartLimits::new());
     SyncArbiter::start(config.view_client_threads, move || {
         // ViewClientActor::start_in_arbiter(&Arbiter::current(), move |_ctx| {
         let validator_account_id1 = validator_account_id.clone();
@@ -1115,6 +1565,7 @@ pub fn start_view_client(
         let network_adapter1 = network_adapter.clone();
         let config1 = config.clone();
         let request_manager1 = request_manager.clone();
+        let state_part_limits1 = state_part_limits.clone();
         ViewClientActor::new(
             validator_account_id1,
             &chain_genesis,
@@ -1122,6 +1573,7 @@ pub fn start_view_client(
             network_adapter1,
             config1,
             request_manager1,
+            state_part_limits1,
             #[cfg(feature = "adversarial")]
             adv.clone(),
         )
@@ -46,6 +46,7 @@ fn test_verify_block_double_sign_challenge() {
     let mut block_merkle_tree = PartialMerkleTree::default();
     block_merkle_tree.insert(*genesis.hash());
     let b2 = Block::produce(
+        PROTOCOL_VERSION,
         PROTOCOL_VERSION,
         genesis.header(),
         2,
@@ -344,6 +345,7 @@ fn test_verify_chunk_invalid_state_challenge() {
         client.chain.mut_store().get_block_merkle_tree(&last_block.hash()).unwrap().clone();
     block_merkle_tree.insert(*last_block.hash());
     let block = Block::produce(
+        PROTOCOL_VERSION,
         PROTOCOL_VERSION,
         &last_block.header(),
         last_block.header().height() + 1,
@@ -380,6 +382,8 @@ fn test_verify_chunk_invalid_state_challenge() {
             &economics_config,
             DoomslugThresholdMode::NoApprovals,
             &genesis_block,
+            None,
+            None,
         );
 
         chain_update.create_chunk_state_challenge(&last_block, &block, &block.chunks()[0]).unwrap()
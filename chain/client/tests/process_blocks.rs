@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::iter::FromIterator;
 use std::path::Path;
@@ -9,13 +9,13 @@ use actix::System;
 use futures::{future, FutureExt};
 use num_rational::Rational;
 
-use near_chain::chain::NUM_EPOCHS_TO_KEEP_STORE_DATA;
-use near_chain::types::LatestKnown;
+use near_chain::chain::{collect_receipts_from_response, NUM_EPOCHS_TO_KEEP_STORE_DATA};
+use near_chain::types::{ApplyTransactionResult, LatestKnown};
 use near_chain::validate::validate_chunk_with_chunk_extra;
 use near_chain::{
     Block, ChainGenesis, ChainStore, ChainStoreAccess, ErrorKind, Provenance, RuntimeAdapter,
 };
-use near_chain_configs::{ClientConfig, Genesis};
+use near_chain_configs::{BlockLimitsUpgrade, ClientConfig, Genesis, DEFAULT_MAX_BLOCK_SIZE};
 use near_chunks::{ChunkStatus, ShardsManager};
 use near_client::test_utils::{create_chunk_on_height, setup_mock_all_validators};
 use near_client::test_utils::{setup_client, setup_mock, TestEnv};
@@ -34,20 +34,23 @@ use near_network::{
 use near_primitives::block::{Approval, ApprovalInner};
 use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::{hash, CryptoHash, Digest};
-use near_primitives::merkle::verify_hash;
+use near_primitives::merkle::{merklize, verify_hash};
 use near_primitives::sharding::{
-    EncodedShardChunk, ReedSolomonWrapper, ShardChunkHeader, ShardChunkHeaderV2,
+    EncodedShardChunk, ReceiptProof, ReedSolomonWrapper, ShardChunkHeader, ShardChunkHeaderV2,
+    ShardProof,
 };
 use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader};
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction, Transaction,
 };
-use near_primitives::types::{AccountId, BlockHeight, EpochId, NumBlocks, ValidatorStake};
+use near_primitives::types::{
+    AccountId, BlockHeight, ChunkExtra, EpochId, NumBlocks, ShardId, ValidatorStake,
+};
 use near_primitives::utils::to_timestamp;
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
 use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::{QueryRequest, QueryResponseKind};
-use near_store::test_utils::create_test_store;
+use near_store::test_utils::{create_test_split_store, create_test_store};
 use neard::config::{GenesisExt, TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
 use neard::NEAR_BASE;
 
@@ -190,6 +193,7 @@ fn receive_network_block() {
             let signer = InMemoryValidatorSigner::from_seed("test1", KeyType::ED25519, "test1");
             block_merkle_tree.insert(last_block.header.hash);
             let block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header.clone().into(),
                 last_block.header.height + 1,
@@ -260,6 +264,7 @@ fn produce_block_with_approvals() {
             let signer1 = InMemoryValidatorSigner::from_seed("test2", KeyType::ED25519, "test2");
             block_merkle_tree.insert(last_block.header.hash);
             let block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header.clone().into(),
                 last_block.header.height + 1,
@@ -422,6 +427,7 @@ fn invalid_blocks_common(is_requested: bool) {
             let signer = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "test");
             block_merkle_tree.insert(last_block.header.hash);
             let valid_block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header.clone().into(),
                 last_block.header.height + 1,
@@ -532,6 +538,7 @@ fn invalid_blocks() {
             let signer = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "test");
             // Send block with invalid chunk mask
             let mut block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header.clone().into(),
                 last_block.header.height + 1,
@@ -563,6 +570,7 @@ fn invalid_blocks() {
             // Send proper block.
             block_merkle_tree.insert(last_block.header.hash);
             let block2 = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &last_block.header.clone().into(),
                 last_block.header.height + 1,
@@ -806,6 +814,9 @@ fn client_sync_headers() {
             sent_bytes_per_sec: 0,
             received_bytes_per_sec: 0,
             known_producers: vec![],
+            peer_protocol_versions: BTreeMap::new(),
+            peer_clock_skews: HashMap::new(),
+            peer_transports: HashMap::new(),
             #[cfg(feature = "metric_recorder")]
             metric_recorder: MetricRecorder::default(),
             peer_counter: 0,
@@ -1250,6 +1261,30 @@ fn test_gc_with_epoch_length_common(epoch_length: NumBlocks) {
     assert_eq!(env.clients[0].chain.store().chunk_tail().unwrap(), epoch_length - 1);
 }
 
+/// No garbage is collected while it is paused, the heights left are collected once resumed.
+#[test]
+fn test_gc_paused() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0", "test1"], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env =
+        TestEnv::new_with_runtime(chain_genesis, 1, 1, create_nightshade_runtimes(&genesis, 1));
+    env.clients[0].gc_paused = true;
+    let last_height = epoch_length * (NUM_EPOCHS_TO_KEEP_STORE_DATA + 1);
+    for i in 1..=last_height {
+        env.produce_block(0, i);
+    }
+    assert_eq!(env.clients[0].chain.store().tail().unwrap(), 0);
+    assert!(env.clients[0].chain.gc_debt().unwrap() > 0);
+
+    env.clients[0].gc_paused = false;
+    env.produce_block(0, last_height + 1);
+    assert!(env.clients[0].chain.store().tail().unwrap() > 0);
+    assert_eq!(env.clients[0].chain.gc_debt().unwrap(), 0);
+}
+
 #[test]
 fn test_gc_with_epoch_length() {
     for i in 3..20 {
@@ -1466,6 +1501,67 @@ fn test_gc_fork_tail() {
     assert_eq!(env.clients[1].chain.store().fork_tail().unwrap(), 3);
 }
 
+/// An archival node with a cold store still serves the garbage collected blocks and state, but
+/// not the forks.
+#[test]
+fn test_gc_archival_split_store() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0", "test1"], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let runtime = Arc::new(neard::NightshadeRuntime::new(
+        Path::new("."),
+        create_test_split_store(),
+        &genesis,
+        vec![],
+        vec![],
+    ));
+    let mut env = TestEnv::new_with_runtime(chain_genesis, 1, 1, vec![runtime]);
+    env.clients[0].config.archive = true;
+    env.clients[0].config.cold_store_path = Some("cold".into());
+
+    let b1 = env.clients[0].produce_block(1).unwrap().unwrap();
+    env.process_block(0, b1, Provenance::PRODUCED);
+    // Both are built on `b1`, `b2` ends up on a fork.
+    let b2 = env.clients[0].produce_block(2).unwrap().unwrap();
+    let b3 = env.clients[0].produce_block(3).unwrap().unwrap();
+    env.process_block(0, b2.clone(), Provenance::PRODUCED);
+    env.process_block(0, b3.clone(), Provenance::PRODUCED);
+    assert_eq!(env.clients[0].chain.head().unwrap().last_block_hash, *b3.hash());
+    let query_at_b3 = |env: &TestEnv| {
+        env.clients[0]
+            .runtime_adapter
+            .query(
+                0,
+                &b3.chunks()[0].prev_state_root(),
+                b3.header().height(),
+                b3.header().raw_timestamp(),
+                b3.header().hash(),
+                b3.header().epoch_id(),
+                &QueryRequest::ViewAccount { account_id: "test1".to_string() },
+            )
+            .unwrap()
+            .kind
+    };
+    let view = query_at_b3(&env);
+    let amount = match &view {
+        QueryResponseKind::ViewAccount(account) => account.amount,
+        kind => panic!("Unexpected query response {:?}", kind),
+    };
+
+    // Changes the account, so that its old state is garbage collected.
+    env.send_money(0);
+    for i in 4..=epoch_length * (NUM_EPOCHS_TO_KEEP_STORE_DATA + 2) {
+        env.produce_block(0, i);
+    }
+    assert!(env.clients[0].chain.store().tail().unwrap() > b3.header().height());
+    assert!(env.clients[0].chain.get_block(b3.hash()).is_ok());
+    assert!(env.clients[0].chain.get_block(b2.hash()).is_err());
+    assert_ne!(env.query_balance("test1".to_string()), amount);
+    assert_eq!(query_at_b3(&env), view);
+}
+
 #[test]
 fn test_tx_forwarding() {
     let mut chain_genesis = ChainGenesis::test();
@@ -2024,6 +2120,7 @@ fn test_validate_chunk_extra() {
         &chunk_extra,
         &block1.chunks()[0],
         &chunks.get(&0).cloned().unwrap(),
+        env.clients[0].chain.block_economics_config.gas_limit(PROTOCOL_VERSION),
     )
     .is_ok());
 }
@@ -2218,7 +2315,8 @@ fn test_epoch_protocol_version_change() {
     let epoch_length = 5;
     let mut genesis = Genesis::test(vec!["test0", "test1"], 2);
     genesis.config.epoch_length = epoch_length;
-    genesis.config.protocol_version = PROTOCOL_VERSION;
+    // Versions above `PROTOCOL_VERSION` aren't processed, so the chain upgrades to it.
+    genesis.config.protocol_version = PROTOCOL_VERSION - 1;
     let genesis_height = genesis.config.genesis_height;
     let chain_genesis = ChainGenesis::from(&genesis);
     let mut env =
@@ -2251,14 +2349,14 @@ fn test_epoch_protocol_version_change() {
 
         let mut block = env.clients[index].produce_block(i).unwrap().unwrap();
         // upgrade to new protocol version but in the second epoch one node vote for the old version.
-        if i != 10 {
+        if i == 10 {
             let validator_signer = InMemoryValidatorSigner::from_seed(
                 &format!("test{}", index),
                 KeyType::ED25519,
                 &format!("test{}", index),
             );
 
-            block.mut_header().get_mut().inner_rest.latest_protocol_version = PROTOCOL_VERSION + 1;
+            block.mut_header().get_mut().inner_rest.latest_protocol_version = PROTOCOL_VERSION - 1;
             block.mut_header().resign(&validator_signer);
         }
         for j in 0..2 {
@@ -2272,7 +2370,195 @@ fn test_epoch_protocol_version_change() {
         .runtime_adapter
         .get_epoch_protocol_version(last_block.header().epoch_id())
         .unwrap();
-    assert_eq!(protocol_version, PROTOCOL_VERSION + 1);
+    assert_eq!(protocol_version, PROTOCOL_VERSION);
+}
+
+/// Validators upgrade to a protocol version newer than this release supports. The blocks of the
+/// epoch that switches to it are refused, and a client doesn't start from a head in that epoch.
+#[test]
+fn test_unsupported_protocol_version() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0", "test1"], 2);
+    genesis.config.epoch_length = epoch_length;
+    genesis.config.protocol_version = PROTOCOL_VERSION;
+    let genesis_height = genesis.config.genesis_height;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env =
+        TestEnv::new_with_runtime(chain_genesis, 2, 2, create_nightshade_runtimes(&genesis, 2));
+    let mut refused = false;
+    for i in 1..=16 {
+        let head = env.clients[0].chain.head().unwrap();
+        let epoch_id = env.clients[0]
+            .runtime_adapter
+            .get_epoch_id_from_prev_block(&head.last_block_hash)
+            .unwrap();
+        let block_producer =
+            env.clients[0].runtime_adapter.get_block_producer(&epoch_id, i).unwrap();
+        let index = if block_producer == "test0".to_string() { 0 } else { 1 };
+        let (encoded_chunk, merkle_paths, receipts) =
+            create_chunk_on_height(&mut env.clients[index], i);
+        for j in 0..2 {
+            let mut chain_store =
+                ChainStore::new(env.clients[j].chain.store().owned_store(), genesis_height);
+            env.clients[j]
+                .shards_mgr
+                .distribute_encoded_chunk(
+                    encoded_chunk.clone(),
+                    merkle_paths.clone(),
+                    receipts.clone(),
+                    &mut chain_store,
+                )
+                .unwrap();
+        }
+
+        let mut block = env.clients[index].produce_block(i).unwrap().unwrap();
+        let validator_signer = InMemoryValidatorSigner::from_seed(
+            &format!("test{}", index),
+            KeyType::ED25519,
+            &format!("test{}", index),
+        );
+        block.mut_header().get_mut().inner_rest.latest_protocol_version = PROTOCOL_VERSION + 1;
+        block.mut_header().resign(&validator_signer);
+
+        let protocol_version = env.clients[0]
+            .runtime_adapter
+            .get_epoch_protocol_version(block.header().epoch_id())
+            .unwrap();
+        for j in 0..2 {
+            let (_, res) = env.clients[j].process_block(block.clone(), Provenance::NONE);
+            if protocol_version > PROTOCOL_VERSION {
+                match res.unwrap_err().kind() {
+                    ErrorKind::UnsupportedProtocolVersion(version, supported_version) => {
+                        assert_eq!(version, PROTOCOL_VERSION + 1);
+                        assert_eq!(supported_version, PROTOCOL_VERSION);
+                    }
+                    kind => panic!("Unexpected error {}", kind),
+                }
+            } else {
+                assert!(res.is_ok());
+                env.clients[j].run_catchup(&vec![]).unwrap();
+            }
+        }
+        if protocol_version > PROTOCOL_VERSION {
+            refused = true;
+            break;
+        }
+    }
+    assert!(refused);
+    // The head stays in the last epoch of the supported version.
+    let head = env.clients[0].chain.head().unwrap();
+    assert_eq!(
+        env.clients[0].runtime_adapter.get_epoch_protocol_version(&head.epoch_id).unwrap(),
+        PROTOCOL_VERSION
+    );
+}
+
+/// A client doesn't start on a chain whose head epoch has a newer protocol version than this
+/// release supports, and names the version to upgrade to.
+#[test]
+fn test_client_unsupported_protocol_version() {
+    init_test_logger();
+    let mut genesis = Genesis::test(vec!["test0"], 1);
+    genesis.config.protocol_version = PROTOCOL_VERSION + 1;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let runtime_adapter = create_nightshade_runtimes(&genesis, 1).pop().unwrap();
+    let err = Client::new(
+        ClientConfig::test(true, 10, 20, 1, false),
+        chain_genesis,
+        runtime_adapter,
+        Arc::new(MockNetworkAdapter::default()),
+        None,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains(&format!(
+        "Upgrade to a release that supports protocol version {}",
+        PROTOCOL_VERSION + 1
+    )));
+}
+
+/// Raise the chunk gas limit at a protocol upgrade and check that chunks switch to the new limit
+/// only after the upgrade, while a node that joins later still accepts the blocks produced
+/// before the upgrade.
+#[test]
+fn test_gas_limit_upgrade() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0", "test1"], 2);
+    genesis.config.epoch_length = epoch_length;
+    genesis.config.protocol_version = PROTOCOL_VERSION - 1;
+    let old_gas_limit = genesis.config.gas_limit;
+    let new_gas_limit = old_gas_limit * 2;
+    genesis.config.block_limits_upgrades = vec![BlockLimitsUpgrade {
+        protocol_version: PROTOCOL_VERSION,
+        gas_limit: new_gas_limit,
+        max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+    }];
+    let genesis_height = genesis.config.genesis_height;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env =
+        TestEnv::new_with_runtime(chain_genesis, 3, 2, create_nightshade_runtimes(&genesis, 3));
+    let mut blocks = vec![];
+    for i in 1..=16 {
+        let head = env.clients[0].chain.head().unwrap();
+        let epoch_id = env.clients[0]
+            .runtime_adapter
+            .get_epoch_id_from_prev_block(&head.last_block_hash)
+            .unwrap();
+        let block_producer =
+            env.clients[0].runtime_adapter.get_block_producer(&epoch_id, i).unwrap();
+        let index = if block_producer == "test0".to_string() { 0 } else { 1 };
+        let (encoded_chunk, merkle_paths, receipts) =
+            create_chunk_on_height(&mut env.clients[index], i);
+
+        for j in 0..3 {
+            let mut chain_store =
+                ChainStore::new(env.clients[j].chain.store().owned_store(), genesis_height);
+            env.clients[j]
+                .shards_mgr
+                .distribute_encoded_chunk(
+                    encoded_chunk.clone(),
+                    merkle_paths.clone(),
+                    receipts.clone(),
+                    &mut chain_store,
+                )
+                .unwrap();
+        }
+
+        // Blocks vote for `PROTOCOL_VERSION`, the chain upgrades to it.
+        let block = env.clients[index].produce_block(i).unwrap().unwrap();
+        for j in 0..2 {
+            let (_, res) = env.clients[j].process_block(block.clone(), Provenance::NONE);
+            assert!(res.is_ok());
+            env.clients[j].run_catchup(&vec![]).unwrap();
+        }
+
+        let protocol_version = env.clients[0]
+            .runtime_adapter
+            .get_epoch_protocol_version(block.header().epoch_id())
+            .unwrap();
+        let expected_gas_limit =
+            if protocol_version == PROTOCOL_VERSION { new_gas_limit } else { old_gas_limit };
+        assert_eq!(block.chunks()[0].gas_limit(), expected_gas_limit);
+        blocks.push(block);
+    }
+    let last_block = blocks.last().unwrap();
+    let protocol_version = env.clients[0]
+        .runtime_adapter
+        .get_epoch_protocol_version(last_block.header().epoch_id())
+        .unwrap();
+    assert_eq!(protocol_version, PROTOCOL_VERSION);
+    assert_eq!(last_block.chunks()[0].gas_limit(), new_gas_limit);
+
+    // Node that joins after the upgrade validates old blocks against the old limit.
+    for block in blocks {
+        let (_, res) = env.clients[2].process_block(block, Provenance::NONE);
+        assert!(res.is_ok());
+        env.clients[2].run_catchup(&vec![]).unwrap();
+    }
+    assert_eq!(env.clients[2].chain.head().unwrap().height, 16);
 }
 
 /// Final state should be consistent when a node switches between forks in the following scenario
@@ -2439,3 +2725,283 @@ fn test_fork_execution_outcome() {
         env.clients[0].chain.store().get_outcomes_by_id(&receipt_id).unwrap();
     assert!(receipt_execution_outcomes.is_empty());
 }
+
+/// A client with optimistic block application applies the chunk of one shard while the block
+/// waits for the chunk of the other, and gets the same results as a client applying both chunks
+/// once the block is complete.
+#[test]
+fn test_optimistic_block_application() {
+    init_test_logger();
+    let chain_genesis = ChainGenesis::test();
+    let genesis_height = chain_genesis.height;
+    // Both validators track both shards. The third client applies chunks optimistically.
+    let validators = vec!["test0", "test1"];
+    let mut clients: Vec<Client> = vec!["test0", "test1", "test0"]
+        .into_iter()
+        .map(|account_id| {
+            setup_client(
+                create_test_store(),
+                vec![validators.clone()],
+                1,
+                2,
+                Some(account_id),
+                false,
+                Arc::new(MockNetworkAdapter::default()),
+                chain_genesis.clone(),
+            )
+        })
+        .collect();
+    clients[2].chain.set_optimistic_block_application(true);
+
+    let block1 = clients[1].produce_block(1).unwrap().unwrap();
+    for client in clients.iter_mut() {
+        let (_, res) = client.process_block(block1.clone(), Provenance::NONE);
+        res.unwrap();
+    }
+
+    // The chunks of both shards at height 2, which the first client includes in its block.
+    let epoch_id = block1.header().epoch_id().clone();
+    let mut chunks = vec![];
+    for shard_id in 0..2 {
+        let chunk_producer =
+            clients[0].runtime_adapter.get_chunk_producer(&epoch_id, 2, shard_id).unwrap();
+        let i = validators.iter().position(|account_id| *account_id == chunk_producer).unwrap();
+        let chunk = clients[i]
+            .produce_chunk(
+                *block1.hash(),
+                &epoch_id,
+                block1.chunks()[shard_id as usize].clone(),
+                2,
+                shard_id,
+            )
+            .unwrap()
+            .unwrap();
+        chunks.push(chunk);
+    }
+    let mut chain_store = ChainStore::new(clients[0].chain.store().owned_store(), genesis_height);
+    for (encoded_chunk, merkle_paths, receipts) in chunks.iter().cloned() {
+        clients[0]
+            .shards_mgr
+            .distribute_encoded_chunk(encoded_chunk, merkle_paths, receipts, &mut chain_store)
+            .unwrap();
+    }
+    let block = clients[0].produce_block(2).unwrap().unwrap();
+    assert!(block.chunks().iter().all(|chunk_header| chunk_header.height_included() == 2));
+    let (_, res) = clients[0].process_block(block.clone(), Provenance::PRODUCED);
+    res.unwrap();
+
+    let (_, res) = clients[2].process_block(block.clone(), Provenance::NONE);
+    assert!(matches!(res.unwrap_err().kind(), ErrorKind::ChunksMissing(_)));
+    // The chunk of shard 0 arrives, and only the receipts of the chunk of shard 1.
+    let mut chain_store = ChainStore::new(clients[2].chain.store().owned_store(), genesis_height);
+    let (encoded_chunk, merkle_paths, receipts) = chunks[0].clone();
+    clients[2]
+        .shards_mgr
+        .distribute_encoded_chunk(encoded_chunk, merkle_paths, receipts, &mut chain_store)
+        .unwrap();
+    let (encoded_chunk, merkle_paths, receipts) = chunks[1].clone();
+    let runtime_adapter = clients[2].runtime_adapter.clone();
+    let (_, receipt_proofs) = merklize(&runtime_adapter.build_receipts_hashes(&receipts));
+    let receipt_proofs = receipt_proofs
+        .into_iter()
+        .enumerate()
+        .map(|(to_shard_id, proof)| {
+            let to_shard_id = to_shard_id as ShardId;
+            let shard_receipts = receipts
+                .iter()
+                .filter(|receipt| {
+                    runtime_adapter.account_id_to_shard_id(&receipt.receiver_id) == to_shard_id
+                })
+                .cloned()
+                .collect();
+            ReceiptProof(shard_receipts, ShardProof { from_shard_id: 1, to_shard_id, proof })
+        })
+        .collect();
+    let mut chain_store_update = chain_store.store_update();
+    chain_store_update.save_partial_chunk(encoded_chunk.create_partial_encoded_chunk(
+        vec![],
+        receipt_proofs,
+        &merkle_paths,
+    ));
+    chain_store_update.commit().unwrap();
+
+    let accepted_blocks =
+        clients[2].process_blocks_with_missing_chunks(*block1.hash(), PROTOCOL_VERSION);
+    assert!(accepted_blocks.is_empty());
+    assert!(clients[2].chain.has_optimistic_apply_result(block.hash(), 0));
+    assert!(!clients[2].chain.has_optimistic_apply_result(block.hash(), 1));
+
+    // The chunk of shard 1 arrives, the block is processed with the result applied ahead.
+    let (encoded_chunk, merkle_paths, receipts) = chunks[1].clone();
+    clients[2]
+        .shards_mgr
+        .distribute_encoded_chunk(encoded_chunk, merkle_paths, receipts, &mut chain_store)
+        .unwrap();
+    let accepted_blocks =
+        clients[2].process_blocks_with_missing_chunks(*block1.hash(), PROTOCOL_VERSION);
+    assert_eq!(accepted_blocks.len(), 1);
+    assert!(!clients[2].chain.has_optimistic_apply_result(block.hash(), 0));
+    assert_eq!(clients[2].chain.head().unwrap().last_block_hash, *block.hash());
+    for shard_id in 0..2 {
+        let chunk_extra = clients[2].chain.get_chunk_extra(block.hash(), shard_id).unwrap().clone();
+        assert_eq!(&chunk_extra, clients[0].chain.get_chunk_extra(block.hash(), shard_id).unwrap());
+    }
+}
+
+/// Applying the chunks of a block in parallel gives the same chunk extras, outcomes and outgoing
+/// receipts as applying them one shard after the other.
+#[test]
+fn test_apply_chunks_in_parallel() {
+    init_test_logger();
+    let num_shards = 4;
+    let accounts = vec!["test0", "test1", "test2", "test3", "test4", "test5", "test6", "test7"];
+    let genesis = Genesis::test_sharded(accounts.clone(), 1, vec![1; num_shards]);
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let genesis_height = chain_genesis.height;
+    let mut env =
+        TestEnv::new_with_runtime(chain_genesis, 1, 1, create_nightshade_runtimes(&genesis, 1));
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    // Every account sends money to every other, most transfers go to another shard.
+    for sender in accounts.iter() {
+        let signer = InMemorySigner::from_seed(sender, KeyType::ED25519, sender);
+        for (i, receiver) in accounts.iter().filter(|receiver| *receiver != sender).enumerate() {
+            let tx = SignedTransaction::send_money(
+                i as u64 + 1,
+                sender.to_string(),
+                receiver.to_string(),
+                &signer,
+                100,
+                genesis_hash,
+            );
+            env.clients[0].process_tx(tx, false, false);
+        }
+    }
+    for i in 1..=6 {
+        env.produce_block(0, i);
+    }
+
+    let runtime_adapter = env.clients[0].runtime_adapter.clone();
+    let mut chain_store =
+        ChainStore::new(env.clients[0].chain.store().owned_store(), genesis_height);
+    let mut num_cross_shard_receipts = 0;
+    for height in 1..=6 {
+        let block = env.clients[0].chain.get_block_by_height(height).unwrap().clone();
+        let prev_block =
+            env.clients[0].chain.get_block(block.header().prev_hash()).unwrap().clone();
+        let mut outcomes_by_shard =
+            env.clients[0].chain.get_block_execution_outcomes(block.hash()).unwrap();
+        for (shard_id, (chunk_header, prev_chunk_header)) in
+            block.chunks().iter().zip(prev_block.chunks().iter()).enumerate()
+        {
+            let shard_id = shard_id as ShardId;
+            if chunk_header.height_included() != height {
+                // Without a new chunk the state of the shard only goes through the block changes.
+                let mut chunk_extra = env.clients[0]
+                    .chain
+                    .get_chunk_extra(prev_block.hash(), shard_id)
+                    .unwrap()
+                    .clone();
+                let apply_result = runtime_adapter
+                    .apply_transactions(
+                        shard_id,
+                        &chunk_extra.state_root,
+                        height,
+                        block.header().raw_timestamp(),
+                        prev_block.hash(),
+                        block.hash(),
+                        &[],
+                        &[],
+                        &chunk_extra.validator_proposals,
+                        block.header().gas_price(),
+                        chunk_extra.gas_limit,
+                        block.header().challenges_result(),
+                        *block.header().random_value(),
+                    )
+                    .unwrap();
+                chunk_extra.state_root = apply_result.new_root;
+                assert_eq!(
+                    env.clients[0].chain.get_chunk_extra(block.hash(), shard_id).unwrap(),
+                    &chunk_extra
+                );
+                continue;
+            }
+            let chunk = env.clients[0].chain.get_chunk(&chunk_header.chunk_hash()).unwrap().clone();
+            let receipts = collect_receipts_from_response(
+                &chain_store
+                    .store_update()
+                    .get_incoming_receipts_for_shard(
+                        shard_id,
+                        *block.hash(),
+                        prev_chunk_header.height_included(),
+                    )
+                    .unwrap(),
+            );
+            let apply_result = runtime_adapter
+                .apply_transactions(
+                    shard_id,
+                    &chunk_header.prev_state_root(),
+                    height,
+                    block.header().raw_timestamp(),
+                    &chunk_header.prev_block_hash(),
+                    block.hash(),
+                    &receipts,
+                    chunk.transactions(),
+                    chunk_header.validator_proposals(),
+                    prev_block.header().gas_price(),
+                    chunk_header.gas_limit(),
+                    block.header().challenges_result(),
+                    *block.header().random_value(),
+                )
+                .unwrap();
+
+            let (outcome_root, outcome_paths) =
+                ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
+            let chunk_extra = ChunkExtra::new(
+                &apply_result.new_root,
+                outcome_root,
+                apply_result.validator_proposals,
+                apply_result.total_gas_burnt,
+                chunk_header.gas_limit(),
+                apply_result.total_balance_burnt,
+            );
+            assert_eq!(
+                env.clients[0].chain.get_chunk_extra(block.hash(), shard_id).unwrap(),
+                &chunk_extra
+            );
+
+            let outcomes = outcomes_by_shard.remove(&shard_id).unwrap();
+            assert_eq!(
+                outcomes
+                    .into_iter()
+                    .map(|outcome| (outcome.outcome_with_id, outcome.proof))
+                    .collect::<Vec<_>>(),
+                apply_result.outcomes.into_iter().zip(outcome_paths).collect::<Vec<_>>()
+            );
+
+            let mut receipt_ids = apply_result
+                .receipt_result
+                .values()
+                .flatten()
+                .map(|receipt| receipt.receipt_id)
+                .collect::<Vec<_>>();
+            let mut saved_receipt_ids = env.clients[0]
+                .chain
+                .mut_store()
+                .get_outgoing_receipts(block.hash(), shard_id)
+                .unwrap()
+                .iter()
+                .map(|receipt| receipt.receipt_id)
+                .collect::<Vec<_>>();
+            receipt_ids.sort();
+            saved_receipt_ids.sort();
+            assert_eq!(saved_receipt_ids, receipt_ids);
+            num_cross_shard_receipts += apply_result
+                .receipt_result
+                .iter()
+                .filter(|(to_shard_id, _)| **to_shard_id != shard_id)
+                .map(|(_, receipts)| receipts.len())
+                .sum::<usize>();
+        }
+    }
+    assert!(num_cross_shard_receipts > 0);
+}
@@ -3,7 +3,8 @@ use futures::{future, FutureExt};
 
 use near_client::test_utils::setup_no_network;
 use near_client::{
-    GetBlock, GetBlockWithMerkleTree, GetExecutionOutcomesForBlock, Query, Status, TxStatus,
+    GetBlock, GetBlockWithMerkleTree, GetExecutionOutcomesForBlock, Query, SetGCPaused, Status,
+    TxStatus,
 };
 use near_crypto::{InMemorySigner, KeyType};
 use near_logger_utils::init_test_logger;
@@ -56,6 +57,7 @@ fn query_status_not_crash() {
             let header: BlockHeader = block.header.clone().into();
             block_merkle_tree.insert(*header.hash());
             let mut next_block = Block::produce(
+                PROTOCOL_VERSION,
                 PROTOCOL_VERSION,
                 &header,
                 block.header.height + 1,
@@ -162,3 +164,20 @@ fn test_execution_outcome_for_chunk() {
     })
     .unwrap();
 }
+
+/// Garbage collection is paused and resumed through the client actor.
+#[test]
+fn test_set_gc_paused() {
+    init_test_logger();
+    System::run(|| {
+        let (client, _) = setup_no_network(vec!["test"], "other", true, false);
+        actix::spawn(async move {
+            assert_eq!(client.send(SetGCPaused(true)).await.unwrap(), Ok(true));
+            assert_eq!(client.send(SetGCPaused(true)).await.unwrap(), Ok(true));
+            assert_eq!(client.send(SetGCPaused(false)).await.unwrap(), Ok(false));
+            System::current().stop();
+        });
+        near_network::test_utils::wait_or_panic(5000);
+    })
+    .unwrap();
+}
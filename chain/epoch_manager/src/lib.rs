@@ -52,6 +52,10 @@ pub struct EpochManager {
     blocks_info: SizedCache<CryptoHash, BlockInfo>,
     /// Cache of epoch id to epoch start height
     epoch_id_to_start: SizedCache<EpochId, BlockHeight>,
+    /// Cache of block approvers, by the epoch of the block, the next epoch if its block
+    /// producers approve the block too, and the slashed validators. Header sync validates the
+    /// approvals of thousands of headers of the same epochs.
+    block_approvers: SizedCache<(EpochId, Option<EpochId>, Vec<AccountId>), Vec<ApprovalStake>>,
     /// Aggregator that crunches data when we process block info
     epoch_info_aggregator: Option<EpochInfoAggregator>,
     /// Largest final height. Monotonically increasing.
@@ -77,6 +81,7 @@ impl EpochManager {
             epochs_info: SizedCache::with_size(EPOCH_CACHE_SIZE),
             blocks_info: SizedCache::with_size(BLOCK_CACHE_SIZE),
             epoch_id_to_start: SizedCache::with_size(EPOCH_CACHE_SIZE),
+            block_approvers: SizedCache::with_size(EPOCH_CACHE_SIZE),
             epoch_info_aggregator: None,
             largest_final_height: 0,
         };
@@ -509,8 +514,8 @@ impl EpochManager {
         epoch_id: &EpochId,
         height: BlockHeight,
     ) -> Result<ValidatorStake, EpochError> {
-        let epoch_info = self.get_epoch_info(epoch_id)?.clone();
-        let validator_id = Self::block_producer_from_info(&epoch_info, height);
+        let epoch_info = self.get_epoch_info(epoch_id)?;
+        let validator_id = Self::block_producer_from_info(epoch_info, height);
         Ok(epoch_info.validators[validator_id as usize].clone())
     }
 
@@ -550,6 +555,23 @@ impl EpochManager {
         Ok(result)
     }
 
+    /// Returns all unique chunk producers of the epoch in the order of the shards, chunk-only
+    /// producers included.
+    pub fn get_all_chunk_producers(
+        &mut self,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ValidatorStake>, EpochError> {
+        let epoch_info = self.get_epoch_info(epoch_id)?;
+        let mut result = vec![];
+        let mut validators: HashSet<ValidatorId> = HashSet::default();
+        for validator_id in epoch_info.chunk_producers_settlement.iter().flatten() {
+            if validators.insert(*validator_id) {
+                result.push(epoch_info.validators[*validator_id as usize].clone());
+            }
+        }
+        Ok(result)
+    }
+
     pub fn get_all_block_approvers_ordered(
         &mut self,
         parent_hash: &CryptoHash,
@@ -557,13 +579,26 @@ impl EpochManager {
         let current_epoch_id = self.get_epoch_id_from_prev_block(parent_hash)?;
         let next_epoch_id = self.get_next_epoch_id_from_prev_block(parent_hash)?;
 
+        let block_info = self.get_block_info(parent_hash)?.clone();
+        let need_next_epoch_approvals =
+            self.next_block_need_approvals_from_next_epoch(&block_info)?;
+        let mut slashed: Vec<_> = block_info.slashed.keys().cloned().collect();
+        slashed.sort();
+        let cache_key = (
+            current_epoch_id.clone(),
+            if need_next_epoch_approvals { Some(next_epoch_id.clone()) } else { None },
+            slashed,
+        );
+        if let Some(approvers) = self.block_approvers.cache_get(&cache_key) {
+            return Ok(approvers.clone());
+        }
+
         let mut settlement =
             self.get_all_block_producers_settlement(&current_epoch_id, parent_hash)?;
 
         let settlement_epoch_boundary = settlement.len();
 
-        let block_info = self.get_block_info(parent_hash)?.clone();
-        if self.next_block_need_approvals_from_next_epoch(&block_info)? {
+        if need_next_epoch_approvals {
             settlement.extend(
                 self.get_all_block_producers_settlement(&next_epoch_id, parent_hash)?
                     .iter()
@@ -590,6 +625,7 @@ impl EpochManager {
                 };
             }
         }
+        self.block_approvers.cache_set(cache_key, result.clone());
         Ok(result)
     }
 
@@ -600,8 +636,8 @@ impl EpochManager {
         height: BlockHeight,
         shard_id: ShardId,
     ) -> Result<ValidatorStake, EpochError> {
-        let epoch_info = self.get_epoch_info(epoch_id)?.clone();
-        let validator_id = Self::chunk_producer_from_info(&epoch_info, height, shard_id);
+        let epoch_info = self.get_epoch_info(epoch_id)?;
+        let validator_id = Self::chunk_producer_from_info(epoch_info, height, shard_id);
         Ok(epoch_info.validators[validator_id as usize].clone())
     }
 
@@ -863,6 +899,13 @@ impl EpochManager {
                     .get(&(validator_id as u64))
                     .unwrap_or_else(|| &ValidatorStats { produced: 0, expected: 0 })
                     .clone();
+                let (num_produced_chunks, num_expected_chunks) = epoch_info_aggregator
+                    .shard_tracker
+                    .values()
+                    .filter_map(|tracker| tracker.get(&(validator_id as u64)))
+                    .fold((0, 0), |(produced, expected), stats| {
+                        (produced + stats.produced, expected + stats.expected)
+                    });
                 let mut shards =
                     validator_to_shard[validator_id].clone().into_iter().collect::<Vec<ShardId>>();
                 shards.sort();
@@ -874,6 +917,8 @@ impl EpochManager {
                     shards,
                     num_produced_blocks: validator_stats.produced,
                     num_expected_blocks: validator_stats.expected,
+                    num_produced_chunks,
+                    num_expected_chunks,
                 })
             })
             .collect::<Result<Vec<CurrentEpochValidatorInfo>, EpochError>>()?;
@@ -916,6 +961,23 @@ impl EpochManager {
             .into_iter()
             .map(|(account_id, reason)| ValidatorKickoutView { account_id, reason })
             .collect();
+        // The epoch after next is named after the last block of this epoch, and its info holds
+        // the kickouts for this epoch.
+        let next_next_epoch_id = EpochId(*block_hash);
+        let current_epoch_kickout = if self.has_epoch_info(&next_next_epoch_id)? {
+            Some(
+                self.get_epoch_info(&next_next_epoch_id)?
+                    .validator_kickout
+                    .clone()
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(account_id, reason)| ValidatorKickoutView { account_id, reason })
+                    .collect(),
+            )
+        } else {
+            None
+        };
 
         Ok(EpochValidatorInfo {
             current_validators,
@@ -928,6 +990,7 @@ impl EpochManager {
                 .map(|(_, p)| p.into())
                 .collect(),
             prev_epoch_kickout,
+            current_epoch_kickout,
             epoch_start_height,
         })
     }
@@ -3038,4 +3101,40 @@ mod tests {
             epoch_manager.epoch_info_aggregator.as_ref().map(|a| a.last_block_hash).unwrap();
         assert_eq!(epoch_aggregator_final_hash, new_epoch_aggregator_final_hash);
     }
+
+    #[test]
+    fn test_block_approvers_cache() {
+        let amount_staked = 1_000_000;
+        let validators = vec![("test1", amount_staked), ("test2", amount_staked)];
+        let mut epoch_manager = setup_default_epoch_manager(validators, 10, 1, 2, 0, 90, 60);
+
+        let h = hash_range(10);
+        record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+        record_block(&mut epoch_manager, h[0], h[1], 1, vec![]);
+        let approvers = |epoch_manager: &mut EpochManager, hash: &CryptoHash| -> Vec<AccountId> {
+            epoch_manager
+                .get_all_block_approvers_ordered(hash)
+                .unwrap()
+                .into_iter()
+                .map(|approver| approver.account_id)
+                .collect()
+        };
+        let all = vec!["test1".to_string(), "test2".to_string()];
+        assert_eq!(approvers(&mut epoch_manager, &h[0]), all);
+        assert_eq!(epoch_manager.block_approvers.cache_size(), 1);
+        // The blocks of the same epoch share the approvers.
+        assert_eq!(approvers(&mut epoch_manager, &h[1]), all);
+        assert_eq!(epoch_manager.block_approvers.cache_size(), 1);
+
+        record_block_with_slashes(
+            &mut epoch_manager,
+            h[1],
+            h[2],
+            2,
+            vec![],
+            vec![SlashedValidator::new("test1".to_string(), false)],
+        );
+        assert_eq!(approvers(&mut epoch_manager, &h[2]), vec!["test2".to_string()]);
+        assert_eq!(approvers(&mut epoch_manager, &h[1]), all);
+    }
 }
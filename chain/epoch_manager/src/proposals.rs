@@ -1,6 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::iter;
 
+use near_primitives::checked_feature;
 use near_primitives::epoch_manager::{EpochConfig, EpochInfo};
 use near_primitives::errors::EpochError;
 use near_primitives::types::{
@@ -37,6 +38,43 @@ pub(crate) fn find_threshold(
     }
 }
 
+/// Takes the proposals with the most stake out of the ones without a block producer seat to fill
+/// the chunk-only producer seats. Chunk-only producers need at least the minimum stake required for
+/// staking. Returns them sorted by account id.
+#[cfg(feature = "protocol_feature_chunk_only_producers")]
+fn select_chunk_only_producers(
+    epoch_config: &EpochConfig,
+    threshold: Balance,
+    unselected: &mut Vec<(ValidatorStake, ValidatorKickoutReason)>,
+) -> Vec<ValidatorStake> {
+    let minimum_stake = threshold / epoch_config.minimum_stake_divisor as Balance;
+    let mut candidates = unselected
+        .iter()
+        .filter(|(p, _)| p.stake >= minimum_stake)
+        .map(|(p, _)| (p.stake, p.account_id.clone()))
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(stake1, account_id1), (stake2, account_id2)| {
+        stake2.cmp(stake1).then_with(|| account_id1.cmp(account_id2))
+    });
+    let selected = candidates
+        .into_iter()
+        .take(epoch_config.num_chunk_only_producer_seats as usize)
+        .map(|(_, account_id)| account_id)
+        .collect::<HashSet<_>>();
+
+    let mut chunk_only_producers = vec![];
+    unselected.retain(|(p, _)| {
+        if selected.contains(&p.account_id) {
+            chunk_only_producers.push(p.clone());
+            false
+        } else {
+            true
+        }
+    });
+    chunk_only_producers.sort_by(|p1, p2| p1.account_id.cmp(&p2.account_id));
+    chunk_only_producers
+}
+
 /// Calculates new seat assignments based on current seat assignments and proposals.
 pub fn proposals_to_epoch_info(
     epoch_config: &EpochConfig,
@@ -98,23 +136,15 @@ pub fn proposals_to_epoch_info(
     let threshold = find_threshold(&stakes, num_total_seats)?;
     // Remove proposals under threshold.
     let mut final_proposals = vec![];
+    // Proposals without a block producer seat, with the reason to kick them out.
+    let mut unselected = vec![];
 
-    for (account_id, p) in ordered_proposals {
+    for (_, p) in ordered_proposals {
         if p.stake >= threshold {
             final_proposals.push(p);
-        } else if p.stake >= epoch_config.fishermen_threshold {
-            // Do not return stake back since they will become fishermen
-            fishermen.push(p);
         } else {
-            *stake_change.get_mut(&account_id).unwrap() = 0;
-            if prev_epoch_info.validator_to_index.contains_key(&account_id)
-                || prev_epoch_info.fishermen_to_index.contains_key(&account_id)
-            {
-                validator_kickout.insert(
-                    account_id,
-                    ValidatorKickoutReason::NotEnoughStake { stake: p.stake, threshold },
-                );
-            }
+            let reason = ValidatorKickoutReason::NotEnoughStake { stake: p.stake, threshold };
+            unselected.push((p, reason));
         }
     }
 
@@ -139,7 +169,7 @@ pub fn proposals_to_epoch_info(
         dup_proposals[..epoch_config.num_block_producer_seats as usize].to_vec();
     // remove proposals that are not selected
     let indices_to_keep = block_producers_settlement.iter().copied().collect::<BTreeSet<_>>();
-    let (final_proposals, proposals_to_remove) = final_proposals.into_iter().enumerate().fold(
+    let (mut final_proposals, proposals_to_remove) = final_proposals.into_iter().enumerate().fold(
         (vec![], vec![]),
         |(mut proposals, mut to_remove), (i, p)| {
             if indices_to_keep.contains(&(i as u64)) {
@@ -152,14 +182,26 @@ pub fn proposals_to_epoch_info(
     );
     for p in proposals_to_remove {
         debug_assert!(p.stake >= threshold);
+        unselected.push((p, ValidatorKickoutReason::DidNotGetASeat));
+    }
+
+    let chunk_only_producers = checked_feature!(
+        "protocol_feature_chunk_only_producers",
+        ChunkOnlyProducers,
+        next_version,
+        { select_chunk_only_producers(epoch_config, threshold, &mut unselected) } else { vec![] }
+    );
+
+    for (p, reason) in unselected {
         if p.stake >= epoch_config.fishermen_threshold {
+            // Do not return stake back since they will become fishermen
             fishermen.push(p);
         } else {
             stake_change.insert(p.account_id.clone(), 0);
             if prev_epoch_info.validator_to_index.contains_key(&p.account_id)
                 || prev_epoch_info.fishermen_to_index.contains_key(&p.account_id)
             {
-                validator_kickout.insert(p.account_id, ValidatorKickoutReason::DidNotGetASeat);
+                validator_kickout.insert(p.account_id, reason);
             }
         }
     }
@@ -182,6 +224,15 @@ pub fn proposals_to_epoch_info(
         chunk_producers_settlement.push(shard_settlement);
     }
 
+    // Chunk-only producers follow the block producers in the validators, and take chunk producer
+    // seats in the shards in turn.
+    let num_block_producers = final_proposals.len();
+    let num_shards = chunk_producers_settlement.len();
+    for (i, p) in chunk_only_producers.into_iter().enumerate() {
+        chunk_producers_settlement[i % num_shards].push((num_block_producers + i) as ValidatorId);
+        final_proposals.push(p);
+    }
+
     let fishermen_to_index = fishermen
         .iter()
         .enumerate()
@@ -268,6 +319,7 @@ mod tests {
                     num_shards: 5,
                     num_block_producer_seats: 6,
                     num_block_producer_seats_per_shard: vec![6, 2, 2, 2, 2],
+                    num_chunk_only_producer_seats: 0,
                     avg_hidden_validator_seats_per_shard: vec![6, 2, 2, 2, 2],
                     block_producer_kickout_threshold: 90,
                     chunk_producer_kickout_threshold: 60,
@@ -383,4 +435,52 @@ mod tests {
             epoch_info
         );
     }
+
+    #[cfg(feature = "protocol_feature_chunk_only_producers")]
+    #[test]
+    fn test_chunk_only_producers() {
+        use near_primitives::version::ProtocolFeature;
+
+        let mut config = epoch_config(2, 2, 2, 0, 90, 60, 0);
+        config.num_chunk_only_producer_seats = 2;
+        config.minimum_stake_divisor = 10;
+        let proposals = vec![
+            stake("test1", 1_000_000),
+            stake("test2", 1_000_000),
+            stake("test3", 100_000),
+            stake("test4", 10),
+            stake("test5", 300_000),
+        ];
+        let account_ids = |stakes: &[ValidatorStake]| {
+            stakes.iter().map(|s| s.account_id.clone()).collect::<Vec<_>>()
+        };
+        let epoch_info = |config: &EpochConfig| {
+            proposals_to_epoch_info(
+                config,
+                [0; 32],
+                &EpochInfo::default(),
+                proposals.clone(),
+                HashMap::default(),
+                HashMap::default(),
+                0,
+                ProtocolFeature::ChunkOnlyProducers.protocol_version(),
+            )
+            .unwrap()
+        };
+
+        // test4 doesn't have the minimum stake of a tenth of the seat price.
+        let info = epoch_info(&config);
+        assert_eq!(account_ids(&info.validators), vec!["test1", "test2", "test3", "test5"]);
+        assert!(info.block_producers_settlement.iter().all(|validator_id| *validator_id < 2));
+        assert_eq!(info.chunk_producers_settlement[0].last(), Some(&2));
+        assert_eq!(info.chunk_producers_settlement[1].last(), Some(&3));
+        assert_eq!(account_ids(&info.fishermen), vec!["test4"]);
+        assert_eq!(info.stake_change.get("test3"), Some(&100_000));
+
+        config.num_chunk_only_producer_seats = 1;
+        let info = epoch_info(&config);
+        assert_eq!(account_ids(&info.validators), vec!["test1", "test2", "test5"]);
+        assert_eq!(info.chunk_producers_settlement[0].last(), Some(&2));
+        assert_eq!(account_ids(&info.fishermen), vec!["test3", "test4"]);
+    }
 }
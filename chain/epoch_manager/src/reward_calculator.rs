@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use num_rational::Rational;
 use primitive_types::U256;
 
+use near_primitives::checked_feature;
 use near_primitives::types::{AccountId, Balance, BlockChunkValidatorStats};
 use near_primitives::version::{ProtocolVersion, ENABLE_INFLATION_PROTOCOL_VERSION};
 
@@ -55,20 +56,33 @@ impl RewardCalculator {
         let mut epoch_actual_reward = epoch_protocol_treasury;
         let total_stake: Balance = validator_stake.values().sum();
         for (account_id, stats) in validator_block_chunk_stats {
-            // Uptime is an average of block produced / expected and chunk produced / expected.
-            let average_produced_numer = U256::from(
-                stats.block_stats.produced * stats.chunk_stats.expected
-                    + stats.chunk_stats.produced * stats.block_stats.expected,
-            );
-            let average_produced_denom =
-                U256::from(2 * stats.chunk_stats.expected * stats.block_stats.expected);
+            // Chunk-only producers are not expected to produce blocks.
+            let is_chunk_only_producer = checked_feature!(
+                "protocol_feature_chunk_only_producers",
+                ChunkOnlyProducers,
+                protocol_version
+            ) && stats.block_stats.expected == 0
+                && stats.chunk_stats.expected > 0;
+            // Uptime is an average of block produced / expected and chunk produced / expected,
+            // or chunk produced / expected for chunk-only producers.
+            let (average_produced_numer, average_produced_denom) = if is_chunk_only_producer {
+                (U256::from(stats.chunk_stats.produced), U256::from(stats.chunk_stats.expected))
+            } else {
+                (
+                    U256::from(
+                        stats.block_stats.produced * stats.chunk_stats.expected
+                            + stats.chunk_stats.produced * stats.block_stats.expected,
+                    ),
+                    U256::from(2 * stats.chunk_stats.expected * stats.block_stats.expected),
+                )
+            };
             let online_min_numer = U256::from(*self.online_min_threshold.numer() as u64);
             let online_min_denom = U256::from(*self.online_min_threshold.denom() as u64);
             // If average of produced blocks below online min threshold, validator gets 0 reward.
             let reward = if average_produced_numer * online_min_denom
                 < online_min_numer * average_produced_denom
                 || stats.chunk_stats.expected == 0
-                || stats.block_stats.expected == 0
+                || (stats.block_stats.expected == 0 && !is_chunk_only_producer)
             {
                 0
             } else {
@@ -303,4 +317,62 @@ mod tests {
         );
         assert_eq!(epoch_total_reward, 50000000);
     }
+
+    /// Test that chunk-only producers are rewarded for their chunks.
+    #[cfg(feature = "protocol_feature_chunk_only_producers")]
+    #[test]
+    fn test_reward_chunk_only_producer() {
+        use near_primitives::version::ProtocolFeature;
+
+        let reward_calculator = RewardCalculator {
+            max_inflation_rate: Rational::new(1, 100),
+            num_blocks_per_year: 1000,
+            epoch_length: 1000,
+            protocol_reward_rate: Rational::new(0, 10),
+            protocol_treasury_account: "near".to_string(),
+            online_min_threshold: Rational::new(9, 10),
+            online_max_threshold: Rational::new(99, 100),
+        };
+        let validator_block_chunk_stats = vec![
+            (
+                "test1".to_string(),
+                BlockChunkValidatorStats {
+                    block_stats: ValidatorStats { produced: 1000, expected: 1000 },
+                    chunk_stats: ValidatorStats { produced: 1000, expected: 1000 },
+                },
+            ),
+            (
+                "test2".to_string(),
+                BlockChunkValidatorStats {
+                    block_stats: ValidatorStats { produced: 0, expected: 0 },
+                    chunk_stats: ValidatorStats { produced: 945, expected: 1000 },
+                },
+            ),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let validator_stake = vec![("test1".to_string(), 500_000), ("test2".to_string(), 500_000)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let total_supply = 1_000_000_000;
+        let protocol_version = ProtocolFeature::ChunkOnlyProducers.protocol_version();
+        let result = reward_calculator.calculate_reward(
+            validator_block_chunk_stats,
+            &validator_stake,
+            total_supply,
+            protocol_version,
+            protocol_version,
+        );
+        // Total reward is 10_000_000, test2 with 94.5% of its chunks gets half of its share.
+        assert_eq!(
+            result.0,
+            vec![
+                ("near".to_string(), 0),
+                ("test1".to_string(), 5_000_000u128),
+                ("test2".to_string(), 2_500_000u128)
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
 }
@@ -133,6 +133,7 @@ pub fn epoch_config(
             num_shards,
             num_block_producer_seats,
         ),
+        num_chunk_only_producer_seats: 0,
         avg_hidden_validator_seats_per_shard: (0..num_shards)
             .map(|_| num_hidden_validator_seats)
             .collect(),
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrpcConfig {
+    pub addr: String,
+    /// How often streams check for new blocks.
+    #[serde(default = "default_polling_interval")]
+    pub polling_interval: Duration,
+    /// How long unary calls wait for data the node requested from its peers.
+    #[serde(default = "default_polling_timeout")]
+    pub polling_timeout: Duration,
+}
+
+fn default_polling_interval() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_polling_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            addr: "0.0.0.0:3050".to_owned(),
+            polling_interval: default_polling_interval(),
+            polling_timeout: default_polling_timeout(),
+        }
+    }
+}
+
+impl GrpcConfig {
+    pub fn new(addr: &str) -> Self {
+        Self { addr: addr.to_owned(), ..Default::default() }
+    }
+}
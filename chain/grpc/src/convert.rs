@@ -0,0 +1,518 @@
+//! Conversions between the JSON-RPC views and the protobuf messages.
+use std::convert::TryFrom;
+
+use tonic::Status;
+
+use near_crypto::PublicKey;
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::merkle::{Direction, MerklePathItem};
+use near_primitives::serialize::from_base64;
+use near_primitives::views::{
+    AccessKeyInfoView, AccessKeyList, AccessKeyPermissionView, AccessKeyView, AccountList,
+    AccountView, ActionView, BlockHeaderView, BlockView, CallResult, ChunkHeaderView, ChunkView,
+    ContractMetadataView, DataReceiverView, ExecutionOutcomeView, ExecutionOutcomeWithIdView,
+    ExecutionStatusView, FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum,
+    FinalExecutionStatus, MethodMetadataView, QueryError, QueryRequest, QueryResponse,
+    QueryResponseKind, ReceiptEnumView, ReceiptView, RefundView, SignedTransactionView,
+    TransactionCostView, ValidatorStakeView, ViewStateResult,
+};
+
+use crate::proto;
+
+/// The views carry binary data in base64, always produced by the node itself.
+fn decode_base64(value: &str) -> Vec<u8> {
+    from_base64(value).expect("Views hold valid base64")
+}
+
+fn hashes(hashes: Vec<CryptoHash>) -> Vec<Vec<u8>> {
+    hashes.into_iter().map(Into::into).collect()
+}
+
+fn parse_public_key(public_key: &str) -> Result<PublicKey, Status> {
+    public_key
+        .parse()
+        .map_err(|err| Status::invalid_argument(format!("Invalid public key: {}", err)))
+}
+
+impl From<ValidatorStakeView> for proto::ValidatorStake {
+    fn from(view: ValidatorStakeView) -> Self {
+        Self {
+            account_id: view.account_id,
+            public_key: view.public_key.to_string(),
+            stake: view.stake.to_string(),
+        }
+    }
+}
+
+impl From<BlockHeaderView> for proto::BlockHeader {
+    fn from(view: BlockHeaderView) -> Self {
+        Self {
+            height: view.height,
+            epoch_id: view.epoch_id.into(),
+            next_epoch_id: view.next_epoch_id.into(),
+            hash: view.hash.into(),
+            prev_hash: view.prev_hash.into(),
+            prev_state_root: view.prev_state_root.into(),
+            chunk_receipts_root: view.chunk_receipts_root.into(),
+            chunk_headers_root: view.chunk_headers_root.into(),
+            chunk_tx_root: view.chunk_tx_root.into(),
+            outcome_root: view.outcome_root.into(),
+            chunks_included: view.chunks_included,
+            challenges_root: view.challenges_root.into(),
+            timestamp_nanosec: view.timestamp_nanosec,
+            random_value: view.random_value.into(),
+            validator_proposals: view.validator_proposals.into_iter().map(Into::into).collect(),
+            chunk_mask: view.chunk_mask,
+            gas_price: view.gas_price.to_string(),
+            total_supply: view.total_supply.to_string(),
+            challenges_result: view
+                .challenges_result
+                .into_iter()
+                .map(|slashed| proto::SlashedValidator {
+                    account_id: slashed.account_id,
+                    is_double_sign: slashed.is_double_sign,
+                })
+                .collect(),
+            last_final_block: view.last_final_block.into(),
+            last_ds_final_block: view.last_ds_final_block.into(),
+            next_bp_hash: view.next_bp_hash.into(),
+            block_merkle_root: view.block_merkle_root.into(),
+            approvals: view
+                .approvals
+                .into_iter()
+                .map(|approval| approval.map(|signature| signature.to_string()).unwrap_or_default())
+                .collect(),
+            signature: view.signature.to_string(),
+            latest_protocol_version: view.latest_protocol_version,
+        }
+    }
+}
+
+impl From<ChunkHeaderView> for proto::ChunkHeader {
+    fn from(view: ChunkHeaderView) -> Self {
+        Self {
+            chunk_hash: view.chunk_hash.into(),
+            prev_block_hash: view.prev_block_hash.into(),
+            outcome_root: view.outcome_root.into(),
+            prev_state_root: view.prev_state_root.into(),
+            encoded_merkle_root: view.encoded_merkle_root.into(),
+            encoded_length: view.encoded_length,
+            height_created: view.height_created,
+            height_included: view.height_included,
+            shard_id: view.shard_id,
+            gas_used: view.gas_used,
+            gas_limit: view.gas_limit,
+            balance_burnt: view.balance_burnt.to_string(),
+            outgoing_receipts_root: view.outgoing_receipts_root.into(),
+            tx_root: view.tx_root.into(),
+            validator_proposals: view.validator_proposals.into_iter().map(Into::into).collect(),
+            signature: view.signature.to_string(),
+        }
+    }
+}
+
+impl From<BlockView> for proto::Block {
+    fn from(view: BlockView) -> Self {
+        Self {
+            author: view.author,
+            header: Some(view.header.into()),
+            chunks: view.chunks.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<AccessKeyView> for proto::AccessKey {
+    fn from(view: AccessKeyView) -> Self {
+        use proto::access_key::Permission;
+        use proto::function_call_permission::AllowanceValue;
+        let permission = match view.permission {
+            AccessKeyPermissionView::FullAccess => Permission::FullAccess(proto::Empty {}),
+            AccessKeyPermissionView::FunctionCall { allowance, receiver_id, method_names } => {
+                Permission::FunctionCall(proto::FunctionCallPermission {
+                    allowance_value: allowance
+                        .map(|allowance| AllowanceValue::Allowance(allowance.to_string())),
+                    receiver_id,
+                    method_names,
+                })
+            }
+        };
+        Self { nonce: view.nonce, permission: Some(permission) }
+    }
+}
+
+impl From<ActionView> for proto::Action {
+    fn from(view: ActionView) -> Self {
+        use proto::action::{self, Action};
+        let action = match view {
+            ActionView::CreateAccount => Action::CreateAccount(proto::Empty {}),
+            ActionView::DeployContract { code } => {
+                Action::DeployContract(action::DeployContract { code_hash: decode_base64(&code) })
+            }
+            ActionView::FunctionCall { method_name, args, gas, deposit } => {
+                Action::FunctionCall(action::FunctionCall {
+                    method_name,
+                    args: decode_base64(&args),
+                    gas,
+                    deposit: deposit.to_string(),
+                })
+            }
+            ActionView::Transfer { deposit } => {
+                Action::Transfer(action::Transfer { deposit: deposit.to_string() })
+            }
+            ActionView::Stake { stake, public_key } => Action::Stake(action::Stake {
+                stake: stake.to_string(),
+                public_key: public_key.to_string(),
+            }),
+            ActionView::AddKey { public_key, access_key } => Action::AddKey(action::AddKey {
+                public_key: public_key.to_string(),
+                access_key: Some(access_key.into()),
+            }),
+            ActionView::DeleteKey { public_key } => {
+                Action::DeleteKey(action::DeleteKey { public_key: public_key.to_string() })
+            }
+            ActionView::DeleteAccount { beneficiary_id } => {
+                Action::DeleteAccount(action::DeleteAccount { beneficiary_id })
+            }
+            ActionView::IdempotencyKey { key } => {
+                Action::IdempotencyKey(action::IdempotencyKey { key: decode_base64(&key) })
+            }
+        };
+        Self { action: Some(action) }
+    }
+}
+
+impl From<SignedTransactionView> for proto::SignedTransaction {
+    fn from(view: SignedTransactionView) -> Self {
+        Self {
+            signer_id: view.signer_id,
+            public_key: view.public_key.to_string(),
+            nonce: view.nonce,
+            receiver_id: view.receiver_id,
+            actions: view.actions.into_iter().map(Into::into).collect(),
+            signature: view.signature.to_string(),
+            hash: view.hash.into(),
+        }
+    }
+}
+
+impl From<DataReceiverView> for proto::DataReceiver {
+    fn from(view: DataReceiverView) -> Self {
+        Self { data_id: view.data_id.into(), receiver_id: view.receiver_id }
+    }
+}
+
+impl From<ReceiptView> for proto::Receipt {
+    fn from(view: ReceiptView) -> Self {
+        use proto::receipt::Receipt;
+        let receipt = match view.receipt {
+            ReceiptEnumView::Action {
+                signer_id,
+                signer_public_key,
+                gas_price,
+                output_data_receivers,
+                input_data_ids,
+                actions,
+            } => Receipt::Action(proto::ActionReceipt {
+                signer_id,
+                signer_public_key: signer_public_key.to_string(),
+                gas_price: gas_price.to_string(),
+                output_data_receivers: output_data_receivers.into_iter().map(Into::into).collect(),
+                input_data_ids: hashes(input_data_ids),
+                actions: actions.into_iter().map(Into::into).collect(),
+            }),
+            ReceiptEnumView::Data { data_id, data } => Receipt::Data(proto::DataReceipt {
+                data_id: data_id.into(),
+                value: data.map(proto::data_receipt::Value::Data),
+            }),
+        };
+        Self {
+            predecessor_id: view.predecessor_id,
+            receiver_id: view.receiver_id,
+            receipt_id: view.receipt_id.into(),
+            receipt: Some(receipt),
+        }
+    }
+}
+
+impl From<ChunkView> for proto::Chunk {
+    fn from(view: ChunkView) -> Self {
+        Self {
+            author: view.author,
+            header: Some(view.header.into()),
+            transactions: view.transactions.into_iter().map(Into::into).collect(),
+            receipts: view.receipts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<MerklePathItem> for proto::MerklePathItem {
+    fn from(item: MerklePathItem) -> Self {
+        let direction = match item.direction {
+            Direction::Left => proto::merkle_path_item::Direction::Left,
+            Direction::Right => proto::merkle_path_item::Direction::Right,
+        };
+        Self { hash: item.hash.into(), direction: direction as i32 }
+    }
+}
+
+/// Errors are serialized as in the JSON-RPC, their schema is in `rpc_errors_schema.json`.
+fn execution_error<T: serde::Serialize>(error: &T) -> String {
+    serde_json::to_string(error).expect("Execution errors are serializable")
+}
+
+impl From<ExecutionStatusView> for proto::ExecutionStatus {
+    fn from(view: ExecutionStatusView) -> Self {
+        use proto::execution_status::Status;
+        let status = match view {
+            ExecutionStatusView::Unknown => Status::Unknown(proto::Empty {}),
+            ExecutionStatusView::Failure(error) => Status::Failure(execution_error(&error)),
+            ExecutionStatusView::SuccessValue(value) => Status::SuccessValue(decode_base64(&value)),
+            ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+                Status::SuccessReceiptId(receipt_id.into())
+            }
+        };
+        Self { status: Some(status) }
+    }
+}
+
+impl From<ExecutionOutcomeView> for proto::ExecutionOutcome {
+    fn from(view: ExecutionOutcomeView) -> Self {
+        Self {
+            logs: view.logs,
+            receipt_ids: hashes(view.receipt_ids),
+            gas_burnt: view.gas_burnt,
+            tokens_burnt: view.tokens_burnt.to_string(),
+            executor_id: view.executor_id,
+            status: Some(view.status.into()),
+        }
+    }
+}
+
+impl From<ExecutionOutcomeWithIdView> for proto::ExecutionOutcomeWithId {
+    fn from(view: ExecutionOutcomeWithIdView) -> Self {
+        Self {
+            proof: view.proof.into_iter().map(Into::into).collect(),
+            block_hash: view.block_hash.into(),
+            id: view.id.into(),
+            outcome: Some(view.outcome.into()),
+        }
+    }
+}
+
+impl From<FinalExecutionStatus> for proto::FinalExecutionStatus {
+    fn from(view: FinalExecutionStatus) -> Self {
+        use proto::final_execution_status::Status;
+        let status = match view {
+            FinalExecutionStatus::NotStarted => Status::NotStarted(proto::Empty {}),
+            FinalExecutionStatus::Started => Status::Started(proto::Empty {}),
+            FinalExecutionStatus::Failure(error) => Status::Failure(execution_error(&error)),
+            FinalExecutionStatus::SuccessValue(value) => {
+                Status::SuccessValue(decode_base64(&value))
+            }
+        };
+        Self { status: Some(status) }
+    }
+}
+
+impl From<RefundView> for proto::Refund {
+    fn from(view: RefundView) -> Self {
+        Self {
+            receipt_id: view.receipt_id.into(),
+            receiver_id: view.receiver_id,
+            amount: view.amount.to_string(),
+        }
+    }
+}
+
+impl From<TransactionCostView> for proto::TransactionCost {
+    fn from(view: TransactionCostView) -> Self {
+        Self {
+            gas_burnt: view.gas_burnt,
+            tokens_burnt: view.tokens_burnt.to_string(),
+            refunds: view.refunds.into_iter().map(Into::into).collect(),
+            total_refund: view.total_refund.to_string(),
+        }
+    }
+}
+
+impl From<FinalExecutionOutcomeView> for proto::FinalExecutionOutcome {
+    fn from(view: FinalExecutionOutcomeView) -> Self {
+        Self {
+            status: Some(view.status.into()),
+            transaction: Some(view.transaction.into()),
+            transaction_outcome: Some(view.transaction_outcome.into()),
+            receipts_outcome: view.receipts_outcome.into_iter().map(Into::into).collect(),
+            receipts: vec![],
+            cost: None,
+        }
+    }
+}
+
+impl From<FinalExecutionOutcomeViewEnum> for proto::FinalExecutionOutcome {
+    fn from(view: FinalExecutionOutcomeViewEnum) -> Self {
+        match view {
+            FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome) => outcome.into(),
+            FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(outcome) => Self {
+                receipts: outcome.receipts.into_iter().map(Into::into).collect(),
+                cost: Some(outcome.cost.into()),
+                ..outcome.final_outcome.into()
+            },
+        }
+    }
+}
+
+impl From<AccountView> for proto::Account {
+    fn from(view: AccountView) -> Self {
+        Self {
+            amount: view.amount.to_string(),
+            locked: view.locked.to_string(),
+            code_hash: view.code_hash.into(),
+            storage_usage: view.storage_usage,
+        }
+    }
+}
+
+impl From<ViewStateResult> for proto::ViewStateResult {
+    fn from(view: ViewStateResult) -> Self {
+        Self {
+            values: view
+                .values
+                .into_iter()
+                .map(|item| proto::StateItem {
+                    key: decode_base64(&item.key),
+                    value: decode_base64(&item.value),
+                })
+                .collect(),
+            proof: view.proof.iter().map(|node| decode_base64(node)).collect(),
+            next_cursor_value: view.next_cursor.map(|cursor| {
+                proto::view_state_result::NextCursorValue::NextCursor(decode_base64(&cursor))
+            }),
+        }
+    }
+}
+
+impl From<AccessKeyInfoView> for proto::AccessKeyInfo {
+    fn from(view: AccessKeyInfoView) -> Self {
+        Self { public_key: view.public_key.to_string(), access_key: Some(view.access_key.into()) }
+    }
+}
+
+impl From<AccessKeyList> for proto::AccessKeyList {
+    fn from(view: AccessKeyList) -> Self {
+        Self {
+            keys: view.keys.into_iter().map(Into::into).collect(),
+            next_cursor_value: view.next_cursor.map(|cursor| {
+                proto::access_key_list::NextCursorValue::NextCursor(cursor.to_string())
+            }),
+        }
+    }
+}
+
+impl From<MethodMetadataView> for proto::MethodMetadata {
+    fn from(view: MethodMetadataView) -> Self {
+        Self { method_name: view.method_name, gas: view.gas, deposit: view.deposit.to_string() }
+    }
+}
+
+impl From<ContractMetadataView> for proto::ContractMetadata {
+    fn from(view: ContractMetadataView) -> Self {
+        Self { methods: view.methods.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl From<AccountList> for proto::AccountList {
+    fn from(view: AccountList) -> Self {
+        Self {
+            accounts: view.accounts,
+            next_cursor_value: view
+                .next_cursor
+                .map(proto::account_list::NextCursorValue::NextCursor),
+        }
+    }
+}
+
+impl From<QueryResponse> for proto::QueryResponse {
+    fn from(response: QueryResponse) -> Self {
+        use proto::query_response::Kind;
+        let kind = match response.kind {
+            QueryResponseKind::ViewAccount(view) => Kind::Account(view.into()),
+            QueryResponseKind::ViewState(view) => Kind::ViewState(view.into()),
+            QueryResponseKind::CallResult(CallResult { result, logs }) => {
+                Kind::CallResult(proto::CallResult { result, logs })
+            }
+            QueryResponseKind::Error(QueryError { error, logs }) => {
+                Kind::Error(proto::QueryError { error, logs })
+            }
+            QueryResponseKind::AccessKey(view) => Kind::AccessKey(view.into()),
+            QueryResponseKind::AccessKeyList(view) => Kind::AccessKeyList(view.into()),
+            QueryResponseKind::ContractMetadata(view) => Kind::ContractMetadata(view.into()),
+            QueryResponseKind::AccountList(view) => Kind::AccountList(view.into()),
+        };
+        Self {
+            block_height: response.block_height,
+            block_hash: response.block_hash.into(),
+            kind: Some(kind),
+        }
+    }
+}
+
+/// Zero stands for no limit, as proto3 scalars can't be unset.
+fn limit(limit: u32) -> Option<u32> {
+    if limit == 0 {
+        None
+    } else {
+        Some(limit)
+    }
+}
+
+impl TryFrom<proto::query_request::Request> for QueryRequest {
+    type Error = Status;
+
+    fn try_from(request: proto::query_request::Request) -> Result<Self, Status> {
+        use proto::query_request::{
+            view_access_key_list, view_accounts_by_prefix, view_state, Request,
+        };
+        Ok(match request {
+            Request::ViewAccount(request) => {
+                QueryRequest::ViewAccount { account_id: request.account_id }
+            }
+            Request::ViewState(request) => QueryRequest::ViewStatePaginated {
+                account_id: request.account_id,
+                prefix: request.prefix.into(),
+                cursor: request.cursor_value.map(|view_state::CursorValue::Cursor(cursor)| cursor),
+                limit: limit(request.limit),
+                include_proof: request.include_proof,
+            },
+            Request::ViewAccessKey(request) => QueryRequest::ViewAccessKey {
+                account_id: request.account_id,
+                public_key: parse_public_key(&request.public_key)?,
+            },
+            Request::ViewAccessKeyList(request) => QueryRequest::ViewAccessKeyListPaginated {
+                account_id: request.account_id,
+                cursor: match request.cursor_value {
+                    Some(view_access_key_list::CursorValue::Cursor(cursor)) => {
+                        Some(parse_public_key(&cursor)?)
+                    }
+                    None => None,
+                },
+                limit: limit(request.limit),
+            },
+            Request::CallFunction(request) => QueryRequest::CallFunction {
+                account_id: request.account_id,
+                method_name: request.method_name,
+                args: request.args.into(),
+            },
+            Request::ViewContractMetadata(request) => {
+                QueryRequest::ViewContractMetadata { account_id: request.account_id }
+            }
+            Request::ViewAccountsByPrefix(request) => QueryRequest::ViewAccountsByPrefix {
+                prefix: request.prefix,
+                cursor: request
+                    .cursor_value
+                    .map(|view_accounts_by_prefix::CursorValue::Cursor(cursor)| cursor),
+                limit: limit(request.limit),
+            },
+        })
+    }
+}
@@ -0,0 +1,246 @@
+//! gRPC server mirroring the view methods of the JSON-RPC for indexers that need higher
+//! throughput. The protobuf messages mirror the JSON-RPC views, see `proto/near.proto`.
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use actix::{Addr, MailboxError};
+use log::{debug, error, info};
+use tokio::sync::mpsc;
+use tokio::time::{delay_for, timeout};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use near_client::{GetBlock, GetChunk, Query, TxStatus, ViewClientActor};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality};
+use near_primitives::views::{BlockView, QueryRequest};
+
+pub use config::GrpcConfig;
+
+use proto::view_server::{View, ViewServer};
+use proto::{
+    block_reference, chunk_request, BlockRequest, ChunkRequest, FinalExecutionOutcome,
+    StreamBlocksRequest, TxStatusRequest,
+};
+
+mod config;
+mod convert;
+
+/// Generated messages, server and client of `proto/near.proto`.
+pub mod proto {
+    tonic::include_proto!("near");
+}
+
+/// Number of blocks buffered for a slow stream consumer.
+const STREAM_BUFFER_SIZE: usize = 100;
+
+fn mailbox_error(err: MailboxError) -> Status {
+    Status::internal(err.to_string())
+}
+
+fn invalid_hash(_: impl std::fmt::Debug) -> Status {
+    Status::invalid_argument("Invalid hash")
+}
+
+fn parse_finality(finality: i32) -> Result<Finality, Status> {
+    match proto::Finality::from_i32(finality) {
+        Some(proto::Finality::Optimistic) => Ok(Finality::None),
+        Some(proto::Finality::NearFinal) => Ok(Finality::DoomSlug),
+        Some(proto::Finality::Final) => Ok(Finality::Final),
+        None => Err(Status::invalid_argument(format!("Unknown finality {}", finality))),
+    }
+}
+
+/// Missing block references stand for the latest block.
+fn parse_block_reference(
+    block_reference: Option<proto::BlockReference>,
+) -> Result<BlockReference, Status> {
+    Ok(match block_reference.and_then(|block_reference| block_reference.reference) {
+        None => BlockReference::latest(),
+        Some(block_reference::Reference::Height(height)) => {
+            BlockReference::BlockId(BlockId::Height(height))
+        }
+        Some(block_reference::Reference::Hash(hash)) => BlockReference::BlockId(BlockId::Hash(
+            CryptoHash::try_from(hash).map_err(invalid_hash)?,
+        )),
+        Some(block_reference::Reference::Finality(finality)) => {
+            BlockReference::Finality(parse_finality(finality)?)
+        }
+    })
+}
+
+/// Returns false if the client has disconnected.
+async fn send_block(
+    sender: &mut mpsc::Sender<Result<proto::Block, Status>>,
+    block: BlockView,
+) -> bool {
+    sender.send(Ok(block.into())).await.is_ok()
+}
+
+struct ViewService {
+    view_client_addr: Addr<ViewClientActor>,
+    polling_interval: Duration,
+    polling_timeout: Duration,
+}
+
+impl ViewService {
+    async fn get_block(&self, block_reference: BlockReference) -> Result<BlockView, Status> {
+        self.view_client_addr
+            .send(GetBlock(block_reference))
+            .await
+            .map_err(mailbox_error)?
+            .map_err(Status::not_found)
+    }
+}
+
+#[tonic::async_trait]
+impl View for ViewService {
+    async fn block(
+        &self,
+        request: Request<BlockRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        let block_reference = parse_block_reference(request.into_inner().block_reference)?;
+        Ok(Response::new(self.get_block(block_reference).await?.into()))
+    }
+
+    async fn chunk(
+        &self,
+        request: Request<ChunkRequest>,
+    ) -> Result<Response<proto::Chunk>, Status> {
+        let get_chunk = match request.into_inner().chunk {
+            Some(chunk_request::Chunk::ChunkHash(chunk_hash)) => {
+                GetChunk::ChunkHash(CryptoHash::try_from(chunk_hash).map_err(invalid_hash)?.into())
+            }
+            Some(chunk_request::Chunk::BlockShard(block_shard)) => {
+                // Pin the block, the chunk of the block at a finality is looked up by its hash.
+                let block_reference = parse_block_reference(block_shard.block_reference)?;
+                let block = self.get_block(block_reference).await?;
+                GetChunk::BlockHash(block.header.hash, block_shard.shard_id)
+            }
+            None => return Err(Status::invalid_argument("Chunk is not set")),
+        };
+        let chunk = self
+            .view_client_addr
+            .send(get_chunk)
+            .await
+            .map_err(mailbox_error)?
+            .map_err(Status::not_found)?;
+        Ok(Response::new(chunk.into()))
+    }
+
+    async fn query(
+        &self,
+        request: Request<proto::QueryRequest>,
+    ) -> Result<Response<proto::QueryResponse>, Status> {
+        let request = request.into_inner();
+        let block_reference = parse_block_reference(request.block_reference)?;
+        let query_request = QueryRequest::try_from(
+            request.request.ok_or_else(|| Status::invalid_argument("Request is not set"))?,
+        )?;
+        // The view client responds with `None` while it requests the state from other nodes.
+        let query = Query::new(block_reference, query_request);
+        let response = timeout(self.polling_timeout, async {
+            loop {
+                match self.view_client_addr.send(query.clone()).await.map_err(mailbox_error)? {
+                    Ok(Some(response)) => break Ok(response),
+                    Ok(None) => {}
+                    Err(err) => break Err(Status::unknown(err)),
+                }
+                delay_for(self.polling_interval).await;
+            }
+        })
+        .await
+        .map_err(|_| Status::deadline_exceeded("Query timed out"))??;
+        Ok(Response::new(response.into()))
+    }
+
+    async fn tx_status(
+        &self,
+        request: Request<TxStatusRequest>,
+    ) -> Result<Response<FinalExecutionOutcome>, Status> {
+        let TxStatusRequest { tx_hash, sender_id, fetch_receipt } = request.into_inner();
+        let tx_hash = CryptoHash::try_from(tx_hash).map_err(invalid_hash)?;
+        let outcome = timeout(self.polling_timeout, async {
+            loop {
+                let tx_status =
+                    TxStatus { tx_hash, signer_account_id: sender_id.clone(), fetch_receipt };
+                match self.view_client_addr.send(tx_status).await.map_err(mailbox_error)? {
+                    Ok(Some(outcome)) => break Ok(outcome),
+                    Ok(None) => {}
+                    Err(err) => break Err(Status::not_found(String::from(err))),
+                }
+                delay_for(self.polling_interval).await;
+            }
+        })
+        .await
+        .map_err(|_| Status::deadline_exceeded("Transaction is not final yet"))??;
+        Ok(Response::new(outcome.into()))
+    }
+
+    type StreamBlocksStream = mpsc::Receiver<Result<proto::Block, Status>>;
+
+    async fn stream_blocks(
+        &self,
+        request: Request<StreamBlocksRequest>,
+    ) -> Result<Response<Self::StreamBlocksStream>, Status> {
+        let StreamBlocksRequest { from_height, finality } = request.into_inner();
+        let finality = parse_finality(finality)?;
+        let view_client_addr = self.view_client_addr.clone();
+        let polling_interval = self.polling_interval;
+        let (mut sender, receiver) = mpsc::channel(STREAM_BUFFER_SIZE);
+        tokio::spawn(async move {
+            let mut next_height: Option<BlockHeight> =
+                if from_height == 0 { None } else { Some(from_height) };
+            loop {
+                let tip = match view_client_addr
+                    .send(GetBlock(BlockReference::Finality(finality.clone())))
+                    .await
+                {
+                    Ok(Ok(tip)) => tip,
+                    Ok(Err(err)) => {
+                        debug!(target: "grpc", "Failed to get the latest block: {:?}", err);
+                        delay_for(polling_interval).await;
+                        continue;
+                    }
+                    Err(_) => return,
+                };
+                let tip_height = tip.header.height;
+                let from_height = next_height.unwrap_or(tip_height);
+                for height in from_height..tip_height {
+                    let block_reference = BlockReference::BlockId(BlockId::Height(height));
+                    match view_client_addr.send(GetBlock(block_reference)).await {
+                        Ok(Ok(block)) => {
+                            if !send_block(&mut sender, block).await {
+                                return;
+                            }
+                        }
+                        // Heights without a block are skipped.
+                        Ok(Err(_)) => {}
+                        Err(_) => return,
+                    }
+                }
+                if from_height <= tip_height {
+                    if !send_block(&mut sender, tip).await {
+                        return;
+                    }
+                    next_height = Some(tip_height + 1);
+                }
+                delay_for(polling_interval).await;
+            }
+        });
+        Ok(Response::new(receiver))
+    }
+}
+
+pub fn start_grpc(config: GrpcConfig, view_client_addr: Addr<ViewClientActor>) {
+    let GrpcConfig { addr, polling_interval, polling_timeout } = config;
+    let addr = addr.parse().expect("Invalid gRPC server address");
+    let service = ViewService { view_client_addr, polling_interval, polling_timeout };
+    info!(target: "grpc", "Starting gRPC server on {}", addr);
+    actix::spawn(async move {
+        if let Err(err) = Server::builder().add_service(ViewServer::new(service)).serve(addr).await
+        {
+            error!(target: "grpc", "gRPC server failed: {}", err);
+        }
+    });
+}
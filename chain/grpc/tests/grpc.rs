@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use actix::System;
+use tokio::time::timeout;
+
+use near_client::test_utils::setup_no_network;
+use near_grpc::proto::view_client::ViewClient;
+use near_grpc::proto::{
+    block_reference, query_request, query_response, BlockReference, BlockRequest, QueryRequest,
+    StreamBlocksRequest,
+};
+use near_grpc::{start_grpc, GrpcConfig};
+use near_logger_utils::init_test_logger;
+use near_network::test_utils::open_port;
+
+/// Starts a validator producing blocks and queries it through the gRPC server.
+#[test]
+fn test_grpc_view() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_client_addr, view_client_addr) =
+            setup_no_network(vec!["test1", "test2"], "test1", true, false);
+        let addr = format!("127.0.0.1:{}", open_port());
+        start_grpc(GrpcConfig::new(&addr), view_client_addr);
+
+        actix::spawn(async move {
+            let mut client = loop {
+                // The server is started in the background.
+                match ViewClient::connect(format!("http://{}", addr)).await {
+                    Ok(client) => break client,
+                    Err(_) => tokio::time::delay_for(Duration::from_millis(100)).await,
+                }
+            };
+
+            let genesis = client
+                .block(BlockRequest {
+                    block_reference: Some(BlockReference {
+                        reference: Some(block_reference::Reference::Height(0)),
+                    }),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let header = genesis.header.unwrap();
+            assert_eq!(genesis.author, "test1");
+            assert_eq!(header.height, 0);
+            assert_eq!(header.hash.len(), 32);
+            assert_eq!(header.prev_hash, vec![0; 32]);
+
+            let response = client
+                .query(QueryRequest {
+                    block_reference: None,
+                    request: Some(query_request::Request::ViewAccount(
+                        query_request::ViewAccount { account_id: "test1".to_string() },
+                    )),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            match response.kind.unwrap() {
+                query_response::Kind::Account(account) => {
+                    assert!(account.amount.parse::<u128>().unwrap() > 0);
+                    assert_eq!(account.code_hash.len(), 32);
+                }
+                kind => panic!("Unexpected query response {:?}", kind),
+            }
+
+            let error = client
+                .query(QueryRequest { block_reference: None, request: None })
+                .await
+                .unwrap_err();
+            assert_eq!(error.code(), tonic::Code::InvalidArgument);
+
+            let mut stream = client
+                .stream_blocks(StreamBlocksRequest { from_height: 1, finality: 0 })
+                .await
+                .unwrap()
+                .into_inner();
+            let mut heights = vec![];
+            while heights.len() < 3 {
+                let block = timeout(Duration::from_secs(10), stream.message())
+                    .await
+                    .expect("Timed out waiting for blocks")
+                    .unwrap()
+                    .unwrap();
+                heights.push(block.header.unwrap().height);
+            }
+            assert!(heights[0] >= 1);
+            assert!(heights.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", heights);
+
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
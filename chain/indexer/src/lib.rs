@@ -7,9 +7,13 @@
 //! See the [example] for further details.
 //!
 //! [example]: https://github.com/nearprotocol/nearcore/tree/master/tools/indexer/example
+use std::collections::HashSet;
+use std::sync::Arc;
+
 use actix::System;
 use tokio::sync::mpsc;
 
+pub use near_chain::{ReorgAdapter, ReorgEvent};
 pub use neard::{get_default_home, init_configs, NearConfig};
 mod streamer;
 
@@ -18,6 +22,7 @@ pub use self::streamer::{
     StreamerMessage,
 };
 pub use near_primitives;
+use near_primitives::types::{AccountId, NumShards, ShardId, StoreKey};
 
 /// Enum to define a mode of syncing for NEAR Indexer
 #[derive(Debug, Clone)]
@@ -48,6 +53,45 @@ pub struct IndexerConfig {
     pub sync_mode: SyncModeEnum,
     /// Whether await for node to be synced or not
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
+    /// Stream only the events touching given accounts (`None` streams everything)
+    pub filter: Option<IndexerFilter>,
+}
+
+/// Server-side filter for `StreamerMessage`s. Blocks (with their chunk headers) are always
+/// streamed, while transactions, receipts, execution outcomes and state changes are limited to
+/// the ones touching `accounts`. The filter is applied before fetching: only the chunks and the
+/// execution outcomes of the shards the watched accounts live in are fetched, and receipts of
+/// outcomes executed by other accounts are not fetched at all.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Accounts to stream events for
+    pub accounts: HashSet<AccountId>,
+    /// Contract data changes of `accounts` with keys starting with any of these prefixes are
+    /// streamed in `StreamerMessage::data_changes`
+    pub data_key_prefixes: Vec<StoreKey>,
+}
+
+impl IndexerFilter {
+    pub fn new(accounts: impl IntoIterator<Item = AccountId>) -> Self {
+        Self { accounts: accounts.into_iter().collect(), data_key_prefixes: vec![] }
+    }
+
+    pub fn with_data_key_prefix(mut self, key_prefix: StoreKey) -> Self {
+        self.data_key_prefixes.push(key_prefix);
+        self
+    }
+
+    pub fn is_account_watched(&self, account_id: &AccountId) -> bool {
+        self.accounts.contains(account_id)
+    }
+
+    /// Shards of the watched accounts, the only ones whose chunks and outcomes are fetched
+    pub fn watched_shards(&self, num_shards: NumShards) -> HashSet<ShardId> {
+        self.accounts
+            .iter()
+            .map(|account_id| neard::account_id_to_shard_id(account_id, num_shards))
+            .collect()
+    }
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -90,6 +134,13 @@ impl Indexer {
         receiver
     }
 
+    /// Registers an adapter notified whenever the head of the node switches to another fork, with
+    /// the blocks that left the canonical chain. Only the blocks streamed before their finality
+    /// can be abandoned.
+    pub fn subscribe_reorgs(&self, adapter: Arc<dyn ReorgAdapter>) {
+        self.client.do_send(near_client::SubscribeReorgs(adapter));
+    }
+
     /// Expose neard config
     pub fn near_config(&self) -> &neard::NearConfig {
         &self.near_config
@@ -107,3 +158,21 @@ impl Indexer {
         self.actix_runtime.run().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watched_shards() {
+        let filter = IndexerFilter::new(vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(filter.watched_shards(1), vec![0].into_iter().collect());
+        let shards = filter.watched_shards(8);
+        let expected = vec!["alice".to_string(), "bob".to_string()]
+            .iter()
+            .map(|account_id| neard::account_id_to_shard_id(account_id, 8))
+            .collect::<HashSet<_>>();
+        assert_eq!(shards, expected);
+        assert!(IndexerFilter::default().watched_shards(8).is_empty());
+    }
+}
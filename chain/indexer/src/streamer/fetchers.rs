@@ -1,6 +1,6 @@
 //! Streamer watches the network and collects all the blocks and related chunks
 //! into one struct and pushes in in to the given queue
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use actix::Addr;
 use futures::stream::StreamExt;
@@ -12,6 +12,7 @@ pub use near_primitives::{types, views};
 use super::errors::FailedToFetchData;
 use super::types::IndexerExecutionOutcomeWithReceipt;
 use super::INDEXER;
+use crate::IndexerFilter;
 
 pub(crate) async fn fetch_status(
     client: &Addr<near_client::ClientActor>,
@@ -30,7 +31,7 @@ pub(crate) async fn fetch_latest_block(
     client
         .send(near_client::GetBlock(types::BlockReference::Finality(types::Finality::Final)))
         .await?
-        .map_err(FailedToFetchData::String)
+        .map_err(|err| FailedToFetchData::String(err.into()))
 }
 
 /// Fetches specific block by it's height
@@ -43,7 +44,7 @@ pub(crate) async fn fetch_block_by_height(
             near_primitives::types::BlockId::Height(height),
         )))
         .await?
-        .map_err(FailedToFetchData::String)
+        .map_err(|err| FailedToFetchData::String(err.into()))
 }
 
 /// Fetches specific block by it's hash
@@ -54,7 +55,7 @@ pub(crate) async fn fetch_block_by_hash(
     client
         .send(near_client::GetBlock(near_primitives::types::BlockId::Hash(hash).into()))
         .await?
-        .map_err(FailedToFetchData::String)
+        .map_err(|err| FailedToFetchData::String(err.into()))
 }
 
 pub(crate) async fn fetch_state_changes(
@@ -67,19 +68,46 @@ pub(crate) async fn fetch_state_changes(
         .map_err(FailedToFetchData::String)
 }
 
+/// Fetches contract data changes of the filtered accounts for every key prefix in the filter
+pub(crate) async fn fetch_data_changes(
+    client: &Addr<near_client::ViewClientActor>,
+    block_hash: CryptoHash,
+    filter: &IndexerFilter,
+) -> Result<views::StateChangesView, FailedToFetchData> {
+    let mut data_changes = vec![];
+    for key_prefix in &filter.data_key_prefixes {
+        let changes = client
+            .send(near_client::GetStateChanges {
+                block_hash,
+                state_changes_request: views::StateChangesRequestView::DataChanges {
+                    account_ids: filter.accounts.iter().cloned().collect(),
+                    key_prefix: key_prefix.clone(),
+                },
+            })
+            .await?
+            .map_err(FailedToFetchData::String)?;
+        data_changes.extend(changes);
+    }
+    Ok(data_changes)
+}
+
 /// Fetches single chunk (as `near_primitives::views::ChunkView`) by provided `near_client::GetChunk` enum
 async fn fetch_single_chunk(
     client: &Addr<near_client::ViewClientActor>,
     get_chunk: near_client::GetChunk,
 ) -> Result<views::ChunkView, FailedToFetchData> {
-    client.send(get_chunk).await?.map_err(FailedToFetchData::String)
+    client.send(get_chunk).await?.map_err(|err| FailedToFetchData::String(err.into()))
 }
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is Receipt id or Transaction hash and the value is ExecutionOutcome wth id and proof
+/// Only the outcomes of `shards` are kept (all of them if `None`), and receipts are not fetched
+/// for outcomes executed by accounts that are not watched by the `filter`
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    filter: Option<&IndexerFilter>,
+    shards: Option<&HashSet<types::ShardId>>,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithReceipt>>,
     FailedToFetchData,
@@ -94,8 +122,20 @@ pub(crate) async fn fetch_outcomes(
         Vec<IndexerExecutionOutcomeWithReceipt>,
     > = HashMap::new();
     for (shard_id, shard_outcomes) in outcomes {
+        if shards.map_or(false, |shards| !shards.contains(&shard_id)) {
+            continue;
+        }
         let mut outcomes_with_receipts: Vec<IndexerExecutionOutcomeWithReceipt> = vec![];
         for outcome in shard_outcomes {
+            if let Some(filter) = filter {
+                if !filter.is_account_watched(&outcome.outcome.executor_id) {
+                    outcomes_with_receipts.push(IndexerExecutionOutcomeWithReceipt {
+                        execution_outcome: outcome,
+                        receipt: None,
+                    });
+                    continue;
+                }
+            }
             let receipt = match fetch_receipt_by_id(&client, outcome.id).await {
                 Ok(res) => res,
                 Err(e) => {
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::time::Duration;
 
 use actix::Addr;
@@ -6,20 +7,23 @@ use tokio::sync::mpsc;
 use tokio::time;
 use tracing::{debug, info};
 
+use near_primitives::types::{NumShards, ShardId};
 pub use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::{AwaitForNodeSyncedEnum, IndexerConfig, IndexerFilter};
 
 use self::errors::FailedToFetchData;
 use self::fetchers::{
-    fetch_block_by_height, fetch_chunks, fetch_latest_block, fetch_outcomes, fetch_state_changes,
-    fetch_status,
+    fetch_block_by_height, fetch_chunks, fetch_data_changes, fetch_latest_block, fetch_outcomes,
+    fetch_state_changes, fetch_status,
 };
 pub use self::types::{
     IndexerChunkView, IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome,
     StreamerMessage,
 };
-use self::utils::convert_transactions_sir_into_local_receipts;
+use self::utils::{
+    convert_transactions_sir_into_local_receipts, filter_chunk, filter_state_changes,
+};
 
 mod errors;
 mod fetchers;
@@ -31,17 +35,24 @@ const INTERVAL: Duration = Duration::from_millis(500);
 
 /// This function supposed to return the entire `StreamerMessage`.
 /// It fetches the block and all related parts (chunks, outcomes, state changes etc.)
-/// and returns everything together in one struct
+/// and returns everything together in one struct.
+/// If `filter` is given, only the chunks and outcomes of the shards of the watched accounts are
+/// fetched and only the events touching the watched accounts are kept.
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
     near_config: &neard::NearConfig,
+    filter: Option<&IndexerFilter>,
 ) -> Result<StreamerMessage, FailedToFetchData> {
+    let watched_shards: Option<HashSet<ShardId>> =
+        filter.map(|filter| filter.watched_shards(block.chunks.len() as NumShards));
     let chunks_to_fetch = block
         .chunks
         .iter()
         .filter_map(|c| {
-            if c.height_included == block.header.height {
+            let is_watched =
+                watched_shards.as_ref().map_or(true, |shards| shards.contains(&c.shard_id));
+            if c.height_included == block.header.height && is_watched {
                 Some(c.chunk_hash)
             } else {
                 None
@@ -51,7 +62,8 @@ async fn build_streamer_message(
     let chunks = fetch_chunks(&client, chunks_to_fetch).await?;
 
     let mut local_receipts: Vec<views::ReceiptView> = vec![];
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes =
+        fetch_outcomes(&client, block.header.hash, filter, watched_shards.as_ref()).await?;
     let mut indexer_chunks: Vec<IndexerChunkView> = vec![];
 
     for chunk in chunks {
@@ -101,18 +113,29 @@ async fn build_streamer_message(
             }
         }
 
-        indexer_chunks.push(IndexerChunkView {
+        let mut indexer_chunk = IndexerChunkView {
             author,
             header,
             transactions: indexer_transactions,
             receipts: chunk_receipts,
             receipt_execution_outcomes: receipt_outcomes,
-        });
+        };
+        if let Some(filter) = filter {
+            filter_chunk(&mut indexer_chunk, filter);
+        }
+        indexer_chunks.push(indexer_chunk);
     }
 
-    let state_changes = fetch_state_changes(&client, block.header.hash).await?;
+    let mut state_changes = fetch_state_changes(&client, block.header.hash).await?;
+    let data_changes = match filter {
+        Some(filter) => {
+            state_changes = filter_state_changes(state_changes, filter);
+            fetch_data_changes(&client, block.header.hash, filter).await?
+        }
+        None => vec![],
+    };
 
-    Ok(StreamerMessage { block, chunks: indexer_chunks, state_changes })
+    Ok(StreamerMessage { block, chunks: indexer_chunks, state_changes, data_changes })
 }
 
 /// Function that starts Streamer's busy loop. Every half a seconds it fetches the status
@@ -180,7 +203,13 @@ pub(crate) async fn start(
         );
         for block_height in start_syncing_block_height..=latest_block_height {
             if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block, &near_config).await;
+                let response = build_streamer_message(
+                    &view_client,
+                    block,
+                    &near_config,
+                    indexer_config.filter.as_ref(),
+                )
+                .await;
 
                 match response {
                     Ok(streamer_message) => {
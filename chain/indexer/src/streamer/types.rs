@@ -7,6 +7,8 @@ pub struct StreamerMessage {
     pub block: views::BlockView,
     pub chunks: Vec<IndexerChunkView>,
     pub state_changes: views::StateChangesKindsView,
+    /// Contract data changes matching `IndexerFilter::data_key_prefixes` (empty without a filter)
+    pub data_changes: views::StateChangesView,
 }
 
 #[derive(Debug)]
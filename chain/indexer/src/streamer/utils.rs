@@ -7,7 +7,8 @@ use node_runtime::config::tx_cost;
 
 use super::errors::FailedToFetchData;
 use super::fetchers::fetch_block_by_hash;
-use super::IndexerTransactionWithOutcome;
+use super::{IndexerChunkView, IndexerTransactionWithOutcome};
+use crate::IndexerFilter;
 
 pub(crate) async fn convert_transactions_sir_into_local_receipts(
     client: &Addr<near_client::ViewClientActor>,
@@ -65,3 +66,174 @@ pub(crate) async fn convert_transactions_sir_into_local_receipts(
 
     Ok(local_receipts)
 }
+
+/// Drops transactions, receipts and execution outcomes that don't touch any of the watched accounts
+pub(crate) fn filter_chunk(chunk: &mut IndexerChunkView, filter: &IndexerFilter) {
+    chunk.transactions.retain(|tx| {
+        filter.is_account_watched(&tx.transaction.signer_id)
+            || filter.is_account_watched(&tx.transaction.receiver_id)
+    });
+    chunk.receipts.retain(|receipt| {
+        filter.is_account_watched(&receipt.predecessor_id)
+            || filter.is_account_watched(&receipt.receiver_id)
+    });
+    chunk.receipt_execution_outcomes.retain(|outcome| {
+        filter.is_account_watched(&outcome.execution_outcome.outcome.executor_id)
+    });
+}
+
+/// Keeps only the state changes of the watched accounts
+pub(crate) fn filter_state_changes(
+    state_changes: views::StateChangesKindsView,
+    filter: &IndexerFilter,
+) -> views::StateChangesKindsView {
+    state_changes
+        .into_iter()
+        .filter(|state_change| {
+            let account_id = match state_change {
+                views::StateChangeKindView::AccountTouched { account_id }
+                | views::StateChangeKindView::AccessKeyTouched { account_id }
+                | views::StateChangeKindView::DataTouched { account_id }
+                | views::StateChangeKindView::ContractCodeTouched { account_id } => account_id,
+            };
+            filter.is_account_watched(account_id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, PublicKey, Signature};
+    use near_primitives::hash::CryptoHash;
+
+    use super::*;
+    use crate::IndexerExecutionOutcomeWithReceipt;
+
+    fn outcome(executor_id: &str) -> IndexerExecutionOutcomeWithReceipt {
+        IndexerExecutionOutcomeWithReceipt {
+            execution_outcome: views::ExecutionOutcomeWithIdView {
+                proof: vec![],
+                block_hash: CryptoHash::default(),
+                id: CryptoHash::default(),
+                outcome: views::ExecutionOutcomeView {
+                    logs: vec![],
+                    receipt_ids: vec![],
+                    gas_burnt: 0,
+                    tokens_burnt: 0,
+                    executor_id: executor_id.to_string(),
+                    status: views::ExecutionStatusView::Unknown,
+                },
+            },
+            receipt: None,
+        }
+    }
+
+    fn transaction(signer_id: &str, receiver_id: &str) -> IndexerTransactionWithOutcome {
+        IndexerTransactionWithOutcome {
+            transaction: views::SignedTransactionView {
+                signer_id: signer_id.to_string(),
+                public_key: PublicKey::empty(KeyType::ED25519),
+                nonce: 0,
+                receiver_id: receiver_id.to_string(),
+                actions: vec![],
+                signature: Signature::empty(KeyType::ED25519),
+                hash: CryptoHash::default(),
+            },
+            outcome: outcome(signer_id),
+        }
+    }
+
+    fn receipt(predecessor_id: &str, receiver_id: &str) -> views::ReceiptView {
+        views::ReceiptView {
+            predecessor_id: predecessor_id.to_string(),
+            receiver_id: receiver_id.to_string(),
+            receipt_id: CryptoHash::default(),
+            receipt: views::ReceiptEnumView::Data { data_id: CryptoHash::default(), data: None },
+        }
+    }
+
+    fn chunk(
+        transactions: Vec<IndexerTransactionWithOutcome>,
+        receipts: Vec<views::ReceiptView>,
+        receipt_execution_outcomes: Vec<IndexerExecutionOutcomeWithReceipt>,
+    ) -> IndexerChunkView {
+        IndexerChunkView {
+            author: "test".to_string(),
+            header: views::ChunkHeaderView {
+                chunk_hash: CryptoHash::default(),
+                prev_block_hash: CryptoHash::default(),
+                outcome_root: CryptoHash::default(),
+                prev_state_root: CryptoHash::default(),
+                encoded_merkle_root: CryptoHash::default(),
+                encoded_length: 0,
+                height_created: 0,
+                height_included: 0,
+                shard_id: 0,
+                gas_used: 0,
+                gas_limit: 0,
+                rent_paid: 0,
+                validator_reward: 0,
+                balance_burnt: 0,
+                outgoing_receipts_root: CryptoHash::default(),
+                tx_root: CryptoHash::default(),
+                validator_proposals: vec![],
+                signature: Signature::empty(KeyType::ED25519),
+            },
+            transactions,
+            receipts,
+            receipt_execution_outcomes,
+        }
+    }
+
+    #[test]
+    fn test_filter_chunk() {
+        let filter = IndexerFilter::new(vec!["alice".to_string()]);
+        let mut indexer_chunk = chunk(
+            vec![
+                transaction("alice", "bob"),
+                transaction("bob", "alice"),
+                transaction("bob", "carol"),
+            ],
+            vec![receipt("alice", "bob"), receipt("bob", "alice"), receipt("bob", "carol")],
+            vec![outcome("alice"), outcome("bob")],
+        );
+        filter_chunk(&mut indexer_chunk, &filter);
+        let transactions = indexer_chunk
+            .transactions
+            .iter()
+            .map(|tx| (tx.transaction.signer_id.as_str(), tx.transaction.receiver_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(transactions, vec![("alice", "bob"), ("bob", "alice")]);
+        let receipts = indexer_chunk
+            .receipts
+            .iter()
+            .map(|receipt| (receipt.predecessor_id.as_str(), receipt.receiver_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(receipts, vec![("alice", "bob"), ("bob", "alice")]);
+        assert_eq!(indexer_chunk.receipt_execution_outcomes.len(), 1);
+        assert_eq!(
+            indexer_chunk.receipt_execution_outcomes[0].execution_outcome.outcome.executor_id,
+            "alice"
+        );
+    }
+
+    #[test]
+    fn test_filter_state_changes() {
+        let filter = IndexerFilter::new(vec!["alice".to_string(), "bob".to_string()]);
+        let state_changes = vec![
+            views::StateChangeKindView::AccountTouched { account_id: "alice".to_string() },
+            views::StateChangeKindView::DataTouched { account_id: "carol".to_string() },
+            views::StateChangeKindView::AccessKeyTouched { account_id: "bob".to_string() },
+        ];
+        let state_changes = filter_state_changes(state_changes, &filter);
+        assert_eq!(state_changes.len(), 2);
+        assert!(matches!(
+            &state_changes[0],
+            views::StateChangeKindView::AccountTouched { account_id } if account_id == "alice"
+        ));
+        assert!(matches!(
+            &state_changes[1],
+            views::StateChangeKindView::AccessKeyTouched { account_id } if account_id == "bob"
+        ));
+    }
+}
@@ -5,14 +5,25 @@ use futures::{future, future::LocalBoxFuture, FutureExt, TryFutureExt};
 use serde::Deserialize;
 use serde::Serialize;
 
+use near_chain_configs::ProtocolConfigView;
+use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
 use near_primitives::rpc::{
-    RpcQueryRequest, RpcStateChangesRequest, RpcStateChangesResponse, RpcValidatorsOrderedRequest,
+    RpcChunkReference, RpcChunkRequest, RpcEstimateFeeRequest, RpcEstimateFeeResponse,
+    RpcGasPriceHistoryRequest, RpcLightClientExecutionProofRequest,
+    RpcLightClientExecutionProofResponse, RpcQueryRequest, RpcReceiptRequest,
+    RpcSendTransactionRequest, RpcSendTransactionResponse, RpcStateChangesRequest,
+    RpcStateChangesResponse, RpcValidatorsOrderedRequest, RpcWatchedAccountChangesRequest,
+};
+use near_primitives::types::{
+    BlockId, BlockReference, EpochReference, MaybeBlockId, NumBlocks, ShardId,
 };
-use near_primitives::types::{BlockId, BlockReference, MaybeBlockId, ShardId};
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
-    QueryResponse, StatusResponse, ValidatorStakeView,
+    BlockView, ChunkView, ChunkWithProofsView, DebugStatusView, EpochValidatorInfo, FeeHistoryView,
+    FinalExecutionOutcomeView, FinalExecutionOutcomeWithReceiptView, GasPriceView,
+    IdempotencyKeyView, KnownPeerView, PeerAccessChangeView, PeerAccessView, PeerReputationView,
+    ProtocolFeaturesView, QueryResponse, ReceiptWithOutcomeView, RoutingInfoView, StatusResponse,
+    ValidatorStakeView, WatchedAccountChangesView,
 };
 
 use crate::message::{from_slice, Message, RpcError};
@@ -188,9 +199,47 @@ jsonrpc_client!(pub struct JsonRpcClient {
     pub fn EXPERIMENTAL_genesis_config(&self) -> RpcRequest<serde_json::Value>;
     pub fn health(&self) -> RpcRequest<()>;
     pub fn tx(&self, hash: String, account_id: String) -> RpcRequest<FinalExecutionOutcomeView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_tx_status(
+        &self,
+        tx: String
+    ) -> RpcRequest<FinalExecutionOutcomeWithReceiptView>;
     pub fn chunk(&self, id: ChunkId) -> RpcRequest<ChunkView>;
     pub fn validators(&self, block_id: MaybeBlockId) -> RpcRequest<EpochValidatorInfo>;
     pub fn gas_price(&self, block_id: MaybeBlockId) -> RpcRequest<GasPriceView>;
+    pub fn fee_history(
+        &self,
+        block_count: NumBlocks,
+        block_id: MaybeBlockId
+    ) -> RpcRequest<FeeHistoryView>;
+    pub fn network_info(&self) -> RpcRequest<serde_json::Value>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_routing_info(&self) -> RpcRequest<RoutingInfoView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_debug_status(&self) -> RpcRequest<DebugStatusView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_debug_peer_store(&self) -> RpcRequest<Vec<KnownPeerView>>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_debug_peer_reputation(&self) -> RpcRequest<Vec<PeerReputationView>>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_peer_access(&self) -> RpcRequest<PeerAccessView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_update_peer_access(
+        &self,
+        change: PeerAccessChangeView
+    ) -> RpcRequest<PeerAccessView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_gc_pause(&self, paused: bool) -> RpcRequest<bool>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_undo_blocks(&self, num_blocks: NumBlocks) -> RpcRequest<Tip>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_protocol_features(&self) -> RpcRequest<ProtocolFeaturesView>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_idempotency_key(
+        &self,
+        account_id: String,
+        key: String
+    ) -> RpcRequest<IdempotencyKeyView>;
 });
 
 impl JsonRpcClient {
@@ -204,6 +253,20 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "query", request)
     }
 
+    pub fn send_tx(
+        &self,
+        request: RpcSendTransactionRequest,
+    ) -> RpcRequest<RpcSendTransactionResponse> {
+        call_method(&self.client, &self.server_addr, "send_tx", request)
+    }
+
+    pub fn validators_by_epoch(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> RpcRequest<EpochValidatorInfo> {
+        call_method(&self.client, &self.server_addr, "validators", epoch_reference)
+    }
+
     pub fn block_by_id(&self, block_id: BlockId) -> RpcRequest<BlockView> {
         call_method(&self.client, &self.server_addr, "block", [block_id])
     }
@@ -212,6 +275,14 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "block", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_protocol_config(
+        &self,
+        request: BlockReference,
+    ) -> RpcRequest<ProtocolConfigView> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_protocol_config", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_changes(
         &self,
@@ -220,6 +291,29 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_changes", request)
     }
 
+    pub fn chunk_with_proofs(
+        &self,
+        chunk_reference: RpcChunkReference,
+    ) -> RpcRequest<ChunkWithProofsView> {
+        let request = RpcChunkRequest { chunk_reference, with_proofs: true };
+        call_method(&self.client, &self.server_addr, "chunk", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_receipt(
+        &self,
+        request: RpcReceiptRequest,
+    ) -> RpcRequest<ReceiptWithOutcomeView> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_receipt", request)
+    }
+
+    pub fn light_client_proof(
+        &self,
+        request: RpcLightClientExecutionProofRequest,
+    ) -> RpcRequest<RpcLightClientExecutionProofResponse> {
+        call_method(&self.client, &self.server_addr, "light_client_proof", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_validators_ordered(
         &self,
@@ -227,6 +321,35 @@ impl JsonRpcClient {
     ) -> RpcRequest<Vec<ValidatorStakeView>> {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_validators_ordered", request)
     }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_estimate_fee(
+        &self,
+        request: RpcEstimateFeeRequest,
+    ) -> RpcRequest<RpcEstimateFeeResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_estimate_fee", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_gas_price_history(
+        &self,
+        request: RpcGasPriceHistoryRequest,
+    ) -> RpcRequest<FeeHistoryView> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_gas_price_history", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_watched_account_changes(
+        &self,
+        request: RpcWatchedAccountChangesRequest,
+    ) -> RpcRequest<WatchedAccountChangesView> {
+        call_method(
+            &self.client,
+            &self.server_addr,
+            "EXPERIMENTAL_watched_account_changes",
+            request,
+        )
+    }
 }
 
 fn create_client() -> Client {
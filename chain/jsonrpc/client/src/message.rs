@@ -72,14 +72,109 @@ impl Request {
     }
 }
 
+/// Category of a structured error.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcErrorName {
+    /// The request is malformed, sending it again won't help.
+    RequestValidationError,
+    /// The request is valid, but the node couldn't serve it.
+    HandlerError,
+    InternalError,
+}
+
+/// What exactly went wrong. Unlike the messages, the names are stable, so clients can branch on
+/// them.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcErrorCauseName {
+    ParseError,
+    InvalidRequest,
+    MethodNotFound,
+    InvalidParams,
+    UnknownBlock,
+    GarbageCollectedBlock,
+    UnknownChunk,
+    InvalidShardId,
+    UnknownTransaction,
+    UnknownReceipt,
+    InvalidTransaction,
+    NotSyncedYet,
+    Timeout,
+    Closed,
+    RateLimited,
+    RequestTooLarge,
+    ResponseTooLarge,
+    InternalError,
+}
+
+impl RpcErrorCauseName {
+    pub fn error_name(self) -> RpcErrorName {
+        match self {
+            RpcErrorCauseName::ParseError
+            | RpcErrorCauseName::InvalidRequest
+            | RpcErrorCauseName::MethodNotFound
+            | RpcErrorCauseName::InvalidParams
+            | RpcErrorCauseName::RequestTooLarge => RpcErrorName::RequestValidationError,
+            RpcErrorCauseName::InternalError | RpcErrorCauseName::Closed => {
+                RpcErrorName::InternalError
+            }
+            _ => RpcErrorName::HandlerError,
+        }
+    }
+
+    /// Whether the same request may succeed later.
+    pub fn is_retriable(self) -> bool {
+        match self {
+            RpcErrorCauseName::NotSyncedYet
+            | RpcErrorCauseName::Timeout
+            | RpcErrorCauseName::Closed
+            | RpcErrorCauseName::RateLimited => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RpcErrorCause {
+    pub name: RpcErrorCauseName,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<Value>,
+}
+
+/// Causes unknown to this version are dropped, so that newer nodes can add them.
+fn deserialize_cause<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<RpcErrorCause>, D::Error> {
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| serde_json::from_value(value).ok()))
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 /// An error code.
+///
+/// `code`, `message` and `data` are kept as they were before the structured `name`, `cause` and
+/// `retriable` fields were added, so existing clients keep working. Unknown fields are ignored, so
+/// that newer nodes can add more.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(deny_unknown_fields)]
 pub struct RpcError {
     pub code: i64,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<RpcErrorName>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_cause"
+    )]
+    pub cause: Option<RpcErrorCause>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub retriable: bool,
 }
 
 impl RpcError {
@@ -87,7 +182,18 @@ impl RpcError {
     ///
     /// Mostly for completeness, doesn't do anything but filling in the corresponding fields.
     pub fn new(code: i64, message: String, data: Option<Value>) -> Self {
-        RpcError { code, message, data }
+        RpcError { code, message, data, name: None, cause: None, retriable: false }
+    }
+    /// Adds the structured description of the error.
+    pub fn with_cause(mut self, cause: RpcErrorCauseName, info: Option<Value>) -> Self {
+        self.name = Some(cause.error_name());
+        self.retriable = cause.is_retriable();
+        self.cause = Some(RpcErrorCause { name: cause, info });
+        self
+    }
+    /// The name of the cause of a structured error.
+    pub fn cause_name(&self) -> Option<RpcErrorCauseName> {
+        self.cause.as_ref().map(|cause| cause.name)
     }
     /// Create an Invalid Param error.
     pub fn invalid_params(data: impl Serialize) -> Self {
@@ -100,7 +206,8 @@ impl RpcError {
                 )))
             }
         };
-        RpcError::new(-32_602, "Invalid params".to_owned(), Some(value))
+        RpcError::new(-32_602, "Invalid params".to_owned(), Some(value.clone()))
+            .with_cause(RpcErrorCauseName::InvalidParams, Some(value))
     }
     /// Create a server error.
     pub fn server_error<E: Serialize>(e: Option<E>) -> Self {
@@ -113,14 +220,17 @@ impl RpcError {
     /// Create an invalid request error.
     pub fn invalid_request() -> Self {
         RpcError::new(-32_600, "Invalid request".to_owned(), None)
+            .with_cause(RpcErrorCauseName::InvalidRequest, None)
     }
     /// Create a parse error.
     pub fn parse_error(e: String) -> Self {
-        RpcError::new(-32_700, "Parse error".to_owned(), Some(Value::String(e)))
+        RpcError::new(-32_700, "Parse error".to_owned(), Some(Value::String(e.clone())))
+            .with_cause(RpcErrorCauseName::ParseError, Some(Value::String(e)))
     }
     /// Create a method not found error.
     pub fn method_not_found(method: String) -> Self {
-        RpcError::new(-32_601, "Method not found".to_owned(), Some(Value::String(method)))
+        RpcError::new(-32_601, "Method not found".to_owned(), Some(Value::String(method.clone())))
+            .with_cause(RpcErrorCauseName::MethodNotFound, Some(Value::String(method)))
     }
 }
 
@@ -566,4 +676,69 @@ mod tests {
             panic!("Not a response");
         }
     }
+
+    /// Structured errors round-trip, and causes unknown to the client don't break parsing.
+    #[test]
+    fn structured_error_serde() {
+        let error =
+            RpcError::server_error(Some("Timeout")).with_cause(RpcErrorCauseName::Timeout, None);
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "code": -32_000,
+                "message": "Server error",
+                "data": "Timeout",
+                "name": "HANDLER_ERROR",
+                "cause": {"name": "TIMEOUT"},
+                "retriable": true,
+            })
+        );
+        assert_eq!(serde_json::from_value::<RpcError>(value).unwrap(), error);
+
+        let error: RpcError = serde_json::from_value(json!({
+            "code": -32_000,
+            "message": "Server error",
+            "name": "HANDLER_ERROR",
+            "cause": {"name": "SOMETHING_NEW", "info": {}},
+        }))
+        .unwrap();
+        assert_eq!(error.name, Some(RpcErrorName::HandlerError));
+        assert_eq!(error.cause, None);
+        assert!(!error.retriable);
+    }
+
+    /// Clients that only know `code`, `message` and `data` parse the structured errors, and this
+    /// client parses errors with fields it doesn't know.
+    #[test]
+    fn structured_error_compatibility() {
+        #[derive(Deserialize)]
+        struct OldRpcError {
+            code: i64,
+            message: String,
+            data: Option<Value>,
+        }
+
+        let error = RpcError::invalid_params("Missing account_id");
+        let response = Message::Response(Response {
+            jsonrpc: Version,
+            result: Err(error.clone()),
+            id: json!(1),
+        });
+        let response: Value = serde_json::to_value(&response).unwrap();
+        let old_error: OldRpcError = serde_json::from_value(response["error"].clone()).unwrap();
+        assert_eq!(old_error.code, error.code);
+        assert_eq!(old_error.message, error.message);
+        assert_eq!(old_error.data, error.data);
+
+        let response = r#"{"jsonrpc": "2.0", "id": 1, "error": {
+            "code": -32602, "message": "Invalid params", "name": "REQUEST_VALIDATION_ERROR",
+            "cause": {"name": "INVALID_PARAMS"}, "trace_id": "abc"}}"#;
+        match from_str(response).unwrap() {
+            Message::Response(Response { result: Err(error), .. }) => {
+                assert_eq!(error.cause_name(), Some(RpcErrorCauseName::InvalidParams));
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
 }
@@ -0,0 +1,147 @@
+//! Export of final blocks with their chunks and execution outcomes as newline-delimited JSON.
+//!
+//! `GET /blocks/stream?from_height=N` streams one `StreamedBlock` per line, in the order of
+//! heights, up to the final head or `to_height`. With `follow=true` the stream waits for new final
+//! blocks instead of ending. Blocks are fetched only when the connection is ready to send more, so
+//! slow readers don't make the node buffer the chain in memory. If a block can't be fetched, the
+//! last line is `{"error": ...}`.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::Addr;
+use actix_web::web::Bytes;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::time::delay_for;
+
+use near_client::{
+    GetBlock, GetBlockError, GetChunk, GetExecutionOutcomesForBlock, ViewClientActor,
+};
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality, ShardId};
+use near_primitives::views::{BlockView, ChunkView, ExecutionOutcomeWithIdView};
+
+pub const CONTENT_TYPE: &str = "application/x-ndjson";
+
+#[derive(Deserialize)]
+pub struct BlockStreamRequest {
+    pub from_height: BlockHeight,
+    /// Last height to stream, inclusive.
+    #[serde(default)]
+    pub to_height: Option<BlockHeight>,
+    /// Whether to wait for new final blocks once the final head is reached.
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// A block with the chunks included in it and the outcomes of its execution.
+#[derive(Serialize)]
+struct StreamedBlock {
+    block: BlockView,
+    /// Only the chunks that are new in this block, missing chunks are skipped.
+    chunks: Vec<ChunkView>,
+    outcomes: HashMap<ShardId, Vec<ExecutionOutcomeWithIdView>>,
+}
+
+#[derive(Serialize)]
+struct StreamError {
+    error: String,
+}
+
+struct StreamState {
+    view_client_addr: Addr<ViewClientActor>,
+    polling_interval: Duration,
+    next_height: BlockHeight,
+    to_height: Option<BlockHeight>,
+    follow: bool,
+    /// Height of the final head as of the last check.
+    final_height: Option<BlockHeight>,
+}
+
+pub fn block_stream(
+    view_client_addr: Addr<ViewClientActor>,
+    polling_interval: Duration,
+    request: BlockStreamRequest,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let state = StreamState {
+        view_client_addr,
+        polling_interval,
+        next_height: request.from_height,
+        to_height: request.to_height,
+        follow: request.follow,
+        final_height: None,
+    };
+    futures::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        match next_block(&mut state).await {
+            Ok(Some(block)) => Some((Ok(to_line(&block)), Some(state))),
+            Ok(None) => None,
+            Err(error) => Some((Ok(to_line(&StreamError { error })), None)),
+        }
+    })
+}
+
+/// Returns the next block to stream, or `None` once the stream is over.
+async fn next_block(state: &mut StreamState) -> Result<Option<StreamedBlock>, String> {
+    loop {
+        if state.to_height.map_or(false, |to_height| state.next_height > to_height) {
+            return Ok(None);
+        }
+        if state.final_height.map_or(true, |final_height| state.next_height > final_height) {
+            let head = state
+                .view_client_addr
+                .send(GetBlock(BlockReference::Finality(Finality::Final)))
+                .await
+                .map_err(|err| err.to_string())??;
+            state.final_height = Some(head.header.height);
+            if state.next_height > head.header.height {
+                if !state.follow {
+                    return Ok(None);
+                }
+                delay_for(state.polling_interval).await;
+                continue;
+            }
+        }
+        let height = state.next_height;
+        state.next_height += 1;
+        if let Some(block) = fetch_block(&state.view_client_addr, height).await? {
+            return Ok(Some(block));
+        }
+    }
+}
+
+/// Fetches the block at the given height, or `None` if the height was skipped.
+async fn fetch_block(
+    view_client_addr: &Addr<ViewClientActor>,
+    height: BlockHeight,
+) -> Result<Option<StreamedBlock>, String> {
+    let block = match view_client_addr
+        .send(GetBlock(BlockReference::BlockId(BlockId::Height(height))))
+        .await
+        .map_err(|err| err.to_string())?
+    {
+        Ok(block) => block,
+        Err(GetBlockError::UnknownBlock(_)) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut chunks = vec![];
+    for chunk in block.chunks.iter().filter(|chunk| chunk.height_included == height) {
+        chunks.push(
+            view_client_addr
+                .send(GetChunk::ChunkHash(ChunkHash(chunk.chunk_hash)))
+                .await
+                .map_err(|err| err.to_string())??,
+        );
+    }
+    let outcomes = view_client_addr
+        .send(GetExecutionOutcomesForBlock { block_hash: block.header.hash })
+        .await
+        .map_err(|err| err.to_string())??;
+    Ok(Some(StreamedBlock { block, chunks, outcomes }))
+}
+
+fn to_line<T: Serialize>(value: &T) -> Bytes {
+    let mut line = serde_json::to_vec(value).expect("views are always serializable");
+    line.push(b'\n');
+    Bytes::from(line)
+}
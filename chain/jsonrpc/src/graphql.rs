@@ -0,0 +1,460 @@
+//! GraphQL endpoint over the view methods, built with the `graphql` feature.
+//!
+//! `POST /graphql` serves accounts, access keys, blocks, chunks, transactions and execution
+//! outcomes. Nested fields are resolved only when they are selected, so a query for the heights of
+//! blocks doesn't fetch their chunks, and `block { chunks { transactions { outcome } } }` fetches
+//! everything in one request.
+//!
+//! Requests count against the rate limit of the view methods, and the depth and complexity of the
+//! queries are limited, as a single query can make many calls to the view client.
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use actix::Addr;
+use actix_web::{web, HttpRequest, HttpResponse};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Json, Object, Result, Schema};
+use futures::lock::Mutex;
+use serde_json::json;
+use tokio::time::{delay_for, timeout};
+
+use near_client::{
+    GetBlock, GetChunk, GetExecutionOutcome, GetExecutionOutcomesForBlock, Query, ViewClientActor,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{
+    AccountId, BlockId, BlockReference, Finality, TransactionOrReceiptId,
+};
+use near_primitives::views::{
+    AccessKeyInfoView, AccessKeyPermissionView, AccountView, BlockView, ChunkHeaderView, ChunkView,
+    ExecutionOutcomeWithIdView, ExecutionStatusView, QueryRequest, QueryResponse,
+    QueryResponseKind, SignedTransactionView,
+};
+
+use crate::{JsonRpcHandler, RpcPollingConfig};
+
+/// Deep enough for every query of the schema and for the introspection query of GraphiQL.
+const MAX_QUERY_DEPTH: usize = 12;
+/// Every selected field counts as one, which bounds the number of aliased fields, and so the
+/// number of calls to the view client made by a query.
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+pub type GraphQLSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn configure(
+    cfg: &mut web::ServiceConfig,
+    view_client_addr: Addr<ViewClientActor>,
+    polling_config: RpcPollingConfig,
+) {
+    let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(ViewClient { addr: view_client_addr, polling_config })
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish();
+    cfg.data(schema).service(web::resource("/graphql").route(web::post().to(graphql_handler)));
+}
+
+async fn graphql_handler(
+    http_request: HttpRequest,
+    handler: web::Data<JsonRpcHandler>,
+    schema: web::Data<GraphQLSchema>,
+    request: web::Json<async_graphql::Request>,
+) -> HttpResponse {
+    let client_ip = http_request.peer_addr().map(|addr| addr.ip());
+    if let Err(err) = handler.check_rate_limit(client_ip, "graphql") {
+        return HttpResponse::TooManyRequests().json(json!({
+            "errors": [{"message": err.data, "extensions": {"cause": err.cause}}],
+        }));
+    }
+    HttpResponse::Ok().json(schema.execute(request.into_inner()).await)
+}
+
+/// Calls to the view client made by the resolvers.
+struct ViewClient {
+    addr: Addr<ViewClientActor>,
+    polling_config: RpcPollingConfig,
+}
+
+impl ViewClient {
+    async fn block(&self, block_reference: BlockReference) -> Result<BlockView, String> {
+        Ok(self.addr.send(GetBlock(block_reference)).await.map_err(|err| err.to_string())??)
+    }
+
+    async fn chunk(&self, chunk_hash: CryptoHash) -> Result<ChunkView, String> {
+        Ok(self
+            .addr
+            .send(GetChunk::ChunkHash(ChunkHash(chunk_hash)))
+            .await
+            .map_err(|err| err.to_string())??)
+    }
+
+    async fn outcome(
+        &self,
+        id: TransactionOrReceiptId,
+    ) -> Result<ExecutionOutcomeWithIdView, String> {
+        let response =
+            self.addr.send(GetExecutionOutcome { id }).await.map_err(|err| err.to_string())??;
+        Ok(response.outcome_proof)
+    }
+
+    async fn outcomes_for_block(
+        &self,
+        block_hash: CryptoHash,
+    ) -> Result<Vec<ExecutionOutcomeWithIdView>, String> {
+        let outcomes = self
+            .addr
+            .send(GetExecutionOutcomesForBlock { block_hash })
+            .await
+            .map_err(|err| err.to_string())??;
+        let mut shards: Vec<_> = outcomes.into_iter().collect();
+        shards.sort_by_key(|(shard_id, _)| *shard_id);
+        Ok(shards.into_iter().flat_map(|(_, outcomes)| outcomes).collect())
+    }
+
+    /// Runs the query, waiting for the view client to be able to answer it like the `query`
+    /// RPC method does.
+    async fn query(
+        &self,
+        block_reference: BlockReference,
+        request: QueryRequest,
+    ) -> Result<QueryResponse, String> {
+        let query = Query::new(block_reference, request);
+        timeout(self.polling_config.polling_timeout, async {
+            loop {
+                match self.addr.send(query.clone()).await.map_err(|err| err.to_string())?? {
+                    Some(response) => match response.kind {
+                        QueryResponseKind::Error(error) => break Err(error.error),
+                        _ => break Ok(response),
+                    },
+                    None => delay_for(self.polling_config.polling_interval).await,
+                }
+            }
+        })
+        .await
+        .map_err(|_| "query has timed out".to_string())?
+    }
+}
+
+fn view_client<'a>(ctx: &'a Context<'_>) -> Result<&'a ViewClient> {
+    ctx.data::<ViewClient>()
+}
+
+fn parse_hash(hash: &str) -> Result<CryptoHash, String> {
+    CryptoHash::try_from(hash).map_err(|_| format!("Invalid hash {}", hash))
+}
+
+/// The block given by hash or height, the latest final block if neither is given.
+fn block_reference(hash: Option<String>, height: Option<u64>) -> Result<BlockReference, String> {
+    match (hash, height) {
+        (Some(_), Some(_)) => Err("Only one of hash and height can be given".to_string()),
+        (Some(hash), None) => Ok(BlockReference::BlockId(BlockId::Hash(parse_hash(&hash)?))),
+        (None, Some(height)) => Ok(BlockReference::BlockId(BlockId::Height(height))),
+        (None, None) => Ok(BlockReference::Finality(Finality::Final)),
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Block by hash or height, the latest final block if neither is given.
+    async fn block(
+        &self,
+        ctx: &Context<'_>,
+        hash: Option<String>,
+        height: Option<u64>,
+    ) -> Result<Block> {
+        let block = view_client(ctx)?.block(block_reference(hash, height)?).await?;
+        Ok(Block(block))
+    }
+
+    async fn chunk(&self, ctx: &Context<'_>, hash: String) -> Result<Chunk> {
+        let chunk = view_client(ctx)?.chunk(parse_hash(&hash)?).await?;
+        Ok(Chunk { header: chunk.header.clone(), body: Mutex::new(Some(Arc::new(chunk))) })
+    }
+
+    /// Account at the block given by hash or height, the latest final block if neither is given.
+    async fn account(
+        &self,
+        ctx: &Context<'_>,
+        account_id: String,
+        block_hash: Option<String>,
+        block_height: Option<u64>,
+    ) -> Result<Account> {
+        let response = view_client(ctx)?
+            .query(
+                block_reference(block_hash, block_height)?,
+                QueryRequest::ViewAccount { account_id: account_id.clone() },
+            )
+            .await?;
+        match response.kind {
+            QueryResponseKind::ViewAccount(view) => {
+                Ok(Account { account_id, block_hash: response.block_hash, view })
+            }
+            _ => Err("Unexpected query response".into()),
+        }
+    }
+
+    /// Outcome of the transaction sent by `sender_id`.
+    async fn transaction_outcome(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        sender_id: String,
+    ) -> Result<Outcome> {
+        let id =
+            TransactionOrReceiptId::Transaction { transaction_hash: parse_hash(&hash)?, sender_id };
+        Ok(Outcome(view_client(ctx)?.outcome(id).await?))
+    }
+
+    /// Outcome of the receipt executed by `receiver_id`.
+    async fn receipt_outcome(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        receiver_id: String,
+    ) -> Result<Outcome> {
+        let id = TransactionOrReceiptId::Receipt { receipt_id: parse_hash(&id)?, receiver_id };
+        Ok(Outcome(view_client(ctx)?.outcome(id).await?))
+    }
+}
+
+pub struct Block(BlockView);
+
+#[Object]
+impl Block {
+    async fn hash(&self) -> String {
+        self.0.header.hash.to_string()
+    }
+
+    async fn height(&self) -> u64 {
+        self.0.header.height
+    }
+
+    async fn prev_hash(&self) -> String {
+        self.0.header.prev_hash.to_string()
+    }
+
+    async fn epoch_id(&self) -> String {
+        self.0.header.epoch_id.to_string()
+    }
+
+    /// Unix timestamp in nanoseconds.
+    async fn timestamp(&self) -> u64 {
+        self.0.header.timestamp_nanosec
+    }
+
+    async fn author(&self) -> &str {
+        &self.0.author
+    }
+
+    async fn gas_price(&self) -> String {
+        self.0.header.gas_price.to_string()
+    }
+
+    /// Chunks of all shards, the ones missing in this block are the latest ones included before.
+    async fn chunks(&self) -> Vec<Chunk> {
+        self.0.chunks.iter().cloned().map(Chunk::new).collect()
+    }
+
+    /// Outcomes of the transactions and receipts executed in this block.
+    async fn outcomes(&self, ctx: &Context<'_>) -> Result<Vec<Outcome>> {
+        let outcomes = view_client(ctx)?.outcomes_for_block(self.0.header.hash).await?;
+        Ok(outcomes.into_iter().map(Outcome).collect())
+    }
+}
+
+/// Header of a chunk, the body is fetched once when transactions or receipts are selected.
+pub struct Chunk {
+    header: ChunkHeaderView,
+    body: Mutex<Option<Arc<ChunkView>>>,
+}
+
+impl Chunk {
+    fn new(header: ChunkHeaderView) -> Self {
+        Self { header, body: Mutex::new(None) }
+    }
+
+    /// The lock is held while fetching, so that the fields resolved concurrently wait for the
+    /// same body.
+    async fn body(&self, ctx: &Context<'_>) -> Result<Arc<ChunkView>> {
+        let mut body = self.body.lock().await;
+        if let Some(body) = &*body {
+            return Ok(body.clone());
+        }
+        let chunk = Arc::new(view_client(ctx)?.chunk(self.header.chunk_hash).await?);
+        *body = Some(chunk.clone());
+        Ok(chunk)
+    }
+}
+
+#[Object]
+impl Chunk {
+    async fn hash(&self) -> String {
+        self.header.chunk_hash.to_string()
+    }
+
+    async fn shard_id(&self) -> u64 {
+        self.header.shard_id
+    }
+
+    async fn height_created(&self) -> u64 {
+        self.header.height_created
+    }
+
+    async fn height_included(&self) -> u64 {
+        self.header.height_included
+    }
+
+    async fn gas_used(&self) -> u64 {
+        self.header.gas_used
+    }
+
+    async fn gas_limit(&self) -> u64 {
+        self.header.gas_limit
+    }
+
+    async fn transactions(&self, ctx: &Context<'_>) -> Result<Vec<Transaction>> {
+        Ok(self.body(ctx).await?.transactions.iter().cloned().map(Transaction).collect())
+    }
+
+    async fn receipt_ids(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        let receipts = self.body(ctx).await?.receipts;
+        Ok(receipts.iter().map(|receipt| receipt.receipt_id.to_string()).collect())
+    }
+}
+
+pub struct Transaction(SignedTransactionView);
+
+#[Object]
+impl Transaction {
+    async fn hash(&self) -> String {
+        self.0.hash.to_string()
+    }
+
+    async fn signer_id(&self) -> &str {
+        &self.0.signer_id
+    }
+
+    async fn receiver_id(&self) -> &str {
+        &self.0.receiver_id
+    }
+
+    async fn public_key(&self) -> String {
+        self.0.public_key.to_string()
+    }
+
+    async fn nonce(&self) -> u64 {
+        self.0.nonce
+    }
+
+    async fn outcome(&self, ctx: &Context<'_>) -> Result<Outcome> {
+        let id = TransactionOrReceiptId::Transaction {
+            transaction_hash: self.0.hash,
+            sender_id: self.0.signer_id.clone(),
+        };
+        Ok(Outcome(view_client(ctx)?.outcome(id).await?))
+    }
+}
+
+pub struct Outcome(ExecutionOutcomeWithIdView);
+
+#[Object]
+impl Outcome {
+    /// Hash of the transaction or ID of the receipt.
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn block_hash(&self) -> String {
+        self.0.block_hash.to_string()
+    }
+
+    async fn executor_id(&self) -> &str {
+        &self.0.outcome.executor_id
+    }
+
+    async fn gas_burnt(&self) -> u64 {
+        self.0.outcome.gas_burnt
+    }
+
+    async fn tokens_burnt(&self) -> String {
+        self.0.outcome.tokens_burnt.to_string()
+    }
+
+    async fn logs(&self) -> Vec<String> {
+        self.0.outcome.logs.clone()
+    }
+
+    async fn receipt_ids(&self) -> Vec<String> {
+        self.0.outcome.receipt_ids.iter().map(ToString::to_string).collect()
+    }
+
+    /// Status in the format of the JSON RPC.
+    async fn status(&self) -> Json<ExecutionStatusView> {
+        Json(self.0.outcome.status.clone())
+    }
+}
+
+pub struct Account {
+    account_id: AccountId,
+    /// Block the account was viewed at, nested fields are read at the same block.
+    block_hash: CryptoHash,
+    view: AccountView,
+}
+
+#[Object]
+impl Account {
+    async fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    async fn amount(&self) -> String {
+        self.view.amount.to_string()
+    }
+
+    async fn locked(&self) -> String {
+        self.view.locked.to_string()
+    }
+
+    async fn code_hash(&self) -> String {
+        self.view.code_hash.to_string()
+    }
+
+    async fn storage_usage(&self) -> u64 {
+        self.view.storage_usage
+    }
+
+    async fn access_keys(&self, ctx: &Context<'_>) -> Result<Vec<AccessKey>> {
+        let response = view_client(ctx)?
+            .query(
+                BlockReference::BlockId(BlockId::Hash(self.block_hash)),
+                QueryRequest::ViewAccessKeyList { account_id: self.account_id.clone() },
+            )
+            .await?;
+        match response.kind {
+            QueryResponseKind::AccessKeyList(list) => {
+                Ok(list.keys.into_iter().map(AccessKey).collect())
+            }
+            _ => Err("Unexpected query response".into()),
+        }
+    }
+}
+
+pub struct AccessKey(AccessKeyInfoView);
+
+#[Object]
+impl AccessKey {
+    async fn public_key(&self) -> String {
+        self.0.public_key.to_string()
+    }
+
+    async fn nonce(&self) -> u64 {
+        self.0.access_key.nonce
+    }
+
+    /// Permission in the format of the JSON RPC.
+    async fn permission(&self) -> Json<AccessKeyPermissionView> {
+        Json(self.0.access_key.permission.clone())
+    }
+}
@@ -1,52 +1,98 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt::Display;
-use std::string::FromUtf8Error;
-use std::time::Duration;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use actix::{Addr, MailboxError};
+use actix::{Actor, Addr, MailboxError};
 use actix_cors::{Cors, CorsFactory};
-use actix_web::{http, middleware, web, App, Error as HttpError, HttpResponse, HttpServer};
+use actix_web::dev::Server;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::{
+    http, middleware, web, App, Error as HttpError, HttpRequest, HttpResponse, HttpServer,
+};
+use actix_web_actors::ws;
 use borsh::BorshDeserialize;
+use futures::future::join_all;
 use futures::Future;
 use futures::{FutureExt, TryFutureExt};
-use prometheus;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::time::{delay_for, timeout};
+use tracing_futures::Instrument;
 
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetStateChanges, GetStateChangesInBlock,
-    GetValidatorInfo, GetValidatorOrdered, Query, Status, TxStatus, TxStatusError, ViewClientActor,
+    report_deprecated_usage, CheckReadiness, ClientActor, GetBlock, GetBlockError, GetBlockProof,
+    GetChunk, GetChunkError, GetChunkWithProofs, GetDebugStatus, GetExecutionOutcome,
+    GetFeeHistory, GetGasPrice, GetGasPriceHistory, GetIdempotencyKey, GetNetworkInfo,
+    GetNextLightClientBlock, GetPeerAccess, GetPeerReputation, GetPeerStore, GetProtocolConfig,
+    GetProtocolFeatures, GetReceiptError, GetReceiptWithOutcome, GetRoutingInfo, GetStateChanges,
+    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, GetWatchedAccountChanges, Query,
+    SetGCPaused, Status, TxStatus, TxStatusError, UndoBlocks, UpdatePeerAccess, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
-use near_jsonrpc_client::message::{Message, Request, RpcError};
+use near_jsonrpc_client::message::{Message, Request, RpcError, RpcErrorCauseName};
 use near_jsonrpc_client::ChunkId;
-use near_metrics::{Encoder, TextEncoder};
 #[cfg(feature = "adversarial")]
 use near_network::types::{NetworkAdversarialMessage, NetworkViewClientMessages};
-use near_network::{NetworkClientMessages, NetworkClientResponses};
+use near_network::{NetworkClientMessages, NetworkClientResponses, PeerAccessChange};
 use near_primitives::errors::{InvalidTxError, TxExecutionError};
 use near_primitives::hash::CryptoHash;
 use near_primitives::rpc::{
-    RpcBroadcastTxSyncResponse, RpcLightClientExecutionProofRequest,
-    RpcLightClientExecutionProofResponse, RpcQueryRequest, RpcStateChangesInBlockRequest,
+    RpcBroadcastTxSyncResponse, RpcChunkReference, RpcChunkRequest, RpcEstimateFeeRequest,
+    RpcEstimateFeeResponse, RpcGasPriceHistoryRequest, RpcLightClientExecutionProofRequest,
+    RpcLightClientExecutionProofResponse, RpcQueryRequest, RpcReceiptRequest,
+    RpcSendTransactionRequest, RpcSendTransactionResponse, RpcStateChangesInBlockRequest,
     RpcStateChangesInBlockResponse, RpcStateChangesRequest, RpcStateChangesResponse,
-    RpcValidatorsOrderedRequest, TransactionInfo,
+    RpcValidatorsOrderedRequest, RpcWatchedAccountChangesRequest, TransactionInfo,
+    TxExecutionStatus,
 };
 use near_primitives::serialize::{from_base, from_base64, BaseEncode};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockId, BlockReference, MaybeBlockId};
+use near_primitives::types::{
+    AccountId, Balance, BlockHeightDelta, BlockId, BlockReference, EpochReference, Finality, Gas,
+    MaybeBlockId, NumBlocks,
+};
 use near_primitives::utils::is_valid_account_id;
+use near_primitives::version::DeprecatedSurface;
 use near_primitives::views::{
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, QueryRequest,
+    ContractMetadataView, FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum,
+    FinalExecutionStatus, PeerAccessChangeView, QueryRequest,
 };
+mod block_stream;
+#[cfg(feature = "graphql")]
+mod graphql;
 mod metrics;
+mod rate_limit;
+mod request_trace;
+mod subscriptions;
+
+use rate_limit::RateLimiter;
+pub use rate_limit::{RateLimitConfig, RpcRateLimitsConfig};
+use request_trace::TracedAddr;
 
 /// Max size of the query path (soft-deprecated)
 const QUERY_DATA_MAX_SIZE: usize = 10 * 1024;
 
+/// Gas attached to a function call by fee estimation when the contract doesn't recommend any.
+const DEFAULT_FUNCTION_CALL_GAS: Gas = 30_000_000_000_000;
+
+/// Number of watched account changes returned when the request doesn't set a limit.
+const DEFAULT_WATCHED_ACCOUNT_CHANGES_LIMIT: u64 = 100;
+
+/// Maximum number of requests in a batch if the config doesn't set it.
+const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+/// Maximum byte size of a response if the config doesn't set it.
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Format of the access log, the default one with the request ID added.
+const ACCESS_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %{x-request-id}o %T"#;
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
     pub polling_interval: Duration,
@@ -66,11 +112,57 @@ impl Default for RpcPollingConfig {
 pub struct RpcLimitsConfig {
     /// Maximum byte size of the json payload.
     pub json_payload_max_size: usize,
+    /// Maximum number of requests in a batch.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    /// Requests over these limits are answered with 429 Too Many Requests.
+    #[serde(default)]
+    pub rate_limits: RpcRateLimitsConfig,
+    /// Maximum byte size of the serialized response, larger results are replaced with errors.
+    #[serde(default = "default_max_response_size")]
+    pub max_response_size: usize,
+    /// Whether responses are compressed for clients that accept gzip, deflate or brotli.
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+}
+
+fn default_max_batch_size() -> usize {
+    DEFAULT_MAX_BATCH_SIZE
+}
+
+fn default_max_response_size() -> usize {
+    DEFAULT_MAX_RESPONSE_SIZE
+}
+
+fn default_compression() -> bool {
+    true
 }
 
 impl Default for RpcLimitsConfig {
     fn default() -> Self {
-        Self { json_payload_max_size: 10 * 1024 * 1024 }
+        Self {
+            json_payload_max_size: 10 * 1024 * 1024,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            rate_limits: RpcRateLimitsConfig::default(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            compression: true,
+        }
+    }
+}
+
+/// Thresholds of `/health/ready`, the node is not ready to serve requests when it's over any of
+/// them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RpcHealthConfig {
+    /// How far the head can be behind the highest height reported by the peers.
+    pub max_blocks_behind: BlockHeightDelta,
+    /// Minimum number of active peers.
+    pub min_peers: usize,
+}
+
+impl Default for RpcHealthConfig {
+    fn default() -> Self {
+        Self { max_blocks_behind: 10, min_peers: 1 }
     }
 }
 
@@ -81,6 +173,8 @@ pub struct RpcConfig {
     pub polling_config: RpcPollingConfig,
     #[serde(default)]
     pub limits_config: RpcLimitsConfig,
+    #[serde(default)]
+    pub health_config: RpcHealthConfig,
 }
 
 impl Default for RpcConfig {
@@ -90,6 +184,7 @@ impl Default for RpcConfig {
             cors_allowed_origins: vec!["*".to_owned()],
             polling_config: Default::default(),
             limits_config: Default::default(),
+            health_config: Default::default(),
         }
     }
 }
@@ -121,15 +216,17 @@ fn jsonify<T: serde::Serialize>(
     response: Result<Result<T, String>, MailboxError>,
 ) -> Result<Value, RpcError> {
     response
-        .map_err(|err| err.to_string())
-        .and_then(|value| {
-            value.and_then(|value| serde_json::to_value(value).map_err(|err| err.to_string()))
-        })
+        .map_err(mailbox_error)?
+        .and_then(|value| serde_json::to_value(value).map_err(|err| err.to_string()))
         .map_err(|err| RpcError::server_error(Some(err)))
 }
 
 fn parse_tx(params: Option<Value>) -> Result<SignedTransaction, RpcError> {
     let (encoded,) = parse_params::<(String,)>(params)?;
+    decode_signed_tx(encoded)
+}
+
+fn decode_signed_tx(encoded: String) -> Result<SignedTransaction, RpcError> {
     let bytes = from_base64_or_parse_err(encoded)?;
     SignedTransaction::try_from_slice(&bytes)
         .map_err(|e| RpcError::invalid_params(format!("Failed to decode transaction: {}", e)))
@@ -172,38 +269,201 @@ impl From<MailboxError> for ServerError {
 
 impl From<ServerError> for RpcError {
     fn from(e: ServerError) -> RpcError {
-        RpcError::server_error(Some(e))
+        let cause = match &e {
+            // Failed actions are reported in the outcomes, only invalid transactions get here.
+            ServerError::TxExecutionError(_) => RpcErrorCauseName::InvalidTransaction,
+            ServerError::Timeout => RpcErrorCauseName::Timeout,
+            ServerError::Closed => RpcErrorCauseName::Closed,
+            ServerError::InternalError => RpcErrorCauseName::InternalError,
+        };
+        RpcError::server_error(Some(e)).with_cause(cause, None)
     }
 }
 
+fn mailbox_error(err: MailboxError) -> RpcError {
+    let cause = match err {
+        MailboxError::Closed => RpcErrorCauseName::Closed,
+        MailboxError::Timeout => RpcErrorCauseName::Timeout,
+    };
+    RpcError::server_error(Some(err.to_string())).with_cause(cause, None)
+}
+
+fn get_block_error(err: GetBlockError) -> RpcError {
+    let cause = match &err {
+        GetBlockError::UnknownBlock(_) => RpcErrorCauseName::UnknownBlock,
+        GetBlockError::GarbageCollectedBlock(_) => RpcErrorCauseName::GarbageCollectedBlock,
+        GetBlockError::NotSyncedYet => RpcErrorCauseName::NotSyncedYet,
+        GetBlockError::InternalError(_) => RpcErrorCauseName::InternalError,
+    };
+    RpcError::server_error(Some(String::from(err))).with_cause(cause, None)
+}
+
+fn get_chunk_error(err: GetChunkError) -> RpcError {
+    let cause = match &err {
+        GetChunkError::UnknownBlock(_) => RpcErrorCauseName::UnknownBlock,
+        GetChunkError::GarbageCollectedBlock(_) => RpcErrorCauseName::GarbageCollectedBlock,
+        GetChunkError::InvalidShardId(_) => RpcErrorCauseName::InvalidShardId,
+        GetChunkError::UnknownChunk(_) => RpcErrorCauseName::UnknownChunk,
+        GetChunkError::InternalError(_) => RpcErrorCauseName::InternalError,
+    };
+    RpcError::server_error(Some(String::from(err))).with_cause(cause, None)
+}
+
+fn tx_status_error(err: TxStatusError) -> RpcError {
+    let cause = match &err {
+        TxStatusError::MissingTransaction(_) => RpcErrorCauseName::UnknownTransaction,
+        TxStatusError::InvalidTx(_) => RpcErrorCauseName::InvalidTransaction,
+        TxStatusError::TimeoutError => RpcErrorCauseName::Timeout,
+        TxStatusError::ChainError(_) | TxStatusError::InternalError => {
+            RpcErrorCauseName::InternalError
+        }
+    };
+    RpcError::server_error(Some(String::from(err))).with_cause(cause, None)
+}
+
+fn get_receipt_error(err: GetReceiptError) -> RpcError {
+    let cause = match &err {
+        GetReceiptError::UnknownReceipt(_) => RpcErrorCauseName::UnknownReceipt,
+        GetReceiptError::InternalError(_) => RpcErrorCauseName::InternalError,
+    };
+    RpcError::server_error(Some(String::from(err))).with_cause(cause, None)
+}
+
 fn timeout_err() -> RpcError {
-    RpcError::server_error(Some(ServerError::Timeout))
+    ServerError::Timeout.into()
+}
+
+/// Returns an error if a client at `client_ip` is over its rate limit for `method`.
+fn check_rate_limit(
+    rate_limiter: &RateLimiter,
+    client_ip: Option<IpAddr>,
+    method: &str,
+) -> Result<(), RpcError> {
+    match client_ip {
+        Some(ip) if !rate_limiter.check(ip, method) => {
+            near_metrics::inc_counter_vec(&metrics::RPC_RATE_LIMITED_COUNT, &[method]);
+            Err(RpcError::server_error(Some(format!("Rate limit exceeded for method {}", method)))
+                .with_cause(RpcErrorCauseName::RateLimited, None))
+        }
+        _ => Ok(()),
+    }
 }
 
 struct JsonRpcHandler {
-    client_addr: Addr<ClientActor>,
-    view_client_addr: Addr<ViewClientActor>,
+    client_addr: TracedAddr<ClientActor>,
+    view_client_addr: TracedAddr<ViewClientActor>,
     polling_config: RpcPollingConfig,
+    health_config: RpcHealthConfig,
     genesis_config: GenesisConfig,
+    max_batch_size: usize,
+    max_response_size: usize,
+    rate_limiter: Arc<RateLimiter>,
+    events_broadcaster_addr: Addr<subscriptions::ChainEventsBroadcaster>,
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+    fn check_rate_limit(&self, client_ip: Option<IpAddr>, method: &str) -> Result<(), RpcError> {
+        check_rate_limit(&self.rate_limiter, client_ip, method)
+    }
+
+    /// Serializes the response, replacing the results that don't fit into the size limit with
+    /// errors. A batch that is still over the limit after that is answered with a single error.
+    fn serialize_response(&self, message: Message) -> Result<Vec<u8>, HttpError> {
+        let body = serde_json::to_vec(&message)?;
+        if body.len() <= self.max_response_size {
+            return Ok(body);
+        }
+        let message = match message {
+            Message::Batch(messages) => Message::Batch(
+                messages.into_iter().map(|message| self.limit_response_size(message)).collect(),
+            ),
+            message => self.limit_response_size(message),
+        };
+        let body = serde_json::to_vec(&message)?;
+        if body.len() <= self.max_response_size {
+            Ok(body)
+        } else {
+            Ok(serde_json::to_vec(&Message::error(self.response_too_large(body.len())))?)
+        }
+    }
+
+    fn limit_response_size(&self, message: Message) -> Message {
+        match message {
+            Message::Response(response) => {
+                let size = serde_json::to_vec(&response).map_or(0, |body| body.len());
+                if size <= self.max_response_size {
+                    Message::Response(response)
+                } else {
+                    Message::response(response.id, Err(self.response_too_large(size)))
+                }
+            }
+            message => message,
+        }
+    }
+
+    fn response_too_large(&self, size: usize) -> RpcError {
+        RpcError::server_error(Some(format!(
+            "Response of {} bytes exceeds the limit of {} bytes",
+            size, self.max_response_size
+        )))
+        .with_cause(RpcErrorCauseName::ResponseTooLarge, None)
+    }
+
+    /// Rate limits of single requests are checked by the caller, which answers them with 429.
+    pub async fn process(
+        &self,
+        message: Message,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Message, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+                Ok(Message::response(id, self.process_request(request, client_ip).await))
+            }
+            Message::Batch(messages) => {
+                if messages.is_empty() {
+                    return Ok(Message::error(RpcError::invalid_request()));
+                }
+                if messages.len() > self.max_batch_size {
+                    return Ok(Message::error(
+                        RpcError::server_error(Some(format!(
+                            "Batch of {} requests exceeds the limit of {}",
+                            messages.len(),
+                            self.max_batch_size
+                        )))
+                        .with_cause(RpcErrorCauseName::InvalidRequest, None),
+                    ));
+                }
+                // Every entry gets its own response, so one failed request doesn't fail the batch.
+                let responses = messages.into_iter().map(|message| async move {
+                    match message {
+                        Message::Request(request) => {
+                            let id = request.id.clone();
+                            if let Err(err) = self.check_rate_limit(client_ip, &request.method) {
+                                return Message::response(id, Err(err));
+                            }
+                            Message::response(id, self.process_request(request, client_ip).await)
+                        }
+                        _ => Message::error(RpcError::invalid_request()),
+                    }
+                });
+                Ok(Message::Batch(join_all(responses).await))
             }
             _ => Ok(Message::error(RpcError::invalid_request())),
         }
     }
 
-    async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
+    async fn process_request(
+        &self,
+        request: Request,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Value, RpcError> {
         near_metrics::inc_counter_vec(&metrics::HTTP_RPC_REQUEST_COUNT, &[request.method.as_ref()]);
         let _rpc_processing_time = near_metrics::start_timer_vec(
             &metrics::RPC_PROCESSING_TIME,
             &[request.method.as_ref()],
         );
+        report_deprecated_usage(DeprecatedSurface::RpcMethod, &request.method);
 
         #[cfg(feature = "adversarial")]
         {
@@ -230,6 +490,7 @@ impl JsonRpcHandler {
             "broadcast_tx_async" => self.send_tx_async(request.params).await,
             "EXPERIMENTAL_broadcast_tx_sync" => self.send_tx_sync(request.params).await,
             "broadcast_tx_commit" => self.send_tx_commit(request.params).await,
+            "send_tx" => self.send_tx_wait(request.params).await,
             "EXPERIMENTAL_check_tx" => self.check_tx(request.params).await,
             "validators" => self.validators(request.params).await,
             "EXPERIMENTAL_validators_ordered" => self.validators_ordered(request.params).await,
@@ -239,6 +500,7 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_genesis_config" => self.genesis_config().await,
             "tx" => self.tx_status_common(request.params, false).await,
             "EXPERIMENTAL_tx_status" => self.tx_status_common(request.params, true).await,
+            "EXPERIMENTAL_receipt" => self.receipt(request.params).await,
             "block" => self.block(request.params).await,
             "chunk" => self.chunk(request.params).await,
             "EXPERIMENTAL_changes" => self.changes_in_block_by_type(request.params).await,
@@ -249,7 +511,26 @@ impl JsonRpcHandler {
             }
             "light_client_proof" => self.light_client_execution_outcome_proof(request.params).await,
             "network_info" => self.network_info().await,
+            "EXPERIMENTAL_routing_info" => self.routing_info().await,
+            "EXPERIMENTAL_debug_status" => self.debug_status().await,
+            "EXPERIMENTAL_debug_peer_store" => self.debug_peer_store().await,
+            "EXPERIMENTAL_debug_peer_reputation" => self.debug_peer_reputation().await,
+            "EXPERIMENTAL_peer_access" => self.peer_access().await,
+            "EXPERIMENTAL_update_peer_access" => {
+                self.update_peer_access(request.params, client_ip).await
+            }
+            "EXPERIMENTAL_gc_pause" => self.gc_pause(request.params, client_ip).await,
+            "EXPERIMENTAL_undo_blocks" => self.undo_blocks(request.params, client_ip).await,
+            "EXPERIMENTAL_protocol_config" => self.protocol_config(request.params).await,
+            "EXPERIMENTAL_protocol_features" => self.protocol_features().await,
+            "EXPERIMENTAL_idempotency_key" => self.idempotency_key(request.params).await,
+            "EXPERIMENTAL_watched_account_changes" => {
+                self.watched_account_changes(request.params).await
+            }
+            "EXPERIMENTAL_estimate_fee" => self.estimate_fee(request.params).await,
             "gas_price" => self.gas_price(request.params).await,
+            "fee_history" => self.fee_history(request.params).await,
+            "EXPERIMENTAL_gas_price_history" => self.gas_price_history(request.params).await,
             _ => Err(RpcError::method_not_found(request.method.clone())),
         };
 
@@ -364,9 +645,7 @@ impl JsonRpcHandler {
                     // If transaction is missing, keep polling.
                     Err(TxStatusError::MissingTransaction(_)) => {}
                     // If we hit any other error, we return to the user.
-                    Err(err) => {
-                        break jsonify::<FinalExecutionOutcomeView>(Ok(Err(err.into())));
-                    }
+                    Err(err) => break Err(tx_status_error(err)),
                 }
                 let _ = delay_for(self.polling_config.polling_interval).await;
             }
@@ -395,7 +674,7 @@ impl JsonRpcHandler {
                 is_forwarded: false,
                 check_only,
             })
-            .map_err(|err| RpcError::server_error(Some(ServerError::from(err))))
+            .map_err(|err| RpcError::from(ServerError::from(err)))
             .await?;
 
         // If we receive InvalidNonce error, it might be the case that the transaction was
@@ -452,14 +731,14 @@ impl JsonRpcHandler {
                 }
             }
             NetworkClientResponses::InvalidTx(err) => {
-                Err(RpcError::server_error(Some(ServerError::TxExecutionError(err.into()))))
+                Err(ServerError::TxExecutionError(err.into()).into())
             }
             NetworkClientResponses::DoesNotTrackShard => {
                 Err(RpcError::server_error(Some(does_not_track_shard_err.to_string())))
             }
             _ => {
                 // this is only possible if something went wrong with the node internally.
-                Err(RpcError::server_error(Some(ServerError::InternalError)))
+                Err(ServerError::InternalError.into())
             }
         }
     }
@@ -471,7 +750,7 @@ impl JsonRpcHandler {
                 return jsonify(Ok(Ok(outcome)));
             }
             Err(TxStatusError::InvalidTx(e)) => {
-                return Err(RpcError::server_error(Some(ServerError::TxExecutionError(e.into()))));
+                return Err(ServerError::TxExecutionError(e.into()).into());
             }
             _ => {}
         }
@@ -480,13 +759,143 @@ impl JsonRpcHandler {
                 self.tx_polling(TransactionInfo::Transaction(tx)).await
             }
             NetworkClientResponses::InvalidTx(err) => {
-                Err(RpcError::server_error(Some(ServerError::TxExecutionError(err.into()))))
+                Err(ServerError::TxExecutionError(err.into()).into())
             }
-            NetworkClientResponses::NoResponse => {
-                Err(RpcError::server_error(Some(ServerError::Timeout)))
+            NetworkClientResponses::NoResponse => Err(ServerError::Timeout.into()),
+            _ => Err(ServerError::InternalError.into()),
+        }
+    }
+
+    /// Sends a transaction and waits until it gets as far as the caller asked for, or the polling
+    /// timeout passes.
+    async fn send_tx_wait(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcSendTransactionRequest { signed_tx_base64, wait_until } = parse_params(params)?;
+        let tx = decode_signed_tx(signed_tx_base64)?;
+        let tx_hash = tx.get_hash();
+        let signer_account_id = tx.transaction.signer_id.clone();
+        if wait_until == TxExecutionStatus::None {
+            self.client_addr.do_send(NetworkClientMessages::Transaction {
+                transaction: tx,
+                is_forwarded: false,
+                check_only: false,
+            });
+            return jsonify(Ok(Ok(RpcSendTransactionResponse {
+                transaction_hash: tx_hash,
+                final_execution_status: TxExecutionStatus::None,
+                outcome: None,
+            })));
+        }
+        match self.send_tx(tx, false).await? {
+            NetworkClientResponses::ValidTx | NetworkClientResponses::RequestRouted => {}
+            NetworkClientResponses::InvalidTx(err) => {
+                return Err(ServerError::TxExecutionError(err.into()).into());
             }
-            _ => Err(RpcError::server_error(Some(ServerError::InternalError))),
+            NetworkClientResponses::NoResponse => return Err(ServerError::Timeout.into()),
+            _ => return Err(ServerError::InternalError.into()),
         }
+        let outcome = if wait_until == TxExecutionStatus::Broadcasted {
+            None
+        } else {
+            let outcome = timeout(
+                self.polling_config.polling_timeout,
+                self.wait_for_tx(tx_hash, &signer_account_id, wait_until),
+            )
+            .await
+            .map_err(|_| {
+                near_metrics::inc_counter(&metrics::RPC_TIMEOUT_TOTAL);
+                // The transaction is already sent, so the caller can keep polling its status.
+                RpcError::from(ServerError::Timeout).with_cause(
+                    RpcErrorCauseName::Timeout,
+                    Some(serde_json::json!({ "transaction_hash": tx_hash })),
+                )
+            })??;
+            Some(outcome)
+        };
+        jsonify(Ok(Ok(RpcSendTransactionResponse {
+            transaction_hash: tx_hash,
+            final_execution_status: wait_until,
+            outcome,
+        })))
+    }
+
+    async fn wait_for_tx(
+        &self,
+        tx_hash: CryptoHash,
+        signer_account_id: &AccountId,
+        wait_until: TxExecutionStatus,
+    ) -> Result<FinalExecutionOutcomeView, RpcError> {
+        loop {
+            let tx_status = self
+                .view_client_addr
+                .send(TxStatus {
+                    tx_hash,
+                    signer_account_id: signer_account_id.clone(),
+                    fetch_receipt: false,
+                })
+                .await
+                .map_err(mailbox_error)?;
+            match tx_status {
+                Ok(Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome))) => {
+                    if self.tx_reached(&outcome, wait_until).await? {
+                        return Ok(outcome);
+                    }
+                }
+                Ok(Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(_))) => {
+                    return Err(ServerError::InternalError.into());
+                }
+                // A routed transaction may not have reached the chunk producers yet.
+                Ok(None) | Err(TxStatusError::MissingTransaction(_)) => {}
+                Err(err) => return Err(tx_status_error(err)),
+            }
+            delay_for(self.polling_config.polling_interval).await;
+        }
+    }
+
+    async fn tx_reached(
+        &self,
+        outcome: &FinalExecutionOutcomeView,
+        status: TxExecutionStatus,
+    ) -> Result<bool, RpcError> {
+        let executed = match outcome.status {
+            FinalExecutionStatus::SuccessValue(_) | FinalExecutionStatus::Failure(_) => true,
+            FinalExecutionStatus::NotStarted | FinalExecutionStatus::Started => false,
+        };
+        match status {
+            TxExecutionStatus::None
+            | TxExecutionStatus::Broadcasted
+            | TxExecutionStatus::Included => Ok(true),
+            TxExecutionStatus::ExecutedOptimistic => Ok(executed),
+            TxExecutionStatus::Final => Ok(executed && self.outcomes_are_final(outcome).await?),
+        }
+    }
+
+    /// Whether all the blocks the transaction and its receipts were executed in are final.
+    async fn outcomes_are_final(
+        &self,
+        outcome: &FinalExecutionOutcomeView,
+    ) -> Result<bool, RpcError> {
+        let final_block = self
+            .view_client_addr
+            .send(GetBlock(BlockReference::Finality(Finality::Final)))
+            .await
+            .map_err(mailbox_error)?
+            .map_err(get_block_error)?;
+        let block_hashes: HashSet<CryptoHash> = std::iter::once(&outcome.transaction_outcome)
+            .chain(outcome.receipts_outcome.iter())
+            .map(|outcome| outcome.block_hash)
+            .collect();
+        for block_hash in block_hashes {
+            let block = self
+                .view_client_addr
+                .send(GetBlock(BlockId::Hash(block_hash).into()))
+                .await
+                .map_err(mailbox_error)?
+                .map_err(get_block_error)?;
+            if block.header.height > final_block.header.height {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
     async fn health(&self) -> Result<Value, RpcError> {
@@ -497,6 +906,21 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Whether the client actor is responsive, regardless of the state of the node.
+    async fn liveness(&self) -> Result<(), MailboxError> {
+        self.client_addr.send(Status { is_health_check: false }).await.map(|_| ())
+    }
+
+    async fn readiness(&self) -> Result<(), String> {
+        self.client_addr
+            .send(CheckReadiness {
+                max_blocks_behind: self.health_config.max_blocks_behind,
+                min_peers: self.health_config.min_peers,
+            })
+            .await
+            .map_err(|err| err.to_string())?
+    }
+
     pub async fn status(&self) -> Result<Value, RpcError> {
         match self.client_addr.send(Status { is_health_check: false }).await {
             Ok(Ok(result)) => jsonify(Ok(Ok(result))),
@@ -521,6 +945,7 @@ impl JsonRpcHandler {
             // positional arguments with a "path"-style first argument.
             //
             // This whole block can be removed one day, when the new API is 100% adopted.
+            report_deprecated_usage(DeprecatedSurface::RpcMethod, "query_by_path");
             let data = from_base_or_parse_err(data)?;
             let query_data_size = path.len() + data.len();
             if query_data_size > QUERY_DATA_MAX_SIZE {
@@ -613,10 +1038,41 @@ impl JsonRpcHandler {
                 TransactionInfo::Transaction(tx)
             };
 
-        jsonify(Ok(self
-            .tx_status_fetch(tx_status_request, fetch_receipt)
+        self.tx_status_fetch(tx_status_request, fetch_receipt)
             .await
-            .map_err(|err| err.into())))
+            .map_err(tx_status_error)
+            .and_then(|outcome| jsonify(Ok(Ok(outcome))))
+    }
+
+    /// Resolves references by finality or sync checkpoint to the hash of the block, so that the
+    /// block can't change between the requests made to serve a method.
+    async fn block_reference_to_block_id(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockId, RpcError> {
+        match block_reference {
+            BlockReference::BlockId(block_id) => Ok(block_id),
+            block_reference => {
+                let block = self
+                    .view_client_addr
+                    .send(GetBlock(block_reference))
+                    .await
+                    .map_err(mailbox_error)?
+                    .map_err(get_block_error)?;
+                Ok(BlockId::Hash(block.header.hash))
+            }
+        }
+    }
+
+    /// Parses either the positional `[block_id]` parameters, where `null` stands for the head, or
+    /// a named block reference.
+    async fn parse_maybe_block_id(&self, params: Option<Value>) -> Result<MaybeBlockId, RpcError> {
+        if let Ok((block_id,)) = parse_params::<(MaybeBlockId,)>(params.clone()) {
+            Ok(block_id)
+        } else {
+            let block_reference = parse_params::<BlockReference>(params)?;
+            Ok(Some(self.block_reference_to_block_id(block_reference).await?))
+        }
     }
 
     async fn block(&self, params: Option<Value>) -> Result<Value, RpcError> {
@@ -625,24 +1081,55 @@ impl JsonRpcHandler {
         } else {
             parse_params::<BlockReference>(params)?
         };
-        jsonify(self.view_client_addr.send(GetBlock(block_reference)).await)
+        let block = self
+            .view_client_addr
+            .send(GetBlock(block_reference))
+            .await
+            .map_err(mailbox_error)?
+            .map_err(get_block_error)?;
+        jsonify(Ok(Ok(block)))
     }
 
     async fn chunk(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (chunk_id,) = parse_params::<(ChunkId,)>(params)?;
-        jsonify(
-            self.view_client_addr
-                .send(match chunk_id {
-                    ChunkId::BlockShardId(block_id, shard_id) => match block_id {
-                        BlockId::Height(height) => GetChunk::Height(height, shard_id),
-                        BlockId::Hash(block_hash) => {
-                            GetChunk::BlockHash(block_hash.into(), shard_id)
-                        }
-                    },
-                    ChunkId::Hash(chunk_hash) => GetChunk::ChunkHash(chunk_hash.into()),
-                })
-                .await,
-        )
+        let (chunk_id, with_proofs) =
+            if let Ok((chunk_id,)) = parse_params::<(ChunkId,)>(params.clone()) {
+                (chunk_id, false)
+            } else {
+                let RpcChunkRequest { chunk_reference, with_proofs } = parse_params(params)?;
+                let chunk_id = match chunk_reference {
+                    RpcChunkReference::ChunkHash { chunk_id } => ChunkId::Hash(chunk_id),
+                    RpcChunkReference::BlockShardId { block_reference, shard_id } => {
+                        ChunkId::BlockShardId(
+                            self.block_reference_to_block_id(block_reference).await?,
+                            shard_id,
+                        )
+                    }
+                };
+                (chunk_id, with_proofs)
+            };
+        let get_chunk = match chunk_id {
+            ChunkId::BlockShardId(block_id, shard_id) => match block_id {
+                BlockId::Height(height) => GetChunk::Height(height, shard_id),
+                BlockId::Hash(block_hash) => GetChunk::BlockHash(block_hash.into(), shard_id),
+            },
+            ChunkId::Hash(chunk_hash) => GetChunk::ChunkHash(chunk_hash.into()),
+        };
+        if with_proofs {
+            let chunk = self
+                .view_client_addr
+                .send(GetChunkWithProofs(get_chunk))
+                .await
+                .map_err(mailbox_error)?
+                .map_err(get_chunk_error)?;
+            return jsonify(Ok(Ok(chunk)));
+        }
+        let chunk = self
+            .view_client_addr
+            .send(get_chunk)
+            .await
+            .map_err(mailbox_error)?
+            .map_err(get_chunk_error)?;
+        jsonify(Ok(Ok(chunk)))
     }
 
     async fn changes_in_block(&self, params: Option<Value>) -> Result<Value, RpcError> {
@@ -651,8 +1138,8 @@ impl JsonRpcHandler {
             .view_client_addr
             .send(GetBlock(block_reference))
             .await
-            .map_err(|err| RpcError::server_error(Some(err.to_string())))?
-            .map_err(|err| RpcError::server_error(Some(err)))?;
+            .map_err(mailbox_error)?
+            .map_err(get_block_error)?;
         let block_hash = block.header.hash.clone();
         jsonify(self.view_client_addr.send(GetStateChangesInBlock { block_hash }).await.map(|v| {
             v.map(|changes| RpcStateChangesInBlockResponse {
@@ -669,8 +1156,8 @@ impl JsonRpcHandler {
             .view_client_addr
             .send(GetBlock(block_reference))
             .await
-            .map_err(|err| RpcError::server_error(Some(err.to_string())))?
-            .map_err(|err| RpcError::server_error(Some(err)))?;
+            .map_err(mailbox_error)?
+            .map_err(get_block_error)?;
         let block_hash = block.header.hash.clone();
         jsonify(
             self.view_client_addr
@@ -690,6 +1177,17 @@ impl JsonRpcHandler {
         jsonify(self.view_client_addr.send(GetNextLightClientBlock { last_block_hash }).await)
     }
 
+    async fn receipt(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcReceiptRequest { receipt_id } = parse_params(params)?;
+        let receipt = self
+            .view_client_addr
+            .send(GetReceiptWithOutcome { receipt_id })
+            .await
+            .map_err(mailbox_error)?
+            .map_err(get_receipt_error)?;
+        jsonify(Ok(Ok(receipt)))
+    }
+
     async fn light_client_execution_outcome_proof(
         &self,
         params: Option<Value>,
@@ -722,23 +1220,212 @@ impl JsonRpcHandler {
         jsonify(self.client_addr.send(GetNetworkInfo {}).await)
     }
 
+    async fn routing_info(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetRoutingInfo {}).await)
+    }
+
+    async fn debug_status(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetDebugStatus {}).await)
+    }
+
+    async fn debug_peer_store(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetPeerStore {}).await)
+    }
+
+    async fn debug_peer_reputation(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetPeerReputation {}).await)
+    }
+
+    async fn peer_access(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetPeerAccess {}).await)
+    }
+
+    /// Changes the peer access rules, only allowed to clients on the same host.
+    async fn update_peer_access(
+        &self,
+        params: Option<Value>,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Value, RpcError> {
+        if !client_ip.map_or(false, |ip| ip.is_loopback()) {
+            return Err(RpcError::server_error(Some(
+                "Peer access can only be changed from localhost".to_string(),
+            ))
+            .with_cause(RpcErrorCauseName::InvalidRequest, None));
+        }
+        let (change,) = parse_params::<(PeerAccessChangeView,)>(params)?;
+        let change = PeerAccessChange::try_from(change).map_err(RpcError::invalid_params)?;
+        jsonify(self.client_addr.send(UpdatePeerAccess(change)).await)
+    }
+
+    async fn gc_pause(
+        &self,
+        params: Option<Value>,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Value, RpcError> {
+        if !client_ip.map_or(false, |ip| ip.is_loopback()) {
+            return Err(RpcError::server_error(Some(
+                "Garbage collection can only be paused from localhost".to_string(),
+            ))
+            .with_cause(RpcErrorCauseName::InvalidRequest, None));
+        }
+        let (paused,) = parse_params::<(bool,)>(params)?;
+        jsonify(self.client_addr.send(SetGCPaused(paused)).await)
+    }
+
+    async fn undo_blocks(
+        &self,
+        params: Option<Value>,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Value, RpcError> {
+        if !client_ip.map_or(false, |ip| ip.is_loopback()) {
+            return Err(RpcError::server_error(Some(
+                "Blocks can only be undone from localhost".to_string(),
+            ))
+            .with_cause(RpcErrorCauseName::InvalidRequest, None));
+        }
+        let (num_blocks,) = parse_params::<(NumBlocks,)>(params)?;
+        // A running node stops at the last final block, `neard undo-blocks` rewinds past it.
+        jsonify(self.client_addr.send(UndoBlocks(num_blocks)).await)
+    }
+
+    async fn protocol_config(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let block_reference = parse_params::<BlockReference>(params)?;
+        let config = self
+            .view_client_addr
+            .send(GetProtocolConfig(block_reference))
+            .await
+            .map_err(mailbox_error)?
+            .map_err(get_block_error)?;
+        jsonify(Ok(Ok(config)))
+    }
+
+    async fn protocol_features(&self) -> Result<Value, RpcError> {
+        jsonify(self.view_client_addr.send(GetProtocolFeatures {}).await)
+    }
+
+    async fn idempotency_key(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (account_id, key) = parse_params::<(AccountId, String)>(params)?;
+        if !is_valid_account_id(&account_id) {
+            return Err(RpcError::invalid_params(format!("Invalid account id: {}", account_id)));
+        }
+        let key = from_base64_or_parse_err(key)?;
+        jsonify(self.view_client_addr.send(GetIdempotencyKey { account_id, key }).await)
+    }
+
+    async fn watched_account_changes(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcWatchedAccountChangesRequest { account_id, from_block_height, from_index, limit } =
+            parse_params::<RpcWatchedAccountChangesRequest>(params)?;
+        if !is_valid_account_id(&account_id) {
+            return Err(RpcError::invalid_params(format!("Invalid account id: {}", account_id)));
+        }
+        jsonify(
+            self.view_client_addr
+                .send(GetWatchedAccountChanges {
+                    account_id,
+                    from_block_height,
+                    from_index,
+                    limit: limit.unwrap_or(DEFAULT_WATCHED_ACCOUNT_CHANGES_LIMIT),
+                })
+                .await,
+        )
+    }
+
     async fn gas_price(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (block_id,) = parse_params::<(MaybeBlockId,)>(params)?;
+        let block_id = self.parse_maybe_block_id(params).await?;
         jsonify(self.view_client_addr.send(GetGasPrice { block_id }).await)
     }
 
-    pub async fn metrics(&self) -> Result<String, FromUtf8Error> {
-        // Gather metrics and return them as a String
-        let mut buffer = vec![];
-        let encoder = TextEncoder::new();
-        encoder.encode(&prometheus::gather(), &mut buffer).unwrap();
+    async fn estimate_fee(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let request = parse_params::<RpcEstimateFeeRequest>(params)?;
+        for account_id in &[&request.signer_id, &request.receiver_id] {
+            if !is_valid_account_id(account_id) {
+                return Err(RpcError::invalid_params(format!(
+                    "Invalid account id: {}",
+                    account_id
+                )));
+            }
+        }
+        let query = Query::new(
+            BlockReference::latest(),
+            QueryRequest::ViewContractMetadata { account_id: request.receiver_id.clone() },
+        );
+        let method = match self.view_client_addr.send(query).await {
+            // Accounts without a contract or metadata, as well as accounts in shards this node
+            // doesn't track, fall back to the defaults.
+            Ok(Ok(Some(response))) => {
+                ContractMetadataView::try_from(response).ok().and_then(|metadata| {
+                    metadata
+                        .methods
+                        .into_iter()
+                        .find(|method| method.method_name == request.method_name)
+                })
+            }
+            Ok(Ok(None)) => None,
+            Ok(Err(err)) => return Err(RpcError::server_error(Some(err))),
+            Err(err) => return Err(RpcError::server_error(Some(err.to_string()))),
+        };
+        let (gas, deposit, from_metadata) = match method {
+            Some(method) => (method.gas, method.deposit, true),
+            None => (DEFAULT_FUNCTION_CALL_GAS, 0, false),
+        };
+        let gas_price = self
+            .view_client_addr
+            .send(GetGasPrice { block_id: None })
+            .await
+            .map_err(|err| RpcError::server_error(Some(err.to_string())))?
+            .map_err(|err| RpcError::server_error(Some(err)))?
+            .gas_price;
+
+        let fees = &self.genesis_config.runtime_config.transaction_costs;
+        let sender_is_receiver = request.signer_id == request.receiver_id;
+        let receipt_fee = &fees.action_receipt_creation_config;
+        let call_fee = &fees.action_creation_config.function_call_cost;
+        let call_fee_per_byte = &fees.action_creation_config.function_call_cost_per_byte;
+        let num_bytes = (request.method_name.len() + request.args.len()) as Balance;
+        let total_gas = Balance::from(receipt_fee.send_fee(sender_is_receiver))
+            + Balance::from(receipt_fee.exec_fee())
+            + Balance::from(call_fee.send_fee(sender_is_receiver))
+            + Balance::from(call_fee.exec_fee())
+            + num_bytes
+                * Balance::from(
+                    call_fee_per_byte.send_fee(sender_is_receiver) + call_fee_per_byte.exec_fee(),
+                )
+            + Balance::from(gas);
+        jsonify(Ok(Ok(RpcEstimateFeeResponse {
+            gas,
+            deposit,
+            gas_price,
+            fee: total_gas.saturating_mul(gas_price),
+            from_metadata,
+        })))
+    }
+
+    async fn fee_history(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (block_count, block_id) = parse_params::<(NumBlocks, MaybeBlockId)>(params)?;
+        jsonify(self.view_client_addr.send(GetFeeHistory { block_count, block_id }).await)
+    }
+
+    async fn gas_price_history(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcGasPriceHistoryRequest { from_height, to_height } = parse_params(params)?;
+        jsonify(self.view_client_addr.send(GetGasPriceHistory { from_height, to_height }).await)
+    }
 
-        String::from_utf8(buffer)
+    pub async fn metrics(&self) -> near_metrics::Result<String> {
+        near_metrics::gather_text()
     }
 
+    /// Accepts an epoch by its ID or a block in it, `[block_id]` is kept for compatibility.
     async fn validators(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (block_id,) = parse_params::<(MaybeBlockId,)>(params)?;
-        jsonify(self.view_client_addr.send(GetValidatorInfo { block_id }).await)
+        let epoch_reference =
+            if let Ok((block_id,)) = parse_params::<(MaybeBlockId,)>(params.clone()) {
+                EpochReference::from(block_id)
+            } else if let Ok(epoch_reference) = parse_params::<EpochReference>(params.clone()) {
+                epoch_reference
+            } else {
+                let block_reference = parse_params::<BlockReference>(params)?;
+                EpochReference::BlockId(self.block_reference_to_block_id(block_reference).await?)
+            };
+        jsonify(self.view_client_addr.send(GetValidatorInfo { epoch_reference }).await)
     }
 
     /// Returns the current epoch validators ordered in the block producer order with repetition.
@@ -864,14 +1551,55 @@ impl JsonRpcHandler {
 }
 
 fn rpc_handler(
+    request: HttpRequest,
     message: web::Json<Message>,
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let client_ip = request.peer_addr().map(|addr| addr.ip());
+    let request_id = request_trace::request_id(&request);
+    let with_timing = request_trace::timing_requested(&request);
+    let span = tracing::info_span!(target: "jsonrpc", "rpc_request", request_id = %request_id);
     let response = async move {
-        let message = handler.process(message.0).await?;
-        Ok(HttpResponse::Ok().json(message))
+        let start = Instant::now();
+        if let Message::Request(request) = &message.0 {
+            tracing::debug!(target: "jsonrpc", method = %request.method, "Processing request");
+            if let Err(err) = handler.check_rate_limit(client_ip, &request.method) {
+                let message = Message::response(request.id.clone(), Err(err));
+                return Ok(HttpResponse::TooManyRequests()
+                    .header(request_trace::REQUEST_ID_HEADER, request_id)
+                    .json(message));
+            }
+        }
+        let (message, timings) =
+            request_trace::collect_timings(handler.process(message.0, client_ip)).await;
+        let mut response = HttpResponse::Ok();
+        response.header(request_trace::REQUEST_ID_HEADER, request_id);
+        if with_timing {
+            response.header(
+                request_trace::SERVER_TIMING_HEADER,
+                request_trace::server_timing(&timings, start.elapsed()),
+            );
+        }
+        let body = handler.serialize_response(message?)?;
+        Ok(response.content_type("application/json").body(body))
     };
-    response.boxed()
+    response.instrument(span).boxed()
+}
+
+/// Answers requests over the size limit with a JSON-RPC error instead of a plain text one.
+fn json_error_handler(err: JsonPayloadError, max_size: usize) -> HttpError {
+    match err {
+        JsonPayloadError::Overflow => {
+            let error = RpcError::server_error(Some(format!(
+                "Request body exceeds the limit of {} bytes",
+                max_size
+            )))
+            .with_cause(RpcErrorCauseName::RequestTooLarge, None);
+            let response = HttpResponse::PayloadTooLarge().json(Message::error(error));
+            InternalError::from_response(err, response).into()
+        }
+        err => err.into(),
+    }
 }
 
 fn status_handler(
@@ -900,6 +1628,30 @@ fn health_handler(
     response.boxed()
 }
 
+fn liveness_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let response = async move {
+        match handler.liveness().await {
+            Ok(()) => Ok(HttpResponse::Ok().finish()),
+            Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+        }
+    };
+    response.boxed()
+}
+
+fn readiness_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let response = async move {
+        match handler.readiness().await {
+            Ok(()) => Ok(HttpResponse::Ok().finish()),
+            Err(reason) => Ok(HttpResponse::ServiceUnavailable().body(reason)),
+        }
+    };
+    response.boxed()
+}
+
 fn network_info_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
@@ -926,6 +1678,37 @@ fn prometheus_handler(
     response.boxed()
 }
 
+async fn ws_handler(
+    request: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let client_ip = request.peer_addr().map(|addr| addr.ip());
+    // Connections are limited as subscriptions, each request over them is limited separately.
+    if let Err(err) = handler.check_rate_limit(client_ip, "subscribe") {
+        return Ok(HttpResponse::TooManyRequests().json(Message::error(err)));
+    }
+    let session = subscriptions::WsSession::new(
+        handler.view_client_addr.addr().clone(),
+        handler.events_broadcaster_addr.clone(),
+        handler.rate_limiter.clone(),
+        client_ip,
+    );
+    ws::start(session, &request, stream)
+}
+
+async fn block_stream_handler(
+    request: web::Query<block_stream::BlockStreamRequest>,
+    handler: web::Data<JsonRpcHandler>,
+) -> HttpResponse {
+    let stream = block_stream::block_stream(
+        handler.view_client_addr.addr().clone(),
+        handler.polling_config.polling_interval,
+        request.into_inner(),
+    );
+    HttpResponse::Ok().content_type(block_stream::CONTENT_TYPE).streaming(Box::pin(stream))
+}
+
 fn get_cors(cors_allowed_origins: &[String]) -> CorsFactory {
     let mut cors = Cors::new();
     if cors_allowed_origins != ["*".to_string()] {
@@ -936,6 +1719,9 @@ fn get_cors(cors_allowed_origins: &[String]) -> CorsFactory {
     cors.allowed_methods(vec!["GET", "POST"])
         .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
         .allowed_header(http::header::CONTENT_TYPE)
+        .allowed_header(request_trace::REQUEST_ID_HEADER)
+        .allowed_header(request_trace::REQUEST_TIMING_HEADER)
+        .expose_headers(vec![request_trace::REQUEST_ID_HEADER, request_trace::SERVER_TIMING_HEADER])
         .max_age(3600)
         .finish()
 }
@@ -945,19 +1731,42 @@ pub fn start_http(
     genesis_config: GenesisConfig,
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
-) {
-    let RpcConfig { addr, cors_allowed_origins, polling_config, limits_config } = config;
+) -> Server {
+    let RpcConfig { addr, cors_allowed_origins, polling_config, limits_config, health_config } =
+        config;
+    // Workers share the limiter so that the limits don't depend on the number of workers.
+    let rate_limiter = Arc::new(RateLimiter::new(limits_config.rate_limits.clone()));
+    // WebSocket connections of all the workers share the polling of the chain events.
+    let events_broadcaster_addr = subscriptions::ChainEventsBroadcaster::new(
+        view_client_addr.clone(),
+        polling_config.polling_interval,
+    )
+    .start();
+    let json_payload_max_size = limits_config.json_payload_max_size;
     HttpServer::new(move || {
         App::new()
             .wrap(get_cors(&cors_allowed_origins))
             .data(JsonRpcHandler {
-                client_addr: client_addr.clone(),
-                view_client_addr: view_client_addr.clone(),
+                client_addr: TracedAddr::new(client_addr.clone(), "client"),
+                view_client_addr: TracedAddr::new(view_client_addr.clone(), "view_client"),
                 polling_config,
+                health_config,
                 genesis_config: genesis_config.clone(),
+                max_batch_size: limits_config.max_batch_size,
+                max_response_size: limits_config.max_response_size,
+                rate_limiter: rate_limiter.clone(),
+                events_broadcaster_addr: events_broadcaster_addr.clone(),
             })
-            .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
-            .wrap(middleware::Logger::default())
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(json_payload_max_size)
+                    .error_handler(move |err, _| json_error_handler(err, json_payload_max_size)),
+            )
+            .wrap(middleware::Condition::new(
+                limits_config.compression,
+                middleware::Compress::default(),
+            ))
+            .wrap(middleware::Logger::new(ACCESS_LOG_FORMAT))
             .service(web::resource("/").route(web::post().to(rpc_handler)))
             .service(
                 web::resource("/status")
@@ -969,12 +1778,28 @@ pub fn start_http(
                     .route(web::get().to(health_handler))
                     .route(web::head().to(health_handler)),
             )
+            .service(
+                web::resource("/health/live")
+                    .route(web::get().to(liveness_handler))
+                    .route(web::head().to(liveness_handler)),
+            )
+            .service(
+                web::resource("/health/ready")
+                    .route(web::get().to(readiness_handler))
+                    .route(web::head().to(readiness_handler)),
+            )
             .service(web::resource("/network_info").route(web::get().to(network_info_handler)))
             .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
+            .service(web::resource("/ws").route(web::get().to(ws_handler)))
+            .service(web::resource("/blocks/stream").route(web::get().to(block_stream_handler)))
+            .configure(|_cfg| {
+                #[cfg(feature = "graphql")]
+                graphql::configure(_cfg, view_client_addr.clone(), polling_config);
+            })
     })
     .bind(addr)
     .unwrap()
     .workers(4)
     .shutdown_timeout(5)
-    .run();
+    .run()
 }
@@ -36,4 +36,10 @@ lazy_static! {
             "Total count of errors by method and message",
             &["method", "err_code"]
         );
+    pub static ref RPC_RATE_LIMITED_COUNT: near_metrics::Result<IntCounterVec> =
+        near_metrics::try_create_int_counter_vec(
+            "near_rpc_rate_limited_total",
+            "Total count of rpc requests rejected by rate limits, by method",
+            &["method"]
+        );
 }
@@ -0,0 +1,269 @@
+//! Token-bucket rate limits of JSON-RPC requests per client IP and method class.
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of tracked buckets checked for eviction on every request. More than one, so that the
+/// number of tracked buckets shrinks back once clients stop sending requests.
+const EVICTION_CHECKS_PER_REQUEST: usize = 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitConfig {
+    /// Number of requests a client can make per second on average.
+    pub requests_per_second: u32,
+    /// Number of requests a client can make at once after being idle.
+    pub burst: u32,
+}
+
+/// Rate limits per class of methods, a class without limits is not limited.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RpcRateLimitsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view: Option<RateLimitConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_tx: Option<RateLimitConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<RateLimitConfig>,
+}
+
+impl RpcRateLimitsConfig {
+    fn get(&self, class: MethodClass) -> Option<&RateLimitConfig> {
+        match class {
+            MethodClass::View => self.view.as_ref(),
+            MethodClass::SendTx => self.send_tx.as_ref(),
+            MethodClass::Experimental => self.experimental.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MethodClass {
+    View,
+    SendTx,
+    Experimental,
+}
+
+impl MethodClass {
+    pub fn from_method(method: &str) -> Self {
+        match method {
+            "broadcast_tx_async"
+            | "broadcast_tx_commit"
+            | "EXPERIMENTAL_broadcast_tx_sync"
+            | "EXPERIMENTAL_check_tx"
+            | "send_tx" => MethodClass::SendTx,
+            _ if method.starts_with("EXPERIMENTAL_") => MethodClass::Experimental,
+            _ => MethodClass::View,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: &RateLimitConfig, now: Instant) -> Self {
+        Self { tokens: config.burst as f64, last_refill: now }
+    }
+
+    fn refill(&mut self, config: &RateLimitConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * config.requests_per_second as f64).min(config.burst as f64);
+        self.last_refill = now;
+    }
+
+    /// Whether the bucket is back to the state of a new one.
+    fn is_full(&mut self, config: &RateLimitConfig, now: Instant) -> bool {
+        self.refill(config, now);
+        self.tokens >= config.burst as f64
+    }
+
+    fn try_take(&mut self, config: &RateLimitConfig, now: Instant) -> bool {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Clients are limited by IPv4 address, and by IPv6 /64 prefix as a single host usually gets the
+/// whole prefix.
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            if segments[..5] == [0; 5] && segments[5] == 0xffff {
+                // IPv4-mapped address of a dual stack socket.
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return IpAddr::from([a, b, c, d]);
+            }
+            IpAddr::V6(Ipv6Addr::new(
+                segments[0],
+                segments[1],
+                segments[2],
+                segments[3],
+                0,
+                0,
+                0,
+                0,
+            ))
+        }
+    }
+}
+
+type BucketKey = (IpAddr, MethodClass);
+
+#[derive(Default)]
+struct Buckets {
+    buckets: HashMap<BucketKey, TokenBucket>,
+    /// Every tracked bucket once. Buckets are checked in turn and dropped once full, as clients
+    /// with full buckets are indistinguishable from new ones.
+    eviction_queue: VecDeque<BucketKey>,
+}
+
+impl Buckets {
+    fn evict_full(&mut self, config: &RpcRateLimitsConfig, now: Instant) {
+        for _ in 0..EVICTION_CHECKS_PER_REQUEST {
+            let key = match self.eviction_queue.pop_front() {
+                Some(key) => key,
+                None => return,
+            };
+            let is_full = match (self.buckets.get_mut(&key), config.get(key.1)) {
+                (Some(bucket), Some(class_config)) => bucket.is_full(class_config, now),
+                _ => true,
+            };
+            if is_full {
+                self.buckets.remove(&key);
+            } else {
+                self.eviction_queue.push_back(key);
+            }
+        }
+    }
+}
+
+/// Shared by all the workers of the HTTP server.
+pub struct RateLimiter {
+    config: RpcRateLimitsConfig,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RpcRateLimitsConfig) -> Self {
+        Self { config, buckets: Mutex::new(Buckets::default()) }
+    }
+
+    /// Takes a token for a call of `method` from `ip`, returns false if the client is over its
+    /// limit.
+    pub fn check(&self, ip: IpAddr, method: &str) -> bool {
+        self.check_at(ip, method, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, method: &str, now: Instant) -> bool {
+        let class = MethodClass::from_method(method);
+        let config = match self.config.get(class) {
+            Some(config) => config,
+            None => return true,
+        };
+        let key = (client_key(ip), class);
+        let mut buckets = self.buckets.lock().expect("Rate limiter lock is poisoned");
+        let Buckets { buckets: tracked, eviction_queue } = &mut *buckets;
+        let allowed = tracked
+            .entry(key)
+            .or_insert_with(|| {
+                eviction_queue.push_back(key);
+                TokenBucket::new(config, now)
+            })
+            .try_take(config, now);
+        buckets.evict_full(&self.config, now);
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_method_class() {
+        assert_eq!(MethodClass::from_method("query"), MethodClass::View);
+        assert_eq!(MethodClass::from_method("broadcast_tx_commit"), MethodClass::SendTx);
+        assert_eq!(MethodClass::from_method("EXPERIMENTAL_broadcast_tx_sync"), MethodClass::SendTx);
+        assert_eq!(MethodClass::from_method("EXPERIMENTAL_changes"), MethodClass::Experimental);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(RpcRateLimitsConfig {
+            view: Some(RateLimitConfig { requests_per_second: 2, burst: 3 }),
+            send_tx: None,
+            experimental: None,
+        });
+        let ip1: IpAddr = "10.0.0.1".parse().unwrap();
+        let ip2: IpAddr = "10.0.0.2".parse().unwrap();
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at(ip1, "block", now));
+        }
+        assert!(!limiter.check_at(ip1, "block", now));
+        // Other clients and unlimited classes are not affected.
+        assert!(limiter.check_at(ip2, "block", now));
+        assert!(limiter.check_at(ip1, "broadcast_tx_async", now));
+        // Two tokens are refilled per second.
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(ip1, "block", later));
+        assert!(limiter.check_at(ip1, "block", later));
+        assert!(!limiter.check_at(ip1, "block", later));
+    }
+
+    #[test]
+    fn test_ipv6_prefix() {
+        let limiter = RateLimiter::new(RpcRateLimitsConfig {
+            view: Some(RateLimitConfig { requests_per_second: 1, burst: 1 }),
+            send_tx: None,
+            experimental: None,
+        });
+        let now = Instant::now();
+        assert!(limiter.check_at("2001:db8:1:2::1".parse().unwrap(), "block", now));
+        // Same /64 prefix.
+        assert!(!limiter.check_at("2001:db8:1:2:ffff::2".parse().unwrap(), "block", now));
+        assert!(limiter.check_at("2001:db8:1:3::1".parse().unwrap(), "block", now));
+        // IPv4-mapped addresses are limited as the IPv4 ones.
+        assert!(limiter.check_at("10.0.0.1".parse().unwrap(), "block", now));
+        assert!(!limiter.check_at("::ffff:10.0.0.1".parse().unwrap(), "block", now));
+        assert!(limiter.check_at("::ffff:10.0.0.2".parse().unwrap(), "block", now));
+    }
+
+    #[test]
+    fn test_full_buckets_are_evicted() {
+        let limiter = RateLimiter::new(RpcRateLimitsConfig {
+            view: Some(RateLimitConfig { requests_per_second: 1, burst: 2 }),
+            send_tx: None,
+            experimental: None,
+        });
+        let now = Instant::now();
+        for i in 0..100u8 {
+            assert!(limiter.check_at(IpAddr::from([10, 0, 0, i]), "block", now));
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().buckets.len(), 100);
+        // Once refilled, the buckets are dropped as other requests come.
+        let later = now + Duration::from_secs(2);
+        for _ in 0..60 {
+            limiter.check_at(IpAddr::from([10, 0, 1, 0]), "block", later);
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.buckets.len(), 1);
+        assert_eq!(buckets.eviction_queue.len(), 1);
+    }
+}
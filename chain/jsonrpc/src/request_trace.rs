@@ -0,0 +1,172 @@
+//! Correlation IDs and timing breakdowns of JSON-RPC requests.
+//!
+//! Every request gets an ID, taken from the `X-Request-Id` header or generated, which is echoed in
+//! the response and attached to the tracing span of the request. Calls to the client actors made
+//! while handling the request get spans of their own, and if the request carries the
+//! `X-Request-Timing` header their durations are returned in the `Server-Timing` header.
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use actix::dev::ToEnvelope;
+use actix::{Actor, Addr, Handler, MailboxError, Message};
+use actix_web::HttpRequest;
+use futures::Future;
+use tracing_futures::Instrument;
+
+use near_primitives::utils::generate_random_string;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const REQUEST_TIMING_HEADER: &str = "x-request-timing";
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+/// Longer request IDs sent by clients are replaced with generated ones.
+const MAX_REQUEST_ID_LEN: usize = 128;
+const GENERATED_REQUEST_ID_LEN: usize = 16;
+
+tokio::task_local! {
+    static TIMINGS: RefCell<Vec<Timing>>;
+}
+
+/// Duration of a call to an actor made while handling a request.
+#[derive(Clone, Debug)]
+pub struct Timing {
+    pub actor: &'static str,
+    pub message: &'static str,
+    pub duration: Duration,
+}
+
+/// Returns the ID sent by the client if it's printable ASCII of reasonable length, otherwise a
+/// newly generated one.
+pub fn request_id(request: &HttpRequest) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .map(String::from)
+        .unwrap_or_else(|| generate_random_string(GENERATED_REQUEST_ID_LEN))
+}
+
+pub fn timing_requested(request: &HttpRequest) -> bool {
+    request.headers().contains_key(REQUEST_TIMING_HEADER)
+}
+
+/// Runs `future`, collecting the timings of the actor calls it makes.
+pub async fn collect_timings<F: Future>(future: F) -> (F::Output, Vec<Timing>) {
+    TIMINGS
+        .scope(RefCell::new(vec![]), async move {
+            let output = future.await;
+            (output, TIMINGS.with(|timings| timings.replace(vec![])))
+        })
+        .await
+}
+
+/// Formats the timings as a `Server-Timing` header value, with the total time of the request last.
+pub fn server_timing(timings: &[Timing], total: Duration) -> String {
+    let mut value = String::new();
+    for timing in timings {
+        write!(
+            value,
+            "{};desc=\"{}\";dur={:.3}, ",
+            timing.actor,
+            timing.message,
+            timing.duration.as_secs_f64() * 1000.0
+        )
+        .unwrap();
+    }
+    write!(value, "total;dur={:.3}", total.as_secs_f64() * 1000.0).unwrap();
+    value
+}
+
+/// Name of the message type without the module path.
+fn message_name<M>() -> &'static str {
+    let name = std::any::type_name::<M>();
+    let end = name.find('<').unwrap_or(name.len());
+    name[..end].rsplit("::").next().unwrap_or(name)
+}
+
+/// Address of an actor whose calls are traced and timed.
+pub struct TracedAddr<A: Actor> {
+    addr: Addr<A>,
+    actor: &'static str,
+}
+
+impl<A: Actor> Clone for TracedAddr<A> {
+    fn clone(&self) -> Self {
+        Self { addr: self.addr.clone(), actor: self.actor }
+    }
+}
+
+impl<A: Actor> TracedAddr<A> {
+    pub fn new(addr: Addr<A>, actor: &'static str) -> Self {
+        Self { addr, actor }
+    }
+
+    pub fn addr(&self) -> &Addr<A> {
+        &self.addr
+    }
+
+    /// Sends the message and records how long the actor took to answer, including the time the
+    /// message waited in the mailbox.
+    pub fn send<M>(&self, msg: M) -> impl Future<Output = Result<M::Result, MailboxError>>
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+    {
+        let actor = self.actor;
+        let message = message_name::<M>();
+        let span = tracing::debug_span!(target: "jsonrpc", "actor_call", actor, message);
+        let request = self.addr.send(msg);
+        async move {
+            let start = Instant::now();
+            let result = request.await;
+            let timing = Timing { actor, message, duration: start.elapsed() };
+            tracing::debug!(target: "jsonrpc", duration = ?timing.duration, "Actor answered");
+            // Outside of `collect_timings` there is nowhere to record the timing.
+            let _ = TIMINGS.try_with(|timings| timings.borrow_mut().push(timing));
+            result
+        }
+        .instrument(span)
+    }
+
+    pub fn do_send<M>(&self, msg: M)
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
+    {
+        self.addr.do_send(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_name() {
+        assert_eq!(message_name::<near_client::GetBlock>(), "GetBlock");
+        assert_eq!(message_name::<Vec<near_client::GetBlock>>(), "Vec");
+    }
+
+    #[test]
+    fn test_server_timing() {
+        let timings = vec![Timing {
+            actor: "view_client",
+            message: "GetBlock",
+            duration: Duration::from_micros(1500),
+        }];
+        assert_eq!(
+            server_timing(&timings, Duration::from_millis(2)),
+            "view_client;desc=\"GetBlock\";dur=1.500, total;dur=2.000"
+        );
+    }
+}
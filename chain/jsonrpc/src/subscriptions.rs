@@ -0,0 +1,526 @@
+//! JSON-RPC over WebSocket with subscriptions to chain events.
+//!
+//! Clients call `subscribe` with a `RpcSubscribeRequest` and get back the id of the subscription,
+//! then receive `subscription` notifications with `{"subscription": id, "result": ...}` params
+//! until they call `unsubscribe` with the id.
+//!
+//! A single `ChainEventsBroadcaster` polls the view client for new heads and final blocks and
+//! sends them to all the connections. Each connection queues at most `MAX_QUEUED_EVENTS` events,
+//! a connection that falls further behind misses the final blocks and gets a `subscription_gap`
+//! notification with the range of the missed heights instead.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::prelude::SendError;
+use actix::{
+    Actor, ActorContext, ActorFuture, Addr, AsyncContext, Context, ContextFutureSpawner, Handler,
+    Recipient, StreamHandler, WrapFuture,
+};
+use actix_web_actors::ws;
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::Value;
+
+use near_client::{GetBlock, GetStateChanges, TxStatus, ViewClientActor};
+use near_jsonrpc_client::message::{self, Message, Request, RpcError};
+use near_primitives::hash::CryptoHash;
+use near_primitives::rpc::{
+    RpcStateChangesResponse, RpcSubscribeRequest, RpcSubscriptionGap, RpcSubscriptionNotification,
+};
+use near_primitives::types::{AccountId, BlockHeight, BlockId, BlockReference, Finality};
+use near_primitives::views::{BlockView, StateChangesRequestView};
+
+use crate::rate_limit::RateLimiter;
+use crate::{check_rate_limit, parse_params};
+
+/// How often a ping is sent to the client.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// The connection is closed if the client doesn't respond for this long.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum number of final blocks broadcast in one poll if the broadcaster fell behind.
+const MAX_FINAL_BLOCKS_PER_POLL: usize = 100;
+/// Maximum number of subscriptions of a single connection.
+const MAX_SUBSCRIPTIONS: usize = 100;
+/// Maximum number of chain events waiting to be processed by a single connection.
+const MAX_QUEUED_EVENTS: usize = 16;
+
+/// Chain event broadcast to all the connections.
+#[derive(Clone, Debug)]
+pub enum ChainEvent {
+    NewHead(Arc<BlockView>),
+    FinalBlock {
+        block: Arc<BlockView>,
+        /// Height of the final block before this one, `None` for the first broadcast block.
+        previous_height: Option<BlockHeight>,
+    },
+}
+
+impl actix::Message for ChainEvent {
+    type Result = ();
+}
+
+/// Adds a connection to the receivers of the chain events.
+pub struct RegisterSession(pub Recipient<ChainEvent>);
+
+impl actix::Message for RegisterSession {
+    type Result = ();
+}
+
+/// Polls the view client for the chain events once for all the connections.
+pub struct ChainEventsBroadcaster {
+    view_client_addr: Addr<ViewClientActor>,
+    polling_interval: Duration,
+    sessions: Vec<Recipient<ChainEvent>>,
+    /// Height of the last broadcast head.
+    last_head_height: Option<BlockHeight>,
+    /// Height of the last broadcast final block.
+    last_final_height: Option<BlockHeight>,
+    /// Whether a poll is in flight.
+    polling: bool,
+}
+
+impl ChainEventsBroadcaster {
+    pub fn new(view_client_addr: Addr<ViewClientActor>, polling_interval: Duration) -> Self {
+        Self {
+            view_client_addr,
+            polling_interval,
+            sessions: vec![],
+            last_head_height: None,
+            last_final_height: None,
+            polling: false,
+        }
+    }
+
+    fn poll(&mut self, ctx: &mut Context<Self>) {
+        if self.sessions.is_empty() {
+            // Connections made later start from the current blocks.
+            self.last_head_height = None;
+            self.last_final_height = None;
+            return;
+        }
+        if self.polling {
+            return;
+        }
+        self.polling = true;
+        fetch_blocks(self.view_client_addr.clone(), self.last_final_height)
+            .into_actor(self)
+            .map(|result, act, _ctx| {
+                act.polling = false;
+                match result {
+                    Ok((head, final_blocks)) => act.broadcast(head, final_blocks),
+                    Err(err) => debug!(target: "jsonrpc", "Failed to poll chain events: {}", err),
+                }
+            })
+            .spawn(ctx);
+    }
+
+    fn broadcast(&mut self, head: BlockView, final_blocks: Vec<(BlockView, Option<BlockHeight>)>) {
+        let mut events = vec![];
+        if self.last_head_height != Some(head.header.height) {
+            self.last_head_height = Some(head.header.height);
+            events.push(ChainEvent::NewHead(Arc::new(head)));
+        }
+        for (block, previous_height) in final_blocks {
+            self.last_final_height = Some(block.header.height);
+            events.push(ChainEvent::FinalBlock { block: Arc::new(block), previous_height });
+        }
+        self.sessions.retain(|session| {
+            for event in events.iter() {
+                match session.try_send(event.clone()) {
+                    // A connection with a full queue detects the missed final blocks itself.
+                    Ok(()) | Err(SendError::Full(_)) => {}
+                    Err(SendError::Closed(_)) => return false,
+                }
+            }
+            true
+        });
+    }
+}
+
+impl Actor for ChainEventsBroadcaster {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.polling_interval, |act, ctx| act.poll(ctx));
+    }
+}
+
+impl Handler<RegisterSession> for ChainEventsBroadcaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterSession, _ctx: &mut Self::Context) {
+        self.sessions.push(msg.0);
+    }
+}
+
+/// Fetches the head and the final blocks after `last_final_height`, with the heights of the
+/// final blocks before them, in the order of heights.
+async fn fetch_blocks(
+    view_client_addr: Addr<ViewClientActor>,
+    last_final_height: Option<BlockHeight>,
+) -> Result<(BlockView, Vec<(BlockView, Option<BlockHeight>)>), String> {
+    let head = view_client_addr.send(GetBlock::latest()).await.map_err(|e| e.to_string())??;
+
+    let mut final_blocks = vec![];
+    let mut previous_height = None;
+    let mut block_reference = BlockReference::Finality(Finality::Final);
+    loop {
+        let block =
+            view_client_addr.send(GetBlock(block_reference)).await.map_err(|e| e.to_string())??;
+        if last_final_height.map_or(false, |height| block.header.height <= height)
+            || final_blocks.len() >= MAX_FINAL_BLOCKS_PER_POLL
+        {
+            previous_height = Some(block.header.height);
+            break;
+        }
+        block_reference = BlockReference::BlockId(BlockId::Hash(block.header.prev_hash));
+        final_blocks.push(block);
+        // Without a previous final block only the current one is broadcast.
+        if last_final_height.is_none() {
+            break;
+        }
+    }
+    final_blocks.reverse();
+
+    let mut result = vec![];
+    for block in final_blocks {
+        let height = block.header.height;
+        result.push((block, previous_height));
+        previous_height = Some(height);
+    }
+    Ok((head, result))
+}
+
+/// Range of the heights of the final blocks a connection missed, if it did.
+fn missed_heights(
+    last_final_height: Option<BlockHeight>,
+    previous_height: Option<BlockHeight>,
+) -> Option<(BlockHeight, BlockHeight)> {
+    match (last_final_height, previous_height) {
+        (Some(last), Some(previous)) if previous > last => Some((last + 1, previous)),
+        _ => None,
+    }
+}
+
+pub struct WsSession {
+    view_client_addr: Addr<ViewClientActor>,
+    broadcaster_addr: Addr<ChainEventsBroadcaster>,
+    rate_limiter: Arc<RateLimiter>,
+    client_ip: Option<IpAddr>,
+    subscriptions: HashMap<u64, RpcSubscribeRequest>,
+    next_subscription_id: u64,
+    /// Height of the last final block received from the broadcaster.
+    last_final_height: Option<BlockHeight>,
+    /// Whether the transaction subscriptions are being checked.
+    checking_transactions: bool,
+    last_heartbeat: Instant,
+}
+
+impl WsSession {
+    pub fn new(
+        view_client_addr: Addr<ViewClientActor>,
+        broadcaster_addr: Addr<ChainEventsBroadcaster>,
+        rate_limiter: Arc<RateLimiter>,
+        client_ip: Option<IpAddr>,
+    ) -> Self {
+        Self {
+            view_client_addr,
+            broadcaster_addr,
+            rate_limiter,
+            client_ip,
+            subscriptions: HashMap::new(),
+            next_subscription_id: 0,
+            last_final_height: None,
+            checking_transactions: false,
+            last_heartbeat: Instant::now(),
+        }
+    }
+
+    fn process_request(&mut self, request: Request) -> Result<Value, RpcError> {
+        check_rate_limit(&self.rate_limiter, self.client_ip, &request.method)?;
+        match request.method.as_ref() {
+            "subscribe" => {
+                let subscription: RpcSubscribeRequest = parse_params(request.params)?;
+                if self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return Err(RpcError::server_error(Some(format!(
+                        "Too many subscriptions, at most {} are allowed",
+                        MAX_SUBSCRIPTIONS
+                    ))));
+                }
+                let id = self.next_subscription_id;
+                self.next_subscription_id += 1;
+                self.subscriptions.insert(id, subscription);
+                Ok(Value::from(id))
+            }
+            "unsubscribe" => {
+                let (id,): (u64,) = parse_params(request.params)?;
+                Ok(Value::Bool(self.subscriptions.remove(&id).is_some()))
+            }
+            _ => Err(RpcError::method_not_found(request.method)),
+        }
+    }
+
+    fn send(ctx: &mut ws::WebsocketContext<Self>, message: Message) {
+        let text: String = message.into();
+        ctx.text(text);
+    }
+
+    fn notify<T: Serialize>(ctx: &mut ws::WebsocketContext<Self>, subscription: u64, result: T) {
+        let params = serde_json::to_value(RpcSubscriptionNotification { subscription, result })
+            .expect("Notifications are always serializable");
+        Self::send(ctx, Message::notification("subscription".to_string(), Some(params)));
+    }
+
+    fn subscribers(&self, filter: impl Fn(&RpcSubscribeRequest) -> bool) -> Vec<u64> {
+        self.subscriptions.iter().filter(|(_, s)| filter(s)).map(|(id, _)| *id).collect()
+    }
+
+    fn on_new_head(&mut self, head: &BlockView, ctx: &mut ws::WebsocketContext<Self>) {
+        for id in self.subscribers(|s| s == &RpcSubscribeRequest::NewHeads) {
+            Self::notify(ctx, id, head);
+        }
+        self.check_transactions(ctx);
+    }
+
+    fn on_final_block(
+        &mut self,
+        block: Arc<BlockView>,
+        previous_height: Option<BlockHeight>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let missed = missed_heights(self.last_final_height, previous_height);
+        self.last_final_height = Some(block.header.height);
+        if let Some((from_height, to_height)) = missed {
+            let subscribers = self.subscribers(|s| match s {
+                RpcSubscribeRequest::FinalBlocks | RpcSubscribeRequest::AccountChanges { .. } => {
+                    true
+                }
+                _ => false,
+            });
+            for subscription in subscribers {
+                let params = serde_json::to_value(RpcSubscriptionGap {
+                    subscription,
+                    from_height,
+                    to_height,
+                })
+                .expect("Notifications are always serializable");
+                Self::send(
+                    ctx,
+                    Message::notification("subscription_gap".to_string(), Some(params)),
+                );
+            }
+        }
+
+        for id in self.subscribers(|s| s == &RpcSubscribeRequest::FinalBlocks) {
+            Self::notify(ctx, id, &*block);
+        }
+
+        let account_changes: Vec<_> = self
+            .subscriptions
+            .iter()
+            .filter_map(|(id, subscription)| match subscription {
+                RpcSubscribeRequest::AccountChanges { account_ids } => {
+                    Some((*id, account_ids.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        if account_changes.is_empty() {
+            return;
+        }
+        // Waiting keeps the notifications in the order of blocks, the events queue up meanwhile.
+        fetch_account_changes(self.view_client_addr.clone(), block.header.hash, account_changes)
+            .into_actor(self)
+            .map(|result, act, ctx| match result {
+                Ok(notifications) => act.send_notifications(notifications, ctx),
+                Err(err) => debug!(target: "jsonrpc", "Failed to fetch account changes: {}", err),
+            })
+            .wait(ctx);
+    }
+
+    fn check_transactions(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.checking_transactions {
+            return;
+        }
+        let transactions: Vec<_> = self
+            .subscriptions
+            .iter()
+            .filter_map(|(id, subscription)| match subscription {
+                RpcSubscribeRequest::Transaction { tx_hash, sender_id } => {
+                    Some((*id, *tx_hash, sender_id.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        if transactions.is_empty() {
+            return;
+        }
+        self.checking_transactions = true;
+        fetch_transaction_outcomes(self.view_client_addr.clone(), transactions)
+            .into_actor(self)
+            .map(|notifications, act, ctx| {
+                act.checking_transactions = false;
+                let finished: Vec<_> = notifications.iter().map(|(id, _)| *id).collect();
+                act.send_notifications(notifications, ctx);
+                for id in finished {
+                    act.subscriptions.remove(&id);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    fn send_notifications(
+        &mut self,
+        notifications: Vec<(u64, Value)>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        // Subscriptions could have been removed while the notifications were fetched.
+        for (id, result) in notifications {
+            if self.subscriptions.contains_key(&id) {
+                Self::notify(ctx, id, result);
+            }
+        }
+    }
+
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        if Instant::now().duration_since(self.last_heartbeat) > CLIENT_TIMEOUT {
+            debug!(target: "jsonrpc", "WebSocket client timed out");
+            ctx.stop();
+            return;
+        }
+        ctx.ping(b"");
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_capacity(MAX_QUEUED_EVENTS);
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| act.heartbeat(ctx));
+        self.broadcaster_addr.do_send(RegisterSession(ctx.address().recipient()));
+    }
+}
+
+impl Handler<ChainEvent> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, event: ChainEvent, ctx: &mut Self::Context) {
+        match event {
+            ChainEvent::NewHead(head) => self.on_new_head(&head, ctx),
+            ChainEvent::FinalBlock { block, previous_height } => {
+                self.on_final_block(block, previous_height, ctx)
+            }
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                warn!(target: "jsonrpc", "WebSocket protocol error: {}", err);
+                ctx.stop();
+                return;
+            }
+        };
+        self.last_heartbeat = Instant::now();
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Pong(_) => {}
+            ws::Message::Text(text) => {
+                let response = match message::from_str(&text) {
+                    Ok(Message::Request(request)) => {
+                        let id = request.id.clone();
+                        Message::response(id, self.process_request(request))
+                    }
+                    Ok(_) => Message::error(RpcError::invalid_request()),
+                    Err(broken) => broken.reply(),
+                };
+                Self::send(ctx, response);
+            }
+            ws::Message::Binary(_) => {
+                Self::send(ctx, Message::error(RpcError::invalid_request()));
+            }
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fetches the changes of the accounts of the subscriptions in the block.
+async fn fetch_account_changes(
+    view_client_addr: Addr<ViewClientActor>,
+    block_hash: CryptoHash,
+    account_changes: Vec<(u64, Vec<AccountId>)>,
+) -> Result<Vec<(u64, Value)>, String> {
+    let mut notifications = vec![];
+    for (id, account_ids) in account_changes {
+        let changes = view_client_addr
+            .send(GetStateChanges {
+                block_hash,
+                state_changes_request: StateChangesRequestView::AccountChanges { account_ids },
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+        if !changes.is_empty() {
+            let response = RpcStateChangesResponse { block_hash, changes };
+            notifications.push((id, serde_json::to_value(response).unwrap()));
+        }
+    }
+    Ok(notifications)
+}
+
+/// Fetches the final outcomes of the transactions of the subscriptions, skips the transactions
+/// that don't have one yet.
+async fn fetch_transaction_outcomes(
+    view_client_addr: Addr<ViewClientActor>,
+    transactions: Vec<(u64, CryptoHash, AccountId)>,
+) -> Vec<(u64, Value)> {
+    let mut notifications = vec![];
+    for (id, tx_hash, signer_account_id) in transactions {
+        let tx_status = TxStatus { tx_hash, signer_account_id, fetch_receipt: false };
+        // The transaction may not be known yet, keep waiting for it.
+        if let Ok(Ok(Some(outcome))) = view_client_addr.send(tx_status).await {
+            notifications.push((id, serde_json::to_value(outcome).unwrap()));
+        }
+    }
+    notifications
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subscribe_request() {
+        let request: RpcSubscribeRequest =
+            serde_json::from_str(r#"{"subscription_type": "new_heads"}"#).unwrap();
+        assert_eq!(request, RpcSubscribeRequest::NewHeads);
+        let request: RpcSubscribeRequest = serde_json::from_str(
+            r#"{"subscription_type": "account_changes", "account_ids": ["test.near"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            RpcSubscribeRequest::AccountChanges { account_ids: vec!["test.near".to_string()] }
+        );
+        assert!(serde_json::from_str::<RpcSubscribeRequest>(r#"{"subscription_type": "blocks"}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_missed_heights() {
+        // The first received block and the first broadcast block don't follow anything.
+        assert_eq!(missed_heights(None, Some(10)), None);
+        assert_eq!(missed_heights(Some(10), None), None);
+        assert_eq!(missed_heights(Some(10), Some(10)), None);
+        assert_eq!(missed_heights(Some(10), Some(15)), Some((11, 15)));
+    }
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "graphql")]
+
+use actix::System;
+use actix_web::http::StatusCode;
+use serde_json::{json, Value};
+
+use near_jsonrpc::{RateLimitConfig, RpcLimitsConfig, RpcRateLimitsConfig};
+use near_logger_utils::init_test_logger;
+
+pub mod test_utils;
+
+async fn graphql_request(
+    client: &actix_web::client::Client,
+    addr: &str,
+    query: &str,
+) -> (StatusCode, Value) {
+    let mut response = client
+        .post(format!("http://{}/graphql", addr))
+        .send_json(&json!({ "query": query }))
+        .await
+        .unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+/// Nested fields of blocks, chunks and accounts are resolved.
+#[test]
+fn test_graphql_query() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let (status, response) = graphql_request(
+                &client,
+                &addr,
+                "{ block(height: 0) { height chunks { shardId transactions { hash } receiptIds } }
+                   account(accountId: \"test1\") { accountId accessKeys { publicKey } } }",
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(response["errors"], Value::Null, "{}", response);
+            let block = &response["data"]["block"];
+            assert_eq!(block["height"], 0);
+            let chunks = block["chunks"].as_array().unwrap();
+            assert!(!chunks.is_empty());
+            assert_eq!(chunks[0]["transactions"], json!([]));
+            assert_eq!(chunks[0]["receiptIds"], json!([]));
+            let account = &response["data"]["account"];
+            assert_eq!(account["accountId"], "test1");
+            assert_eq!(account["accessKeys"].as_array().unwrap().len(), 1);
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// Queries over the depth or complexity limits are rejected before being run.
+#[test]
+fn test_graphql_limits() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let deep_query = format!(
+                "{{ __schema {{ types {{ fields {{ type {}{{ name }}{} }} }} }} }}",
+                "{ ofType ".repeat(10),
+                " }".repeat(10)
+            );
+            let (_, response) = graphql_request(&client, &addr, &deep_query).await;
+            assert_eq!(response["data"], Value::Null, "{}", response);
+            assert!(response["errors"][0]["message"].as_str().unwrap().contains("nested"));
+
+            let complex_query = format!(
+                "{{ {} }}",
+                (0..300)
+                    .map(|i| format!("b{}: block(height: 0) {{ height }}", i))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+            let (_, response) = graphql_request(&client, &addr, &complex_query).await;
+            assert_eq!(response["data"], Value::Null, "{}", response);
+            assert!(response["errors"][0]["message"].as_str().unwrap().contains("complex"));
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// GraphQL requests count against the rate limit of the view methods.
+#[test]
+fn test_graphql_rate_limit() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all_with_limits(
+            test_utils::NodeType::NonValidator,
+            RpcLimitsConfig {
+                rate_limits: RpcRateLimitsConfig {
+                    // No tokens are refilled during the test.
+                    view: Some(RateLimitConfig { requests_per_second: 0, burst: 1 }),
+                    ..RpcRateLimitsConfig::default()
+                },
+                ..RpcLimitsConfig::default()
+            },
+        );
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let query = "{ block(height: 0) { height } }";
+            let (status, _) = graphql_request(&client, &addr, query).await;
+            assert_eq!(status, StatusCode::OK);
+            let (status, response) = graphql_request(&client, &addr, query).await;
+            assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response["errors"][0]["extensions"]["cause"]["name"], "RATE_LIMITED");
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
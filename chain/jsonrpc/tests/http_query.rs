@@ -1,7 +1,10 @@
 use actix::System;
-use futures::{future, FutureExt};
+use actix_web::http::StatusCode;
+use actix_web_actors::ws;
+use futures::{future, FutureExt, SinkExt, Stream, StreamExt};
 
 use near_jsonrpc::client::new_http_client;
+use near_jsonrpc::{RateLimitConfig, RpcLimitsConfig, RpcRateLimitsConfig};
 use near_logger_utils::init_test_logger;
 
 pub mod test_utils;
@@ -26,3 +29,253 @@ fn test_status() {
     })
     .unwrap();
 }
+
+/// Probe liveness and readiness via HTTP GET, the test node has no peers so it's not ready.
+#[test]
+fn test_health_probes() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let response = client.get(format!("http://{}/health/live", addr)).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let mut response =
+                client.get(format!("http://{}/health/ready", addr)).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let reason = response.body().await.unwrap();
+            assert!(String::from_utf8_lossy(&reason).contains("peers"));
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// The request ID is echoed and the actor calls are timed when the client asks for it.
+#[test]
+fn test_request_id_and_timing() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "dontcare",
+                "method": "status",
+                "params": [],
+            });
+            let response = client
+                .post(format!("http://{}", addr))
+                .header("X-Request-Id", "test-request-1")
+                .header("X-Request-Timing", "1")
+                .send_json(&request)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get("x-request-id").unwrap(), "test-request-1");
+            let timing = response.headers().get("server-timing").unwrap().to_str().unwrap();
+            assert!(timing.starts_with("client;desc=\"Status\";dur="), "{}", timing);
+            assert!(timing.contains("total;dur="), "{}", timing);
+
+            // Without the headers an ID is generated and no timings are returned.
+            let response =
+                client.post(format!("http://{}", addr)).send_json(&request).await.unwrap();
+            assert!(!response.headers().get("x-request-id").unwrap().is_empty());
+            assert!(response.headers().get("server-timing").is_none());
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// Requests and responses over the size limits are answered with typed errors.
+#[test]
+fn test_payload_size_limits() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all_with_limits(
+            test_utils::NodeType::NonValidator,
+            RpcLimitsConfig {
+                json_payload_max_size: 1024,
+                max_response_size: 256,
+                ..RpcLimitsConfig::default()
+            },
+        );
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "dontcare",
+                "method": "tx",
+                "params": ["a".repeat(2048), "test1"],
+            });
+            let mut response =
+                client.post(format!("http://{}", addr)).send_json(&request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            let message: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(message["error"]["cause"]["name"], "REQUEST_TOO_LARGE");
+
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "dontcare",
+                "method": "block",
+                "params": {"block_id": 0},
+            });
+            let mut response = client
+                .post(format!("http://{}", addr))
+                .header("Accept-Encoding", "gzip")
+                .send_json(&request)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+            let message: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(message["id"], "dontcare");
+            assert_eq!(message["error"]["cause"]["name"], "RESPONSE_TOO_LARGE");
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// Final blocks are streamed as newline-delimited JSON, the stream ends at the final head.
+#[test]
+fn test_block_stream() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let mut response = client
+                .get(format!("http://{}/blocks/stream?from_height=0", addr))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get("content-type").unwrap(), "application/x-ndjson");
+            let body = response.body().await.unwrap();
+            let lines: Vec<serde_json::Value> = String::from_utf8_lossy(&body)
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(lines.len(), 1);
+            assert_eq!(lines[0]["block"]["header"]["height"], 0);
+            assert!(lines[0]["chunks"].is_array());
+
+            let response = client
+                .get(format!("http://{}/blocks/stream?from_height=1", addr))
+                .send()
+                .await
+                .unwrap()
+                .body()
+                .await
+                .unwrap();
+            assert!(response.is_empty());
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// Reads the next JSON-RPC message from the WebSocket, skipping the control frames.
+async fn next_ws_message(
+    connection: &mut (impl Stream<Item = Result<ws::Frame, ws::ProtocolError>> + Unpin),
+) -> serde_json::Value {
+    loop {
+        if let ws::Frame::Text(text) = connection.next().await.unwrap().unwrap() {
+            return serde_json::from_slice(&text).unwrap();
+        }
+    }
+}
+
+fn subscribe_request(subscription_type: &str) -> ws::Message {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": "dontcare",
+        "method": "subscribe",
+        "params": {"subscription_type": subscription_type},
+    });
+    ws::Message::Text(request.to_string())
+}
+
+/// All the connections get the new heads from the shared poller, in the order of heights.
+#[test]
+fn test_ws_subscriptions() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let mut connections = vec![];
+            for _ in 0..2 {
+                let (_, mut connection) =
+                    client.ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+                connection.send(subscribe_request("new_heads")).await.unwrap();
+                let response = next_ws_message(&mut connection).await;
+                assert_eq!(response["result"], 0);
+                connections.push(connection);
+            }
+            for connection in connections.iter_mut() {
+                let mut last_height = None;
+                for _ in 0..3 {
+                    let notification = next_ws_message(connection).await;
+                    assert_eq!(notification["method"], "subscription");
+                    assert_eq!(notification["params"]["subscription"], 0);
+                    let height =
+                        notification["params"]["result"]["header"]["height"].as_u64().unwrap();
+                    assert!(last_height.map_or(true, |last_height| height > last_height));
+                    last_height = Some(height);
+                }
+            }
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
+
+/// Opening a WebSocket connection and every request over it take a token of the rate limit.
+#[test]
+fn test_ws_rate_limit() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_all_with_limits(
+            test_utils::NodeType::NonValidator,
+            RpcLimitsConfig {
+                rate_limits: RpcRateLimitsConfig {
+                    // No tokens are refilled during the test.
+                    view: Some(RateLimitConfig { requests_per_second: 0, burst: 2 }),
+                    ..RpcRateLimitsConfig::default()
+                },
+                ..RpcLimitsConfig::default()
+            },
+        );
+
+        let client = actix_web::client::Client::new();
+        actix::spawn(async move {
+            let (_, mut connection) =
+                client.ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+            connection.send(subscribe_request("final_blocks")).await.unwrap();
+            let response = next_ws_message(&mut connection).await;
+            assert_eq!(response["result"], 0);
+            connection.send(subscribe_request("new_heads")).await.unwrap();
+            let response = next_ws_message(&mut connection).await;
+            assert_eq!(response["error"]["cause"]["name"], "RATE_LIMITED");
+
+            assert!(client.ws(format!("ws://{}/ws", addr)).connect().await.is_err());
+            System::current().stop();
+        });
+    })
+    .unwrap();
+}
@@ -1,19 +1,40 @@
 use std::convert::TryFrom;
+use std::time::Duration;
 
+use actix::clock::delay_for;
 use actix::{Actor, System};
+use borsh::BorshSerialize;
 use futures::{future, FutureExt};
 
-use near_crypto::{KeyType, PublicKey, Signature};
+use near_crypto::{InMemorySigner, KeyType, PublicKey, Signature};
 use near_jsonrpc::client::new_client;
+use near_jsonrpc_client::message::{RpcErrorCauseName, RpcErrorName};
 use near_jsonrpc_client::ChunkId;
 use near_logger_utils::init_test_logger;
 use near_network::test_utils::WaitOrTimeout;
 use near_primitives::account::{AccessKey, AccessKeyPermission};
+use near_primitives::block_header::BlockHeader;
 use near_primitives::hash::CryptoHash;
-use near_primitives::rpc::RpcQueryRequest;
+use near_primitives::light_client::{
+    verify_block_proof, verify_execution_proof, verify_outcome_proof,
+};
 use near_primitives::rpc::RpcValidatorsOrderedRequest;
-use near_primitives::types::{BlockId, BlockReference, ShardId, SyncCheckpoint};
-use near_primitives::views::{QueryRequest, QueryResponseKind};
+use near_primitives::rpc::{
+    RpcChunkReference, RpcEstimateFeeRequest, RpcGasPriceHistoryRequest,
+    RpcLightClientExecutionProofRequest, RpcQueryRequest, RpcReceiptRequest,
+    RpcStateChangesRequest, RpcWatchedAccountChangesRequest,
+};
+use near_primitives::serialize::to_base64;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{
+    Balance, BlockId, BlockReference, EpochId, EpochReference, Finality, ShardId, SyncCheckpoint,
+    TransactionOrReceiptId,
+};
+use near_primitives::version::{ProtocolFeature, PROTOCOL_VERSION};
+use near_primitives::views::{
+    AccountList, ContractMetadataView, LightClientBlockLiteView, QueryRequest, QueryResponseKind,
+    StateChangesRequestView,
+};
 
 #[macro_use]
 pub mod test_utils;
@@ -114,6 +135,33 @@ fn test_chunk_by_hash() {
     });
 }
 
+/// Retrieve chunk with the proofs of its contents via json rpc
+#[test]
+fn test_chunk_with_proofs() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let chunk = client
+            .chunk_with_proofs(RpcChunkReference::BlockShardId {
+                block_reference: BlockReference::BlockId(BlockId::Height(0)),
+                shard_id: 0,
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunk.chunk.author, "test2");
+        assert!(chunk.proofs.transactions.is_empty());
+        assert_eq!(chunk.proofs.outgoing_receipts.len(), 1);
+        // The only chunk of the block is the root of the chunk headers.
+        assert_eq!(chunk.proofs.chunk_header, Some(vec![]));
+        let same_chunk = client
+            .chunk_with_proofs(RpcChunkReference::ChunkHash {
+                chunk_id: chunk.chunk.header.chunk_hash,
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunk.chunk.header.chunk_hash, same_chunk.chunk.header.chunk_hash);
+        assert_eq!(same_chunk.proofs.chunk_header, None);
+    });
+}
+
 /// Retrieve chunk via json rpc
 #[test]
 fn test_chunk_invalid_shard_id() {
@@ -149,6 +197,12 @@ fn test_query_by_path_account() {
         assert_eq!(account_info.locked, 0);
         assert_eq!(account_info.storage_paid_at, 0);
         assert_eq!(account_info.storage_usage, 0);
+
+        let status = client.status().await.unwrap();
+        assert!(status
+            .deprecated_usage
+            .iter()
+            .any(|usage| usage.name == "query_by_path" && usage.count > 0));
     });
 }
 
@@ -241,6 +295,62 @@ fn test_query_access_keys() {
     });
 }
 
+/// Connect to json rpc and query access keys page by page.
+#[test]
+fn test_query_access_keys_paginated() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let query_response = client
+            .query(RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewAccessKeyListPaginated {
+                    account_id: "test".to_string(),
+                    cursor: None,
+                    limit: Some(1),
+                },
+            })
+            .await
+            .unwrap();
+        let access_keys = if let QueryResponseKind::AccessKeyList(access_keys) = query_response.kind
+        {
+            access_keys
+        } else {
+            panic!("queried access keys, but received something else: {:?}", query_response.kind);
+        };
+        assert_eq!(access_keys.keys.len(), 1);
+        assert_eq!(access_keys.next_cursor, None);
+    });
+}
+
+/// Connect to json rpc and list accounts by prefix page by page.
+#[test]
+fn test_query_accounts_by_prefix() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let mut accounts = vec![];
+        let mut cursor = None;
+        loop {
+            let query_response = client
+                .query(RpcQueryRequest {
+                    block_reference: BlockReference::latest(),
+                    request: QueryRequest::ViewAccountsByPrefix {
+                        prefix: "test".to_string(),
+                        cursor,
+                        limit: Some(1),
+                    },
+                })
+                .await
+                .unwrap();
+            let account_list = AccountList::try_from(query_response).unwrap();
+            assert!(account_list.accounts.len() <= 1);
+            accounts.extend(account_list.accounts);
+            cursor = account_list.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(accounts, vec!["test1".to_string(), "test2".to_string()]);
+    });
+}
+
 /// Connect to json rpc and query account info with soft-deprecated query API.
 #[test]
 fn test_query_by_path_access_key() {
@@ -314,6 +424,33 @@ fn test_query_state() {
     });
 }
 
+/// Connect to json rpc and query state page by page.
+#[test]
+fn test_query_state_paginated() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let query_response = client
+            .query(RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewStatePaginated {
+                    account_id: "test".to_string(),
+                    prefix: vec![].into(),
+                    cursor: None,
+                    limit: Some(1),
+                    include_proof: true,
+                },
+            })
+            .await
+            .unwrap();
+        let state = if let QueryResponseKind::ViewState(state) = query_response.kind {
+            state
+        } else {
+            panic!("queried state, but received something else: {:?}", query_response.kind);
+        };
+        assert_eq!(state.values.len(), 0);
+        assert_eq!(state.next_cursor, None);
+    });
+}
+
 /// Connect to json rpc and call function
 #[test]
 fn test_query_call_function() {
@@ -354,6 +491,49 @@ fn test_status() {
     });
 }
 
+/// Retrieve routing info via JSON RPC.
+#[test]
+fn test_routing_info() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let routing_info = client.EXPERIMENTAL_routing_info().await.unwrap();
+        assert_eq!(routing_info.chain_id, "unittest");
+        assert_eq!(routing_info.num_shards, 1);
+        assert_eq!(routing_info.latest_block_height, 0);
+        assert_eq!(routing_info.syncing, false);
+        assert_eq!(routing_info.sync_status, "NoSync");
+    });
+}
+
+/// Retrieve the internal state of the client via JSON RPC.
+#[test]
+fn test_debug_status() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let debug_status = client.EXPERIMENTAL_debug_status().await.unwrap();
+        assert_eq!(debug_status.sync_status, "NoSync");
+        assert_eq!(debug_status.sync_current_height, None);
+        assert_eq!(debug_status.head_height, 0);
+        assert!(debug_status.orphans.is_empty());
+        assert!(debug_status.blocks_with_missing_chunks.is_empty());
+        assert!(debug_status.catchup_blocks.is_empty());
+    });
+}
+
+/// Retrieve compiled-in protocol features via JSON RPC.
+#[test]
+fn test_protocol_features() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let protocol_features = client.EXPERIMENTAL_protocol_features().await.unwrap();
+        assert_eq!(protocol_features.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(protocol_features.features.len(), ProtocolFeature::all().len());
+        for feature in protocol_features.features {
+            assert_eq!(
+                feature.active,
+                feature.protocol_version <= protocol_features.epoch_protocol_version
+            );
+        }
+    });
+}
+
 /// Retrieve client status failed.
 #[test]
 fn test_status_fail() {
@@ -491,6 +671,346 @@ fn test_gas_price() {
     });
 }
 
+/// Retrieve fee history
+#[test]
+fn test_fee_history() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let fee_history = client.fee_history(10, None).await.unwrap();
+        assert_eq!(fee_history.blocks.len(), 1);
+        let genesis = &fee_history.blocks[0];
+        assert_eq!(genesis.height, 0);
+        assert!(genesis.gas_price > 0);
+        assert_eq!(genesis.gas_used, vec![0]);
+        assert_eq!(genesis.chunk_fullness, vec![0.0]);
+    });
+}
+
+/// Retrieve gas prices in a range of heights
+#[test]
+fn test_gas_price_history() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let request = RpcGasPriceHistoryRequest { from_height: 0, to_height: Some(0) };
+        let history = client.EXPERIMENTAL_gas_price_history(request).await.unwrap();
+        assert_eq!(history.blocks.len(), 1);
+        assert_eq!(history.blocks[0].height, 0);
+        assert!(history.blocks[0].gas_price > 0);
+
+        let request = RpcGasPriceHistoryRequest { from_height: 0, to_height: Some(1_000_000) };
+        assert!(client.EXPERIMENTAL_gas_price_history(request).await.is_err());
+    });
+}
+
+/// Retrieve method metadata of a contract
+#[test]
+fn test_query_contract_metadata() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let query_response = client
+            .query(RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewContractMetadata { account_id: "test".to_string() },
+            })
+            .await
+            .unwrap();
+        assert_eq!(query_response.block_height, 0);
+        let metadata = ContractMetadataView::try_from(query_response).unwrap();
+        assert!(metadata.methods.is_empty());
+    });
+}
+
+/// Estimate fee of a function call of a contract without method metadata
+#[test]
+fn test_estimate_fee() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let estimate = client
+            .EXPERIMENTAL_estimate_fee(RpcEstimateFeeRequest {
+                signer_id: "test1".to_string(),
+                receiver_id: "test".to_string(),
+                method_name: "hello".to_string(),
+                args: vec![],
+            })
+            .await
+            .unwrap();
+        assert!(!estimate.from_metadata);
+        assert_eq!(estimate.deposit, 0);
+        assert!(estimate.gas > 0);
+        assert!(estimate.fee > Balance::from(estimate.gas) * estimate.gas_price);
+    });
+}
+
+/// Retrieve executions of an unused idempotency key
+#[test]
+fn test_idempotency_key() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let key = to_base64(b"transfer-1");
+        let idempotency_key =
+            client.EXPERIMENTAL_idempotency_key("test".to_string(), key.clone()).await.unwrap();
+        assert_eq!(idempotency_key.account_id, "test");
+        assert_eq!(idempotency_key.key, key);
+        assert!(idempotency_key.executions.is_empty());
+        assert!(client.EXPERIMENTAL_idempotency_key("Invalid".to_string(), key).await.is_err());
+    });
+}
+
+/// Retrieve all kinds of changes of accounts in a block
+#[test]
+fn test_changes_all_changes() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let block = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        let response = client
+            .EXPERIMENTAL_changes(RpcStateChangesRequest {
+                block_reference: BlockReference::BlockId(BlockId::Hash(block.header.hash)),
+                state_changes_request: StateChangesRequestView::AllChanges {
+                    account_ids: vec!["test1".to_string()],
+                },
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.block_hash, block.header.hash);
+    });
+}
+
+/// Outcomes of unknown transactions have no proof
+#[test]
+fn test_light_client_proof_unknown_transaction() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let block = client.block(BlockReference::latest()).await.unwrap();
+        let request = RpcLightClientExecutionProofRequest {
+            id: TransactionOrReceiptId::Transaction {
+                transaction_hash: CryptoHash::default(),
+                sender_id: "test1".to_string(),
+            },
+            light_client_head: block.header.hash,
+        };
+        assert!(client.light_client_proof(request).await.is_err());
+    });
+}
+
+/// Outcome of an executed transaction is proven against a final light client head
+#[test]
+fn test_light_client_proof() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = test_utils::start_single_validator();
+        let client = new_client(&format!("http://{}", addr));
+
+        actix::spawn(async move {
+            let block_hash = client.block(BlockReference::latest()).await.unwrap().header.hash;
+            let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+            let tx = SignedTransaction::send_money(
+                1,
+                "test1".to_string(),
+                "test2".to_string(),
+                &signer,
+                100,
+                block_hash,
+            );
+            let bytes = tx.try_to_vec().unwrap();
+            let outcome = client.broadcast_tx_commit(to_base64(&bytes)).await.unwrap();
+            let id = TransactionOrReceiptId::Transaction {
+                transaction_hash: outcome.transaction_outcome.id,
+                sender_id: "test1".to_string(),
+            };
+            // The outcome is proven by the next block with a chunk, and the head has to be final
+            // and after that block for the block proof to go through its merkle root.
+            for _ in 0..100 {
+                let head = client.block(BlockReference::Finality(Finality::Final)).await.unwrap();
+                let request = RpcLightClientExecutionProofRequest {
+                    id: id.clone(),
+                    light_client_head: head.header.hash,
+                };
+                if let Ok(proof) = client.light_client_proof(request).await {
+                    if proof.block_header_lite.inner_lite.height < head.header.height {
+                        let head = LightClientBlockLiteView::from(BlockHeader::from(head.header));
+                        assert!(!proof.block_proof.is_empty());
+                        verify_outcome_proof(&proof).unwrap();
+                        verify_block_proof(
+                            &proof.outcome_proof.block_hash,
+                            &proof.block_proof,
+                            &head,
+                        )
+                        .unwrap();
+                        assert_eq!(
+                            verify_execution_proof(&proof, &head).unwrap(),
+                            &outcome.transaction_outcome.outcome.status
+                        );
+                        System::current().stop();
+                        return;
+                    }
+                }
+                delay_for(Duration::from_millis(100)).await;
+            }
+            panic!("Transaction outcome wasn't proven against a final head");
+        });
+    })
+    .unwrap();
+}
+
+/// Watched account changes are only available for the accounts in the node config
+#[test]
+fn test_watched_account_changes_not_watched() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        for account_id in &["test", "Invalid"] {
+            let request = RpcWatchedAccountChangesRequest {
+                account_id: account_id.to_string(),
+                from_block_height: 0,
+                from_index: 0,
+                limit: None,
+            };
+            assert!(client.EXPERIMENTAL_watched_account_changes(request).await.is_err());
+        }
+    });
+}
+
+/// View methods accept named block references
+#[test]
+fn test_block_reference_params() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let block = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        let block_hash = serde_json::json!(block.header.hash);
+        let requests = vec![
+            ("block", serde_json::json!({"finality": "final"})),
+            ("block", serde_json::json!({"block_id": block_hash})),
+            ("chunk", serde_json::json!({"finality": "final", "shard_id": 0})),
+            ("chunk", serde_json::json!({"block_id": 0, "shard_id": 0})),
+            ("chunk", serde_json::json!({"chunk_id": block.chunks[0].chunk_hash})),
+            ("validators", serde_json::json!({"finality": "final"})),
+            ("validators", serde_json::json!({"block_id": block_hash})),
+            ("gas_price", serde_json::json!({"finality": "optimistic"})),
+            ("gas_price", serde_json::json!({"sync_checkpoint": "genesis"})),
+        ];
+        for (method, params) in requests {
+            let json = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "dontcare",
+                "method": method,
+                "params": &params,
+            });
+            let response = &mut client
+                .client
+                .post(&client.server_addr)
+                .header("Content-Type", "application/json")
+                .send_json(&json)
+                .await
+                .unwrap();
+            let response = response.json::<serde_json::Value>().await.unwrap();
+            assert!(
+                response["error"] == serde_json::json!(null),
+                "{} with {} failed: {}",
+                method,
+                params,
+                response
+            );
+        }
+    });
+}
+
+/// Requests of a batch are answered independently
+#[test]
+fn test_batch() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let json = serde_json::json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "block", "params": [0]},
+            {"jsonrpc": "2.0", "id": 2, "method": "invalid_method", "params": []},
+            {"invalid": true},
+        ]);
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .header("Content-Type", "application/json")
+            .send_json(&json)
+            .await
+            .unwrap();
+        let response = response.json::<serde_json::Value>().await.unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["header"]["height"], 0);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["error"]["code"], -32_601);
+        assert_eq!(responses[2]["error"]["code"], -32_600);
+    });
+}
+
+/// Batches larger than the limit are rejected as a whole
+#[test]
+fn test_batch_too_large() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "status"});
+        let json = serde_json::Value::Array(vec![request; 101]);
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .header("Content-Type", "application/json")
+            .send_json(&json)
+            .await
+            .unwrap();
+        let response = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(response["error"]["code"], -32_000);
+    });
+}
+
+/// Errors carry a structured cause next to the legacy code and data
+#[test]
+fn test_structured_errors() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let block_reference = BlockReference::BlockId(BlockId::Height(1_000_000));
+        let err = client.block(block_reference).await.unwrap_err();
+        assert_eq!(err.code, -32_000);
+        assert_eq!(err.name, Some(RpcErrorName::HandlerError));
+        assert_eq!(err.cause_name(), Some(RpcErrorCauseName::UnknownBlock));
+        assert!(!err.retriable);
+
+        let err = client.chunk(ChunkId::BlockShardId(BlockId::Height(0), 100)).await.unwrap_err();
+        assert_eq!(err.cause_name(), Some(RpcErrorCauseName::InvalidShardId));
+
+        let json = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "no_such_method"});
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .header("Content-Type", "application/json")
+            .send_json(&json)
+            .await
+            .unwrap();
+        let response = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(response["error"]["code"], -32_601);
+        assert_eq!(response["error"]["name"], "REQUEST_VALIDATION_ERROR");
+        assert_eq!(response["error"]["cause"]["name"], "METHOD_NOT_FOUND");
+    });
+}
+
+/// Looking up a receipt the node doesn't know about fails with a stable cause.
+#[test]
+fn test_receipt_unknown() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let err = client
+            .EXPERIMENTAL_receipt(RpcReceiptRequest { receipt_id: CryptoHash::default() })
+            .await
+            .unwrap_err();
+        assert_eq!(err.cause_name(), Some(RpcErrorCauseName::UnknownReceipt));
+        assert!(!err.retriable);
+    });
+}
+
+/// The protocol config is looked up by the block, unknown blocks are typed errors.
+#[test]
+fn test_protocol_config() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let config = client
+            .EXPERIMENTAL_protocol_config(BlockReference::BlockId(BlockId::Height(0)))
+            .await
+            .unwrap();
+        assert_eq!(config.protocol_version, PROTOCOL_VERSION);
+        let latest = client.EXPERIMENTAL_protocol_config(BlockReference::latest()).await.unwrap();
+        assert_eq!(latest.epoch_length, config.epoch_length);
+        let err = client
+            .EXPERIMENTAL_protocol_config(BlockReference::BlockId(BlockId::Height(1_000_000)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.cause_name(), Some(RpcErrorCauseName::UnknownBlock));
+    });
+}
+
 #[test]
 fn test_invalid_methods() {
     test_with_client!(test_utils::NodeType::NonValidator, client, async move {
@@ -642,6 +1162,25 @@ fn test_view_state_non_existing_account_invalid_prefix() {
     });
 }
 
+/// Validators can be looked up by the epoch ID, unknown epochs are errors.
+#[test]
+fn test_validators_by_epoch() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        client.validators_by_epoch(EpochReference::Latest).await.unwrap();
+        let block = client.block(BlockReference::latest()).await.unwrap();
+        client
+            .validators_by_epoch(EpochReference::EpochId(EpochId(block.header.epoch_id)))
+            .await
+            .unwrap();
+        let unknown_epoch_id =
+            EpochId(CryptoHash::try_from("123PXBoQKnTnARA49ctEzAiradrAAAEtLRCJGpjH24qC").unwrap());
+        assert!(client
+            .validators_by_epoch(EpochReference::EpochId(unknown_epoch_id))
+            .await
+            .is_err());
+    });
+}
+
 #[test]
 #[ignore] // https://github.com/nearprotocol/nearcore/issues/2800
 fn test_validators_non_existing_block_hash() {
@@ -9,6 +9,7 @@ use near_jsonrpc::client::new_client;
 use near_logger_utils::{init_integration_logger, init_test_logger};
 use near_network::test_utils::WaitOrTimeout;
 use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::rpc::{RpcSendTransactionRequest, TxExecutionStatus};
 use near_primitives::serialize::{to_base, to_base64};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::BlockReference;
@@ -97,6 +98,89 @@ fn test_send_tx_commit() {
     });
 }
 
+/// Test that `send_tx` responds once the transaction reaches the requested status.
+#[test]
+fn test_send_tx_wait_until() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let block_hash = client.block(BlockReference::latest()).await.unwrap().header.hash;
+        let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+        let mut nonce = 0;
+        for wait_until in vec![
+            TxExecutionStatus::None,
+            TxExecutionStatus::Broadcasted,
+            TxExecutionStatus::Included,
+            TxExecutionStatus::ExecutedOptimistic,
+            TxExecutionStatus::Final,
+        ] {
+            nonce += 1;
+            let tx = SignedTransaction::send_money(
+                nonce,
+                "test1".to_string(),
+                "test2".to_string(),
+                &signer,
+                100,
+                block_hash,
+            );
+            let bytes = tx.try_to_vec().unwrap();
+            let result = client
+                .send_tx(RpcSendTransactionRequest {
+                    signed_tx_base64: to_base64(&bytes),
+                    wait_until,
+                })
+                .await
+                .unwrap();
+            assert_eq!(result.transaction_hash, tx.get_hash());
+            assert_eq!(result.final_execution_status, wait_until);
+            let outcome = result.outcome;
+            match wait_until {
+                TxExecutionStatus::None | TxExecutionStatus::Broadcasted => {
+                    assert!(outcome.is_none())
+                }
+                TxExecutionStatus::Included => assert!(outcome.is_some()),
+                TxExecutionStatus::ExecutedOptimistic | TxExecutionStatus::Final => {
+                    assert_eq!(
+                        outcome.unwrap().status,
+                        FinalExecutionStatus::SuccessValue(to_base64(&[]))
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Test that the status of a committed transaction includes its receipts and their cost.
+#[test]
+fn test_tx_status_with_receipts() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let block_hash = client.block(BlockReference::latest()).await.unwrap().header.hash;
+        let signer = InMemorySigner::from_seed("test1", KeyType::ED25519, "test1");
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".to_string(),
+            "test2".to_string(),
+            &signer,
+            100,
+            block_hash,
+        );
+        let bytes = tx.try_to_vec().unwrap();
+        client.broadcast_tx_commit(to_base64(&bytes)).await.unwrap();
+        let result = client.EXPERIMENTAL_tx_status(to_base64(&bytes)).await.unwrap();
+        let outcome = &result.final_outcome;
+        assert_eq!(outcome.status, FinalExecutionStatus::SuccessValue(to_base64(&[])));
+        assert!(result.receipts.iter().any(|receipt| receipt.receiver_id == "test2"));
+        let tokens_burnt: u128 = std::iter::once(&outcome.transaction_outcome)
+            .chain(outcome.receipts_outcome.iter())
+            .map(|outcome| outcome.outcome.tokens_burnt)
+            .sum();
+        assert!(result.cost.gas_burnt > 0);
+        assert_eq!(result.cost.tokens_burnt, tokens_burnt);
+        assert_eq!(
+            result.cost.total_refund,
+            result.cost.refunds.iter().map(|refund| refund.amount).sum::<u128>()
+        );
+    });
+}
+
 /// Test that expired transaction should be rejected
 #[test]
 fn test_expired_tx() {
@@ -3,7 +3,7 @@ use actix::Addr;
 use near_chain_configs::GenesisConfig;
 use near_client::test_utils::setup_no_network_with_validity_period;
 use near_client::ViewClientActor;
-use near_jsonrpc::{start_http, RpcConfig};
+use near_jsonrpc::{start_http, RpcConfig, RpcLimitsConfig};
 use near_network::test_utils::open_port;
 use near_primitives::types::NumBlocks;
 
@@ -21,13 +21,48 @@ pub fn start_all(node_type: NodeType) -> (Addr<ViewClientActor>, String) {
     start_all_with_validity_period(node_type, 100, false)
 }
 
+pub fn start_all_with_limits(
+    node_type: NodeType,
+    limits_config: RpcLimitsConfig,
+) -> (Addr<ViewClientActor>, String) {
+    start_all_with_config(node_type, vec!["test1", "test2"], 100, false, limits_config)
+}
+
+/// Starts the node as the only validator, so that blocks are produced at every height and become
+/// final.
+pub fn start_single_validator() -> (Addr<ViewClientActor>, String) {
+    start_all_with_config(
+        NodeType::Validator,
+        vec!["test1"],
+        100,
+        false,
+        RpcLimitsConfig::default(),
+    )
+}
+
 pub fn start_all_with_validity_period(
     node_type: NodeType,
     transaction_validity_period: NumBlocks,
     enable_doomslug: bool,
 ) -> (Addr<ViewClientActor>, String) {
-    let (client_addr, view_client_addr) = setup_no_network_with_validity_period(
+    start_all_with_config(
+        node_type,
         vec!["test1", "test2"],
+        transaction_validity_period,
+        enable_doomslug,
+        RpcLimitsConfig::default(),
+    )
+}
+
+fn start_all_with_config(
+    node_type: NodeType,
+    validators: Vec<&'static str>,
+    transaction_validity_period: NumBlocks,
+    enable_doomslug: bool,
+    limits_config: RpcLimitsConfig,
+) -> (Addr<ViewClientActor>, String) {
+    let (client_addr, view_client_addr) = setup_no_network_with_validity_period(
+        validators,
         if let NodeType::Validator = node_type { "test1" } else { "other" },
         true,
         transaction_validity_period,
@@ -37,7 +72,7 @@ pub fn start_all_with_validity_period(
     let addr = format!("127.0.0.1:{}", open_port());
 
     start_http(
-        RpcConfig::new(&addr),
+        RpcConfig { limits_config, ..RpcConfig::new(&addr) },
         TEST_GENESIS_CONFIG.clone(),
         client_addr.clone(),
         view_client_addr.clone(),
@@ -0,0 +1,291 @@
+//! Rules which peers may connect to the node.
+//!
+//! Boot nodes and validators behind sentries need to limit the nodes they talk to. A rule matches
+//! a node key, or the IP of the connection by address or network in CIDR notation. Peers that
+//! match a denied rule are refused, and disconnected if they are connected when the rule is added.
+//! With `allow_only` inbound connections are only accepted from peers that match an allowed rule,
+//! outbound connections are still made to any peer that is not denied.
+//!
+//! The rules are read from the config and can be changed over RPC, changes are lost on restart.
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use near_crypto::PublicKey;
+use near_primitives::network::PeerId;
+use near_primitives::views::{PeerAccessChangeView, PeerAccessView};
+
+use crate::types::PeerType;
+
+/// IP network in CIDR notation, or a single address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let bytes = (prefix_len / 8) as usize;
+    let bits = prefix_len % 8;
+    network[..bytes] == ip[..bytes]
+        && (bits == 0 || (network[bytes] ^ ip[bytes]) >> (8 - bits) == 0)
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = parts
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|_| format!("Invalid IP address in {}", s))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match parts.next() {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or_else(|| format!("Invalid prefix length in {}", s))?,
+            None => max_prefix_len,
+        };
+        Ok(IpNetwork { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Node key or IP network a peer is matched by.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AccessRule {
+    Peer(PeerId),
+    Network(IpNetwork),
+}
+
+impl AccessRule {
+    /// Whether the rule matches the peer, whose node key is not known before the handshake.
+    pub fn matches(&self, peer_id: Option<&PeerId>, ip: IpAddr) -> bool {
+        match self {
+            AccessRule::Peer(rule) => peer_id == Some(rule),
+            AccessRule::Network(network) => network.contains(ip),
+        }
+    }
+}
+
+impl FromStr for AccessRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(network) = s.parse() {
+            return Ok(AccessRule::Network(network));
+        }
+        PublicKey::from_str(s)
+            .map(|public_key| AccessRule::Peer(public_key.into()))
+            .map_err(|_| format!("{} is neither a node key nor an IP network", s))
+    }
+}
+
+impl fmt::Display for AccessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessRule::Peer(peer_id) => write!(f, "{}", peer_id),
+            AccessRule::Network(network) => write!(f, "{}", network),
+        }
+    }
+}
+
+impl Serialize for AccessRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Allowed and denied peers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PeerAccess {
+    /// Only accept inbound connections from peers that match `allowed`.
+    pub allow_only: bool,
+    pub allowed: Vec<AccessRule>,
+    pub denied: Vec<AccessRule>,
+}
+
+impl PeerAccess {
+    fn is_denied(&self, peer_id: Option<&PeerId>, ip: IpAddr) -> bool {
+        self.denied.iter().any(|rule| rule.matches(peer_id, ip))
+    }
+
+    /// Whether the peer matches an allowed rule, by IP only if the node key is not known yet.
+    pub fn is_allowed(&self, peer_id: Option<&PeerId>, ip: IpAddr) -> bool {
+        self.allowed.iter().any(|rule| rule.matches(peer_id, ip))
+    }
+
+    /// Whether a connection from the IP may be accepted before the node key of the peer is known.
+    pub fn accepts_addr(&self, ip: IpAddr) -> bool {
+        !self.is_denied(None, ip)
+            && (!self.allow_only
+                || self.is_allowed(None, ip)
+                || self.allowed.iter().any(|rule| matches!(rule, AccessRule::Peer(_))))
+    }
+
+    /// Whether the peer may stay connected once its node key is known.
+    pub fn accepts(&self, peer_type: PeerType, peer_id: &PeerId, ip: IpAddr) -> bool {
+        !self.is_denied(Some(peer_id), ip)
+            && (peer_type == PeerType::Outbound
+                || !self.allow_only
+                || self.is_allowed(Some(peer_id), ip))
+    }
+
+    pub fn apply(&mut self, change: PeerAccessChange) {
+        if let Some(allow_only) = change.allow_only {
+            self.allow_only = allow_only;
+        }
+        self.allowed.retain(|rule| !change.remove_allowed.contains(rule));
+        self.denied.retain(|rule| !change.remove_denied.contains(rule));
+        for rule in change.allow {
+            if !self.allowed.contains(&rule) {
+                self.allowed.push(rule);
+            }
+        }
+        for rule in change.deny {
+            if !self.denied.contains(&rule) {
+                self.denied.push(rule);
+            }
+        }
+    }
+
+    pub fn view(&self) -> PeerAccessView {
+        PeerAccessView {
+            allow_only: self.allow_only,
+            allowed: self.allowed.iter().map(ToString::to_string).collect(),
+            denied: self.denied.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Change of the rules, see `PeerAccessChangeView`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerAccessChange {
+    pub allow_only: Option<bool>,
+    pub allow: Vec<AccessRule>,
+    pub remove_allowed: Vec<AccessRule>,
+    pub deny: Vec<AccessRule>,
+    pub remove_denied: Vec<AccessRule>,
+}
+
+impl TryFrom<PeerAccessChangeView> for PeerAccessChange {
+    type Error = String;
+
+    fn try_from(view: PeerAccessChangeView) -> Result<Self, Self::Error> {
+        let parse = |rules: Vec<String>| -> Result<Vec<AccessRule>, String> {
+            rules.iter().map(|rule| rule.parse()).collect()
+        };
+        Ok(PeerAccessChange {
+            allow_only: view.allow_only,
+            allow: parse(view.allow)?,
+            remove_allowed: parse(view.remove_allowed)?,
+            deny: parse(view.deny)?,
+            remove_denied: parse(view.remove_denied)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ip_network() {
+        let network: IpNetwork = "10.1.0.0/15".parse().unwrap();
+        assert!(network.contains(ip("10.0.255.1")));
+        assert!(network.contains(ip("10.1.0.1")));
+        assert!(!network.contains(ip("10.2.0.1")));
+        assert!(!network.contains(ip("::1")));
+        assert_eq!(network.to_string(), "10.1.0.0/15");
+
+        let single: IpNetwork = "2001:db8::1".parse().unwrap();
+        assert!(single.contains(ip("2001:db8::1")));
+        assert!(!single.contains(ip("2001:db8::2")));
+        assert!("0.0.0.0/0".parse::<IpNetwork>().unwrap().contains(ip("1.2.3.4")));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_peer_access() {
+        let peer_id: PeerId = SecretKey::from_seed(KeyType::ED25519, "test").public_key().into();
+        let rule = |s: &str| s.parse::<AccessRule>().unwrap();
+        assert_eq!(rule(&peer_id.to_string()), AccessRule::Peer(peer_id.clone()));
+        assert!("test".parse::<AccessRule>().is_err());
+
+        let mut access = PeerAccess::default();
+        assert!(access.accepts(PeerType::Inbound, &peer_id, ip("1.2.3.4")));
+
+        access.apply(PeerAccessChange {
+            allow_only: Some(true),
+            allow: vec![rule("10.0.0.0/8"), rule(&peer_id.to_string())],
+            deny: vec![rule("10.0.0.1")],
+            ..Default::default()
+        });
+        // Allowed by node key from anywhere, or by network.
+        assert!(access.accepts(PeerType::Inbound, &peer_id, ip("1.2.3.4")));
+        let other: PeerId = SecretKey::from_seed(KeyType::ED25519, "other").public_key().into();
+        assert!(access.accepts(PeerType::Inbound, &other, ip("10.0.0.2")));
+        assert!(!access.accepts(PeerType::Inbound, &other, ip("1.2.3.4")));
+        assert!(access.accepts(PeerType::Outbound, &other, ip("1.2.3.4")));
+        // Denied rules win.
+        assert!(!access.accepts(PeerType::Outbound, &peer_id, ip("10.0.0.1")));
+        assert!(!access.accepts_addr(ip("10.0.0.1")));
+        // The node key is only known after the handshake.
+        assert!(access.accepts_addr(ip("1.2.3.4")));
+
+        access.apply(PeerAccessChange {
+            remove_allowed: vec![rule(&peer_id.to_string())],
+            ..Default::default()
+        });
+        assert!(!access.accepts_addr(ip("1.2.3.4")));
+        assert_eq!(
+            access.view(),
+            PeerAccessView {
+                allow_only: true,
+                allowed: vec!["10.0.0.0/8".to_string()],
+                denied: vec!["10.0.0.1/32".to_string()],
+            }
+        );
+    }
+}
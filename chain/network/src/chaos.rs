@@ -0,0 +1,185 @@
+//! Faults injected into the messages sent to peers, to reproduce consensus edge cases in tests.
+//!
+//! Only built with the `adversarial` feature. Integration tests set the rules by sending
+//! `SetChaosRules` to the `PeerManagerActor`, and every connection checks the messages it sends
+//! against them. A rule matches messages by type, as in `PeerMessage::msg_variant`, and by the
+//! peer they are sent to. It can be limited to the next few messages it matches, so that a test
+//! can hit exactly the message it wants.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix::Message;
+
+use near_primitives::network::PeerId;
+
+use crate::types::PeerMessage;
+
+/// How often delayed messages are checked for.
+pub const CHAOS_TICK: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChaosAction {
+    /// Don't send the message.
+    Drop,
+    /// Send the message later.
+    Delay(Duration),
+    /// Send the message after the next one sent to the same peer.
+    Reorder,
+    /// Send the message this many more times.
+    Duplicate(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct ChaosRule {
+    /// Type of the messages, all types if `None`.
+    pub msg_type: Option<String>,
+    /// Peer the messages are sent to, all peers if `None`.
+    pub peer_id: Option<PeerId>,
+    pub action: ChaosAction,
+    /// Messages left for the rule to apply to, unlimited if `None`.
+    pub count: Option<usize>,
+}
+
+impl ChaosRule {
+    /// Rule applying the action to all messages.
+    pub fn new(action: ChaosAction) -> Self {
+        ChaosRule { msg_type: None, peer_id: None, action, count: None }
+    }
+
+    fn matches(&self, peer_id: &PeerId, msg: &PeerMessage) -> bool {
+        self.count != Some(0)
+            && self.msg_type.as_ref().map_or(true, |msg_type| msg_type == msg.msg_variant())
+            && self.peer_id.as_ref().map_or(true, |rule_peer_id| rule_peer_id == peer_id)
+    }
+}
+
+/// Rules shared by the peer manager and the connections.
+#[derive(Clone, Default)]
+pub struct Chaos {
+    rules: Arc<Mutex<Vec<ChaosRule>>>,
+}
+
+impl Chaos {
+    pub fn set_rules(&self, rules: Vec<ChaosRule>) {
+        *self.rules.lock().unwrap() = rules;
+    }
+
+    /// Action of the first rule that matches the message sent to the peer, which counts it.
+    fn action(&self, peer_id: &PeerId, msg: &PeerMessage) -> Option<ChaosAction> {
+        let mut rules = self.rules.lock().unwrap();
+        let rule = rules.iter_mut().find(|rule| rule.matches(peer_id, msg))?;
+        if let Some(count) = rule.count.as_mut() {
+            *count -= 1;
+        }
+        Some(rule.action.clone())
+    }
+}
+
+/// Messages of a connection held back by the rules.
+pub struct ConnectionChaos {
+    chaos: Chaos,
+    /// Messages to send after the next one.
+    reordered: Vec<PeerMessage>,
+    /// Messages to send at the given time.
+    delayed: Vec<(Instant, PeerMessage)>,
+}
+
+impl ConnectionChaos {
+    pub fn new(chaos: Chaos) -> Self {
+        ConnectionChaos { chaos, reordered: vec![], delayed: vec![] }
+    }
+
+    /// Messages to send now in place of the message sent to the peer.
+    pub fn apply(&mut self, peer_id: &PeerId, msg: PeerMessage, now: Instant) -> Vec<PeerMessage> {
+        let mut messages = match self.chaos.action(peer_id, &msg) {
+            None => vec![msg],
+            Some(ChaosAction::Drop) => return vec![],
+            Some(ChaosAction::Delay(delay)) => {
+                self.delayed.push((now + delay, msg));
+                return vec![];
+            }
+            Some(ChaosAction::Reorder) => {
+                self.reordered.push(msg);
+                return vec![];
+            }
+            Some(ChaosAction::Duplicate(copies)) => vec![msg; copies + 1],
+        };
+        messages.append(&mut self.reordered);
+        messages
+    }
+
+    /// Delayed messages to send by now.
+    pub fn due(&mut self, now: Instant) -> Vec<PeerMessage> {
+        let (due, delayed): (Vec<_>, Vec<_>) =
+            self.delayed.drain(..).partition(|(at, _)| *at <= now);
+        self.delayed = delayed;
+        due.into_iter().map(|(_, msg)| msg).collect()
+    }
+}
+
+/// Replaces the rules of the node.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetChaosRules(pub Vec<ChaosRule>);
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    fn peer_id(seed: &str) -> PeerId {
+        SecretKey::from_seed(KeyType::ED25519, seed).public_key().into()
+    }
+
+    #[test]
+    fn test_chaos() {
+        let chaos = Chaos::default();
+        let mut connection = ConnectionChaos::new(chaos.clone());
+        let (peer, other) = (peer_id("peer"), peer_id("other"));
+        let now = Instant::now();
+        let peers_request = PeerMessage::PeersRequest;
+        let disconnect = PeerMessage::Disconnect;
+
+        chaos.set_rules(vec![
+            ChaosRule {
+                msg_type: Some("PeersRequest".to_string()),
+                peer_id: Some(peer.clone()),
+                count: Some(1),
+                ..ChaosRule::new(ChaosAction::Drop)
+            },
+            ChaosRule {
+                msg_type: Some("PeersRequest".to_string()),
+                ..ChaosRule::new(ChaosAction::Duplicate(2))
+            },
+        ]);
+        assert_eq!(connection.apply(&peer, peers_request.clone(), now), vec![]);
+        // Only the next message was dropped.
+        assert_eq!(
+            connection.apply(&peer, peers_request.clone(), now),
+            vec![peers_request.clone(); 3]
+        );
+        assert_eq!(connection.apply(&other, disconnect.clone(), now), vec![disconnect.clone()]);
+
+        chaos.set_rules(vec![
+            ChaosRule {
+                msg_type: Some("PeersRequest".to_string()),
+                ..ChaosRule::new(ChaosAction::Reorder)
+            },
+            ChaosRule {
+                msg_type: Some("Disconnect".to_string()),
+                count: Some(1),
+                ..ChaosRule::new(ChaosAction::Delay(Duration::from_secs(1)))
+            },
+        ]);
+        assert_eq!(connection.apply(&peer, peers_request.clone(), now), vec![]);
+        assert_eq!(connection.apply(&peer, disconnect.clone(), now), vec![]);
+        assert_eq!(
+            connection.apply(&peer, disconnect.clone(), now),
+            vec![disconnect.clone(), peers_request.clone()]
+        );
+        assert_eq!(connection.due(now), vec![]);
+        assert_eq!(connection.due(now + Duration::from_secs(1)), vec![disconnect]);
+        assert_eq!(connection.due(now + Duration::from_secs(2)), vec![]);
+    }
+}
@@ -1,13 +1,33 @@
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Read};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use bytes::{Buf, BufMut, BytesMut};
+use strum::VariantNames;
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::types::{PeerMessage, ReasonForBan};
+use near_primitives::version::ProtocolVersion;
+
+use crate::metrics;
+use crate::types::{
+    MessageCapability, PeerMessage, ReasonForBan, RoutedMessageBody, VersionedMessage,
+    HANDSHAKE_PROTOCOL_VERSION,
+};
 
 const NETWORK_MESSAGE_MAX_SIZE: u32 = 512 << 20; // 512MB
 
+/// Messages larger than this are compressed, mostly state parts, chunk parts and blocks.
+const COMPRESSION_THRESHOLD: usize = 4 << 10; // 4KB
+
+/// Fast level, the messages are compressed again on every hop.
+const COMPRESSION_LEVEL: i32 = 1;
+
+/// Compressed messages may be this many times larger once decompressed, so that a small frame
+/// can't make the node allocate a lot. Messages that compress better are sent uncompressed.
+const MAX_COMPRESSION_RATIO: usize = 32;
+
+/// Decompressed size allowed for any compressed frame, however small.
+const MIN_DECOMPRESSED_LIMIT: usize = 64 << 10; // 64KB
+
 pub struct Codec {
     max_length: u32,
 }
@@ -66,12 +86,128 @@ impl Decoder for Codec {
     }
 }
 
-pub fn peer_message_to_bytes(peer_message: PeerMessage) -> Result<Vec<u8>, std::io::Error> {
-    peer_message.try_to_vec()
+/// Serializes the message in the encoding understood by a peer that talks the negotiated
+/// `protocol_version` and has the `capabilities` it sent in its handshake, wrapping it with its
+/// version and compressing it if it's large.
+pub fn peer_message_to_bytes(
+    peer_message: PeerMessage,
+    protocol_version: ProtocolVersion,
+    capabilities: u64,
+) -> Result<Vec<u8>, std::io::Error> {
+    let peer_message = match peer_message {
+        PeerMessage::Handshake(handshake) if protocol_version < HANDSHAKE_PROTOCOL_VERSION => {
+            PeerMessage::HandshakeV2(handshake.into())
+        }
+        peer_message => peer_message,
+    };
+    let mut bytes = peer_message.try_to_vec()?;
+    // The protocol version and the capabilities are only negotiated by the handshake.
+    if is_handshake(&peer_message) {
+        return Ok(bytes);
+    }
+    if MessageCapability::Versioned.is_in(capabilities) {
+        let kind = bytes[0];
+        let version = VersionedMessage::version(kind, protocol_version).expect("Known message");
+        bytes = PeerMessage::Versioned(VersionedMessage { kind, version, payload: bytes })
+            .try_to_vec()?;
+    }
+    if !MessageCapability::Compression.is_in(capabilities) || bytes.len() <= COMPRESSION_THRESHOLD {
+        return Ok(bytes);
+    }
+    compress(peer_message.msg_variant(), bytes)
+}
+
+fn is_handshake(peer_message: &PeerMessage) -> bool {
+    matches!(
+        peer_message,
+        PeerMessage::Handshake(_)
+            | PeerMessage::HandshakeV2(_)
+            | PeerMessage::HandshakeFailure(_, _)
+    )
+}
+
+/// Wraps the serialized message into `PeerMessage::Compressed`, unless that doesn't make it
+/// smaller.
+fn compress(msg_variant: &str, bytes: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
+    let compressed =
+        PeerMessage::Compressed(zstd::encode_all(&bytes[..], COMPRESSION_LEVEL)?).try_to_vec()?;
+    if compressed.len() >= bytes.len() || bytes.len() > max_decompressed_len(compressed.len()) {
+        return Ok(bytes);
+    }
+    if let Ok(histogram) = &*metrics::PEER_MESSAGE_COMPRESSION_RATIO {
+        histogram
+            .with_label_values(&[msg_variant])
+            .observe(compressed.len() as f64 / bytes.len() as f64);
+    }
+    if let Ok(counter) = &*metrics::PEER_MESSAGE_COMPRESSION_SAVED_BYTES {
+        counter.with_label_values(&[msg_variant]).inc_by((bytes.len() - compressed.len()) as i64);
+    }
+    Ok(compressed)
 }
 
-pub fn bytes_to_peer_message(bytes: &[u8]) -> Result<PeerMessage, std::io::Error> {
-    PeerMessage::try_from_slice(bytes)
+/// Deserializes a message of a peer that talks the negotiated `protocol_version`, unwrapping
+/// compressed and versioned messages. A versioned message that the node doesn't know, or whose
+/// version isn't the one of the negotiated protocol version, is returned as is.
+pub fn bytes_to_peer_message(
+    bytes: &[u8],
+    protocol_version: ProtocolVersion,
+) -> Result<PeerMessage, std::io::Error> {
+    match PeerMessage::try_from_slice(bytes)? {
+        PeerMessage::Compressed(compressed) => {
+            match PeerMessage::try_from_slice(&decompress(&compressed)?)? {
+                PeerMessage::Compressed(_) => {
+                    Err(Error::new(ErrorKind::InvalidData, "Compressed message is compressed"))
+                }
+                PeerMessage::Versioned(versioned) => unwrap_versioned(versioned, protocol_version),
+                peer_message => Ok(peer_message),
+            }
+        }
+        PeerMessage::Versioned(versioned) => unwrap_versioned(versioned, protocol_version),
+        peer_message => Ok(peer_message),
+    }
+}
+
+fn unwrap_versioned(
+    versioned: VersionedMessage,
+    protocol_version: ProtocolVersion,
+) -> Result<PeerMessage, std::io::Error> {
+    if VersionedMessage::version(versioned.kind, protocol_version) != Some(versioned.version) {
+        return Ok(PeerMessage::Versioned(versioned));
+    }
+    if versioned.payload.first() != Some(&versioned.kind) {
+        return Err(Error::new(ErrorKind::InvalidData, "Versioned message of another kind"));
+    }
+    match PeerMessage::try_from_slice(&versioned.payload)? {
+        PeerMessage::Compressed(_) | PeerMessage::Versioned(_) => {
+            Err(Error::new(ErrorKind::InvalidData, "Versioned message is wrapped"))
+        }
+        peer_message if is_handshake(&peer_message) => {
+            Err(Error::new(ErrorKind::InvalidData, "Versioned message is a handshake"))
+        }
+        peer_message => Ok(peer_message),
+    }
+}
+
+/// Longest message a compressed frame of the given length may decompress to.
+fn max_decompressed_len(compressed_len: usize) -> usize {
+    compressed_len
+        .saturating_mul(MAX_COMPRESSION_RATIO)
+        .max(MIN_DECOMPRESSED_LIMIT)
+        .min(NETWORK_MESSAGE_MAX_SIZE as usize)
+}
+
+/// Decompresses the message, refusing messages that would be larger than `max_decompressed_len`
+/// before they are fully decompressed.
+fn decompress(compressed: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let max_len = max_decompressed_len(compressed.len());
+    let mut bytes = Vec::new();
+    zstd::stream::read::Decoder::new(compressed)?
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() > max_len {
+        return Err(Error::new(ErrorKind::InvalidData, "Decompressed message is too long"));
+    }
+    Ok(bytes)
 }
 
 fn peer_id_type_field_len(enum_var: u8) -> Option<usize> {
@@ -84,11 +220,19 @@ fn peer_id_type_field_len(enum_var: u8) -> Option<usize> {
     }
 }
 
+lazy_static! {
+    static ref VERSIONED_TAG: u8 = PeerMessage::tag("Versioned").unwrap();
+    static ref ROUTED_TAG: u8 = PeerMessage::tag("Routed").unwrap();
+    static ref FORWARD_TX_TAG: u8 =
+        RoutedMessageBody::VARIANTS.iter().position(|name| *name == "ForwardTx").unwrap() as u8;
+}
+
 pub fn is_forward_tx(bytes: &[u8]) -> Option<bool> {
+    // A versioned message follows the kind, the version and the length of the payload
+    let bytes = if *bytes.get(0)? == *VERSIONED_TAG { bytes.get(1 + 1 + 1 + 4..)? } else { bytes };
     let peer_message_variant = *bytes.get(0)?;
 
-    // PeerMessage::Routed variant == 13
-    if peer_message_variant != 13 {
+    if peer_message_variant != *ROUTED_TAG {
         return Some(false);
     }
 
@@ -122,7 +266,7 @@ pub fn is_forward_tx(bytes: &[u8]) -> Option<bool> {
     let message_body_idx = ttl_idx + 1;
     let message_body_variant = *bytes.get(message_body_idx)?;
 
-    Some(message_body_variant == 1)
+    Some(message_body_variant == *FORWARD_TX_TAG)
 }
 
 #[cfg(test)]
@@ -139,8 +283,9 @@ mod test {
 
     use crate::routing::EdgeInfo;
     use crate::types::{
-        Handshake, HandshakeFailureReason, HandshakeV2, PeerChainInfo, PeerChainInfoV2,
-        PeerIdOrHash, PeerInfo, RoutedMessage, RoutedMessageBody, SyncData,
+        negotiate_protocol_version, Handshake, HandshakeFailureReason, HandshakeV2, PeerChainInfo,
+        PeerChainInfoV2, PeerIdOrHash, PeerInfo, RoutedMessage, RoutedMessageBody, SyncData,
+        CAPABILITIES_PROTOCOL_VERSION, SUPPORTED_CAPABILITIES,
     };
 
     use super::*;
@@ -148,9 +293,15 @@ mod test {
     fn test_codec(msg: PeerMessage) {
         let mut codec = Codec::new();
         let mut buffer = BytesMut::new();
-        codec.encode(peer_message_to_bytes(msg.clone()).unwrap(), &mut buffer).unwrap();
+        codec
+            .encode(
+                peer_message_to_bytes(msg.clone(), PROTOCOL_VERSION, SUPPORTED_CAPABILITIES)
+                    .unwrap(),
+                &mut buffer,
+            )
+            .unwrap();
         let decoded = codec.decode(&mut buffer).unwrap().unwrap().unwrap();
-        assert_eq!(bytes_to_peer_message(&decoded).unwrap(), msg);
+        assert_eq!(bytes_to_peer_message(&decoded, PROTOCOL_VERSION).unwrap(), msg);
     }
 
     #[derive(Debug, Copy, Clone)]
@@ -226,6 +377,10 @@ mod test {
         schemas.for_each(|s| {
             let msg = create_tx_forward(s);
             let bytes = msg.try_to_vec().unwrap();
+            assert_eq!(bytes[0], *ROUTED_TAG);
+            assert!(is_forward_tx(&bytes).unwrap());
+            let capabilities = MessageCapability::Versioned as u64;
+            let bytes = peer_message_to_bytes(msg, PROTOCOL_VERSION, capabilities).unwrap();
             assert!(is_forward_tx(&bytes).unwrap());
         })
     }
@@ -246,6 +401,11 @@ mod test {
                 archival: false,
             },
             edge_info: EdgeInfo::default(),
+            capabilities: if PROTOCOL_VERSION >= CAPABILITIES_PROTOCOL_VERSION {
+                SUPPORTED_CAPABILITIES
+            } else {
+                0
+            },
         };
         let msg = PeerMessage::Handshake(fake_handshake);
         test_codec(msg);
@@ -290,10 +450,16 @@ mod test {
 
         let mut codec = Codec::new();
         let mut buffer = BytesMut::new();
-        codec.encode(peer_message_to_bytes(msg.clone()).unwrap(), &mut buffer).unwrap();
+        codec
+            .encode(
+                peer_message_to_bytes(msg.clone(), PROTOCOL_VERSION, SUPPORTED_CAPABILITIES)
+                    .unwrap(),
+                &mut buffer,
+            )
+            .unwrap();
         let decoded = codec.decode(&mut buffer).unwrap().unwrap().unwrap();
 
-        let err = bytes_to_peer_message(&decoded).unwrap_err();
+        let err = bytes_to_peer_message(&decoded, PROTOCOL_VERSION).unwrap_err();
 
         assert_eq!(
             *err.get_ref()
@@ -307,6 +473,78 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_peer_message_handshake_old_protocol_version() {
+        let peer_info = PeerInfo::random();
+        let handshake = Handshake {
+            version: HANDSHAKE_PROTOCOL_VERSION - 1,
+            oldest_supported_version: OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION,
+            peer_id: peer_info.id.clone(),
+            target_peer_id: peer_info.id,
+            listen_port: None,
+            chain_info: PeerChainInfoV2 {
+                genesis_id: Default::default(),
+                height: 0,
+                tracked_shards: vec![],
+                archival: false,
+            },
+            edge_info: EdgeInfo::default(),
+            capabilities: 0,
+        };
+        let bytes = peer_message_to_bytes(
+            PeerMessage::Handshake(handshake.clone()),
+            HANDSHAKE_PROTOCOL_VERSION - 1,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            bytes_to_peer_message(&bytes, PROTOCOL_VERSION).unwrap(),
+            PeerMessage::HandshakeV2(handshake.into())
+        );
+    }
+
+    #[test]
+    fn test_peer_message_handshake_unsupported_version() {
+        let fake_handshake = Handshake {
+            version: PROTOCOL_VERSION + 2,
+            oldest_supported_version: PROTOCOL_VERSION + 1,
+            peer_id: PeerId::new(PublicKey::empty(KeyType::ED25519)),
+            target_peer_id: PeerId::new(PublicKey::empty(KeyType::ED25519)),
+            listen_port: None,
+            chain_info: PeerChainInfoV2 {
+                genesis_id: Default::default(),
+                height: 0,
+                tracked_shards: vec![],
+                archival: false,
+            },
+            edge_info: EdgeInfo::default(),
+            capabilities: 0,
+        };
+        let bytes = peer_message_to_bytes(
+            PeerMessage::Handshake(fake_handshake),
+            PROTOCOL_VERSION,
+            SUPPORTED_CAPABILITIES,
+        )
+        .unwrap();
+        let err = bytes_to_peer_message(&bytes, PROTOCOL_VERSION).unwrap_err();
+
+        assert_eq!(
+            *err.get_ref()
+                .map(|inner| inner.downcast_ref::<HandshakeFailureReason>())
+                .unwrap()
+                .unwrap(),
+            HandshakeFailureReason::ProtocolVersionMismatch {
+                version: PROTOCOL_VERSION + 2,
+                oldest_supported_version: PROTOCOL_VERSION + 1,
+            }
+        );
+        assert_eq!(negotiate_protocol_version(PROTOCOL_VERSION + 2, PROTOCOL_VERSION + 1), None);
+        assert_eq!(
+            negotiate_protocol_version(PROTOCOL_VERSION + 2, PROTOCOL_VERSION),
+            Some(PROTOCOL_VERSION)
+        );
+    }
+
     #[test]
     fn test_peer_message_info_gossip() {
         let peer_info1 = PeerInfo::random();
@@ -315,6 +553,88 @@ mod test {
         test_codec(msg);
     }
 
+    #[test]
+    fn test_peer_message_compressed() {
+        let peer_info = PeerInfo::random();
+        let msg = PeerMessage::PeersResponse(vec![peer_info; 200]);
+        let uncompressed = msg.try_to_vec().unwrap();
+        assert!(uncompressed.len() > COMPRESSION_THRESHOLD);
+        test_codec(msg.clone());
+
+        let capabilities = MessageCapability::Compression as u64;
+        let bytes = peer_message_to_bytes(msg.clone(), PROTOCOL_VERSION, capabilities).unwrap();
+        assert!(bytes.len() < uncompressed.len());
+        assert!(matches!(PeerMessage::try_from_slice(&bytes).unwrap(), PeerMessage::Compressed(_)));
+        assert_eq!(bytes_to_peer_message(&bytes, PROTOCOL_VERSION).unwrap(), msg);
+
+        // Older peers don't understand compressed messages.
+        let bytes = peer_message_to_bytes(msg, PROTOCOL_VERSION, 0).unwrap();
+        assert_eq!(bytes, uncompressed);
+
+        let nested = PeerMessage::Compressed(
+            zstd::encode_all(&PeerMessage::Compressed(vec![]).try_to_vec().unwrap()[..], 0)
+                .unwrap(),
+        );
+        assert!(bytes_to_peer_message(&nested.try_to_vec().unwrap(), PROTOCOL_VERSION).is_err());
+    }
+
+    #[test]
+    fn test_decompression_limit() {
+        // A megabyte of zeros compresses to a few dozen bytes, more than the limit allows.
+        let bytes = vec![0u8; 1 << 20];
+        let frame =
+            PeerMessage::Compressed(zstd::encode_all(&bytes[..], COMPRESSION_LEVEL).unwrap());
+        let err =
+            bytes_to_peer_message(&frame.try_to_vec().unwrap(), PROTOCOL_VERSION).unwrap_err();
+        assert_eq!(err.to_string(), "Decompressed message is too long");
+        // Such messages are sent uncompressed instead.
+        assert_eq!(compress("Test", bytes.clone()).unwrap(), bytes);
+
+        assert_eq!(max_decompressed_len(10), MIN_DECOMPRESSED_LIMIT);
+        assert_eq!(max_decompressed_len(1 << 20), MAX_COMPRESSION_RATIO << 20);
+        assert_eq!(max_decompressed_len(usize::MAX), NETWORK_MESSAGE_MAX_SIZE as usize);
+    }
+
+    #[test]
+    fn test_peer_message_versioned() {
+        let msg = PeerMessage::PeersResponse(vec![PeerInfo::random()]);
+        let bytes = peer_message_to_bytes(
+            msg.clone(),
+            PROTOCOL_VERSION,
+            MessageCapability::Versioned as u64,
+        )
+        .unwrap();
+        assert!(matches!(PeerMessage::try_from_slice(&bytes).unwrap(), PeerMessage::Versioned(_)));
+        assert_eq!(bytes[0], *VERSIONED_TAG);
+        assert_eq!(bytes_to_peer_message(&bytes, PROTOCOL_VERSION).unwrap(), msg);
+
+        // Messages or versions the node doesn't know are left to the caller to skip.
+        let payload = msg.try_to_vec().unwrap();
+        let unknown = PeerMessage::Versioned(VersionedMessage {
+            kind: payload[0],
+            version: VersionedMessage::version(payload[0], PROTOCOL_VERSION).unwrap() + 1,
+            payload: payload.clone(),
+        });
+        assert_eq!(
+            bytes_to_peer_message(&unknown.try_to_vec().unwrap(), PROTOCOL_VERSION).unwrap(),
+            unknown
+        );
+        let unknown =
+            PeerMessage::Versioned(VersionedMessage { kind: 255, version: 0, payload: vec![255] });
+        assert_eq!(
+            bytes_to_peer_message(&unknown.try_to_vec().unwrap(), PROTOCOL_VERSION).unwrap(),
+            unknown
+        );
+
+        // The payload has to be the message it claims to be.
+        let mislabeled = PeerMessage::Versioned(VersionedMessage {
+            kind: payload[0] + 1,
+            version: 0,
+            payload: payload.clone(),
+        });
+        assert!(bytes_to_peer_message(&mislabeled.try_to_vec().unwrap(), PROTOCOL_VERSION).is_err());
+    }
+
     #[test]
     fn test_peer_message_announce_account() {
         let sk = SecretKey::from_random(KeyType::ED25519);
@@ -0,0 +1,246 @@
+//! Limits on inbound connections against eclipse and connection exhaustion attacks.
+//!
+//! A node whose connections are all taken by an attacker only learns what the attacker wants it to.
+//! Addresses are cheap within a subnet but not across subnets, so only `max_inbound_per_subnet`
+//! inbound connections are accepted from a /24 IPv4 or /48 IPv6 subnet, counting the connections
+//! still in their handshake. The last `reserved_slots` of `max_num_peers` are left to outbound
+//! connections and to peers allowed by the `PeerAccess` rules. A peer that disconnects has to wait
+//! before it connects again, twice as long every time it disconnects soon after connecting, so that
+//! it can't make the node churn through handshakes. IPv6 peers wait by /64, the block usually
+//! assigned to a single host. Peers on private networks, such as the sentries of a validator, are
+//! neither limited by subnet nor made to wait.
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConnectionLimits {
+    /// Inbound connections accepted from a single subnet.
+    pub max_inbound_per_subnet: usize,
+    /// Connections that only outbound and allowed peers can take.
+    pub reserved_slots: u32,
+    /// Seconds an inbound peer has to wait to reconnect after it disconnects.
+    pub reconnect_backoff_secs: u64,
+    /// Longest wait to reconnect, also how long a peer has to stay connected to reset it.
+    pub max_reconnect_backoff_secs: u64,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_inbound_per_subnet: 4,
+            reserved_slots: 5,
+            reconnect_backoff_secs: 2,
+            max_reconnect_backoff_secs: 600,
+        }
+    }
+}
+
+/// The /24 IPv4 or /48 IPv6 subnet of an IP, the block usually assigned to a single operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subnet {
+    V4([u8; 3]),
+    V6([u16; 3]),
+}
+
+impl Subnet {
+    pub fn of(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => {
+                let octets = ip.octets();
+                Subnet::V4([octets[0], octets[1], octets[2]])
+            }
+            IpAddr::V6(ip) => {
+                let segments = ip.segments();
+                // IPv4 peers of a dual-stack listener.
+                if segments[..6] == [0, 0, 0, 0, 0, 0xffff] {
+                    let [a, b] = segments[6].to_be_bytes();
+                    let [c, _] = segments[7].to_be_bytes();
+                    Subnet::V4([a, b, c])
+                } else {
+                    Subnet::V6([segments[0], segments[1], segments[2]])
+                }
+            }
+        }
+    }
+}
+
+/// Inbound connections by subnet that aren't consolidated yet. Each is counted as long as its
+/// `PendingInboundGuard` lives, which the peer actor drops once the peer manager accepts it.
+#[derive(Clone, Default)]
+pub struct PendingInbound {
+    counts: Arc<Mutex<HashMap<Subnet, usize>>>,
+}
+
+impl PendingInbound {
+    pub fn count(&self, subnet: Subnet) -> usize {
+        self.counts.lock().unwrap().get(&subnet).copied().unwrap_or_default()
+    }
+
+    /// Counts a connection from the IP until the returned guard is dropped.
+    pub fn add(&self, ip: IpAddr) -> PendingInboundGuard {
+        let subnet = Subnet::of(ip);
+        *self.counts.lock().unwrap().entry(subnet).or_default() += 1;
+        PendingInboundGuard { pending: self.clone(), subnet }
+    }
+}
+
+pub struct PendingInboundGuard {
+    pending: PendingInbound,
+    subnet: Subnet,
+}
+
+impl Drop for PendingInboundGuard {
+    fn drop(&mut self) {
+        let mut counts = self.pending.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.subnet) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.subnet);
+            }
+        }
+    }
+}
+
+struct Disconnect {
+    at: Instant,
+    backoff: Duration,
+}
+
+/// Time that inbound peers have to wait to reconnect, by IP as the node key is only known after
+/// the handshake. IPv6 peers are tracked by /64, as a host can take any address of its /64.
+pub struct ReconnectBackoff {
+    initial: Duration,
+    max: Duration,
+    disconnects: HashMap<IpAddr, Disconnect>,
+}
+
+/// The IPv4 address, or the /64 network of the IPv6 address, the backoff is tracked by.
+fn backoff_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ip) => match ip.to_ipv4() {
+            // IPv4 peers of a dual-stack listener.
+            Some(ipv4) if ip.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => IpAddr::V4(ipv4),
+            _ => {
+                let segments = ip.segments();
+                IpAddr::V6(Ipv6Addr::new(
+                    segments[0],
+                    segments[1],
+                    segments[2],
+                    segments[3],
+                    0,
+                    0,
+                    0,
+                    0,
+                ))
+            }
+        },
+    }
+}
+
+impl ReconnectBackoff {
+    pub fn new(limits: &ConnectionLimits) -> Self {
+        ReconnectBackoff {
+            initial: Duration::from_secs(limits.reconnect_backoff_secs),
+            max: Duration::from_secs(limits.max_reconnect_backoff_secs),
+            disconnects: HashMap::new(),
+        }
+    }
+
+    /// Records that the inbound peer at the IP, connected since `connected`, disconnected.
+    pub fn disconnected(&mut self, ip: IpAddr, connected: Instant, now: Instant) {
+        let max = self.max;
+        let ip = backoff_key(ip);
+        self.disconnects.retain(|_, disconnect| now < disconnect.at + max);
+        let backoff = match self.disconnects.get(&ip) {
+            Some(disconnect) if now.saturating_duration_since(connected) < max => {
+                (disconnect.backoff * 2).min(max)
+            }
+            _ => self.initial,
+        };
+        self.disconnects.insert(ip, Disconnect { at: now, backoff });
+    }
+
+    /// Whether a peer at the IP may connect again.
+    pub fn allows(&self, ip: IpAddr, now: Instant) -> bool {
+        self.disconnects
+            .get(&backoff_key(ip))
+            .map_or(true, |disconnect| now >= disconnect.at + disconnect.backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_subnet() {
+        assert_eq!(Subnet::of(ip("10.1.2.3")), Subnet::of(ip("10.1.2.200")));
+        assert_ne!(Subnet::of(ip("10.1.2.3")), Subnet::of(ip("10.1.3.3")));
+        assert_eq!(Subnet::of(ip("::ffff:10.1.2.3")), Subnet::of(ip("10.1.2.4")));
+        assert_eq!(Subnet::of(ip("2001:db8:1::1")), Subnet::of(ip("2001:db8:1:ff::1")));
+        assert_ne!(Subnet::of(ip("2001:db8:1::1")), Subnet::of(ip("2001:db8:2::1")));
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let limits = ConnectionLimits {
+            reconnect_backoff_secs: 10,
+            max_reconnect_backoff_secs: 30,
+            ..Default::default()
+        };
+        let mut backoff = ReconnectBackoff::new(&limits);
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+        assert!(backoff.allows(ip("1.2.3.4"), start));
+
+        backoff.disconnected(ip("1.2.3.4"), start, secs(1));
+        assert!(!backoff.allows(ip("1.2.3.4"), secs(10)));
+        assert!(backoff.allows(ip("1.2.3.5"), secs(10)));
+        assert!(backoff.allows(ip("1.2.3.4"), secs(11)));
+
+        // Disconnecting soon after reconnecting doubles the backoff, up to the maximum.
+        backoff.disconnected(ip("1.2.3.4"), secs(11), secs(12));
+        assert!(!backoff.allows(ip("1.2.3.4"), secs(31)));
+        assert!(backoff.allows(ip("1.2.3.4"), secs(32)));
+        backoff.disconnected(ip("1.2.3.4"), secs(32), secs(33));
+        assert!(!backoff.allows(ip("1.2.3.4"), secs(62)));
+        assert!(backoff.allows(ip("1.2.3.4"), secs(63)));
+
+        // Staying connected resets it.
+        backoff.disconnected(ip("1.2.3.4"), secs(63), secs(93));
+        assert!(backoff.allows(ip("1.2.3.4"), secs(103)));
+
+        // Other addresses of the same /64 wait too, and IPv4 peers of a dual-stack listener wait
+        // with their IPv4 address.
+        backoff.disconnected(ip("2001:db8:1:2::1"), start, secs(1));
+        assert!(!backoff.allows(ip("2001:db8:1:2:ffff::1"), secs(10)));
+        assert!(backoff.allows(ip("2001:db8:1:3::1"), secs(10)));
+        backoff.disconnected(ip("::ffff:5.6.7.8"), start, secs(1));
+        assert!(!backoff.allows(ip("5.6.7.8"), secs(10)));
+    }
+
+    #[test]
+    fn test_pending_inbound() {
+        let pending = PendingInbound::default();
+        let subnet = Subnet::of(ip("1.2.3.4"));
+        let first = pending.add(ip("1.2.3.4"));
+        let second = pending.add(ip("1.2.3.5"));
+        let _other = pending.add(ip("1.2.4.4"));
+        assert_eq!(pending.count(subnet), 2);
+        drop(first);
+        assert_eq!(pending.count(subnet), 1);
+        drop(second);
+        assert_eq!(pending.count(subnet), 0);
+        assert_eq!(pending.count(Subnet::of(ip("1.2.4.4"))), 1);
+    }
+}
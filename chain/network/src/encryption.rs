@@ -0,0 +1,313 @@
+//! Encryption of TCP connections.
+//!
+//! Messages used to be sent in plaintext, so anyone on the path could read the transactions and
+//! approvals of a node or tamper with them. The dialing node now starts the connection with
+//! `PREAMBLE` and a handshake, in which both nodes send an ephemeral X25519 key and sign the
+//! transcript with their node key. The session keys mix the ephemeral keys with the node keys, so
+//! only the two nodes can derive them, and every frame after the handshake is encrypted and
+//! authenticated with the key of its direction. The dialing node checks that the peer signed with
+//! the key of the node it dialed, the accepting node checks that the handshake message of the peer
+//! comes from the node key the connection was encrypted with.
+//!
+//! Read as the length of a frame, `PREAMBLE` is over the maximum, so the accepting node tells
+//! encrypted connections from plaintext ones by their first four bytes. Nodes that accept encrypted
+//! connections list `PeerCapability::Encryption` in their peer record. With
+//! `EncryptionMode::Preferred` other peers are dialed in plaintext, with `EncryptionMode::Required`
+//! all connections are encrypted and plaintext ones are refused. QUIC connections have their own
+//! TLS encryption, bound to the node keys, see `transport`.
+use std::io;
+use std::time::Duration;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use near_crypto::{
+    derive_session_keys, sign_transcript, verify_transcript, EphemeralKey, HandshakeRole,
+    HandshakeTranscript, SecretKey, SessionCipher, Signature, X25519PublicKey,
+};
+use near_primitives::network::PeerId;
+
+/// First bytes of encrypted connections.
+pub const PREAMBLE: [u8; 4] = [0xff, b'n', b'e', b'1'];
+/// Longest handshake message accepted.
+const MAX_HANDSHAKE_MESSAGE_LENGTH: usize = 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionMode {
+    /// Connections are neither encrypted nor accepted encrypted.
+    Disabled,
+    /// Peers that advertise encryption are dialed over encrypted connections, both encrypted and
+    /// plaintext connections are accepted.
+    Preferred,
+    /// All TCP connections are encrypted and plaintext ones are refused. QUIC connections are
+    /// encrypted by TLS either way.
+    Required,
+}
+
+impl Default for EncryptionMode {
+    fn default() -> Self {
+        EncryptionMode::Preferred
+    }
+}
+
+impl EncryptionMode {
+    /// Whether to encrypt the connection to a peer, given whether it advertises encryption.
+    pub fn encrypt_outbound(self, supported: bool) -> bool {
+        match self {
+            EncryptionMode::Disabled => false,
+            EncryptionMode::Preferred => supported,
+            EncryptionMode::Required => true,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct Hello {
+    ephemeral: [u8; 32],
+    peer_id: PeerId,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct Reply {
+    hello: Hello,
+    signature: Signature,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct Finish {
+    signature: Signature,
+}
+
+/// Ciphers of an encrypted connection.
+pub struct Session {
+    /// Node key of the peer, verified in the handshake.
+    pub peer_id: PeerId,
+    pub send: SessionCipher,
+    pub recv: SessionCipher,
+}
+
+/// TCP connection after encryption was negotiated.
+pub struct TcpConnection {
+    pub stream: TcpStream,
+    /// Bytes of the first frame, read while looking for the preamble.
+    pub prefix: Vec<u8>,
+    /// Ciphers of the connection if it's encrypted.
+    pub session: Option<Session>,
+}
+
+impl TcpConnection {
+    pub fn plaintext(stream: TcpStream) -> Self {
+        TcpConnection { stream, prefix: vec![], session: None }
+    }
+
+    /// Encrypts the connection dialed to the node with the given key.
+    pub async fn connect(
+        mut stream: TcpStream,
+        secret_key: SecretKey,
+        peer_id: PeerId,
+        timeout: Duration,
+    ) -> io::Result<Self> {
+        let session = with_timeout(timeout, initiate(&mut stream, &secret_key, &peer_id)).await?;
+        Ok(TcpConnection { stream, prefix: vec![], session: Some(session) })
+    }
+
+    /// Encrypts the accepted connection if the peer starts with the preamble, or keeps it in
+    /// plaintext if the mode allows it.
+    pub async fn accept(
+        mut stream: TcpStream,
+        secret_key: SecretKey,
+        mode: EncryptionMode,
+        timeout: Duration,
+    ) -> io::Result<Self> {
+        if mode == EncryptionMode::Disabled {
+            return Ok(Self::plaintext(stream));
+        }
+        with_timeout(timeout, async move {
+            let mut prefix = vec![0u8; PREAMBLE.len()];
+            stream.read_exact(&mut prefix).await?;
+            if prefix[..] != PREAMBLE[..] {
+                if mode == EncryptionMode::Required {
+                    return Err(invalid_data("Plaintext connections are refused"));
+                }
+                return Ok(TcpConnection { stream, prefix, session: None });
+            }
+            let session = respond(&mut stream, &secret_key).await?;
+            Ok(TcpConnection { stream, prefix: vec![], session: Some(session) })
+        })
+        .await
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Encryption handshake timed out"))?
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+async fn write_message<S: AsyncWrite + Unpin>(stream: &mut S, message: &[u8]) -> io::Result<()> {
+    stream.write_all(&(message.len() as u16).to_le_bytes()).await?;
+    stream.write_all(message).await
+}
+
+async fn read_message<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await?;
+    let len = u16::from_le_bytes(len) as usize;
+    if len > MAX_HANDSHAKE_MESSAGE_LENGTH {
+        return Err(invalid_data("Handshake message is too long"));
+    }
+    let mut message = vec![0u8; len];
+    stream.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+/// Runs the handshake of the dialing node, which sends the preamble, its hello and, once the reply
+/// is verified, its signature of the transcript.
+async fn initiate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    secret_key: &SecretKey,
+    peer_id: &PeerId,
+) -> io::Result<Session> {
+    let ephemeral = EphemeralKey::from_random();
+    let hello =
+        Hello { ephemeral: ephemeral.public_key().0, peer_id: secret_key.public_key().into() }
+            .try_to_vec()?;
+    let mut transcript = HandshakeTranscript::new();
+    transcript.mix(&PREAMBLE);
+    transcript.mix(&hello);
+    stream.write_all(&PREAMBLE).await?;
+    write_message(stream, &hello).await?;
+
+    let reply = Reply::try_from_slice(&read_message(stream).await?)?;
+    if &reply.hello.peer_id != peer_id {
+        return Err(invalid_data("Peer has another node key"));
+    }
+    transcript.mix(&reply.hello.try_to_vec()?);
+    if !verify_transcript(&reply.signature, &peer_id.public_key(), &transcript) {
+        return Err(invalid_data("Invalid handshake signature"));
+    }
+    let finish = Finish { signature: sign_transcript(secret_key, &transcript) };
+    write_message(stream, &finish.try_to_vec()?).await?;
+    session(HandshakeRole::Initiator, secret_key, &ephemeral, reply.hello, &transcript)
+}
+
+/// Runs the handshake of the accepting node, after the preamble was read.
+async fn respond<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    secret_key: &SecretKey,
+) -> io::Result<Session> {
+    let peer_hello = read_message(stream).await?;
+    let mut transcript = HandshakeTranscript::new();
+    transcript.mix(&PREAMBLE);
+    transcript.mix(&peer_hello);
+    let peer_hello = Hello::try_from_slice(&peer_hello)?;
+
+    let ephemeral = EphemeralKey::from_random();
+    let hello =
+        Hello { ephemeral: ephemeral.public_key().0, peer_id: secret_key.public_key().into() };
+    transcript.mix(&hello.try_to_vec()?);
+    let reply = Reply { hello, signature: sign_transcript(secret_key, &transcript) };
+    write_message(stream, &reply.try_to_vec()?).await?;
+
+    let finish = Finish::try_from_slice(&read_message(stream).await?)?;
+    if !verify_transcript(&finish.signature, &peer_hello.peer_id.public_key(), &transcript) {
+        return Err(invalid_data("Invalid handshake signature"));
+    }
+    session(HandshakeRole::Responder, secret_key, &ephemeral, peer_hello, &transcript)
+}
+
+fn session(
+    role: HandshakeRole,
+    secret_key: &SecretKey,
+    ephemeral: &EphemeralKey,
+    peer_hello: Hello,
+    transcript: &HandshakeTranscript,
+) -> io::Result<Session> {
+    let keys = derive_session_keys(
+        role,
+        secret_key,
+        ephemeral,
+        &peer_hello.peer_id.public_key(),
+        &X25519PublicKey(peer_hello.ephemeral),
+        transcript,
+    )
+    .ok_or_else(|| invalid_data("Failed to derive session keys"))?;
+    let (send, recv) = keys.into_ciphers();
+    Ok(Session { peer_id: peer_hello.peer_id, send, recv })
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::KeyType;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Runs the handshake between two nodes over a local connection, the dialing node expecting
+    /// the accepting one to have `expected` as node key.
+    fn handshake(
+        initiator: &SecretKey,
+        responder: &SecretKey,
+        expected: &PeerId,
+    ) -> (io::Result<Session>, io::Result<Session>) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut outbound = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+            let (mut inbound, _) = listener.accept().await.unwrap();
+            // Each side closes its end once done, so that a failure unblocks the other one.
+            let dialing = async move { initiate(&mut outbound, initiator, expected).await };
+            let accepting = async move {
+                let mut preamble = [0u8; 4];
+                inbound.read_exact(&mut preamble).await?;
+                assert_eq!(preamble, PREAMBLE);
+                respond(&mut inbound, responder).await
+            };
+            futures::join!(dialing, accepting)
+        })
+    }
+
+    #[test]
+    fn test_encryption_handshake() {
+        let alice = SecretKey::from_seed(KeyType::ED25519, "alice");
+        let bob = SecretKey::from_seed(KeyType::ED25519, "bob");
+        let bob_id: PeerId = bob.public_key().into();
+        let (alice_session, bob_session) = handshake(&alice, &bob, &bob_id);
+        let (mut alice_session, mut bob_session) = (alice_session.unwrap(), bob_session.unwrap());
+        assert_eq!(alice_session.peer_id, bob_id);
+        assert_eq!(bob_session.peer_id, PeerId::from(alice.public_key()));
+
+        let message = alice_session.send.seal(b"block approval".to_vec());
+        assert_eq!(bob_session.recv.open(message), Some(b"block approval".to_vec()));
+        let message = bob_session.send.seal(b"chunk part".to_vec());
+        assert_eq!(alice_session.recv.open(message), Some(b"chunk part".to_vec()));
+    }
+
+    #[test]
+    fn test_encryption_handshake_wrong_peer() {
+        let alice = SecretKey::from_seed(KeyType::ED25519, "alice");
+        let bob = SecretKey::from_seed(KeyType::ED25519, "bob");
+        let carol = SecretKey::from_seed(KeyType::ED25519, "carol");
+        let (alice_session, bob_session) = handshake(&alice, &bob, &carol.public_key().into());
+        assert!(alice_session.is_err());
+        assert!(bob_session.is_err());
+    }
+
+    #[test]
+    fn test_encrypt_outbound() {
+        assert!(!EncryptionMode::Disabled.encrypt_outbound(true));
+        assert!(EncryptionMode::Preferred.encrypt_outbound(true));
+        assert!(!EncryptionMode::Preferred.encrypt_outbound(false));
+        assert!(EncryptionMode::Required.encrypt_outbound(false));
+    }
+}
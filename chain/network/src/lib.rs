@@ -1,22 +1,46 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub use access::{PeerAccess, PeerAccessChange};
+pub use connection_limits::ConnectionLimits;
+pub use encryption::EncryptionMode;
+pub use nat::PortMapping;
 pub use peer_manager::PeerManagerActor;
+pub use proxy::{ProxyConfig, ProxyKind};
+pub use reputation::{Misbehavior, ReputationConfig};
+pub use send_queue::SendQueueConfig;
+pub use throttle::BandwidthLimits;
+pub use transport::Transport;
 pub use types::{
-    FullPeerInfo, NetworkAdapter, NetworkClientMessages, NetworkClientResponses, NetworkConfig,
-    NetworkRecipient, NetworkRequests, NetworkResponses, PeerInfo,
+    BootNodeHost, FullPeerInfo, NetworkAdapter, NetworkClientMessages, NetworkClientResponses,
+    NetworkConfig, NetworkRecipient, NetworkRequests, NetworkResponses, PeerInfo,
 };
 
+mod access;
 mod cache;
+#[cfg(feature = "adversarial")]
+pub mod chaos;
 mod codec;
+mod connection_limits;
+mod encryption;
 pub mod metrics;
+mod nat;
 mod peer;
+mod peer_clock;
 mod peer_manager;
 pub mod peer_store;
+mod proxy;
 mod rate_counter;
 #[cfg(feature = "metric_recorder")]
 pub mod recorder;
+mod reputation;
+mod route_liveness;
 pub mod routing;
+mod send_queue;
+mod throttle;
+mod tier1;
+mod transport;
+mod tx_gossip;
 pub mod types;
 pub mod utils;
 
@@ -1,7 +1,9 @@
 use crate::types::{PeerMessage, RoutedMessageBody};
 use near_metrics::{
-    inc_counter_by_opt, inc_counter_opt, try_create_histogram, try_create_int_counter,
-    try_create_int_gauge, Histogram, IntCounter, IntGauge,
+    inc_counter_by_opt, inc_counter_opt, try_create_histogram, try_create_histogram_vec,
+    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge,
+    try_create_int_gauge_vec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec,
 };
 use std::collections::HashMap;
 use strum::VariantNames;
@@ -9,8 +11,16 @@ use strum::VariantNames;
 lazy_static! {
     pub static ref PEER_CONNECTIONS_TOTAL: near_metrics::Result<IntGauge> =
         try_create_int_gauge("near_peer_connections_total", "Number of connected peers");
+    pub static ref PEER_PROTOCOL_VERSION: near_metrics::Result<IntGaugeVec> =
+        try_create_int_gauge_vec(
+            "near_peer_protocol_version",
+            "Number of connected peers by the protocol version they advertised",
+            &["version"]
+        );
     pub static ref PEER_DATA_RECEIVED_BYTES: near_metrics::Result<IntCounter> =
         try_create_int_counter("near_peer_data_received_bytes", "Total data received from peers");
+    pub static ref PEER_DATA_SENT_BYTES: near_metrics::Result<IntCounter> =
+        try_create_int_counter("near_peer_data_sent_bytes", "Total data sent to peers");
     pub static ref PEER_MESSAGE_RECEIVED_TOTAL: near_metrics::Result<IntCounter> =
         try_create_int_counter(
             "near_peer_message_received_total",
@@ -31,6 +41,52 @@ lazy_static! {
             "near_peer_transaction_received_total",
             "Number of transactions received by peers"
         );
+    pub static ref PEER_MESSAGE_SEND_DROPPED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_message_send_dropped_total",
+            "Number of messages to peers dropped because the connection was too slow, by traffic class",
+            &["class"]
+        );
+    pub static ref PEER_INBOUND_REJECTED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_inbound_rejected_total",
+            "Number of inbound connections rejected before the handshake or by the connection limits, by reason",
+            &["reason"]
+        );
+    pub static ref PEER_TX_ANNOUNCEMENT_RECEIVED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_tx_announcement_received_total",
+            "Number of routed transactions announced by peers, by whether the transaction was known, requested or already waited for",
+            &["outcome"]
+        );
+    pub static ref PEER_DUPLICATE_FORWARD_TX_DROPPED: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_peer_duplicate_forward_tx_dropped_total",
+            "Number of routed transactions dropped because the peer already sent them"
+        );
+    pub static ref PEER_RTT: near_metrics::Result<Histogram> =
+        try_create_histogram(
+            "near_peer_rtt_seconds",
+            "Round trip time of the clock pings to peers"
+        );
+    pub static ref PEER_CLOCK_SKEW: near_metrics::Result<Histogram> =
+        try_create_histogram(
+            "near_peer_clock_skew_seconds",
+            "How far the clock of peers is off ours, ahead or behind, by clock ping"
+        );
+    pub static ref PEER_MESSAGE_COMPRESSION_RATIO: near_metrics::Result<HistogramVec> =
+        try_create_histogram_vec(
+            "near_peer_message_compression_ratio",
+            "Size of compressed messages sent to peers relative to their uncompressed size, by type",
+            &["type"],
+            Some(vec![0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0])
+        );
+    pub static ref PEER_MESSAGE_COMPRESSION_SAVED_BYTES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_message_compression_saved_bytes_total",
+            "Bytes saved by compressing messages sent to peers, by type",
+            &["type"]
+        );
 
     // Routing table metrics
     pub static ref ROUTING_TABLE_RECALCULATIONS: near_metrics::Result<IntCounter> =
@@ -61,6 +117,22 @@ lazy_static! {
             "near_peer_reachable",
             "Total peers such that there is a path potentially through other peers"
         );
+    pub static ref ROUTED_MESSAGE_DROPPED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_routed_message_dropped_total",
+            "Number of routed messages dropped, by reason",
+            &["reason"]
+        );
+    pub static ref ROUTES_PRUNED: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_routes_pruned_total",
+            "Number of routes removed because the next hop is not connected anymore"
+        );
+    pub static ref ROUTE_TARGETS_BACKED_OFF: near_metrics::Result<IntGauge> =
+        try_create_int_gauge(
+            "near_route_targets_backed_off",
+            "Number of peers messages are not routed to because they didn't answer"
+        );
     pub static ref DROP_MESSAGE_UNKNOWN_ACCOUNT: near_metrics::Result<IntCounter> =
         try_create_int_counter(
             "near_drop_message_unknown_account",
@@ -96,6 +168,12 @@ impl NetworkMetrics {
                 try_create_int_counter(counter_name.as_ref(), counter_name.as_ref()).ok(),
             );
 
+            let counter_name = NetworkMetrics::peer_message_bytes_tx(name.as_ref());
+            peer_messages.insert(
+                counter_name.clone(),
+                try_create_int_counter(counter_name.as_ref(), counter_name.as_ref()).ok(),
+            );
+
             let counter_name = NetworkMetrics::peer_message_dropped(name.as_ref());
             peer_messages.insert(
                 counter_name.clone(),
@@ -114,6 +192,10 @@ impl NetworkMetrics {
         format!("near_{}_bytes", message_name.to_lowercase())
     }
 
+    pub fn peer_message_bytes_tx(message_name: &str) -> String {
+        format!("near_{}_sent_bytes", message_name.to_lowercase())
+    }
+
     pub fn peer_message_dropped(message_name: &str) -> String {
         format!("near_{}_dropped", message_name.to_lowercase())
     }
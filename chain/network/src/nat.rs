@@ -0,0 +1,380 @@
+//! Making a node behind NAT dialable.
+//!
+//! Home validators usually sit behind a router that only lets outbound connections through. With
+//! `PortMapping` the node asks the router to forward the listening port to it, over UPnP or
+//! NAT-PMP, and learns the external IP of the router on the way. The mapping is leased, so it's
+//! renewed periodically for as long as the node runs.
+//!
+//! The external IP can also be learnt from the peers: every peer tells the nodes that connect to
+//! it the address it sees them connect from in `PeerMessage::ObservedAddr`. Only the peers this
+//! node connected to are asked, and only if enough of them agree, so that a few peers can't make
+//! the node advertise a wrong address.
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use actix::Recipient;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use near_primitives::network::PeerId;
+
+use crate::types::PortMapped;
+
+/// How long the router keeps a mapping.
+const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Period to renew the mapping at, well before it expires.
+const RENEW_PERIOD: Duration = Duration::from_secs(20 * 60);
+/// Time to wait for the router to answer.
+const GATEWAY_TIMEOUT: Duration = Duration::from_secs(2);
+/// Attempts of NAT-PMP requests, which go over UDP.
+const NAT_PMP_ATTEMPTS: usize = 3;
+const NAT_PMP_PORT: u16 = 5351;
+/// Number of peers that must observe the same IP for it to be taken as the external one.
+const MIN_OBSERVATIONS: usize = 3;
+/// Description of the mapping shown by the router.
+const MAPPING_DESCRIPTION: &str = "near";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PortMapping {
+    /// The port is forwarded manually, if at all.
+    Disabled,
+    Upnp,
+    NatPmp,
+    /// UPnP, or NAT-PMP if there is no UPnP gateway.
+    Auto,
+}
+
+impl Default for PortMapping {
+    fn default() -> Self {
+        PortMapping::Disabled
+    }
+}
+
+/// Transport protocol of a mapping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn nat_pmp_opcode(self) -> u8 {
+        match self {
+            Protocol::Udp => 1,
+            Protocol::Tcp => 2,
+        }
+    }
+
+    fn upnp(self) -> igd::PortMappingProtocol {
+        match self {
+            Protocol::Tcp => igd::PortMappingProtocol::TCP,
+            Protocol::Udp => igd::PortMappingProtocol::UDP,
+        }
+    }
+}
+
+/// Whether other nodes on the internet can connect to the IP.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10.
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local addresses, fc00::/7.
+                || segments[0] & 0xfe00 == 0xfc00
+                // Link-local addresses, fe80::/10.
+                || segments[0] & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// IPs of this node observed by the peers it connected to.
+#[derive(Default)]
+pub struct ObservedAddrs {
+    by_peer: HashMap<PeerId, IpAddr>,
+}
+
+impl ObservedAddrs {
+    pub fn insert(&mut self, peer_id: PeerId, ip: IpAddr) {
+        if is_public(ip) {
+            self.by_peer.insert(peer_id, ip);
+        }
+    }
+
+    pub fn remove(&mut self, peer_id: &PeerId) {
+        self.by_peer.remove(peer_id);
+    }
+
+    /// The IP observed by most peers, if at least `MIN_OBSERVATIONS` of them agree.
+    pub fn external_ip(&self) -> Option<IpAddr> {
+        self.most_observed(|_| true)
+    }
+
+    /// The IPv4 or IPv6 address observed by most peers, if at least `MIN_OBSERVATIONS` of them
+    /// agree.
+    pub fn external_ip_of_version(&self, ipv4: bool) -> Option<IpAddr> {
+        self.most_observed(|ip| ip.is_ipv4() == ipv4)
+    }
+
+    fn most_observed(&self, filter: impl Fn(&IpAddr) -> bool) -> Option<IpAddr> {
+        let mut counts = HashMap::new();
+        for ip in self.by_peer.values().filter(|ip| filter(ip)) {
+            *counts.entry(*ip).or_insert(0) += 1;
+        }
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_OBSERVATIONS)
+            .max_by_key(|(ip, count)| (*count, *ip))
+            .map(|(ip, _)| ip)
+    }
+}
+
+/// Maps the port on the router for as long as the peer manager runs, sending it the external
+/// address after every attempt.
+pub fn spawn_port_mapping(
+    mode: PortMapping,
+    port: u16,
+    protocols: Vec<Protocol>,
+    recipient: Recipient<PortMapped>,
+) {
+    let result = thread::Builder::new().name("port-mapping".to_string()).spawn(move || loop {
+        let external_addr = match map_port(mode, port, &protocols) {
+            Ok(external_addr) => {
+                debug!(target: "network", "Mapped port {} to {}", port, external_addr);
+                Some(external_addr)
+            }
+            Err(err) => {
+                warn!(target: "network", "Failed to map port {}: {}", port, err);
+                None
+            }
+        };
+        if recipient.do_send(PortMapped(external_addr)).is_err() {
+            break;
+        }
+        thread::sleep(RENEW_PERIOD);
+    });
+    match result {
+        Ok(_) => info!(target: "network", "Mapping port {} over {:?}", port, mode),
+        Err(err) => warn!(target: "network", "Failed to start port mapping: {}", err),
+    }
+}
+
+fn map_port(mode: PortMapping, port: u16, protocols: &[Protocol]) -> Result<SocketAddr, String> {
+    match mode {
+        PortMapping::Disabled => Err("Port mapping is disabled".to_string()),
+        PortMapping::Upnp => map_upnp(port, protocols),
+        PortMapping::NatPmp => map_nat_pmp(port, protocols),
+        PortMapping::Auto => map_upnp(port, protocols).or_else(|upnp_err| {
+            map_nat_pmp(port, protocols)
+                .map_err(|nat_pmp_err| format!("UPnP: {}, NAT-PMP: {}", upnp_err, nat_pmp_err))
+        }),
+    }
+}
+
+/// Local IP of the interface that packets to `addr` leave through.
+fn local_ip_towards(addr: SocketAddr) -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|err| err.to_string())?;
+    socket.connect(addr).map_err(|err| err.to_string())?;
+    Ok(socket.local_addr().map_err(|err| err.to_string())?.ip())
+}
+
+fn map_upnp(port: u16, protocols: &[Protocol]) -> Result<SocketAddr, String> {
+    let options = igd::SearchOptions { timeout: Some(GATEWAY_TIMEOUT), ..Default::default() };
+    let gateway = igd::search_gateway(options).map_err(|err| err.to_string())?;
+    let local_ip = match local_ip_towards(SocketAddr::V4(gateway.addr))? {
+        IpAddr::V4(local_ip) => local_ip,
+        IpAddr::V6(_) => return Err("UPnP gateway is not reachable over IPv4".to_string()),
+    };
+    for protocol in protocols {
+        gateway
+            .add_port(
+                protocol.upnp(),
+                port,
+                SocketAddrV4::new(local_ip, port),
+                LEASE_DURATION.as_secs() as u32,
+                MAPPING_DESCRIPTION,
+            )
+            .map_err(|err| err.to_string())?;
+    }
+    let external_ip = gateway.get_external_ip().map_err(|err| err.to_string())?;
+    Ok(SocketAddr::new(external_ip.into(), port))
+}
+
+/// Maps the port over NAT-PMP, see RFC 6886. The port mapped for the first protocol is returned.
+fn map_nat_pmp(port: u16, protocols: &[Protocol]) -> Result<SocketAddr, String> {
+    let gateway = fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|routes| parse_default_gateway(&routes))
+        .ok_or_else(|| "Default gateway not found".to_string())?;
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|err| err.to_string())?;
+    socket.connect((gateway, NAT_PMP_PORT)).map_err(|err| err.to_string())?;
+    socket.set_read_timeout(Some(GATEWAY_TIMEOUT)).map_err(|err| err.to_string())?;
+
+    let response = nat_pmp_request(&socket, &[0, 0])?;
+    let external_ip = parse_external_ip_response(&response)?;
+    let mut external_port = None;
+    for protocol in protocols {
+        let request = mapping_request(*protocol, port, external_port.unwrap_or(port));
+        let response = nat_pmp_request(&socket, &request)?;
+        let mapped_port = parse_mapping_response(&response, *protocol)?;
+        external_port.get_or_insert(mapped_port);
+    }
+    Ok(SocketAddr::new(external_ip.into(), external_port.unwrap_or(port)))
+}
+
+fn nat_pmp_request(socket: &UdpSocket, request: &[u8]) -> Result<Vec<u8>, String> {
+    let mut buf = [0; 16];
+    for _ in 0..NAT_PMP_ATTEMPTS {
+        socket.send(request).map_err(|err| err.to_string())?;
+        if let Ok(len) = socket.recv(&mut buf) {
+            return Ok(buf[..len].to_vec());
+        }
+    }
+    Err("NAT-PMP gateway didn't respond".to_string())
+}
+
+fn mapping_request(protocol: Protocol, internal_port: u16, external_port: u16) -> Vec<u8> {
+    let mut request = vec![0, protocol.nat_pmp_opcode(), 0, 0];
+    request.extend_from_slice(&internal_port.to_be_bytes());
+    request.extend_from_slice(&external_port.to_be_bytes());
+    request.extend_from_slice(&(LEASE_DURATION.as_secs() as u32).to_be_bytes());
+    request
+}
+
+/// Checks the header of the response to a request with `opcode`, returning the rest of it.
+fn nat_pmp_response_body(response: &[u8], opcode: u8) -> Result<&[u8], String> {
+    if response.len() < 8 || response[0] != 0 || response[1] != 128 + opcode {
+        return Err("Invalid NAT-PMP response".to_string());
+    }
+    match u16::from_be_bytes([response[2], response[3]]) {
+        // Bytes 4..8 are the time since the gateway started.
+        0 => Ok(&response[8..]),
+        code => Err(format!("NAT-PMP request failed with code {}", code)),
+    }
+}
+
+fn parse_external_ip_response(response: &[u8]) -> Result<Ipv4Addr, String> {
+    let body = nat_pmp_response_body(response, 0)?;
+    if body.len() < 4 {
+        return Err("Invalid NAT-PMP response".to_string());
+    }
+    Ok(Ipv4Addr::new(body[0], body[1], body[2], body[3]))
+}
+
+/// Returns the external port of the mapping.
+fn parse_mapping_response(response: &[u8], protocol: Protocol) -> Result<u16, String> {
+    let body = nat_pmp_response_body(response, protocol.nat_pmp_opcode())?;
+    if body.len() < 8 {
+        return Err("Invalid NAT-PMP response".to_string());
+    }
+    Ok(u16::from_be_bytes([body[2], body[3]]))
+}
+
+/// Gateway of the default route in the format of `/proc/net/route`.
+fn parse_default_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        // The address as a number in the byte order of the host.
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        assert!(is_public("8.8.8.8".parse().unwrap()));
+        assert!(is_public("2001:4860::8888".parse().unwrap()));
+        for ip in &["10.1.2.3", "192.168.0.1", "127.0.0.1", "100.64.0.1", "fd00::1", "fe80::1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_observed_addrs() {
+        let peer_id = |seed: &str| -> PeerId {
+            SecretKey::from_seed(KeyType::ED25519, seed).public_key().into()
+        };
+        let ip = |s: &str| -> IpAddr { s.parse().unwrap() };
+        let mut observed = ObservedAddrs::default();
+        observed.insert(peer_id("a"), ip("1.1.1.1"));
+        observed.insert(peer_id("b"), ip("1.1.1.1"));
+        observed.insert(peer_id("c"), ip("192.168.0.2"));
+        assert_eq!(observed.external_ip(), None);
+
+        observed.insert(peer_id("d"), ip("1.1.1.1"));
+        assert_eq!(observed.external_ip(), Some(ip("1.1.1.1")));
+
+        // The latest observation of a peer counts.
+        observed.insert(peer_id("a"), ip("2.2.2.2"));
+        assert_eq!(observed.external_ip(), None);
+        observed.insert(peer_id("a"), ip("1.1.1.1"));
+        observed.remove(&peer_id("b"));
+        assert_eq!(observed.external_ip(), None);
+
+        // IPv6 observations don't outvote the IPv4 address.
+        observed.insert(peer_id("b"), ip("1.1.1.1"));
+        for seed in &["e", "f", "g", "h"] {
+            observed.insert(peer_id(seed), ip("2001:4860::1"));
+        }
+        assert_eq!(observed.external_ip(), Some(ip("2001:4860::1")));
+        assert_eq!(observed.external_ip_of_version(true), Some(ip("1.1.1.1")));
+        assert_eq!(observed.external_ip_of_version(false), Some(ip("2001:4860::1")));
+    }
+
+    #[test]
+    fn test_nat_pmp_messages() {
+        assert_eq!(
+            mapping_request(Protocol::Tcp, 24567, 24568),
+            vec![0, 2, 0, 0, 0x5f, 0xf7, 0x5f, 0xf8, 0, 0, 0x0e, 0x10]
+        );
+        let response = [0, 128, 0, 0, 0, 0, 1, 0, 203, 0, 113, 7];
+        assert_eq!(parse_external_ip_response(&response), Ok(Ipv4Addr::new(203, 0, 113, 7)));
+        let response = [0, 130, 0, 0, 0, 0, 1, 0, 0x5f, 0xf7, 0x5f, 0xf9, 0, 0, 0x0e, 0x10];
+        assert_eq!(parse_mapping_response(&response, Protocol::Tcp), Ok(24569));
+        assert!(parse_mapping_response(&response, Protocol::Udp).is_err());
+        // Not authorized.
+        let response = [0, 128, 0, 2, 0, 0, 1, 0, 0, 0, 0, 0];
+        assert!(parse_external_ip_response(&response).is_err());
+    }
+
+    #[test]
+    fn test_parse_default_gateway() {
+        let routes =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                      eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                      eth0\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+        let expected = if cfg!(target_endian = "little") {
+            Ipv4Addr::new(192, 168, 0, 1)
+        } else {
+            Ipv4Addr::new(1, 0, 168, 192)
+        };
+        assert_eq!(parse_default_gateway(routes), Some(expected));
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+}
@@ -1,5 +1,5 @@
 use std::cmp::max;
-use std::io;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
@@ -7,11 +7,11 @@ use std::sync::{
 };
 use std::time::{Duration, Instant};
 
-use actix::io::{FramedWrite, WriteHandler};
 use actix::{
     Actor, ActorContext, ActorFuture, Addr, Arbiter, AsyncContext, Context, ContextFutureSpawner,
     Handler, Recipient, Running, StreamHandler, WrapFuture,
 };
+use chrono::Utc;
 use tracing::{debug, error, info, trace, warn};
 
 use near_metrics;
@@ -19,23 +19,32 @@ use near_primitives::block::GenesisId;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::unwrap_option_or_return;
-use near_primitives::utils::DisplayOption;
+use near_primitives::utils::{to_timestamp, DisplayOption};
 use near_primitives::version::{
     ProtocolVersion, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 
-use crate::codec::{self, bytes_to_peer_message, peer_message_to_bytes, Codec};
+#[cfg(feature = "adversarial")]
+use crate::chaos::{Chaos, ConnectionChaos, CHAOS_TICK};
+use crate::codec::{self, bytes_to_peer_message, peer_message_to_bytes};
+use crate::connection_limits::PendingInboundGuard;
+use crate::peer_clock::{PeerClock, CLOCK_PING_INTERVAL, MAX_CLOCK_SKEW_MILLIS};
 use crate::rate_counter::RateCounter;
 #[cfg(feature = "metric_recorder")]
 use crate::recorder::{PeerMessageMetadata, Status};
+use crate::reputation::Misbehavior;
 use crate::routing::{Edge, EdgeInfo};
+use crate::send_queue::QueuedMessage;
+use crate::transport::{PeerWriter, TrafficClass};
+use crate::tx_gossip::{Announced, RecentTransactions, TxGossip, MAX_TRANSACTIONS_REQUEST};
 use crate::types::{
-    Ban, Consolidate, ConsolidateResponse, Handshake, HandshakeFailureReason, HandshakeV2,
-    NetworkClientMessages, NetworkClientResponses, NetworkRequests, NetworkViewClientMessages,
-    NetworkViewClientResponses, PeerChainInfo, PeerChainInfoV2, PeerInfo, PeerManagerRequest,
-    PeerMessage, PeerRequest, PeerResponse, PeerStatsResult, PeerStatus, PeerType, PeersRequest,
-    PeersResponse, QueryPeerStats, ReasonForBan, RoutedMessage, RoutedMessageBody,
-    RoutedMessageFrom, SendMessage, StateResponseInfo, Unregister,
+    negotiate_protocol_version, Ban, Consolidate, ConsolidateResponse, Handshake,
+    HandshakeFailureReason, MessageCapability, NetworkClientMessages, NetworkClientResponses,
+    NetworkRequests, NetworkViewClientMessages, NetworkViewClientResponses, ObservedAddr,
+    PeerChainInfoV2, PeerInfo, PeerList, PeerManagerRequest, PeerMessage, PeerRequest,
+    PeerResponse, PeerStatsResult, PeerStatus, PeerType, PeersRequest, PeersResponse,
+    QueryPeerStats, ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom, SendMessage,
+    StateResponseInfo, TimePong, Unregister, SUPPORTED_CAPABILITIES,
     UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
 };
 use crate::PeerManagerActor;
@@ -45,8 +54,6 @@ use delay_detector::DelayDetector;
 use metrics::NetworkMetrics;
 use near_primitives::sharding::PartialEncodedChunk;
 
-type WriteHalf = tokio::io::WriteHalf<tokio::net::TcpStream>;
-
 /// Maximum number of requests and responses to track.
 const MAX_TRACK_SIZE: usize = 30;
 /// Maximum number of messages per minute from single peer.
@@ -58,6 +65,10 @@ const MAX_PEER_MSG_PER_MIN: u64 = std::u64::MAX;
 /// dispatching transactions when we should be focusing on consensus-related messages.
 const MAX_TXNS_PER_BLOCK_MESSAGE: usize = 1000;
 
+/// Invalid transactions a peer may forward per minute before it is reported for spamming. Honest
+/// peers forward some too, e.g. transactions that expired or whose nonce got used meanwhile.
+const MAX_INVALID_TXS_PER_MIN: u64 = 60;
+
 /// Internal structure to keep a circular queue within a tracker with unique hashes.
 struct CircularUniqueQueue {
     v: Vec<CryptoHash>,
@@ -92,10 +103,9 @@ impl CircularUniqueQueue {
 }
 
 /// Keeps track of requests and received hashes of transactions and blocks.
-/// Also keeps track of number of bytes sent and received from this peer to prevent abuse.
+/// Also keeps track of number of bytes received from this peer to prevent abuse, the sent ones are
+/// counted by the `PeerWriter`.
 pub struct Tracker {
-    /// Bytes we've sent.
-    sent_bytes: RateCounter,
     /// Bytes we've received.
     received_bytes: RateCounter,
     /// Sent requests.
@@ -107,7 +117,6 @@ pub struct Tracker {
 impl Default for Tracker {
     fn default() -> Self {
         Tracker {
-            sent_bytes: RateCounter::new(),
             received_bytes: RateCounter::new(),
             requested: CircularUniqueQueue::new(MAX_TRACK_SIZE),
             received: CircularUniqueQueue::new(MAX_TRACK_SIZE),
@@ -120,10 +129,6 @@ impl Tracker {
         self.received_bytes.increment(size);
     }
 
-    fn increment_sent(&mut self, size: u64) {
-        self.sent_bytes.increment(size);
-    }
-
     fn has_received(&self, hash: &CryptoHash) -> bool {
         self.received.contains(hash)
     }
@@ -154,8 +159,17 @@ pub struct Peer {
     pub peer_status: PeerStatus,
     /// Protocol version to communicate with this peer.
     pub protocol_version: ProtocolVersion,
-    /// Framed wrapper to send messages through the TCP connection.
-    framed: FramedWrite<WriteHalf, Codec>,
+    /// Protocol version advertised in the handshake of the peer, none until it's received.
+    peer_protocol_version: Option<ProtocolVersion>,
+    /// `MessageCapability` bits both this node and the peer have, none until the handshake of
+    /// the peer is received.
+    capabilities: u64,
+    /// Writer to send messages through the connection.
+    writer: PeerWriter,
+    /// Node key the connection is encrypted with, which the handshake must be from.
+    authenticated_peer_id: Option<PeerId>,
+    /// Counts the inbound connection against the limit of its subnet until it's consolidated.
+    pending_inbound: Option<PendingInboundGuard>,
     /// Handshake timeout.
     handshake_timeout: Duration,
     /// Peer manager recipient to break the dependency loop.
@@ -174,12 +188,23 @@ pub struct Peer {
     edge_info: Option<EdgeInfo>,
     /// Last time an update of received message was sent to PeerManager
     last_time_received_message_update: Instant,
+    /// Last time PeerManager was told that the peer sent data for the client.
+    last_time_useful_data_update: Option<Instant>,
     /// Dynamic Prometheus metrics
     network_metrics: NetworkMetrics,
     /// How many transactions we have received since the last block message
     txns_since_last_block: Arc<AtomicUsize>,
     /// How many peer actors are created
     peer_counter: Arc<AtomicUsize>,
+    /// Routed transactions sent to and received from the peer.
+    tx_gossip: TxGossip,
+    /// Round trip time and clock skew of the peer.
+    clock: PeerClock,
+    /// Invalid transactions received from the peer over the last minute.
+    invalid_txs: RateCounter,
+    /// Faults injected into the messages sent to the peer.
+    #[cfg(feature = "adversarial")]
+    chaos: ConnectionChaos,
 }
 
 impl Peer {
@@ -188,7 +213,9 @@ impl Peer {
         peer_addr: SocketAddr,
         peer_info: Option<PeerInfo>,
         peer_type: PeerType,
-        framed: FramedWrite<WriteHalf, Codec>,
+        writer: PeerWriter,
+        authenticated_peer_id: Option<PeerId>,
+        pending_inbound: Option<PendingInboundGuard>,
         handshake_timeout: Duration,
         peer_manager_addr: Addr<PeerManagerActor>,
         client_addr: Recipient<NetworkClientMessages>,
@@ -197,6 +224,8 @@ impl Peer {
         network_metrics: NetworkMetrics,
         txns_since_last_block: Arc<AtomicUsize>,
         peer_counter: Arc<AtomicUsize>,
+        recent_txs: Arc<RecentTransactions>,
+        #[cfg(feature = "adversarial")] chaos: Chaos,
     ) -> Self {
         Peer {
             node_info,
@@ -205,7 +234,11 @@ impl Peer {
             peer_type,
             peer_status: PeerStatus::Connecting,
             protocol_version: PROTOCOL_VERSION,
-            framed,
+            peer_protocol_version: None,
+            capabilities: 0,
+            writer,
+            authenticated_peer_id,
+            pending_inbound,
             handshake_timeout,
             peer_manager_addr,
             client_addr,
@@ -215,22 +248,45 @@ impl Peer {
             chain_info: Default::default(),
             edge_info,
             last_time_received_message_update: Instant::now(),
+            last_time_useful_data_update: None,
             network_metrics,
             txns_since_last_block,
             peer_counter,
+            tx_gossip: TxGossip::new(recent_txs),
+            clock: PeerClock::default(),
+            invalid_txs: RateCounter::new(),
+            #[cfg(feature = "adversarial")]
+            chaos: ConnectionChaos::new(chaos),
         }
     }
 
+    /// Whether the message of the capability may be sent to the peer.
+    fn has_capability(&self, capability: MessageCapability) -> bool {
+        capability.is_in(self.capabilities)
+    }
+
     /// Whether the peer is considered abusive due to sending too many messages.
     // I am allowing this for now because I assume `MAX_PEER_MSG_PER_MIN` will
     // some day be less than `u64::MAX`.
     #[allow(clippy::absurd_extreme_comparisons)]
     fn is_abusive(&self) -> bool {
         self.tracker.received_bytes.count_per_min() > MAX_PEER_MSG_PER_MIN
-            || self.tracker.sent_bytes.count_per_min() > MAX_PEER_MSG_PER_MIN
+            || self.writer.sent_per_min().1 > MAX_PEER_MSG_PER_MIN
     }
 
     fn send_message(&mut self, msg: PeerMessage) {
+        let msg = match msg {
+            PeerMessage::Routed(routed_message)
+                if matches!(routed_message.body, RoutedMessageBody::ForwardTx(_)) =>
+            {
+                let announce = self.has_capability(MessageCapability::TxAnnouncement);
+                match self.tx_gossip.outgoing(routed_message, announce) {
+                    Some(msg) => msg,
+                    None => return,
+                }
+            }
+            msg => msg,
+        };
         // Skip sending block and headers if we received it or header from this peer.
         // Record block requests in tracker.
         match &msg {
@@ -238,6 +294,20 @@ impl Peer {
             PeerMessage::BlockRequest(h) => self.tracker.push_request(*h),
             _ => (),
         };
+        #[cfg(feature = "adversarial")]
+        {
+            if let Some(peer_id) = self.peer_id() {
+                for msg in self.chaos.apply(&peer_id, msg, Instant::now()) {
+                    self.write_message(msg);
+                }
+                return;
+            }
+        }
+        self.write_message(msg);
+    }
+
+    /// Encodes the message and queues it to be sent.
+    fn write_message(&mut self, msg: PeerMessage) {
         #[cfg(feature = "metric_recorder")]
         let metadata = {
             let mut metadata: PeerMessageMetadata = (&msg).into();
@@ -248,12 +318,14 @@ impl Peer {
             metadata
         };
 
-        match peer_message_to_bytes(msg) {
+        let class = TrafficClass::of(&msg);
+        let variant = msg.msg_variant();
+        match peer_message_to_bytes(msg, self.protocol_version, self.capabilities) {
             Ok(bytes) => {
                 #[cfg(feature = "metric_recorder")]
                 self.peer_manager_addr.do_send(metadata.set_size(bytes.len()));
-                self.tracker.increment_sent(bytes.len() as u64);
-                self.framed.write(bytes);
+                // The message counts as sent once the connection takes it from the queue.
+                self.writer.write(class, QueuedMessage { bytes, variant });
             }
             Err(err) => error!(target: "network", "Error converting message to bytes: {}", err),
         };
@@ -294,30 +366,29 @@ impl Peer {
                     tracked_shards,
                     archival,
                 }) => {
-                    let handshake = match act.protocol_version {
-                        39..=PROTOCOL_VERSION => PeerMessage::Handshake(Handshake::new(
-                            act.protocol_version,
-                            act.node_id(),
-                            act.peer_id().unwrap(),
-                            act.node_info.addr_port(),
-                            PeerChainInfoV2 { genesis_id, height, tracked_shards, archival },
-                            act.edge_info.as_ref().unwrap().clone(),
-                        )),
-                        34..=38 => PeerMessage::HandshakeV2(HandshakeV2::new(
-                            act.protocol_version,
-                            act.node_id(),
-                            act.peer_id().unwrap(),
-                            act.node_info.addr_port(),
-                            PeerChainInfo { genesis_id, height, tracked_shards },
-                            act.edge_info.as_ref().unwrap().clone(),
-                        )),
-                        _ => {
-                            error!(target: "network", "Trying to talk with peer with no supported version: {}", act.protocol_version);
-                            return actix::fut::ready(());
-                        }
-                    };
+                    if act.protocol_version < OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION
+                        || act.protocol_version > PROTOCOL_VERSION
+                    {
+                        error!(target: "network", "Trying to talk with peer with no supported version: {}", act.protocol_version);
+                        return actix::fut::ready(());
+                    }
+                    // The codec falls back to `HandshakeV2` for peers talking older versions.
+                    let handshake = PeerMessage::Handshake(Handshake::new(
+                        act.protocol_version,
+                        act.node_id(),
+                        act.peer_id().unwrap(),
+                        act.node_info.addr_port(),
+                        PeerChainInfoV2 { genesis_id, height, tracked_shards, archival },
+                        act.edge_info.as_ref().unwrap().clone(),
+                    ));
 
                     act.send_message(handshake);
+                    // Tell inbound peers the address they connect from, after the handshake.
+                    if act.peer_type == PeerType::Inbound
+                        && act.has_capability(MessageCapability::ObservedAddr)
+                    {
+                        act.send_message(PeerMessage::ObservedAddr(act.peer_addr));
+                    }
                     actix::fut::ready(())
                 }
                 Err(err) => {
@@ -336,6 +407,14 @@ impl Peer {
         ctx.stop();
     }
 
+    /// Report misbehavior to PeerManager, which bans the peer if it misbehaves too often.
+    fn report_misbehavior(&self, misbehavior: Misbehavior) {
+        if let Some(peer_id) = self.peer_id() {
+            self.peer_manager_addr
+                .do_send(NetworkRequests::ReportMisbehavior { peer_id, misbehavior });
+        }
+    }
+
     fn node_id(&self) -> PeerId {
         self.node_info.id.clone()
     }
@@ -344,10 +423,37 @@ impl Peer {
         self.peer_info.as_ref().as_ref().map(|peer_info| peer_info.id.clone())
     }
 
+    /// Checks the signature of the routed message and passes it to the peer manager, which
+    /// tells whether it's for this node.
+    fn receive_routed_message(&mut self, ctx: &mut Context<Peer>, routed_message: RoutedMessage) {
+        trace!(target: "network", "Received routed message from {} to {:?}.", self.peer_info, routed_message.target);
+
+        // Receive invalid routed message from peer.
+        if !routed_message.verify() {
+            self.ban_peer(ctx, ReasonForBan::InvalidSignature);
+            return;
+        }
+        if !self.tx_gossip.incoming(&routed_message) {
+            near_metrics::inc_counter(&metrics::PEER_DUPLICATE_FORWARD_TX_DROPPED);
+            return;
+        }
+        self.peer_manager_addr
+            .send(RoutedMessageFrom { msg: routed_message.clone(), from: self.peer_id().unwrap() })
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                if res.unwrap_or(false) {
+                    act.receive_message(ctx, PeerMessage::Routed(routed_message));
+                }
+                actix::fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
     fn receive_message(&mut self, ctx: &mut Context<Peer>, msg: PeerMessage) {
         if msg.is_view_client_message() {
             self.receive_view_client_message(ctx, msg);
         } else if msg.is_client_message() {
+            self.on_receive_useful_data();
             self.receive_client_message(ctx, msg);
         } else {
             debug_assert!(false);
@@ -385,7 +491,12 @@ impl Peer {
                         NetworkViewClientMessages::StateRequestHeader { shard_id, sync_hash }
                     }
                     RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => {
-                        NetworkViewClientMessages::StateRequestPart { shard_id, sync_hash, part_id }
+                        NetworkViewClientMessages::StateRequestPart {
+                            shard_id,
+                            sync_hash,
+                            part_id,
+                            requester: message.author,
+                        }
                     }
                     body => {
                         error!(target: "network", "Peer receive_view_client_message received unexpected type: {:?}", body);
@@ -546,6 +657,16 @@ impl Peer {
             | PeerMessage::PeersRequest
             | PeerMessage::PeersResponse(_)
             | PeerMessage::RoutingTableSync(_)
+            | PeerMessage::SyncAccountsData(_)
+            | PeerMessage::PeerRecords(_)
+            | PeerMessage::Compressed(_)
+            | PeerMessage::Versioned(_)
+            | PeerMessage::TimePing(_)
+            | PeerMessage::TimePong(_)
+            | PeerMessage::ObservedAddr(_)
+            | PeerMessage::ForwardTxAnnouncement(_)
+            | PeerMessage::TransactionsRequest(_)
+            | PeerMessage::TransactionsResponse(_)
             | PeerMessage::LastEdge(_)
             | PeerMessage::Disconnect
             | PeerMessage::RequestUpdateNonce(_)
@@ -565,7 +686,10 @@ impl Peer {
                 match res {
                     Ok(NetworkClientResponses::InvalidTx(err)) => {
                         warn!(target: "network", "Received invalid tx from peer {}: {}", act.peer_info, err);
-                        // TODO: count as malicious behavior?
+                        act.invalid_txs.increment(1);
+                        if act.invalid_txs.count_per_min() > MAX_INVALID_TXS_PER_MIN {
+                            act.report_misbehavior(Misbehavior::SpammyGossip);
+                        }
                     }
                     Ok(NetworkClientResponses::Ban { ban_reason }) => {
                         act.ban_peer(ctx, ban_reason);
@@ -599,6 +723,34 @@ impl Peer {
             }
         }
     }
+
+    fn receive_time_pong(&mut self, pong: TimePong) {
+        let was_off = self
+            .clock
+            .estimate()
+            .map_or(false, |estimate| estimate.skew_millis.abs() > MAX_CLOCK_SKEW_MILLIS);
+        let sample = unwrap_option_or_return!(self.clock.received(&pong, to_timestamp(Utc::now())));
+        near_metrics::observe(&metrics::PEER_RTT, sample.rtt.as_secs_f64());
+        near_metrics::observe(&metrics::PEER_CLOCK_SKEW, sample.skew_millis.abs() as f64 / 1000.0);
+        let estimate = unwrap_option_or_return!(self.clock.estimate());
+        let peer_id = unwrap_option_or_return!(self.peer_id());
+        if estimate.skew_millis.abs() > MAX_CLOCK_SKEW_MILLIS && !was_off {
+            warn!(target: "network", "Clock of {} is {}ms off ours, round trip {:?}", self.peer_info, estimate.skew_millis, estimate.rtt);
+        }
+        self.peer_manager_addr.do_send(PeerRequest::ClockSkew(peer_id, estimate.skew_millis));
+    }
+
+    /// Hook called on every message for the client, which ranks the peer higher on restart.
+    fn on_receive_useful_data(&mut self) {
+        if let Some(peer_id) = self.peer_id() {
+            if self.last_time_useful_data_update.map_or(true, |last_update| {
+                last_update.elapsed() > UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE
+            }) {
+                self.last_time_useful_data_update = Some(Instant::now());
+                self.peer_manager_addr.do_send(PeerRequest::ReceivedUsefulData(peer_id));
+            }
+        }
+    }
 }
 
 impl Actor for Peer {
@@ -622,6 +774,21 @@ impl Actor for Peer {
         if self.peer_type == PeerType::Outbound {
             self.send_handshake(ctx);
         }
+
+        ctx.run_interval(CLOCK_PING_INTERVAL, |act, _ctx| {
+            if act.peer_status == PeerStatus::Ready && act.has_capability(MessageCapability::Clock)
+            {
+                let ping = act.clock.ping(to_timestamp(Utc::now()));
+                act.send_message(PeerMessage::TimePing(ping));
+            }
+        });
+
+        #[cfg(feature = "adversarial")]
+        ctx.run_interval(CHAOS_TICK, |act, _ctx| {
+            for msg in act.chaos.due(Instant::now()) {
+                act.write_message(msg);
+            }
+        });
     }
 
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
@@ -653,8 +820,6 @@ impl Actor for Peer {
     }
 }
 
-impl WriteHandler<io::Error> for Peer {}
-
 impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
     fn handle(&mut self, msg: Result<Vec<u8>, ReasonForBan>, ctx: &mut Self::Context) {
         let msg = match msg {
@@ -678,7 +843,7 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                 return;
             }
         }
-        let mut peer_msg = match bytes_to_peer_message(&msg) {
+        let mut peer_msg = match bytes_to_peer_message(&msg, self.protocol_version) {
             Ok(peer_msg) => peer_msg,
             Err(err) => {
                 if let Some(version) = err
@@ -704,13 +869,18 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                     ));
                 } else {
                     info!(target: "network", "Received invalid data {:?} from {}: {}", msg, self.peer_info, err);
+                    // Peers of other versions may send messages this node doesn't know.
+                    if self.peer_protocol_version == Some(PROTOCOL_VERSION) {
+                        self.report_misbehavior(Misbehavior::MalformedMessage);
+                    }
                 }
                 return;
             }
         };
         if let PeerMessage::Routed(RoutedMessage {
             body: RoutedMessageBody::ForwardTx(_), ..
-        }) = &peer_msg
+        })
+        | PeerMessage::ForwardTxAnnouncement(_) = &peer_msg
         {
             self.txns_since_last_block.fetch_add(1, Ordering::AcqRel);
         } else if let PeerMessage::Block(_) = &peer_msg {
@@ -756,13 +926,8 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                         version,
                         oldest_supported_version,
                     } => {
-                        let target_version = std::cmp::min(version, PROTOCOL_VERSION);
-
-                        if target_version
-                            >= std::cmp::max(
-                                oldest_supported_version,
-                                OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION,
-                            )
+                        if let Some(target_version) =
+                            negotiate_protocol_version(version, oldest_supported_version)
                         {
                             // Use target_version as protocol_version to talk with this peer
                             self.protocol_version = target_version;
@@ -782,13 +947,29 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
             (_, PeerStatus::Connecting, PeerMessage::Handshake(handshake)) => {
                 debug!(target: "network", "{:?}: Received handshake {:?}", self.node_info.id, handshake);
 
-                debug_assert!(
-                    OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION <= handshake.version
-                        && handshake.version <= PROTOCOL_VERSION
-                );
-
-                let target_version = std::cmp::min(handshake.version, PROTOCOL_VERSION);
-                self.protocol_version = target_version;
+                match negotiate_protocol_version(
+                    handshake.version,
+                    handshake.oldest_supported_version,
+                ) {
+                    Some(target_version) => {
+                        self.protocol_version = target_version;
+                        self.peer_protocol_version = Some(handshake.version);
+                    }
+                    None => {
+                        debug!(target: "network", "Received connection from node with unsupported version: {:?}", (handshake.version, handshake.oldest_supported_version));
+                        ctx.address().do_send(SendMessage {
+                            message: PeerMessage::HandshakeFailure(
+                                self.node_info.clone(),
+                                HandshakeFailureReason::ProtocolVersionMismatch {
+                                    version: PROTOCOL_VERSION,
+                                    oldest_supported_version:
+                                        OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION,
+                                },
+                            ),
+                        });
+                        return;
+                    }
+                }
 
                 if handshake.chain_info.genesis_id != self.genesis_id {
                     debug!(target: "network", "Received connection from node with different genesis.");
@@ -809,6 +990,14 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                     return;
                 }
 
+                if let Some(peer_id) = &self.authenticated_peer_id {
+                    if peer_id != &handshake.peer_id {
+                        warn!(target: "network", "Received handshake from {} over connection encrypted by {}. Disconnecting peer", handshake.peer_id, peer_id);
+                        ctx.stop();
+                        return;
+                    }
+                }
+
                 if handshake.target_peer_id != self.node_info.id {
                     debug!(target: "network", "Received handshake from {:?} to {:?} but I am {:?}", handshake.peer_id, handshake.target_peer_id, self.node_info.id);
                     self.send_message(PeerMessage::HandshakeFailure(
@@ -836,25 +1025,33 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                         != self.edge_info.as_ref().map(|edge_info| edge_info.nonce).unwrap()
                     {
                         warn!(target: "network", "Received invalid nonce on handshake. Disconnecting peer {}", handshake.peer_id);
+                        self.report_misbehavior(Misbehavior::HandshakeAbuse);
                         ctx.stop();
                         return;
                     }
                 }
 
-                let peer_info = PeerInfo {
-                    id: handshake.peer_id.clone(),
-                    addr: handshake
-                        .listen_port
-                        .map(|port| SocketAddr::new(self.peer_addr.ip(), port)),
-                    account_id: None,
+                // The address of outbound peers is the one we dialed, as the connection may go
+                // through a proxy.
+                let addr = if self.peer_type == PeerType::Outbound {
+                    self.peer_info.as_ref().as_ref().and_then(|peer_info| peer_info.addr)
+                } else {
+                    handshake.listen_port.map(|port| SocketAddr::new(self.peer_addr.ip(), port))
                 };
+                let remote_ip = addr.map_or(self.peer_addr.ip(), |addr| addr.ip());
+                let peer_info = PeerInfo { id: handshake.peer_id.clone(), addr, account_id: None };
                 self.chain_info = handshake.chain_info.clone();
+                self.capabilities = handshake.capabilities & SUPPORTED_CAPABILITIES;
                 self.peer_manager_addr
                     .send(Consolidate {
                         actor: ctx.address(),
                         peer_info: peer_info.clone(),
                         peer_type: self.peer_type,
                         chain_info: handshake.chain_info.clone(),
+                        protocol_version: handshake.version,
+                        capabilities: self.capabilities,
+                        transport: self.writer.transport(),
+                        remote_ip,
                         this_edge_info: self.edge_info.clone(),
                         other_edge_info: handshake.edge_info.clone(),
                     })
@@ -864,6 +1061,8 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                             Ok(ConsolidateResponse::Accept(edge_info)) => {
                                 act.peer_info = Some(peer_info).into();
                                 act.peer_status = PeerStatus::Ready;
+                                // The peer manager counts it as an active peer now.
+                                act.pending_inbound = None;
                                 // Respond to handshake if it's inbound and connection was consolidated.
                                 if act.peer_type == PeerType::Inbound {
                                     act.edge_info = edge_info;
@@ -922,13 +1121,23 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
             (_, PeerStatus::Ready, PeerMessage::Handshake(_)) => {
                 // Received handshake after already have seen handshake from this peer.
                 debug!(target: "network", "Duplicate handshake from {}", self.peer_info);
+                self.report_misbehavior(Misbehavior::HandshakeAbuse);
             }
             (_, PeerStatus::Ready, PeerMessage::PeersRequest) => {
                 self.peer_manager_addr.send(PeersRequest {}).into_actor(self).then(|res, act, _ctx| {
-                    if let Ok(peers) = res {
-                        if !peers.peers.is_empty() {
-                            debug!(target: "network", "Peers request from {}: sending {} peers.", act.peer_info, peers.peers.len());
-                            act.send_message(PeerMessage::PeersResponse(peers.peers));
+                    if let Ok(PeerList { mut peers, records }) = res {
+                        if act.has_capability(MessageCapability::PeerRecords) {
+                            let recorded: HashSet<_> =
+                                records.iter().map(|signed_record| &signed_record.record.peer_id).collect();
+                            peers.retain(|peer_info| !recorded.contains(&peer_info.id));
+                            if !records.is_empty() {
+                                debug!(target: "network", "Peers request from {}: sending {} peer records.", act.peer_info, records.len());
+                                act.send_message(PeerMessage::PeerRecords(records));
+                            }
+                        }
+                        if !peers.is_empty() {
+                            debug!(target: "network", "Peers request from {}: sending {} peers.", act.peer_info, peers.len());
+                            act.send_message(PeerMessage::PeersResponse(peers));
                         }
                     }
                     actix::fut::ready(())
@@ -936,7 +1145,20 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
             }
             (_, PeerStatus::Ready, PeerMessage::PeersResponse(peers)) => {
                 debug!(target: "network", "Received peers from {}: {} peers.", self.peer_info, peers.len());
-                self.peer_manager_addr.do_send(PeersResponse { peers });
+                self.peer_manager_addr.do_send(PeersResponse { peers, records: vec![] });
+            }
+            (_, PeerStatus::Ready, PeerMessage::PeerRecords(records)) => {
+                debug!(target: "network", "Received peer records from {}: {} peers.", self.peer_info, records.len());
+                if records.iter().all(|signed_record| signed_record.verify()) {
+                    self.peer_manager_addr.do_send(PeersResponse { peers: vec![], records });
+                } else {
+                    self.ban_peer(ctx, ReasonForBan::InvalidSignature);
+                }
+            }
+            (PeerType::Outbound, PeerStatus::Ready, PeerMessage::ObservedAddr(addr)) => {
+                debug!(target: "network", "{} observed this node at {}", self.peer_info, addr);
+                self.peer_manager_addr
+                    .do_send(ObservedAddr { peer_id: self.peer_id().unwrap(), addr });
             }
             (_, PeerStatus::Ready, PeerMessage::RequestUpdateNonce(edge_info)) => self
                 .peer_manager_addr
@@ -973,27 +1195,56 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                 self.peer_manager_addr
                     .do_send(NetworkRequests::Sync { peer_id: self.peer_id().unwrap(), sync_data });
             }
+            (_, PeerStatus::Ready, PeerMessage::SyncAccountsData(accounts_data)) => {
+                self.peer_manager_addr.do_send(NetworkRequests::SyncAccountsData {
+                    peer_id: self.peer_id().unwrap(),
+                    accounts_data,
+                });
+            }
             (_, PeerStatus::Ready, PeerMessage::Routed(routed_message)) => {
-                trace!(target: "network", "Received routed message from {} to {:?}.", self.peer_info, routed_message.target);
-
-                // Receive invalid routed message from peer.
-                if !routed_message.verify() {
-                    self.ban_peer(ctx, ReasonForBan::InvalidSignature);
-                } else {
-                    self.peer_manager_addr
-                        .send(RoutedMessageFrom {
-                            msg: routed_message.clone(),
-                            from: self.peer_id().unwrap(),
-                        })
-                        .into_actor(self)
-                        .then(move |res, act, ctx| {
-                            if res.unwrap_or(false) {
-                                act.receive_message(ctx, PeerMessage::Routed(routed_message));
-                            }
-                            actix::fut::ready(())
-                        })
-                        .spawn(ctx);
+                self.receive_routed_message(ctx, routed_message);
+            }
+            (_, PeerStatus::Ready, PeerMessage::ForwardTxAnnouncement(announcement)) => {
+                let outcome = match self.tx_gossip.announced(announcement) {
+                    Announced::Rebuilt(routed_message) => {
+                        self.receive_routed_message(ctx, routed_message);
+                        "known"
+                    }
+                    Announced::Request(signed_tx_hash) => {
+                        self.send_message(PeerMessage::TransactionsRequest(vec![signed_tx_hash]));
+                        "requested"
+                    }
+                    Announced::Waiting => "waiting",
+                };
+                near_metrics::inc_counter_vec(&metrics::PEER_TX_ANNOUNCEMENT_RECEIVED, &[outcome]);
+            }
+            (_, PeerStatus::Ready, PeerMessage::TransactionsRequest(hashes)) => {
+                if hashes.len() > MAX_TRANSACTIONS_REQUEST {
+                    self.report_misbehavior(Misbehavior::MalformedMessage);
+                    return;
                 }
+                let txs = self.tx_gossip.requested(&hashes);
+                if !txs.is_empty() {
+                    self.send_message(PeerMessage::TransactionsResponse(txs));
+                }
+            }
+            (_, PeerStatus::Ready, PeerMessage::TransactionsResponse(txs)) => {
+                for routed_message in self.tx_gossip.received(txs) {
+                    self.receive_routed_message(ctx, routed_message);
+                }
+            }
+            (_, PeerStatus::Ready, PeerMessage::TimePing(ping)) => {
+                self.send_message(PeerMessage::TimePong(PeerClock::pong(
+                    &ping,
+                    to_timestamp(Utc::now()),
+                )));
+            }
+            (_, PeerStatus::Ready, PeerMessage::TimePong(pong)) => {
+                self.receive_time_pong(pong);
+            }
+            (_, _, PeerMessage::Versioned(versioned)) => {
+                // Messages newer than this node are left to the nodes that understand them.
+                debug!(target: "network", "Skipping message {} of version {} from {}", versioned.kind, versioned.version, self.peer_info);
             }
             (_, PeerStatus::Ready, msg) => {
                 self.receive_message(ctx, msg);
@@ -1021,15 +1272,13 @@ impl Handler<QueryPeerStats> for Peer {
     fn handle(&mut self, _: QueryPeerStats, _: &mut Self::Context) -> Self::Result {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("query peer stats".into());
+        let (sent_bytes_per_min, sent_messages_per_min) = self.writer.sent_per_min();
         PeerStatsResult {
             chain_info: self.chain_info.clone(),
             received_bytes_per_sec: self.tracker.received_bytes.bytes_per_min() / 60,
-            sent_bytes_per_sec: self.tracker.sent_bytes.bytes_per_min() / 60,
+            sent_bytes_per_sec: sent_bytes_per_min / 60,
             is_abusive: self.is_abusive(),
-            message_counts: (
-                self.tracker.sent_bytes.count_per_min(),
-                self.tracker.received_bytes.count_per_min(),
-            ),
+            message_counts: (sent_messages_per_min, self.tracker.received_bytes.count_per_min()),
         }
     }
 }
@@ -0,0 +1,109 @@
+//! Round trip time and clock skew of peers.
+//!
+//! Every `CLOCK_PING_INTERVAL` the node sends a `TimePing` to the peers with
+//! `MessageCapability::Clock`, which answer right away with a `TimePong` holding their clock. As in
+//! NTP, assuming the ping and the pong take as long on the wire, the clock of the peer was ahead of
+//! ours by the difference between its time and the middle of the round trip. Samples taken over a
+//! busy connection wait in queues, so the skew of the recent sample with the shortest round trip
+//! is the estimate.
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::types::{TimePing, TimePong};
+
+/// How often peers are pinged.
+pub const CLOCK_PING_INTERVAL: Duration = Duration::from_secs(30);
+/// Skews larger than this are logged, blocks and approvals of peers that far off arrive early or
+/// late for the doomslug timers.
+pub const MAX_CLOCK_SKEW_MILLIS: i64 = 1_000;
+/// Samples the estimate is picked from.
+const CLOCK_SAMPLES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockSample {
+    pub rtt: Duration,
+    /// Clock of the peer minus ours, in milliseconds.
+    pub skew_millis: i64,
+}
+
+/// Clock measurements of a connection, with times as nanosecond timestamps.
+#[derive(Default)]
+pub struct PeerClock {
+    nonce: u64,
+    /// Nonce and time of the ping waiting for a pong, a ping that is not answered until the next
+    /// one is dropped.
+    pending: Option<(u64, u64)>,
+    samples: VecDeque<ClockSample>,
+}
+
+impl PeerClock {
+    pub fn ping(&mut self, now: u64) -> TimePing {
+        self.nonce += 1;
+        self.pending = Some((self.nonce, now));
+        TimePing { nonce: self.nonce }
+    }
+
+    /// Answer to a ping of the peer.
+    pub fn pong(ping: &TimePing, now: u64) -> TimePong {
+        TimePong { nonce: ping.nonce, time: now }
+    }
+
+    /// Records the answer of the peer to the last ping, `None` if it answers another one.
+    pub fn received(&mut self, pong: &TimePong, now: u64) -> Option<ClockSample> {
+        let sent = match self.pending {
+            Some((nonce, sent)) if nonce == pong.nonce && sent <= now => sent,
+            _ => return None,
+        };
+        self.pending = None;
+        let middle = sent as i128 + (now - sent) as i128 / 2;
+        let sample = ClockSample {
+            rtt: Duration::from_nanos(now - sent),
+            skew_millis: ((pong.time as i128 - middle) / 1_000_000) as i64,
+        };
+        if self.samples.len() == CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        Some(sample)
+    }
+
+    /// Recent sample with the shortest round trip.
+    pub fn estimate(&self) -> Option<ClockSample> {
+        self.samples.iter().min_by_key(|sample| sample.rtt).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MILLI: u64 = 1_000_000;
+
+    #[test]
+    fn test_peer_clock() {
+        let mut clock = PeerClock::default();
+        let start = 1_000_000 * MILLI;
+
+        // The peer is 500ms ahead, the pong takes longer than the ping.
+        let ping = clock.ping(start);
+        let pong = PeerClock::pong(&ping, start + 10 * MILLI + 500 * MILLI);
+        let sample = clock.received(&pong, start + 40 * MILLI).unwrap();
+        assert_eq!(sample, ClockSample { rtt: Duration::from_millis(40), skew_millis: 490 });
+        // Answered once.
+        assert_eq!(clock.received(&pong, start + 50 * MILLI), None);
+
+        // Pongs of older pings are ignored.
+        let old = clock.ping(start + 100 * MILLI);
+        clock.ping(start + 200 * MILLI);
+        assert_eq!(clock.received(&PeerClock::pong(&old, start), start + 210 * MILLI), None);
+
+        // The peer is 500ms behind over a fast connection.
+        let ping = clock.ping(start + 300 * MILLI);
+        let pong = PeerClock::pong(&ping, start + 305 * MILLI - 500 * MILLI);
+        clock.received(&pong, start + 310 * MILLI).unwrap();
+        assert_eq!(
+            clock.estimate(),
+            Some(ClockSample { rtt: Duration::from_millis(10), skew_millis: -500 })
+        );
+    }
+}
@@ -1,14 +1,13 @@
 use rand::seq::{IteratorRandom, SliceRandom};
 use std::cmp;
-use std::collections::{HashMap, HashSet};
-use std::net::SocketAddr;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
 use std::sync::atomic::Ordering;
 use std::sync::{atomic::AtomicUsize, Arc};
 use std::time::{Duration, Instant};
 
 use actix::actors::resolver::{ConnectAddr, Resolver};
-use actix::io::FramedWrite;
 use actix::{
     Actor, ActorFuture, Addr, Arbiter, AsyncContext, Context, ContextFutureSpawner, Handler,
     Recipient, Running, StreamHandler, SyncArbiter, SyncContext, SystemService, WrapFuture,
@@ -16,34 +15,49 @@ use actix::{
 use chrono::Utc;
 use futures::task::Poll;
 use futures::{future, Stream, StreamExt};
+use net2::TcpBuilder;
 use tokio::net::{TcpListener, TcpStream};
-use tokio_util::codec::FramedRead;
 use tracing::{debug, error, info, trace, warn};
 
+use near_crypto::KeyType;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::types::AccountId;
-use near_primitives::utils::from_timestamp;
+use near_primitives::utils::{from_timestamp, to_timestamp};
+use near_primitives::version::{ProtocolVersion, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION};
 use near_store::Store;
 
-use crate::codec::Codec;
+use crate::access::PeerAccess;
+#[cfg(feature = "adversarial")]
+use crate::chaos::{Chaos, SetChaosRules};
+use crate::connection_limits::{PendingInbound, ReconnectBackoff, Subnet};
+use crate::encryption::{EncryptionMode, TcpConnection};
 use crate::metrics;
+use crate::nat::{self, ObservedAddrs, PortMapping};
 use crate::peer::Peer;
-use crate::peer_store::{PeerStore, TrustLevel};
+use crate::peer_store::{AddrFamilies, PeerStore, TrustLevel};
+use crate::proxy::{self, ProxyConfig, ProxyTarget};
 #[cfg(feature = "metric_recorder")]
 use crate::recorder::{MetricRecorder, PeerMessageMetadata};
-use crate::routing::{Edge, EdgeInfo, EdgeType, ProcessEdgeResult, RoutingTable};
+use crate::reputation::{Misbehavior, ReputationTable};
+use crate::route_liveness::ROUTE_PRUNE_PERIOD;
+use crate::routing::{Edge, EdgeInfo, EdgeType, FindRouteError, ProcessEdgeResult, RoutingTable};
+use crate::throttle::Bandwidth;
+use crate::tier1::{self, Tier1, MAX_TIER1_PROXIES};
+use crate::transport::{self, PeerConnection, QuicConnection, QuicEndpoint, Transport};
+use crate::tx_gossip::RecentTransactions;
 use crate::types::{
-    AccountOrPeerIdOrHash, Ban, BlockedPorts, Consolidate, ConsolidateResponse, FullPeerInfo,
-    InboundTcpConnect, KnownPeerStatus, KnownProducer, NetworkInfo, NetworkViewClientMessages,
-    NetworkViewClientResponses, OutboundTcpConnect, PeerIdOrHash, PeerList, PeerManagerRequest,
-    PeerMessage, PeerRequest, PeerResponse, PeerType, PeersRequest, PeersResponse, Ping, Pong,
-    QueryPeerStats, RawRoutedMessage, ReasonForBan, RoutedMessage, RoutedMessageBody,
-    RoutedMessageFrom, SendMessage, StateResponseInfo, SyncData, Unregister,
+    AccountData, AccountOrPeerIdOrHash, Ban, BlockedPorts, Consolidate, ConsolidateResponse,
+    FullPeerInfo, InboundQuicConnect, InboundTcpConnect, KnownPeerStatus, KnownProducer,
+    NetworkInfo, NetworkViewClientMessages, NetworkViewClientResponses, ObservedAddr,
+    OutboundTcpConnect, PeerIdOrHash, PeerList, PeerManagerRequest, PeerMessage, PeerRequest,
+    PeerResponse, PeerType, PeersRequest, PeersResponse, Ping, Pong, PortMapped, QueryPeerStats,
+    RawRoutedMessage, ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom,
+    SendMessage, SignedAccountData, StateResponseInfo, SyncData, Unregister,
 };
 use crate::types::{
-    EdgeList, KnownPeerState, NetworkClientMessages, NetworkConfig, NetworkRequests,
-    NetworkResponses, PeerInfo,
+    EdgeList, KnownPeerState, MessageCapability, NetworkClientMessages, NetworkConfig,
+    NetworkRequests, NetworkResponses, PeerCapability, PeerInfo, PeerRecord,
 };
 #[cfg(feature = "delay_detector")]
 use delay_detector::DelayDetector;
@@ -76,6 +90,58 @@ const EXPONENTIAL_BACKOFF_LIMIT: u64 = 91;
 const WAIT_BEFORE_PING: u64 = 20_000;
 /// Limit number of pending Peer actors to avoid OOM.
 const LIMIT_PENDING_PEERS: usize = 60;
+/// How often to connect to the TIER1 peers of other validators.
+const TIER1_UPDATE_PERIOD: Duration = Duration::from_secs(10);
+/// How often to advertise the TIER1 peers of this validator again, for the peers that dropped the
+/// data because they didn't know the announcement of the account yet.
+const TIER1_ADVERTISE_PERIOD: Duration = Duration::from_secs(10 * 60);
+/// Warn about peers with protocol version at most this much above the oldest version we are
+/// still compatible with, they will be disconnected soon after the next releases.
+const DEPRECATED_PEER_PROTOCOL_VERSION_MARGIN: ProtocolVersion = 2;
+
+/// Record of the public addresses and the capabilities of the node. The configured public
+/// addresses take precedence over the discovered `external_addr`.
+fn own_peer_record(
+    config: &NetworkConfig,
+    external_addr: Option<SocketAddr>,
+) -> Option<SignedPeerRecord> {
+    let addrs: Vec<_> = if config.public_addrs.is_empty() {
+        external_addr
+            .into_iter()
+            .chain(config.listen_addrs().into_iter().filter(|addr| !addr.ip().is_unspecified()))
+            .collect()
+    } else {
+        config.public_addrs.clone()
+    };
+    if addrs.is_empty() {
+        return None;
+    }
+    let mut capabilities = vec![];
+    if config.archive {
+        capabilities.push(PeerCapability::Archival);
+    }
+    if uses_quic(config) {
+        capabilities.push(PeerCapability::Quic);
+    }
+    if config.encryption != EncryptionMode::Disabled {
+        capabilities.push(PeerCapability::Encryption);
+    }
+    let peer_id = config.public_key.clone().into();
+    let record = PeerRecord::new(peer_id, addrs, config.account_id.clone(), &capabilities);
+    Some(record.sign(&config.secret_key))
+}
+
+/// Whether to use QUIC. Its connections are always encrypted and bound to the node key of the peer
+/// by its certificate, so they're used whatever the `EncryptionMode`. The certificates are issued
+/// for the node key, which must be an ED25519 key.
+fn uses_quic(config: &NetworkConfig) -> bool {
+    config.transport == Transport::Quic && config.public_key.key_type() == KeyType::ED25519
+}
+
+fn is_deprecated_protocol_version(protocol_version: ProtocolVersion) -> bool {
+    protocol_version
+        <= OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION + DEPRECATED_PEER_PROTOCOL_VERSION_MARGIN
+}
 
 macro_rules! unwrap_or_error(($obj: expr, $error: expr) => (match $obj {
     Ok(result) => result,
@@ -101,6 +167,15 @@ struct ActivePeer {
     connection_established_time: Instant,
     /// Who started connection. Inbound (other) or Outbound (us).
     peer_type: PeerType,
+    /// Protocol version advertised by the peer in the handshake.
+    protocol_version: ProtocolVersion,
+    /// `MessageCapability` bits both this node and the peer have.
+    capabilities: u64,
+    /// Estimated clock of the peer minus ours, in milliseconds.
+    clock_skew_millis: Option<i64>,
+    transport: Transport,
+    /// IP the access rules are matched against, see `Consolidate::remote_ip`.
+    remote_ip: IpAddr,
 }
 
 struct EdgeVerifier {}
@@ -149,7 +224,38 @@ pub struct PeerManagerActor {
     edge_verifier_pool: Addr<EdgeVerifier>,
     txns_since_last_block: Arc<AtomicUsize>,
     pending_incoming_connections_counter: Arc<AtomicUsize>,
+    /// Inbound TCP connections whose encryption handshake is in progress, they take a peer slot.
+    inbound_handshakes: usize,
     peer_counter: Arc<AtomicUsize>,
+    /// Endpoint for QUIC connections, if it's the configured transport.
+    quic_endpoint: Option<QuicEndpoint>,
+    /// Misbehavior scores of peers.
+    reputation: ReputationTable,
+    /// IP versions of the listening addresses.
+    addr_families: AddrFamilies,
+    /// Bandwidth limits shared by all connections.
+    bandwidth: Bandwidth,
+    /// TIER1 peers of the validators.
+    tier1: Tier1,
+    /// Record of this node sent in peer exchange, if it has addresses to advertise.
+    own_record: Option<SignedPeerRecord>,
+    /// Peers allowed and denied to connect, changed over RPC.
+    access: PeerAccess,
+    /// IPs of this node observed by outbound peers.
+    observed_addrs: ObservedAddrs,
+    /// External address the listening port is mapped to on the router.
+    mapped_addr: Option<SocketAddr>,
+    /// External address advertised in `own_record`.
+    external_addr: Option<SocketAddr>,
+    /// Time inbound peers that disconnected have to wait to reconnect.
+    reconnect_backoff: ReconnectBackoff,
+    /// Inbound connections by subnet that aren't consolidated yet.
+    pending_inbound: PendingInbound,
+    /// Transactions recently forwarded by the peers, shared by the connections.
+    recent_txs: Arc<RecentTransactions>,
+    /// Faults injected into the messages sent to peers, set by tests.
+    #[cfg(feature = "adversarial")]
+    chaos: Chaos,
 }
 
 impl PeerManagerActor {
@@ -172,6 +278,12 @@ impl PeerManagerActor {
         let metric_recorder = MetricRecorder::default().set_me(me.clone());
 
         let txns_since_last_block = Arc::new(AtomicUsize::new(0));
+        let reputation = ReputationTable::new(config.reputation.clone());
+        let addr_families = AddrFamilies::of(&config.listen_addrs());
+        let bandwidth = Bandwidth::new(config.bandwidth.clone());
+        let own_record = own_peer_record(&config, None);
+        let access = config.access.clone();
+        let reconnect_backoff = ReconnectBackoff::new(&config.connection_limits);
 
         Ok(PeerManagerActor {
             peer_id: me,
@@ -191,7 +303,23 @@ impl PeerManagerActor {
             metric_recorder,
             txns_since_last_block,
             pending_incoming_connections_counter: Arc::new(AtomicUsize::new(0)),
+            inbound_handshakes: 0,
             peer_counter: Arc::new(AtomicUsize::new(0)),
+            quic_endpoint: None,
+            reputation,
+            addr_families,
+            bandwidth,
+            tier1: Tier1::default(),
+            own_record,
+            access,
+            observed_addrs: ObservedAddrs::default(),
+            mapped_addr: None,
+            external_addr: None,
+            reconnect_backoff,
+            pending_inbound: PendingInbound::default(),
+            recent_txs: Arc::new(RecentTransactions::default()),
+            #[cfg(feature = "adversarial")]
+            chaos: Chaos::default(),
         })
     }
 
@@ -199,6 +327,24 @@ impl PeerManagerActor {
         self.active_peers.len()
     }
 
+    /// Number of active peers by the protocol version they advertised.
+    fn peer_protocol_versions(&self) -> BTreeMap<ProtocolVersion, usize> {
+        let mut versions = BTreeMap::new();
+        for active_peer in self.active_peers.values() {
+            *versions.entry(active_peer.protocol_version).or_insert(0) += 1;
+        }
+        versions
+    }
+
+    fn update_peer_protocol_version_metrics(&self) {
+        if let Ok(gauge) = &*metrics::PEER_PROTOCOL_VERSION {
+            gauge.reset();
+            for (version, num_peers) in self.peer_protocol_versions() {
+                gauge.with_label_values(&[&version.to_string()]).set(num_peers as i64);
+            }
+        }
+    }
+
     fn is_blacklisted(&self, addr: &SocketAddr) -> bool {
         if let Some(blocked_ports) = self.config.blacklist.get(&addr.ip()) {
             match blocked_ports {
@@ -210,6 +356,63 @@ impl PeerManagerActor {
         }
     }
 
+    /// Advertises the address of the port mapping, or else the IP observed by the peers with the
+    /// listening port, if either is public.
+    fn update_external_addr(&mut self) {
+        let observed_addr = if self.config.discover_external_addr {
+            self.observed_addrs
+                .external_ip()
+                .and_then(|ip| self.config.addr.map(|addr| SocketAddr::new(ip, addr.port())))
+        } else {
+            None
+        };
+        let external_addr =
+            self.mapped_addr.filter(|addr| nat::is_public(addr.ip())).or(observed_addr);
+        if external_addr != self.external_addr {
+            info!(target: "network", "External address changed to {:?}", external_addr);
+            self.external_addr = external_addr;
+            self.own_record = own_peer_record(&self.config, external_addr);
+        }
+    }
+
+    /// Listening addresses to tell peers about. Unspecified addresses are replaced by the IPs the
+    /// peers observe of every IP version the listener accepts, if they agree on them.
+    fn advertised_listen_addrs(&self) -> Vec<SocketAddr> {
+        let listen_addrs = self.config.listen_addrs();
+        let has_ipv4_listener = listen_addrs.iter().any(|addr| addr.is_ipv4());
+        let mut addrs = vec![];
+        for addr in listen_addrs.iter() {
+            if !addr.ip().is_unspecified() {
+                addrs.push(*addr);
+                continue;
+            }
+            if !self.config.discover_external_addr {
+                continue;
+            }
+            // An unspecified IPv6 listener accepts IPv4 connections too if there is no IPv4 one.
+            let accepts_ipv4 = addr.is_ipv4() || !has_ipv4_listener;
+            let accepts_ipv6 = addr.is_ipv6();
+            for (ipv4, accepted) in [(true, accepts_ipv4), (false, accepts_ipv6)].iter() {
+                if let Some(ip) = self.observed_addrs.external_ip_of_version(*ipv4) {
+                    if *accepted && !addrs.contains(&SocketAddr::new(ip, addr.port())) {
+                        addrs.push(SocketAddr::new(ip, addr.port()));
+                    }
+                }
+            }
+        }
+        addrs
+    }
+
+    /// Disconnects the peers that the access rules no longer accept.
+    fn enforce_peer_access(&self) {
+        for (peer_id, active_peer) in self.active_peers.iter() {
+            if !self.access.accepts(active_peer.peer_type, peer_id, active_peer.remote_ip) {
+                debug!(target: "network", "Disconnecting peer {} denied by access rules", peer_id);
+                active_peer.addr.do_send(PeerManagerRequest::UnregisterPeer);
+            }
+        }
+    }
+
     /// Register a direct connection to a new peer. This will be called after successfully
     /// establishing a connection with another peer. It become part of the active peers.
     ///
@@ -221,10 +424,23 @@ impl PeerManagerActor {
         full_peer_info: FullPeerInfo,
         edge_info: EdgeInfo,
         peer_type: PeerType,
+        protocol_version: ProtocolVersion,
+        capabilities: u64,
+        transport: Transport,
+        remote_ip: IpAddr,
         addr: Addr<Peer>,
         ctx: &mut Context<Self>,
     ) {
         debug!(target: "network", "Consolidated connection with {:?}", full_peer_info);
+        if is_deprecated_protocol_version(protocol_version) {
+            warn!(
+                target: "network",
+                peer_id = %full_peer_info.peer_info.id,
+                protocol_version,
+                oldest_supported_version = OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION,
+                "Peer uses a protocol version that will soon be unsupported"
+            );
+        }
 
         if self.outgoing_peers.contains(&full_peer_info.peer_info.id) {
             self.outgoing_peers.remove(&full_peer_info.peer_info.id);
@@ -255,8 +471,14 @@ impl PeerManagerActor {
                 last_time_received_message: Instant::now(),
                 connection_established_time: Instant::now(),
                 peer_type,
+                protocol_version,
+                capabilities,
+                transport,
+                remote_ip,
+                clock_skew_millis: None,
             },
         );
+        self.update_peer_protocol_version_metrics();
 
         self.process_edges(ctx, vec![new_edge.clone()]);
 
@@ -276,6 +498,12 @@ impl PeerManagerActor {
                 }),
             });
 
+            if MessageCapability::Tier1.is_in(capabilities) && !act.tier1.is_empty() {
+                let _ = addr.do_send(SendMessage {
+                    message: PeerMessage::SyncAccountsData(act.tier1.all()),
+                });
+            }
+
             // Ask for peers list on connection.
             let _ = addr.do_send(SendMessage { message: PeerMessage::PeersRequest });
             if let Some(active_peer) = act.active_peers.get_mut(&target_peer_id) {
@@ -315,7 +543,18 @@ impl PeerManagerActor {
 
         // If the last edge we have with this peer represent a connection addition, create the edge
         // update that represents the connection removal.
-        self.active_peers.remove(&peer_id);
+        if let Some(active_peer) = self.active_peers.remove(&peer_id) {
+            if active_peer.peer_type == PeerType::Inbound {
+                self.reconnect_backoff.disconnected(
+                    active_peer.remote_ip,
+                    active_peer.connection_established_time,
+                    Instant::now(),
+                );
+            }
+        }
+        self.update_peer_protocol_version_metrics();
+        self.observed_addrs.remove(&peer_id);
+        self.update_external_addr();
 
         if let Some(edge) = self.routing_table.get_edge(self.peer_id.clone(), peer_id.clone()) {
             if edge.edge_type() == EdgeType::Added {
@@ -383,26 +622,58 @@ impl PeerManagerActor {
         }
     }
 
-    /// Connects peer with given TcpStream and optional information if it's outbound.
+    /// Count misbehavior of the peer, and ban it if its score reached the ban threshold.
+    fn report_misbehavior(
+        &mut self,
+        ctx: &mut Context<Self>,
+        peer_id: &PeerId,
+        misbehavior: Misbehavior,
+    ) {
+        debug!(target: "network", "Peer {:?} misbehaved: {:?}", peer_id, misbehavior);
+        if let Some(ban_reason) = self.reputation.report(peer_id, misbehavior, Instant::now()) {
+            self.try_ban_peer(ctx, peer_id, ban_reason);
+        }
+    }
+
+    /// Listening address of the same IP version as the given address of a peer, or `addr` if
+    /// there is none.
+    fn listen_addr_for(&self, remote_addr: &SocketAddr) -> Option<SocketAddr> {
+        let remote_is_ipv4 = AddrFamilies { ipv4: true, ipv6: false }.contains(remote_addr);
+        self.config
+            .listen_addrs()
+            .into_iter()
+            .find(|addr| addr.is_ipv4() == remote_is_ipv4)
+            .or(self.config.addr)
+    }
+
+    /// Connects peer with given connection and optional information if it's outbound.
     /// This might fail if the other peers drop listener at its endpoint while establishing connection.
     fn try_connect_peer(
         &mut self,
         recipient: Addr<Self>,
-        stream: TcpStream,
+        connection: PeerConnection,
         peer_type: PeerType,
         peer_info: Option<PeerInfo>,
         edge_info: Option<EdgeInfo>,
     ) {
         let peer_id = self.peer_id.clone();
         let account_id = self.config.account_id.clone();
-        let server_addr = self.config.addr;
         let handshake_timeout = self.config.handshake_timeout;
         let client_addr = self.client_addr.clone();
         let view_client_addr = self.view_client_addr.clone();
 
-        let server_addr = match server_addr {
+        let remote_addr = match connection.peer_addr() {
+            Ok(remote_addr) => remote_addr,
+            _ => {
+                warn!(target: "network", "Failed establishing connection with {:?}", peer_info);
+                return;
+            }
+        };
+
+        // The peer learns our address from the IP of the connection and the port we send it.
+        let server_addr = match self.listen_addr_for(&remote_addr) {
             Some(server_addr) => server_addr,
-            None => match stream.local_addr() {
+            None => match connection.local_addr() {
                 Ok(server_addr) => server_addr,
                 _ => {
                     warn!(target: "network", "Failed establishing connection with {:?}", peer_info);
@@ -411,16 +682,18 @@ impl PeerManagerActor {
             },
         };
 
-        let remote_addr = match stream.peer_addr() {
-            Ok(remote_addr) => remote_addr,
-            _ => {
-                warn!(target: "network", "Failed establishing connection with {:?}", peer_info);
-                return;
-            }
-        };
-
         let network_metrics = self.network_metrics.clone();
         let txns_since_last_block = Arc::clone(&self.txns_since_last_block);
+        let recent_txs = Arc::clone(&self.recent_txs);
+        #[cfg(feature = "adversarial")]
+        let chaos = self.chaos.clone();
+        let (upload, download) = self.bandwidth.connection();
+        let send_queue_config = self.config.send_queue.clone();
+        let authenticated_peer_id = connection.authenticated_peer_id().cloned();
+        let pending_inbound = match peer_type {
+            PeerType::Inbound => Some(self.pending_inbound.add(remote_addr.ip())),
+            PeerType::Outbound => None,
+        };
 
         // Start every peer actor on separate thread.
         let arbiter = Arbiter::new();
@@ -428,28 +701,25 @@ impl PeerManagerActor {
         peer_counter.fetch_add(1, Ordering::SeqCst);
 
         Peer::start_in_arbiter(&arbiter, move |ctx| {
-            let (read, write) = tokio::io::split(stream);
-
-            // TODO: check if peer is banned or known based on IP address and port.
-            Peer::add_stream(
-                FramedRead::new(read, Codec::new())
-                    .take_while(|x| match x {
-                        Ok(_) => future::ready(true),
-                        Err(e) => {
-                            warn!(target: "network", "Peer stream error: {:?}", e);
-                            future::ready(false)
-                        }
-                    })
-                    .map(Result::unwrap),
+            let (messages, writer) = connection.split(
                 ctx,
+                upload,
+                download,
+                &send_queue_config,
+                network_metrics.clone(),
             );
 
+            // TODO: check if peer is banned or known based on IP address and port.
+            Peer::add_stream(messages, ctx);
+
             Peer::new(
                 PeerInfo { id: peer_id, addr: Some(server_addr), account_id },
                 remote_addr,
                 peer_info,
                 peer_type,
-                FramedWrite::new(write, Codec::new(), ctx),
+                writer,
+                authenticated_peer_id,
+                pending_inbound,
                 handshake_timeout,
                 recipient,
                 client_addr,
@@ -458,10 +728,180 @@ impl PeerManagerActor {
                 network_metrics,
                 txns_since_last_block,
                 peer_counter,
+                recent_txs,
+                #[cfg(feature = "adversarial")]
+                chaos,
             )
         });
     }
 
+    fn connect_tcp(&mut self, ctx: &mut Context<Self>, peer_info: PeerInfo, addr: SocketAddr) {
+        Resolver::from_registry()
+            .send(ConnectAddr(addr))
+            .into_actor(self)
+            .then(move |res, act, ctx| match res {
+                Ok(res) => match res {
+                    Ok(stream) => {
+                        debug!(target: "network", "Connecting to {}", peer_info);
+                        act.connect_outbound_tcp(ctx, stream, peer_info);
+                        actix::fut::ready(())
+                    }
+                    Err(err) => {
+                        info!(target: "network", "Error connecting to {}: {}", addr, err);
+                        act.outgoing_peers.remove(&peer_info.id);
+                        actix::fut::ready(())
+                    }
+                },
+                Err(err) => {
+                    info!(target: "network", "Error connecting to {}: {}", addr, err);
+                    act.outgoing_peers.remove(&peer_info.id);
+                    actix::fut::ready(())
+                }
+            })
+            .wait(ctx);
+    }
+
+    /// Starts the peer on the dialed stream, encrypting it first if the peer supports it or
+    /// encryption is required.
+    fn connect_outbound_tcp(
+        &mut self,
+        ctx: &mut Context<Self>,
+        stream: TcpStream,
+        peer_info: PeerInfo,
+    ) {
+        let supports_encryption =
+            self.peer_store.record(&peer_info.id).map_or(false, |signed_record| {
+                signed_record.record.has_capability(PeerCapability::Encryption)
+            });
+        if !self.config.encryption.encrypt_outbound(supports_encryption) {
+            let edge_info = self.propose_edge(peer_info.id.clone(), None);
+            self.try_connect_peer(
+                ctx.address(),
+                PeerConnection::Tcp(TcpConnection::plaintext(stream)),
+                PeerType::Outbound,
+                Some(peer_info),
+                Some(edge_info),
+            );
+            return;
+        }
+        TcpConnection::connect(
+            stream,
+            self.config.secret_key.clone(),
+            peer_info.id.clone(),
+            self.config.handshake_timeout,
+        )
+        .into_actor(self)
+        .then(move |res, act, ctx| {
+            match res {
+                Ok(connection) => {
+                    let edge_info = act.propose_edge(peer_info.id.clone(), None);
+                    act.try_connect_peer(
+                        ctx.address(),
+                        PeerConnection::Tcp(connection),
+                        PeerType::Outbound,
+                        Some(peer_info),
+                        Some(edge_info),
+                    );
+                }
+                Err(err) => {
+                    info!(target: "network", "Error encrypting connection to {}: {}", peer_info, err);
+                    act.outgoing_peers.remove(&peer_info.id);
+                }
+            }
+            actix::fut::ready(())
+        })
+        .spawn(ctx);
+    }
+
+    /// Connects to the peer through the proxy. Unlike direct connections this doesn't block the
+    /// actor, as the proxy may take long to reach the peer.
+    fn connect_proxied(
+        &mut self,
+        ctx: &mut Context<Self>,
+        proxy: ProxyConfig,
+        peer_info: PeerInfo,
+        target: ProxyTarget,
+    ) {
+        async move {
+            let res = proxy::connect(&proxy, &target).await;
+            (target, res)
+        }
+        .into_actor(self)
+        .then(move |(target, res), act, ctx| {
+            match res {
+                Ok(stream) => {
+                    debug!(target: "network", "Connecting to {} through proxy", peer_info);
+                    act.connect_outbound_tcp(ctx, stream, peer_info);
+                }
+                Err(err) => {
+                    info!(target: "network", "Error connecting to {} through proxy: {}", target, err);
+                    act.outgoing_peers.remove(&peer_info.id);
+                }
+            }
+            actix::fut::ready(())
+        })
+        .spawn(ctx);
+    }
+
+    /// Connects to the boot nodes given by host name. They are not in the peer store until
+    /// connected, since only the proxy knows their address.
+    fn connect_boot_node_hosts(&mut self, ctx: &mut Context<Self>) {
+        let proxy = match self.config.proxy.clone() {
+            Some(proxy) => proxy,
+            None => return,
+        };
+        for boot_node in self.config.boot_node_hosts.clone() {
+            if self.active_peers.contains_key(&boot_node.id)
+                || self.peer_store.is_banned(&boot_node.id)
+                || !self.outgoing_peers.insert(boot_node.id.clone())
+            {
+                continue;
+            }
+            debug!(target: "network", "Trying to connect to boot node {}", boot_node);
+            let peer_info = PeerInfo { id: boot_node.id, addr: None, account_id: None };
+            let target = ProxyTarget::Host(boot_node.host, boot_node.port);
+            self.connect_proxied(ctx, proxy.clone(), peer_info, target);
+        }
+    }
+
+    /// Binds the QUIC endpoint on the UDP port of the listening address. If it fails, the node
+    /// keeps working over TCP only.
+    fn start_quic_endpoint(&mut self, ctx: &mut Context<Self>) {
+        let (endpoint, incoming) = match transport::quic_endpoint(
+            self.config.addr,
+            &self.config.secret_key,
+        ) {
+            Ok(endpoint) => endpoint,
+            Err(err) => {
+                error!(target: "network", "Failed to start QUIC endpoint, using TCP only: {}", err);
+                return;
+            }
+        };
+        let local_addr = match endpoint.local_addr() {
+            Ok(local_addr) => local_addr,
+            Err(err) => {
+                error!(target: "network", "Failed to start QUIC endpoint, using TCP only: {}", err);
+                return;
+            }
+        };
+        if self.config.addr.is_some() {
+            info!(target: "stats", "QUIC listening at {}@{}", self.peer_id, local_addr);
+        }
+        ctx.add_message_stream(
+            incoming
+                .map(move |connecting| QuicConnection::accept(connecting, local_addr))
+                .buffer_unordered(LIMIT_PENDING_PEERS)
+                .filter_map(|connection| match connection {
+                    Ok(connection) => future::ready(Some(InboundQuicConnect { connection })),
+                    Err(err) => {
+                        debug!(target: "network", "Failed to accept QUIC connection: {}", err);
+                        future::ready(None)
+                    }
+                }),
+        );
+        self.quic_endpoint = Some(endpoint);
+    }
+
     fn num_active_outgoing_peers(&self) -> usize {
         self.active_peers
             .values()
@@ -491,8 +931,59 @@ impl PeerManagerActor {
             && !self.config.outbound_disabled
     }
 
-    fn is_inbound_allowed(&self) -> bool {
-        self.active_peers.len() + self.outgoing_peers.len() < self.config.max_num_peers as usize
+    /// Whether there is a free slot for an inbound peer, including the reserved slots if the peer
+    /// is allowed by the access rules. Connections still in their encryption handshake take slots.
+    fn is_inbound_allowed(&self, allowed: bool) -> bool {
+        let reserved_slots = if allowed { 0 } else { self.config.connection_limits.reserved_slots };
+        self.active_peers.len() + self.outgoing_peers.len() + self.inbound_handshakes
+            < self.config.max_num_peers.saturating_sub(reserved_slots) as usize
+    }
+
+    /// Whether the subnet of the public IP has as many inbound peers as it's allowed, counting the
+    /// connections that aren't consolidated yet if `count_pending`.
+    fn is_subnet_full(&self, ip: IpAddr, count_pending: bool) -> bool {
+        if !nat::is_public(ip) {
+            return false;
+        }
+        let subnet = Subnet::of(ip);
+        let num_active = self
+            .active_peers
+            .values()
+            .filter(|active_peer| {
+                active_peer.peer_type == PeerType::Inbound
+                    && Subnet::of(active_peer.remote_ip) == subnet
+            })
+            .count();
+        let num_pending = if count_pending { self.pending_inbound.count(subnet) } else { 0 };
+        num_active + num_pending >= self.config.connection_limits.max_inbound_per_subnet
+    }
+
+    /// Whether to accept an inbound connection from the address of the socket, before the
+    /// handshake.
+    fn accepts_inbound(&self, addr: SocketAddr) -> bool {
+        let reject = |reason: &str| {
+            debug!(target: "network", "Inbound connection from {} dropped: {}", addr, reason);
+            near_metrics::inc_counter_vec(&metrics::PEER_INBOUND_REJECTED, &[reason]);
+            false
+        };
+        // The node key of the peer isn't known yet, the reserved slots are checked on handshake.
+        if !self.is_inbound_allowed(true) {
+            return reject("max_capacity");
+        }
+        let ip = addr.ip();
+        if !self.access.accepts_addr(ip) {
+            return reject("access_rules");
+        }
+        if !nat::is_public(ip) {
+            return true;
+        }
+        if !self.reconnect_backoff.allows(ip, Instant::now()) {
+            return reject("reconnect_backoff");
+        }
+        if !self.access.is_allowed(None, ip) && self.is_subnet_full(ip, true) {
+            return reject("subnet_limit");
+        }
+        true
     }
 
     /// Returns single random peer with close to the highest height
@@ -529,10 +1020,21 @@ impl PeerManagerActor {
         (sent_bps, received_bps)
     }
 
-    /// Get a random peer we are not connected to from the known list.
+    /// Get a random peer we are not connected to from the known list, preferring peers with
+    /// addresses of the IP versions we listen on. Peers are weighted by their connection history,
+    /// so that peers that connected fast and served data before are tried first.
     fn sample_random_peer(&self, ignore_fn: impl Fn(&KnownPeerState) -> bool) -> Option<PeerInfo> {
-        let unconnected_peers = self.peer_store.unconnected_peers(ignore_fn);
-        unconnected_peers.choose(&mut rand::thread_rng()).cloned()
+        let (preferred, others): (Vec<_>, Vec<_>) =
+            self.peer_store.unconnected_peers(ignore_fn).into_iter().partition(|peer_info| {
+                peer_info.addr.map_or(false, |addr| self.addr_families.contains(&addr))
+            });
+        let now = to_timestamp(Utc::now());
+        let weight = |peer_info: &PeerInfo| self.peer_store.score(&peer_info.id, now);
+        preferred
+            .choose_weighted(&mut rand::thread_rng(), weight)
+            .or_else(|_| others.choose_weighted(&mut rand::thread_rng(), weight))
+            .ok()
+            .cloned()
     }
 
     /// Query current peers for more peers.
@@ -671,6 +1173,17 @@ impl PeerManagerActor {
         });
     }
 
+    /// Periodically removes the routes through peers that are not connected anymore, and stops
+    /// routing to peers that don't answer for a while.
+    fn prune_routes(&mut self, ctx: &mut Context<Self>) {
+        let active_peers = &self.active_peers;
+        self.routing_table.prune_routes(|peer_id| active_peers.contains_key(peer_id));
+
+        ctx.run_later(ROUTE_PRUNE_PERIOD, move |act, ctx| {
+            act.prune_routes(ctx);
+        });
+    }
+
     /// Select one peer and send signal to stop connection to it gracefully.
     /// Selection process:
     ///     Create a safe set of peers, and among the remaining peers select one at random.
@@ -707,6 +1220,13 @@ impl PeerManagerActor {
             }
         }
 
+        // Keep the TIER1 connections.
+        for peer_id in self.active_peers.keys() {
+            if self.tier1.is_tier1_peer(peer_id) {
+                safe_set.insert(peer_id.clone());
+            }
+        }
+
         // Find all recent connections
         let mut recent_connections = self
             .active_peers
@@ -780,6 +1300,7 @@ impl PeerManagerActor {
         for peer_id in to_unban {
             unwrap_or_error!(self.peer_store.peer_unban(&peer_id), "Failed to unban a peer");
         }
+        self.reputation.prune(Instant::now());
 
         if self.is_outbound_bootstrap_needed() {
             if let Some(peer_info) = self.sample_random_peer(|peer_state| {
@@ -798,6 +1319,7 @@ impl PeerManagerActor {
                 self.outgoing_peers.insert(peer_info.id.clone());
                 ctx.notify(OutboundTcpConnect { peer_info });
             } else {
+                self.connect_boot_node_hosts(ctx);
                 self.query_active_peers_for_more_peers(ctx);
             }
         }
@@ -856,6 +1378,97 @@ impl PeerManagerActor {
         }
     }
 
+    /// Send account data to the active peers that understand it.
+    fn broadcast_accounts_data(&self, accounts_data: Vec<SignedAccountData>) {
+        let msg = SendMessage { message: PeerMessage::SyncAccountsData(accounts_data) };
+        for active_peer in self.active_peers.values() {
+            if MessageCapability::Tier1.is_in(active_peer.capabilities) {
+                active_peer.addr.do_send(msg.clone());
+            }
+        }
+    }
+
+    /// Drops the account data of past epochs, advertises the TIER1 peers of this validator and
+    /// connects to those of the others.
+    fn update_tier1(&mut self, ctx: &mut Context<Self>) {
+        let routing_table = &mut self.routing_table;
+        self.tier1.retain(|data| {
+            routing_table
+                .get_announce(&data.account_id)
+                .map_or(false, |announce| tier1::matches_announce(data, &announce))
+        });
+
+        let own_announce = self
+            .config
+            .account_id
+            .clone()
+            .and_then(|account_id| self.routing_table.get_announce(&account_id))
+            .filter(|announce| announce.peer_id == self.peer_id);
+        if let Some(announce) = own_announce {
+            self.advertise_account_data(announce);
+            self.connect_tier1_peers(ctx);
+        }
+
+        ctx.run_later(TIER1_UPDATE_PERIOD, move |act, ctx| {
+            act.update_tier1(ctx);
+        });
+    }
+
+    /// Advertises the TIER1 peers of this validator if they changed since the last time, or to
+    /// renew the advertisement.
+    fn advertise_account_data(&mut self, announce: AnnounceAccount) {
+        if self.config.tier1_proxies.is_empty() {
+            return;
+        }
+        let now = to_timestamp(Utc::now());
+        let is_current = self.tier1.get(&announce.account_id).map_or(false, |data| {
+            tier1::matches_announce(data, &announce)
+                && data.proxies == self.config.tier1_proxies
+                && now.saturating_sub(data.timestamp) < TIER1_ADVERTISE_PERIOD.as_nanos() as u64
+        });
+        if is_current {
+            return;
+        }
+        let data = AccountData {
+            account_id: announce.account_id,
+            peer_id: self.peer_id.clone(),
+            epoch_id: announce.epoch_id,
+            proxies: self.config.tier1_proxies.clone(),
+            timestamp: now,
+        };
+        debug!(target: "network", "{:?} Advertise TIER1 peers: {:?}", self.config.account_id, data.proxies);
+        let new_data = self.tier1.insert(vec![data.sign(&self.config.secret_key)]);
+        self.broadcast_accounts_data(new_data);
+    }
+
+    /// Connects to a TIER1 peer of every other validator that there is no connection to yet.
+    fn connect_tier1_peers(&mut self, ctx: &mut Context<Self>) {
+        let mut to_connect = vec![];
+        for data in self.tier1.others(&self.peer_id) {
+            let is_connected = std::iter::once(&data.peer_id)
+                .chain(data.proxies.iter().map(|proxy| &proxy.id))
+                .any(|peer_id| {
+                    self.active_peers.contains_key(peer_id) || self.outgoing_peers.contains(peer_id)
+                });
+            if is_connected {
+                continue;
+            }
+            let proxy = data.proxies.iter().find(|proxy| {
+                proxy.id != self.peer_id
+                    && !self.peer_store.is_banned(&proxy.id)
+                    && proxy.addr.map_or(false, |addr| !self.is_blacklisted(&addr))
+            });
+            if let Some(proxy) = proxy {
+                to_connect.push(proxy.clone());
+            }
+        }
+        for peer_info in to_connect {
+            debug!(target: "network", "Connecting to TIER1 peer {}", peer_info);
+            self.outgoing_peers.insert(peer_info.id.clone());
+            ctx.notify(OutboundTcpConnect { peer_info });
+        }
+    }
+
     /// Send message to peer that belong to our active set
     /// Return whether the message is sent or not.
     fn send_message(
@@ -919,22 +1532,46 @@ impl PeerManagerActor {
         if let PeerIdOrHash::PeerId(target) = &msg.target {
             if target == &self.peer_id {
                 debug!(target: "network", "{:?} Drop signed message to myself ({:?}). Message: {:?}.", self.config.account_id, self.peer_id, msg);
+                near_metrics::inc_counter_vec(&metrics::ROUTED_MESSAGE_DROPPED, &["to_self"]);
                 return false;
             }
         }
 
-        match self.routing_table.find_route(&msg.target) {
+        let route = match self.tier1_route(&msg) {
+            Some(peer_id) => Ok(peer_id),
+            // Approvals, chunks and other messages that don't expect a response are always sent,
+            // they don't tell whether the target is reachable.
+            None if msg.expect_response() && self.routing_table.is_backed_off(&msg.target) => {
+                Err(FindRouteError::Backoff)
+            }
+            None => self.routing_table.find_route(&msg.target),
+        };
+        match route {
             Ok(peer_id) => {
                 // Remember if we expect a response for this message.
                 if msg.author == self.peer_id && msg.expect_response() {
                     trace!(target: "network", "initiate route back {:?}", msg);
                     self.routing_table.add_route_back(msg.hash(), self.peer_id.clone());
+                    if let PeerIdOrHash::PeerId(target) = &msg.target {
+                        self.routing_table.waiting_response(target);
+                    }
                 }
 
-                self.send_message(ctx, peer_id, PeerMessage::Routed(msg))
+                let sent = self.send_message(ctx, peer_id, PeerMessage::Routed(msg));
+                if !sent {
+                    near_metrics::inc_counter_vec(
+                        &metrics::ROUTED_MESSAGE_DROPPED,
+                        &["next_hop_not_connected"],
+                    );
+                }
+                sent
             }
             Err(find_route_error) => {
                 // TODO(MarX, #1369): Message is dropped here. Define policy for this case.
+                near_metrics::inc_counter_vec(
+                    &metrics::ROUTED_MESSAGE_DROPPED,
+                    &[find_route_error.reason()],
+                );
                 self.network_metrics.inc(
                     NetworkMetrics::peer_message_dropped(strum::AsStaticRef::as_static(&msg.body))
                         .as_str(),
@@ -952,6 +1589,21 @@ impl PeerManagerActor {
         }
     }
 
+    /// Active TIER1 connection to send the message over, to its target or to one of its proxies.
+    fn tier1_route(&self, msg: &RoutedMessage) -> Option<PeerId> {
+        if !tier1::is_tier1_message(&msg.body) {
+            return None;
+        }
+        match &msg.target {
+            PeerIdOrHash::PeerId(target) => self
+                .tier1
+                .peers_of(target)
+                .into_iter()
+                .find(|peer_id| self.active_peers.contains_key(peer_id)),
+            PeerIdOrHash::Hash(_) => None,
+        }
+    }
+
     /// Route message to target peer.
     /// Return whether the message is sent or not.
     fn send_message_to_peer(&mut self, ctx: &mut Context<Self>, msg: RawRoutedMessage) -> bool {
@@ -972,6 +1624,10 @@ impl PeerManagerActor {
             Err(find_route_error) => {
                 // TODO(MarX, #1369): Message is dropped here. Define policy for this case.
                 near_metrics::inc_counter(&metrics::DROP_MESSAGE_UNKNOWN_ACCOUNT);
+                near_metrics::inc_counter_vec(
+                    &metrics::ROUTED_MESSAGE_DROPPED,
+                    &[find_route_error.reason()],
+                );
                 debug!(target: "network", "{:?} Drop message to {} Reason {:?}. Message {:?}",
                        self.config.account_id,
                        account_id,
@@ -1073,6 +1729,19 @@ impl PeerManagerActor {
                     addr: None,
                 })
                 .collect(),
+            peer_protocol_versions: self.peer_protocol_versions(),
+            peer_clock_skews: self
+                .active_peers
+                .iter()
+                .filter_map(|(peer_id, active_peer)| {
+                    active_peer.clock_skew_millis.map(|skew_millis| (peer_id.clone(), skew_millis))
+                })
+                .collect(),
+            peer_transports: self
+                .active_peers
+                .iter()
+                .map(|(peer_id, active_peer)| (peer_id.clone(), active_peer.transport))
+                .collect(),
             #[cfg(feature = "metric_recorder")]
             metric_recorder: self.metric_recorder.clone(),
             peer_counter: self.peer_counter.load(Ordering::SeqCst),
@@ -1094,6 +1763,18 @@ pub struct IncomingCrutch {
     listener: TcpListener,
 }
 
+/// Binds a TCP listener. With `v6_only` an IPv6 listener doesn't accept IPv4 connections, so that
+/// a separate IPv4 listener can use the same port.
+fn bind_listener(addr: SocketAddr, v6_only: bool) -> std::io::Result<TcpListener> {
+    let builder = if addr.is_ipv4() { TcpBuilder::new_v4()? } else { TcpBuilder::new_v6()? };
+    if addr.is_ipv6() {
+        builder.only_v6(v6_only)?;
+    }
+    #[cfg(unix)]
+    builder.reuse_address(true)?;
+    TcpListener::from_std(builder.bind(addr)?.listen(1024)?)
+}
+
 impl Stream for IncomingCrutch {
     type Item = std::io::Result<TcpStream>;
 
@@ -1109,37 +1790,56 @@ impl Actor for PeerManagerActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        // Start server if address provided.
-        if let Some(server_addr) = self.config.addr {
+        match self.config.addr {
+            Some(addr) if self.config.port_mapping != PortMapping::Disabled => {
+                let mut protocols = vec![nat::Protocol::Tcp];
+                if uses_quic(&self.config) {
+                    protocols.push(nat::Protocol::Udp);
+                }
+                nat::spawn_port_mapping(
+                    self.config.port_mapping,
+                    addr.port(),
+                    protocols,
+                    ctx.address().recipient(),
+                );
+            }
+            _ => {}
+        }
+        // Start server at every provided address.
+        let listen_addrs = self.config.listen_addrs();
+        // Let IPv4 and IPv6 listeners share the port.
+        let v6_only = listen_addrs.iter().any(|addr| addr.is_ipv4());
+        for server_addr in listen_addrs {
             // TODO: for now crashes if server didn't start.
+            let listener = bind_listener(server_addr, v6_only)
+                .unwrap_or_else(|err| panic!("Failed to listen at {}: {}", server_addr, err));
+            let incoming = IncomingCrutch { listener };
+            info!(target: "stats", "Server listening at {}@{}", self.peer_id, server_addr);
 
             let pending_incoming_connections_counter =
                 self.pending_incoming_connections_counter.clone();
             let peer_counter = self.peer_counter.clone();
             let max_num_peers: usize = self.config.max_num_peers as usize;
 
-            ctx.spawn(TcpListener::bind(server_addr).into_actor(self).then(
-                move |listener, act, ctx| {
-                    let listener = listener.unwrap();
-                    let incoming = IncomingCrutch { listener };
-                    info!(target: "stats", "Server listening at {}@{}", act.peer_id, server_addr);
-
-                    ctx.add_message_stream(incoming.filter_map(move |conn| {
-                        if let Ok(conn) = conn {
-                            if pending_incoming_connections_counter.load(Ordering::SeqCst)
-                                + peer_counter.load(Ordering::SeqCst)
-                                < max_num_peers + LIMIT_PENDING_PEERS
-                            {
-                                pending_incoming_connections_counter.fetch_add(1, Ordering::SeqCst);
-                                return future::ready(Some(InboundTcpConnect::new(conn)));
-                            }
-                        }
+            ctx.add_message_stream(incoming.filter_map(move |conn| {
+                if let Ok(conn) = conn {
+                    if pending_incoming_connections_counter.load(Ordering::SeqCst)
+                        + peer_counter.load(Ordering::SeqCst)
+                        < max_num_peers + LIMIT_PENDING_PEERS
+                    {
+                        pending_incoming_connections_counter.fetch_add(1, Ordering::SeqCst);
+                        return future::ready(Some(InboundTcpConnect::new(conn)));
+                    }
+                }
 
-                        future::ready(None)
-                    }));
-                    actix::fut::ready(())
-                },
-            ));
+                future::ready(None)
+            }));
+        }
+
+        if uses_quic(&self.config) {
+            self.start_quic_endpoint(ctx);
+        } else if self.config.transport == Transport::Quic {
+            warn!(target: "network", "Not using QUIC, since the node key isn't an ED25519 key");
         }
 
         // Periodically push network information to client
@@ -1151,6 +1851,12 @@ impl Actor for PeerManagerActor {
         // Start active peer stats querying.
         self.monitor_peer_stats(ctx);
 
+        // Keep the TIER1 connections to other validators.
+        self.update_tier1(ctx);
+
+        // Drop stale routes.
+        self.prune_routes(ctx);
+
         // Periodically ping all peers to determine latencies between pair of peers.
         #[cfg(feature = "metric_recorder")]
         self.ping_all_peers(ctx);
@@ -1243,6 +1949,10 @@ impl Handler<NetworkRequests> for PeerManagerActor {
                 self.try_ban_peer(ctx, &peer_id, ban_reason);
                 NetworkResponses::NoResponse
             }
+            NetworkRequests::ReportMisbehavior { peer_id, misbehavior } => {
+                self.report_misbehavior(ctx, &peer_id, misbehavior);
+                NetworkResponses::NoResponse
+            }
             NetworkRequests::AnnounceAccount(announce_account) => {
                 self.announce_account(ctx, announce_account);
                 NetworkResponses::NoResponse
@@ -1500,6 +2210,29 @@ impl Handler<NetworkRequests> for PeerManagerActor {
                 );
                 NetworkResponses::NoResponse
             }
+            NetworkRequests::SyncAccountsData { peer_id, accounts_data } => {
+                if !accounts_data.iter().all(|signed_data| signed_data.verify()) {
+                    self.try_ban_peer(ctx, &peer_id, ReasonForBan::InvalidSignature);
+                    return NetworkResponses::NoResponse;
+                }
+                // Data for unknown announcements is dropped, the validator advertises it again.
+                let routing_table = &mut self.routing_table;
+                let accounts_data = accounts_data
+                    .into_iter()
+                    .filter(|signed_data| {
+                        let data = &signed_data.data;
+                        data.proxies.len() <= MAX_TIER1_PROXIES
+                            && routing_table
+                                .get_announce(&data.account_id)
+                                .map_or(false, |announce| tier1::matches_announce(data, &announce))
+                    })
+                    .collect();
+                let new_data = self.tier1.insert(accounts_data);
+                if !new_data.is_empty() {
+                    self.broadcast_accounts_data(new_data);
+                }
+                NetworkResponses::NoResponse
+            }
             NetworkRequests::RequestUpdateNonce(peer_id, edge_info) => {
                 if Edge::partial_verify(self.peer_id.clone(), peer_id.clone(), &edge_info) {
                     if let Some(cur_edge) =
@@ -1549,6 +2282,19 @@ impl Handler<NetworkRequests> for PeerManagerActor {
                 let (pings, pongs) = self.routing_table.fetch_ping_pong();
                 NetworkResponses::PingPongInfo { pings, pongs }
             }
+            NetworkRequests::FetchPeerStore => NetworkResponses::PeerStore(
+                self.peer_store.iter().map(|(_, peer_state)| peer_state.into()).collect(),
+            ),
+            NetworkRequests::FetchPeerReputation => NetworkResponses::PeerReputation(
+                self.reputation.view(Instant::now(), |peer_id| self.peer_store.is_banned(peer_id)),
+            ),
+            NetworkRequests::FetchPeerAccess => NetworkResponses::PeerAccess(self.access.view()),
+            NetworkRequests::UpdatePeerAccess(change) => {
+                info!(target: "network", "Updating peer access rules: {:?}", change);
+                self.access.apply(change);
+                self.enforce_peer_access();
+                NetworkResponses::PeerAccess(self.access.view())
+            }
         }
     }
 }
@@ -1559,13 +2305,61 @@ impl Handler<InboundTcpConnect> for PeerManagerActor {
     fn handle(&mut self, msg: InboundTcpConnect, ctx: &mut Self::Context) {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("inbound tcp connect".into());
-        if self.is_inbound_allowed() {
-            self.try_connect_peer(ctx.address(), msg.stream, PeerType::Inbound, None, None);
-        } else {
-            // TODO(1896): Gracefully drop inbound connection for other peer.
-            debug!(target: "network", "Inbound connection dropped (network at max capacity).");
+        // A socket without a peer address is already closed.
+        let addr = match msg.stream.peer_addr() {
+            Ok(addr) if self.accepts_inbound(addr) => addr,
+            _ => {
+                self.pending_incoming_connections_counter.fetch_sub(1, Ordering::SeqCst);
+                return;
+            }
+        };
+        // The connection stays pending until the handshake is over, so that peers can't hold more
+        // handshakes open than there are free slots, or than their subnet is allowed.
+        self.inbound_handshakes += 1;
+        let pending = self.pending_inbound.add(addr.ip());
+        TcpConnection::accept(
+            msg.stream,
+            self.config.secret_key.clone(),
+            self.config.encryption,
+            self.config.handshake_timeout,
+        )
+        .into_actor(self)
+        .then(move |res, act, ctx| {
+            act.inbound_handshakes -= 1;
+            act.pending_incoming_connections_counter.fetch_sub(1, Ordering::SeqCst);
+            // The peer actor counts the connection from now on.
+            drop(pending);
+            match res {
+                Ok(connection) => act.try_connect_peer(
+                    ctx.address(),
+                    PeerConnection::Tcp(connection),
+                    PeerType::Inbound,
+                    None,
+                    None,
+                ),
+                Err(err) => debug!(target: "network", "Failed to accept connection: {}", err),
+            }
+            actix::fut::ready(())
+        })
+        .spawn(ctx);
+    }
+}
+
+impl Handler<InboundQuicConnect> for PeerManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: InboundQuicConnect, ctx: &mut Self::Context) {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new("inbound quic connect".into());
+        if self.accepts_inbound(msg.connection.remote_addr()) {
+            self.try_connect_peer(
+                ctx.address(),
+                PeerConnection::Quic(msg.connection),
+                PeerType::Inbound,
+                None,
+                None,
+            );
         }
-        self.pending_incoming_connections_counter.fetch_sub(1, Ordering::SeqCst);
     }
 }
 
@@ -1577,37 +2371,55 @@ impl Handler<OutboundTcpConnect> for PeerManagerActor {
         let _d = DelayDetector::new("outbound tcp connect".into());
         debug!(target: "network", "Trying to connect to {}", msg.peer_info);
         if let Some(addr) = msg.peer_info.addr {
-            Resolver::from_registry()
-                .send(ConnectAddr(addr))
-                .into_actor(self)
-                .then(move |res, act, ctx| match res {
-                    Ok(res) => match res {
-                        Ok(stream) => {
-                            debug!(target: "network", "Connecting to {}", msg.peer_info);
-                            let edge_info = act.propose_edge(msg.peer_info.id.clone(), None);
-
-                            act.try_connect_peer(
-                                ctx.address(),
-                                stream,
-                                PeerType::Outbound,
-                                Some(msg.peer_info),
-                                Some(edge_info),
-                            );
-                            actix::fut::ready(())
-                        }
-                        Err(err) => {
-                            info!(target: "network", "Error connecting to {}: {}", addr, err);
-                            act.outgoing_peers.remove(&msg.peer_info.id);
+            if !self.access.accepts(PeerType::Outbound, &msg.peer_info.id, addr.ip()) {
+                debug!(target: "network", "Not connecting to {} denied by access rules", msg.peer_info);
+                self.outgoing_peers.remove(&msg.peer_info.id);
+                return;
+            }
+            if let Err(err) = self.peer_store.connection_attempted(&msg.peer_info.id) {
+                error!(target: "network", "Failed to save peer data: {}", err);
+            }
+            // QUIC can't go through the proxy.
+            if let Some(proxy) = self.config.proxy.clone() {
+                self.connect_proxied(ctx, proxy, msg.peer_info, ProxyTarget::Addr(addr));
+                return;
+            }
+            // Peers whose record doesn't list QUIC are connected to over TCP right away.
+            let supports_quic =
+                self.peer_store.record(&msg.peer_info.id).map_or(true, |signed_record| {
+                    signed_record.record.has_capability(PeerCapability::Quic)
+                });
+            match self.quic_endpoint.clone().filter(|_| supports_quic) {
+                Some(endpoint) => {
+                    // Don't block the actor while waiting for the QUIC handshake, unlike TCP it can
+                    // take until the timeout if the peer doesn't listen for QUIC.
+                    QuicConnection::connect(endpoint, addr, msg.peer_info.id.clone())
+                        .into_actor(self)
+                        .then(move |res, act, ctx| {
+                            match res {
+                                Ok(connection) => {
+                                    debug!(target: "network", "Connecting to {} over QUIC", msg.peer_info);
+                                    let edge_info =
+                                        act.propose_edge(msg.peer_info.id.clone(), None);
+                                    act.try_connect_peer(
+                                        ctx.address(),
+                                        PeerConnection::Quic(connection),
+                                        PeerType::Outbound,
+                                        Some(msg.peer_info),
+                                        Some(edge_info),
+                                    );
+                                }
+                                Err(err) => {
+                                    debug!(target: "network", "Error connecting to {} over QUIC, trying TCP: {}", addr, err);
+                                    act.connect_tcp(ctx, msg.peer_info, addr);
+                                }
+                            }
                             actix::fut::ready(())
-                        }
-                    },
-                    Err(err) => {
-                        info!(target: "network", "Error connecting to {}: {}", addr, err);
-                        act.outgoing_peers.remove(&msg.peer_info.id);
-                        actix::fut::ready(())
-                    }
-                })
-                .wait(ctx);
+                        })
+                        .spawn(ctx);
+                }
+                None => self.connect_tcp(ctx, msg.peer_info, addr),
+            }
         } else {
             warn!(target: "network", "Trying to connect to peer with no public address: {:?}", msg.peer_info);
         }
@@ -1631,6 +2443,11 @@ impl Handler<Consolidate> for PeerManagerActor {
             return ConsolidateResponse::Reject;
         }
 
+        if !self.access.accepts(msg.peer_type, &msg.peer_info.id, msg.remote_ip) {
+            debug!(target: "network", "Dropping connection from peer denied by access rules: {:?}", msg.peer_info);
+            return ConsolidateResponse::Reject;
+        }
+
         // We already connected to this peer.
         if self.active_peers.contains_key(&msg.peer_info.id) {
             debug!(target: "network", "Dropping handshake (Active Peer). {:?} {:?}", self.peer_id, msg.peer_info.id);
@@ -1647,10 +2464,19 @@ impl Handler<Consolidate> for PeerManagerActor {
             }
         }
 
-        if msg.peer_type == PeerType::Inbound && !self.is_inbound_allowed() {
-            // TODO(1896): Gracefully drop inbound connection for other peer.
-            debug!(target: "network", "Inbound connection dropped (network at max capacity).");
-            return ConsolidateResponse::Reject;
+        if msg.peer_type == PeerType::Inbound && !self.tier1.is_tier1_peer(&msg.peer_info.id) {
+            let allowed = self.access.is_allowed(Some(&msg.peer_info.id), msg.remote_ip);
+            if !self.is_inbound_allowed(allowed) {
+                // TODO(1896): Gracefully drop inbound connection for other peer.
+                debug!(target: "network", "Inbound connection dropped (network at max capacity).");
+                near_metrics::inc_counter_vec(&metrics::PEER_INBOUND_REJECTED, &["max_capacity"]);
+                return ConsolidateResponse::Reject;
+            }
+            if !allowed && self.is_subnet_full(msg.remote_ip, false) {
+                debug!(target: "network", "Inbound connection dropped (too many connections from subnet).");
+                near_metrics::inc_counter_vec(&metrics::PEER_INBOUND_REJECTED, &["subnet_limit"]);
+                return ConsolidateResponse::Reject;
+            }
         }
 
         if msg.other_edge_info.nonce == 0 {
@@ -1690,6 +2516,10 @@ impl Handler<Consolidate> for PeerManagerActor {
             },
             edge_info,
             msg.peer_type,
+            msg.protocol_version,
+            msg.capabilities,
+            msg.transport,
+            msg.remote_ip,
             msg.actor,
             ctx,
         );
@@ -1724,7 +2554,42 @@ impl Handler<PeersRequest> for PeerManagerActor {
     fn handle(&mut self, _msg: PeersRequest, _ctx: &mut Self::Context) -> Self::Result {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("peers request".into());
-        PeerList { peers: self.peer_store.healthy_peers(self.config.max_send_peers) }
+        let mut peers = self.peer_store.healthy_peers(self.config.max_send_peers);
+        // Peers only learn the address we are connected to them by, so advertise the others.
+        if !self.config.additional_addrs.is_empty() {
+            peers.extend(
+                self.advertised_listen_addrs()
+                    .into_iter()
+                    .map(|addr| PeerInfo::new(self.peer_id.clone(), addr)),
+            );
+        }
+        let records = peers
+            .iter()
+            .filter(|peer_info| peer_info.id != self.peer_id)
+            .filter_map(|peer_info| self.peer_store.record(&peer_info.id).cloned())
+            .chain(self.own_record.clone())
+            .collect();
+        PeerList { peers, records }
+    }
+}
+
+impl Handler<ObservedAddr> for PeerManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ObservedAddr, _ctx: &mut Self::Context) {
+        if self.active_peers.contains_key(&msg.peer_id) {
+            self.observed_addrs.insert(msg.peer_id, msg.addr.ip());
+            self.update_external_addr();
+        }
+    }
+}
+
+impl Handler<PortMapped> for PeerManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: PortMapped, _ctx: &mut Self::Context) {
+        self.mapped_addr = msg.0;
+        self.update_external_addr();
     }
 }
 
@@ -1734,9 +2599,20 @@ impl Handler<PeersResponse> for PeerManagerActor {
     fn handle(&mut self, msg: PeersResponse, _ctx: &mut Self::Context) {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("peers response".into());
+        unwrap_or_error!(
+            self.peer_store.add_peer_records(
+                msg.records
+                    .into_iter()
+                    .filter(|signed_record| signed_record.record.peer_id != self.peer_id)
+                    .collect(),
+                self.addr_families,
+            ),
+            "Fail to update peer store"
+        );
         unwrap_or_error!(
             self.peer_store.add_indirect_peers(
-                msg.peers.into_iter().filter(|peer_info| peer_info.id != self.peer_id).collect()
+                msg.peers.into_iter().filter(|peer_info| peer_info.id != self.peer_id).collect(),
+                self.addr_families,
             ),
             "Fail to update peer store"
         );
@@ -1754,6 +2630,7 @@ impl Handler<RoutedMessageFrom> for PeerManagerActor {
             format!("routed message from {}", strum::AsStaticRef::as_static(&msg.msg.body)).into(),
         );
         let RoutedMessageFrom { mut msg, from } = msg;
+        self.routing_table.route_alive(&msg.author);
 
         if msg.expect_response() {
             trace!(target: "network", "Received peer message that requires route back: {}", PeerMessage::Routed(msg.clone()));
@@ -1775,6 +2652,7 @@ impl Handler<RoutedMessageFrom> for PeerManagerActor {
                 self.send_signed_message_to_peer(ctx, msg);
             } else {
                 warn!(target: "network", "Message dropped because TTL reached 0. Message: {:?} From: {:?}", msg, from);
+                near_metrics::inc_counter_vec(&metrics::ROUTED_MESSAGE_DROPPED, &["ttl"]);
             }
             false
         }
@@ -1827,6 +2705,18 @@ impl Handler<PeerRequest> for PeerManagerActor {
                 }
                 PeerResponse::NoResponse
             }
+            PeerRequest::ReceivedUsefulData(peer_id) => {
+                if let Err(err) = self.peer_store.peer_useful(&peer_id) {
+                    error!(target: "network", "Failed to save peer data: {}", err);
+                }
+                PeerResponse::NoResponse
+            }
+            PeerRequest::ClockSkew(peer_id, skew_millis) => {
+                if let Some(active_peer) = self.active_peers.get_mut(&peer_id) {
+                    active_peer.clock_skew_millis = Some(skew_millis);
+                }
+                PeerResponse::NoResponse
+            }
         }
     }
 }
@@ -1840,3 +2730,13 @@ impl Handler<PeerMessageMetadata> for PeerManagerActor {
         self.metric_recorder.handle_peer_message(msg);
     }
 }
+
+#[cfg(feature = "adversarial")]
+impl Handler<SetChaosRules> for PeerManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetChaosRules, _ctx: &mut Self::Context) {
+        debug!(target: "network", "Chaos rules set: {:?}", msg.0);
+        self.chaos.set_rules(msg.0);
+    }
+}
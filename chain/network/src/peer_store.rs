@@ -3,10 +3,11 @@ use std::collections::{
     HashMap,
 };
 use std::convert::TryInto;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Instant;
 
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::Utc;
 use log::{debug, error};
 use rand::seq::SliceRandom;
@@ -14,15 +15,19 @@ use rand::thread_rng;
 
 use near_primitives::network::PeerId;
 use near_primitives::utils::to_timestamp;
-use near_store::{ColPeers, Store};
+use near_store::{ColPeerStats, ColPeers, Store};
 
-use crate::types::{KnownPeerState, KnownPeerStatus, NetworkConfig, PeerInfo, ReasonForBan};
+use crate::types::{
+    KnownPeerState, KnownPeerStatus, NetworkConfig, PeerInfo, ReasonForBan, SignedPeerRecord,
+};
 
 /// Level of trust we have about a new (PeerId, Addr) pair.
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum TrustLevel {
     /// We learn about it from other peers.
     Indirect,
+    /// The peer signed a record with this address, which other peers relayed.
+    Recorded,
     /// Responding node at addr claims to possess PeerId.
     Direct,
     /// Responding peer proved to have SecretKey associated with this PeerID.
@@ -44,6 +49,71 @@ impl VerifiedPeer {
     }
 }
 
+/// IP versions of the addresses the node listens on. Peers are preferably connected to over these.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct AddrFamilies {
+    pub ipv4: bool,
+    pub ipv6: bool,
+}
+
+impl AddrFamilies {
+    /// Families of the given listening addresses. An unspecified IPv6 address accepts IPv4
+    /// connections too unless there is a separate IPv4 address. Without any address both are used.
+    pub fn of(addrs: &[SocketAddr]) -> Self {
+        let ipv4 = addrs.iter().any(|addr| addr.is_ipv4());
+        let ipv6 = addrs.iter().any(|addr| addr.is_ipv6());
+        let dual_stack =
+            !ipv4 && addrs.iter().any(|addr| addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        if addrs.is_empty() {
+            AddrFamilies { ipv4: true, ipv6: true }
+        } else {
+            AddrFamilies { ipv4: ipv4 || dual_stack, ipv6 }
+        }
+    }
+
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        match addr.ip() {
+            IpAddr::V4(_) => self.ipv4,
+            // IPv4 peers accepted on a dual stack socket have IPv4-mapped addresses.
+            IpAddr::V6(ip) => match ip.segments() {
+                [0, 0, 0, 0, 0, 0xffff, _, _] => self.ipv4,
+                _ => self.ipv6,
+            },
+        }
+    }
+}
+
+/// Nanoseconds in a day, the unit of the timestamps.
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Connection history of a peer, kept across restarts to connect to the peers that served the
+/// node well before first.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct PeerStats {
+    /// Outbound connections attempted.
+    pub connection_attempts: u32,
+    /// Connections established, inbound or outbound, after an outbound attempt.
+    pub connections: u32,
+    /// Moving average of the time to connect and handshake, in milliseconds.
+    pub latency_millis: Option<u32>,
+    /// Last time the peer sent blocks, chunks, transactions or other data for the client.
+    pub last_useful: Option<u64>,
+}
+
+impl PeerStats {
+    /// Weight of the peer among the peers to connect to, in (0, 1]. Peers without history get
+    /// the weight of a peer that connects half of the time in a second and was never useful.
+    pub fn score(&self, now: u64) -> f64 {
+        let success_rate =
+            (self.connections as f64 + 1.0) / (self.connection_attempts as f64 + 2.0);
+        let latency = self.latency_millis.map_or(1.0, |latency| latency as f64 / 1000.0);
+        let recency = self.last_useful.map_or(0.25, |last_useful| {
+            1.0 / (1.0 + now.saturating_sub(last_useful) as f64 / DAY as f64)
+        });
+        success_rate.min(1.0) / (1.0 + latency) * recency
+    }
+}
+
 /// Known peers store, maintaining cache of known peers and connection to storage to save/load them.
 pub struct PeerStore {
     store: Arc<Store>,
@@ -52,6 +122,12 @@ pub struct PeerStore {
     // It can happens that some peers don't have known address, so
     // they will not be present in this list, otherwise they will be present.
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
+    /// Newest record of each peer, not persisted since peers send them again.
+    records: HashMap<PeerId, SignedPeerRecord>,
+    /// Connection history of the known peers.
+    stats: HashMap<PeerId, PeerStats>,
+    /// Start of the outbound connection attempts that are not established yet.
+    attempts: HashMap<PeerId, Instant>,
 }
 
 impl PeerStore {
@@ -109,7 +185,23 @@ impl PeerStore {
                 }
             }
         }
-        Ok(PeerStore { store, peer_states, addr_peers })
+        let mut stats = HashMap::default();
+        // History of peers that are not known any more is dropped with them.
+        for (key, value) in store.iter(ColPeerStats) {
+            let key: Vec<u8> = key.into();
+            let peer_id: PeerId = key.try_into()?;
+            if peer_states.contains_key(&peer_id) {
+                stats.insert(peer_id, PeerStats::try_from_slice(&value)?);
+            }
+        }
+        Ok(PeerStore {
+            store,
+            peer_states,
+            addr_peers,
+            records: HashMap::default(),
+            stats,
+            attempts: HashMap::default(),
+        })
     }
 
     pub fn len(&self) -> usize {
@@ -136,9 +228,61 @@ impl PeerStore {
         entry.status = KnownPeerStatus::Connected;
         let mut store_update = self.store.store_update();
         store_update.set_ser(ColPeers, &peer_info.id.try_to_vec()?, entry)?;
+        if let Some(started) = self.attempts.remove(&peer_info.id) {
+            let latency = started.elapsed().as_millis().min(u32::MAX as u128) as u32;
+            let stats = self.stats.entry(peer_info.id.clone()).or_default();
+            stats.connections += 1;
+            stats.latency_millis = Some(
+                stats
+                    .latency_millis
+                    .map_or(latency, |average| ((average as u64 * 4 + latency as u64) / 5) as u32),
+            );
+            store_update.set_ser(ColPeerStats, &peer_info.id.try_to_vec()?, stats)?;
+        }
         store_update.commit().map_err(|err| err.into())
     }
 
+    /// Records an outbound connection attempt to the peer, which succeeds if the peer connects
+    /// before the next one.
+    pub fn connection_attempted(
+        &mut self,
+        peer_id: &PeerId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.peer_states.contains_key(peer_id) {
+            return Ok(());
+        }
+        self.attempts.insert(peer_id.clone(), Instant::now());
+        let stats = self.stats.entry(peer_id.clone()).or_default();
+        stats.connection_attempts += 1;
+        let mut store_update = self.store.store_update();
+        store_update.set_ser(ColPeerStats, &peer_id.try_to_vec()?, stats)?;
+        store_update.commit().map_err(|err| err.into())
+    }
+
+    /// Records that the peer sent data for the client.
+    pub fn peer_useful(&mut self, peer_id: &PeerId) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.peer_states.contains_key(peer_id) {
+            return Ok(());
+        }
+        let stats = self.stats.entry(peer_id.clone()).or_default();
+        stats.last_useful = Some(to_timestamp(Utc::now()));
+        let mut store_update = self.store.store_update();
+        store_update.set_ser(ColPeerStats, &peer_id.try_to_vec()?, stats)?;
+        store_update.commit().map_err(|err| err.into())
+    }
+
+    /// Connection history of the peer, if it's known.
+    pub fn stats(&self, peer_id: &PeerId) -> Option<&PeerStats> {
+        self.stats.get(peer_id)
+    }
+
+    /// Weight of the peer among the peers to connect to, see `PeerStats::score`.
+    pub fn score(&self, peer_id: &PeerId, now: u64) -> f64 {
+        self.stats
+            .get(peer_id)
+            .map_or_else(|| PeerStats::default().score(now), |stats| stats.score(now))
+    }
+
     pub fn peer_disconnected(
         &mut self,
         peer_id: &PeerId,
@@ -245,6 +389,11 @@ impl PeerStore {
         let mut store_update = self.store.store_update();
         for peer_id in to_remove {
             self.peer_states.remove(&peer_id);
+            self.records.remove(&peer_id);
+            self.attempts.remove(&peer_id);
+            if self.stats.remove(&peer_id).is_some() {
+                store_update.delete(ColPeerStats, &peer_id.try_to_vec()?);
+            }
             store_update.delete(ColPeers, &peer_id.try_to_vec()?);
         }
         store_update.commit().map_err(|err| err.into())
@@ -352,12 +501,104 @@ impl PeerStore {
         Ok(())
     }
 
+    /// Add peers learned from other nodes. The address of a peer is replaced if the known one is
+    /// of a family not in `families` and was only learned from other nodes too, but the new one is
+    /// of a family in `families`.
     pub fn add_indirect_peers(
         &mut self,
         peers: Vec<PeerInfo>,
+        families: AddrFamilies,
     ) -> Result<(), Box<dyn std::error::Error>> {
         for peer_info in peers {
-            self.add_peer(peer_info, TrustLevel::Indirect)?;
+            match peer_info.addr {
+                Some(peer_addr)
+                    if families.contains(&peer_addr)
+                        && !self.addr_peers.contains_key(&peer_addr)
+                        && self.has_unpreferred_addr(&peer_info.id, families) =>
+                {
+                    self.update_peer_info(peer_info, peer_addr, TrustLevel::Indirect)?;
+                }
+                _ => self.add_peer(peer_info, TrustLevel::Indirect)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the peer is known by an address of a family not in `families` that only other
+    /// nodes told about. Addresses the peer connected from, answered at or signed are kept.
+    fn has_unpreferred_addr(&self, peer_id: &PeerId, families: AddrFamilies) -> bool {
+        let addr = match self.peer_states.get(peer_id).and_then(|state| state.peer_info.addr) {
+            Some(addr) => addr,
+            None => return false,
+        };
+        !families.contains(&addr)
+            && self
+                .addr_peers
+                .get(&addr)
+                .map_or(true, |verified_peer| verified_peer.trust_level == TrustLevel::Indirect)
+    }
+
+    /// Newest known record of the peer.
+    pub fn record(&self, peer_id: &PeerId) -> Option<&SignedPeerRecord> {
+        self.records.get(peer_id)
+    }
+
+    /// Add records with verified signatures, replacing older records of the same peers. The peer
+    /// is known by the first recorded address of a family in `families`, unless it's connected at
+    /// one of the recorded addresses already, or the address belongs to another connected or
+    /// recorded peer. Addresses of recorded peers aren't replaced by ones other peers relay
+    /// unsigned.
+    pub fn add_peer_records(
+        &mut self,
+        records: Vec<SignedPeerRecord>,
+        families: AddrFamilies,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for signed_record in records {
+            let peer_id = signed_record.record.peer_id.clone();
+            if let Some(known_record) = self.records.get(&peer_id) {
+                if !signed_record.supersedes(known_record) {
+                    continue;
+                }
+            }
+            let record = &signed_record.record;
+            let addr = record
+                .addrs
+                .iter()
+                .find(|addr| families.contains(addr))
+                .or_else(|| record.addrs.first())
+                .cloned();
+            let peer_info =
+                PeerInfo { id: peer_id.clone(), addr, account_id: record.account_id.clone() };
+            let connected_at_recorded_addr = self
+                .peer_states
+                .get(&peer_id)
+                .and_then(|state| state.peer_info.addr)
+                .map_or(false, |current_addr| {
+                    record.addrs.contains(&current_addr)
+                        && self.addr_peers.get(&current_addr).map_or(false, |verified_peer| {
+                            verified_peer.trust_level == TrustLevel::Signed
+                        })
+                });
+            self.records.insert(peer_id.clone(), signed_record);
+
+            match addr {
+                Some(_) if connected_at_recorded_addr => {}
+                Some(addr) => {
+                    let taken = self.addr_peers.get(&addr).map_or(false, |verified_peer| {
+                        verified_peer.peer_id != peer_id
+                            && (verified_peer.trust_level == TrustLevel::Signed
+                                || verified_peer.trust_level == TrustLevel::Recorded)
+                    });
+                    if !taken {
+                        self.update_peer_info(peer_info, addr, TrustLevel::Recorded)?;
+                    }
+                }
+                None => {
+                    if !self.peer_states.contains_key(&peer_id) {
+                        self.peer_states.insert(peer_id, KnownPeerState::new(peer_info));
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -378,6 +619,7 @@ mod test {
     use near_store::test_utils::create_test_store;
 
     use super::*;
+    use crate::types::PeerRecord;
 
     fn get_peer_id(seed: String) -> PeerId {
         SecretKey::from_seed(KeyType::ED25519, seed.as_str()).public_key().into()
@@ -419,6 +661,41 @@ mod test {
         }
     }
 
+    #[test]
+    fn peer_stats_store() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_store_stats").tempdir().unwrap();
+        let peer_info_a = gen_peer_info(0);
+        let peer_info_b = gen_peer_info(1);
+        let boot_nodes = vec![peer_info_a.clone(), peer_info_b.clone()];
+        {
+            let store = create_store(tmp_dir.path().to_str().unwrap());
+            let mut peer_store = PeerStore::new(store, &boot_nodes).unwrap();
+            peer_store.connection_attempted(&peer_info_a.id).unwrap();
+            peer_store.peer_connected(&peer_info_a).unwrap();
+            peer_store.peer_useful(&peer_info_a.id).unwrap();
+            peer_store.peer_disconnected(&peer_info_a.id).unwrap();
+            // Connections that fail never connect.
+            peer_store.connection_attempted(&peer_info_b.id).unwrap();
+            peer_store.connection_attempted(&peer_info_b.id).unwrap();
+        }
+        let store = create_store(tmp_dir.path().to_str().unwrap());
+        let peer_store = PeerStore::new(store, &boot_nodes).unwrap();
+        let stats_a = peer_store.stats(&peer_info_a.id).unwrap();
+        assert_eq!((stats_a.connection_attempts, stats_a.connections), (1, 1));
+        assert!(stats_a.latency_millis.is_some());
+        assert!(stats_a.last_useful.is_some());
+        let stats_b = peer_store.stats(&peer_info_b.id).unwrap();
+        assert_eq!((stats_b.connection_attempts, stats_b.connections), (2, 0));
+
+        let now = to_timestamp(Utc::now());
+        let unknown = get_peer_id("unknown".to_string());
+        assert!(peer_store.score(&peer_info_a.id, now) > peer_store.score(&unknown, now));
+        assert!(peer_store.score(&unknown, now) > peer_store.score(&peer_info_b.id, now));
+        // Data served long ago counts less.
+        let a_week_later = now + 7 * DAY;
+        assert!(stats_a.score(a_week_later) < stats_a.score(now));
+    }
+
     fn check_exist(
         peer_store: &PeerStore,
         peer_id: &PeerId,
@@ -581,4 +858,115 @@ mod test {
         assert!(check_exist(&peer_store_2, &peers_id[0], Some((addrs[0], TrustLevel::Indirect))));
         assert!(check_integrity(&peer_store_2));
     }
+
+    #[test]
+    fn addr_families() {
+        let v4: SocketAddr = "0.0.0.0:24567".parse().unwrap();
+        let v6: SocketAddr = "[::]:24567".parse().unwrap();
+        let v6_specified: SocketAddr = "[2001:db8::1]:24567".parse().unwrap();
+        let v4_mapped: SocketAddr = "[::ffff:127.0.0.1]:24567".parse().unwrap();
+
+        assert_eq!(AddrFamilies::of(&[v4]), AddrFamilies { ipv4: true, ipv6: false });
+        assert_eq!(AddrFamilies::of(&[v4, v6]), AddrFamilies { ipv4: true, ipv6: true });
+        assert_eq!(AddrFamilies::of(&[v6]), AddrFamilies { ipv4: true, ipv6: true });
+        assert_eq!(AddrFamilies::of(&[v6_specified]), AddrFamilies { ipv4: false, ipv6: true });
+        assert_eq!(AddrFamilies::of(&[]), AddrFamilies { ipv4: true, ipv6: true });
+
+        let ipv6_only = AddrFamilies { ipv4: false, ipv6: true };
+        assert!(ipv6_only.contains(&v6_specified));
+        assert!(!ipv6_only.contains(&v4));
+        assert!(!ipv6_only.contains(&v4_mapped));
+    }
+
+    /// Addresses of the preferred family replace unverified addresses of the other family.
+    #[test]
+    fn prefer_addr_family() {
+        let store = create_test_store();
+        let mut peer_store = PeerStore::new(store, &[]).unwrap();
+        let ipv6_only = AddrFamilies { ipv4: false, ipv6: true };
+
+        let peers_id = (0..3).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
+        let v4_addrs = (0..3).map(|ix| get_addr(ix)).collect::<Vec<_>>();
+        let v6_addrs = (0..3)
+            .map(|ix| format!("[2001:db8::{}]:24567", ix + 1).parse().unwrap())
+            .collect::<Vec<SocketAddr>>();
+
+        // A is known indirectly by its IPv4 address, B is connected over IPv4 and C answered at
+        // its IPv4 address.
+        peer_store
+            .add_indirect_peers(
+                vec![get_peer_info(peers_id[0].clone(), Some(v4_addrs[0]))],
+                ipv6_only,
+            )
+            .unwrap();
+        peer_store.peer_connected(&get_peer_info(peers_id[1].clone(), Some(v4_addrs[1]))).unwrap();
+        peer_store
+            .add_peer(get_peer_info(peers_id[2].clone(), Some(v4_addrs[2])), TrustLevel::Direct)
+            .unwrap();
+
+        peer_store
+            .add_indirect_peers(
+                (0..3).map(|ix| get_peer_info(peers_id[ix].clone(), Some(v6_addrs[ix]))).collect(),
+                ipv6_only,
+            )
+            .unwrap();
+        assert!(check_exist(&peer_store, &peers_id[0], Some((v6_addrs[0], TrustLevel::Indirect))));
+        assert!(check_exist(&peer_store, &peers_id[1], Some((v4_addrs[1], TrustLevel::Signed))));
+        assert!(check_exist(&peer_store, &peers_id[2], Some((v4_addrs[2], TrustLevel::Direct))));
+        assert!(check_integrity(&peer_store));
+
+        // Without a preference for IPv6 the known address is kept.
+        let both = AddrFamilies { ipv4: true, ipv6: true };
+        peer_store
+            .add_indirect_peers(vec![get_peer_info(peers_id[0].clone(), Some(v4_addrs[0]))], both)
+            .unwrap();
+        assert!(check_exist(&peer_store, &peers_id[0], Some((v6_addrs[0], TrustLevel::Indirect))));
+    }
+
+    fn signed_record(seed: &str, addrs: Vec<SocketAddr>, timestamp: u64) -> SignedPeerRecord {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, seed);
+        let peer_id = secret_key.public_key().into();
+        PeerRecord { peer_id, addrs, account_id: None, timestamp, capabilities: 0 }
+            .sign(&secret_key)
+    }
+
+    /// Signed records move peers to newer addresses, but can't take over those of other peers.
+    #[test]
+    fn peer_records() {
+        let store = create_test_store();
+        let mut peer_store = PeerStore::new(store, &[]).unwrap();
+        let both = AddrFamilies { ipv4: true, ipv6: true };
+        let peer_a = get_peer_id("node0".to_string());
+        let addrs = (0..4).map(|ix| get_addr(ix)).collect::<Vec<_>>();
+
+        peer_store.add_peer_records(vec![signed_record("node0", vec![addrs[0]], 1)], both).unwrap();
+        assert!(check_exist(&peer_store, &peer_a, Some((addrs[0], TrustLevel::Recorded))));
+
+        // Unsigned addresses of the peer and records of other peers for its address are ignored.
+        peer_store
+            .add_indirect_peers(vec![get_peer_info(peer_a.clone(), Some(addrs[1]))], both)
+            .unwrap();
+        peer_store.add_peer_records(vec![signed_record("node1", vec![addrs[0]], 5)], both).unwrap();
+        assert!(check_exist(&peer_store, &peer_a, Some((addrs[0], TrustLevel::Recorded))));
+
+        // A newer record moves the peer, an older one doesn't.
+        peer_store.add_peer_records(vec![signed_record("node0", vec![addrs[2]], 3)], both).unwrap();
+        peer_store.add_peer_records(vec![signed_record("node0", vec![addrs[3]], 2)], both).unwrap();
+        assert!(check_exist(&peer_store, &peer_a, Some((addrs[2], TrustLevel::Recorded))));
+        assert_eq!(peer_store.record(&peer_a).unwrap().record.timestamp, 3);
+        assert!(check_integrity(&peer_store));
+
+        // Records created at the same time are chosen by hash, whatever order they come in.
+        let same_time = vec![
+            signed_record("node0", vec![addrs[1]], 4),
+            signed_record("node0", vec![addrs[3]], 4),
+        ];
+        let mut kept = vec![];
+        for records in vec![same_time.clone(), same_time.into_iter().rev().collect()] {
+            let mut peer_store = PeerStore::new(create_test_store(), &[]).unwrap();
+            peer_store.add_peer_records(records, both).unwrap();
+            kept.push(peer_store.record(&peer_a).cloned().unwrap());
+        }
+        assert_eq!(kept[0], kept[1]);
+    }
 }
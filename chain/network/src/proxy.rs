@@ -0,0 +1,275 @@
+//! Outbound peer connections through a SOCKS5 or HTTP proxy.
+//!
+//! Nodes whose egress is restricted, or that want to hide behind Tor, connect to their peers
+//! through a proxy. Boot nodes given by host name can be resolved by the proxy rather than locally
+//! (`remote_dns`), so that DNS queries don't leave the proxy either. QUIC is not proxied, so with a
+//! proxy configured all outbound connections use TCP.
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Time to connect to the proxy and have it connect to the peer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest response head accepted from an HTTP proxy.
+const MAX_HTTP_RESPONSE_HEAD: usize = 8 * 1024;
+
+const SOCKS5_VERSION: u8 = 5;
+const SOCKS5_NO_AUTH: u8 = 0;
+const SOCKS5_USERNAME_PASSWORD: u8 = 2;
+const SOCKS5_CONNECT: u8 = 1;
+const SOCKS5_ATYP_IPV4: u8 = 1;
+const SOCKS5_ATYP_DOMAIN: u8 = 3;
+const SOCKS5_ATYP_IPV6: u8 = 4;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    Socks5,
+    /// Proxy supporting the HTTP `CONNECT` method.
+    Http,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    /// Address of the proxy as `host:port`.
+    pub addr: String,
+    /// Credentials for the SOCKS5 username/password or the HTTP basic authentication.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Whether boot nodes given by host name are resolved by the proxy instead of locally.
+    #[serde(default)]
+    pub remote_dns: bool,
+}
+
+/// Address to have the proxy connect to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProxyTarget {
+    Addr(SocketAddr),
+    Host(String, u16),
+}
+
+impl fmt::Display for ProxyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyTarget::Addr(addr) => write!(f, "{}", addr),
+            ProxyTarget::Host(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+/// Opens a connection to the target through the proxy.
+pub async fn connect(config: &ProxyConfig, target: &ProxyTarget) -> io::Result<TcpStream> {
+    let connect = async {
+        let mut stream = TcpStream::connect(config.addr.as_str()).await?;
+        match config.kind {
+            ProxyKind::Socks5 => socks5_connect(&mut stream, config, target).await?,
+            ProxyKind::Http => http_connect(&mut stream, config, target).await?,
+        }
+        Ok(stream)
+    };
+    tokio::time::timeout(CONNECT_TIMEOUT, connect).await.map_err(|_| {
+        io::Error::new(io::ErrorKind::TimedOut, format!("Proxy didn't connect to {}", target))
+    })?
+}
+
+fn proxy_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+/// SOCKS5 handshake, see RFC 1928 and RFC 1929 for the authentication.
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    config: &ProxyConfig,
+    target: &ProxyTarget,
+) -> io::Result<()> {
+    let methods: &[u8] = if config.username.is_some() {
+        &[SOCKS5_NO_AUTH, SOCKS5_USERNAME_PASSWORD]
+    } else {
+        &[SOCKS5_NO_AUTH]
+    };
+    let mut greeting = vec![SOCKS5_VERSION, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await?;
+
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice[0] != SOCKS5_VERSION {
+        return Err(proxy_error(format!("Unsupported SOCKS version {}", choice[0])));
+    }
+    match (choice[1], &config.username) {
+        (SOCKS5_NO_AUTH, _) => {}
+        (SOCKS5_USERNAME_PASSWORD, Some(username)) => {
+            let password = config.password.as_deref().unwrap_or("");
+            if username.len() > 255 || password.len() > 255 {
+                return Err(proxy_error("SOCKS5 credentials are too long".to_string()));
+            }
+            let mut request = vec![1, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "SOCKS5 proxy rejected the credentials",
+                ));
+            }
+        }
+        _ => return Err(proxy_error("SOCKS5 proxy requires unsupported authentication".into())),
+    }
+
+    let mut request = vec![SOCKS5_VERSION, SOCKS5_CONNECT, 0];
+    let port = match target {
+        ProxyTarget::Addr(SocketAddr::V4(addr)) => {
+            request.push(SOCKS5_ATYP_IPV4);
+            request.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        ProxyTarget::Addr(SocketAddr::V6(addr)) => {
+            request.push(SOCKS5_ATYP_IPV6);
+            request.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        ProxyTarget::Host(host, port) => {
+            if host.len() > 255 {
+                return Err(proxy_error(format!("Host name {} is too long", host)));
+            }
+            request.push(SOCKS5_ATYP_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+            *port
+        }
+    };
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(proxy_error(format!(
+            "SOCKS5 proxy failed to connect to {}: error {}",
+            target, reply[1]
+        )));
+    }
+    // Skip the address the proxy bound to, and its port.
+    let addr_len = match reply[3] {
+        SOCKS5_ATYP_IPV4 => 4,
+        SOCKS5_ATYP_IPV6 => 16,
+        SOCKS5_ATYP_DOMAIN => stream.read_u8().await? as usize,
+        atyp => return Err(proxy_error(format!("Invalid SOCKS5 address type {}", atyp))),
+    };
+    let mut bound_addr = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound_addr).await?;
+    Ok(())
+}
+
+/// Tunnel with the HTTP `CONNECT` method.
+async fn http_connect(
+    stream: &mut TcpStream,
+    config: &ProxyConfig,
+    target: &ProxyTarget,
+) -> io::Result<()> {
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(username) = &config.username {
+        let password = config.password.as_deref().unwrap_or("");
+        let credentials = base64::encode(&format!("{}:{}", username, password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read the response head byte by byte to not consume what the peer sends after it.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HTTP_RESPONSE_HEAD {
+            return Err(proxy_error("HTTP proxy response is too long".to_string()));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => {
+            Err(proxy_error(format!("HTTP proxy failed to connect to {}: {}", target, status_line)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn proxy_config(kind: ProxyKind, addr: SocketAddr) -> ProxyConfig {
+        ProxyConfig {
+            kind,
+            addr: addr.to_string(),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            remote_dns: true,
+        }
+    }
+
+    /// Plays the proxy side of the handshake, given as the requests to expect and the replies to
+    /// them, and checks that data goes through the connection afterwards.
+    fn check_proxy(kind: ProxyKind, exchanges: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let config = proxy_config(kind, listener.local_addr().unwrap());
+            let proxy = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                for (request, reply) in exchanges {
+                    let mut received = vec![0u8; request.len()];
+                    stream.read_exact(&mut received).await.unwrap();
+                    assert_eq!(received, request);
+                    stream.write_all(&reply).await.unwrap();
+                }
+                stream.write_all(b"peer").await.unwrap();
+            });
+
+            let target = ProxyTarget::Host("node.example".to_string(), 24567);
+            let mut stream = connect(&config, &target).await.unwrap();
+            let mut data = [0u8; 4];
+            stream.read_exact(&mut data).await.unwrap();
+            assert_eq!(&data, b"peer");
+            proxy.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_socks5() {
+        let mut connect_request = b"\x05\x01\x00\x03\x0cnode.example".to_vec();
+        connect_request.extend_from_slice(&24567u16.to_be_bytes());
+        check_proxy(
+            ProxyKind::Socks5,
+            vec![
+                (vec![5, 2, 0, 2], vec![5, 2]),
+                (b"\x01\x04user\x04pass".to_vec(), vec![1, 0]),
+                (connect_request, vec![5, 0, 0, 1, 127, 0, 0, 1, 0, 0]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_http() {
+        let request = b"CONNECT node.example:24567 HTTP/1.1\r\nHost: node.example:24567\r\n\
+            Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+            .to_vec();
+        let reply = b"HTTP/1.1 200 Connection established\r\n\r\n".to_vec();
+        check_proxy(ProxyKind::Http, vec![(request, reply)]);
+    }
+}
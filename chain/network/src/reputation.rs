@@ -0,0 +1,207 @@
+//! Misbehavior scores of peers.
+//!
+//! Every misbehavior reported about a peer adds its weight to the score of the peer, and scores go
+//! down over time so that occasional mistakes of honest peers are forgotten. Once the score of a
+//! peer reaches the threshold, the peer gets banned for `NetworkConfig::ban_window`. Bans are
+//! persisted in the peer store, scores are only kept in memory.
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use near_primitives::network::PeerId;
+use near_primitives::views::PeerReputationView;
+
+use crate::types::ReasonForBan;
+
+/// Misbehavior that counts towards banning a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::AsRefStr)]
+pub enum Misbehavior {
+    /// Block that failed validation.
+    InvalidBlock,
+    /// Message that couldn't be decoded though the peer has the same protocol version, or that
+    /// breaks the limits of the protocol.
+    MalformedMessage,
+    /// Invalid transactions beyond the rate honest peers forward them at, or other gossip that is
+    /// useless to forward.
+    SpammyGossip,
+    /// Handshake with a wrong nonce, or repeated handshakes over an established connection.
+    HandshakeAbuse,
+}
+
+impl Misbehavior {
+    fn weight(self) -> f64 {
+        match self {
+            Misbehavior::InvalidBlock => 50.0,
+            Misbehavior::HandshakeAbuse => 25.0,
+            Misbehavior::MalformedMessage => 10.0,
+            Misbehavior::SpammyGossip => 2.0,
+        }
+    }
+
+    fn ban_reason(self) -> ReasonForBan {
+        match self {
+            Misbehavior::InvalidBlock => ReasonForBan::BadBlock,
+            Misbehavior::HandshakeAbuse => ReasonForBan::BadHandshake,
+            Misbehavior::MalformedMessage | Misbehavior::SpammyGossip => ReasonForBan::Abusive,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReputationConfig {
+    /// Score at which a peer gets banned.
+    pub ban_threshold: u32,
+    /// How much the score of every peer goes down per minute.
+    pub decay_per_minute: u32,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        ReputationConfig { ban_threshold: 100, decay_per_minute: 10 }
+    }
+}
+
+struct PeerReputation {
+    score: f64,
+    /// When the score was last decayed.
+    updated: Instant,
+    misbehaviors: BTreeMap<Misbehavior, u64>,
+}
+
+impl PeerReputation {
+    fn decay(&mut self, now: Instant, decay_per_minute: u32) {
+        let minutes = now.saturating_duration_since(self.updated).as_secs_f64() / 60.0;
+        self.score = (self.score - minutes * f64::from(decay_per_minute)).max(0.0);
+        self.updated = now;
+    }
+}
+
+pub struct ReputationTable {
+    config: ReputationConfig,
+    peers: HashMap<PeerId, PeerReputation>,
+}
+
+impl ReputationTable {
+    pub fn new(config: ReputationConfig) -> Self {
+        ReputationTable { config, peers: HashMap::new() }
+    }
+
+    /// Records the misbehavior of the peer. Returns the reason to ban the peer for if this
+    /// misbehavior made its score reach the threshold.
+    pub fn report(
+        &mut self,
+        peer_id: &PeerId,
+        misbehavior: Misbehavior,
+        now: Instant,
+    ) -> Option<ReasonForBan> {
+        let threshold = f64::from(self.config.ban_threshold);
+        let reputation = self.peers.entry(peer_id.clone()).or_insert_with(|| PeerReputation {
+            score: 0.0,
+            updated: now,
+            misbehaviors: BTreeMap::new(),
+        });
+        reputation.decay(now, self.config.decay_per_minute);
+        let was_below_threshold = reputation.score < threshold;
+        reputation.score += misbehavior.weight();
+        *reputation.misbehaviors.entry(misbehavior).or_default() += 1;
+        if was_below_threshold && reputation.score >= threshold {
+            Some(misbehavior.ban_reason())
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the peers whose score went down to zero.
+    pub fn prune(&mut self, now: Instant) {
+        let decay_per_minute = self.config.decay_per_minute;
+        self.peers.retain(|_, reputation| {
+            reputation.decay(now, decay_per_minute);
+            reputation.score > 0.0
+        });
+    }
+
+    pub fn view(
+        &self,
+        now: Instant,
+        is_banned: impl Fn(&PeerId) -> bool,
+    ) -> Vec<PeerReputationView> {
+        let decay_per_minute = f64::from(self.config.decay_per_minute);
+        let mut view: Vec<_> = self
+            .peers
+            .iter()
+            .map(|(peer_id, reputation)| {
+                let minutes =
+                    now.saturating_duration_since(reputation.updated).as_secs_f64() / 60.0;
+                PeerReputationView {
+                    peer_id: peer_id.to_string(),
+                    score: (reputation.score - minutes * decay_per_minute).max(0.0).round() as u32,
+                    misbehaviors: reputation
+                        .misbehaviors
+                        .iter()
+                        .map(|(misbehavior, count)| (misbehavior.as_ref().to_string(), *count))
+                        .collect(),
+                    banned: is_banned(peer_id),
+                }
+            })
+            .collect();
+        view.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.peer_id.cmp(&b.peer_id)));
+        view
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    fn peer_id(seed: &str) -> PeerId {
+        PeerId::new(SecretKey::from_seed(KeyType::ED25519, seed).public_key())
+    }
+
+    #[test]
+    fn test_ban_on_threshold() {
+        let mut table = ReputationTable::new(ReputationConfig::default());
+        let peer = peer_id("test");
+        let now = Instant::now();
+        assert_eq!(table.report(&peer, Misbehavior::InvalidBlock, now), None);
+        assert_eq!(
+            table.report(&peer, Misbehavior::InvalidBlock, now),
+            Some(ReasonForBan::BadBlock)
+        );
+        // The peer is reported only once when crossing the threshold.
+        assert_eq!(table.report(&peer, Misbehavior::MalformedMessage, now), None);
+
+        let view = table.view(now, |_| true);
+        assert_eq!(view.len(), 1);
+        assert_eq!(view[0].score, 110);
+        assert_eq!(view[0].misbehaviors.get("InvalidBlock"), Some(&2));
+        assert_eq!(view[0].misbehaviors.get("MalformedMessage"), Some(&1));
+        assert!(view[0].banned);
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut table =
+            ReputationTable::new(ReputationConfig { ban_threshold: 100, decay_per_minute: 10 });
+        let peer = peer_id("test");
+        let other = peer_id("other");
+        let now = Instant::now();
+        table.report(&peer, Misbehavior::InvalidBlock, now);
+        table.report(&other, Misbehavior::SpammyGossip, now);
+
+        // Three minutes later the first score went down by 30 and the second one is forgotten.
+        let later = now + Duration::from_secs(3 * 60);
+        assert_eq!(table.view(later, |_| false)[0].score, 20);
+        assert_eq!(table.report(&peer, Misbehavior::InvalidBlock, later), None);
+        table.prune(later);
+        let view = table.view(later, |_| false);
+        assert_eq!(view.len(), 1);
+        assert_eq!(view[0].peer_id, peer.to_string());
+        assert_eq!(view[0].score, 70);
+    }
+}
@@ -0,0 +1,120 @@
+//! Liveness of the routes to the peers in the routing table.
+//!
+//! A route only says that the edges on the way were announced. When a peer on the way goes away
+//! before its edges are removed, the messages routed to the peers behind it are dropped without
+//! anyone noticing. The messages that expect a response tell if a target is still reachable: a
+//! target that sends nothing within `ROUTE_RESPONSE_TIMEOUT` of such a message is unreachable, and
+//! the messages expecting a response from it are dropped right away for a backoff that doubles, up
+//! to `MAX_ROUTE_BACKOFF`, every time it stays unreachable. Other messages, such as approvals and
+//! chunks, are still routed to it. Any routed message authored by the target resets it.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use near_primitives::network::PeerId;
+
+/// How long a target has to send something after a message expecting a response.
+const ROUTE_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+const INITIAL_ROUTE_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_ROUTE_BACKOFF: Duration = Duration::from_secs(300);
+/// How often the routing table is pruned.
+pub const ROUTE_PRUNE_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct TargetLiveness {
+    /// First message expecting a response sent since the target last sent anything.
+    waiting_since: Option<Instant>,
+    /// Messages to the target are dropped until then.
+    backoff_until: Option<Instant>,
+    backoff: Duration,
+}
+
+#[derive(Default)]
+pub struct RouteLiveness {
+    targets: HashMap<PeerId, TargetLiveness>,
+}
+
+impl RouteLiveness {
+    /// Records that a message expecting a response was routed to the target.
+    pub fn waiting_response(&mut self, target: &PeerId, now: Instant) {
+        self.targets.entry(target.clone()).or_default().waiting_since.get_or_insert(now);
+    }
+
+    /// Records that a message authored by the peer was received.
+    pub fn alive(&mut self, author: &PeerId) {
+        self.targets.remove(author);
+    }
+
+    /// Whether messages to the target are dropped for now.
+    pub fn is_backed_off(&self, target: &PeerId, now: Instant) -> bool {
+        self.targets
+            .get(target)
+            .and_then(|liveness| liveness.backoff_until)
+            .map_or(false, |backoff_until| now < backoff_until)
+    }
+
+    /// Backs off the targets that didn't answer in time and forgets the ones without a route,
+    /// returns how many targets are backed off.
+    pub fn prune(&mut self, now: Instant, has_route: impl Fn(&PeerId) -> bool) -> usize {
+        self.targets.retain(|target, _| has_route(target));
+        let mut backed_off = 0;
+        for liveness in self.targets.values_mut() {
+            let timed_out = liveness.waiting_since.map_or(false, |waiting_since| {
+                now.saturating_duration_since(waiting_since) >= ROUTE_RESPONSE_TIMEOUT
+            });
+            if timed_out {
+                liveness.waiting_since = None;
+                liveness.backoff =
+                    (liveness.backoff * 2).max(INITIAL_ROUTE_BACKOFF).min(MAX_ROUTE_BACKOFF);
+                liveness.backoff_until = Some(now + liveness.backoff);
+            }
+            if liveness.backoff_until.map_or(false, |backoff_until| now < backoff_until) {
+                backed_off += 1;
+            }
+        }
+        backed_off
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+
+    use super::*;
+
+    fn peer_id(seed: &str) -> PeerId {
+        SecretKey::from_seed(KeyType::ED25519, seed).public_key().into()
+    }
+
+    #[test]
+    fn test_route_liveness() {
+        let mut liveness = RouteLiveness::default();
+        let (target, other) = (peer_id("target"), peer_id("other"));
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+
+        liveness.waiting_response(&target, start);
+        liveness.waiting_response(&target, secs(5));
+        liveness.waiting_response(&other, start);
+        assert_eq!(liveness.prune(secs(9), |_| true), 0);
+        liveness.alive(&other);
+        assert_eq!(liveness.prune(secs(10), |_| true), 1);
+        assert!(liveness.is_backed_off(&target, secs(11)));
+        assert!(!liveness.is_backed_off(&target, secs(12)));
+        assert!(!liveness.is_backed_off(&other, secs(11)));
+
+        // Still unreachable, the backoff doubles.
+        liveness.waiting_response(&target, secs(12));
+        assert_eq!(liveness.prune(secs(22), |_| true), 1);
+        assert!(liveness.is_backed_off(&target, secs(25)));
+        assert!(!liveness.is_backed_off(&target, secs(26)));
+
+        // Reachable again.
+        liveness.alive(&target);
+        assert!(!liveness.is_backed_off(&target, secs(22)));
+
+        // Targets without a route are forgotten.
+        liveness.waiting_response(&target, secs(30));
+        assert_eq!(liveness.prune(secs(40), |peer_id| peer_id != &target), 0);
+        assert!(!liveness.is_backed_off(&target, secs(41)));
+    }
+}
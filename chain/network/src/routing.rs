@@ -10,6 +10,7 @@ use byteorder::{LittleEndian, WriteBytesExt};
 use cached::{Cached, SizedCache};
 use chrono;
 use log::{trace, warn};
+use rand::seq::IteratorRandom;
 
 use near_crypto::{SecretKey, Signature};
 use near_metrics;
@@ -23,6 +24,7 @@ use near_store::{
 };
 
 use crate::metrics;
+use crate::route_liveness::RouteLiveness;
 use crate::{
     cache::RouteBackCache,
     types::{PeerIdOrHash, Ping, Pong},
@@ -38,6 +40,8 @@ const ROUTE_BACK_CACHE_REMOVE_BATCH: u64 = 100;
 const PING_PONG_CACHE_SIZE: usize = 1_000;
 const ROUND_ROBIN_MAX_NONCE_DIFFERENCE_ALLOWED: usize = 10;
 const ROUND_ROBIN_NONCE_CACHE_SIZE: usize = 10_000;
+/// Next hops kept for each peer, out of all the neighbors on a shortest path to it.
+const MAX_ROUTES_PER_PEER: usize = 8;
 /// Routing table will clean edges if there is at least one node that is not reachable
 /// since `SAVE_PEERS_MAX_TIME` seconds. All peers disconnected since `SAVE_PEERS_AFTER_TIME`
 /// seconds will be removed from cache and persisted in disk.
@@ -284,6 +288,8 @@ pub struct RoutingTable {
     last_ping_nonce: SizedCache<PeerId, usize>,
     /// Last nonce used to store edges on disk.
     pub component_nonce: u64,
+    /// Whether the peers routed to still answer.
+    liveness: RouteLiveness,
 }
 
 #[derive(Debug)]
@@ -292,6 +298,21 @@ pub enum FindRouteError {
     PeerNotFound,
     AccountNotFound,
     RouteBackNotFound,
+    /// The peer didn't answer the last messages routed to it.
+    Backoff,
+}
+
+impl FindRouteError {
+    /// Label of the error in the metrics of dropped messages.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            FindRouteError::Disconnected => "disconnected",
+            FindRouteError::PeerNotFound => "peer_not_found",
+            FindRouteError::AccountNotFound => "account_not_found",
+            FindRouteError::RouteBackNotFound => "route_back_not_found",
+            FindRouteError::Backoff => "backoff",
+        }
+    }
 }
 
 impl RoutingTable {
@@ -321,6 +342,7 @@ impl RoutingTable {
             waiting_pong: SizedCache::with_size(PING_PONG_CACHE_SIZE),
             last_ping_nonce: SizedCache::with_size(PING_PONG_CACHE_SIZE),
             component_nonce,
+            liveness: RouteLiveness::default(),
         }
     }
 
@@ -557,6 +579,43 @@ impl RoutingTable {
         self.edges_info.iter().map(|(_, edge)| edge.clone()).collect()
     }
 
+    /// Records that a message expecting a response was routed to the peer.
+    pub fn waiting_response(&mut self, target: &PeerId) {
+        self.liveness.waiting_response(target, Instant::now());
+    }
+
+    /// Whether messages expecting a response are not routed to the target for now, because it
+    /// didn't answer the last ones.
+    pub fn is_backed_off(&self, target: &PeerIdOrHash) -> bool {
+        match target {
+            PeerIdOrHash::PeerId(peer_id) => self.liveness.is_backed_off(peer_id, Instant::now()),
+            PeerIdOrHash::Hash(_) => false,
+        }
+    }
+
+    /// Records that a message authored by the peer was received, so it's reachable.
+    pub fn route_alive(&mut self, author: &PeerId) {
+        self.liveness.alive(author);
+    }
+
+    /// Removes the routes through next hops that are not connected anymore and backs off the
+    /// peers that didn't answer in time.
+    pub fn prune_routes(&mut self, is_active: impl Fn(&PeerId) -> bool) {
+        let mut pruned = 0;
+        self.peer_forwarding.retain(|_, hops| {
+            let before = hops.len();
+            hops.retain(|hop| is_active(hop));
+            pruned += before - hops.len();
+            !hops.is_empty()
+        });
+        near_metrics::inc_counter_by(&metrics::ROUTES_PRUNED, pruned as i64);
+
+        let peer_forwarding = &self.peer_forwarding;
+        let backed_off =
+            self.liveness.prune(Instant::now(), |peer_id| peer_forwarding.contains_key(peer_id));
+        near_metrics::set_gauge(&metrics::ROUTE_TARGETS_BACKED_OFF, backed_off as i64);
+    }
+
     pub fn add_route_back(&mut self, hash: CryptoHash, peer_id: PeerId) {
         self.route_back.insert(hash, peer_id);
     }
@@ -693,6 +752,16 @@ impl RoutingTable {
         self.recalculation_scheduled = None;
 
         self.peer_forwarding = self.raw_graph.calculate_distance();
+        let mut rng = rand::thread_rng();
+        for hops in self.peer_forwarding.values_mut() {
+            if hops.len() > MAX_ROUTES_PER_PEER {
+                *hops = hops
+                    .drain()
+                    .choose_multiple(&mut rng, MAX_ROUTES_PER_PEER)
+                    .into_iter()
+                    .collect();
+            }
+        }
 
         let now = chrono::Utc::now();
         for peer in self.peer_forwarding.keys() {
@@ -857,8 +926,31 @@ impl Graph {
 
 #[cfg(test)]
 mod test {
-    use crate::routing::Graph;
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    use near_store::test_utils::create_test_store;
+
+    use crate::routing::{Graph, RoutingTable};
     use crate::test_utils::{expected_routing_tables, random_peer_id};
+    use crate::types::PeerIdOrHash;
+
+    #[test]
+    fn backoff_keeps_route() {
+        let mut routing_table = RoutingTable::new(random_peer_id(), create_test_store());
+        let (target, next_hop) = (random_peer_id(), random_peer_id());
+        routing_table
+            .peer_forwarding
+            .insert(target.clone(), vec![next_hop.clone()].into_iter().collect::<HashSet<_>>());
+        let now = Instant::now();
+        routing_table.liveness.waiting_response(&target, now);
+        routing_table.liveness.prune(now + Duration::from_secs(60), |_| true);
+
+        // Only messages expecting a response are held back, the route is still there.
+        let target = PeerIdOrHash::PeerId(target);
+        assert!(routing_table.is_backed_off(&target));
+        assert_eq!(routing_table.find_route(&target).unwrap(), next_hop);
+    }
 
     #[test]
     fn graph_contains_edge() {
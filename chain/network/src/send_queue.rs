@@ -0,0 +1,291 @@
+//! Queues of messages waiting to be written to a connection, by traffic class.
+//!
+//! A TCP stream delivers messages in the order they were written, so a burst of transaction gossip
+//! used to hold back the chunk parts written after it. Messages are instead queued by
+//! `TrafficClass` and handed to the socket only as fast as it takes them, the most urgent class
+//! first. Classes whose messages may be lost have a budget of queued bytes in `SendQueueConfig`,
+//! past which their `DropPolicy` decides which messages are dropped, so that a slow peer doesn't
+//! make the node buffer gossip without bound. A message is always queued if nothing else of its
+//! class is, even if it's larger than the budget.
+//!
+//! Over QUIC every class has its own stream and queue. Either way messages count as sent once the
+//! connection takes them from the queue, dropped messages are counted separately.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::{self, NetworkMetrics};
+use crate::rate_counter::RateCounter;
+use crate::transport::{TrafficClass, NUM_TRAFFIC_CLASSES};
+
+/// Bytes of messages of a class that can be queued for a peer before some are dropped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SendQueueConfig {
+    /// Chunk parts, their requests and responses. The oldest are dropped first.
+    pub chunk_parts_budget: usize,
+    /// State sync, queries and other routed messages. New ones are dropped first.
+    pub sync_budget: usize,
+    /// Transaction gossip. New transactions are dropped first.
+    pub transactions_budget: usize,
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        SendQueueConfig {
+            chunk_parts_budget: 64 * 1024 * 1024,
+            sync_budget: 32 * 1024 * 1024,
+            transactions_budget: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// Messages to drop when a queue is over its budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DropPolicy {
+    /// Messages are queued whatever their size, for messages that are never sent again.
+    Never,
+    /// Older messages are dropped first, for messages that get useless with time.
+    Oldest,
+    /// The new message is dropped, for messages that are retried or gossiped again.
+    Newest,
+}
+
+impl SendQueueConfig {
+    fn drop_policy(&self, class: TrafficClass) -> (DropPolicy, usize) {
+        match class {
+            // Nothing resends a lost handshake or approval, and the peer waits for the blocks and
+            // headers it requested.
+            TrafficClass::Control | TrafficClass::Blocks => (DropPolicy::Never, 0),
+            // Edges are only sent once, a dropped one would leave the routing table of the peer
+            // stale until it reconnects.
+            TrafficClass::Routing => (DropPolicy::Never, 0),
+            // Old chunk parts are useless once the chain moved on, missing ones are requested.
+            TrafficClass::ChunkParts => (DropPolicy::Oldest, self.chunk_parts_budget),
+            TrafficClass::Sync => (DropPolicy::Newest, self.sync_budget),
+            TrafficClass::Transactions => (DropPolicy::Newest, self.transactions_budget),
+        }
+    }
+}
+
+/// Encoded message waiting in a queue.
+pub struct QueuedMessage {
+    pub bytes: Vec<u8>,
+    /// Variant of the message, for the metrics.
+    pub variant: &'static str,
+}
+
+struct Queues {
+    config: SendQueueConfig,
+    /// Messages of each class, indexed by `TrafficClass`.
+    messages: [VecDeque<QueuedMessage>; NUM_TRAFFIC_CLASSES],
+    /// Total size of the messages of each class.
+    bytes: [usize; NUM_TRAFFIC_CLASSES],
+    /// Messages taken from the queues over the last minute.
+    sent: RateCounter,
+    /// Task waiting for messages to send.
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl Queues {
+    fn new(config: SendQueueConfig) -> Self {
+        Queues {
+            config,
+            messages: Default::default(),
+            bytes: [0; NUM_TRAFFIC_CLASSES],
+            sent: RateCounter::new(),
+            waker: None,
+            closed: false,
+        }
+    }
+
+    /// Queues the message, returning the messages dropped to stay within the budget.
+    fn push(&mut self, class: TrafficClass, message: QueuedMessage) -> Vec<QueuedMessage> {
+        let index = class as usize;
+        let (drop_policy, budget) = self.config.drop_policy(class);
+        let mut dropped = vec![];
+        match drop_policy {
+            DropPolicy::Never => {}
+            DropPolicy::Oldest => {
+                while self.bytes[index] + message.bytes.len() > budget {
+                    match self.messages[index].pop_front() {
+                        Some(oldest) => {
+                            self.bytes[index] -= oldest.bytes.len();
+                            dropped.push(oldest);
+                        }
+                        None => break,
+                    }
+                }
+            }
+            DropPolicy::Newest => {
+                if !self.messages[index].is_empty()
+                    && self.bytes[index] + message.bytes.len() > budget
+                {
+                    return vec![message];
+                }
+            }
+        }
+        self.bytes[index] += message.bytes.len();
+        self.messages[index].push_back(message);
+        dropped
+    }
+
+    /// Takes the oldest message of the most urgent class.
+    fn pop(&mut self) -> Option<QueuedMessage> {
+        for index in 0..NUM_TRAFFIC_CLASSES {
+            if let Some(message) = self.messages[index].pop_front() {
+                self.bytes[index] -= message.bytes.len();
+                self.sent.increment(message.bytes.len() as u64);
+                return Some(message);
+            }
+        }
+        None
+    }
+}
+
+/// Sending half of the queues, owned by the peer actor. Dropping it ends `QueuedMessages` once
+/// the queued messages are taken.
+pub struct SendQueue {
+    queues: Rc<RefCell<Queues>>,
+    network_metrics: Rc<NetworkMetrics>,
+}
+
+/// Stream of the queued messages in the order they should be sent.
+pub struct QueuedMessages {
+    queues: Rc<RefCell<Queues>>,
+    network_metrics: Rc<NetworkMetrics>,
+}
+
+pub fn send_queue(
+    config: SendQueueConfig,
+    network_metrics: Rc<NetworkMetrics>,
+) -> (SendQueue, QueuedMessages) {
+    let queues = Rc::new(RefCell::new(Queues::new(config)));
+    (
+        SendQueue { queues: queues.clone(), network_metrics: network_metrics.clone() },
+        QueuedMessages { queues, network_metrics },
+    )
+}
+
+impl SendQueue {
+    pub fn push(&self, class: TrafficClass, message: QueuedMessage) {
+        let mut queues = self.queues.borrow_mut();
+        let dropped = queues.push(class, message);
+        if !dropped.is_empty() {
+            if let Ok(counter) = &*metrics::PEER_MESSAGE_SEND_DROPPED {
+                counter.with_label_values(&[class.as_ref()]).inc_by(dropped.len() as i64);
+            }
+            for message in dropped {
+                self.network_metrics.inc(&NetworkMetrics::peer_message_dropped(message.variant));
+            }
+        }
+        if let Some(waker) = queues.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Bytes and number of messages sent over the last minute.
+    pub fn sent_per_min(&self) -> (u64, u64) {
+        let queues = self.queues.borrow();
+        (queues.sent.bytes_per_min(), queues.sent.count_per_min())
+    }
+}
+
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        let mut queues = self.queues.borrow_mut();
+        queues.closed = true;
+        if let Some(waker) = queues.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Stream for QueuedMessages {
+    type Item = Vec<u8>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut queues = self.queues.borrow_mut();
+        match queues.pop() {
+            Some(message) => {
+                let size = message.bytes.len() as i64;
+                near_metrics::inc_counter_by(&metrics::PEER_DATA_SENT_BYTES, size);
+                self.network_metrics
+                    .inc_by(&NetworkMetrics::peer_message_bytes_tx(message.variant), size);
+                Poll::Ready(Some(message.bytes))
+            }
+            None if queues.closed => Poll::Ready(None),
+            None => {
+                queues.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(byte: u8, len: usize) -> QueuedMessage {
+        QueuedMessage { bytes: vec![byte; len], variant: "Test" }
+    }
+
+    fn drain(queues: &mut Queues) -> Vec<u8> {
+        std::iter::from_fn(|| queues.pop()).map(|message| message.bytes[0]).collect()
+    }
+
+    #[test]
+    fn test_class_order() {
+        let mut queues = Queues::new(SendQueueConfig::default());
+        queues.push(TrafficClass::Transactions, message(4, 1));
+        queues.push(TrafficClass::Routing, message(3, 1));
+        queues.push(TrafficClass::Control, message(0, 1));
+        queues.push(TrafficClass::Transactions, message(5, 1));
+        queues.push(TrafficClass::Blocks, message(2, 1));
+        queues.push(TrafficClass::ChunkParts, message(1, 1));
+        assert_eq!(drain(&mut queues), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(queues.bytes, [0; NUM_TRAFFIC_CLASSES]);
+        assert_eq!(queues.sent.count_per_min(), 6);
+    }
+
+    #[test]
+    fn test_drop_policies() {
+        let config =
+            SendQueueConfig { chunk_parts_budget: 10, sync_budget: 10, transactions_budget: 10 };
+        let mut queues = Queues::new(config);
+        let dropped = |dropped: Vec<QueuedMessage>| -> Vec<u8> {
+            dropped.iter().map(|message| message.bytes[0]).collect()
+        };
+
+        // Transactions over the budget are dropped.
+        assert_eq!(dropped(queues.push(TrafficClass::Transactions, message(0, 5))), vec![]);
+        assert_eq!(dropped(queues.push(TrafficClass::Transactions, message(1, 5))), vec![]);
+        assert_eq!(dropped(queues.push(TrafficClass::Transactions, message(2, 5))), vec![2]);
+
+        // Chunk parts over the budget replace the oldest ones.
+        assert_eq!(dropped(queues.push(TrafficClass::ChunkParts, message(3, 5))), vec![]);
+        assert_eq!(dropped(queues.push(TrafficClass::ChunkParts, message(4, 5))), vec![]);
+        assert_eq!(dropped(queues.push(TrafficClass::ChunkParts, message(5, 5))), vec![3]);
+
+        // Blocks and edges are never dropped.
+        for byte in 6..9 {
+            assert_eq!(dropped(queues.push(TrafficClass::Blocks, message(byte, 100))), vec![]);
+        }
+        assert_eq!(dropped(queues.push(TrafficClass::Routing, message(9, 100))), vec![]);
+
+        assert_eq!(drain(&mut queues), vec![4, 5, 6, 7, 8, 9, 0, 1]);
+        // Only the messages taken from the queue count as sent.
+        assert_eq!(queues.sent.count_per_min(), 8);
+
+        // A message larger than the budget is still sent if nothing else is queued.
+        assert_eq!(dropped(queues.push(TrafficClass::Transactions, message(10, 11))), vec![]);
+        assert_eq!(drain(&mut queues), vec![10]);
+    }
+}
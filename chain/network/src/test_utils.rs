@@ -14,6 +14,14 @@ use near_primitives::network::PeerId;
 use near_primitives::types::EpochId;
 use near_primitives::utils::index_to_bytes;
 
+use crate::access::PeerAccess;
+use crate::connection_limits::ConnectionLimits;
+use crate::encryption::EncryptionMode;
+use crate::nat::PortMapping;
+use crate::reputation::ReputationConfig;
+use crate::send_queue::SendQueueConfig;
+use crate::throttle::BandwidthLimits;
+use crate::transport::Transport;
 use crate::types::{NetworkConfig, NetworkInfo, PeerInfo, ReasonForBan, ROUTED_MESSAGE_TTL};
 use crate::{NetworkAdapter, NetworkRequests, NetworkResponses, PeerManagerActor};
 use futures::future::BoxFuture;
@@ -57,6 +65,7 @@ impl NetworkConfig {
             secret_key,
             account_id: Some(seed.to_string()),
             addr: Some(format!("0.0.0.0:{}", port).parse().unwrap()),
+            additional_addrs: vec![],
             boot_nodes: vec![],
             handshake_timeout: Duration::from_secs(60),
             reconnect_delay: Duration::from_secs(60),
@@ -80,6 +89,19 @@ impl NetworkConfig {
             blacklist: HashMap::new(),
             outbound_disabled: false,
             archive: false,
+            transport: Transport::Tcp,
+            reputation: ReputationConfig::default(),
+            send_queue: SendQueueConfig::default(),
+            proxy: None,
+            boot_node_hosts: vec![],
+            bandwidth: BandwidthLimits::default(),
+            tier1_proxies: vec![],
+            public_addrs: vec![],
+            encryption: EncryptionMode::Preferred,
+            access: PeerAccess::default(),
+            port_mapping: PortMapping::Disabled,
+            discover_external_addr: false,
+            connection_limits: ConnectionLimits { reserved_slots: 0, ..Default::default() },
         }
     }
 }
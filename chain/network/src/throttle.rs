@@ -0,0 +1,133 @@
+//! Limits of the bandwidth used to talk to peers.
+//!
+//! Every connection has an upload and a download `Throttle`, made of the limiter of the connection
+//! itself and the one shared by all connections. Writing a message, or reading the next one,
+//! waits until all limiters have the bytes to spare, which over TCP makes the peer slow down as
+//! well. Limiters are token buckets holding up to a second worth of bytes, so that short bursts go
+//! through unthrottled.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::delay_for;
+
+/// Bandwidth limits in bytes per second, none by default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct BandwidthLimits {
+    /// Upload to each peer.
+    pub upload_per_peer: Option<u64>,
+    /// Download from each peer.
+    pub download_per_peer: Option<u64>,
+    /// Upload to all peers together.
+    pub upload_total: Option<u64>,
+    /// Download from all peers together.
+    pub download_total: Option<u64>,
+}
+
+struct Bucket {
+    /// Bytes that can be used right away. Negative if used ahead of time, which the next user
+    /// has to wait for.
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1) as f64;
+        RateLimiter {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket { tokens: bytes_per_sec, updated: Instant::now() }),
+        }
+    }
+
+    /// Takes the bytes from the bucket, returning how long to wait before using them.
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.bytes_per_sec).min(self.bytes_per_sec);
+        bucket.updated = now;
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec)
+        } else {
+            Duration::from_secs(0)
+        }
+    }
+}
+
+/// Limiters of one direction of a connection.
+#[derive(Clone)]
+pub struct Throttle {
+    limiters: Vec<Arc<RateLimiter>>,
+}
+
+impl Throttle {
+    fn new(per_peer: Option<u64>, total: &Option<Arc<RateLimiter>>) -> Self {
+        let limiters = per_peer.map(|limit| Arc::new(RateLimiter::new(limit)));
+        Throttle { limiters: limiters.into_iter().chain(total.clone()).collect() }
+    }
+
+    /// Waits until the bytes can be transferred.
+    pub async fn consume(&self, bytes: usize) {
+        let now = Instant::now();
+        let wait = self.limiters.iter().map(|limiter| limiter.reserve(bytes, now)).max();
+        if let Some(wait) = wait.filter(|wait| *wait > Duration::from_secs(0)) {
+            delay_for(wait).await;
+        }
+    }
+}
+
+/// Creates the throttles of the connections, sharing the limiters of all peers.
+pub struct Bandwidth {
+    limits: BandwidthLimits,
+    upload_total: Option<Arc<RateLimiter>>,
+    download_total: Option<Arc<RateLimiter>>,
+}
+
+impl Bandwidth {
+    pub fn new(limits: BandwidthLimits) -> Self {
+        Bandwidth {
+            upload_total: limits.upload_total.map(|limit| Arc::new(RateLimiter::new(limit))),
+            download_total: limits.download_total.map(|limit| Arc::new(RateLimiter::new(limit))),
+            limits,
+        }
+    }
+
+    /// Upload and download throttles of a new connection.
+    pub fn connection(&self) -> (Throttle, Throttle) {
+        (
+            Throttle::new(self.limits.upload_per_peer, &self.upload_total),
+            Throttle::new(self.limits.download_per_peer, &self.download_total),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let now = Instant::now();
+        // A second worth of bytes goes through right away.
+        assert_eq!(limiter.reserve(600, now), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(400, now), Duration::from_secs(0));
+        // More has to wait for the bucket to refill.
+        assert_eq!(limiter.reserve(500, now), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(500, now), Duration::from_secs(1));
+        // After waiting, the rate is kept.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.reserve(100, later), Duration::from_millis(100));
+        // The bucket doesn't fill over a second worth of bytes.
+        let much_later = now + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(1000, much_later), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(1, much_later), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,162 @@
+//! TIER1 connections between validators.
+//!
+//! Approvals and chunk parts used to be routed through the graph of peers, taking several hops to
+//! reach the validator they are for. Instead, every validator advertises the peers at which other
+//! validators can connect to it directly: itself, or proxies connected to it. The advertisement is
+//! an `AccountData` signed with the node key and gossiped to all peers. Every validator of the
+//! current epoch keeps a connection to each other validator, or to one of its proxies, and sends
+//! consensus messages over it.
+//!
+//! Account data is only accepted for the node and epoch that the `AnnounceAccount` of the
+//! validator, signed with the key of its account, binds together.
+use std::collections::HashMap;
+
+use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::types::AccountId;
+
+use crate::types::{AccountData, RoutedMessageBody, SignedAccountData};
+
+/// Most proxies accepted in the data of an account.
+pub const MAX_TIER1_PROXIES: usize = 8;
+
+/// Whether the message is sent over TIER1 connections when there is one to its target.
+pub fn is_tier1_message(body: &RoutedMessageBody) -> bool {
+    match body {
+        RoutedMessageBody::BlockApproval(_)
+        | RoutedMessageBody::PartialEncodedChunk(_)
+        | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+        | RoutedMessageBody::PartialEncodedChunkRequest(_) => true,
+        #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+        RoutedMessageBody::PartialEncodedChunkForward(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether the data is for the node and epoch of the announcement of its account.
+pub fn matches_announce(data: &AccountData, announce: &AnnounceAccount) -> bool {
+    data.account_id == announce.account_id
+        && data.peer_id == announce.peer_id
+        && data.epoch_id == announce.epoch_id
+}
+
+/// Latest known account data of the validators.
+#[derive(Default)]
+pub struct Tier1 {
+    accounts: HashMap<AccountId, SignedAccountData>,
+}
+
+impl Tier1 {
+    /// Keeps the data newer than the one known for its account, returning it.
+    pub fn insert(&mut self, accounts_data: Vec<SignedAccountData>) -> Vec<SignedAccountData> {
+        let mut new_data = vec![];
+        for signed_data in accounts_data {
+            let is_newer = self
+                .accounts
+                .get(&signed_data.data.account_id)
+                .map_or(true, |known| known.data.timestamp < signed_data.data.timestamp);
+            if is_newer {
+                self.accounts.insert(signed_data.data.account_id.clone(), signed_data.clone());
+                new_data.push(signed_data);
+            }
+        }
+        new_data
+    }
+
+    pub fn get(&self, account_id: &AccountId) -> Option<&AccountData> {
+        self.accounts.get(account_id).map(|signed_data| &signed_data.data)
+    }
+
+    pub fn all(&self) -> Vec<SignedAccountData> {
+        self.accounts.values().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Removes the data for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&AccountData) -> bool) {
+        self.accounts.retain(|_, signed_data| keep(&signed_data.data));
+    }
+
+    /// Account data of the validators other than `me`.
+    pub fn others<'a>(&'a self, me: &'a PeerId) -> impl Iterator<Item = &'a AccountData> + 'a {
+        self.accounts
+            .values()
+            .map(|signed_data| &signed_data.data)
+            .filter(move |data| &data.peer_id != me)
+    }
+
+    /// Peers over which the validator with the given node is reached, itself first.
+    pub fn peers_of(&self, peer_id: &PeerId) -> Vec<PeerId> {
+        match self.accounts.values().find(|signed_data| &signed_data.data.peer_id == peer_id) {
+            Some(signed_data) => std::iter::once(peer_id.clone())
+                .chain(signed_data.data.proxies.iter().map(|proxy| proxy.id.clone()))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Whether the peer is a validator or one of its proxies.
+    pub fn is_tier1_peer(&self, peer_id: &PeerId) -> bool {
+        self.accounts.values().any(|signed_data| {
+            &signed_data.data.peer_id == peer_id
+                || signed_data.data.proxies.iter().any(|proxy| &proxy.id == peer_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::types::EpochId;
+
+    use super::*;
+    use crate::types::PeerInfo;
+
+    fn account_data(seed: &str, timestamp: u64, proxies: Vec<PeerInfo>) -> SignedAccountData {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, seed);
+        AccountData {
+            account_id: seed.to_string(),
+            peer_id: secret_key.public_key().into(),
+            epoch_id: EpochId::default(),
+            proxies,
+            timestamp,
+        }
+        .sign(&secret_key)
+    }
+
+    #[test]
+    fn test_signed_account_data() {
+        let signed_data = account_data("test0", 1, vec![]);
+        assert!(signed_data.verify());
+        let mut forged = signed_data.clone();
+        forged.data.proxies.push(PeerInfo::random());
+        assert!(!forged.verify());
+        let mut other_node = signed_data;
+        other_node.data.peer_id = PeerInfo::random().id;
+        assert!(!other_node.verify());
+    }
+
+    #[test]
+    fn test_tier1_insert() {
+        let mut tier1 = Tier1::default();
+        let proxy = PeerInfo::random();
+        let old = account_data("test0", 1, vec![]);
+        let new = account_data("test0", 2, vec![proxy.clone()]);
+        let validator = new.data.peer_id.clone();
+
+        assert_eq!(tier1.insert(vec![new.clone()]), vec![new.clone()]);
+        // Older or already known data is ignored.
+        assert!(tier1.insert(vec![old, new.clone()]).is_empty());
+        assert_eq!(tier1.get(&"test0".to_string()), Some(&new.data));
+
+        assert_eq!(tier1.peers_of(&validator), vec![validator.clone(), proxy.id.clone()]);
+        assert!(tier1.is_tier1_peer(&proxy.id));
+        assert!(!tier1.is_tier1_peer(&PeerInfo::random().id));
+        assert_eq!(tier1.others(&validator).count(), 0);
+
+        tier1.retain(|data| data.timestamp > 2);
+        assert!(tier1.is_empty());
+    }
+}
@@ -0,0 +1,656 @@
+//! Transports of peer connections.
+//!
+//! TCP carries all messages on a single stream, sending them in the order of their `TrafficClass`.
+//! With `Transport::Quic` the node also accepts QUIC connections on the UDP port of its listening
+//! address and dials peers over QUIC first, falling back to TCP if the peer doesn't answer or
+//! doesn't speak `ALPN_PROTOCOL`. Over QUIC every `TrafficClass` gets its own unidirectional
+//! stream, so that e.g. a large block doesn't hold back chunk parts, and reconnects to known peers
+//! send the handshake in 0-RTT.
+//!
+//! A node's certificate is issued for its node key, so QUIC needs an ED25519 node key. The dialing
+//! node only accepts the certificate of the peer it dials, which binds the TLS session to the peer
+//! the handshake is addressed to. The dialing node itself is authenticated by the signed edge in
+//! its handshake, which names the peer it's for. A replayed 0-RTT handshake carries an edge nonce
+//! that was already used, so it gets the connection rejected on consolidation. TCP connections are
+//! encrypted and mutually authenticated with the node keys instead, see `encryption`.
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{ActorContext, ActorFuture, AsyncContext, Context};
+use futures::channel::{mpsc, oneshot};
+use futures::stream::LocalBoxStream;
+use futures::{future, FutureExt, Stream, StreamExt};
+use quinn::{
+    Certificate, CertificateChain, ClientConfig, ClientConfigBuilder, Endpoint, Incoming,
+    IncomingUniStreams, NewConnection, PrivateKey, RecvStream, SendStream, ServerConfig,
+    ServerConfigBuilder, VarInt,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWrite};
+use tokio_util::codec::FramedRead;
+use tracing::{debug, warn};
+
+use near_crypto::{ED25519PublicKey, PublicKey, SecretKey, SessionCipher};
+use near_primitives::network::PeerId;
+
+use crate::codec::Codec;
+use crate::encryption::{Session, TcpConnection};
+use crate::metrics::NetworkMetrics;
+use crate::peer::Peer;
+use crate::send_queue::{send_queue, QueuedMessage, SendQueue, SendQueueConfig};
+use crate::throttle::Throttle;
+use crate::types::{PeerMessage, ReasonForBan, RoutedMessageBody};
+
+/// Application protocol negotiated in the TLS handshake of QUIC connections, bumped if the
+/// mapping of messages to streams changes.
+const ALPN_PROTOCOL: &[u8] = b"near/2";
+/// Name the certificates are issued for, the same for all nodes.
+const SERVER_NAME: &str = "near-node";
+/// PKCS #8 encoding of an ED25519 private key, followed by the 32 bytes of its seed.
+const ED25519_PKCS8_PREFIX: &[u8] = &[
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+/// `SubjectPublicKeyInfo` of an ED25519 public key, followed by the 32 bytes of the key.
+const ED25519_SPKI_PREFIX: &[u8] =
+    &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+/// Time to wait for the QUIC handshake before falling back to TCP.
+const QUIC_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of received messages buffered before reading from the streams is paused.
+const QUIC_RECEIVE_BUFFER: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Tcp,
+    Quic,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Tcp
+    }
+}
+
+/// Classes of messages on a connection, from the most urgent. Over QUIC every class has its own
+/// stream, so messages of a class are received in the order they were sent and messages of
+/// different classes may overtake each other. Over TCP the queued messages of the more urgent
+/// classes are written first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::AsRefStr)]
+pub enum TrafficClass {
+    /// Handshake, approvals, challenges and other small messages the connection or consensus
+    /// waits on.
+    Control = 0,
+    /// Chunk parts, their requests and responses.
+    ChunkParts = 1,
+    /// Blocks and headers, their requests and responses.
+    Blocks = 2,
+    /// State sync, queries and other routed messages.
+    Sync = 3,
+    /// Routing table exchange, edges and peer lists.
+    Routing = 4,
+    /// Transaction gossip.
+    Transactions = 5,
+}
+
+pub const NUM_TRAFFIC_CLASSES: usize = 6;
+
+impl TrafficClass {
+    pub fn of(message: &PeerMessage) -> Self {
+        match message {
+            PeerMessage::Handshake(_)
+            | PeerMessage::HandshakeV2(_)
+            | PeerMessage::HandshakeFailure(_, _)
+            | PeerMessage::LastEdge(_)
+            | PeerMessage::Disconnect
+            | PeerMessage::Challenge(_) => TrafficClass::Control,
+            // Time spent behind other messages skews the clock measurements.
+            PeerMessage::TimePing(_) | PeerMessage::TimePong(_) => TrafficClass::Control,
+            PeerMessage::Block(_)
+            | PeerMessage::BlockRequest(_)
+            | PeerMessage::BlockHeaders(_)
+            | PeerMessage::BlockHeadersRequest(_) => TrafficClass::Blocks,
+            // Messages are classified before they are wrapped or compressed.
+            PeerMessage::Compressed(_) | PeerMessage::Versioned(_) => TrafficClass::Sync,
+            PeerMessage::RoutingTableSync(_)
+            | PeerMessage::RequestUpdateNonce(_)
+            | PeerMessage::ResponseUpdateNonce(_)
+            | PeerMessage::PeersRequest
+            | PeerMessage::PeersResponse(_)
+            | PeerMessage::SyncAccountsData(_)
+            | PeerMessage::PeerRecords(_)
+            | PeerMessage::ObservedAddr(_) => TrafficClass::Routing,
+            PeerMessage::Transaction(_)
+            | PeerMessage::ForwardTxAnnouncement(_)
+            | PeerMessage::TransactionsRequest(_)
+            | PeerMessage::TransactionsResponse(_) => TrafficClass::Transactions,
+            PeerMessage::Routed(routed_message) => match routed_message.body {
+                RoutedMessageBody::BlockApproval(_) => TrafficClass::Control,
+                RoutedMessageBody::PartialEncodedChunk(_)
+                | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+                | RoutedMessageBody::PartialEncodedChunkRequest(_)
+                | RoutedMessageBody::PartialEncodedChunkResponse(_) => TrafficClass::ChunkParts,
+                #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+                RoutedMessageBody::PartialEncodedChunkForward(_) => TrafficClass::ChunkParts,
+                RoutedMessageBody::ForwardTx(_) => TrafficClass::Transactions,
+                _ => TrafficClass::Sync,
+            },
+        }
+    }
+}
+
+/// QUIC endpoint of the node, with the client configuration that peer certificates are checked
+/// against when dialing.
+#[derive(Clone)]
+pub struct QuicEndpoint {
+    endpoint: Endpoint,
+    client_config: ClientConfig,
+}
+
+impl QuicEndpoint {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.local_addr()
+    }
+}
+
+/// Binds the QUIC endpoint, accepting connections only if `listen_addr` is given.
+pub fn quic_endpoint(
+    listen_addr: Option<SocketAddr>,
+    secret_key: &SecretKey,
+) -> Result<(QuicEndpoint, Incoming), String> {
+    let mut builder = Endpoint::builder();
+    let bind_addr = match listen_addr {
+        Some(listen_addr) => {
+            builder.listen(server_config(secret_key)?);
+            listen_addr
+        }
+        None => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+    };
+    let (endpoint, incoming) = builder.bind(&bind_addr).map_err(|err| err.to_string())?;
+    let mut client_config = ClientConfigBuilder::default();
+    client_config.protocols(&[ALPN_PROTOCOL]);
+    Ok((QuicEndpoint { endpoint, client_config: client_config.build() }, incoming))
+}
+
+fn server_config(secret_key: &SecretKey) -> Result<ServerConfig, String> {
+    let (certificate, key) = node_certificate(secret_key)?;
+    let mut builder = ServerConfigBuilder::default();
+    builder.protocols(&[ALPN_PROTOCOL]);
+    builder
+        .certificate(CertificateChain::from_certs(vec![certificate]), key)
+        .map_err(|err| err.to_string())?;
+    Ok(builder.build())
+}
+
+/// Self-signed certificate whose key is the node key.
+fn node_certificate(secret_key: &SecretKey) -> Result<(Certificate, PrivateKey), String> {
+    let seed = match secret_key {
+        SecretKey::ED25519(secret_key) => &secret_key.0[..32],
+        _ => return Err("QUIC requires an ED25519 node key".to_string()),
+    };
+    let key_pair = rcgen::KeyPair::from_der(&[ED25519_PKCS8_PREFIX, seed].concat())
+        .map_err(|err| err.to_string())?;
+    let mut params = rcgen::CertificateParams::new(vec![SERVER_NAME.to_string()]);
+    params.alg = &rcgen::PKCS_ED25519;
+    params.key_pair = Some(key_pair);
+    let certificate = rcgen::Certificate::from_params(params).map_err(|err| err.to_string())?;
+    let key = PrivateKey::from_der(&certificate.serialize_private_key_der())
+        .map_err(|err| err.to_string())?;
+    let certificate =
+        Certificate::from_der(&certificate.serialize_der().map_err(|err| err.to_string())?)
+            .map_err(|err| err.to_string())?;
+    Ok((certificate, key))
+}
+
+/// Node key the DER encoded certificate is issued for. The TLS handshake proves that the peer
+/// holds the private key of the certificate.
+fn certificate_peer_id(certificate: &[u8]) -> Option<PeerId> {
+    let tbs_certificate = yasna::parse_der(certificate, |reader| {
+        reader.read_sequence(|reader| {
+            let tbs_certificate = reader.next().read_sequence(|reader| {
+                let mut fields = vec![];
+                while let Some(field) = reader.read_optional(|reader| reader.read_der())? {
+                    fields.push(field);
+                }
+                Ok(fields)
+            })?;
+            // Signature algorithm and signature.
+            reader.next().read_der()?;
+            reader.next().read_der()?;
+            Ok(tbs_certificate)
+        })
+    })
+    .ok()?;
+    // The explicitly tagged version is followed by the serial number, signature algorithm,
+    // issuer, validity and subject.
+    let version = tbs_certificate.first()?.first() == Some(&0xa0);
+    let spki = tbs_certificate.get(if version { 6 } else { 5 })?;
+    if spki.len() != ED25519_SPKI_PREFIX.len() + 32 || !spki.starts_with(ED25519_SPKI_PREFIX) {
+        return None;
+    }
+    let mut public_key = [0; 32];
+    public_key.copy_from_slice(&spki[ED25519_SPKI_PREFIX.len()..]);
+    Some(PublicKey::ED25519(ED25519PublicKey(public_key)).into())
+}
+
+/// Accepts only the certificate of the node key being dialed.
+struct PeerCertificateVerifier {
+    peer_id: PeerId,
+}
+
+impl rustls::ServerCertVerifier for PeerCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        match presented_certs.first().and_then(|certificate| certificate_peer_id(&certificate.0)) {
+            Some(peer_id) if peer_id == self.peer_id => Ok(rustls::ServerCertVerified::assertion()),
+            Some(peer_id) => Err(rustls::TLSError::General(format!(
+                "Certificate of {} instead of {}",
+                peer_id, self.peer_id
+            ))),
+            None => Err(rustls::TLSError::General("Not a node certificate".to_string())),
+        }
+    }
+}
+
+/// QUIC connection with a stream opened for each class of messages.
+pub struct QuicConnection {
+    connection: quinn::Connection,
+    incoming_streams: IncomingUniStreams,
+    outgoing_streams: Vec<SendStream>,
+    local_addr: SocketAddr,
+    /// Node key of the certificate, only checked on connections this node dialed.
+    peer_id: Option<PeerId>,
+}
+
+impl QuicConnection {
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.connection.remote_address()
+    }
+
+    async fn new(
+        new_connection: NewConnection,
+        local_addr: SocketAddr,
+        peer_id: Option<PeerId>,
+    ) -> Result<Self, quinn::ConnectionError> {
+        let NewConnection { connection, uni_streams, .. } = new_connection;
+        // Streams are opened in the order of classes, which is the order the peer accepts them.
+        let mut outgoing_streams = Vec::with_capacity(NUM_TRAFFIC_CLASSES);
+        for _ in 0..NUM_TRAFFIC_CLASSES {
+            outgoing_streams.push(connection.open_uni().await?);
+        }
+        Ok(Self {
+            connection,
+            incoming_streams: uni_streams,
+            outgoing_streams,
+            local_addr,
+            peer_id,
+        })
+    }
+
+    pub async fn accept(
+        connecting: quinn::Connecting,
+        local_addr: SocketAddr,
+    ) -> Result<Self, quinn::ConnectionError> {
+        Self::new(connecting.await?, local_addr, None).await
+    }
+
+    /// Connects to the peer, sending the first messages in 0-RTT if the peer was connected before.
+    /// The connection fails unless the peer presents the certificate of `peer_id`.
+    pub async fn connect(
+        endpoint: QuicEndpoint,
+        addr: SocketAddr,
+        peer_id: PeerId,
+    ) -> Result<Self, String> {
+        let QuicEndpoint { endpoint, mut client_config } = endpoint;
+        let local_addr = endpoint.local_addr().map_err(|err| err.to_string())?;
+        // The configuration is cloned with the session cache, so that 0-RTT works on reconnects.
+        let mut crypto = (*client_config.crypto).clone();
+        crypto.dangerous().set_certificate_verifier(Arc::new(PeerCertificateVerifier {
+            peer_id: peer_id.clone(),
+        }));
+        client_config.crypto = Arc::new(crypto);
+        let connecting = endpoint
+            .connect_with(client_config, &addr, SERVER_NAME)
+            .map_err(|err| err.to_string())?;
+        let new_connection = match connecting.into_0rtt() {
+            Ok((new_connection, zero_rtt_accepted)) => {
+                // Data sent in 0-RTT is lost if the peer rejects it, e.g. after a restart. The
+                // connection is closed so that the handshake is retried.
+                let connection = new_connection.connection.clone();
+                actix::spawn(zero_rtt_accepted.map(move |accepted| {
+                    if !accepted {
+                        debug!(target: "network", "0-RTT rejected by {}", addr);
+                        connection.close(VarInt::from_u32(0), b"0-RTT rejected");
+                    }
+                }));
+                new_connection
+            }
+            Err(connecting) => tokio::time::timeout(QUIC_CONNECT_TIMEOUT, connecting)
+                .await
+                .map_err(|_| "timed out".to_string())?
+                .map_err(|err| err.to_string())?,
+        };
+        Self::new(new_connection, local_addr, Some(peer_id)).await.map_err(|err| err.to_string())
+    }
+}
+
+pub enum PeerConnection {
+    Tcp(TcpConnection),
+    Quic(QuicConnection),
+}
+
+impl PeerConnection {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            PeerConnection::Tcp(connection) => connection.stream.local_addr(),
+            PeerConnection::Quic(connection) => Ok(connection.local_addr),
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            PeerConnection::Tcp(connection) => connection.stream.peer_addr(),
+            PeerConnection::Quic(connection) => Ok(connection.remote_addr()),
+        }
+    }
+
+    /// Node key the connection is encrypted with, if it is.
+    pub fn authenticated_peer_id(&self) -> Option<&PeerId> {
+        match self {
+            PeerConnection::Tcp(connection) => {
+                connection.session.as_ref().map(|session| &session.peer_id)
+            }
+            PeerConnection::Quic(connection) => connection.peer_id.as_ref(),
+        }
+    }
+
+    /// Splits the connection into the received messages and the writer of the peer actor.
+    pub fn split(
+        self,
+        ctx: &mut Context<Peer>,
+        upload: Throttle,
+        download: Throttle,
+        send_queue_config: &SendQueueConfig,
+        network_metrics: NetworkMetrics,
+    ) -> (LocalBoxStream<'static, Result<Vec<u8>, ReasonForBan>>, PeerWriter) {
+        let network_metrics = Rc::new(network_metrics);
+        match self {
+            PeerConnection::Tcp(TcpConnection { stream, prefix, session }) => {
+                let (read, write) = tokio::io::split(stream);
+                let frames = FramedRead::new(io::Cursor::new(prefix).chain(read), Codec::new());
+                let (queue, messages) = send_queue(send_queue_config.clone(), network_metrics);
+                let messages = match session {
+                    Some(Session { mut send, mut recv, .. }) => {
+                        spawn_writer(
+                            ctx,
+                            messages.map(move |bytes| send.seal(bytes)),
+                            write,
+                            upload,
+                        );
+                        read_frames(frames.map(move |frame| open_frame(&mut recv, frame)))
+                            .boxed_local()
+                    }
+                    None => {
+                        spawn_writer(ctx, messages, write, upload);
+                        read_frames(frames).boxed_local()
+                    }
+                };
+                (throttle_reads(messages, download).boxed_local(), PeerWriter::Tcp(queue))
+            }
+            PeerConnection::Quic(QuicConnection {
+                connection,
+                incoming_streams,
+                outgoing_streams,
+                ..
+            }) => {
+                let streams = outgoing_streams
+                    .into_iter()
+                    .map(|stream| {
+                        let (queue, messages) =
+                            send_queue(send_queue_config.clone(), network_metrics.clone());
+                        spawn_writer(ctx, messages, stream, upload.clone());
+                        queue
+                    })
+                    .collect();
+                let messages = receive_quic(incoming_streams);
+                (
+                    throttle_reads(messages, download).boxed_local(),
+                    PeerWriter::Quic { connection, streams },
+                )
+            }
+        }
+    }
+}
+
+/// Writes the queued messages as fast as the stream and the upload throttle allow, stopping the
+/// peer if writing fails.
+fn spawn_writer<S, W>(ctx: &mut Context<Peer>, messages: S, write: W, upload: Throttle)
+where
+    S: Stream<Item = Vec<u8>> + 'static,
+    W: AsyncWrite + Unpin + 'static,
+{
+    let frames = tokio_util::codec::FramedWrite::new(write, Codec::new());
+    let write_all = messages
+        .then(move |message| {
+            let upload = upload.clone();
+            async move {
+                upload.consume(message.len()).await;
+                Ok(message)
+            }
+        })
+        .forward(frames);
+    ctx.spawn(actix::fut::wrap_future::<_, Peer>(write_all).map(|res, _, ctx| {
+        if let Err(err) = res {
+            warn!(target: "network", "Failed writing to peer: {}", err);
+        }
+        ctx.stop();
+    }));
+}
+
+/// Delays every received message until the download throttle allows it.
+fn throttle_reads<S>(
+    messages: S,
+    download: Throttle,
+) -> impl Stream<Item = Result<Vec<u8>, ReasonForBan>>
+where
+    S: Stream<Item = Result<Vec<u8>, ReasonForBan>>,
+{
+    messages.then(move |message| {
+        let download = download.clone();
+        async move {
+            if let Ok(bytes) = &message {
+                download.consume(bytes.len()).await;
+            }
+            message
+        }
+    })
+}
+
+/// Decoded frames until the first error of the underlying stream.
+fn read_frames<S>(frames: S) -> impl Stream<Item = Result<Vec<u8>, ReasonForBan>>
+where
+    S: Stream<Item = io::Result<Result<Vec<u8>, ReasonForBan>>>,
+{
+    frames
+        .take_while(|x| match x {
+            Ok(_) => future::ready(true),
+            Err(e) => {
+                warn!(target: "network", "Peer stream error: {:?}", e);
+                future::ready(false)
+            }
+        })
+        .map(Result::unwrap)
+}
+
+/// Decrypts the frame received over an encrypted connection. A frame that doesn't decrypt ends the
+/// stream, since the following ones won't either.
+fn open_frame(
+    recv: &mut SessionCipher,
+    frame: io::Result<Result<Vec<u8>, ReasonForBan>>,
+) -> io::Result<Result<Vec<u8>, ReasonForBan>> {
+    match frame? {
+        Ok(bytes) => recv
+            .open(bytes)
+            .map(Ok)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt frame")),
+        Err(reason) => Ok(Err(reason)),
+    }
+}
+
+/// Merges the messages of all streams of the peer. Messages of other classes are held back until
+/// the first control message, the handshake, is received.
+fn receive_quic(
+    mut incoming_streams: IncomingUniStreams,
+) -> impl Stream<Item = Result<Vec<u8>, ReasonForBan>> {
+    let (sender, receiver) = mpsc::channel(QUIC_RECEIVE_BUFFER);
+    actix::spawn(async move {
+        // Streams are accepted in the order the peer opened them, the control stream first.
+        let control = match incoming_streams.next().await {
+            Some(Ok(stream)) => stream,
+            _ => return,
+        };
+        let (handshake_sender, handshake_received) = oneshot::channel::<()>();
+        let handshake_received = handshake_received.shared();
+        let mut handshake_sender = Some(handshake_sender);
+        let control = receive_stream(control).inspect(move |_| {
+            if let Some(handshake_sender) = handshake_sender.take() {
+                let _ = handshake_sender.send(());
+            }
+        });
+        actix::spawn(control.forward(sender.clone()).map(drop));
+        while let Some(Ok(stream)) = incoming_streams.next().await {
+            let handshake_received = handshake_received.clone();
+            let sender = sender.clone();
+            actix::spawn(async move {
+                if handshake_received.await.is_ok() {
+                    let _ = receive_stream(stream).forward(sender).await;
+                }
+            });
+        }
+    });
+    receiver
+}
+
+fn receive_stream(
+    stream: RecvStream,
+) -> impl Stream<Item = Result<Result<Vec<u8>, ReasonForBan>, mpsc::SendError>> {
+    read_frames(FramedRead::new(stream, Codec::new())).map(Ok)
+}
+
+/// Writes the messages to the peer, buffering them until the connection is writable.
+pub enum PeerWriter {
+    Tcp(SendQueue),
+    Quic {
+        connection: quinn::Connection,
+        /// Queue of the stream of each class, indexed by `TrafficClass`.
+        streams: Vec<SendQueue>,
+    },
+}
+
+impl PeerWriter {
+    pub fn transport(&self) -> Transport {
+        match self {
+            PeerWriter::Tcp(_) => Transport::Tcp,
+            PeerWriter::Quic { .. } => Transport::Quic,
+        }
+    }
+
+    pub fn write(&mut self, class: TrafficClass, message: QueuedMessage) {
+        match self {
+            PeerWriter::Tcp(queue) => queue.push(class, message),
+            PeerWriter::Quic { streams, .. } => streams[class as usize].push(class, message),
+        }
+    }
+
+    /// Bytes and number of messages sent over the last minute.
+    pub fn sent_per_min(&self) -> (u64, u64) {
+        match self {
+            PeerWriter::Tcp(queue) => queue.sent_per_min(),
+            PeerWriter::Quic { streams, .. } => streams
+                .iter()
+                .map(SendQueue::sent_per_min)
+                .fold((0, 0), |(bytes, count), sent| (bytes + sent.0, count + sent.1)),
+        }
+    }
+}
+
+impl Drop for PeerWriter {
+    fn drop(&mut self) {
+        if let PeerWriter::Quic { connection, .. } = self {
+            connection.close(VarInt::from_u32(0), b"disconnected");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::network::PeerId;
+    use near_primitives::sharding::ChunkHash;
+
+    use crate::types::{PartialEncodedChunkRequestMsg, PeerIdOrHash, RoutedMessage};
+
+    use super::*;
+
+    #[test]
+    fn test_traffic_class() {
+        assert_eq!(
+            TrafficClass::of(&PeerMessage::BlockRequest(CryptoHash::default())),
+            TrafficClass::Blocks
+        );
+        assert_eq!(TrafficClass::of(&PeerMessage::Disconnect), TrafficClass::Control);
+        assert_eq!(TrafficClass::of(&PeerMessage::PeersRequest), TrafficClass::Routing);
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let routed = |body| {
+            PeerMessage::Routed(RoutedMessage {
+                target: PeerIdOrHash::PeerId(PeerId::from(secret_key.public_key())),
+                author: PeerId::from(secret_key.public_key()),
+                signature: secret_key.sign(&[]),
+                ttl: 1,
+                body,
+            })
+        };
+        assert_eq!(
+            TrafficClass::of(&routed(RoutedMessageBody::StateRequestHeader(
+                0,
+                CryptoHash::default()
+            ))),
+            TrafficClass::Sync
+        );
+        assert_eq!(
+            TrafficClass::of(&routed(RoutedMessageBody::PartialEncodedChunkRequest(
+                PartialEncodedChunkRequestMsg {
+                    chunk_hash: ChunkHash(CryptoHash::default()),
+                    part_ords: vec![],
+                    tracking_shards: HashSet::new(),
+                }
+            ))),
+            TrafficClass::ChunkParts
+        );
+    }
+
+    #[test]
+    fn test_node_certificate() {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let (certificate, _) = node_certificate(&secret_key).unwrap();
+        assert_eq!(
+            certificate_peer_id(certificate.as_der()),
+            Some(PeerId::from(secret_key.public_key()))
+        );
+
+        let other_certificate =
+            rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).unwrap();
+        assert_eq!(certificate_peer_id(&other_certificate.serialize_der().unwrap()), None);
+        assert!(node_certificate(&SecretKey::from_seed(KeyType::SECP256K1, "test")).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Forwarding transactions without sending the same transaction twice.
+//!
+//! Transactions travel to chunk producers in routed `ForwardTx` messages, and on busy shards the
+//! same transaction arrives from many RPC nodes and over many routes. To peers with
+//! `MessageCapability::TxAnnouncement`, a routed transaction is sent as a
+//! `ForwardTxAnnouncement` instead: the signed routed message with the hash of the transaction in
+//! place of the transaction. The peer rebuilds the message from the transactions the node got
+//! recently over any connection, and only asks for the ones it doesn't have.
+//!
+//! Every connection also filters the routed transactions recently sent to or received from the
+//! peer, so that the same message is not sent or handled twice.
+use std::sync::{Arc, Mutex};
+
+use borsh::BorshSerialize;
+use cached::{Cached, SizedCache};
+
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::transaction::SignedTransaction;
+
+use crate::types::{ForwardTxAnnouncement, PeerMessage, RoutedMessage, RoutedMessageBody};
+
+/// Transactions kept to rebuild announcements and answer requests.
+const RECENT_TRANSACTIONS_CACHE_SIZE: usize = 10_000;
+/// Routed transactions remembered per connection.
+const SEEN_CACHE_SIZE: usize = 10_000;
+/// Transactions waited for per connection.
+const PENDING_CACHE_SIZE: usize = 1_000;
+/// Transactions that can be asked for in one request.
+pub const MAX_TRANSACTIONS_REQUEST: usize = 256;
+
+/// Hash of the whole signed transaction, which announcements refer to it by.
+pub fn signed_tx_hash(tx: &SignedTransaction) -> CryptoHash {
+    hash(&tx.try_to_vec().expect("Failed to serialize"))
+}
+
+/// Transactions recently sent or received over any connection.
+pub struct RecentTransactions {
+    txs: Mutex<SizedCache<CryptoHash, SignedTransaction>>,
+}
+
+impl Default for RecentTransactions {
+    fn default() -> Self {
+        RecentTransactions {
+            txs: Mutex::new(SizedCache::with_size(RECENT_TRANSACTIONS_CACHE_SIZE)),
+        }
+    }
+}
+
+impl RecentTransactions {
+    pub fn insert(&self, tx: SignedTransaction) {
+        self.txs.lock().unwrap().cache_set(signed_tx_hash(&tx), tx);
+    }
+
+    pub fn get(&self, signed_tx_hash: &CryptoHash) -> Option<SignedTransaction> {
+        self.txs.lock().unwrap().cache_get(signed_tx_hash).cloned()
+    }
+}
+
+/// What to do with an announcement received from the peer.
+#[derive(Debug, PartialEq)]
+pub enum Announced {
+    /// The transaction is known, handle the message as if it was received.
+    Rebuilt(RoutedMessage),
+    /// Ask the peer for the transaction.
+    Request(CryptoHash),
+    /// The transaction was already asked for.
+    Waiting,
+}
+
+/// Transaction forwarding state of a connection.
+pub struct TxGossip {
+    recent_txs: Arc<RecentTransactions>,
+    /// Hashes of the routed transactions sent to or received from the peer.
+    seen: SizedCache<CryptoHash, ()>,
+    /// Announcements received from the peer, by the transaction asked for.
+    pending: SizedCache<CryptoHash, Vec<ForwardTxAnnouncement>>,
+}
+
+impl TxGossip {
+    pub fn new(recent_txs: Arc<RecentTransactions>) -> Self {
+        TxGossip {
+            recent_txs,
+            seen: SizedCache::with_size(SEEN_CACHE_SIZE),
+            pending: SizedCache::with_size(PENDING_CACHE_SIZE),
+        }
+    }
+
+    /// Records the routed message as seen, returns whether it's the first time.
+    fn see(&mut self, msg: &RoutedMessage) -> bool {
+        let hash = msg.hash();
+        if self.seen.cache_get(&hash).is_some() {
+            return false;
+        }
+        self.seen.cache_set(hash, ());
+        true
+    }
+
+    /// Message to send the routed transaction with, announced if the peer understands it, or
+    /// `None` if it was already sent to or received from the peer.
+    pub fn outgoing(&mut self, msg: RoutedMessage, announce: bool) -> Option<PeerMessage> {
+        let tx = match &msg.body {
+            RoutedMessageBody::ForwardTx(tx) => tx.clone(),
+            _ => return Some(PeerMessage::Routed(msg)),
+        };
+        if !self.see(&msg) {
+            return None;
+        }
+        if !announce {
+            return Some(PeerMessage::Routed(msg));
+        }
+        let signed_tx_hash = signed_tx_hash(&tx);
+        self.recent_txs.insert(tx);
+        Some(PeerMessage::ForwardTxAnnouncement(ForwardTxAnnouncement {
+            target: msg.target,
+            author: msg.author,
+            signature: msg.signature,
+            ttl: msg.ttl,
+            signed_tx_hash,
+        }))
+    }
+
+    /// Records the routed transaction received from the peer once its signature is checked,
+    /// returns whether it's the first time.
+    pub fn incoming(&mut self, msg: &RoutedMessage) -> bool {
+        if let RoutedMessageBody::ForwardTx(tx) = &msg.body {
+            if !self.see(msg) {
+                return false;
+            }
+            self.recent_txs.insert(tx.clone());
+        }
+        true
+    }
+
+    /// Handles an announcement received from the peer.
+    pub fn announced(&mut self, announcement: ForwardTxAnnouncement) -> Announced {
+        if let Some(tx) = self.recent_txs.get(&announcement.signed_tx_hash) {
+            return Announced::Rebuilt(rebuild(announcement, tx));
+        }
+        let signed_tx_hash = announcement.signed_tx_hash;
+        match self.pending.cache_get_mut(&signed_tx_hash) {
+            Some(announcements) => {
+                announcements.push(announcement);
+                Announced::Waiting
+            }
+            None => {
+                self.pending.cache_set(signed_tx_hash, vec![announcement]);
+                Announced::Request(signed_tx_hash)
+            }
+        }
+    }
+
+    /// Messages of the announcements waiting for the transactions, which were not asked for are
+    /// left out.
+    pub fn received(&mut self, txs: Vec<SignedTransaction>) -> Vec<RoutedMessage> {
+        let mut messages = vec![];
+        for tx in txs {
+            if let Some(announcements) = self.pending.cache_remove(&signed_tx_hash(&tx)) {
+                messages.extend(
+                    announcements.into_iter().map(|announcement| rebuild(announcement, tx.clone())),
+                );
+            }
+        }
+        messages
+    }
+
+    /// Transactions asked for by the peer that are still known.
+    pub fn requested(&self, hashes: &[CryptoHash]) -> Vec<SignedTransaction> {
+        hashes.iter().filter_map(|signed_tx_hash| self.recent_txs.get(signed_tx_hash)).collect()
+    }
+}
+
+fn rebuild(announcement: ForwardTxAnnouncement, tx: SignedTransaction) -> RoutedMessage {
+    RoutedMessage {
+        target: announcement.target,
+        author: announcement.author,
+        signature: announcement.signature,
+        ttl: announcement.ttl,
+        body: RoutedMessageBody::ForwardTx(tx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType, SecretKey};
+    use near_primitives::network::PeerId;
+
+    use crate::types::{AccountOrPeerIdOrHash, RawRoutedMessage};
+
+    use super::*;
+
+    fn routed_tx(nonce: u64) -> RoutedMessage {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "author");
+        let target: PeerId = SecretKey::from_seed(KeyType::ED25519, "target").public_key().into();
+        let signer = InMemorySigner::from_seed("test", KeyType::ED25519, "test");
+        let tx = SignedTransaction::send_money(
+            nonce,
+            "test".to_string(),
+            "other".to_string(),
+            &signer,
+            1,
+            CryptoHash::default(),
+        );
+        RawRoutedMessage {
+            target: AccountOrPeerIdOrHash::PeerId(target),
+            body: RoutedMessageBody::ForwardTx(tx),
+        }
+        .sign(secret_key.public_key().into(), &secret_key, 100)
+    }
+
+    fn announcement(msg: Option<PeerMessage>) -> ForwardTxAnnouncement {
+        match msg {
+            Some(PeerMessage::ForwardTxAnnouncement(announcement)) => announcement,
+            msg => panic!("Expected announcement, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn test_announce_and_request() {
+        let mut sender = TxGossip::new(Arc::new(RecentTransactions::default()));
+        let mut receiver = TxGossip::new(Arc::new(RecentTransactions::default()));
+        let msg = routed_tx(1);
+
+        // Not announced to old peers.
+        assert_eq!(
+            TxGossip::new(Arc::new(RecentTransactions::default())).outgoing(msg.clone(), false),
+            Some(PeerMessage::Routed(msg.clone()))
+        );
+
+        let first = announcement(sender.outgoing(msg.clone(), true));
+        assert_eq!(sender.outgoing(msg.clone(), true), None);
+        let signed_tx_hash = first.signed_tx_hash;
+        assert_eq!(receiver.announced(first.clone()), Announced::Request(signed_tx_hash));
+        assert_eq!(receiver.announced(first), Announced::Waiting);
+
+        let txs = sender.requested(&[signed_tx_hash, CryptoHash::default()]);
+        assert_eq!(txs.len(), 1);
+        let rebuilt = receiver.received(txs.clone());
+        assert_eq!(rebuilt, vec![msg.clone(), msg.clone()]);
+        assert!(rebuilt[0].verify());
+        assert!(receiver.incoming(&rebuilt[0]));
+        assert!(!receiver.incoming(&rebuilt[1]));
+        // Transactions that were not asked for are dropped.
+        assert!(receiver.received(txs).is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_from_other_connection() {
+        let recent_txs = Arc::new(RecentTransactions::default());
+        let mut first_peer = TxGossip::new(recent_txs.clone());
+        let mut second_peer = TxGossip::new(recent_txs);
+        let msg = routed_tx(2);
+
+        assert!(first_peer.incoming(&msg));
+        let announcement = announcement(
+            TxGossip::new(Arc::new(RecentTransactions::default())).outgoing(msg.clone(), true),
+        );
+        assert_eq!(second_peer.announced(announcement), Announced::Rebuilt(msg.clone()));
+        // The peer the message came from doesn't get it back.
+        assert_eq!(first_peer.outgoing(msg.clone(), true), None);
+        assert!(second_peer.outgoing(msg, true).is_some());
+    }
+}
@@ -1,7 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::{Into, TryFrom, TryInto};
 use std::fmt;
-use std::net::{AddrParseError, IpAddr, SocketAddr};
+use std::net::{AddrParseError, IpAddr, SocketAddr, ToSocketAddrs};
 use std::str::FromStr;
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
@@ -12,6 +12,7 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::{DateTime, Utc};
 use futures::{future::BoxFuture, FutureExt};
 use serde::{Deserialize, Serialize};
+use strum::VariantNames;
 use tokio::net::TcpStream;
 use tracing::{error, warn};
 
@@ -33,12 +34,24 @@ use near_primitives::utils::{from_timestamp, to_timestamp};
 use near_primitives::version::{
     ProtocolVersion, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
-use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest, QueryResponse};
+use near_primitives::views::{
+    FinalExecutionOutcomeView, KnownPeerView, PeerAccessView, PeerReputationView, QueryRequest,
+    QueryResponse,
+};
 
+use crate::access::{PeerAccess, PeerAccessChange};
+use crate::connection_limits::ConnectionLimits;
+use crate::encryption::EncryptionMode;
+use crate::nat::PortMapping;
 use crate::peer::Peer;
+use crate::proxy::ProxyConfig;
 #[cfg(feature = "metric_recorder")]
 use crate::recorder::MetricRecorder;
+use crate::reputation::{Misbehavior, ReputationConfig};
 use crate::routing::{Edge, EdgeInfo, RoutingTableInfo};
+use crate::send_queue::SendQueueConfig;
+use crate::throttle::BandwidthLimits;
+use crate::transport::{QuicConnection, Transport};
 use serde::export::fmt::Error;
 use serde::export::Formatter;
 use std::{fmt::Debug, io};
@@ -129,6 +142,58 @@ impl TryFrom<&str> for PeerInfo {
     }
 }
 
+/// Boot node given by host name instead of IP address, as `id@host:port`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootNodeHost {
+    pub id: PeerId,
+    pub host: String,
+    pub port: u16,
+}
+
+impl BootNodeHost {
+    /// Resolves the host name locally, for when the proxy doesn't resolve it.
+    pub fn resolve(&self) -> io::Result<PeerInfo> {
+        let addr = (self.host.as_str(), self.port).to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", self.host))
+        })?;
+        Ok(PeerInfo::new(self.id.clone(), addr))
+    }
+}
+
+impl fmt::Display for BootNodeHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}:{}", self.id, self.host, self.port)
+    }
+}
+
+impl FromStr for BootNodeHost {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || -> Self::Err {
+            Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid boot node host format: {:?}", s),
+            ))
+        };
+        let (id, host_port) = match s.split('@').collect::<Vec<_>>().as_slice() {
+            [id, host_port] => (*id, *host_port),
+            _ => return Err(invalid()),
+        };
+        let colon = host_port.rfind(':').ok_or_else(invalid)?;
+        let host = &host_port[..colon];
+        // IP addresses are parsed as `PeerInfo`.
+        if host.is_empty() || host.contains(':') || host.parse::<IpAddr>().is_ok() {
+            return Err(invalid());
+        }
+        Ok(BootNodeHost {
+            id: PeerId(id.parse()?),
+            host: host.to_string(),
+            port: host_port[colon + 1..].parse()?,
+        })
+    }
+}
+
 /// Peer chain information.
 /// TODO: Remove in next version
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Clone, Debug, Eq, PartialEq, Default)]
@@ -194,13 +259,89 @@ pub enum HandshakeFailureReason {
 
 impl fmt::Display for HandshakeFailureReason {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "HandshakeFailureReason")
+        match self {
+            HandshakeFailureReason::ProtocolVersionMismatch {
+                version,
+                oldest_supported_version,
+            } => write!(
+                f,
+                "Protocol version mismatch: peer supports versions {}..={}, we support {}..={}",
+                oldest_supported_version,
+                version,
+                OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            ),
+            HandshakeFailureReason::GenesisMismatch(genesis_id) => {
+                write!(f, "Genesis mismatch: {:?}", genesis_id)
+            }
+            HandshakeFailureReason::InvalidTarget => write!(f, "Invalid target peer"),
+        }
     }
 }
 
 impl std::error::Error for HandshakeFailureReason {}
 
-#[derive(BorshSerialize, Serialize, PartialEq, Eq, Clone, Debug)]
+/// Oldest protocol version that sends `PeerMessage::Handshake`. Peers talking an older version
+/// expect `PeerMessage::HandshakeV2` instead.
+pub const HANDSHAKE_PROTOCOL_VERSION: ProtocolVersion = 39;
+
+/// Oldest protocol version whose handshake lists the `MessageCapability` of the peer. Peers
+/// talking an older version have none.
+pub const CAPABILITIES_PROTOCOL_VERSION: ProtocolVersion = 42;
+
+/// Messages a peer understands, as bits of `Handshake::capabilities`. New messages are only sent
+/// to peers that list them, so that they roll out without bumping the protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageCapability {
+    /// `PeerMessage::SyncAccountsData`.
+    Tier1 = 1,
+    /// `PeerMessage::PeerRecords`.
+    PeerRecords = 1 << 1,
+    /// `PeerMessage::Compressed`.
+    Compression = 1 << 2,
+    /// `PeerMessage::ObservedAddr`.
+    ObservedAddr = 1 << 3,
+    /// `PeerMessage::ForwardTxAnnouncement` and the transaction requests.
+    TxAnnouncement = 1 << 4,
+    /// `PeerMessage::Versioned`.
+    Versioned = 1 << 5,
+    /// `PeerMessage::TimePing` and `PeerMessage::TimePong`.
+    Clock = 1 << 6,
+}
+
+impl MessageCapability {
+    pub fn is_in(self, capabilities: u64) -> bool {
+        capabilities & self as u64 != 0
+    }
+}
+
+/// Capabilities of this node.
+pub const SUPPORTED_CAPABILITIES: u64 = MessageCapability::Tier1 as u64
+    | MessageCapability::PeerRecords as u64
+    | MessageCapability::Compression as u64
+    | MessageCapability::ObservedAddr as u64
+    | MessageCapability::TxAnnouncement as u64
+    | MessageCapability::Versioned as u64
+    | MessageCapability::Clock as u64;
+
+/// Protocol version to talk with a peer that supports versions from `oldest_supported_version`
+/// to `version`: the newest version supported by both nodes, or `None` if the ranges of
+/// supported versions don't overlap.
+pub fn negotiate_protocol_version(
+    version: ProtocolVersion,
+    oldest_supported_version: ProtocolVersion,
+) -> Option<ProtocolVersion> {
+    let target_version = std::cmp::min(version, PROTOCOL_VERSION);
+    if target_version
+        >= std::cmp::max(oldest_supported_version, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION)
+    {
+        Some(target_version)
+    } else {
+        None
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, Clone, Debug)]
 pub struct Handshake {
     pub version: u32,
     /// Oldest supported protocol version.
@@ -215,6 +356,9 @@ pub struct Handshake {
     pub chain_info: PeerChainInfoV2,
     /// Info for new edge.
     pub edge_info: EdgeInfo,
+    /// Bits of the `MessageCapability` the sender supports, only sent from
+    /// `CAPABILITIES_PROTOCOL_VERSION` on.
+    pub capabilities: u64,
 }
 
 /// Struct describing the layout for Handshake.
@@ -256,16 +400,33 @@ impl Handshake {
             listen_port,
             chain_info,
             edge_info,
+            capabilities: SUPPORTED_CAPABILITIES,
         }
     }
 }
 
+impl BorshSerialize for Handshake {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.version.serialize(writer)?;
+        self.oldest_supported_version.serialize(writer)?;
+        self.peer_id.serialize(writer)?;
+        self.target_peer_id.serialize(writer)?;
+        self.listen_port.serialize(writer)?;
+        self.chain_info.serialize(writer)?;
+        self.edge_info.serialize(writer)?;
+        if self.version >= CAPABILITIES_PROTOCOL_VERSION {
+            self.capabilities.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
 // Use custom deserializer for HandshakeV2. Try to read version of the other peer from the header.
 // If the version is supported then fallback to standard deserializer.
 impl BorshDeserialize for Handshake {
     fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
         // Detect the current and oldest supported version from the header
-        if buf.len() < 4 {
+        if buf.len() < 8 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 ERROR_UNEXPECTED_LENGTH_OF_INPUT,
@@ -273,16 +434,21 @@ impl BorshDeserialize for Handshake {
         }
 
         let version = u32::from_le_bytes(buf[..4].try_into().unwrap());
+        let oldest_supported_version = u32::from_le_bytes(buf[4..8].try_into().unwrap());
 
         if OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION <= version && version <= PROTOCOL_VERSION {
             // If we support this version, then try to deserialize with custom deserializer
-            HandshakeAutoDes::deserialize(buf).map(Into::into)
+            let mut handshake: Handshake = HandshakeAutoDes::deserialize(buf)?.into();
+            if version >= CAPABILITIES_PROTOCOL_VERSION {
+                handshake.capabilities = u64::deserialize(buf)?;
+            }
+            Ok(handshake)
         } else {
             Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 HandshakeFailureReason::ProtocolVersionMismatch {
                     version,
-                    oldest_supported_version: version,
+                    oldest_supported_version,
                 },
             ))
         }
@@ -299,6 +465,7 @@ impl From<HandshakeAutoDes> for Handshake {
             listen_port: handshake.listen_port,
             chain_info: handshake.chain_info,
             edge_info: handshake.edge_info,
+            capabilities: 0,
         }
     }
 }
@@ -395,6 +562,24 @@ impl From<HandshakeV2AutoDes> for HandshakeV2 {
     }
 }
 
+impl From<Handshake> for HandshakeV2 {
+    fn from(handshake: Handshake) -> Self {
+        Self {
+            version: handshake.version,
+            oldest_supported_version: handshake.oldest_supported_version,
+            peer_id: handshake.peer_id,
+            target_peer_id: handshake.target_peer_id,
+            listen_port: handshake.listen_port,
+            chain_info: PeerChainInfo {
+                genesis_id: handshake.chain_info.genesis_id,
+                height: handshake.chain_info.height,
+                tracked_shards: handshake.chain_info.tracked_shards,
+            },
+            edge_info: handshake.edge_info,
+        }
+    }
+}
+
 impl From<HandshakeV2> for Handshake {
     fn from(handshake: HandshakeV2) -> Self {
         Self {
@@ -685,6 +870,32 @@ impl RoutedMessage {
     }
 }
 
+/// Routed `ForwardTx` without the transaction, which the receiver rebuilds from the transactions
+/// it has or asks for with `PeerMessage::TransactionsRequest`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct ForwardTxAnnouncement {
+    pub target: PeerIdOrHash,
+    pub author: PeerId,
+    pub signature: Signature,
+    pub ttl: u8,
+    /// Hash of the whole signed transaction, unlike `SignedTransaction::get_hash` which leaves
+    /// out the signature.
+    pub signed_tx_hash: CryptoHash,
+}
+
+/// Asks the peer for its clock, see `peer_clock`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct TimePing {
+    pub nonce: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct TimePong {
+    pub nonce: u64,
+    /// Time the ping was answered, as a nanosecond timestamp.
+    pub time: u64,
+}
+
 /// Routed Message wrapped with previous sender of the message.
 pub struct RoutedMessageFrom {
     /// Routed messages.
@@ -717,6 +928,117 @@ impl SyncData {
     }
 }
 
+/// Addresses at which a validator accepts TIER1 connections, see `Tier1`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct AccountData {
+    pub account_id: AccountId,
+    /// Node of the validator, whose key signs the data.
+    pub peer_id: PeerId,
+    /// Epoch of the `AnnounceAccount` of the validator.
+    pub epoch_id: EpochId,
+    /// Peers to connect to in order to reach the validator, which may be the validator itself.
+    pub proxies: Vec<PeerInfo>,
+    /// Creation time in nanoseconds, newer data of the same account replaces older.
+    pub timestamp: u64,
+}
+
+impl AccountData {
+    pub fn hash(&self) -> CryptoHash {
+        hash(&self.try_to_vec().expect("Failed to serialize"))
+    }
+
+    /// Signs the data with the key of the node.
+    pub fn sign(self, secret_key: &SecretKey) -> SignedAccountData {
+        let signature = secret_key.sign(self.hash().as_ref());
+        SignedAccountData { data: self, signature }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct SignedAccountData {
+    pub data: AccountData,
+    pub signature: Signature,
+}
+
+impl SignedAccountData {
+    pub fn verify(&self) -> bool {
+        self.signature.verify(self.data.hash().as_ref(), &self.data.peer_id.public_key())
+    }
+}
+
+/// Features a peer supports, as bits of `PeerRecord::capabilities`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerCapability {
+    /// Keeps the whole history of the chain.
+    Archival = 1,
+    /// Accepts QUIC connections.
+    Quic = 1 << 1,
+    /// Accepts encrypted TCP connections.
+    Encryption = 1 << 2,
+}
+
+/// Addresses and features of a peer, signed with its node key so that other nodes can't spoof
+/// them. Newer records of a peer replace older ones.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct PeerRecord {
+    pub peer_id: PeerId,
+    /// Addresses at which the peer accepts connections.
+    pub addrs: Vec<SocketAddr>,
+    pub account_id: Option<AccountId>,
+    /// Creation time in nanoseconds.
+    pub timestamp: u64,
+    /// Bits of the `PeerCapability` the peer supports.
+    pub capabilities: u64,
+}
+
+impl PeerRecord {
+    pub fn new(
+        peer_id: PeerId,
+        addrs: Vec<SocketAddr>,
+        account_id: Option<AccountId>,
+        capabilities: &[PeerCapability],
+    ) -> Self {
+        PeerRecord {
+            peer_id,
+            addrs,
+            account_id,
+            timestamp: to_timestamp(Utc::now()),
+            capabilities: capabilities.iter().fold(0, |bits, capability| bits | *capability as u64),
+        }
+    }
+
+    pub fn has_capability(&self, capability: PeerCapability) -> bool {
+        self.capabilities & capability as u64 != 0
+    }
+
+    pub fn hash(&self) -> CryptoHash {
+        hash(&self.try_to_vec().expect("Failed to serialize"))
+    }
+
+    pub fn sign(self, secret_key: &SecretKey) -> SignedPeerRecord {
+        let signature = secret_key.sign(self.hash().as_ref());
+        SignedPeerRecord { record: self, signature }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct SignedPeerRecord {
+    pub record: PeerRecord,
+    pub signature: Signature,
+}
+
+impl SignedPeerRecord {
+    pub fn verify(&self) -> bool {
+        self.signature.verify(self.record.hash().as_ref(), &self.record.peer_id.public_key())
+    }
+
+    /// Whether this record replaces the other record of the peer: it's newer, or as old and has a
+    /// larger hash, so that all nodes keep the same record.
+    pub fn supersedes(&self, other: &SignedPeerRecord) -> bool {
+        (self.record.timestamp, self.record.hash()) > (other.record.timestamp, other.record.hash())
+    }
+}
+
 /// Warning, position of each message type in this enum defines the protocol due to serialization.
 /// DO NOT MOVE, REORDER, DELETE items from the list. Only add new items to the end.
 /// If need to remove old items - replace with `None`.
@@ -759,6 +1081,62 @@ pub enum PeerMessage {
     Disconnect,
     Challenge(Challenge),
     HandshakeV2(HandshakeV2),
+    /// TIER1 addresses of validators, only sent to peers with `MessageCapability::Tier1`.
+    SyncAccountsData(Vec<SignedAccountData>),
+    /// Records of known peers, only sent to peers with `MessageCapability::PeerRecords`. Peers
+    /// with a record are left out of `PeersResponse` then.
+    PeerRecords(Vec<SignedPeerRecord>),
+    /// Another message compressed with zstd, only sent to peers with
+    /// `MessageCapability::Compression`. It never leaves the codec.
+    Compressed(Vec<u8>),
+    /// Address the peer connected from, sent to inbound peers with
+    /// `MessageCapability::ObservedAddr` so that they learn their external address.
+    ObservedAddr(SocketAddr),
+    /// Routed transaction without the transaction, sent instead of `ForwardTx` to peers with
+    /// `MessageCapability::TxAnnouncement`.
+    ForwardTxAnnouncement(ForwardTxAnnouncement),
+    /// Transactions of announcements, by `ForwardTxAnnouncement::signed_tx_hash`.
+    TransactionsRequest(Vec<CryptoHash>),
+    TransactionsResponse(Vec<SignedTransaction>),
+    /// Another message with the version of its layout, only sent to peers with
+    /// `MessageCapability::Versioned`. It never leaves the codec, unless the node doesn't know
+    /// the message or its version.
+    Versioned(VersionedMessage),
+    /// Clock of the peer, only sent to peers with `MessageCapability::Clock`.
+    TimePing(TimePing),
+    TimePong(TimePong),
+}
+
+/// Message wrapped with the version of its layout, so that the layout of a message can change
+/// without the peers that don't know it failing to decode the stream.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct VersionedMessage {
+    /// Borsh tag of the `PeerMessage` variant.
+    pub kind: u8,
+    pub version: u8,
+    /// The borsh encoded message, tag included.
+    pub payload: Vec<u8>,
+}
+
+/// Layouts of `PeerMessage` variants: the name of the variant, the version of its layout and the
+/// protocol version from which peers talk it. Variants that aren't listed have the layout 0. Add
+/// an entry when the layout of a variant changes.
+const MESSAGE_LAYOUTS: &[(&str, u8, ProtocolVersion)] = &[];
+
+impl VersionedMessage {
+    /// Version of the layout of the variant with the given tag that peers talking the negotiated
+    /// `protocol_version` use, `None` for variants this node doesn't know.
+    pub fn version(kind: u8, protocol_version: ProtocolVersion) -> Option<u8> {
+        let variant = PeerMessage::VARIANTS.get(kind as usize)?;
+        Some(
+            MESSAGE_LAYOUTS
+                .iter()
+                .filter(|(name, _, since)| name == variant && *since <= protocol_version)
+                .map(|(_, version, _)| *version)
+                .max()
+                .unwrap_or(0),
+        )
+    }
 }
 
 impl fmt::Display for PeerMessage {
@@ -768,7 +1146,12 @@ impl fmt::Display for PeerMessage {
 }
 
 impl PeerMessage {
-    pub fn msg_variant(&self) -> &str {
+    /// Borsh tag of the variant with the given name.
+    pub fn tag(variant: &str) -> Option<u8> {
+        PeerMessage::VARIANTS.iter().position(|name| *name == variant).map(|tag| tag as u8)
+    }
+
+    pub fn msg_variant(&self) -> &'static str {
         match self {
             PeerMessage::Routed(routed_message) => {
                 strum::AsStaticRef::as_static(&routed_message.body)
@@ -833,6 +1216,8 @@ pub struct NetworkConfig {
     pub secret_key: SecretKey,
     pub account_id: Option<AccountId>,
     pub addr: Option<SocketAddr>,
+    /// Further addresses to listen on, typically of the other IP version than `addr`.
+    pub additional_addrs: Vec<SocketAddr>,
     pub boot_nodes: Vec<PeerInfo>,
     pub handshake_timeout: Duration,
     pub reconnect_delay: Duration,
@@ -885,9 +1270,41 @@ pub struct NetworkConfig {
     pub outbound_disabled: bool,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Transport to try first when connecting to peers, see `Transport`.
+    pub transport: Transport,
+    /// Scoring of peer misbehavior, see `ReputationTable`.
+    pub reputation: ReputationConfig,
+    /// Bytes of messages queued for a slow peer before some are dropped, see `SendQueue`.
+    pub send_queue: SendQueueConfig,
+    /// Proxy to make outbound connections through.
+    pub proxy: Option<ProxyConfig>,
+    /// Boot nodes for the proxy to resolve the host name of, in addition to `boot_nodes`.
+    pub boot_node_hosts: Vec<BootNodeHost>,
+    /// Upload and download limits, see `Throttle`.
+    pub bandwidth: BandwidthLimits,
+    /// Peers at which other validators connect directly to this validator, see `Tier1`.
+    pub tier1_proxies: Vec<PeerInfo>,
+    /// Addresses to advertise in the `PeerRecord` of this node. The listening addresses with a
+    /// specified IP if empty.
+    pub public_addrs: Vec<SocketAddr>,
+    /// Whether TCP connections are encrypted, see `EncryptionMode`.
+    pub encryption: EncryptionMode,
+    /// Peers allowed and denied to connect, see `PeerAccess`.
+    pub access: PeerAccess,
+    /// Protocol to map the listening port on the router with, see `PortMapping`.
+    pub port_mapping: PortMapping,
+    /// Advertise the IP that outbound peers observe, if `public_addrs` is empty.
+    pub discover_external_addr: bool,
+    /// Limits on inbound connections, see `ConnectionLimits`.
+    pub connection_limits: ConnectionLimits,
 }
 
 impl NetworkConfig {
+    /// All addresses to listen on, `addr` first.
+    pub fn listen_addrs(&self) -> Vec<SocketAddr> {
+        self.addr.iter().chain(self.additional_addrs.iter()).cloned().collect()
+    }
+
     pub fn verify(&self) {
         if self.ideal_connections_lo + 1 >= self.ideal_connections_hi {
             error!(target: "network",
@@ -998,6 +1415,25 @@ impl KnownPeerState {
     }
 }
 
+impl From<&KnownPeerState> for KnownPeerView {
+    fn from(peer_state: &KnownPeerState) -> Self {
+        let status = match peer_state.status {
+            KnownPeerStatus::Unknown => "Unknown",
+            KnownPeerStatus::NotConnected => "NotConnected",
+            KnownPeerStatus::Connected => "Connected",
+            KnownPeerStatus::Banned(_, _) => "Banned",
+        };
+        KnownPeerView {
+            peer_id: peer_state.peer_info.id.to_string(),
+            addr: peer_state.peer_info.addr.map(|addr| addr.to_string()),
+            account_id: peer_state.peer_info.account_id.clone(),
+            status: status.to_string(),
+            first_seen: peer_state.first_seen,
+            last_seen: peer_state.last_seen,
+        }
+    }
+}
+
 impl TryFrom<Vec<u8>> for KnownPeerState {
     type Error = Box<dyn std::error::Error>;
 
@@ -1021,6 +1457,13 @@ impl InboundTcpConnect {
     }
 }
 
+/// Actor message that holds an inbound QUIC connection
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct InboundQuicConnect {
+    pub connection: QuicConnection,
+}
+
 /// Actor message to request the creation of an outbound TCP connection to a peer.
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -1042,6 +1485,14 @@ pub struct Consolidate {
     pub peer_info: PeerInfo,
     pub peer_type: PeerType,
     pub chain_info: PeerChainInfoV2,
+    /// Protocol version advertised by the peer in the handshake.
+    pub protocol_version: ProtocolVersion,
+    /// `MessageCapability` bits both this node and the peer have.
+    pub capabilities: u64,
+    pub transport: Transport,
+    /// IP the access rules are matched against: the dialed one for outbound peers, which may be
+    /// reached through a proxy, the one of the socket for inbound peers.
+    pub remote_ip: IpAddr,
     // Edge information from this node.
     // If this is None it implies we are outbound connection, so we need to create our
     // EdgeInfo part and send it to the other peer.
@@ -1072,6 +1523,7 @@ pub struct Unregister {
 
 pub struct PeerList {
     pub peers: Vec<PeerInfo>,
+    pub records: Vec<SignedPeerRecord>,
 }
 
 /// Message from peer to peer manager
@@ -1081,6 +1533,11 @@ pub enum PeerRequest {
     RouteBack(Box<RoutedMessageBody>, CryptoHash),
     UpdatePeerInfo(PeerInfo),
     ReceivedMessage(PeerId, Instant),
+    /// The peer sent data for the client, at most once per
+    /// `UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE`.
+    ReceivedUsefulData(PeerId),
+    /// Estimated clock of the peer minus ours, in milliseconds.
+    ClockSkew(PeerId, i64),
 }
 
 impl Message for PeerRequest {
@@ -1105,8 +1562,22 @@ impl Message for PeersRequest {
 #[rtype(result = "()")]
 pub struct PeersResponse {
     pub peers: Vec<PeerInfo>,
+    pub records: Vec<SignedPeerRecord>,
 }
 
+/// Address an outbound peer observed this node connect from.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ObservedAddr {
+    pub peer_id: PeerId,
+    pub addr: SocketAddr,
+}
+
+/// External address the listening port is mapped to on the router, `None` if mapping failed.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PortMapped(pub Option<SocketAddr>);
+
 impl<A, M> MessageResponse<A, M> for PeerList
 where
     A: Actor,
@@ -1189,6 +1660,11 @@ pub enum NetworkRequests {
         peer_id: PeerId,
         ban_reason: ReasonForBan,
     },
+    /// Count misbehavior of given peer, which gets banned once it misbehaves too much.
+    ReportMisbehavior {
+        peer_id: PeerId,
+        misbehavior: Misbehavior,
+    },
     /// Announce account
     AnnounceAccount(AnnounceAccount),
 
@@ -1236,6 +1712,11 @@ pub enum NetworkRequests {
         peer_id: PeerId,
         sync_data: SyncData,
     },
+    /// TIER1 addresses of validators received from active peer.
+    SyncAccountsData {
+        peer_id: PeerId,
+        accounts_data: Vec<SignedAccountData>,
+    },
 
     RequestUpdateNonce(PeerId, EdgeInfo),
     ResponseUpdateNonce(Edge),
@@ -1244,6 +1725,14 @@ pub enum NetworkRequests {
     PingTo(usize, PeerId),
     /// Fetch all received ping and pong so far.
     FetchPingPongInfo,
+    /// Fetch the peers known to the node, for debugging.
+    FetchPeerStore,
+    /// Fetch the misbehavior scores of peers, for debugging.
+    FetchPeerReputation,
+    /// Fetch the rules which peers may connect.
+    FetchPeerAccess,
+    /// Change the rules which peers may connect, disconnecting the peers no longer accepted.
+    UpdatePeerAccess(PeerAccessChange),
 
     /// A challenge to invalidate a block.
     Challenge(Challenge),
@@ -1288,6 +1777,12 @@ pub struct NetworkInfo {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
+    /// Number of active peers by the protocol version they advertised.
+    pub peer_protocol_versions: BTreeMap<ProtocolVersion, usize>,
+    /// Clock of the active peers minus ours in milliseconds, for the peers that were measured.
+    pub peer_clock_skews: HashMap<PeerId, i64>,
+    /// Transport of the connection with each active peer.
+    pub peer_transports: HashMap<PeerId, Transport>,
     #[cfg(feature = "metric_recorder")]
     pub metric_recorder: MetricRecorder,
     pub peer_counter: usize,
@@ -1309,6 +1804,9 @@ where
 pub enum NetworkResponses {
     NoResponse,
     RoutingTableInfo(RoutingTableInfo),
+    PeerStore(Vec<KnownPeerView>),
+    PeerReputation(Vec<PeerReputationView>),
+    PeerAccess(PeerAccessView),
     PingPongInfo { pings: HashMap<usize, Ping>, pongs: HashMap<usize, Pong> },
     BanPeer(ReasonForBan),
     EdgeUpdate(Box<Edge>),
@@ -1487,8 +1985,8 @@ pub enum NetworkViewClientMessages {
     BlockHeadersRequest(Vec<CryptoHash>),
     /// State request header.
     StateRequestHeader { shard_id: ShardId, sync_hash: CryptoHash },
-    /// State request part.
-    StateRequestPart { shard_id: ShardId, sync_hash: CryptoHash, part_id: u64 },
+    /// State request part, the requester is throttled.
+    StateRequestPart { shard_id: ShardId, sync_hash: CryptoHash, part_id: u64, requester: PeerId },
     /// Get Chain information from Client.
     GetChainInfo,
     /// Account announcements that needs to be validated before being processed.
@@ -1736,6 +2234,7 @@ mod tests {
         assert_size!(NetworkConfig);
         assert_size!(KnownPeerState);
         assert_size!(InboundTcpConnect);
+        assert_size!(InboundQuicConnect);
         assert_size!(OutboundTcpConnect);
         assert_size!(SendMessage);
         assert_size!(Consolidate);
@@ -1750,4 +2249,20 @@ mod tests {
         assert_size!(QueryPeerStats);
         assert_size!(PartialEncodedChunkRequestMsg);
     }
+
+    #[test]
+    fn test_boot_node_host() {
+        let id = PeerId::random();
+        let boot_node: BootNodeHost = format!("{}@boot.example:24567", id).parse().unwrap();
+        assert_eq!(
+            boot_node,
+            BootNodeHost { id: id.clone(), host: "boot.example".to_string(), port: 24567 }
+        );
+        assert_eq!(boot_node.to_string().parse::<BootNodeHost>().unwrap(), boot_node);
+        // IP addresses and account ids are left to `PeerInfo`.
+        assert!(format!("{}@127.0.0.1:24567", id).parse::<BootNodeHost>().is_err());
+        assert!(format!("{}@[::1]:24567", id).parse::<BootNodeHost>().is_err());
+        assert!(format!("{}@test.near", id).parse::<BootNodeHost>().is_err());
+        assert!(format!("{}@boot.example:port", id).parse::<BootNodeHost>().is_err());
+    }
 }
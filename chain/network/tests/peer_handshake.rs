@@ -13,9 +13,13 @@ use futures::{future, FutureExt};
 use core::time::Duration;
 use near_client::{ClientActor, ViewClientActor};
 use near_logger_utils::init_test_logger;
-use near_network::test_utils::{convert_boot_nodes, open_port, GetInfo, StopSignal, WaitOrTimeout};
+use near_network::test_utils::{
+    convert_boot_nodes, open_port, peer_id_from_seed, GetInfo, StopSignal, WaitOrTimeout,
+};
 use near_network::types::{NetworkViewClientMessages, NetworkViewClientResponses};
-use near_network::{NetworkClientResponses, NetworkConfig, PeerManagerActor};
+use near_network::{
+    EncryptionMode, NetworkClientResponses, NetworkConfig, PeerManagerActor, Transport,
+};
 use near_store::test_utils::create_test_store;
 
 type ClientMock = Mocker<ClientActor>;
@@ -27,11 +31,51 @@ fn make_peer_manager(
     port: u16,
     boot_nodes: Vec<(&str, u16)>,
     peer_max_count: u32,
+) -> PeerManagerActor {
+    make_peer_manager_with_transport(seed, port, boot_nodes, peer_max_count, Transport::Tcp)
+}
+
+#[cfg(test)]
+fn make_peer_manager_with_transport(
+    seed: &str,
+    port: u16,
+    boot_nodes: Vec<(&str, u16)>,
+    peer_max_count: u32,
+    transport: Transport,
+) -> PeerManagerActor {
+    make_peer_manager_with_config(seed, port, boot_nodes, peer_max_count, |config| {
+        config.transport = transport
+    })
+}
+
+#[cfg(test)]
+fn make_peer_manager_with_encryption(
+    seed: &str,
+    port: u16,
+    boot_nodes: Vec<(&str, u16)>,
+    peer_max_count: u32,
+    transport: Transport,
+    encryption: EncryptionMode,
+) -> PeerManagerActor {
+    make_peer_manager_with_config(seed, port, boot_nodes, peer_max_count, |config| {
+        config.transport = transport;
+        config.encryption = encryption;
+    })
+}
+
+#[cfg(test)]
+fn make_peer_manager_with_config(
+    seed: &str,
+    port: u16,
+    boot_nodes: Vec<(&str, u16)>,
+    peer_max_count: u32,
+    update_config: impl FnOnce(&mut NetworkConfig),
 ) -> PeerManagerActor {
     let store = create_test_store();
     let mut config = NetworkConfig::from_seed(seed, port);
     config.boot_nodes = convert_boot_nodes(boot_nodes);
     config.max_num_peers = peer_max_count;
+    update_config(&mut config);
     let client_addr = ClientMock::mock(Box::new(move |_msg, _ctx| {
         Box::new(Some(NetworkClientResponses::NoResponse))
     }))
@@ -81,6 +125,146 @@ fn peer_handshake() {
     .unwrap();
 }
 
+/// Peers connect over QUIC if both use it, and over TCP if only one of them does.
+#[test]
+fn peer_handshake_quic() {
+    init_test_logger();
+
+    System::run(|| {
+        let (port1, port2, port3) = (open_port(), open_port(), open_port());
+        let pm1 = make_peer_manager_with_transport(
+            "test1",
+            port1,
+            vec![("test2", port2), ("test3", port3)],
+            10,
+            Transport::Quic,
+        )
+        .start();
+        let _pm2 = make_peer_manager_with_transport(
+            "test2",
+            port2,
+            vec![("test1", port1)],
+            10,
+            Transport::Quic,
+        )
+        .start();
+        let _pm3 = make_peer_manager("test3", port3, vec![("test1", port1)], 10).start();
+        WaitOrTimeout::new(
+            Box::new(move |_| {
+                actix::spawn(pm1.send(GetInfo {}).then(move |res| {
+                    let info = res.unwrap();
+                    if info.num_active_peers == 2 {
+                        let transport_of = |seed| info.peer_transports[&peer_id_from_seed(seed)];
+                        assert_eq!(transport_of("test2"), Transport::Quic);
+                        assert_eq!(transport_of("test3"), Transport::Tcp);
+                        System::current().stop();
+                    }
+                    future::ready(())
+                }));
+            }),
+            100,
+            10000,
+        )
+        .start();
+    })
+    .unwrap();
+}
+
+/// Peers that require encryption connect over encrypted TCP, and over QUIC if both use it.
+#[test]
+fn peer_handshake_encryption_required() {
+    init_test_logger();
+
+    System::run(|| {
+        let (port1, port2, port3) = (open_port(), open_port(), open_port());
+        let pm1 = make_peer_manager_with_encryption(
+            "test1",
+            port1,
+            vec![("test2", port2), ("test3", port3)],
+            10,
+            Transport::Quic,
+            EncryptionMode::Required,
+        )
+        .start();
+        let _pm2 = make_peer_manager_with_encryption(
+            "test2",
+            port2,
+            vec![("test1", port1)],
+            10,
+            Transport::Quic,
+            EncryptionMode::Required,
+        )
+        .start();
+        // Plaintext connections would be refused by both ends.
+        let _pm3 = make_peer_manager_with_encryption(
+            "test3",
+            port3,
+            vec![("test1", port1)],
+            10,
+            Transport::Tcp,
+            EncryptionMode::Required,
+        )
+        .start();
+        WaitOrTimeout::new(
+            Box::new(move |_| {
+                actix::spawn(pm1.send(GetInfo {}).then(move |res| {
+                    let info = res.unwrap();
+                    if info.num_active_peers == 2 {
+                        let transport_of = |seed| info.peer_transports[&peer_id_from_seed(seed)];
+                        assert_eq!(transport_of("test2"), Transport::Quic);
+                        assert_eq!(transport_of("test3"), Transport::Tcp);
+                        System::current().stop();
+                    }
+                    future::ready(())
+                }));
+            }),
+            100,
+            10000,
+        )
+        .start();
+    })
+    .unwrap();
+}
+
+/// Access rules apply to inbound peers that don't tell their listening port too.
+#[test]
+fn peer_access_without_listen_addr() {
+    init_test_logger();
+
+    System::run(|| {
+        let (port1, port2, port3) = (open_port(), open_port(), open_port());
+        let pm1 = make_peer_manager_with_config("test1", port1, vec![], 10, |config| {
+            config.access.denied = vec![peer_id_from_seed("test3").to_string().parse().unwrap()]
+        })
+        .start();
+        let _pm2 = make_peer_manager("test2", port2, vec![("test1", port1)], 10).start();
+        let _pm3 =
+            make_peer_manager_with_config("test3", port3, vec![("test1", port1)], 10, |config| {
+                config.addr = None
+            })
+            .start();
+        let polls = Arc::new(AtomicUsize::new(0));
+        WaitOrTimeout::new(
+            Box::new(move |_| {
+                let polls = polls.clone();
+                actix::spawn(pm1.send(GetInfo {}).then(move |res| {
+                    let info = res.unwrap();
+                    assert!(!info.peer_transports.contains_key(&peer_id_from_seed("test3")));
+                    // Give the denied peer a couple of seconds to try.
+                    if info.num_active_peers == 1 && polls.fetch_add(1, Ordering::SeqCst) > 20 {
+                        System::current().stop();
+                    }
+                    future::ready(())
+                }));
+            }),
+            100,
+            10000,
+        )
+        .start();
+    })
+    .unwrap();
+}
+
 #[test]
 fn peers_connect_all() {
     init_test_logger();
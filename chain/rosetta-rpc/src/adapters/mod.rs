@@ -611,6 +611,9 @@ impl From<NearActions> for Vec<crate::models::Operation> {
                     );
                     operations.push(deploy_contract_operation);
                 }
+
+                // Idempotency key doesn't change any balances.
+                near_primitives::transaction::Action::IdempotencyKey(_) => {}
             }
         }
         operations
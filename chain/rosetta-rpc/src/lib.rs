@@ -86,7 +86,8 @@ async fn network_status(
         )),
     )?;
     let network_info = network_info.map_err(errors::ErrorKind::InternalError)?;
-    let genesis_block = genesis_block.map_err(errors::ErrorKind::InternalInvariantError)?;
+    let genesis_block =
+        genesis_block.map_err(|err| errors::ErrorKind::InternalInvariantError(err.into()))?;
     let earliest_block = earliest_block;
 
     let genesis_block_identifier: models::BlockIdentifier = (&genesis_block.header).into();
@@ -220,7 +221,7 @@ async fn block_details(
                 near_primitives::types::BlockId::Hash(block.header.prev_hash).into(),
             ))
             .await?
-            .map_err(errors::ErrorKind::InternalError)?;
+            .map_err(|err| errors::ErrorKind::InternalError(err.into()))?;
 
         models::BlockIdentifier {
             index: parent_block.header.height.try_into().unwrap(),
@@ -297,7 +298,7 @@ async fn block_transaction_details(
     let block = view_client_addr
         .send(near_client::GetBlock(block_id.clone()))
         .await?
-        .map_err(errors::ErrorKind::NotFound)?;
+        .map_err(|err| errors::ErrorKind::NotFound(err.into()))?;
 
     let transaction = crate::adapters::collect_transactions(
         Arc::clone(&genesis),
@@ -365,7 +366,7 @@ async fn account_balance(
     let block = view_client_addr
         .send(near_client::GetBlock(block_id.clone()))
         .await?
-        .map_err(errors::ErrorKind::NotFound)?;
+        .map_err(|err| errors::ErrorKind::NotFound(err.into()))?;
 
     let (block_hash, block_height, account_info) =
         match crate::utils::query_account(block_id, account_identifier.address, &view_client_addr)
@@ -1022,17 +1022,14 @@ impl From<&near_crypto::PublicKey> for PublicKey {
 }
 
 impl TryFrom<&PublicKey> for near_crypto::PublicKey {
-    type Error = near_crypto::TryFromSliceError;
+    type Error = near_crypto::ParseKeyError;
 
     fn try_from(PublicKey { curve_type, hex_bytes }: &PublicKey) -> Result<Self, Self::Error> {
-        Ok(match curve_type {
-            CurveType::Edwards25519 => {
-                near_crypto::PublicKey::ED25519((hex_bytes.as_ref() as &[u8]).try_into()?)
-            }
-            CurveType::Secp256k1 => {
-                near_crypto::PublicKey::SECP256K1((hex_bytes.as_ref() as &[u8]).try_into()?)
-            }
-        })
+        let key_type = match curve_type {
+            CurveType::Edwards25519 => near_crypto::KeyType::ED25519,
+            CurveType::Secp256k1 => near_crypto::KeyType::SECP256K1,
+        };
+        near_crypto::PublicKey::from_parts(key_type, hex_bytes.as_ref())
     }
 }
 
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix::Message;
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Header with the hex encoded HMAC-SHA256 of the body, if the webhook has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Near-Signature";
+
+/// Critical events that operators are notified about.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// The validator produces too few blocks and may be kicked out at the end of the epoch.
+    KickoutRisk,
+    /// The chain head was switched to a different fork.
+    ForkDetected,
+    /// The protocol version changes in the next epoch.
+    ProtocolUpgrade,
+    /// The disk with the node data is nearly full.
+    DiskSpaceLow,
+    /// The node is behind its peers and doesn't make progress.
+    SyncStalled,
+}
+
+impl AlertKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::KickoutRisk => "kickout_risk",
+            AlertKind::ForkDetected => "fork_detected",
+            AlertKind::ProtocolUpgrade => "protocol_upgrade",
+            AlertKind::DiskSpaceLow => "disk_space_low",
+            AlertKind::SyncStalled => "sync_stalled",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key for the signature of the body in the `X-Near-Signature` header.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Template of the body. `{kind}`, `{message}`, `{node_id}`, `{chain_id}`, `{timestamp}` and
+    /// `{suppressed}` are replaced with the values of the alert, strings are escaped for JSON but
+    /// not quoted, e.g. `{"text": "{chain_id}: {message}"}`. The alert is sent as JSON by default.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Kinds of alerts sent to the webhook, all if empty.
+    #[serde(default)]
+    pub kinds: Vec<AlertKind>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub webhooks: Vec<WebhookConfig>,
+    /// Minimum time between two alerts of the same kind. Alerts in between are not sent, but
+    /// their number is reported with the next one.
+    pub min_interval: Duration,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig { webhooks: vec![], min_interval: Duration::from_secs(600) }
+    }
+}
+
+/// Alert to send to the webhooks.
+#[derive(Message, Serialize, Clone, Debug, PartialEq)]
+#[rtype(result = "()")]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub message: String,
+    pub node_id: String,
+    pub chain_id: String,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    /// Number of alerts of the same kind that were dropped by rate limiting since the last one.
+    pub suppressed: u64,
+}
+
+impl Alert {
+    pub fn new(kind: AlertKind, message: String, node_id: String, chain_id: String) -> Self {
+        let timestamp =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        Alert { kind, message, node_id, chain_id, timestamp, suppressed: 0 }
+    }
+
+    /// Body of the request to the webhook.
+    pub fn render(&self, template: Option<&str>) -> String {
+        let template = match template {
+            Some(template) => template,
+            None => return serde_json::to_string(self).expect("Alert must serialize to json"),
+        };
+        template
+            .replace("{kind}", self.kind.as_str())
+            .replace("{message}", &escape_json(&self.message))
+            .replace("{node_id}", &escape_json(&self.node_id))
+            .replace("{chain_id}", &escape_json(&self.chain_id))
+            .replace("{timestamp}", &self.timestamp.to_string())
+            .replace("{suppressed}", &self.suppressed.to_string())
+    }
+}
+
+/// Escapes the string for JSON, without the surrounding quotes.
+fn escape_json(value: &str) -> String {
+    let quoted = serde_json::to_string(value).expect("String must serialize to json");
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Hex encoded HMAC-SHA256 of the body.
+pub fn sign_body(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Allows at most one alert of each kind per interval.
+pub struct AlertRateLimiter {
+    min_interval: Duration,
+    /// Time of the last sent alert and the number of suppressed ones since then, by kind.
+    last_sent: HashMap<AlertKind, (Instant, u64)>,
+}
+
+impl AlertRateLimiter {
+    pub fn new(min_interval: Duration) -> Self {
+        Self { min_interval, last_sent: HashMap::new() }
+    }
+
+    /// Returns the number of suppressed alerts if the alert should be sent now.
+    pub fn check(&mut self, kind: AlertKind, now: Instant) -> Option<u64> {
+        match self.last_sent.get_mut(&kind) {
+            Some((last_sent, suppressed)) if now.duration_since(*last_sent) < self.min_interval => {
+                *suppressed += 1;
+                None
+            }
+            entry => {
+                let suppressed = entry.map_or(0, |(_, suppressed)| *suppressed);
+                self.last_sent.insert(kind, (now, 0));
+                Some(suppressed)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut rate_limiter = AlertRateLimiter::new(Duration::from_secs(10));
+        let now = Instant::now();
+        assert_eq!(rate_limiter.check(AlertKind::SyncStalled, now), Some(0));
+        assert_eq!(rate_limiter.check(AlertKind::SyncStalled, now + Duration::from_secs(1)), None);
+        assert_eq!(rate_limiter.check(AlertKind::SyncStalled, now + Duration::from_secs(9)), None);
+        assert_eq!(rate_limiter.check(AlertKind::DiskSpaceLow, now), Some(0));
+        assert_eq!(
+            rate_limiter.check(AlertKind::SyncStalled, now + Duration::from_secs(10)),
+            Some(2)
+        );
+        assert_eq!(
+            rate_limiter.check(AlertKind::SyncStalled, now + Duration::from_secs(20)),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut alert = Alert::new(
+            AlertKind::DiskSpaceLow,
+            "Only 3% of \"/data\" is free".to_string(),
+            "ed25519:node".to_string(),
+            "testnet".to_string(),
+        );
+        alert.suppressed = 2;
+        let body: serde_json::Value = serde_json::from_str(&alert.render(None)).unwrap();
+        assert_eq!(body["kind"], "disk_space_low");
+        assert_eq!(body["suppressed"], 2);
+
+        let body =
+            alert.render(Some(r#"{"text": "[{chain_id}] {kind}: {message} (+{suppressed})"}"#));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["text"], "[testnet] disk_space_low: Only 3% of \"/data\" is free (+2)");
+    }
+
+    #[test]
+    fn test_sign_body() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign_body("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -1,10 +1,15 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix::{Actor, Addr, Context, Handler, Message};
 use actix_web::client::{Client, Connector};
 use futures::FutureExt;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::alerts::{sign_body, AlertRateLimiter, SIGNATURE_HEADER};
+pub use crate::alerts::{Alert, AlertKind, AlertsConfig, WebhookConfig};
+
+mod alerts;
 
 /// Timeout for establishing connection.
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
@@ -12,6 +17,9 @@ const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct TelemetryConfig {
     pub endpoints: Vec<String>,
+    /// Webhooks notified about critical events of the node.
+    #[serde(default)]
+    pub alerts: AlertsConfig,
 }
 
 /// Event to send over telemetry.
@@ -24,6 +32,7 @@ pub struct TelemetryEvent {
 pub struct TelemetryActor {
     config: TelemetryConfig,
     client: Client,
+    alert_rate_limiter: AlertRateLimiter,
 }
 
 impl Default for TelemetryActor {
@@ -52,7 +61,8 @@ impl TelemetryActor {
                     .finish(),
             )
             .finish();
-        Self { config, client }
+        let alert_rate_limiter = AlertRateLimiter::new(config.alerts.min_interval);
+        Self { config, client, alert_rate_limiter }
     }
 }
 
@@ -80,7 +90,41 @@ impl Handler<TelemetryEvent> for TelemetryActor {
     }
 }
 
+impl Handler<Alert> for TelemetryActor {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: Alert, _ctx: &mut Context<Self>) {
+        warn!(target: "telemetry", "Alert {}: {}", msg.kind.as_str(), msg.message);
+        msg.suppressed = match self.alert_rate_limiter.check(msg.kind, Instant::now()) {
+            Some(suppressed) => suppressed,
+            None => return,
+        };
+        for webhook in self.config.alerts.webhooks.iter() {
+            if !webhook.kinds.is_empty() && !webhook.kinds.contains(&msg.kind) {
+                continue;
+            }
+            let body = msg.render(webhook.template.as_ref().map(String::as_str));
+            let mut request =
+                self.client.post(&webhook.url).header("Content-Type", "application/json");
+            if let Some(secret) = &webhook.secret {
+                request = request
+                    .header(SIGNATURE_HEADER, format!("sha256={}", sign_body(secret, &body)));
+            }
+            actix::spawn(request.send_body(body).map(|response| {
+                if let Err(error) = response {
+                    info!(target: "telemetry", "Alert could not be sent due to: {}", error);
+                }
+            }));
+        }
+    }
+}
+
 /// Send telemetry event to all the endpoints.
 pub fn telemetry(telemetry: &Addr<TelemetryActor>, content: serde_json::Value) {
     telemetry.do_send(TelemetryEvent { content });
 }
+
+/// Notify the configured webhooks about a critical event.
+pub fn alert(telemetry: &Addr<TelemetryActor>, alert: Alert) {
+    telemetry.do_send(alert);
+}
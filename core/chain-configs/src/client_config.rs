@@ -1,10 +1,15 @@
 //! Chain Client Configuration
 use std::cmp::min;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
-use near_primitives::types::{AccountId, BlockHeightDelta, NumBlocks, NumSeats, ShardId};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{
+    AccountId, BlockHeight, BlockHeightDelta, MerkleHash, NumBlocks, NumSeats, ShardId,
+};
+use near_primitives::upgrade_schedule::ProtocolUpgradeVotingSchedule;
 use near_primitives::version::Version;
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -15,6 +20,31 @@ pub enum LogSummaryStyle {
     Colored,
 }
 
+/// Storage that state sync downloads state headers and parts from, in addition to the peers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExternalStorageLocation {
+    /// Directory on the local filesystem, such as a mounted bucket.
+    #[serde(rename = "filesystem")]
+    Filesystem { root_dir: PathBuf },
+    /// HTTP mirror, or the public endpoint of an S3 or GCS bucket such as
+    /// `https://storage.googleapis.com/<bucket>`.
+    #[serde(rename = "http")]
+    Http { url: String },
+}
+
+/// Block trusted to be on the canonical chain, such as one published with a release. The
+/// signatures of the block and of its ancestors, found through `prev_hash` once its header is
+/// synced, are not checked, while the rest of the checks still are. Headers above its height must
+/// descend from it. The chunks of the first blocks above it are checked against the state computed
+/// without the signatures.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrustedCheckpoint {
+    pub height: BlockHeight,
+    pub block_hash: CryptoHash,
+    /// `prev_state_root` in the header of the block.
+    pub state_root: MerkleHash,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// Version of the binary.
@@ -79,14 +109,37 @@ pub struct ClientConfig {
     pub block_header_fetch_horizon: BlockHeightDelta,
     /// Number of blocks to garbage collect at every gc call.
     pub gc_blocks_limit: NumBlocks,
+    /// Longest time a gc call takes, the heights left are collected by the next ones.
+    pub gc_step_time_limit: Duration,
+    /// Number of epochs whose data is not garbage collected.
+    pub gc_num_epochs_to_keep: u64,
+    /// Maximum number of blocks in the orphan pool.
+    pub max_orphans: usize,
+    /// Time after which orphans are dropped from the pool.
+    pub max_orphan_age: Duration,
+    /// Whether the chunks of a block waiting for its other chunks are applied as they arrive. Only
+    /// the blocks with missing chunks are applied ahead.
+    pub optimistic_block_application: bool,
+    /// Storage to download state from during state sync, next to the peers.
+    pub state_sync_external: Option<ExternalStorageLocation>,
+    /// Block whose ancestors' signatures are not checked during sync.
+    pub trusted_checkpoint: Option<TrustedCheckpoint>,
     /// Accounts that this client tracks
     pub tracked_accounts: Vec<AccountId>,
     /// Shards that this client tracks
     pub tracked_shards: Vec<ShardId>,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Database that an archive node moves the data of the old epochs to, relative to the home
+    /// directory. The old data stays in the main database if not set.
+    pub cold_store_path: Option<PathBuf>,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
+    /// Time after which produced blocks vote for the new protocol version, immediately if not set.
+    pub protocol_upgrade_voting_schedule: Option<ProtocolUpgradeVotingSchedule>,
+    /// Accounts whose transactions, receipts and balance changes are indexed for the
+    /// `EXPERIMENTAL_watched_account_changes` RPC.
+    pub watched_accounts: Vec<AccountId>,
 }
 
 impl ClientConfig {
@@ -134,11 +187,21 @@ impl ClientConfig {
             doosmslug_step_period: Duration::from_millis(100),
             block_header_fetch_horizon: 50,
             gc_blocks_limit: 100,
+            gc_step_time_limit: Duration::from_secs(1),
+            gc_num_epochs_to_keep: 5,
+            max_orphans: 1024,
+            max_orphan_age: Duration::from_secs(300),
+            optimistic_block_application: false,
+            state_sync_external: None,
+            trusted_checkpoint: None,
             tracked_accounts: vec![],
             tracked_shards: vec![],
             archive,
+            cold_store_path: None,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
+            protocol_upgrade_voting_schedule: None,
+            watched_accounts: vec![],
         }
     }
 }
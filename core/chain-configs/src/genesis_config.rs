@@ -39,8 +39,23 @@ fn default_protocol_upgrade_stake_threshold() -> Rational {
     Rational::new(8, 10)
 }
 
+fn default_max_block_size() -> u64 {
+    DEFAULT_MAX_BLOCK_SIZE
+}
+
 const MAX_GAS_PRICE: Balance = 10_000_000_000_000_000_000_000;
 
+/// Default max size of a borsh-serialized block.
+pub const DEFAULT_MAX_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Chunk gas limit and max block size that apply starting from the given protocol version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockLimitsUpgrade {
+    pub protocol_version: ProtocolVersion,
+    pub gas_limit: Gas,
+    pub max_block_size: u64,
+}
+
 #[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
 pub struct GenesisConfig {
     /// Protocol version that this genesis works with.
@@ -57,6 +72,10 @@ pub struct GenesisConfig {
     pub num_block_producer_seats: NumSeats,
     /// Defines number of shards and number of block producer seats per each shard at genesis.
     pub num_block_producer_seats_per_shard: Vec<NumSeats>,
+    /// Number of seats for chunk-only producers, taken by the validators without a block producer
+    /// seat once chunk-only producers are enabled.
+    #[serde(default)]
+    pub num_chunk_only_producer_seats: NumSeats,
     /// Expected number of hidden validators per shard.
     pub avg_hidden_validator_seats_per_shard: Vec<NumSeats>,
     /// Enable dynamic re-sharding.
@@ -71,6 +90,14 @@ pub struct GenesisConfig {
     pub epoch_length: BlockHeightDelta,
     /// Initial gas limit.
     pub gas_limit: Gas,
+    /// Initial max size of a borsh-serialized block.
+    #[serde(default = "default_max_block_size")]
+    #[default(DEFAULT_MAX_BLOCK_SIZE)]
+    pub max_block_size: u64,
+    /// Changes of the gas limit and max block size at protocol upgrades, ordered by protocol
+    /// version.
+    #[serde(default)]
+    pub block_limits_upgrades: Vec<BlockLimitsUpgrade>,
     /// Minimum gas price. It is also the initial gas price.
     #[serde(with = "u128_dec_format_compatible")]
     pub min_gas_price: Balance,
@@ -120,6 +147,81 @@ pub struct GenesisConfig {
     pub minimum_stake_divisor: u64,
 }
 
+/// Protocol parameters in effect at some block: the genesis config with the gas limit, block size,
+/// gas prices and runtime config of the protocol version of that block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolConfigView {
+    pub protocol_version: ProtocolVersion,
+    pub genesis_time: DateTime<Utc>,
+    pub chain_id: String,
+    pub genesis_height: BlockHeight,
+    pub num_block_producer_seats: NumSeats,
+    pub num_block_producer_seats_per_shard: Vec<NumSeats>,
+    pub avg_hidden_validator_seats_per_shard: Vec<NumSeats>,
+    pub dynamic_resharding: bool,
+    pub protocol_upgrade_stake_threshold: Rational,
+    pub protocol_upgrade_num_epochs: EpochHeight,
+    pub epoch_length: BlockHeightDelta,
+    pub gas_limit: Gas,
+    pub max_block_size: u64,
+    #[serde(with = "u128_dec_format")]
+    pub min_gas_price: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub max_gas_price: Balance,
+    pub block_producer_kickout_threshold: u8,
+    pub chunk_producer_kickout_threshold: u8,
+    pub online_min_threshold: Rational,
+    pub online_max_threshold: Rational,
+    pub gas_price_adjustment_rate: Rational,
+    pub runtime_config: RuntimeConfig,
+    pub transaction_validity_period: NumBlocks,
+    pub protocol_reward_rate: Rational,
+    pub max_inflation_rate: Rational,
+    pub num_blocks_per_year: NumBlocks,
+    pub protocol_treasury_account: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub fishermen_threshold: Balance,
+    pub minimum_stake_divisor: u64,
+}
+
+/// The config at the genesis protocol version.
+impl From<&GenesisConfig> for ProtocolConfigView {
+    fn from(config: &GenesisConfig) -> Self {
+        Self {
+            protocol_version: config.protocol_version,
+            genesis_time: config.genesis_time,
+            chain_id: config.chain_id.clone(),
+            genesis_height: config.genesis_height,
+            num_block_producer_seats: config.num_block_producer_seats,
+            num_block_producer_seats_per_shard: config.num_block_producer_seats_per_shard.clone(),
+            avg_hidden_validator_seats_per_shard: config
+                .avg_hidden_validator_seats_per_shard
+                .clone(),
+            dynamic_resharding: config.dynamic_resharding,
+            protocol_upgrade_stake_threshold: config.protocol_upgrade_stake_threshold,
+            protocol_upgrade_num_epochs: config.protocol_upgrade_num_epochs,
+            epoch_length: config.epoch_length,
+            gas_limit: config.gas_limit,
+            max_block_size: config.max_block_size,
+            min_gas_price: config.min_gas_price,
+            max_gas_price: config.max_gas_price,
+            block_producer_kickout_threshold: config.block_producer_kickout_threshold,
+            chunk_producer_kickout_threshold: config.chunk_producer_kickout_threshold,
+            online_min_threshold: config.online_min_threshold,
+            online_max_threshold: config.online_max_threshold,
+            gas_price_adjustment_rate: config.gas_price_adjustment_rate,
+            runtime_config: config.runtime_config.clone(),
+            transaction_validity_period: config.transaction_validity_period,
+            protocol_reward_rate: config.protocol_reward_rate,
+            max_inflation_rate: config.max_inflation_rate,
+            num_blocks_per_year: config.num_blocks_per_year,
+            protocol_treasury_account: config.protocol_treasury_account.clone(),
+            fishermen_threshold: config.fishermen_threshold,
+            minimum_stake_divisor: config.minimum_stake_divisor,
+        }
+    }
+}
+
 /// Records in storage at genesis (get split into shards at genesis creation).
 #[derive(
     Debug,
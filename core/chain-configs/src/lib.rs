@@ -1,5 +1,10 @@
 mod client_config;
 mod genesis_config;
 
-pub use client_config::{ClientConfig, LogSummaryStyle};
-pub use genesis_config::{Genesis, GenesisConfig, GenesisRecords};
+pub use client_config::{
+    ClientConfig, ExternalStorageLocation, LogSummaryStyle, TrustedCheckpoint,
+};
+pub use genesis_config::{
+    BlockLimitsUpgrade, Genesis, GenesisConfig, GenesisRecords, ProtocolConfigView,
+    DEFAULT_MAX_BLOCK_SIZE,
+};
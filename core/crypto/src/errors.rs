@@ -7,6 +7,16 @@ pub enum ParseSignatureError {
     InvalidData(String),
 }
 
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SealError {
+    #[error("passphrase key derivation failed: {0}")]
+    KeyDerivation(String),
+    #[error("invalid passphrase or corrupted sealed key")]
+    InvalidPassphrase,
+    #[error("failed to read key file: {0}")]
+    InvalidKeyFile(String),
+}
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum ParseKeyError {
     #[error("unknown curve kind: {0}")]
@@ -0,0 +1,318 @@
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{AeadInPlace, NewAead};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::x25519::{X25519PublicKey, X25519SecretKey};
+use crate::{PublicKey, SecretKey, Signature};
+
+/// Version of the handshake, mixed into the transcript and the key derivation so that keys of
+/// different versions never match.
+pub const HANDSHAKE_PROTOCOL_NAME: &[u8] = b"near-handshake-v1:X25519:ChaChaPoly:SHA256";
+const SIGNATURE_DOMAIN: &[u8] = b"near-handshake-v1 transcript signature";
+pub const SESSION_KEY_LENGTH: usize = 32;
+/// Length of the Poly1305 tag appended to every sealed message.
+pub const SESSION_TAG_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeRole {
+    Initiator,
+    Responder,
+}
+
+/// Running hash of all handshake messages. Both sides must mix the same data in the same order,
+/// the resulting hash is signed by both identities and salts the session keys.
+#[derive(Clone)]
+pub struct HandshakeTranscript {
+    hasher: Sha256,
+}
+
+impl HandshakeTranscript {
+    pub fn new() -> Self {
+        let mut transcript = Self { hasher: Sha256::new() };
+        transcript.mix(HANDSHAKE_PROTOCOL_NAME);
+        transcript
+    }
+
+    /// Adds length prefixed data, so that message boundaries are part of the transcript.
+    pub fn mix(&mut self, data: &[u8]) {
+        self.hasher.update(&(data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        self.hasher.clone().finalize().into()
+    }
+}
+
+impl Default for HandshakeTranscript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ephemeral X25519 key generated for a single handshake.
+pub struct EphemeralKey {
+    secret_key: X25519SecretKey,
+    public_key: X25519PublicKey,
+}
+
+impl EphemeralKey {
+    pub fn from_random() -> Self {
+        let secret_key = X25519SecretKey::from_random(&mut OsRng);
+        let public_key = secret_key.public_key();
+        Self { secret_key, public_key }
+    }
+
+    pub fn public_key(&self) -> &X25519PublicKey {
+        &self.public_key
+    }
+}
+
+/// Signs the transcript hash with the node identity key, binding the ephemeral keys of the
+/// handshake to the identity.
+pub fn sign_transcript(identity: &SecretKey, transcript: &HandshakeTranscript) -> Signature {
+    identity.sign(&transcript_signature_data(transcript))
+}
+
+pub fn verify_transcript(
+    signature: &Signature,
+    identity: &PublicKey,
+    transcript: &HandshakeTranscript,
+) -> bool {
+    signature.verify(&transcript_signature_data(transcript), identity)
+}
+
+fn transcript_signature_data(transcript: &HandshakeTranscript) -> Vec<u8> {
+    let mut data = SIGNATURE_DOMAIN.to_vec();
+    data.extend_from_slice(&transcript.hash());
+    data
+}
+
+/// Symmetric keys for the two directions of the connection.
+pub struct SessionKeys {
+    pub send_key: [u8; SESSION_KEY_LENGTH],
+    pub recv_key: [u8; SESSION_KEY_LENGTH],
+}
+
+impl Drop for SessionKeys {
+    fn drop(&mut self) {
+        self.send_key.zeroize();
+        self.recv_key.zeroize();
+    }
+}
+
+impl SessionKeys {
+    /// Ciphers of the messages sent and received.
+    pub fn into_ciphers(self) -> (SessionCipher, SessionCipher) {
+        (SessionCipher::new(&self.send_key), SessionCipher::new(&self.recv_key))
+    }
+}
+
+impl std::fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SessionKeys(..)")
+    }
+}
+
+/// Cipher of the messages of one direction of a session: ChaCha20-Poly1305 (RFC 8439), the
+/// cipher of Noise `ChaChaPoly`. The nonce is the number of messages before, so a message that was
+/// dropped, reordered or replayed fails to open.
+pub struct SessionCipher {
+    /// Wipes its key when dropped.
+    cipher: ChaCha20Poly1305,
+    nonce: u64,
+}
+
+impl SessionCipher {
+    fn new(session_key: &[u8; SESSION_KEY_LENGTH]) -> Self {
+        Self { cipher: ChaCha20Poly1305::new(GenericArray::from_slice(session_key)), nonce: 0 }
+    }
+
+    /// Nonce of the next message: 32 zero bits followed by the little endian counter, as in Noise.
+    fn next_nonce(&mut self) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce = self.nonce.checked_add(1).expect("Session nonce overflow");
+        nonce
+    }
+
+    /// Encrypts the next message, appending the tag.
+    pub fn seal(&mut self, mut message: Vec<u8>) -> Vec<u8> {
+        let nonce = self.next_nonce();
+        self.cipher
+            .encrypt_in_place(GenericArray::from_slice(&nonce), b"", &mut message)
+            .expect("Message is shorter than the ChaCha20 limit");
+        message
+    }
+
+    /// Decrypts the next message. Returns `None` if its tag doesn't match, after which the session
+    /// can't be used anymore.
+    pub fn open(&mut self, mut message: Vec<u8>) -> Option<Vec<u8>> {
+        let nonce = self.next_nonce();
+        self.cipher.decrypt_in_place(GenericArray::from_slice(&nonce), b"", &mut message).ok()?;
+        Some(message)
+    }
+}
+
+impl std::fmt::Debug for SessionCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SessionCipher(..)")
+    }
+}
+
+/// Derives session keys from the ephemeral-ephemeral and both ephemeral-static Diffie-Hellman
+/// outputs, the same combination as the Noise XX pattern, with the transcript hash as HKDF salt.
+/// Identity keys must be ED25519, they are converted to X25519. Returns `None` if any key can't be
+/// converted or any of the shared secrets is degenerate.
+pub fn derive_session_keys(
+    role: HandshakeRole,
+    identity: &SecretKey,
+    ephemeral: &EphemeralKey,
+    peer_identity: &PublicKey,
+    peer_ephemeral: &X25519PublicKey,
+    transcript: &HandshakeTranscript,
+) -> Option<SessionKeys> {
+    let static_key = identity.to_x25519()?;
+    let peer_static_key = peer_identity.to_x25519()?;
+    let mut ee = ephemeral.secret_key.diffie_hellman(peer_ephemeral)?;
+    let mut es = ephemeral.secret_key.diffie_hellman(&peer_static_key)?;
+    let mut se = static_key.diffie_hellman(peer_ephemeral)?;
+    // Initiator's ephemeral with responder's static goes first on both sides.
+    let (first, second) = match role {
+        HandshakeRole::Initiator => (&es, &se),
+        HandshakeRole::Responder => (&se, &es),
+    };
+    let mut input_key_material = [0u8; 96];
+    input_key_material[..32].copy_from_slice(&ee);
+    input_key_material[32..64].copy_from_slice(first);
+    input_key_material[64..].copy_from_slice(second);
+    ee.zeroize();
+    es.zeroize();
+    se.zeroize();
+
+    let hkdf = Hkdf::<Sha256>::new(Some(&transcript.hash()), &input_key_material);
+    input_key_material.zeroize();
+    let mut initiator_key = [0u8; SESSION_KEY_LENGTH];
+    let mut responder_key = [0u8; SESSION_KEY_LENGTH];
+    hkdf.expand(b"initiator", &mut initiator_key).expect("Output length is valid");
+    hkdf.expand(b"responder", &mut responder_key).expect("Output length is valid");
+    Some(match role {
+        HandshakeRole::Initiator => {
+            SessionKeys { send_key: initiator_key, recv_key: responder_key }
+        }
+        HandshakeRole::Responder => {
+            SessionKeys { send_key: responder_key, recv_key: initiator_key }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::KeyType;
+
+    use super::*;
+
+    #[test]
+    fn test_handshake() {
+        let alice = SecretKey::from_seed(KeyType::ED25519, "alice");
+        let bob = SecretKey::from_seed(KeyType::ED25519, "bob");
+        let alice_ephemeral = EphemeralKey::from_random();
+        let bob_ephemeral = EphemeralKey::from_random();
+
+        let mut transcript = HandshakeTranscript::new();
+        transcript.mix(&alice_ephemeral.public_key().0);
+        transcript.mix(&bob_ephemeral.public_key().0);
+        let alice_signature = sign_transcript(&alice, &transcript);
+        let bob_signature = sign_transcript(&bob, &transcript);
+        assert!(verify_transcript(&alice_signature, &alice.public_key(), &transcript));
+        assert!(verify_transcript(&bob_signature, &bob.public_key(), &transcript));
+        assert!(!verify_transcript(&alice_signature, &bob.public_key(), &transcript));
+        let mut other_transcript = transcript.clone();
+        other_transcript.mix(b"other");
+        assert!(!verify_transcript(&alice_signature, &alice.public_key(), &other_transcript));
+
+        let alice_keys = derive_session_keys(
+            HandshakeRole::Initiator,
+            &alice,
+            &alice_ephemeral,
+            &bob.public_key(),
+            bob_ephemeral.public_key(),
+            &transcript,
+        )
+        .unwrap();
+        let bob_keys = derive_session_keys(
+            HandshakeRole::Responder,
+            &bob,
+            &bob_ephemeral,
+            &alice.public_key(),
+            alice_ephemeral.public_key(),
+            &transcript,
+        )
+        .unwrap();
+        assert_eq!(alice_keys.send_key, bob_keys.recv_key);
+        assert_eq!(alice_keys.recv_key, bob_keys.send_key);
+        assert_ne!(alice_keys.send_key, alice_keys.recv_key);
+
+        let carol = SecretKey::from_seed(KeyType::ED25519, "carol");
+        let carol_keys = derive_session_keys(
+            HandshakeRole::Responder,
+            &carol,
+            &bob_ephemeral,
+            &alice.public_key(),
+            alice_ephemeral.public_key(),
+            &transcript,
+        )
+        .unwrap();
+        assert_ne!(alice_keys.send_key, carol_keys.recv_key);
+        let other_keys = derive_session_keys(
+            HandshakeRole::Responder,
+            &bob,
+            &bob_ephemeral,
+            &alice.public_key(),
+            alice_ephemeral.public_key(),
+            &other_transcript,
+        )
+        .unwrap();
+        assert_ne!(alice_keys.send_key, other_keys.recv_key);
+
+        let secp = SecretKey::from_seed(KeyType::SECP256K1, "alice");
+        assert!(derive_session_keys(
+            HandshakeRole::Initiator,
+            &secp,
+            &alice_ephemeral,
+            &bob.public_key(),
+            bob_ephemeral.public_key(),
+            &transcript,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_session_cipher() {
+        let keys = || SessionKeys { send_key: [1; 32], recv_key: [2; 32] };
+        let (mut sender, _) = keys().into_ciphers();
+        let first = sender.seal(b"first".to_vec());
+        let second = sender.seal(b"second".to_vec());
+        assert_ne!(&first[..5], b"first");
+        assert_eq!(first.len(), 5 + SESSION_TAG_LENGTH);
+
+        let receiver = || SessionKeys { send_key: [2; 32], recv_key: [1; 32] }.into_ciphers().1;
+        // Messages only open in the order they were sealed.
+        assert_eq!(receiver().open(second.clone()), None);
+        let mut tampered = first.clone();
+        tampered[0] ^= 1;
+        assert_eq!(receiver().open(tampered), None);
+        // The key of the other direction doesn't open them.
+        assert_eq!(keys().into_ciphers().1.open(first.clone()), None);
+        // Nor do messages shorter than a tag.
+        assert_eq!(receiver().open(first[..4].to_vec()), None);
+
+        let mut receiver = receiver();
+        assert_eq!(receiver.open(first), Some(b"first".to_vec()));
+        assert_eq!(receiver.open(second), Some(b"second".to_vec()));
+    }
+}
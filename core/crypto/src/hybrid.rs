@@ -0,0 +1,112 @@
+//! Experimental hybrid signature, a pair of ED25519 and Dilithium signatures over the same data.
+//! It is valid only if both signatures are valid, so it stays secure until both schemes are
+//! broken. Only used to measure the overhead of post-quantum signatures on test networks.
+use std::fmt::{Debug, Formatter};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pqcrypto_dilithium::dilithium2;
+use pqcrypto_traits::sign::{DetachedSignature as _, PublicKey as _};
+
+use crate::{KeyType, PublicKey, SecretKey, Signature};
+
+#[derive(Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct HybridPublicKey {
+    pub ed25519: PublicKey,
+    pub dilithium: Vec<u8>,
+}
+
+impl Debug for HybridPublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}+dilithium2", self.ed25519)
+    }
+}
+
+pub struct HybridSecretKey {
+    ed25519: SecretKey,
+    dilithium: dilithium2::SecretKey,
+    public_key: HybridPublicKey,
+}
+
+impl HybridSecretKey {
+    pub fn from_random() -> Self {
+        let ed25519 = SecretKey::from_random(KeyType::ED25519);
+        let (dilithium_public_key, dilithium) = dilithium2::keypair();
+        let public_key = HybridPublicKey {
+            ed25519: ed25519.public_key(),
+            dilithium: dilithium_public_key.as_bytes().to_vec(),
+        };
+        Self { ed25519, dilithium, public_key }
+    }
+
+    pub fn public_key(&self) -> &HybridPublicKey {
+        &self.public_key
+    }
+
+    pub fn sign(&self, data: &[u8]) -> HybridSignature {
+        HybridSignature {
+            ed25519: self.ed25519.sign(data),
+            dilithium: dilithium2::detached_sign(data, &self.dilithium).as_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct HybridSignature {
+    pub ed25519: Signature,
+    pub dilithium: Vec<u8>,
+}
+
+impl HybridSignature {
+    /// Checks both signatures, the ED25519 one first since it's much cheaper.
+    pub fn verify(&self, data: &[u8], public_key: &HybridPublicKey) -> bool {
+        if public_key.ed25519.key_type() != KeyType::ED25519
+            || !self.ed25519.verify(data, &public_key.ed25519)
+        {
+            return false;
+        }
+        let signature = match dilithium2::DetachedSignature::from_bytes(&self.dilithium) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        let dilithium_public_key = match dilithium2::PublicKey::from_bytes(&public_key.dilithium) {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+        dilithium2::verify_detached_signature(&signature, data, &dilithium_public_key).is_ok()
+    }
+}
+
+impl Debug for HybridSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}+dilithium2({} bytes)", self.ed25519, self.dilithium.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hybrid_signature() {
+        let secret_key = HybridSecretKey::from_random();
+        let signature = secret_key.sign(b"123");
+        assert!(signature.verify(b"123", secret_key.public_key()));
+        assert!(!signature.verify(b"321", secret_key.public_key()));
+        assert_eq!(signature.dilithium.len(), dilithium2::signature_bytes());
+
+        let other_key = HybridSecretKey::from_random();
+        assert!(!signature.verify(b"123", other_key.public_key()));
+        // Both halves must be valid.
+        let mut mixed_key = secret_key.public_key().clone();
+        mixed_key.dilithium = other_key.public_key().dilithium.clone();
+        assert!(!signature.verify(b"123", &mixed_key));
+        let mut mixed_signature = signature.clone();
+        mixed_signature.ed25519 = other_key.sign(b"123").ed25519;
+        assert!(!mixed_signature.verify(b"123", secret_key.public_key()));
+
+        let bytes = signature.try_to_vec().unwrap();
+        assert_eq!(HybridSignature::try_from_slice(&bytes).unwrap(), signature);
+        let bytes = secret_key.public_key().try_to_vec().unwrap();
+        assert_eq!(&HybridPublicKey::try_from_slice(&bytes).unwrap(), secret_key.public_key());
+    }
+}
@@ -1,9 +1,21 @@
-pub use errors::{ParseKeyError, ParseSignatureError, TryFromSliceError};
+pub use errors::{ParseKeyError, ParseSignatureError, SealError, TryFromSliceError};
+pub use handshake::{
+    derive_session_keys, sign_transcript, verify_transcript, EphemeralKey, HandshakeRole,
+    HandshakeTranscript, SessionCipher, SessionKeys, HANDSHAKE_PROTOCOL_NAME, SESSION_KEY_LENGTH,
+    SESSION_TAG_LENGTH,
+};
+#[cfg(feature = "pq_crypto")]
+pub use hybrid::{HybridPublicKey, HybridSecretKey, HybridSignature};
 pub use key_file::KeyFile;
+pub use prehash::SignatureHasher;
+pub use sealed::{KdfParams, SealedSecretKey};
 pub use signature::{
-    ED25519PublicKey, KeyType, PublicKey, Secp256K1PublicKey, SecretKey, Signature,
+    verify_batch_parallel, ED25519PublicKey, KeyType, KeyTypeInfo, PublicKey, Secp256K1PublicKey,
+    SecretKey, Signature, KEY_TYPES, PRIVATE_KEY_TYPE_TAGS, RESERVED_KEY_TYPE_TAGS,
 };
-pub use signer::{EmptySigner, InMemorySigner, Signer};
+pub use signer::{EmptySigner, InMemorySigner, PassphraseSource, SealedSigner, Signer};
+pub use verification_cache::{SignatureVerificationCache, DEFAULT_VERIFICATION_CACHE_SIZE};
+pub use x25519::{shared_secret, X25519PublicKey, X25519SecretKey};
 
 #[macro_use]
 mod hash;
@@ -13,10 +25,18 @@ mod traits;
 mod util;
 
 mod errors;
+mod handshake;
+#[cfg(feature = "pq_crypto")]
+mod hybrid;
 pub mod key_conversion;
 mod key_file;
+mod pkcs8;
+mod prehash;
 pub mod randomness;
+mod sealed;
 mod signature;
 mod signer;
 mod test_utils;
+mod verification_cache;
 pub mod vrf;
+mod x25519;
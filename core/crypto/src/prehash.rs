@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use sha2::Digest;
+
+use crate::{KeyType, PublicKey, SecretKey, Signature};
+
+#[derive(Clone)]
+enum HasherState {
+    ED25519(sha2::Sha512),
+    SECP256K1(sha2::Sha256),
+}
+
+/// Incremental hasher for signing payloads that are not available as one contiguous buffer
+/// (state parts, large challenges). Data is fed with `update` (or through `std::io::Write`,
+/// which allows borsh-serializing straight into the hasher) and then signed or verified.
+///
+/// ED25519 uses Ed25519ph (RFC 8032) over SHA-512, SECP256K1 signs the SHA-256 digest.
+/// Note that these signatures are not interchangeable with the ones produced by
+/// `SecretKey::sign` over the same bytes.
+#[derive(Clone)]
+pub struct SignatureHasher {
+    state: HasherState,
+}
+
+impl SignatureHasher {
+    pub fn new(key_type: KeyType) -> Self {
+        let state = match key_type {
+            KeyType::ED25519 => HasherState::ED25519(sha2::Sha512::new()),
+            KeyType::SECP256K1 => HasherState::SECP256K1(sha2::Sha256::new()),
+        };
+        Self { state }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self.state {
+            HasherState::ED25519(_) => KeyType::ED25519,
+            HasherState::SECP256K1(_) => KeyType::SECP256K1,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            HasherState::ED25519(hasher) => hasher.update(data),
+            HasherState::SECP256K1(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Signs the data fed so far. Panics if the secret key has a different type than the
+    /// hasher was created for.
+    pub fn sign(self, secret_key: &SecretKey) -> Signature {
+        match (self.state, secret_key) {
+            (HasherState::ED25519(hasher), SecretKey::ED25519(secret_key)) => {
+                let keypair = ed25519_dalek::Keypair::from_bytes(&secret_key.0).unwrap();
+                Signature::ED25519(
+                    keypair.sign_prehashed(hasher, None).expect("Failed to sign prehashed"),
+                )
+            }
+            (HasherState::SECP256K1(hasher), SecretKey::SECP256K1(_)) => {
+                let digest = hasher.finalize();
+                // SECP256K1 signing already operates over a 32 bytes message.
+                secret_key.sign(digest.as_slice())
+            }
+            _ => panic!("Key type of secret key doesn't match the hasher"),
+        }
+    }
+
+    /// Verifies the signature over the data fed so far.
+    /// Returns `false` if the public key doesn't match the hasher's curve.
+    pub fn verify(self, signature: &Signature, public_key: &PublicKey) -> bool {
+        match (self.state, signature, public_key) {
+            (
+                HasherState::ED25519(hasher),
+                Signature::ED25519(signature),
+                PublicKey::ED25519(public_key),
+            ) => match ed25519_dalek::PublicKey::from_bytes(&public_key.0) {
+                Err(_) => false,
+                Ok(public_key) => public_key.verify_prehashed(hasher, None, signature).is_ok(),
+            },
+            (HasherState::SECP256K1(hasher), Signature::SECP256K1(_), PublicKey::SECP256K1(_)) => {
+                let digest = hasher.finalize();
+                signature.verify(digest.as_slice(), public_key)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Write for SignatureHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prehashed_sign_verify() {
+        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1] {
+            let secret_key = SecretKey::from_random(key_type);
+            let public_key = secret_key.public_key();
+
+            let mut hasher = SignatureHasher::new(key_type);
+            hasher.update(b"hello ");
+            hasher.update(b"world");
+            let signature = hasher.sign(&secret_key);
+
+            let mut hasher = SignatureHasher::new(key_type);
+            hasher.update(b"hello world");
+            assert!(hasher.clone().verify(&signature, &public_key));
+
+            let other_key = SecretKey::from_random(key_type).public_key();
+            assert!(!hasher.verify(&signature, &other_key));
+
+            let mut hasher = SignatureHasher::new(key_type);
+            hasher.update(b"hello world!");
+            assert!(!hasher.verify(&signature, &public_key));
+        }
+    }
+
+    #[test]
+    fn test_prehashed_write() {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let mut hasher = SignatureHasher::new(KeyType::ED25519);
+        hasher.write_all(&[7u8; 1024]).unwrap();
+        let signature = hasher.sign(&secret_key);
+
+        let mut hasher = SignatureHasher::new(KeyType::ED25519);
+        for _ in 0..8 {
+            hasher.update(&[7u8; 128]);
+        }
+        assert!(hasher.verify(&signature, &secret_key.public_key()));
+    }
+
+    #[test]
+    fn test_prehashed_key_type_mismatch() {
+        let secret_key = SecretKey::from_random(KeyType::SECP256K1);
+        let mut hasher = SignatureHasher::new(KeyType::ED25519);
+        hasher.update(b"123");
+        let signature = SecretKey::from_random(KeyType::ED25519).sign(b"123");
+        assert!(!hasher.verify(&signature, &secret_key.public_key()));
+    }
+}
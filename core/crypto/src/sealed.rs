@@ -0,0 +1,196 @@
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{AeadInPlace, NewAead};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroize;
+
+use crate::errors::SealError;
+use crate::signature::{ED25519SecretKey, SECP256K1};
+use crate::{KeyType, PublicKey, SecretKey, Signature};
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// Argon2id cost parameters used to derive the wrapping key from the passphrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory cost in KiB.
+    pub mem_cost: u32,
+    /// Number of passes over the memory.
+    pub time_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self { mem_cost: 64 * 1024, time_cost: 3 }
+    }
+}
+
+/// Secret key that is kept encrypted in memory and is only decrypted for the duration of a
+/// signing call, after which the plaintext is wiped.
+///
+/// The key is encrypted with ChaCha20-Poly1305. Only the salt, the nonce and the ciphertext with
+/// its tag are kept: the encryption key is derived from the passphrase with Argon2id on every
+/// unseal and wiped right after, so signing pays for a key derivation and `kdf_params` should be
+/// chosen with the signing rate in mind.
+pub struct SealedSecretKey {
+    public_key: PublicKey,
+    kdf_params: KdfParams,
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: Vec<u8>,
+}
+
+impl SealedSecretKey {
+    pub fn seal(secret_key: &SecretKey, passphrase: &str) -> Result<Self, SealError> {
+        Self::seal_with_params(secret_key, passphrase, KdfParams::default())
+    }
+
+    pub fn seal_with_params(
+        secret_key: &SecretKey,
+        passphrase: &str,
+        kdf_params: KdfParams,
+    ) -> Result<Self, SealError> {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let mut encryption_key = derive_key(passphrase, &salt, kdf_params)?;
+        let mut ciphertext = match secret_key {
+            SecretKey::ED25519(secret_key) => secret_key.0.to_vec(),
+            SecretKey::SECP256K1(secret_key) => secret_key[..].to_vec(),
+        };
+        cipher(&encryption_key)
+            .encrypt_in_place(GenericArray::from_slice(&nonce), b"", &mut ciphertext)
+            .expect("Key is shorter than the ChaCha20 limit");
+        encryption_key.zeroize();
+        Ok(Self { public_key: secret_key.public_key(), kdf_params, salt, nonce, ciphertext })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the plaintext secret key if the passphrase matches the one used for sealing. The
+    /// caller is responsible for wiping it.
+    pub fn unseal(&self, passphrase: &str) -> Result<SecretKey, SealError> {
+        let mut encryption_key = derive_key(passphrase, &self.salt, self.kdf_params)?;
+        let result = self.decrypt(&encryption_key);
+        encryption_key.zeroize();
+        result
+    }
+
+    /// Runs `f` with the decrypted secret key, wiping the plaintext afterwards.
+    pub(crate) fn with_secret_key<T>(
+        &self,
+        passphrase: &str,
+        f: impl FnOnce(&SecretKey) -> T,
+    ) -> Result<T, SealError> {
+        let mut secret_key = self.unseal(passphrase)?;
+        let result = f(&secret_key);
+        secret_key.zeroize();
+        Ok(result)
+    }
+
+    pub fn sign(&self, passphrase: &str, data: &[u8]) -> Result<Signature, SealError> {
+        self.with_secret_key(passphrase, |secret_key| secret_key.sign(data))
+    }
+
+    fn decrypt(&self, encryption_key: &[u8; KEY_LENGTH]) -> Result<SecretKey, SealError> {
+        let mut plaintext = self.ciphertext.clone();
+        if cipher(encryption_key)
+            .decrypt_in_place(GenericArray::from_slice(&self.nonce), b"", &mut plaintext)
+            .is_err()
+        {
+            plaintext.zeroize();
+            return Err(SealError::InvalidPassphrase);
+        }
+        let secret_key = match self.public_key.key_type() {
+            KeyType::ED25519 if plaintext.len() == ed25519_dalek::KEYPAIR_LENGTH => {
+                let mut keypair = [0u8; ed25519_dalek::KEYPAIR_LENGTH];
+                keypair.copy_from_slice(&plaintext);
+                Ok(SecretKey::ED25519(ED25519SecretKey(keypair)))
+            }
+            KeyType::SECP256K1 => secp256k1::key::SecretKey::from_slice(&SECP256K1, &plaintext)
+                .map(SecretKey::SECP256K1)
+                .map_err(|_| SealError::InvalidPassphrase),
+            _ => Err(SealError::InvalidPassphrase),
+        };
+        plaintext.zeroize();
+        secret_key
+    }
+}
+
+impl std::fmt::Debug for SealedSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SealedSecretKey").field("public_key", &self.public_key).finish()
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf_params: KdfParams,
+) -> Result<[u8; KEY_LENGTH], SealError> {
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        mem_cost: kdf_params.mem_cost,
+        time_cost: kdf_params.time_cost,
+        hash_length: KEY_LENGTH as u32,
+        ..Default::default()
+    };
+    let mut output = argon2::hash_raw(passphrase.as_bytes(), salt, &config)
+        .map_err(|err| SealError::KeyDerivation(err.to_string()))?;
+    let mut key = [0u8; KEY_LENGTH];
+    key.copy_from_slice(&output);
+    output.zeroize();
+    Ok(key)
+}
+
+/// ChaCha20-Poly1305 with the key, which it wipes when dropped.
+fn cipher(key: &[u8; KEY_LENGTH]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(GenericArray::from_slice(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use zeroize::Zeroizing;
+
+    use crate::{SealedSigner, Signer};
+
+    use super::*;
+
+    const TEST_PARAMS: KdfParams = KdfParams { mem_cost: 64, time_cost: 1 };
+
+    #[test]
+    fn test_seal_unseal() {
+        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1] {
+            let secret_key = SecretKey::from_seed(key_type, "test");
+            let sealed =
+                SealedSecretKey::seal_with_params(&secret_key, "passphrase", TEST_PARAMS).unwrap();
+            assert_eq!(sealed.public_key(), &secret_key.public_key());
+            assert_eq!(sealed.unseal("passphrase").unwrap(), secret_key);
+            assert!(matches!(sealed.unseal("wrong"), Err(SealError::InvalidPassphrase)));
+            let data = [7u8; 32];
+            let signature = sealed.sign("passphrase", &data).unwrap();
+            assert!(signature.verify(&data, &secret_key.public_key()));
+            assert!(matches!(sealed.sign("wrong", &data), Err(SealError::InvalidPassphrase)));
+        }
+    }
+
+    #[test]
+    fn test_sealed_signer() {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let sealed =
+            SealedSecretKey::seal_with_params(&secret_key, "passphrase", TEST_PARAMS).unwrap();
+        let signer = SealedSigner::from_sealed_key(
+            "test".to_string(),
+            sealed,
+            Box::new(|| Zeroizing::new("passphrase".to_string())),
+        );
+        let data = b"data";
+        assert!(signer.verify(data, &signer.sign(data)));
+        assert_eq!(signer.sign(data), secret_key.sign(data));
+    }
+}
@@ -3,34 +3,86 @@ use std::convert::{AsRef, TryFrom, TryInto};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind, Write};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use ed25519_dalek::ed25519::signature::{Signature as _, Signer as _, Verifier as _};
 use lazy_static::lazy_static;
 use rand_core::OsRng;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 lazy_static! {
     pub static ref SECP256K1: secp256k1::Secp256k1 = secp256k1::Secp256k1::new();
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// Supported key types. The discriminant is the tag that prefixes keys and signatures in binary
+/// encodings. Adding a key type means adding a variant here and its entry to `KEY_TYPES`, names,
+/// tags and lengths are looked up from there by the encoders and parsers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeyType {
     ED25519 = 0,
     SECP256K1 = 1,
 }
 
+/// Static properties of a key type.
+#[derive(Debug)]
+pub struct KeyTypeInfo {
+    pub key_type: KeyType,
+    /// Prefix of the string encoding, e.g. `ed25519:<base58 data>`.
+    pub name: &'static str,
+    pub public_key_length: usize,
+    pub signature_length: usize,
+}
+
+/// Registry of supported key types, indexed by tag.
+pub static KEY_TYPES: [KeyTypeInfo; 2] = [
+    KeyTypeInfo {
+        key_type: KeyType::ED25519,
+        name: "ed25519",
+        public_key_length: ed25519_dalek::PUBLIC_KEY_LENGTH,
+        signature_length: ed25519_dalek::SIGNATURE_LENGTH,
+    },
+    KeyTypeInfo {
+        key_type: KeyType::SECP256K1,
+        name: "secp256k1",
+        public_key_length: 64,
+        signature_length: 65,
+    },
+];
+
+/// Tags reserved for key types that may become part of the protocol, e.g. BLS.
+pub const RESERVED_KEY_TYPE_TAGS: RangeInclusive<u8> = 2..=127;
+/// Tags that will never be used by the protocol, for private and experimental key types.
+pub const PRIVATE_KEY_TYPE_TAGS: RangeInclusive<u8> = 128..=255;
+
+impl KeyType {
+    pub fn info(self) -> &'static KeyTypeInfo {
+        &KEY_TYPES[self as usize]
+    }
+
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn public_key_length(self) -> usize {
+        self.info().public_key_length
+    }
+
+    pub fn signature_length(self) -> usize {
+        self.info().signature_length
+    }
+}
+
 impl Display for KeyType {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "{}",
-            match self {
-                KeyType::ED25519 => "ed25519",
-                KeyType::SECP256K1 => "secp256k1",
-            },
-        )
+        write!(f, "{}", self.name())
     }
 }
 
@@ -38,11 +90,12 @@ impl FromStr for KeyType {
     type Err = crate::ParseKeyError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match value.to_ascii_lowercase().as_str() {
-            "ed25519" => Ok(KeyType::ED25519),
-            "secp256k1" => Ok(KeyType::SECP256K1),
-            _ => Err(Self::Err::UnknownCurve(value.to_string())),
-        }
+        let name = value.to_ascii_lowercase();
+        KEY_TYPES
+            .iter()
+            .find(|info| info.name == name)
+            .map(|info| info.key_type)
+            .ok_or_else(|| Self::Err::UnknownCurve(value.to_string()))
     }
 }
 
@@ -50,11 +103,17 @@ impl TryFrom<u8> for KeyType {
     type Error = crate::ParseKeyError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            0 => Ok(KeyType::ED25519),
-            1 => Ok(KeyType::SECP256K1),
-            _ => Err(Self::Error::UnknownCurve(value.to_string())),
-        }
+        KEY_TYPES
+            .iter()
+            .find(|info| info.key_type.tag() == value)
+            .map(|info| info.key_type)
+            .ok_or_else(|| {
+                if RESERVED_KEY_TYPE_TAGS.contains(&value) {
+                    Self::Error::UnknownCurve(format!("{} (reserved)", value))
+                } else {
+                    Self::Error::UnknownCurve(value.to_string())
+                }
+            })
     }
 }
 
@@ -180,10 +239,34 @@ pub enum PublicKey {
 }
 
 impl PublicKey {
+    /// Length of the compact binary encoding, see `to_bytes`.
     pub fn len(&self) -> usize {
-        match self {
-            Self::ED25519(_) => ed25519_dalek::PUBLIC_KEY_LENGTH + 1,
-            Self::SECP256K1(_) => 65,
+        self.key_type().public_key_length() + 1
+    }
+
+    /// Constructs public key from key type and raw key data.
+    pub fn from_parts(key_type: KeyType, key_data: &[u8]) -> Result<Self, crate::ParseKeyError> {
+        if key_data.len() != key_type.public_key_length() {
+            return Err(crate::ParseKeyError::InvalidLength(key_data.len()));
+        }
+        let mut public_key = PublicKey::empty(key_type);
+        public_key.key_data_mut().copy_from_slice(key_data);
+        Ok(public_key)
+    }
+
+    /// Compact binary encoding: one byte key type tag followed by the key data. Borsh uses the
+    /// same layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len());
+        bytes.push(self.key_type().tag());
+        bytes.extend_from_slice(self.key_data());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::ParseKeyError> {
+        match bytes.split_first() {
+            Some((tag, key_data)) => Self::from_parts(KeyType::try_from(*tag)?, key_data),
+            None => Err(crate::ParseKeyError::InvalidLength(0)),
         }
     }
 
@@ -210,6 +293,13 @@ impl PublicKey {
         }
     }
 
+    fn key_data_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::ED25519(key) => &mut key.0,
+            Self::SECP256K1(key) => &mut key.0,
+        }
+    }
+
     pub fn unwrap_as_ed25519(&self) -> &ED25519PublicKey {
         match self {
             Self::ED25519(key) => &key,
@@ -223,16 +313,8 @@ impl PublicKey {
 #[allow(clippy::derive_hash_xor_eq)]
 impl Hash for PublicKey {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
-            PublicKey::ED25519(public_key) => {
-                state.write_u8(0u8);
-                state.write(&public_key.0);
-            }
-            PublicKey::SECP256K1(public_key) => {
-                state.write_u8(1u8);
-                state.write(&public_key.0);
-            }
-        }
+        state.write_u8(self.key_type().tag());
+        state.write(self.key_data());
     }
 }
 
@@ -250,17 +332,8 @@ impl Debug for PublicKey {
 
 impl BorshSerialize for PublicKey {
     fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
-        match self {
-            PublicKey::ED25519(public_key) => {
-                BorshSerialize::serialize(&0u8, writer)?;
-                writer.write_all(&public_key.0)?;
-            }
-            PublicKey::SECP256K1(public_key) => {
-                BorshSerialize::serialize(&1u8, writer)?;
-                writer.write_all(&public_key.0)?;
-            }
-        }
-        Ok(())
+        BorshSerialize::serialize(&self.key_type().tag(), writer)?;
+        writer.write_all(self.key_data())
     }
 }
 
@@ -268,14 +341,14 @@ impl BorshDeserialize for PublicKey {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
         let key_type = KeyType::try_from(<u8 as BorshDeserialize>::deserialize(buf)?)
             .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
-        match key_type {
-            KeyType::ED25519 => {
-                Ok(PublicKey::ED25519(ED25519PublicKey(BorshDeserialize::deserialize(buf)?)))
-            }
-            KeyType::SECP256K1 => {
-                Ok(PublicKey::SECP256K1(Secp256K1PublicKey(BorshDeserialize::deserialize(buf)?)))
-            }
+        let length = key_type.public_key_length();
+        if buf.len() < length {
+            return Err(Error::new(ErrorKind::InvalidInput, "Unexpected length of input"));
         }
+        let public_key = PublicKey::from_parts(key_type, &buf[..length])
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        *buf = &buf[length..];
+        Ok(public_key)
     }
 }
 
@@ -303,16 +376,7 @@ impl<'de> serde::Deserialize<'de> for PublicKey {
 
 impl From<&PublicKey> for String {
     fn from(public_key: &PublicKey) -> Self {
-        match public_key {
-            PublicKey::ED25519(public_key) => {
-                format!("{}:{}", KeyType::ED25519, bs58::encode(&public_key.0).into_string())
-            }
-            PublicKey::SECP256K1(public_key) => format!(
-                "{}:{}",
-                KeyType::SECP256K1,
-                bs58::encode(&public_key.0.to_vec()).into_string()
-            ),
-        }
+        format!("{}:{}", public_key.key_type(), bs58::encode(public_key.key_data()).into_string())
     }
 }
 
@@ -321,28 +385,14 @@ impl FromStr for PublicKey {
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         let (key_type, key_data) = split_key_type_data(&value)?;
-        match key_type {
-            KeyType::ED25519 => {
-                let mut array = [0; ed25519_dalek::PUBLIC_KEY_LENGTH];
-                let length = bs58::decode(key_data)
-                    .into(&mut array)
-                    .map_err(|err| Self::Err::InvalidData(err.to_string()))?;
-                if length != ed25519_dalek::PUBLIC_KEY_LENGTH {
-                    return Err(crate::ParseKeyError::InvalidLength(length));
-                }
-                Ok(PublicKey::ED25519(ED25519PublicKey(array)))
-            }
-            KeyType::SECP256K1 => {
-                let mut array = [0; 64];
-                let length = bs58::decode(key_data)
-                    .into(&mut array[..])
-                    .map_err(|err| Self::Err::InvalidData(err.to_string()))?;
-                if length != 64 {
-                    return Err(crate::ParseKeyError::InvalidLength(length));
-                }
-                Ok(PublicKey::SECP256K1(Secp256K1PublicKey(array)))
-            }
+        let mut public_key = PublicKey::empty(key_type);
+        let length = bs58::decode(key_data)
+            .into(public_key.key_data_mut())
+            .map_err(|err| Self::Err::InvalidData(err.to_string()))?;
+        if length != key_type.public_key_length() {
+            return Err(crate::ParseKeyError::InvalidLength(length));
         }
+        Ok(public_key)
     }
 }
 
@@ -456,6 +506,25 @@ impl SecretKey {
     }
 }
 
+impl Zeroize for SecretKey {
+    fn zeroize(&mut self) {
+        match self {
+            SecretKey::ED25519(secret_key) => secret_key.0.zeroize(),
+            SecretKey::SECP256K1(secret_key) => {
+                // `secp256k1::key::SecretKey` is a `repr(C)` wrapper of its bytes and doesn't expose
+                // them mutably otherwise.
+                unsafe {
+                    std::slice::from_raw_parts_mut(
+                        secret_key.as_mut_ptr(),
+                        secp256k1::constants::SECRET_KEY_SIZE,
+                    )
+                }
+                .zeroize()
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for SecretKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let data = match self {
@@ -639,6 +708,27 @@ impl Signature {
     }
 }
 
+/// Verifies a batch of `(data, signature, public_key)` triples on the global rayon thread pool.
+/// Returns `false` as soon as any of the signatures fails to verify, remaining checks are skipped.
+pub fn verify_batch_parallel<T: AsRef<[u8]> + Sync>(items: &[(T, &Signature, &PublicKey)]) -> bool {
+    items
+        .par_iter()
+        .all(|(data, signature, public_key)| signature.verify(data.as_ref(), public_key))
+}
+
+// This `Hash` implementation is safe since it retains the property
+// `k1 == k2 ⇒ hash(k1) == hash(k2)`.
+#[allow(clippy::derive_hash_xor_eq)]
+impl Hash for Signature {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.key_type().tag());
+        match self {
+            Signature::ED25519(signature) => state.write(&signature.to_bytes()),
+            Signature::SECP256K1(signature) => state.write(&signature.0),
+        }
+    }
+}
+
 impl Default for Signature {
     fn default() -> Self {
         Signature::empty(KeyType::ED25519)
@@ -647,17 +737,11 @@ impl Default for Signature {
 
 impl BorshSerialize for Signature {
     fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        BorshSerialize::serialize(&self.key_type().tag(), writer)?;
         match self {
-            Signature::ED25519(signature) => {
-                BorshSerialize::serialize(&0u8, writer)?;
-                writer.write_all(&signature.to_bytes())?;
-            }
-            Signature::SECP256K1(signature) => {
-                BorshSerialize::serialize(&1u8, writer)?;
-                writer.write_all(&signature.0)?;
-            }
+            Signature::ED25519(signature) => writer.write_all(&signature.to_bytes()),
+            Signature::SECP256K1(signature) => writer.write_all(&signature.0),
         }
-        Ok(())
     }
 }
 
@@ -725,27 +809,12 @@ impl TryFrom<&str> for Signature {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         let (sig_type, sig_data) = split_key_type_data(&value)?;
-        match sig_type {
-            KeyType::ED25519 => {
-                let mut array = [0; ed25519_dalek::SIGNATURE_LENGTH];
-                let length = bs58::decode(sig_data).into(&mut array[..])?;
-                if length != ed25519_dalek::SIGNATURE_LENGTH {
-                    return Err(format!("Invalid length {} of ED25519 signature", length).into());
-                }
-                Ok(Signature::ED25519(
-                    ed25519_dalek::Signature::from_bytes(&array)
-                        .map_err(|e| format!("Invalid ED25519 signature: {}", e.to_string()))?,
-                ))
-            }
-            KeyType::SECP256K1 => {
-                let mut array = [0; 65];
-                let length = bs58::decode(sig_data).into(&mut array[..])?;
-                if length != 65 {
-                    return Err(format!("Invalid length {} of SECP256K1 signature", length).into());
-                }
-                Ok(Signature::SECP256K1(Secp256K1Signature(array)))
-            }
+        let mut data = vec![0; sig_type.signature_length()];
+        let length = bs58::decode(sig_data).into(&mut data[..])?;
+        if length != data.len() {
+            return Err(format!("Invalid length {} of {} signature", length, sig_type).into());
         }
+        Ok(Signature::from_parts(sig_type, &data)?)
     }
 }
 
@@ -776,6 +845,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_verify_batch_parallel() {
+        let data: Vec<Vec<u8>> = (0..64u8).map(|i| vec![i; 32]).collect();
+        let keys: Vec<_> = (0..64)
+            .map(|i| {
+                let key_type = if i % 2 == 0 { KeyType::ED25519 } else { KeyType::SECP256K1 };
+                SecretKey::from_random(key_type)
+            })
+            .collect();
+        let public_keys: Vec<_> = keys.iter().map(|key| key.public_key()).collect();
+        let signatures: Vec<_> = keys.iter().zip(data.iter()).map(|(key, d)| key.sign(d)).collect();
+        let mut items: Vec<_> = data
+            .iter()
+            .zip(signatures.iter())
+            .zip(public_keys.iter())
+            .map(|((d, signature), public_key)| (d.as_slice(), signature, public_key))
+            .collect();
+        assert!(verify_batch_parallel(&items));
+
+        items[37].0 = &data[36];
+        assert!(!verify_batch_parallel(&items));
+        assert!(verify_batch_parallel::<&[u8]>(&[]));
+    }
+
     #[test]
     fn test_json_serialize_ed25519() {
         let sk = SecretKey::from_seed(KeyType::ED25519, "test");
@@ -848,6 +941,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_key_type_registry() {
+        for (index, info) in KEY_TYPES.iter().enumerate() {
+            let key_type = info.key_type;
+            // Fails to compile when a key type is added, so that it gets covered here.
+            let expected_tag = match key_type {
+                KeyType::ED25519 => 0,
+                KeyType::SECP256K1 => 1,
+            };
+            assert_eq!(key_type.tag(), expected_tag);
+            assert_eq!(key_type.tag() as usize, index);
+            assert!(!RESERVED_KEY_TYPE_TAGS.contains(&key_type.tag()));
+            assert!(!PRIVATE_KEY_TYPE_TAGS.contains(&key_type.tag()));
+            assert_eq!(KeyType::try_from(key_type.tag()).unwrap(), key_type);
+            assert_eq!(KeyType::from_str(&key_type.to_string()).unwrap(), key_type);
+            assert_eq!(KeyType::from_str(&key_type.name().to_uppercase()).unwrap(), key_type);
+
+            let secret_key = SecretKey::from_seed(key_type, "test");
+            let public_key = secret_key.public_key();
+            assert_eq!(public_key.key_data().len(), key_type.public_key_length());
+            assert_eq!(public_key.try_to_vec().unwrap().len(), public_key.len());
+            let signature = secret_key.sign(&[1u8; 32]);
+            assert_eq!(signature.try_to_vec().unwrap().len(), key_type.signature_length() + 1);
+            assert_eq!(Signature::try_from(signature.to_string()).unwrap(), signature);
+        }
+        for tag in RESERVED_KEY_TYPE_TAGS.chain(PRIVATE_KEY_TYPE_TAGS) {
+            assert!(KeyType::try_from(tag).is_err());
+        }
+    }
+
+    #[test]
+    fn test_public_key_bytes() {
+        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1] {
+            let public_key = SecretKey::from_seed(key_type, "test").public_key();
+            let bytes = public_key.to_bytes();
+            assert_eq!(bytes, public_key.try_to_vec().unwrap());
+            assert_eq!(bytes.len(), public_key.len());
+            assert_eq!(PublicKey::from_bytes(&bytes).unwrap(), public_key);
+            assert_eq!(PublicKey::from_parts(key_type, public_key.key_data()).unwrap(), public_key);
+            assert!(PublicKey::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+            assert!(PublicKey::try_from_slice(&bytes[..bytes.len() - 1]).is_err());
+            assert!(PublicKey::from_parts(key_type, &[0u8; 3]).is_err());
+        }
+        assert!(PublicKey::from_bytes(&[]).is_err());
+    }
+
     #[test]
     fn test_invalid_data() {
         let invalid = "\"secp256k1:2xVqteU8PWhadHTv99TGh3bSf\"";
@@ -1,8 +1,14 @@
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 use std::sync::Arc;
 
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::errors::SealError;
 use crate::key_conversion::convert_secret_key;
 use crate::key_file::KeyFile;
+use crate::sealed::SealedSecretKey;
 use crate::{KeyType, PublicKey, SecretKey, Signature};
 
 /// Generic signer trait, that can sign with some subset of supported curves.
@@ -81,6 +87,73 @@ impl Signer for InMemorySigner {
     }
 }
 
+/// Source of the passphrase of a `SealedSigner`, asked for it on every signature, e.g. reading
+/// it from a file or an agent, so that the passphrase doesn't have to stay in memory either.
+pub type PassphraseSource = Box<dyn Fn() -> Zeroizing<String> + Send + Sync>;
+
+/// Signer that keeps secret key encrypted in memory with a passphrase, see `SealedSecretKey`.
+pub struct SealedSigner {
+    pub account_id: String,
+    pub public_key: PublicKey,
+    sealed_key: SealedSecretKey,
+    passphrase: PassphraseSource,
+}
+
+impl SealedSigner {
+    pub fn from_secret_key(
+        account_id: String,
+        secret_key: &SecretKey,
+        passphrase: PassphraseSource,
+    ) -> Result<Self, SealError> {
+        let sealed_key = SealedSecretKey::seal(secret_key, &passphrase())?;
+        Ok(Self::from_sealed_key(account_id, sealed_key, passphrase))
+    }
+
+    pub fn from_sealed_key(
+        account_id: String,
+        sealed_key: SealedSecretKey,
+        passphrase: PassphraseSource,
+    ) -> Self {
+        Self { account_id, public_key: sealed_key.public_key().clone(), sealed_key, passphrase }
+    }
+
+    /// Reads key file and seals the secret key from it, wiping the file content and the plaintext
+    /// key afterwards.
+    pub fn from_file(path: &Path, passphrase: PassphraseSource) -> Result<Self, SealError> {
+        let mut content = Zeroizing::new(String::new());
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(|err| SealError::InvalidKeyFile(err.to_string()))?;
+        let mut key_file: KeyFile = serde_json::from_str(&content)
+            .map_err(|err| SealError::InvalidKeyFile(err.to_string()))?;
+        let result = Self::from_secret_key(key_file.account_id, &key_file.secret_key, passphrase);
+        key_file.secret_key.zeroize();
+        result
+    }
+
+    fn with_secret_key<T>(&self, f: impl FnOnce(&SecretKey) -> T) -> T {
+        self.sealed_key
+            .with_secret_key(&(self.passphrase)(), f)
+            .expect("Failed to unseal the secret key")
+    }
+}
+
+impl Signer for SealedSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key.clone()
+    }
+
+    fn sign(&self, data: &[u8]) -> Signature {
+        self.with_secret_key(|secret_key| secret_key.sign(data))
+    }
+
+    fn compute_vrf_with_proof(&self, data: &[u8]) -> (crate::vrf::Value, crate::vrf::Proof) {
+        self.with_secret_key(|secret_key| {
+            convert_secret_key(secret_key.unwrap_as_ed25519()).compute_vrf_with_proof(&data)
+        })
+    }
+}
+
 impl From<KeyFile> for InMemorySigner {
     fn from(key_file: KeyFile) -> Self {
         Self {
@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+
+use cached::{Cached, SizedCache};
+use sha2::Digest;
+
+use crate::{PublicKey, Signature};
+
+/// Default number of verified signatures to remember.
+pub const DEFAULT_VERIFICATION_CACHE_SIZE: usize = 100_000;
+
+type CacheKey = (PublicKey, [u8; 32], Signature);
+
+/// LRU of signatures known to be valid, keyed by the public key, the hash of the signed data and
+/// the signature itself. The same transaction and approval signatures are verified on gossip, at
+/// the pool admission and when applying chunks; callers that share a cache between these paths
+/// verify each valid signature only once.
+///
+/// Failed verifications are not remembered, so an entry never answers for anything but the exact
+/// signature that was verified, and invalid signatures can't evict the valid ones.
+pub struct SignatureVerificationCache {
+    cache: Mutex<SizedCache<CacheKey, ()>>,
+}
+
+impl SignatureVerificationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { cache: Mutex::new(SizedCache::with_size(capacity)) }
+    }
+
+    fn key(data: &[u8], signature: &Signature, public_key: &PublicKey) -> CacheKey {
+        (public_key.clone(), sha2::Sha256::digest(data).into(), signature.clone())
+    }
+
+    /// Returns true if the signature of the data by the key is known to be valid.
+    pub fn contains(&self, data: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+        let key = Self::key(data, signature, public_key);
+        self.cache.lock().expect("Poisoned lock").cache_get(&key).is_some()
+    }
+
+    /// Remembers the signature as valid. The caller must have verified it.
+    pub fn insert(&self, data: &[u8], signature: &Signature, public_key: &PublicKey) {
+        let key = Self::key(data, signature, public_key);
+        self.cache.lock().expect("Poisoned lock").cache_set(key, ());
+    }
+
+    /// Same as `Signature::verify`, but skips the verification if the signature is known to be
+    /// valid, and remembers it if it is.
+    pub fn verify(&self, data: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+        if self.contains(data, signature, public_key) {
+            return true;
+        }
+        let is_valid = signature.verify(data, public_key);
+        if is_valid {
+            self.insert(data, signature, public_key);
+        }
+        is_valid
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().expect("Poisoned lock").cache_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for SignatureVerificationCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_VERIFICATION_CACHE_SIZE)
+    }
+}
+
+impl std::fmt::Debug for SignatureVerificationCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignatureVerificationCache").field("len", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InMemorySigner, KeyType, Signer};
+
+    use super::*;
+
+    #[test]
+    fn test_verification_cache() {
+        let signer = InMemorySigner::from_seed("test", KeyType::ED25519, "test");
+        let other = InMemorySigner::from_seed("other", KeyType::ED25519, "other");
+        let cache = SignatureVerificationCache::new(10);
+        let data = b"approval";
+        let signature = signer.sign(data);
+        assert!(cache.verify(data, &signature, &signer.public_key()));
+        assert!(cache.contains(data, &signature, &signer.public_key()));
+        assert_eq!(cache.len(), 1);
+
+        // Invalid signatures are never cached, nor answered by a cached valid one.
+        let forged = other.sign(data);
+        assert!(!cache.verify(data, &forged, &signer.public_key()));
+        assert!(!cache.contains(data, &forged, &signer.public_key()));
+        assert!(!cache.verify(b"other data", &signature, &signer.public_key()));
+        assert!(!cache.contains(data, &signature, &other.public_key()));
+        assert_eq!(cache.len(), 1);
+    }
+}
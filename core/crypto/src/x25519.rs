@@ -0,0 +1,116 @@
+use arrayref::array_ref;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+
+use crate::{PublicKey, SecretKey};
+
+/// X25519 public key, i.e. u-coordinate of the Montgomery form of an ED25519 public key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct X25519PublicKey(pub [u8; 32]);
+
+/// X25519 secret scalar derived from an ED25519 secret key the same way ED25519 derives its
+/// signing scalar, so that it matches the converted public key.
+#[derive(Clone)]
+pub struct X25519SecretKey(Scalar);
+
+impl X25519SecretKey {
+    pub fn from_random<R: rand_core::RngCore + rand_core::CryptoRng>(rng: &mut R) -> Self {
+        X25519SecretKey(Scalar::random(rng))
+    }
+
+    pub fn public_key(&self) -> X25519PublicKey {
+        X25519PublicKey((curve25519_dalek::constants::X25519_BASEPOINT * self.0).to_bytes())
+    }
+
+    /// Raw Diffie-Hellman output. Returns `None` if the peer key is of small order, in which case
+    /// the output doesn't depend on our secret.
+    pub fn diffie_hellman(&self, public_key: &X25519PublicKey) -> Option<[u8; 32]> {
+        let shared = (MontgomeryPoint(public_key.0) * self.0).to_bytes();
+        if shared == [0u8; 32] {
+            None
+        } else {
+            Some(shared)
+        }
+    }
+}
+
+impl std::fmt::Debug for X25519SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "X25519SecretKey(..)")
+    }
+}
+
+impl PublicKey {
+    /// Converts ED25519 public key to X25519. Returns `None` for SECP256K1 keys and for bytes that
+    /// are not a valid curve point.
+    pub fn to_x25519(&self) -> Option<X25519PublicKey> {
+        match self {
+            PublicKey::ED25519(key) => {
+                let point = CompressedEdwardsY::from_slice(&key.0).decompress()?;
+                Some(X25519PublicKey(point.to_montgomery().to_bytes()))
+            }
+            PublicKey::SECP256K1(_) => None,
+        }
+    }
+}
+
+impl SecretKey {
+    /// Converts ED25519 secret key to X25519. Returns `None` for SECP256K1 keys.
+    pub fn to_x25519(&self) -> Option<X25519SecretKey> {
+        match self {
+            SecretKey::ED25519(key) => {
+                let expanded = ed25519_dalek::ExpandedSecretKey::from(
+                    &ed25519_dalek::SecretKey::from_bytes(&key.0[..32]).unwrap(),
+                )
+                .to_bytes();
+                Some(X25519SecretKey(Scalar::from_bits(*array_ref!(&expanded, 0, 32))))
+            }
+            SecretKey::SECP256K1(_) => None,
+        }
+    }
+}
+
+/// Computes X25519 shared secret between our ED25519 identity key and the peer's one. Both sides
+/// get the same value. The output is not uniformly random and must be passed through a KDF before
+/// being used as an encryption key.
+pub fn shared_secret(secret_key: &SecretKey, public_key: &PublicKey) -> Option<[u8; 32]> {
+    secret_key.to_x25519()?.diffie_hellman(&public_key.to_x25519()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::KeyType;
+
+    use super::*;
+
+    #[test]
+    fn test_x25519_conversion() {
+        for _ in 0..10 {
+            let secret_key = SecretKey::from_random(KeyType::ED25519);
+            assert_eq!(
+                secret_key.to_x25519().unwrap().public_key(),
+                secret_key.public_key().to_x25519().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_shared_secret() {
+        let alice = SecretKey::from_seed(KeyType::ED25519, "alice");
+        let bob = SecretKey::from_seed(KeyType::ED25519, "bob");
+        let carol = SecretKey::from_seed(KeyType::ED25519, "carol");
+        let secret = shared_secret(&alice, &bob.public_key()).unwrap();
+        assert_eq!(secret, shared_secret(&bob, &alice.public_key()).unwrap());
+        assert_ne!(secret, shared_secret(&carol, &alice.public_key()).unwrap());
+
+        let secp = SecretKey::from_seed(KeyType::SECP256K1, "alice");
+        assert!(shared_secret(&secp, &bob.public_key()).is_none());
+        assert!(shared_secret(&alice, &secp.public_key()).is_none());
+        // Identity point has small order.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let identity = PublicKey::ED25519(crate::ED25519PublicKey(identity));
+        assert!(shared_secret(&alice, &identity).is_none());
+    }
+}
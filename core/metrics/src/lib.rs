@@ -55,18 +55,49 @@
 //! }
 //! ```
 
+use std::sync::Mutex;
+
+pub use prometheus::core::Collector;
 pub use prometheus::{
-    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, Result, TextEncoder,
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Result,
+    TextEncoder,
 };
 use prometheus::{HistogramOpts, HistogramTimer, Opts};
 
+use lazy_static::lazy_static;
 use log::error;
 
+lazy_static! {
+    static ref GATHER_HOOKS: Mutex<Vec<Box<dyn Fn() + Send>>> = Mutex::new(vec![]);
+}
+
 /// Collect all the metrics for reporting.
 pub fn gather() -> Vec<prometheus::proto::MetricFamily> {
+    for hook in GATHER_HOOKS.lock().expect("Metrics hooks lock is poisoned").iter() {
+        hook();
+    }
     prometheus::gather()
 }
 
+/// Collect all the metrics and encode them in the Prometheus text format.
+pub fn gather_text() -> Result<String> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&gather(), &mut buffer)?;
+    String::from_utf8(buffer).map_err(|err| prometheus::Error::Msg(err.to_string()))
+}
+
+/// Registers a collector with its own metrics in the global registry, returning `Err` on naming
+/// conflicts.
+pub fn register(collector: Box<dyn Collector>) -> Result<()> {
+    prometheus::register(collector)
+}
+
+/// Registers a function called before every collection of the metrics, to update the ones that
+/// are only worth computing when read (e.g., database statistics).
+pub fn register_gather_hook<F: Fn() + Send + 'static>(hook: F) {
+    GATHER_HOOKS.lock().expect("Metrics hooks lock is poisoned").push(Box::new(hook));
+}
+
 /// Attempts to crate an `IntCounter`, returning `Err` if the registry does not accept the counter
 /// (potentially due to naming conflict).
 pub fn try_create_int_counter(name: &str, help: &str) -> Result<IntCounter> {
@@ -98,6 +129,15 @@ pub fn try_create_int_gauge(name: &str, help: &str) -> Result<IntGauge> {
     Ok(gauge)
 }
 
+/// Attempts to crate an `IntGaugeVec`, returning `Err` if the registry does not accept the gauge
+/// (potentially due to naming conflict).
+pub fn try_create_int_gauge_vec(name: &str, help: &str, labels: &[&str]) -> Result<IntGaugeVec> {
+    let opts = Opts::new(name, help);
+    let gauge = IntGaugeVec::new(opts, labels)?;
+    prometheus::register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
 /// Attempts to crate a `Histogram`, returning `Err` if the registry does not accept the counter
 /// (potentially due to naming conflict).
 pub fn try_create_histogram(name: &str, help: &str) -> Result<Histogram> {
@@ -5,7 +5,7 @@ use chrono::{DateTime, Utc};
 use near_crypto::Signature;
 use num_rational::Rational;
 use primitive_types::U256;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::block::BlockValidityError::{
     InvalidChallengeRoot, InvalidChunkHeaderRoot, InvalidChunkMask, InvalidReceiptRoot,
@@ -19,7 +19,7 @@ use crate::sharding::{
     ChunkHashHeight, EncodedShardChunk, ReedSolomonWrapper, ShardChunk, ShardChunkHeader,
     ShardChunkHeaderV1,
 };
-use crate::types::{Balance, BlockHeight, EpochId, Gas, NumShards, StateRoot};
+use crate::types::{Balance, BlockFeeInfo, BlockHeight, EpochId, Gas, NumShards, StateRoot};
 use crate::utils::to_timestamp;
 use crate::validator_signer::{EmptyValidatorSigner, ValidatorSigner};
 use crate::version::{ProtocolVersion, SHARD_CHUNK_HEADER_UPGRADE_VERSION};
@@ -187,6 +187,7 @@ impl Block {
     /// Produces new block from header of previous block, current state root and set of transactions.
     pub fn produce(
         protocol_version: ProtocolVersion,
+        latest_protocol_version: ProtocolVersion,
         prev: &BlockHeader,
         height: BlockHeight,
         chunks: Vec<ShardChunkHeader>,
@@ -250,6 +251,7 @@ impl Block {
 
         let header = BlockHeader::new(
             protocol_version,
+            latest_protocol_version,
             height,
             prev.hash().clone(),
             Block::compute_state_root(&chunks),
@@ -463,6 +465,24 @@ impl Block {
         self.header().hash()
     }
 
+    /// Gas price and gas usage of chunks included in this block, same values that gas price
+    /// adjustment is based on.
+    pub fn fee_info(&self) -> BlockFeeInfo {
+        let height = self.header().height();
+        let (gas_used, gas_limit) = self
+            .chunks()
+            .iter()
+            .map(|chunk| {
+                if chunk.height_included() == height {
+                    (chunk.gas_used(), chunk.gas_limit())
+                } else {
+                    (0, 0)
+                }
+            })
+            .unzip();
+        BlockFeeInfo { height, gas_price: self.header().gas_price(), gas_used, gas_limit }
+    }
+
     /// Checks that block content matches block hash, with the possible exception of chunk signatures
     pub fn check_validity(&self) -> Result<(), BlockValidityError> {
         // Check that state root stored in the header matches the state root of the chunks
@@ -577,7 +597,7 @@ impl<'a> ChunksCollection<'a> {
 /// The tip of a fork. A handle to the fork ancestry from its leaf in the
 /// blockchain tree. References the max height and the latest and previous
 /// blocks for convenience
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Tip {
     /// Height of the tip (max height of the fork)
     pub height: BlockHeight,
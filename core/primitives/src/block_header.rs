@@ -11,7 +11,7 @@ use crate::network::PeerId;
 use crate::types::{AccountId, Balance, BlockHeight, EpochId, MerkleHash, ValidatorStake};
 use crate::utils::{from_timestamp, to_timestamp};
 use crate::validator_signer::ValidatorSigner;
-use crate::version::{ProtocolVersion, PROTOCOL_VERSION};
+use crate::version::ProtocolVersion;
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct BlockHeaderInnerLite {
@@ -253,6 +253,7 @@ impl BlockHeader {
 
     pub fn new(
         protocol_version: ProtocolVersion,
+        latest_protocol_version: ProtocolVersion,
         height: BlockHeight,
         prev_hash: CryptoHash,
         prev_state_root: MerkleHash,
@@ -304,7 +305,7 @@ impl BlockHeader {
                 last_final_block,
                 last_ds_final_block,
                 approvals,
-                latest_protocol_version: PROTOCOL_VERSION,
+                latest_protocol_version,
             };
             let (hash, signature) = signer.sign_block_header_parts(
                 prev_hash,
@@ -333,7 +334,7 @@ impl BlockHeader {
                 last_final_block,
                 last_ds_final_block,
                 approvals,
-                latest_protocol_version: PROTOCOL_VERSION,
+                latest_protocol_version,
             };
             let (hash, signature) = signer.sign_block_header_parts(
                 prev_hash,
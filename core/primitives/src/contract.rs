@@ -1,4 +1,7 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
 use crate::hash::{hash as sha256, CryptoHash};
+use crate::types::{Balance, Gas};
 
 pub struct ContractCode {
     pub code: Vec<u8>,
@@ -19,3 +22,12 @@ impl ContractCode {
         &self.code
     }
 }
+
+/// Recommended gas and deposit to attach to calls of a contract method. Contracts store it in
+/// the contract code, see `near_vm_runner::METHOD_METADATA_SECTION`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MethodMetadata {
+    pub method_name: String,
+    pub gas: Gas,
+    pub deposit: Balance,
+}
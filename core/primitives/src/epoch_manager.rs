@@ -29,6 +29,9 @@ pub struct EpochConfig {
     pub num_block_producer_seats: NumSeats,
     /// Number of seats of block producers per each shard.
     pub num_block_producer_seats_per_shard: Vec<NumSeats>,
+    /// Number of seats for chunk-only producers, the validators without a block producer seat
+    /// that only produce chunks.
+    pub num_chunk_only_producer_seats: NumSeats,
     /// Expected number of hidden validator seats per each shard.
     pub avg_hidden_validator_seats_per_shard: Vec<NumSeats>,
     /// Criterion for kicking out block producers.
@@ -141,6 +141,8 @@ pub enum InvalidTxError {
     Expired,
     /// An error occurred while validating actions of a Transaction.
     ActionsValidation(ActionsValidationError),
+    /// A transaction of the signer with the same idempotency key was already executed
+    DuplicateIdempotencyKey { transaction_hash: CryptoHash },
 }
 
 #[derive(
@@ -197,6 +199,14 @@ pub enum ActionsValidationError {
     UnsuitableStakingKey { public_key: PublicKey },
     /// The attached amount of gas in a FunctionCall action has to be a positive number.
     FunctionCallZeroAttachedGas,
+    /// The action is not enabled by the current protocol version.
+    UnsupportedProtocolFeature { protocol_feature: String, version: u32 },
+    /// The IdempotencyKey action must be the first action in transaction.
+    IdempotencyKeyMustBeFirst,
+    /// The transaction with an IdempotencyKey action has no FunctionCall actions.
+    IdempotencyKeyWithoutFunctionCall,
+    /// The length of the key exceeded the limit in an IdempotencyKey action.
+    IdempotencyKeyLengthExceeded { length: u64, limit: u64 },
 }
 
 /// Describes the error for validating a receipt.
@@ -311,6 +321,23 @@ impl Display for ActionsValidationError {
                 f,
                 "The attached amount of gas in a FunctionCall action has to be a positive number",
             ),
+            ActionsValidationError::UnsupportedProtocolFeature { protocol_feature, version } => write!(
+                f,
+                "Transaction requires protocol feature {} which is not supported by the current protocol version {}",
+                protocol_feature, version
+            ),
+            ActionsValidationError::IdempotencyKeyMustBeFirst => {
+                write!(f, "The idempotency key action must be the first action in transaction")
+            }
+            ActionsValidationError::IdempotencyKeyWithoutFunctionCall => write!(
+                f,
+                "The transaction with an idempotency key must have at least one FunctionCall action"
+            ),
+            ActionsValidationError::IdempotencyKeyLengthExceeded { length, limit } => write!(
+                f,
+                "The length of the idempotency key {} exceeds the maximum allowed length {}",
+                length, limit
+            ),
         }
     }
 }
@@ -404,7 +431,9 @@ impl Display for InvalidTxError {
             InvalidTxError::SignerDoesNotExist { signer_id } => {
                 write!(f, "Signer {:?} does not exist", signer_id)
             }
-            InvalidTxError::InvalidAccessKeyError(access_key_error) => Display::fmt(&access_key_error, f),
+            InvalidTxError::InvalidAccessKeyError(access_key_error) => {
+                Display::fmt(&access_key_error, f)
+            }
             InvalidTxError::InvalidNonce { tx_nonce, ak_nonce } => write!(
                 f,
                 "Transaction nonce {} must be larger than nonce of the used access key {}",
@@ -436,6 +465,11 @@ impl Display for InvalidTxError {
             InvalidTxError::ActionsValidation(error) => {
                 write!(f, "Transaction actions validation error: {}", error)
             }
+            InvalidTxError::DuplicateIdempotencyKey { transaction_hash } => write!(
+                f,
+                "Transaction with the same idempotency key was already executed: {}",
+                transaction_hash
+            ),
         }
     }
 }
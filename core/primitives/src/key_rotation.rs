@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use near_crypto::{PublicKey, Signature, Signer};
+
+use crate::types::{BlockHeight, BlockHeightDelta};
+
+/// Key that is scheduled to replace the active one.
+#[derive(Clone)]
+struct ScheduledKey {
+    signer: Arc<dyn Signer>,
+    /// First height signed with the new key.
+    activation_height: BlockHeight,
+    /// Number of heights before and after `activation_height` where both keys are accepted.
+    overlap: BlockHeightDelta,
+}
+
+/// Tracks the active validator key and the next one, which allows to rotate keys without
+/// stopping the node. Heights below `activation_height` are signed with the active key and the
+/// rest with the next key, while verification accepts either key within the overlap window
+/// around the activation height, so that signatures produced by lagging or leading nodes remain
+/// valid during the transition.
+#[derive(Clone)]
+pub struct KeyRotation {
+    active: Arc<dyn Signer>,
+    next: Option<ScheduledKey>,
+}
+
+impl KeyRotation {
+    pub fn new(active: Arc<dyn Signer>) -> Self {
+        Self { active, next: None }
+    }
+
+    /// Schedules `next` to become the signing key at `activation_height`. Replaces previously
+    /// scheduled key if any.
+    pub fn schedule(
+        &mut self,
+        next: Arc<dyn Signer>,
+        activation_height: BlockHeight,
+        overlap: BlockHeightDelta,
+    ) {
+        self.next = Some(ScheduledKey { signer: next, activation_height, overlap });
+    }
+
+    /// Cancels scheduled rotation.
+    pub fn cancel(&mut self) {
+        self.next = None;
+    }
+
+    /// Returns the height at which the scheduled key becomes active.
+    pub fn activation_height(&self) -> Option<BlockHeight> {
+        self.next.as_ref().map(|next| next.activation_height)
+    }
+
+    /// Signer that should be used for the given height.
+    pub fn signer(&self, height: BlockHeight) -> &Arc<dyn Signer> {
+        match &self.next {
+            Some(next) if height >= next.activation_height => &next.signer,
+            _ => &self.active,
+        }
+    }
+
+    pub fn public_key(&self, height: BlockHeight) -> PublicKey {
+        self.signer(height).public_key()
+    }
+
+    pub fn sign(&self, height: BlockHeight, data: &[u8]) -> Signature {
+        self.signer(height).sign(data)
+    }
+
+    /// Public keys that are accepted for the given height.
+    pub fn accepted_public_keys(&self, height: BlockHeight) -> Vec<PublicKey> {
+        match &self.next {
+            None => vec![self.active.public_key()],
+            Some(next) => {
+                let mut keys = vec![];
+                if height < next.activation_height.saturating_add(next.overlap) {
+                    keys.push(self.active.public_key());
+                }
+                if height >= next.activation_height.saturating_sub(next.overlap) {
+                    keys.push(next.signer.public_key());
+                }
+                keys
+            }
+        }
+    }
+
+    /// Verifies signature against any of the keys accepted for the given height.
+    pub fn verify(&self, height: BlockHeight, data: &[u8], signature: &Signature) -> bool {
+        self.accepted_public_keys(height)
+            .iter()
+            .any(|public_key| signature.verify(data, public_key))
+    }
+
+    /// Promotes the next key to active once `height` is past the transition window.
+    /// Returns true if the rotation happened.
+    pub fn finalize(&mut self, height: BlockHeight) -> bool {
+        match &self.next {
+            Some(next) if height >= next.activation_height.saturating_add(next.overlap) => {
+                self.active = self.next.take().unwrap().signer;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType};
+
+    use super::*;
+
+    fn signer(seed: &str) -> Arc<dyn Signer> {
+        Arc::new(InMemorySigner::from_seed("test", KeyType::ED25519, seed))
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = signer("old");
+        let new = signer("new");
+        let mut rotation = KeyRotation::new(old.clone());
+        rotation.schedule(new.clone(), 100, 10);
+
+        assert_eq!(rotation.public_key(99), old.public_key());
+        assert_eq!(rotation.public_key(100), new.public_key());
+        assert_eq!(rotation.accepted_public_keys(89), vec![old.public_key()]);
+        assert_eq!(rotation.accepted_public_keys(95), vec![old.public_key(), new.public_key()]);
+        assert_eq!(rotation.accepted_public_keys(110), vec![new.public_key()]);
+
+        let data = b"approval";
+        let old_signature = old.sign(data);
+        let new_signature = new.sign(data);
+        assert!(rotation.verify(105, data, &old_signature));
+        assert!(rotation.verify(95, data, &new_signature));
+        assert!(!rotation.verify(110, data, &old_signature));
+        assert!(!rotation.verify(89, data, &new_signature));
+
+        assert!(!rotation.finalize(109));
+        assert!(rotation.finalize(110));
+        assert_eq!(rotation.activation_height(), None);
+        assert_eq!(rotation.accepted_public_keys(0), vec![new.public_key()]);
+    }
+}
@@ -15,8 +15,11 @@ pub mod contract;
 pub mod epoch_manager;
 pub mod errors;
 pub mod hash;
+pub mod key_rotation;
+pub mod light_client;
 pub mod logging;
 pub mod merkle;
+pub mod multisig;
 pub mod network;
 pub mod receipt;
 pub mod rpc;
@@ -29,6 +32,7 @@ pub mod test_utils;
 pub mod transaction;
 pub mod trie_key;
 pub mod types;
+pub mod upgrade_schedule;
 pub mod utils;
 pub mod validator_signer;
 pub mod version;
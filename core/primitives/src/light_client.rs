@@ -0,0 +1,289 @@
+//! Offline verification of execution outcome proofs returned by the `light_client_proof` RPC.
+//!
+//! Given a block header that was already verified by a light client, these functions check that
+//! a transaction or receipt outcome was included into the chain without trusting the RPC node
+//! that served the proof. This is what custodians need to confirm deposits.
+//!
+//! ```no_run
+//! use near_primitives::light_client::verify_execution_proof;
+//! use near_primitives::rpc::RpcLightClientExecutionProofResponse;
+//! use near_primitives::views::{ExecutionStatusView, LightClientBlockLiteView};
+//!
+//! # fn read_json(_path: &str) -> serde_json::Value { unimplemented!() }
+//! // Head tracked by the light client, its hash is used as `light_client_head` in the request.
+//! let head: LightClientBlockLiteView = serde_json::from_value(read_json("head.json")).unwrap();
+//! // Response of `light_client_proof` for the receipt that credits the deposit.
+//! let proof: RpcLightClientExecutionProofResponse =
+//!     serde_json::from_value(read_json("proof.json")).unwrap();
+//! match verify_execution_proof(&proof, &head) {
+//!     Ok(ExecutionStatusView::SuccessValue(_)) => println!("deposit confirmed"),
+//!     Ok(status) => println!("deposit is not successful: {:?}", status),
+//!     Err(err) => println!("invalid proof: {}", err),
+//! }
+//! ```
+use std::fmt;
+
+use borsh::BorshSerialize;
+
+use crate::block_header::BlockHeaderInnerLite;
+use crate::hash::{hash, CryptoHash};
+use crate::merkle::{
+    combine_hash, compute_root_from_path, compute_root_from_path_and_item, MerklePath,
+};
+use crate::rpc::RpcLightClientExecutionProofResponse;
+use crate::serialize::from_base64;
+use crate::transaction::{PartialExecutionOutcome, PartialExecutionStatus};
+use crate::types::EpochId;
+use crate::views::{ExecutionOutcomeWithIdView, ExecutionStatusView, LightClientBlockLiteView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutcomeProofError {
+    /// Success value of the outcome is not valid base64.
+    InvalidSuccessValue,
+    /// Outcome claims to be included into a different block than the one in the proof.
+    BlockHashMismatch { expected: CryptoHash, actual: CryptoHash },
+    /// Outcome is not part of the outcome root of the block.
+    InvalidOutcomeProof,
+    /// Block is not part of the block merkle root of the light client head.
+    InvalidBlockProof,
+}
+
+impl fmt::Display for OutcomeProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutcomeProofError::InvalidSuccessValue => {
+                write!(f, "Success value of the outcome is not valid base64")
+            }
+            OutcomeProofError::BlockHashMismatch { expected, actual } => write!(
+                f,
+                "Outcome is included into block {}, but the proof is for block {}",
+                expected, actual
+            ),
+            OutcomeProofError::InvalidOutcomeProof => {
+                write!(f, "Outcome doesn't match the outcome root of the block")
+            }
+            OutcomeProofError::InvalidBlockProof => {
+                write!(f, "Block doesn't match the block merkle root of the light client head")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OutcomeProofError {}
+
+/// Hash of the block described by the lite header.
+pub fn compute_block_hash(block: &LightClientBlockLiteView) -> CryptoHash {
+    let inner_lite = BlockHeaderInnerLite {
+        height: block.inner_lite.height,
+        epoch_id: EpochId(block.inner_lite.epoch_id),
+        next_epoch_id: EpochId(block.inner_lite.next_epoch_id),
+        prev_state_root: block.inner_lite.prev_state_root,
+        outcome_root: block.inner_lite.outcome_root,
+        timestamp: block.inner_lite.timestamp_nanosec,
+        next_bp_hash: block.inner_lite.next_bp_hash,
+        block_merkle_root: block.inner_lite.block_merkle_root,
+    };
+    let inner_lite_hash = hash(&inner_lite.try_to_vec().expect("Failed to serialize"));
+    combine_hash(combine_hash(inner_lite_hash, block.inner_rest_hash), block.prev_block_hash)
+}
+
+/// Hashes of the outcome that are merklized into the chunk outcome root, same as
+/// `ExecutionOutcomeWithId::to_hashes`. Failure details are not part of the hashes.
+pub fn outcome_hashes(
+    outcome: &ExecutionOutcomeWithIdView,
+) -> Result<Vec<CryptoHash>, OutcomeProofError> {
+    let status = match &outcome.outcome.status {
+        ExecutionStatusView::Unknown => PartialExecutionStatus::Unknown,
+        ExecutionStatusView::Failure(_) => PartialExecutionStatus::Failure,
+        ExecutionStatusView::SuccessValue(value) => PartialExecutionStatus::SuccessValue(
+            from_base64(value).map_err(|_| OutcomeProofError::InvalidSuccessValue)?,
+        ),
+        ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+            PartialExecutionStatus::SuccessReceiptId(*receipt_id)
+        }
+    };
+    let partial_outcome = PartialExecutionOutcome {
+        receipt_ids: outcome.outcome.receipt_ids.clone(),
+        gas_burnt: outcome.outcome.gas_burnt,
+        tokens_burnt: outcome.outcome.tokens_burnt,
+        executor_id: outcome.outcome.executor_id.clone(),
+        status,
+    };
+    let mut result = vec![outcome.id];
+    result.push(hash(&partial_outcome.try_to_vec().expect("Failed to serialize")));
+    for log in outcome.outcome.logs.iter() {
+        result.push(hash(log.as_bytes()));
+    }
+    Ok(result)
+}
+
+/// Checks that the outcome is included into the block described by `block_header_lite`.
+pub fn verify_outcome_proof(
+    proof: &RpcLightClientExecutionProofResponse,
+) -> Result<(), OutcomeProofError> {
+    let block_hash = compute_block_hash(&proof.block_header_lite);
+    if block_hash != proof.outcome_proof.block_hash {
+        return Err(OutcomeProofError::BlockHashMismatch {
+            expected: proof.outcome_proof.block_hash,
+            actual: block_hash,
+        });
+    }
+    let chunk_outcome_root = compute_root_from_path_and_item(
+        &proof.outcome_proof.proof,
+        &outcome_hashes(&proof.outcome_proof)?,
+    );
+    let outcome_root =
+        compute_root_from_path_and_item(&proof.outcome_root_proof, &chunk_outcome_root);
+    if outcome_root != proof.block_header_lite.inner_lite.outcome_root {
+        return Err(OutcomeProofError::InvalidOutcomeProof);
+    }
+    Ok(())
+}
+
+/// Checks that the block with the given hash is an ancestor of `light_client_head` or the head
+/// itself.
+pub fn verify_block_proof(
+    block_hash: &CryptoHash,
+    block_proof: &MerklePath,
+    light_client_head: &LightClientBlockLiteView,
+) -> Result<(), OutcomeProofError> {
+    if block_proof.is_empty() && *block_hash == compute_block_hash(light_client_head) {
+        return Ok(());
+    }
+    if compute_root_from_path(block_proof, *block_hash)
+        != light_client_head.inner_lite.block_merkle_root
+    {
+        return Err(OutcomeProofError::InvalidBlockProof);
+    }
+    Ok(())
+}
+
+/// Verifies the whole `light_client_proof` response against a head that the caller already
+/// verified, and returns the execution status of the outcome.
+///
+/// The status of a transaction only tells whether it was converted to a receipt, deposits should
+/// be confirmed with the proof of the receipt that credits the account. The error details of a
+/// failed outcome are not covered by the proof.
+pub fn verify_execution_proof<'a>(
+    proof: &'a RpcLightClientExecutionProofResponse,
+    light_client_head: &LightClientBlockLiteView,
+) -> Result<&'a ExecutionStatusView, OutcomeProofError> {
+    verify_outcome_proof(proof)?;
+    verify_block_proof(&proof.outcome_proof.block_hash, &proof.block_proof, light_client_head)?;
+    Ok(&proof.outcome_proof.outcome.status)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::block_header::BlockHeader;
+    use crate::merkle::{merklize, Direction, MerklePathItem};
+    use crate::transaction::{
+        ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
+    };
+    use crate::version::PROTOCOL_VERSION;
+
+    use super::*;
+
+    fn header_with_roots(outcome_root: CryptoHash, block_merkle_root: CryptoHash) -> BlockHeader {
+        let mut header = BlockHeader::genesis(
+            PROTOCOL_VERSION,
+            1,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            1,
+            CryptoHash::default(),
+            Utc::now(),
+            100,
+            1_000_000,
+            CryptoHash::default(),
+        );
+        header.get_mut().inner_lite.outcome_root = outcome_root;
+        header.get_mut().inner_lite.block_merkle_root = block_merkle_root;
+        header.get_mut().init();
+        header
+    }
+
+    #[test]
+    fn test_verify_execution_proof() {
+        let outcomes = vec![
+            ExecutionOutcomeWithId { id: hash(&[1]), outcome: ExecutionOutcome::default() },
+            ExecutionOutcomeWithId {
+                id: hash(&[2]),
+                outcome: ExecutionOutcome {
+                    logs: vec!["deposit".to_string()],
+                    gas_burnt: 100,
+                    tokens_burnt: 1000,
+                    executor_id: "alice.near".to_string(),
+                    status: ExecutionStatus::SuccessValue(vec![1, 2, 3]),
+                    ..Default::default()
+                },
+            },
+        ];
+        let (chunk_outcome_root, outcome_paths) =
+            merklize(&outcomes.iter().map(|outcome| outcome.to_hashes()).collect::<Vec<_>>());
+        let (outcome_root, outcome_root_paths) = merklize(&[chunk_outcome_root, hash(&[3])]);
+        let block = header_with_roots(outcome_root, CryptoHash::default());
+        let block_hash = *block.hash();
+        assert_eq!(compute_block_hash(&block.clone().into()), block_hash);
+
+        let sibling = hash(&[4]);
+        let head: LightClientBlockLiteView =
+            header_with_roots(CryptoHash::default(), combine_hash(block_hash, sibling)).into();
+        let proof = || RpcLightClientExecutionProofResponse {
+            outcome_proof: ExecutionOutcomeWithIdAndProof {
+                proof: outcome_paths[1].clone(),
+                block_hash,
+                outcome_with_id: outcomes[1].clone(),
+            }
+            .into(),
+            outcome_root_proof: outcome_root_paths[0].clone(),
+            block_header_lite: block.clone().into(),
+            block_proof: vec![MerklePathItem { hash: sibling, direction: Direction::Right }],
+        };
+        assert_eq!(
+            verify_execution_proof(&proof(), &head),
+            Ok(&ExecutionStatusView::SuccessValue("AQID".to_string()))
+        );
+
+        let mut wrong_status = proof();
+        wrong_status.outcome_proof.outcome.status =
+            ExecutionStatusView::SuccessValue(String::new());
+        assert_eq!(
+            verify_execution_proof(&wrong_status, &head),
+            Err(OutcomeProofError::InvalidOutcomeProof)
+        );
+        let mut wrong_logs = proof();
+        wrong_logs.outcome_proof.outcome.logs.clear();
+        assert_eq!(
+            verify_execution_proof(&wrong_logs, &head),
+            Err(OutcomeProofError::InvalidOutcomeProof)
+        );
+        let mut wrong_block = proof();
+        wrong_block.block_header_lite.inner_lite.height += 1;
+        assert!(matches!(
+            verify_execution_proof(&wrong_block, &head),
+            Err(OutcomeProofError::BlockHashMismatch { .. })
+        ));
+        let mut wrong_block_proof = proof();
+        wrong_block_proof.block_proof[0].direction = Direction::Left;
+        assert_eq!(
+            verify_execution_proof(&wrong_block_proof, &head),
+            Err(OutcomeProofError::InvalidBlockProof)
+        );
+
+        let mut at_head = proof();
+        at_head.block_proof.clear();
+        assert_eq!(
+            verify_execution_proof(&at_head, &block.clone().into()),
+            Ok(&ExecutionStatusView::SuccessValue("AQID".to_string()))
+        );
+        assert_eq!(
+            verify_execution_proof(&at_head, &head),
+            Err(OutcomeProofError::InvalidBlockProof)
+        );
+    }
+}
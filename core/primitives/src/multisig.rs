@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use near_crypto::{PublicKey, Signature, Signer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultisigError {
+    /// Threshold must be between 1 and the number of keys.
+    InvalidThreshold {
+        threshold: u32,
+        num_keys: usize,
+    },
+    DuplicatePublicKey(PublicKey),
+}
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultisigError::InvalidThreshold { threshold, num_keys } => {
+                write!(f, "Threshold {} is invalid for {} public keys", threshold, num_keys)
+            }
+            MultisigError::DuplicatePublicKey(public_key) => {
+                write!(f, "Public key {} is used more than once", public_key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MultisigError {}
+
+/// `threshold` out of `public_keys` need to sign the payload.
+///
+/// Deserialization goes through `MultiPublicKey::new`, so any instance has a valid threshold and
+/// distinct keys.
+#[derive(BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "UncheckedMultiPublicKey")]
+pub struct MultiPublicKey {
+    threshold: u32,
+    public_keys: Vec<PublicKey>,
+}
+
+#[derive(Deserialize)]
+struct UncheckedMultiPublicKey {
+    threshold: u32,
+    public_keys: Vec<PublicKey>,
+}
+
+impl TryFrom<UncheckedMultiPublicKey> for MultiPublicKey {
+    type Error = MultisigError;
+
+    fn try_from(unchecked: UncheckedMultiPublicKey) -> Result<Self, Self::Error> {
+        MultiPublicKey::new(unchecked.threshold, unchecked.public_keys)
+    }
+}
+
+impl BorshDeserialize for MultiPublicKey {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+        let threshold = <u32 as BorshDeserialize>::deserialize(buf)?;
+        let public_keys = <Vec<PublicKey> as BorshDeserialize>::deserialize(buf)?;
+        MultiPublicKey::new(threshold, public_keys)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))
+    }
+}
+
+impl MultiPublicKey {
+    pub fn new(threshold: u32, public_keys: Vec<PublicKey>) -> Result<Self, MultisigError> {
+        if threshold == 0 || threshold as usize > public_keys.len() {
+            return Err(MultisigError::InvalidThreshold { threshold, num_keys: public_keys.len() });
+        }
+        let mut seen = HashSet::new();
+        for public_key in public_keys.iter() {
+            if !seen.insert(public_key) {
+                return Err(MultisigError::DuplicatePublicKey(public_key.clone()));
+            }
+        }
+        Ok(Self { threshold, public_keys })
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn public_keys(&self) -> &[PublicKey] {
+        &self.public_keys
+    }
+
+    /// Index of the given key, used to tag signatures.
+    pub fn key_index(&self, public_key: &PublicKey) -> Option<u32> {
+        self.public_keys.iter().position(|key| key == public_key).map(|index| index as u32)
+    }
+
+    /// Returns true if at least `threshold` distinct keys signed the data. Any invalid signature,
+    /// unknown key index or key that signed twice makes the whole multi signature invalid.
+    pub fn verify(&self, data: &[u8], signature: &MultiSignature) -> bool {
+        let mut signed = HashSet::new();
+        for IndexedSignature { key_index, signature } in signature.signatures.iter() {
+            let public_key = match self.public_keys.get(*key_index as usize) {
+                Some(public_key) => public_key,
+                None => return false,
+            };
+            if !signed.insert(*key_index) || !signature.verify(data, public_key) {
+                return false;
+            }
+        }
+        signed.len() >= self.threshold as usize
+    }
+}
+
+/// Signature by the key with the given index in `MultiPublicKey`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IndexedSignature {
+    pub key_index: u32,
+    pub signature: Signature,
+}
+
+/// Signatures of the same payload by the keys of a `MultiPublicKey`.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq,
+)]
+pub struct MultiSignature {
+    signatures: Vec<IndexedSignature>,
+}
+
+impl MultiSignature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, key_index: u32, signature: Signature) {
+        self.signatures.push(IndexedSignature { key_index, signature });
+    }
+
+    /// Signs the data with the given signer and adds the signature. Returns false if the signer's
+    /// key is not part of `multi_public_key`.
+    pub fn sign(
+        &mut self,
+        multi_public_key: &MultiPublicKey,
+        signer: &dyn Signer,
+        data: &[u8],
+    ) -> bool {
+        match multi_public_key.key_index(&signer.public_key()) {
+            Some(key_index) => {
+                self.add(key_index, signer.sign(data));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn signatures(&self) -> &[IndexedSignature] {
+        &self.signatures
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType};
+
+    use super::*;
+
+    #[test]
+    fn test_multisig() {
+        let signers: Vec<_> = (0..3)
+            .map(|i| InMemorySigner::from_seed("test", KeyType::ED25519, &format!("test{}", i)))
+            .collect();
+        let public_keys: Vec<_> = signers.iter().map(|signer| signer.public_key()).collect();
+        assert!(MultiPublicKey::new(0, public_keys.clone()).is_err());
+        assert!(MultiPublicKey::new(4, public_keys.clone()).is_err());
+        assert!(
+            MultiPublicKey::new(1, vec![public_keys[0].clone(), public_keys[0].clone()]).is_err()
+        );
+        let multi_public_key = MultiPublicKey::new(2, public_keys).unwrap();
+
+        let data = b"payload";
+        let mut signature = MultiSignature::new();
+        assert!(signature.sign(&multi_public_key, &signers[2], data));
+        assert!(!multi_public_key.verify(data, &signature));
+        let mut duplicate = signature.clone();
+        assert!(duplicate.sign(&multi_public_key, &signers[2], data));
+        assert!(!multi_public_key.verify(data, &duplicate));
+        assert!(signature.sign(&multi_public_key, &signers[0], data));
+        assert!(multi_public_key.verify(data, &signature));
+        assert!(!multi_public_key.verify(b"other payload", &signature));
+
+        let other = InMemorySigner::from_seed("test", KeyType::ED25519, "other");
+        assert!(!signature.sign(&multi_public_key, &other, data));
+        let mut wrong_index = signature.clone();
+        wrong_index.add(1, other.sign(data));
+        assert!(!multi_public_key.verify(data, &wrong_index));
+
+        let encoded = signature.try_to_vec().unwrap();
+        assert_eq!(MultiSignature::try_from_slice(&encoded).unwrap(), signature);
+        let json = serde_json::to_string(&multi_public_key).unwrap();
+        assert_eq!(serde_json::from_str::<MultiPublicKey>(&json).unwrap(), multi_public_key);
+    }
+
+    #[test]
+    fn test_multi_public_key_deserialize_invalid() {
+        let public_key = InMemorySigner::from_seed("test", KeyType::ED25519, "test").public_key();
+        let multi_public_key = MultiPublicKey::new(1, vec![public_key.clone()]).unwrap();
+        let encoded = multi_public_key.try_to_vec().unwrap();
+        assert_eq!(MultiPublicKey::try_from_slice(&encoded).unwrap(), multi_public_key);
+
+        let duplicate = UncheckedMultiPublicKey {
+            threshold: 1,
+            public_keys: vec![public_key.clone(), public_key.clone()],
+        };
+        let too_high = UncheckedMultiPublicKey { threshold: 2, public_keys: vec![public_key] };
+        for unchecked in vec![duplicate, too_high] {
+            let mut encoded = unchecked.threshold.try_to_vec().unwrap();
+            encoded.extend(unchecked.public_keys.try_to_vec().unwrap());
+            assert!(MultiPublicKey::try_from_slice(&encoded).is_err());
+            let json = serde_json::json!({
+                "threshold": unchecked.threshold,
+                "public_keys": unchecked.public_keys,
+            });
+            assert!(serde_json::from_value::<MultiPublicKey>(json).is_err());
+        }
+    }
+}
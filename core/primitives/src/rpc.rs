@@ -6,11 +6,15 @@ use serde::{Deserialize, Serialize};
 
 use crate::hash::CryptoHash;
 use crate::merkle::MerklePath;
+use crate::serialize::{base64_format, u128_dec_format};
 use crate::transaction::SignedTransaction;
-use crate::types::{AccountId, BlockReference, MaybeBlockId, TransactionOrReceiptId};
+use crate::types::{
+    AccountId, Balance, BlockHeight, BlockReference, Gas, MaybeBlockId, ShardId,
+    TransactionOrReceiptId,
+};
 use crate::views::{
-    ExecutionOutcomeWithIdView, LightClientBlockLiteView, QueryRequest, StateChangeWithCauseView,
-    StateChangesKindsView, StateChangesRequestView,
+    ExecutionOutcomeWithIdView, FinalExecutionOutcomeView, LightClientBlockLiteView, QueryRequest,
+    StateChangeWithCauseView, StateChangesKindsView, StateChangesRequestView,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -29,6 +33,30 @@ pub struct RpcStateChangesRequest {
     pub state_changes_request: StateChangesRequestView,
 }
 
+/// The chunk hash, or the block and shard of the chunk.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RpcChunkReference {
+    ChunkHash {
+        chunk_id: CryptoHash,
+    },
+    BlockShardId {
+        #[serde(flatten)]
+        block_reference: BlockReference,
+        shard_id: ShardId,
+    },
+}
+
+/// Named parameters of the `chunk` method.
+#[derive(Serialize, Deserialize)]
+pub struct RpcChunkRequest {
+    #[serde(flatten)]
+    pub chunk_reference: RpcChunkReference,
+    /// Whether to return the Merkle proofs of the transactions and receipts of the chunk.
+    #[serde(default)]
+    pub with_proofs: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RpcStateChangesResponse {
     pub block_hash: CryptoHash,
@@ -53,6 +81,46 @@ pub struct RpcBroadcastTxSyncResponse {
     pub is_routed: bool,
 }
 
+/// How far a transaction sent with `send_tx` has to get before the node responds, each level
+/// implies all the previous ones.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TxExecutionStatus {
+    /// The transaction is not validated, the node responds right away.
+    None,
+    /// The transaction is valid and was forwarded to the validators.
+    Broadcasted,
+    /// The transaction is included in a chunk.
+    Included,
+    /// The transaction and its receipts are executed, the blocks may still be reverted.
+    ExecutedOptimistic,
+    /// The transaction and its receipts are executed in final blocks.
+    Final,
+}
+
+impl Default for TxExecutionStatus {
+    fn default() -> Self {
+        TxExecutionStatus::ExecutedOptimistic
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcSendTransactionRequest {
+    /// Borsh serialized `SignedTransaction` encoded in base64.
+    pub signed_tx_base64: String,
+    #[serde(default)]
+    pub wait_until: TxExecutionStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcSendTransactionResponse {
+    pub transaction_hash: CryptoHash,
+    pub final_execution_status: TxExecutionStatus,
+    /// Present once the transaction is included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<FinalExecutionOutcomeView>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RpcLightClientExecutionProofRequest {
     #[serde(flatten)]
@@ -60,6 +128,11 @@ pub struct RpcLightClientExecutionProofRequest {
     pub light_client_head: CryptoHash,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RpcReceiptRequest {
+    pub receipt_id: CryptoHash,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RpcLightClientExecutionProofResponse {
     pub outcome_proof: ExecutionOutcomeWithIdView,
@@ -78,3 +151,75 @@ pub enum TransactionInfo {
 pub struct RpcValidatorsOrderedRequest {
     pub block_id: MaybeBlockId,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcEstimateFeeRequest {
+    pub signer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub method_name: String,
+    #[serde(default, rename = "args_base64", with = "base64_format")]
+    pub args: Vec<u8>,
+}
+
+/// Gas and deposit to attach to a function call and the fee to pay for it at the current gas
+/// price, if all the attached gas is burnt.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcEstimateFeeResponse {
+    pub gas: Gas,
+    #[serde(with = "u128_dec_format")]
+    pub deposit: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub gas_price: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub fee: Balance,
+    /// Whether gas and deposit are recommended by the contract rather than the defaults.
+    pub from_metadata: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcGasPriceHistoryRequest {
+    pub from_height: BlockHeight,
+    /// Defaults to the head of the chain.
+    #[serde(default)]
+    pub to_height: Option<BlockHeight>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcWatchedAccountChangesRequest {
+    pub account_id: AccountId,
+    /// Block height and index in the block of the first returned change.
+    #[serde(default)]
+    pub from_block_height: BlockHeight,
+    #[serde(default)]
+    pub from_index: u32,
+    pub limit: Option<u64>,
+}
+
+/// Events a WebSocket client can subscribe to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "subscription_type", rename_all = "snake_case")]
+pub enum RpcSubscribeRequest {
+    /// Every new head of the chain, including the heads of forks.
+    NewHeads,
+    /// Every final block, in the order of heights.
+    FinalBlocks,
+    /// The final outcome of the transaction, sent once after which the subscription ends.
+    Transaction { tx_hash: CryptoHash, sender_id: AccountId },
+    /// Changes of the accounts in final blocks.
+    AccountChanges { account_ids: Vec<AccountId> },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcSubscriptionNotification<T> {
+    pub subscription: u64,
+    pub result: T,
+}
+
+/// Sent instead of the notifications of the final blocks at the heights in the range, which the
+/// connection fell too far behind to receive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcSubscriptionGap {
+    pub subscription: u64,
+    pub from_height: BlockHeight,
+    pub to_height: BlockHeight,
+}
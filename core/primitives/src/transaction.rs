@@ -36,6 +36,14 @@ pub struct Transaction {
 }
 
 impl Transaction {
+    /// Idempotency key of the function calls of the transaction, if it has one.
+    pub fn idempotency_key(&self) -> Option<&[u8]> {
+        match self.actions.first() {
+            Some(Action::IdempotencyKey(action)) => Some(&action.key),
+            _ => None,
+        }
+    }
+
     /// Computes a hash of the transaction for signing
     pub fn get_hash(&self) -> CryptoHash {
         let bytes = self.try_to_vec().expect("Failed to deserialize");
@@ -56,6 +64,7 @@ pub enum Action {
     AddKey(AddKeyAction),
     DeleteKey(DeleteKeyAction),
     DeleteAccount(DeleteAccountAction),
+    IdempotencyKey(IdempotencyKeyAction),
 }
 
 impl Action {
@@ -198,6 +207,25 @@ impl From<DeleteAccountAction> for Action {
     }
 }
 
+/// Maximum length of the key in an `IdempotencyKeyAction`.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: u64 = 64;
+
+/// Key chosen by the signer, e.g. a relayer or a bridge, for the function calls of the
+/// transaction. Nodes index executed keys for the garbage collection period, so a retried
+/// submission can be detected even if the hash of the original transaction is lost. Has to be the
+/// first action of the transaction.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct IdempotencyKeyAction {
+    #[serde(with = "base64_format")]
+    pub key: Vec<u8>,
+}
+
+impl From<IdempotencyKeyAction> for Action {
+    fn from(idempotency_key_action: IdempotencyKeyAction) -> Self {
+        Self::IdempotencyKey(idempotency_key_action)
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Eq, Debug, Clone)]
 #[borsh_init(init)]
 pub struct SignedTransaction {
@@ -278,7 +306,7 @@ impl Default for ExecutionStatus {
 
 /// ExecutionOutcome for proof. Excludes logs.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Clone)]
-struct PartialExecutionOutcome {
+pub(crate) struct PartialExecutionOutcome {
     pub receipt_ids: Vec<CryptoHash>,
     pub gas_burnt: Gas,
     #[serde(with = "u128_dec_format")]
@@ -208,6 +208,8 @@ pub enum StateChangesRequest {
     AllAccessKeyChanges { account_ids: Vec<AccountId> },
     ContractCodeChanges { account_ids: Vec<AccountId> },
     DataChanges { account_ids: Vec<AccountId>, key_prefix: StoreKey },
+    /// Changes of the accounts, their access keys, contract code and data.
+    AllChanges { account_ids: Vec<AccountId> },
 }
 
 #[derive(Debug)]
@@ -388,6 +390,7 @@ impl StateRootNode {
     BorshSerialize,
     BorshDeserialize,
     Serialize,
+    Deserialize,
 )]
 #[as_ref(forward)]
 pub struct EpochId(pub CryptoHash);
@@ -438,6 +441,57 @@ pub struct BlockExtra {
     pub challenges_result: ChallengesResult,
 }
 
+/// Gas price and gas usage of a block, indexed for fee history queries.
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Clone, Eq)]
+pub struct BlockFeeInfo {
+    pub height: BlockHeight,
+    #[serde(with = "u128_dec_format")]
+    pub gas_price: Balance,
+    /// Gas used by the chunk of each shard included in the block, 0 if shard has no new chunk.
+    pub gas_used: Vec<Gas>,
+    /// Gas limit of the chunk of each shard included in the block, 0 if shard has no new chunk.
+    pub gas_limit: Vec<Gas>,
+}
+
+/// Transaction with an idempotency key and the block that executed it. Keys are indexed by the
+/// signer of the transaction.
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Eq)]
+pub struct IdempotencyKeyExecution {
+    pub transaction_hash: CryptoHash,
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+}
+
+/// Change affecting a watched account, indexed by the account, block height and the index of the
+/// change in the block.
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Eq)]
+pub struct WatchedAccountChange {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub index: u32,
+    pub kind: WatchedAccountChangeKind,
+}
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Eq)]
+pub enum WatchedAccountChangeKind {
+    /// Transaction signed by or sent to the account.
+    Transaction { hash: CryptoHash, signer_id: AccountId, receiver_id: AccountId, success: bool },
+    /// Receipt sent by or to the account.
+    Receipt {
+        receipt_id: CryptoHash,
+        predecessor_id: AccountId,
+        receiver_id: AccountId,
+        success: bool,
+    },
+    /// Balance of the account after it was changed in the block.
+    Balance {
+        #[serde(with = "u128_dec_format")]
+        amount: Balance,
+        #[serde(with = "u128_dec_format")]
+        locked: Balance,
+    },
+}
+
 /// Information after chunk was processed, used to produce or check next chunk.
 #[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Clone, Eq)]
 pub struct ChunkExtra {
@@ -517,6 +571,21 @@ impl From<Finality> for BlockReference {
     }
 }
 
+/// Epoch to look up, either by its ID or by a block in it.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EpochReference {
+    EpochId(EpochId),
+    BlockId(BlockId),
+    Latest,
+}
+
+impl From<MaybeBlockId> for EpochReference {
+    fn from(block_id: MaybeBlockId) -> Self {
+        block_id.map_or(Self::Latest, Self::BlockId)
+    }
+}
+
 #[derive(Default, BorshSerialize, BorshDeserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct ValidatorStats {
     pub produced: NumBlocks,
@@ -566,7 +635,7 @@ pub trait CompiledContractCache: Send + Sync {
 
 /// Provides information about current epoch validators.
 /// Used to break dependency between epoch manager and runtime.
-pub trait EpochInfoProvider {
+pub trait EpochInfoProvider: Send + Sync {
     /// Get current stake of a validator in the given epoch.
     /// If the account is not a validator, returns `None`.
     fn validator_stake(
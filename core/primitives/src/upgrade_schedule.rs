@@ -0,0 +1,102 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::version::{ProtocolVersion, PROTOCOL_VERSION};
+
+/// Environment variable that overrides the schedule from the config, e.g.
+/// `NEAR_PROTOCOL_UPGRADE_VOTING_SCHEDULE=2021-05-04T18:00:00Z`.
+pub const PROTOCOL_UPGRADE_VOTING_SCHEDULE_ENV: &str = "NEAR_PROTOCOL_UPGRADE_VOTING_SCHEDULE";
+
+/// Time after which the node starts to vote for its `PROTOCOL_VERSION`. Until then it keeps voting
+/// for the protocol version of the next epoch, so the upgrade of all validators to a new release
+/// can be coordinated instead of happening as soon as enough of them restart.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ProtocolUpgradeVotingSchedule {
+    timestamp: DateTime<Utc>,
+}
+
+impl ProtocolUpgradeVotingSchedule {
+    pub fn new(timestamp: DateTime<Utc>) -> Self {
+        Self { timestamp }
+    }
+
+    /// Reads the schedule from `NEAR_PROTOCOL_UPGRADE_VOTING_SCHEDULE`, if it is set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var(PROTOCOL_UPGRADE_VOTING_SCHEDULE_ENV) {
+            Ok(value) => value.parse().map(Some),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(err) => Err(format!("{}: {}", PROTOCOL_UPGRADE_VOTING_SCHEDULE_ENV, err)),
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn is_in_future(&self, now: DateTime<Utc>) -> bool {
+        now < self.timestamp
+    }
+}
+
+impl FromStr for ProtocolUpgradeVotingSchedule {
+    type Err = String;
+
+    /// Parses RFC 3339 timestamp, e.g. `2021-05-04T18:00:00Z`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DateTime::parse_from_rfc3339(s)
+            .map(|timestamp| Self::new(timestamp.with_timezone(&Utc)))
+            .map_err(|err| format!("Invalid protocol upgrade voting schedule {:?}: {}", s, err))
+    }
+}
+
+/// Protocol version to put into the header of a block produced at `now`.
+pub fn get_protocol_version(
+    next_epoch_protocol_version: ProtocolVersion,
+    schedule: Option<ProtocolUpgradeVotingSchedule>,
+    now: DateTime<Utc>,
+) -> ProtocolVersion {
+    match schedule {
+        Some(schedule)
+            if next_epoch_protocol_version < PROTOCOL_VERSION && schedule.is_in_future(now) =>
+        {
+            next_epoch_protocol_version
+        }
+        _ => PROTOCOL_VERSION,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule: ProtocolUpgradeVotingSchedule = "2021-05-04T18:00:00+02:00".parse().unwrap();
+        assert_eq!(schedule.timestamp().to_rfc3339(), "2021-05-04T16:00:00+00:00");
+        assert_eq!(
+            serde_json::from_str::<ProtocolUpgradeVotingSchedule>("\"2021-05-04T16:00:00Z\"")
+                .unwrap(),
+            schedule
+        );
+        assert!("2021-05-04".parse::<ProtocolUpgradeVotingSchedule>().is_err());
+    }
+
+    #[test]
+    fn test_get_protocol_version() {
+        let now = Utc::now();
+        let schedule = ProtocolUpgradeVotingSchedule::new(now + Duration::hours(1));
+        let old_version = PROTOCOL_VERSION - 1;
+        assert_eq!(get_protocol_version(old_version, None, now), PROTOCOL_VERSION);
+        assert_eq!(get_protocol_version(old_version, Some(schedule), now), old_version);
+        assert_eq!(
+            get_protocol_version(old_version, Some(schedule), now + Duration::hours(1)),
+            PROTOCOL_VERSION
+        );
+        assert_eq!(get_protocol_version(PROTOCOL_VERSION, Some(schedule), now), PROTOCOL_VERSION);
+    }
+}
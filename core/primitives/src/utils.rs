@@ -14,7 +14,7 @@ use lazy_static::lazy_static;
 use crate::hash::{hash, CryptoHash};
 use crate::receipt::Receipt;
 use crate::transaction::SignedTransaction;
-use crate::types::{AccountId, CompiledContractCache, NumSeats, NumShards, ShardId};
+use crate::types::{AccountId, BlockHeight, CompiledContractCache, NumSeats, NumShards, ShardId};
 use crate::version::{
     ProtocolVersion, CORRECT_RANDOM_VALUE_PROTOCOL_VERSION, CREATE_HASH_PROTOCOL_VERSION,
 };
@@ -49,6 +49,41 @@ pub fn get_block_shard_id_rev(
     Ok((block_hash, shard_id))
 }
 
+/// Key of the idempotency key of given signer. Account ids can't contain `,`, so it separates
+/// the account id from the key.
+pub fn get_idempotency_key_id(account_id: &AccountId, key: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(account_id.len() + 1 + key.len());
+    res.extend_from_slice(account_id.as_bytes());
+    res.push(b',');
+    res.extend_from_slice(key);
+    res
+}
+
+/// Prefix of the keys of the changes of a watched account in the block at the given height. The
+/// big-endian height comes first, so that all changes at a height are garbage collected together.
+/// Account ids can't contain `,`, so it separates the account id from the index of the change.
+pub fn get_watched_account_changes_prefix(
+    block_height: BlockHeight,
+    account_id: &AccountId,
+) -> Vec<u8> {
+    let mut res = Vec::with_capacity(8 + account_id.len() + 1 + 4);
+    res.extend_from_slice(&block_height.to_be_bytes());
+    res.extend_from_slice(account_id.as_bytes());
+    res.push(b',');
+    res
+}
+
+/// Key of the change of a watched account, ordered by height, account id and index.
+pub fn get_watched_account_change_id(
+    block_height: BlockHeight,
+    account_id: &AccountId,
+    index: u32,
+) -> Vec<u8> {
+    let mut res = get_watched_account_changes_prefix(block_height, account_id);
+    res.extend_from_slice(&index.to_be_bytes());
+    res
+}
+
 /// Creates a new Receipt ID from a given signed transaction and a block hash.
 /// This method is backward compatible, so it takes the current protocol version.
 pub fn create_receipt_id_from_transaction(
@@ -597,4 +632,26 @@ mod tests {
             create_hash_upgradable(CREATE_HASH_PROTOCOL_VERSION, &base, &other_extra_base, salt)
         );
     }
+
+    #[test]
+    fn test_watched_account_change_id_order() {
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        assert!(
+            get_watched_account_change_id(1, &alice, 5)
+                < get_watched_account_change_id(1, &alice, 6)
+        );
+        assert!(
+            get_watched_account_change_id(255, &bob, 0)
+                < get_watched_account_change_id(256, &alice, 0)
+        );
+        // All changes at a height share the prefix of the height.
+        assert!(get_watched_account_change_id(7, &alice, 3).starts_with(&7u64.to_be_bytes()));
+        // Changes of other accounts don't share the prefix of the account.
+        let prefix = get_watched_account_changes_prefix(0, &alice);
+        assert!(get_watched_account_change_id(0, &alice, 0).starts_with(&prefix));
+        assert!(
+            !get_watched_account_change_id(0, &"alice.near".to_string(), 0).starts_with(&prefix)
+        );
+    }
 }
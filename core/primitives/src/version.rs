@@ -1,6 +1,3 @@
-use std::collections::HashMap;
-
-use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
 use crate::types::Balance;
@@ -16,7 +13,7 @@ pub struct Version {
 pub type DbVersion = u32;
 
 /// Current version of the database.
-pub const DB_VERSION: DbVersion = 16;
+pub const DB_VERSION: DbVersion = 21;
 
 /// Protocol version type.
 pub type ProtocolVersion = u32;
@@ -52,6 +49,9 @@ pub const SHARD_CHUNK_HEADER_UPGRADE_VERSION: ProtocolVersion = 41;
 /// Fix the storage usage of the delete key action.
 pub const DELETE_KEY_STORAGE_USAGE_PROTOCOL_VERSION: ProtocolVersion = 40;
 
+/// Protocol versions from `lower` inclusive to `upper` exclusive, or without upper bound if
+/// `upper` is `None`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ProtocolVersionRange {
     lower: ProtocolVersion,
     upper: Option<ProtocolVersion>,
@@ -65,69 +65,204 @@ impl ProtocolVersionRange {
     pub fn contains(&self, version: ProtocolVersion) -> bool {
         self.lower <= version && self.upper.map_or(true, |upper| version < upper)
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.upper.map_or(false, |upper| upper <= self.lower)
+    }
+
+    /// Versions contained in both ranges.
+    pub fn intersect(&self, other: &ProtocolVersionRange) -> ProtocolVersionRange {
+        let upper = match (self.upper, other.upper) {
+            (Some(upper), Some(other_upper)) => Some(std::cmp::min(upper, other_upper)),
+            (upper, None) | (None, upper) => upper,
+        };
+        Self::new(std::cmp::max(self.lower, other.lower), upper)
+    }
+
+    /// Whether no version is contained in both ranges, e.g. for mutually exclusive features.
+    pub fn is_disjoint(&self, other: &ProtocolVersionRange) -> bool {
+        self.intersect(other).is_empty()
+    }
+
+    /// Versions in the range in ascending order. Never ends if the range has no upper bound.
+    pub fn iter(&self) -> impl Iterator<Item = ProtocolVersion> {
+        let upper = self.upper;
+        (self.lower..).take_while(move |version| upper.map_or(true, |upper| *version < upper))
+    }
 }
 
-/// New Protocol features should go here. Features are guarded by their corresponding feature flag.
-/// For example, if we have `ProtocolFeature::EVM` and a corresponding feature flag `evm`, it will look
-/// like
-/// ```
-/// #[cfg(feature = "evm")]
-/// EVM
-/// ```
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
-pub enum ProtocolFeature {
-    #[cfg(feature = "protocol_feature_forward_chunk_parts")]
-    ForwardChunkParts,
+/// Kind of interface that can be deprecated.
+#[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DeprecatedSurface {
+    RpcMethod,
+    /// Field of `config.json`, nested fields are separated by dots.
+    ConfigField,
+    ProtocolFeature,
 }
 
-/// Current latest stable version of the protocol.
-#[cfg(not(feature = "nightly_protocol"))]
-pub const PROTOCOL_VERSION: ProtocolVersion = 41;
+impl DeprecatedSurface {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeprecatedSurface::RpcMethod => "rpc_method",
+            DeprecatedSurface::ConfigField => "config_field",
+            DeprecatedSurface::ProtocolFeature => "protocol_feature",
+        }
+    }
+}
 
-/// Current latest nightly version of the protocol.
-#[cfg(feature = "nightly_protocol")]
-pub const PROTOCOL_VERSION: ProtocolVersion = 42;
+#[derive(Debug)]
+pub struct Deprecation {
+    pub surface: DeprecatedSurface,
+    pub name: &'static str,
+    /// First protocol version that no longer supports it.
+    pub removed_in_version: ProtocolVersion,
+    /// What to use instead.
+    pub replacement: &'static str,
+}
 
-lazy_static! {
-    static ref STABLE_PROTOCOL_FEATURES_TO_VERSION_MAPPING: HashMap<ProtocolFeature, ProtocolVersion> = vec![
-        /* add mapping here */
-    ].into_iter().collect();
+/// Interfaces that still work but are going to be removed. Using any of them is reported in the
+/// logs, the `status` RPC and metrics, so that operators and SDKs have a release to migrate.
+pub static DEPRECATIONS: &[Deprecation] = &[
+    Deprecation {
+        surface: DeprecatedSurface::RpcMethod,
+        name: "EXPERIMENTAL_light_client_proof",
+        removed_in_version: 45,
+        replacement: "light_client_proof",
+    },
+    Deprecation {
+        surface: DeprecatedSurface::RpcMethod,
+        name: "query_by_path",
+        removed_in_version: 45,
+        replacement: "query with named parameters",
+    },
+    Deprecation {
+        surface: DeprecatedSurface::ConfigField,
+        name: "consensus.reduce_wait_for_missing_block",
+        removed_in_version: 45,
+        replacement: "nothing, it has no effect",
+    },
+];
+
+pub fn find_deprecation(surface: DeprecatedSurface, name: &str) -> Option<&'static Deprecation> {
+    DEPRECATIONS
+        .iter()
+        .find(|deprecation| deprecation.surface == surface && deprecation.name == name)
 }
 
+/// Current latest stable version of the protocol.
+pub const STABLE_PROTOCOL_VERSION: ProtocolVersion = 41;
+
+/// Current latest nightly version of the protocol.
+pub const NIGHTLY_PROTOCOL_VERSION: ProtocolVersion = 42;
+
 #[cfg(not(feature = "nightly_protocol"))]
-lazy_static! {
-    /// Map of feature to the minimal protocol version that introduces the feature. We can determine
-    /// whether to apply the new feature by comparing the current protocol version of the network to
-    /// `PROTOCOL_FEATURES_TO_VERSION_MAPPING[feature]`.
-    pub static ref PROTOCOL_FEATURES_TO_VERSION_MAPPING: HashMap<ProtocolFeature, ProtocolVersion> =
-        STABLE_PROTOCOL_FEATURES_TO_VERSION_MAPPING.clone();
-}
+pub const PROTOCOL_VERSION: ProtocolVersion = STABLE_PROTOCOL_VERSION;
 
 #[cfg(feature = "nightly_protocol")]
-lazy_static! {
-    pub static ref PROTOCOL_FEATURES_TO_VERSION_MAPPING: HashMap<ProtocolFeature, ProtocolVersion> = {
-        let nightly_protocol_features_to_version_mapping: HashMap<
-            ProtocolFeature,
-            ProtocolVersion,
-        > = vec![(ProtocolFeature::ForwardChunkParts, 42)].into_iter().collect();
-        for (stable_protocol_feature, stable_protocol_version) in
-            STABLE_PROTOCOL_FEATURES_TO_VERSION_MAPPING.iter()
-        {
-            assert!(
-                PROTOCOL_FEATURES_TO_VERSION_MAPPING[&stable_protocol_feature]
-                    >= *stable_protocol_version
-            );
+pub const PROTOCOL_VERSION: ProtocolVersion = NIGHTLY_PROTOCOL_VERSION;
+
+/// Declares `ProtocolFeature` together with the protocol version that enables each feature, and
+/// checks at compile time that stable features are enabled by `STABLE_PROTOCOL_VERSION` and
+/// nightly ones are enabled only by nightly versions.
+macro_rules! protocol_features {
+    (
+        stable {
+            $($(#[doc = $stable_doc:literal])* $stable:ident => $stable_version:expr,)*
+        }
+        nightly {
+            $(
+                $(#[doc = $nightly_doc:literal])*
+                #[cfg(feature = $nightly_feature:literal)]
+                $nightly:ident => $nightly_version:expr,
+            )*
         }
-        nightly_protocol_features_to_version_mapping
+    ) => {
+        #[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum ProtocolFeature {
+            $($(#[doc = $stable_doc])* $stable,)*
+            $($(#[doc = $nightly_doc])* #[cfg(feature = $nightly_feature)] $nightly,)*
+        }
+
+        impl ProtocolFeature {
+            /// All features compiled into the binary, stable ones first.
+            pub fn all() -> Vec<ProtocolFeature> {
+                #[allow(unused_mut)]
+                let mut features = vec![];
+                $(features.push(ProtocolFeature::$stable);)*
+                $(#[cfg(feature = $nightly_feature)] features.push(ProtocolFeature::$nightly);)*
+                features
+            }
+
+            /// The first protocol version that enables the feature.
+            pub fn protocol_version(self) -> ProtocolVersion {
+                match self {
+                    $(ProtocolFeature::$stable => $stable_version,)*
+                    $(#[cfg(feature = $nightly_feature)] ProtocolFeature::$nightly => $nightly_version,)*
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(ProtocolFeature::$stable => stringify!($stable),)*
+                    $(#[cfg(feature = $nightly_feature)] ProtocolFeature::$nightly => stringify!($nightly),)*
+                }
+            }
+
+            /// Cargo feature that compiles the feature in, `None` for stable features.
+            pub fn nightly_feature(self) -> Option<&'static str> {
+                match self {
+                    $(ProtocolFeature::$stable => None,)*
+                    $(#[cfg(feature = $nightly_feature)] ProtocolFeature::$nightly => Some($nightly_feature),)*
+                }
+            }
+        }
+
+        $(const _: [(); 0] = [(); ($stable_version > STABLE_PROTOCOL_VERSION) as usize];)*
+        $(
+            const _: [(); 0] = [(); ($nightly_version <= STABLE_PROTOCOL_VERSION
+                || $nightly_version > NIGHTLY_PROTOCOL_VERSION) as usize];
+        )*
     };
 }
 
+// New protocol features should go here. Nightly features are guarded by their corresponding
+// feature flag, for example `ProtocolFeature::EVM` with a feature flag `evm` is declared as
+// ```ignore
+// #[cfg(feature = "evm")]
+// EVM => 43,
+// ```
+// in the `nightly` section. Once stabilized, the feature flag is removed and the feature is moved
+// to the `stable` section.
+protocol_features! {
+    stable {}
+    nightly {
+        #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+        ForwardChunkParts => 42,
+        /// Experimental hybrid ED25519+Dilithium signing of block approvals, to measure its overhead.
+        #[cfg(feature = "protocol_feature_pq_crypto")]
+        HybridApprovalSignature => 42,
+        /// `IdempotencyKey` action for the function calls of a transaction.
+        #[cfg(feature = "protocol_feature_idempotency_key")]
+        IdempotencyKey => 42,
+        /// Validators with stake below the seat price fill the chunk-only producer seats, they
+        /// produce chunks but neither produce nor approve blocks.
+        #[cfg(feature = "protocol_feature_chunk_only_producers")]
+        ChunkOnlyProducers => 42,
+    }
+}
+
+/// Checks whether the nightly protocol feature is enabled in the protocol version. Code that uses
+/// items behind the feature flag has to be passed as a block, so it is only compiled with the flag:
+/// ```ignore
+/// if checked_feature!("evm", EVM, protocol_version) { ... }
+/// checked_feature!("evm", EVM, protocol_version, { ... });
+/// let result = checked_feature!("evm", EVM, protocol_version, { new_value } else { old_value });
+/// ```
 #[macro_export]
 macro_rules! checked_feature {
     ($feature_name:tt, $feature:ident, $current_protocol_version:expr) => {{
         #[cfg(feature = $feature_name)]
-        let is_feature_enabled = near_primitives::version::PROTOCOL_FEATURES_TO_VERSION_MAPPING
-            [&near_primitives::version::ProtocolFeature::$feature]
+        let is_feature_enabled = $crate::version::ProtocolFeature::$feature.protocol_version()
             <= $current_protocol_version;
         #[cfg(not(feature = $feature_name))]
         let is_feature_enabled = {
@@ -150,4 +285,108 @@ macro_rules! checked_feature {
         #[cfg(not(feature = $feature_name))]
         let _ = $current_protocol_version;
     }};
+
+    ($feature_name:tt, $feature:ident, $current_protocol_version:expr, $feature_block:block else $non_feature_block:block) => {{
+        #[cfg(feature = $feature_name)]
+        {
+            if checked_feature!($feature_name, $feature, $current_protocol_version) {
+                $feature_block
+            } else {
+                $non_feature_block
+            }
+        }
+        #[cfg(not(feature = $feature_name))]
+        {
+            // Workaround unused variable warning
+            let _ = $current_protocol_version;
+
+            $non_feature_block
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_version_range() {
+        let range = ProtocolVersionRange::new(38, Some(41));
+        assert!(range.contains(38) && range.contains(40) && !range.contains(41));
+        assert_eq!(range.iter().collect::<Vec<_>>(), vec![38, 39, 40]);
+        assert_eq!(
+            ProtocolVersionRange::new(40, None).iter().take(2).collect::<Vec<_>>(),
+            vec![40, 41]
+        );
+
+        assert!(!range.is_empty());
+        assert!(ProtocolVersionRange::new(41, Some(41)).is_empty());
+        assert!(!ProtocolVersionRange::new(41, None).is_empty());
+
+        assert_eq!(
+            range.intersect(&ProtocolVersionRange::new(40, None)),
+            ProtocolVersionRange::new(40, Some(41))
+        );
+        assert!(range.is_disjoint(&ProtocolVersionRange::new(41, None)));
+        assert!(!range.is_disjoint(&ProtocolVersionRange::new(0, Some(39))));
+        assert_eq!(range.intersect(&ProtocolVersionRange::new(41, None)).iter().count(), 0);
+
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, r#"{"lower":38,"upper":41}"#);
+        assert_eq!(serde_json::from_str::<ProtocolVersionRange>(&json).unwrap(), range);
+    }
+
+    #[test]
+    fn test_find_deprecation() {
+        let deprecation = find_deprecation(DeprecatedSurface::RpcMethod, "query_by_path").unwrap();
+        assert_eq!(deprecation.replacement, "query with named parameters");
+        assert!(find_deprecation(DeprecatedSurface::ConfigField, "query_by_path").is_none());
+        assert!(find_deprecation(DeprecatedSurface::RpcMethod, "query").is_none());
+        assert!(find_deprecation(
+            DeprecatedSurface::ConfigField,
+            "consensus.reduce_wait_for_missing_block"
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn test_checked_feature_else() {
+        let value = checked_feature!(
+            "protocol_feature_forward_chunk_parts",
+            ForwardChunkParts,
+            PROTOCOL_VERSION,
+            { "new" } else { "old" }
+        );
+        let expected =
+            if cfg!(feature = "protocol_feature_forward_chunk_parts") { "new" } else { "old" };
+        assert_eq!(value, expected);
+        let value = checked_feature!(
+            "protocol_feature_forward_chunk_parts",
+            ForwardChunkParts,
+            STABLE_PROTOCOL_VERSION,
+            { "new" } else { "old" }
+        );
+        assert_eq!(value, "old");
+    }
+
+    #[test]
+    fn test_all_protocol_features() {
+        let features = ProtocolFeature::all();
+        for feature in features.iter() {
+            assert!(feature.protocol_version() <= NIGHTLY_PROTOCOL_VERSION);
+            assert_eq!(
+                feature.nightly_feature().is_some(),
+                feature.protocol_version() > STABLE_PROTOCOL_VERSION
+            );
+        }
+        #[cfg(feature = "protocol_feature_forward_chunk_parts")]
+        {
+            assert!(features.contains(&ProtocolFeature::ForwardChunkParts));
+            assert_eq!(ProtocolFeature::ForwardChunkParts.name(), "ForwardChunkParts");
+            assert_eq!(
+                ProtocolFeature::ForwardChunkParts.nightly_feature(),
+                Some("protocol_feature_forward_chunk_parts")
+            );
+        }
+    }
 }
@@ -3,6 +3,7 @@
 //! These types should only change when we cannot avoid this. Thus, when the counterpart internal
 //! type gets changed, the view should preserve the old shape and only re-map the necessary bits
 //! from the source structure in the relevant `From<SourceStruct>` impl.
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 
@@ -19,6 +20,7 @@ use crate::block_header::{
     BlockHeaderV2,
 };
 use crate::challenge::{Challenge, ChallengesResult};
+use crate::contract::MethodMetadata;
 use crate::errors::TxExecutionError;
 use crate::hash::{hash, CryptoHash};
 use crate::logging;
@@ -34,15 +36,19 @@ use crate::sharding::{
 use crate::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, ExecutionOutcome, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
-    FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+    FunctionCallAction, IdempotencyKeyAction, SignedTransaction, StakeAction, TransferAction,
 };
 use crate::types::{
-    AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
-    EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
-    StateChangeValue, StateChangeWithCause, StateChangesRequest, StateRoot, StorageUsage, StoreKey,
-    StoreValue, ValidatorKickoutReason, ValidatorStake,
+    AccountId, AccountWithPublicKey, Balance, BlockFeeInfo, BlockHeight, CompiledContractCache,
+    EpochHeight, EpochId, FunctionArgs, Gas, IdempotencyKeyExecution, Nonce, NumBlocks, ShardId,
+    StateChangeCause, StateChangeKind, StateChangeValue, StateChangeWithCause, StateChangesRequest,
+    StateRoot, StorageUsage, StoreKey, StoreValue, ValidatorKickoutReason, ValidatorStake,
+    WatchedAccountChange,
+};
+use crate::utils::system_account;
+use crate::version::{
+    DeprecatedSurface, ProtocolFeature, ProtocolVersion, Version, PROTOCOL_VERSION,
 };
-use crate::version::{ProtocolVersion, Version};
 use std::sync::Arc;
 
 /// A view of the account
@@ -183,7 +189,33 @@ pub struct StateItem {
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ViewStateResult {
     pub values: Vec<StateItem>,
+    /// Base64-encoded trie nodes visited while reading the values, if requested. They are enough
+    /// to check the values against the state root of the block.
     pub proof: TrieProofPath,
+    /// Key of the last returned value if there are more values, to be passed as the cursor to get
+    /// the next page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct MethodMetadataView {
+    pub method_name: String,
+    pub gas: Gas,
+    #[serde(with = "u128_dec_format")]
+    pub deposit: Balance,
+}
+
+impl From<MethodMetadata> for MethodMetadataView {
+    fn from(metadata: MethodMetadata) -> Self {
+        Self { method_name: metadata.method_name, gas: metadata.gas, deposit: metadata.deposit }
+    }
+}
+
+/// Recommended gas and deposit of the contract methods that have them.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ContractMetadataView {
+    pub methods: Vec<MethodMetadataView>,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -207,14 +239,26 @@ pub struct AccessKeyInfoView {
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct AccessKeyList {
     pub keys: Vec<AccessKeyInfoView>,
+    /// Set when the list was cut at the requested limit, pass it as `cursor` to get the next page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<PublicKey>,
 }
 
 impl std::iter::FromIterator<AccessKeyInfoView> for AccessKeyList {
     fn from_iter<I: IntoIterator<Item = AccessKeyInfoView>>(iter: I) -> Self {
-        Self { keys: iter.into_iter().collect() }
+        Self { keys: iter.into_iter().collect(), next_cursor: None }
     }
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AccountList {
+    /// Sorted account ids.
+    pub accounts: Vec<AccountId>,
+    /// Set when there are more accounts, pass it as `cursor` to get the next page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<AccountId>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(untagged)]
 pub enum QueryResponseKind {
@@ -224,8 +268,17 @@ pub enum QueryResponseKind {
     Error(QueryError),
     AccessKey(AccessKeyView),
     AccessKeyList(AccessKeyList),
+    ContractMetadata(ContractMetadataView),
+    AccountList(AccountList),
 }
 
+/// Maximum number of accounts returned by a single `ViewAccountsByPrefix` query.
+pub const MAX_ACCOUNTS_PER_QUERY: u32 = 1000;
+/// Maximum number of access keys returned by a single `ViewAccessKeyListPaginated` query.
+pub const MAX_ACCESS_KEYS_PER_QUERY: u32 = 1000;
+/// Maximum number of values returned by a single `ViewStatePaginated` query.
+pub const MAX_STATE_VALUES_PER_QUERY: u32 = 1000;
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(tag = "request_type", rename_all = "snake_case")]
 pub enum QueryRequest {
@@ -250,6 +303,49 @@ pub enum QueryRequest {
         #[serde(rename = "args_base64", with = "base64_format")]
         args: FunctionArgs,
     },
+    ViewContractMetadata {
+        account_id: AccountId,
+    },
+    /// Lists the accounts starting with `prefix` across all the shards.
+    ViewAccountsByPrefix {
+        prefix: String,
+        /// Only accounts after this one are returned.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<AccountId>,
+        /// Maximum number of accounts to return, capped at `MAX_ACCOUNTS_PER_QUERY`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+    },
+    /// Page of the access keys of the account, ordered by public key.
+    ViewAccessKeyListPaginated {
+        account_id: AccountId,
+        /// Only keys after this one are returned.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<PublicKey>,
+        /// Maximum number of keys to return, capped at `MAX_ACCESS_KEYS_PER_QUERY`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+    },
+    /// Page of the contract state of the account with keys starting with `prefix`.
+    ViewStatePaginated {
+        account_id: AccountId,
+        #[serde(rename = "prefix_base64", with = "base64_format")]
+        prefix: StoreKey,
+        /// Only keys after this one are returned.
+        #[serde(
+            default,
+            rename = "cursor_base64",
+            skip_serializing_if = "Option::is_none",
+            with = "option_base64_format"
+        )]
+        cursor: Option<Vec<u8>>,
+        /// Maximum number of values to return, capped at `MAX_STATE_VALUES_PER_QUERY`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+        /// Whether to return the trie nodes proving the returned values, see `ViewStateResult`.
+        #[serde(default)]
+        include_proof: bool,
+    },
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -295,6 +391,132 @@ pub struct StatusResponse {
     pub sync_info: StatusSyncInfo,
     /// Validator id of the node
     pub validator_account_id: Option<AccountId>,
+    /// Deprecated interfaces used since the node started.
+    #[serde(default)]
+    pub deprecated_usage: Vec<DeprecatedUsageView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeprecatedUsageView {
+    pub surface: DeprecatedSurface,
+    pub name: String,
+    pub removed_in_version: ProtocolVersion,
+    pub replacement: String,
+    /// Number of times it was used.
+    pub count: u64,
+}
+
+/// Compact description of the transactions a node can process itself, for load balancing on the
+/// client side.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoutingInfoView {
+    pub chain_id: String,
+    pub num_shards: ShardId,
+    /// Shards that the node tracks at the head. Transactions are validated against the state of
+    /// the shard of the signer, for other shards they are forwarded to the chunk producers.
+    pub tracked_shards: Vec<ShardId>,
+    pub latest_block_hash: CryptoHash,
+    pub latest_block_height: BlockHeight,
+    pub latest_block_time: DateTime<Utc>,
+    pub syncing: bool,
+    /// Stage of the sync, e.g. `NoSync` or `HeaderSync`.
+    pub sync_status: String,
+}
+
+/// Receipt with the chunk it was executed in, see `EXPERIMENTAL_receipt`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptWithOutcomeView {
+    pub receipt: ReceiptView,
+    /// Shard of the receiver, where the receipt is executed.
+    pub shard_id: ShardId,
+    /// Block and chunk the receipt was executed in, not set until it is executed.
+    pub block_hash: Option<CryptoHash>,
+    pub block_height: Option<BlockHeight>,
+    pub chunk_hash: Option<CryptoHash>,
+    pub outcome: Option<ExecutionOutcomeWithIdView>,
+}
+
+/// Block waiting in one of the pools of the chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DebugBlockView {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    pub prev_hash: CryptoHash,
+    /// Time since the block was added to the pool, in milliseconds.
+    pub age_ms: u64,
+}
+
+/// Internal state of the client, to diagnose nodes that don't make progress.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DebugStatusView {
+    /// Stage of the sync, e.g. `NoSync` or `HeaderSync`.
+    pub sync_status: String,
+    /// Heights of the header or body sync, if one is in progress.
+    pub sync_current_height: Option<BlockHeight>,
+    pub sync_highest_height: Option<BlockHeight>,
+    /// Stage of the state sync of each shard, if one is in progress.
+    pub state_sync_shards: Vec<(ShardId, String)>,
+    pub head_hash: CryptoHash,
+    pub head_height: BlockHeight,
+    /// Blocks whose previous block is not known yet.
+    pub orphans: Vec<DebugBlockView>,
+    /// Number of orphans dropped because the pool was full.
+    pub orphans_evicted: u64,
+    /// Blocks waiting for the chunks of the tracked shards.
+    pub blocks_with_missing_chunks: Vec<DebugBlockView>,
+    /// Number of chunks requested from the peers and not received yet.
+    pub num_requested_chunks: u64,
+    /// Blocks for which the state of the next epoch is being caught up.
+    pub catchup_blocks: Vec<CryptoHash>,
+    /// Number of blocks for which approvals were received before the block itself.
+    pub num_pending_approvals: u64,
+    pub num_challenges: u64,
+}
+
+/// Peer known to the node, connected or not.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KnownPeerView {
+    pub peer_id: String,
+    pub addr: Option<String>,
+    pub account_id: Option<AccountId>,
+    /// One of `Unknown`, `NotConnected`, `Connected` or `Banned`.
+    pub status: String,
+    /// Timestamps in nanoseconds.
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+/// Misbehavior score of a peer, for debugging.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerReputationView {
+    pub peer_id: String,
+    /// Banned once it reaches the ban threshold, goes down over time.
+    pub score: u32,
+    /// Number of reported misbehaviors by kind.
+    pub misbehaviors: BTreeMap<String, u64>,
+    pub banned: bool,
+}
+
+/// Rules which peers may connect to the node. Rules are node keys, IP addresses or networks in
+/// CIDR notation.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerAccessView {
+    /// Whether inbound connections are only accepted from peers that match an allowed rule.
+    pub allow_only: bool,
+    pub allowed: Vec<String>,
+    pub denied: Vec<String>,
+}
+
+/// Change of the rules which peers may connect, see `PeerAccessView`. Rules are removed before the
+/// new ones are added.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PeerAccessChangeView {
+    pub allow_only: Option<bool>,
+    pub allow: Vec<String>,
+    pub remove_allowed: Vec<String>,
+    pub deny: Vec<String>,
+    pub remove_denied: Vec<String>,
 }
 
 impl TryFrom<QueryResponse> for AccountView {
@@ -319,6 +541,28 @@ impl TryFrom<QueryResponse> for ViewStateResult {
     }
 }
 
+impl TryFrom<QueryResponse> for ContractMetadataView {
+    type Error = String;
+
+    fn try_from(query_response: QueryResponse) -> Result<Self, Self::Error> {
+        match query_response.kind {
+            QueryResponseKind::ContractMetadata(metadata) => Ok(metadata),
+            _ => Err("Invalid type of response".into()),
+        }
+    }
+}
+
+impl TryFrom<QueryResponse> for AccountList {
+    type Error = String;
+
+    fn try_from(query_response: QueryResponse) -> Result<Self, Self::Error> {
+        match query_response.kind {
+            QueryResponseKind::AccountList(accounts) => Ok(accounts),
+            _ => Err("Invalid type of response".into()),
+        }
+    }
+}
+
 impl TryFrom<QueryResponse> for AccessKeyView {
     type Error = String;
 
@@ -341,7 +585,7 @@ impl From<Challenge> for ChallengeView {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct BlockHeaderView {
     pub height: BlockHeight,
     pub epoch_id: CryptoHash,
@@ -541,7 +785,7 @@ impl From<BlockHeader> for BlockHeaderInnerLiteView {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct ChunkHeaderView {
     pub chunk_hash: CryptoHash,
     pub prev_block_hash: CryptoHash,
@@ -624,7 +868,7 @@ impl From<ChunkHeaderView> for ShardChunkHeader {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 pub struct BlockView {
     pub author: AccountId,
     pub header: BlockHeaderView,
@@ -641,7 +885,7 @@ impl BlockView {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 pub struct ChunkView {
     pub author: AccountId,
     pub header: ChunkHeaderView,
@@ -649,6 +893,26 @@ pub struct ChunkView {
     pub receipts: Vec<ReceiptView>,
 }
 
+/// Merkle proofs of the contents of a chunk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkProofsView {
+    /// Proof of each transaction against `tx_root` of the chunk header.
+    pub transactions: Vec<MerklePath>,
+    /// Proof of the receipts sent to each shard against `outgoing_receipts_root` of the chunk
+    /// header, by receiver shard. The leaves are the hashes of the receipts sent to a shard.
+    pub outgoing_receipts: Vec<MerklePath>,
+    /// Proof of the chunk header against `chunk_headers_root` of the block, only known if the
+    /// chunk was requested by block.
+    pub chunk_header: Option<MerklePath>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkWithProofsView {
+    #[serde(flatten)]
+    pub chunk: ChunkView,
+    pub proofs: ChunkProofsView,
+}
+
 impl ChunkView {
     pub fn from_author_chunk(author: AccountId, chunk: ShardChunk) -> Self {
         match chunk {
@@ -700,6 +964,9 @@ pub enum ActionView {
     DeleteAccount {
         beneficiary_id: AccountId,
     },
+    IdempotencyKey {
+        key: String,
+    },
 }
 
 impl From<Action> for ActionView {
@@ -727,6 +994,9 @@ impl From<Action> for ActionView {
             Action::DeleteAccount(action) => {
                 ActionView::DeleteAccount { beneficiary_id: action.beneficiary_id }
             }
+            Action::IdempotencyKey(action) => {
+                ActionView::IdempotencyKey { key: to_base64(&action.key) }
+            }
         }
     }
 }
@@ -761,6 +1031,9 @@ impl TryFrom<ActionView> for Action {
             ActionView::DeleteAccount { beneficiary_id } => {
                 Action::DeleteAccount(DeleteAccountAction { beneficiary_id })
             }
+            ActionView::IdempotencyKey { key } => {
+                Action::IdempotencyKey(IdempotencyKeyAction { key: from_base64(&key)? })
+            }
         })
     }
 }
@@ -972,6 +1245,65 @@ pub struct FinalExecutionOutcomeWithReceiptView {
     pub final_outcome: FinalExecutionOutcomeView,
     /// Receipts generated from the transaction
     pub receipts: Vec<ReceiptView>,
+    /// Gas and tokens burnt by the transaction and the receipts, and the refunds.
+    pub cost: TransactionCostView,
+}
+
+impl FinalExecutionOutcomeWithReceiptView {
+    pub fn new(final_outcome: FinalExecutionOutcomeView, receipts: Vec<ReceiptView>) -> Self {
+        let cost = TransactionCostView::new(&final_outcome, &receipts);
+        Self { final_outcome, receipts, cost }
+    }
+}
+
+/// Refund of unused gas or of the deposit of a failed receipt.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct RefundView {
+    pub receipt_id: CryptoHash,
+    pub receiver_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct TransactionCostView {
+    /// Gas burnt by the transaction and all the receipts.
+    pub gas_burnt: Gas,
+    #[serde(with = "u128_dec_format")]
+    pub tokens_burnt: Balance,
+    /// Refund receipts, which are sent by the system account.
+    pub refunds: Vec<RefundView>,
+    #[serde(with = "u128_dec_format")]
+    pub total_refund: Balance,
+}
+
+impl TransactionCostView {
+    pub fn new(final_outcome: &FinalExecutionOutcomeView, receipts: &[ReceiptView]) -> Self {
+        let outcomes = std::iter::once(&final_outcome.transaction_outcome)
+            .chain(final_outcome.receipts_outcome.iter());
+        let gas_burnt = outcomes.clone().map(|outcome| outcome.outcome.gas_burnt).sum();
+        let tokens_burnt = outcomes.map(|outcome| outcome.outcome.tokens_burnt).sum();
+        let refunds: Vec<_> = receipts
+            .iter()
+            .filter(|receipt| receipt.predecessor_id == system_account())
+            .filter_map(|receipt| match &receipt.receipt {
+                ReceiptEnumView::Action { actions, .. } => Some(RefundView {
+                    receipt_id: receipt.receipt_id,
+                    receiver_id: receipt.receiver_id.clone(),
+                    amount: actions
+                        .iter()
+                        .map(|action| match action {
+                            ActionView::Transfer { deposit } => *deposit,
+                            _ => 0,
+                        })
+                        .sum(),
+                }),
+                ReceiptEnumView::Data { .. } => None,
+            })
+            .collect();
+        let total_refund = refunds.iter().map(|refund| refund.amount).sum();
+        Self { gas_burnt, tokens_burnt, refunds, total_refund }
+    }
 }
 
 impl From<FinalExecutionOutcomeWithReceiptView> for FinalExecutionOutcomeView {
@@ -1123,6 +1455,9 @@ pub struct EpochValidatorInfo {
     pub current_proposals: Vec<ValidatorStakeView>,
     /// Kickout in the previous epoch
     pub prev_epoch_kickout: Vec<ValidatorKickoutView>,
+    /// Kickout for the performance in the current epoch, known once the epoch is finished
+    #[serde(default)]
+    pub current_epoch_kickout: Option<Vec<ValidatorKickoutView>>,
     /// Epoch start height
     pub epoch_start_height: BlockHeight,
 }
@@ -1143,6 +1478,10 @@ pub struct CurrentEpochValidatorInfo {
     pub shards: Vec<ShardId>,
     pub num_produced_blocks: NumBlocks,
     pub num_expected_blocks: NumBlocks,
+    #[serde(default)]
+    pub num_produced_chunks: NumBlocks,
+    #[serde(default)]
+    pub num_expected_chunks: NumBlocks,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -1187,6 +1526,94 @@ pub struct GasPriceView {
     pub gas_price: Balance,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockFeeView {
+    pub block_hash: CryptoHash,
+    pub height: BlockHeight,
+    #[serde(with = "u128_dec_format")]
+    pub gas_price: Balance,
+    /// Gas used by the new chunk of each shard, 0 if the shard has no new chunk in the block.
+    pub gas_used: Vec<Gas>,
+    /// Ratio of gas used to gas limit of the new chunk of each shard.
+    pub chunk_fullness: Vec<f64>,
+}
+
+impl BlockFeeView {
+    pub fn new(block_hash: CryptoHash, fee_info: &BlockFeeInfo) -> Self {
+        let chunk_fullness = fee_info
+            .gas_used
+            .iter()
+            .zip(fee_info.gas_limit.iter())
+            // Gas used is 0 for shards without a new chunk, same as gas limit.
+            .map(|(&gas_used, &gas_limit)| gas_used as f64 / std::cmp::max(gas_limit, 1) as f64)
+            .collect();
+        Self {
+            block_hash,
+            height: fee_info.height,
+            gas_price: fee_info.gas_price,
+            gas_used: fee_info.gas_used.clone(),
+            chunk_fullness,
+        }
+    }
+}
+
+/// Gas prices and gas usage of consecutive blocks, from the oldest to the newest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeHistoryView {
+    pub blocks: Vec<BlockFeeView>,
+}
+
+/// Transactions with the idempotency key of the account that were executed on the canonical chain
+/// and are not garbage collected yet, from the oldest to the newest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IdempotencyKeyView {
+    pub account_id: AccountId,
+    pub key: String,
+    pub executions: Vec<IdempotencyKeyExecution>,
+}
+
+/// Indexed changes of a watched account in final blocks, from the oldest to the newest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchedAccountChangesView {
+    pub account_id: AccountId,
+    pub changes: Vec<WatchedAccountChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProtocolFeatureView {
+    pub name: String,
+    /// The first protocol version that enables the feature.
+    pub protocol_version: ProtocolVersion,
+    /// Cargo feature that compiles the feature in, not set for stable features.
+    pub nightly_feature: Option<String>,
+    /// Whether the feature is enabled in the current epoch.
+    pub active: bool,
+}
+
+/// Protocol features compiled into the node and whether they are enabled in the current epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProtocolFeaturesView {
+    /// Latest protocol version supported by the node.
+    pub protocol_version: ProtocolVersion,
+    pub epoch_protocol_version: ProtocolVersion,
+    pub features: Vec<ProtocolFeatureView>,
+}
+
+impl ProtocolFeaturesView {
+    pub fn new(epoch_protocol_version: ProtocolVersion) -> Self {
+        let features = ProtocolFeature::all()
+            .into_iter()
+            .map(|feature| ProtocolFeatureView {
+                name: feature.name().to_string(),
+                protocol_version: feature.protocol_version(),
+                nightly_feature: feature.nightly_feature().map(str::to_string),
+                active: feature.protocol_version() <= epoch_protocol_version,
+            })
+            .collect();
+        Self { protocol_version: PROTOCOL_VERSION, epoch_protocol_version, features }
+    }
+}
+
 /// It is a [serializable view] of [`StateChangesRequest`].
 ///
 /// [serializable view]: ./index.html
@@ -1211,6 +1638,9 @@ pub enum StateChangesRequestView {
         #[serde(rename = "key_prefix_base64", with = "base64_format")]
         key_prefix: StoreKey,
     },
+    AllChanges {
+        account_ids: Vec<AccountId>,
+    },
 }
 
 impl From<StateChangesRequestView> for StateChangesRequest {
@@ -1231,6 +1661,7 @@ impl From<StateChangesRequestView> for StateChangesRequest {
             StateChangesRequestView::DataChanges { account_ids, key_prefix } => {
                 Self::DataChanges { account_ids, key_prefix }
             }
+            StateChangesRequestView::AllChanges { account_ids } => Self::AllChanges { account_ids },
         }
     }
 }
@@ -1394,3 +1825,27 @@ impl From<StateChangeWithCause> for StateChangeWithCauseView {
 }
 
 pub type StateChangesView = Vec<StateChangeWithCauseView>;
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+
+    use super::*;
+
+    /// New query requests are added as new variants, so that the encoding of the existing ones
+    /// doesn't change for the clients and the peers.
+    #[test]
+    fn test_query_request_borsh_encoding() {
+        let request = QueryRequest::ViewAccessKeyList { account_id: "a".to_string() };
+        assert_eq!(request.try_to_vec().unwrap(), vec![3, 1, 0, 0, 0, b'a']);
+        let request = QueryRequest::ViewAccessKeyListPaginated {
+            account_id: "a".to_string(),
+            cursor: None,
+            limit: Some(1),
+        };
+        assert_eq!(request.try_to_vec().unwrap(), vec![7, 1, 0, 0, 0, b'a', 0, 1, 1, 0, 0, 0]);
+        let request =
+            QueryRequest::ViewState { account_id: "a".to_string(), prefix: vec![b'b'].into() };
+        assert_eq!(request.try_to_vec().unwrap(), vec![1, 1, 0, 0, 0, b'a', 1, 0, 0, 0, b'b']);
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use near_primitives::version::ProtocolVersion;
+
+use crate::RuntimeConfig;
+
+/// Changes of the runtime config in protocol versions, as JSON objects that are merged into the
+/// config of the previous protocol version, e.g. `(42, include_str!("../res/42.json"))`. Only the
+/// changed fields need to be present, `Balance` fields are strings.
+static CONFIG_DIFFS: &[(ProtocolVersion, &str)] = &[];
+
+/// Runtime configs of all protocol versions: the genesis config with the changes of the protocol
+/// versions up to the given one applied.
+#[derive(Debug)]
+pub struct RuntimeConfigStore {
+    store: BTreeMap<ProtocolVersion, Arc<RuntimeConfig>>,
+}
+
+impl RuntimeConfigStore {
+    pub fn new(genesis_runtime_config: &RuntimeConfig) -> Self {
+        Self::with_diffs(genesis_runtime_config, CONFIG_DIFFS)
+            .expect("Failed to apply the runtime config diffs")
+    }
+
+    /// Applies `diffs` in the order of protocol versions on top of `genesis_runtime_config`.
+    pub fn with_diffs(
+        genesis_runtime_config: &RuntimeConfig,
+        diffs: &[(ProtocolVersion, &str)],
+    ) -> Result<Self, String> {
+        let mut diffs = diffs.to_vec();
+        diffs.sort_by_key(|(protocol_version, _)| *protocol_version);
+        let mut config = serde_json::to_value(genesis_runtime_config).map_err(|e| e.to_string())?;
+        let mut store = BTreeMap::new();
+        store.insert(0, Arc::new(genesis_runtime_config.clone()));
+        for (protocol_version, diff) in diffs {
+            let diff: Value = serde_json::from_str(diff).map_err(|e| {
+                format!(
+                    "Invalid runtime config diff of protocol version {}: {}",
+                    protocol_version, e
+                )
+            })?;
+            merge(&mut config, diff, "").map_err(|e| {
+                format!(
+                    "Invalid runtime config diff of protocol version {}: {}",
+                    protocol_version, e
+                )
+            })?;
+            let runtime_config = serde_json::from_value(config.clone()).map_err(|e| {
+                format!("Invalid runtime config of protocol version {}: {}", protocol_version, e)
+            })?;
+            store.insert(protocol_version, Arc::new(runtime_config));
+        }
+        Ok(Self { store })
+    }
+
+    pub fn get_config(&self, protocol_version: ProtocolVersion) -> &Arc<RuntimeConfig> {
+        self.store
+            .range((Bound::Unbounded, Bound::Included(protocol_version)))
+            .next_back()
+            .map(|(_, config)| config)
+            .expect("Config of the protocol version 0 is always present")
+    }
+}
+
+/// Recursively overwrites the fields of `config` present in `diff`. Fails on the fields of `diff`
+/// that are not in `config`, so that a misspelled field isn't silently ignored.
+fn merge(config: &mut Value, diff: Value, path: &str) -> Result<(), String> {
+    match (config, diff) {
+        (Value::Object(config), Value::Object(diff)) => {
+            for (key, value) in diff {
+                let field_path =
+                    if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match config.get_mut(&key) {
+                    Some(field) => merge(field, value, &field_path)?,
+                    None => return Err(format!("Unknown field `{}`", field_path)),
+                }
+            }
+        }
+        (config, diff) => *config = diff,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_diffs() {
+        let genesis_runtime_config = RuntimeConfig::default();
+        let store = RuntimeConfigStore::with_diffs(&genesis_runtime_config, &[]).unwrap();
+        assert_eq!(**store.get_config(0), genesis_runtime_config);
+        assert_eq!(**store.get_config(ProtocolVersion::max_value()), genesis_runtime_config);
+    }
+
+    #[test]
+    fn test_diffs_apply_from_protocol_version() {
+        let genesis_runtime_config = RuntimeConfig::default();
+        let store = RuntimeConfigStore::with_diffs(
+            &genesis_runtime_config,
+            &[
+                (45, r#"{"storage_amount_per_byte": "1"}"#),
+                (42, r#"{"wasm_config": {"limit_config": {"max_gas_burnt": 10}}}"#),
+            ],
+        )
+        .unwrap();
+        assert_eq!(**store.get_config(41), genesis_runtime_config);
+
+        let config = store.get_config(42);
+        assert_eq!(config.wasm_config.limit_config.max_gas_burnt, 10);
+        assert_eq!(
+            config.wasm_config.limit_config.max_total_prepaid_gas,
+            genesis_runtime_config.wasm_config.limit_config.max_total_prepaid_gas
+        );
+        assert_eq!(config.storage_amount_per_byte, genesis_runtime_config.storage_amount_per_byte);
+
+        let config = store.get_config(46);
+        assert_eq!(config.wasm_config.limit_config.max_gas_burnt, 10);
+        assert_eq!(config.storage_amount_per_byte, 1);
+    }
+
+    #[test]
+    fn test_invalid_diff() {
+        let result = RuntimeConfigStore::with_diffs(
+            &RuntimeConfig::default(),
+            &[(42, r#"{"storage_amount_per_byte": -1}"#)],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unknown_field_in_diff() {
+        let result = RuntimeConfigStore::with_diffs(
+            &RuntimeConfig::default(),
+            &[(42, r#"{"wasm_config": {"limit_config": {"max_gas_brunt": 10}}}"#)],
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "Invalid runtime config diff of protocol version 42: Unknown field \
+             `wasm_config.limit_config.max_gas_brunt`"
+        );
+    }
+}
@@ -7,7 +7,10 @@ use near_primitives::types::{AccountId, Balance};
 use near_primitives::version::ProtocolVersion;
 use near_runtime_fees::RuntimeFeesConfig;
 use near_vm_logic::VMConfig;
-use std::sync::Arc;
+
+pub use crate::config_store::RuntimeConfigStore;
+
+mod config_store;
 
 /// The structure that holds the parameters of the runtime, mostly economics.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -47,16 +50,6 @@ impl RuntimeConfig {
             account_creation_config: AccountCreationConfig::default(),
         }
     }
-
-    /// Returns a `RuntimeConfig` for the corresponding protocol version.
-    /// It uses `genesis_runtime_config` to keep the unchanged fees.
-    /// TODO: https://github.com/nearprotocol/NEPs/issues/120
-    pub fn from_protocol_version(
-        genesis_runtime_config: &Arc<RuntimeConfig>,
-        _protocol_version: ProtocolVersion,
-    ) -> Arc<Self> {
-        genesis_runtime_config.clone()
-    }
 }
 
 /// The structure describes configuration for creation of new accounts.
@@ -67,6 +60,10 @@ pub struct AccountCreationConfig {
     /// The account ID of the account registrar. This account ID allowed to create top-level
     /// accounts of any valid length.
     pub registrar_account_id: AccountId,
+    /// Protocol version since which short top-level accounts can only be created by the registrar.
+    /// Before it, any account can create top-level accounts of any valid length.
+    #[serde(default)]
+    pub registrar_only_from_protocol_version: ProtocolVersion,
 }
 
 impl Default for AccountCreationConfig {
@@ -74,6 +71,7 @@ impl Default for AccountCreationConfig {
         Self {
             min_allowed_top_level_account_length: 0,
             registrar_account_id: AccountId::from("registrar"),
+            registrar_only_from_protocol_version: 0,
         }
     }
 }
@@ -2,10 +2,12 @@
 use std::cmp;
 use std::collections::HashMap;
 use std::io;
+use std::iter::Peekable;
 use std::marker::PhantomPinned;
 use std::sync::RwLock;
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use rocksdb::checkpoint::Checkpoint;
 #[cfg(feature = "single_thread_rocksdb")]
 use rocksdb::Env;
 use rocksdb::{
@@ -16,7 +18,7 @@ use strum_macros::EnumIter;
 
 use near_primitives::version::DbVersion;
 
-use crate::db::refcount::merge_refcounted_records;
+use crate::db::refcount::{decode_value_with_rc, encode_value_with_rc, merge_refcounted_records};
 
 pub(crate) mod migration_utils;
 pub(crate) mod refcount;
@@ -114,10 +116,20 @@ pub enum DBCol {
     ColReceipts = 45,
     /// Precompiled machine code of the contract
     ColCachedContractCode = 46,
+    /// Gas price and gas usage of each block, for fee history queries
+    ColBlockFeeInfo = 47,
+    /// Executions of transactions by signer and idempotency key
+    ColIdempotencyKeys = 48,
+    /// Signer and idempotency key pairs of transactions by block hash
+    ColIdempotencyKeysByBlock = 49,
+    /// Changes of the watched accounts in final blocks, by block height, account id and index
+    ColWatchedAccountChanges = 50,
+    /// Connection history of each saved peer, to rank the peers to connect to
+    ColPeerStats = 51,
 }
 
 // Do not move this line from enum DBCol
-pub const NUM_COLS: usize = 47;
+pub const NUM_COLS: usize = 52;
 
 impl std::fmt::Display for DBCol {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -169,6 +181,11 @@ impl std::fmt::Display for DBCol {
             Self::ColProcessedBlockHeights => "processed block heights",
             Self::ColReceipts => "receipts",
             Self::ColCachedContractCode => "cached code",
+            Self::ColBlockFeeInfo => "block fee info",
+            Self::ColIdempotencyKeys => "idempotency keys",
+            Self::ColIdempotencyKeysByBlock => "idempotency keys by block",
+            Self::ColWatchedAccountChanges => "watched account changes",
+            Self::ColPeerStats => "peer connection stats",
         };
         write!(formatter, "{}", desc)
     }
@@ -178,6 +195,11 @@ impl DBCol {
     pub fn is_rc(&self) -> bool {
         IS_COL_RC[*self as usize]
     }
+
+    /// Whether old data of the column is moved to the cold database of a split storage.
+    pub fn is_cold(&self) -> bool {
+        IS_COL_COLD[*self as usize]
+    }
 }
 
 // List of columns for which GC should be implemented
@@ -200,6 +222,7 @@ lazy_static! {
         col_gc[DBCol::ColEpochInfo as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
         col_gc[DBCol::ColEpochStart as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
         col_gc[DBCol::ColCachedContractCode as usize] = false;
+        col_gc[DBCol::ColPeerStats as usize] = false;
         col_gc
     };
 }
@@ -213,6 +236,11 @@ lazy_static! {
         col_gc[DBCol::ColStateHeaders as usize] = true;
         // True until #2515
         col_gc[DBCol::ColStateParts as usize] = true;
+        // Only GCed for blocks with transactions that have idempotency keys
+        col_gc[DBCol::ColIdempotencyKeys as usize] = true;
+        col_gc[DBCol::ColIdempotencyKeysByBlock as usize] = true;
+        // Only GCed on nodes with watched accounts
+        col_gc[DBCol::ColWatchedAccountChanges as usize] = true;
         col_gc
     };
 }
@@ -229,6 +257,36 @@ lazy_static! {
     };
 }
 
+// List of columns whose garbage collected data is kept in the cold database of a split storage
+lazy_static! {
+    pub static ref IS_COL_COLD: Vec<bool> = {
+        let mut col_cold = vec![false; NUM_COLS];
+        col_cold[DBCol::ColBlock as usize] = true;
+        col_cold[DBCol::ColBlockExtra as usize] = true;
+        col_cold[DBCol::ColBlockInfo as usize] = true;
+        col_cold[DBCol::ColBlockFeeInfo as usize] = true;
+        col_cold[DBCol::ColBlockPerHeight as usize] = true;
+        col_cold[DBCol::ColNextBlockHashes as usize] = true;
+        col_cold[DBCol::ColChunks as usize] = true;
+        col_cold[DBCol::ColPartialChunks as usize] = true;
+        col_cold[DBCol::ColChunkExtra as usize] = true;
+        col_cold[DBCol::ColChunkPerHeightShard as usize] = true;
+        col_cold[DBCol::ColChunkHashesByHeight as usize] = true;
+        col_cold[DBCol::ColInvalidChunks as usize] = true;
+        col_cold[DBCol::ColOutgoingReceipts as usize] = true;
+        col_cold[DBCol::ColIncomingReceipts as usize] = true;
+        col_cold[DBCol::ColState as usize] = true;
+        col_cold[DBCol::ColStateChanges as usize] = true;
+        col_cold[DBCol::ColTransactions as usize] = true;
+        col_cold[DBCol::ColReceipts as usize] = true;
+        col_cold[DBCol::ColReceiptIdToShardId as usize] = true;
+        col_cold[DBCol::ColTransactionResult as usize] = true;
+        col_cold[DBCol::ColOutcomeIds as usize] = true;
+        col_cold[DBCol::ColWatchedAccountChanges as usize] = true;
+        col_cold
+    };
+}
+
 pub const HEAD_KEY: &[u8; 4] = b"HEAD";
 pub const TAIL_KEY: &[u8; 4] = b"TAIL";
 pub const CHUNK_TAIL_KEY: &[u8; 10] = b"CHUNK_TAIL";
@@ -237,12 +295,17 @@ pub const HEADER_HEAD_KEY: &[u8; 11] = b"HEADER_HEAD";
 pub const FINAL_HEAD_KEY: &[u8; 10] = b"FINAL_HEAD";
 pub const LATEST_KNOWN_KEY: &[u8; 12] = b"LATEST_KNOWN";
 pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
+pub const WATCHED_ACCOUNTS_HEAD_KEY: &[u8; 21] = b"WATCHED_ACCOUNTS_HEAD";
+pub const UNDO_COUNT_KEY: &[u8; 10] = b"UNDO_COUNT";
 pub const VERSION_KEY: &[u8; 7] = b"VERSION";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
 pub const GENESIS_STATE_ROOTS_KEY: &[u8; 19] = b"GENESIS_STATE_ROOTS";
 
 pub struct DBTransaction {
     pub ops: Vec<DBOp>,
+    /// Whether the data the transaction deletes is garbage, such as the data of forks, that the
+    /// cold database of a split storage doesn't keep either.
+    pub discard: bool,
 }
 
 pub enum DBOp {
@@ -293,9 +356,21 @@ pub struct TestDB {
     db: RwLock<Vec<HashMap<Vec<u8>, Vec<u8>>>>,
 }
 
+/// Storage of an archival node split between a hot database holding the recent data and a cold
+/// one, usually on a larger and slower disk, holding the rest. Data of the cold columns that is
+/// garbage collected is moved to the cold database instead of being removed, unless the
+/// transaction discards it as garbage, and reads of these columns fall through to the cold
+/// database. Reference counted values are copied to the cold database whenever their reference
+/// count is decreased, the cold database never removes them. Both databases have their own
+/// version and are migrated separately.
+pub struct SplitDB {
+    hot: Box<dyn Database>,
+    cold: Box<dyn Database>,
+}
+
 pub trait Database: Sync + Send {
     fn transaction(&self) -> DBTransaction {
-        DBTransaction { ops: Vec::new() }
+        DBTransaction { ops: Vec::new(), discard: false }
     }
     fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError>;
     fn iter<'a>(&'a self, column: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
@@ -406,10 +481,12 @@ impl Database for TestDB {
         &'a self,
         col: DBCol,
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
-        let iterator = self.db.read().unwrap()[col as usize]
-            .clone()
-            .into_iter()
-            .map(|(k, v)| (k.into_boxed_slice(), v.into_boxed_slice()));
+        // Sorted by key, as in RocksDB.
+        let mut entries: Vec<_> =
+            self.db.read().unwrap()[col as usize].clone().into_iter().collect();
+        entries.sort();
+        let iterator =
+            entries.into_iter().map(|(k, v)| (k.into_boxed_slice(), v.into_boxed_slice()));
         Box::new(iterator)
     }
 
@@ -445,6 +522,123 @@ impl Database for TestDB {
     }
 }
 
+impl SplitDB {
+    pub fn new(hot: Box<dyn Database>, cold: Box<dyn Database>) -> Self {
+        Self { hot, cold }
+    }
+
+    /// Merges the entries of both databases in the order of the keys. Entries of the hot database
+    /// take precedence over the entries of the cold one with the same key.
+    fn iter_split<'a>(
+        &'a self,
+        col: DBCol,
+        hot: Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>,
+        cold: Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>,
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        if !col.is_cold() {
+            return hot;
+        }
+        Box::new(SplitIterator { hot: hot.peekable(), cold: cold.peekable() })
+    }
+}
+
+/// Entries of the hot and cold databases, both sorted by key, merged in the order of the keys.
+struct SplitIterator<'a> {
+    hot: Peekable<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>,
+    cold: Peekable<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>,
+}
+
+impl<'a> Iterator for SplitIterator<'a> {
+    type Item = (Box<[u8]>, Box<[u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.hot.peek(), self.cold.peek()) {
+            (Some((hot_key, _)), Some((cold_key, _))) => hot_key.cmp(cold_key),
+            (Some(_), None) => cmp::Ordering::Less,
+            (None, _) => cmp::Ordering::Greater,
+        };
+        match order {
+            cmp::Ordering::Less => self.hot.next(),
+            cmp::Ordering::Greater => self.cold.next(),
+            cmp::Ordering::Equal => {
+                self.cold.next();
+                self.hot.next()
+            }
+        }
+    }
+}
+
+impl Database for SplitDB {
+    fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        match self.hot.get(col, key)? {
+            None if col.is_cold() => self.cold.get(col, key),
+            value => Ok(value),
+        }
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        self.iter_split(col, self.hot.iter(col), self.cold.iter(col))
+    }
+
+    fn iter_without_rc_logic<'a>(
+        &'a self,
+        col: DBCol,
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        self.iter_split(
+            col,
+            self.hot.iter_without_rc_logic(col),
+            self.cold.iter_without_rc_logic(col),
+        )
+    }
+
+    fn iter_prefix<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        self.iter_split(
+            col,
+            self.hot.iter_prefix(col, key_prefix),
+            self.cold.iter_prefix(col, key_prefix),
+        )
+    }
+
+    fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        if transaction.discard {
+            return self.hot.write(transaction);
+        }
+        // The cold database is written first, so the data is never lost if the node stops between
+        // the writes.
+        let mut cold_transaction = self.cold.transaction();
+        for op in transaction.ops.iter() {
+            let (col, key) = match op {
+                DBOp::Delete { col, key } if col.is_cold() => (*col, key),
+                DBOp::UpdateRefcount { col, key, value }
+                    if col.is_cold() && decode_value_with_rc(value).1 < 0 =>
+                {
+                    (*col, key)
+                }
+                _ => continue,
+            };
+            if let Some(value) = self.hot.get(col, key)? {
+                if col.is_rc() {
+                    cold_transaction.put(col, key, encode_value_with_rc(&value, 1));
+                } else {
+                    cold_transaction.put(col, key, value);
+                }
+            }
+        }
+        if !cold_transaction.ops.is_empty() {
+            self.cold.write(cold_transaction)?;
+        }
+        self.hot.write(transaction)
+    }
+
+    fn as_rocksdb(&self) -> Option<&RocksDB> {
+        self.hot.as_rocksdb()
+    }
+}
+
 fn rocksdb_read_options() -> ReadOptions {
     let mut read_options = ReadOptions::default();
     read_options.set_verify_checksums(false);
@@ -552,6 +746,33 @@ impl RocksDB {
             cf_names.iter().map(|n| db.cf_handle(n).unwrap() as *const ColumnFamily).collect();
         Ok(Self { db, cfs, _pin: PhantomPinned })
     }
+
+    /// Directory of the database on disk.
+    pub fn path(&self) -> &std::path::Path {
+        self.db.path()
+    }
+
+    /// Estimated number of keys in the column, as tracked by RocksDB.
+    pub fn estimate_num_keys(&self, col: DBCol) -> Result<u64, DBError> {
+        let cf_handle = unsafe { &*self.cfs[col as usize] };
+        Ok(self.db.property_int_value_cf(cf_handle, "rocksdb.estimate-num-keys")?.unwrap_or(0))
+    }
+
+    /// Estimated size of the live data of the column in bytes, as tracked by RocksDB.
+    pub fn estimate_live_data_size(&self, col: DBCol) -> Result<u64, DBError> {
+        let cf_handle = unsafe { &*self.cfs[col as usize] };
+        Ok(self
+            .db
+            .property_int_value_cf(cf_handle, "rocksdb.estimate-live-data-size")?
+            .unwrap_or(0))
+    }
+
+    /// Creates a consistent snapshot of the database in `path`, which must not exist. Files are
+    /// hard linked when possible, so the snapshot is cheap until the database diverges from it.
+    pub fn create_checkpoint<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), DBError> {
+        Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
 }
 
 #[cfg(feature = "single_thread_rocksdb")]
@@ -573,8 +794,9 @@ impl TestDB {
 
 #[cfg(test)]
 mod tests {
-    use crate::db::DBCol::ColState;
-    use crate::db::{rocksdb_read_options, DBError, Database, RocksDB};
+    use crate::db::refcount::encode_value_with_rc;
+    use crate::db::DBCol::{ColBlock, ColPeers, ColState};
+    use crate::db::{rocksdb_read_options, DBError, Database, RocksDB, SplitDB, TestDB};
     use crate::{create_store, DBCol};
 
     impl RocksDB {
@@ -652,4 +874,56 @@ mod tests {
             assert_eq!(store.get(ColState, &[1]).unwrap(), None);
         }
     }
+
+    #[test]
+    fn split_db_moves_garbage_collected_data() {
+        let split = SplitDB::new(Box::new(TestDB::new()), Box::new(TestDB::new()));
+        let mut transaction = split.transaction();
+        transaction.put(ColBlock, [1u8], [10u8]);
+        transaction.put(ColPeers, [1u8], [10u8]);
+        transaction.update_refcount(ColState, [2u8], encode_value_with_rc(&[20], 2));
+        split.write(transaction).unwrap();
+
+        let mut transaction = split.transaction();
+        transaction.delete(ColBlock, [1u8]);
+        transaction.delete(ColPeers, [1u8]);
+        transaction.update_refcount(ColState, [2u8], encode_value_with_rc(&[], -1));
+        split.write(transaction).unwrap();
+        assert_eq!(split.hot.get(ColBlock, &[1]).unwrap(), None);
+        assert_eq!(split.get(ColBlock, &[1]).unwrap(), Some(vec![10]));
+        // Columns that are not cold are removed.
+        assert_eq!(split.get(ColPeers, &[1]).unwrap(), None);
+        assert_eq!(split.cold.get(ColState, &[2]).unwrap(), Some(vec![20]));
+
+        let mut transaction = split.transaction();
+        transaction.update_refcount(ColState, [2u8], encode_value_with_rc(&[], -1));
+        split.write(transaction).unwrap();
+        assert_eq!(split.hot.get(ColState, &[2]).unwrap(), None);
+        assert_eq!(split.get(ColState, &[2]).unwrap(), Some(vec![20]));
+
+        // Discarded garbage is not moved.
+        let mut transaction = split.transaction();
+        transaction.put(ColBlock, [4u8], [40u8]);
+        split.write(transaction).unwrap();
+        let mut transaction = split.transaction();
+        transaction.delete(ColBlock, [4u8]);
+        transaction.discard = true;
+        split.write(transaction).unwrap();
+        assert_eq!(split.get(ColBlock, &[4]).unwrap(), None);
+
+        // Entries are merged in the order of the keys, the hot database takes precedence.
+        let mut transaction = split.transaction();
+        transaction.put(ColBlock, [2u8], [20u8]);
+        split.write(transaction).unwrap();
+        let mut transaction = split.transaction();
+        transaction.delete(ColBlock, [2u8]);
+        split.write(transaction).unwrap();
+        let mut transaction = split.transaction();
+        transaction.put(ColBlock, [1u8], [11u8]);
+        transaction.put(ColBlock, [3u8], [30u8]);
+        split.write(transaction).unwrap();
+        let entries: Vec<_> =
+            split.iter(ColBlock).map(|(key, value)| (key.to_vec(), value.to_vec())).collect();
+        assert_eq!(entries, vec![(vec![1], vec![11]), (vec![2], vec![20]), (vec![3], vec![30])]);
+    }
 }
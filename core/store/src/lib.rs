@@ -17,6 +17,7 @@ pub use db::DBCol::{self, *};
 pub use db::{
     CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
     LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, NUM_COLS, SHOULD_COL_GC, SKIP_COL_GC, TAIL_KEY,
+    UNDO_COUNT_KEY, WATCHED_ACCOUNTS_HEAD_KEY,
 };
 use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, Account};
@@ -31,7 +32,7 @@ use near_primitives::types::{AccountId, CompiledContractCache, StateRoot};
 pub use crate::db::refcount::decode_value_with_rc;
 use crate::db::refcount::encode_value_with_rc;
 use crate::db::{
-    DBOp, DBTransaction, Database, RocksDB, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
+    DBOp, DBTransaction, Database, RocksDB, SplitDB, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
 };
 pub use crate::trie::{
     iterator::TrieIterator, update::TrieUpdate, update::TrieUpdateIterator,
@@ -196,6 +197,12 @@ impl StoreUpdate {
         self.transaction.delete(column, key);
     }
 
+    /// Marks the data deleted by the update as garbage, such as the data of forks, so that a split
+    /// storage doesn't move it to its cold database.
+    pub fn discard_garbage(&mut self) {
+        self.transaction.discard = true;
+    }
+
     /// Merge another store update into this one.
     pub fn merge(&mut self, other: StoreUpdate) {
         if let Some(tries) = other.tries {
@@ -214,6 +221,7 @@ impl StoreUpdate {
 
     /// Merge DB Transaction.
     pub fn merge_transaction(&mut self, transaction: DBTransaction) {
+        self.transaction.discard |= transaction.discard;
         for op in transaction.ops {
             match op {
                 DBOp::Insert { col, key, value } => self.transaction.put(col, &key, &value),
@@ -293,6 +301,15 @@ pub fn create_store(path: &str) -> Arc<Store> {
     Arc::new(Store::new(db))
 }
 
+/// Opens the storage of an archival node that moves the old data from the database at `hot_path`
+/// to the one at `cold_path`.
+pub fn create_split_store(hot_path: &str, cold_path: &str) -> Arc<Store> {
+    let hot = RocksDB::new(hot_path).expect("Failed to open the hot database");
+    let cold = RocksDB::new(cold_path).expect("Failed to open the cold database");
+    let db = Arc::pin(SplitDB::new(Box::new(hot), Box::new(cold)));
+    Arc::new(Store::new(db))
+}
+
 /// Reads an object from Trie.
 /// # Errors
 /// see StorageError
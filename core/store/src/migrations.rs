@@ -31,9 +31,11 @@ use near_primitives::merkle::merklize;
 use near_primitives::receipt::{DelayedReceiptIndices, Receipt, ReceiptEnum};
 use near_primitives::syncing::{ShardStateSyncResponseHeader, ShardStateSyncResponseHeaderV1};
 use near_primitives::trie_key::TrieKey;
-use near_primitives::utils::{create_receipt_id_from_transaction, get_block_shard_id};
+use near_primitives::types::WatchedAccountChange;
+use near_primitives::utils::{
+    create_receipt_id_from_transaction, get_block_shard_id, get_watched_account_change_id,
+};
 use near_primitives::validator_signer::InMemoryValidatorSigner;
-use std::rc::Rc;
 
 pub mod v6_to_v7;
 pub mod v8_to_v9;
@@ -56,6 +58,38 @@ pub fn set_store_version(store: &Store, db_version: u32) {
     store_update.commit().expect("Failed to write version to database");
 }
 
+/// Sets the version of a database without one, such as the cold databases of split storages that
+/// were created before they were versioned, to the version it was created at.
+pub fn set_store_version_if_missing(path: &str, db_version: DbVersion) {
+    let store = create_store(path);
+    let version = store.get(DBCol::ColDbVersion, VERSION_KEY).expect("Failed to read the version");
+    if version.is_none() {
+        set_store_version(&store, db_version);
+    }
+}
+
+/// Opens the database with the column options of its version, so that nothing is rewritten
+/// before the migration.
+fn open_db_version(path: &str, db_version: DbVersion) -> RocksDB {
+    let db = if db_version <= 6 { RocksDB::new_v6(path) } else { RocksDB::new(path) };
+    db.expect("Failed to open the database")
+}
+
+/// Estimated number of keys in each of the columns of the database.
+pub fn estimate_num_keys(path: &str, db_version: DbVersion, cols: &[DBCol]) -> Vec<(DBCol, u64)> {
+    let db = open_db_version(path, db_version);
+    cols.iter()
+        .map(|col| (*col, db.estimate_num_keys(*col).expect("Failed to read column properties")))
+        .collect()
+}
+
+/// Snapshots the database into `snapshot_path` before a migration.
+pub fn create_snapshot(path: &str, db_version: DbVersion, snapshot_path: &str) {
+    open_db_version(path, db_version)
+        .create_checkpoint(snapshot_path)
+        .expect("Failed to snapshot the database");
+}
+
 fn get_outcomes_by_block_hash(store: &Store, block_hash: &CryptoHash) -> HashSet<CryptoHash> {
     match store.get_ser(DBCol::ColOutcomeIds, block_hash.as_ref()) {
         Ok(Some(hash_set)) => hash_set,
@@ -332,7 +366,7 @@ pub fn migrate_13_to_14(path: &String) {
 pub fn migrate_14_to_15(path: &String) {
     let store = create_store(path);
     let trie_store = Box::new(TrieCachingStorage::new(store.clone(), TrieCache::new(), 0));
-    let trie = Rc::new(Trie::new(trie_store, 0));
+    let trie = Arc::new(Trie::new(trie_store, 0));
 
     let mut store_update = store.store_update();
     let batch_size_limit = 10_000_000;
@@ -481,3 +515,22 @@ pub fn migrate_14_to_15(path: &String) {
     store_update.commit().unwrap();
     set_store_version(&store, 15);
 }
+
+/// Key the changes of the watched accounts by the block height first, so that garbage collection
+/// of a height only reads the keys at the height.
+pub fn migrate_20_to_21(path: &String) {
+    let store = create_store(path);
+    let mut store_update = store.store_update();
+    for (key, value) in store.iter(DBCol::ColWatchedAccountChanges) {
+        let change = WatchedAccountChange::try_from_slice(&value).unwrap();
+        // The old key is the account id, a comma, the height and the index.
+        let account_id = String::from_utf8(key[..key.len() - 13].to_vec()).unwrap();
+        store_update.delete(DBCol::ColWatchedAccountChanges, &key);
+        store_update.set(
+            DBCol::ColWatchedAccountChanges,
+            &get_watched_account_change_id(change.block_height, &account_id, change.index),
+            &value,
+        );
+    }
+    store_update.commit().unwrap();
+}
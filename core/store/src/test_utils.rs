@@ -4,7 +4,7 @@ use std::sync::Arc;
 use rand::seq::SliceRandom;
 use rand::Rng;
 
-use crate::db::TestDB;
+use crate::db::{SplitDB, TestDB};
 use crate::{ShardTries, Store};
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::ShardId;
@@ -15,6 +15,12 @@ pub fn create_test_store() -> Arc<Store> {
     Arc::new(Store::new(db))
 }
 
+/// Creates an in-memory storage split between a hot and a cold database, as on archival nodes.
+pub fn create_test_split_store() -> Arc<Store> {
+    let db = Arc::pin(SplitDB::new(Box::new(TestDB::new()), Box::new(TestDB::new())));
+    Arc::new(Store::new(db))
+}
+
 /// Creates a Trie using an in-memory database.
 pub fn create_tries() -> ShardTries {
     let store = create_test_store();
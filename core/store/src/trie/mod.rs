@@ -396,7 +396,7 @@ impl RawTrieNodeWithSize {
 }
 
 pub struct Trie {
-    pub(crate) storage: Box<dyn TrieStorage>,
+    pub(crate) storage: Arc<dyn TrieStorage>,
     pub counter: TouchedNodesCounter,
 }
 
@@ -439,7 +439,13 @@ impl TrieChanges {
 
 impl Trie {
     pub fn new(store: Box<dyn TrieStorage>, _shard_id: ShardId) -> Self {
-        Trie { storage: store, counter: TouchedNodesCounter::default() }
+        Trie { storage: Arc::from(store), counter: TouchedNodesCounter::default() }
+    }
+
+    /// Trie over the same storage that counts touched nodes separately, so that it can be used
+    /// concurrently with this one.
+    pub fn with_separate_counter(&self) -> Self {
+        Trie { storage: Arc::clone(&self.storage), counter: TouchedNodesCounter::default() }
     }
 
     pub fn recording_reads(&self) -> Self {
@@ -453,7 +459,7 @@ impl Trie {
             },
             recorded: Arc::new(Mutex::new(Default::default())),
         };
-        Trie { storage: Box::new(storage), counter: TouchedNodesCounter::default() }
+        Trie { storage: Arc::new(storage), counter: TouchedNodesCounter::default() }
     }
 
     pub fn empty_root() -> StateRoot {
@@ -472,7 +478,7 @@ impl Trie {
         let recorded_storage =
             partial_storage.nodes.0.into_iter().map(|value| (hash(&value), value)).collect();
         Trie {
-            storage: Box::new(TrieMemoryPartialStorage {
+            storage: Arc::new(TrieMemoryPartialStorage {
                 recorded_storage,
                 visited_nodes: Default::default(),
             }),
@@ -8,7 +8,6 @@ use near_primitives::types::{
     NumShards, RawStateChange, RawStateChangesWithTrieKey, ShardId, StateChangeCause, StateRoot,
 };
 use near_primitives::utils::get_block_shard_id;
-use std::rc::Rc;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -35,11 +34,11 @@ impl ShardTries {
     }
 
     pub fn new_trie_update(&self, shard_id: ShardId, state_root: CryptoHash) -> TrieUpdate {
-        TrieUpdate::new(Rc::new(self.get_trie_for_shard(shard_id)), state_root)
+        TrieUpdate::new(Arc::new(self.get_trie_for_shard(shard_id)), state_root)
     }
 
     pub fn new_trie_update_view(&self, shard_id: ShardId, state_root: CryptoHash) -> TrieUpdate {
-        TrieUpdate::new(Rc::new(self.get_view_trie_for_shard(shard_id)), state_root)
+        TrieUpdate::new(Arc::new(self.get_view_trie_for_shard(shard_id)), state_root)
     }
 
     fn get_trie_for_shard_internal(&self, shard_id: ShardId, is_view: bool) -> Trie {
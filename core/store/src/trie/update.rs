@@ -11,7 +11,7 @@ use crate::StorageError;
 
 use super::{Trie, TrieIterator};
 use near_primitives::trie_key::TrieKey;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// Key-value update. Contains a TrieKey and a value.
 pub struct TrieKeyValueUpdate {
@@ -24,8 +24,10 @@ pub type TrieUpdates = BTreeMap<Vec<u8>, TrieKeyValueUpdate>;
 
 /// Provides a way to access Storage and record changes with future commit.
 pub struct TrieUpdate {
-    pub trie: Rc<Trie>,
+    pub trie: Arc<Trie>,
     root: CryptoHash,
+    /// Changes committed before the update was forked, shared with the forks and never changed.
+    base: Arc<RawStateChanges>,
     committed: RawStateChanges,
     prospective: TrieUpdates,
 }
@@ -52,8 +54,14 @@ impl<'a> TrieUpdateValuePtr<'a> {
 }
 
 impl TrieUpdate {
-    pub fn new(trie: Rc<Trie>, root: CryptoHash) -> Self {
-        TrieUpdate { trie, root, committed: Default::default(), prospective: Default::default() }
+    pub fn new(trie: Arc<Trie>, root: CryptoHash) -> Self {
+        TrieUpdate {
+            trie,
+            root,
+            base: Default::default(),
+            committed: Default::default(),
+            prospective: Default::default(),
+        }
     }
 
     pub fn trie(&self) -> &Trie {
@@ -64,10 +72,8 @@ impl TrieUpdate {
         let key = key.to_vec();
         if let Some(key_value) = self.prospective.get(&key) {
             return Ok(key_value.value.as_ref().map(<Vec<u8>>::clone));
-        } else if let Some(changes_with_trie_key) = self.committed.get(&key) {
-            if let Some(RawStateChange { data, .. }) = changes_with_trie_key.changes.last() {
-                return Ok(data.as_ref().map(<Vec<u8>>::clone));
-            }
+        } else if let Some(RawStateChange { data, .. }) = self.last_committed_change(&key) {
+            return Ok(data.as_ref().map(<Vec<u8>>::clone));
         }
 
         self.trie.get(&self.root, &key)
@@ -77,16 +83,25 @@ impl TrieUpdate {
         let key = key.to_vec();
         if let Some(key_value) = self.prospective.get(&key) {
             return Ok(key_value.value.as_ref().map(TrieUpdateValuePtr::MemoryRef));
-        } else if let Some(changes_with_trie_key) = self.committed.get(&key) {
-            if let Some(RawStateChange { data, .. }) = changes_with_trie_key.changes.last() {
-                return Ok(data.as_ref().map(TrieUpdateValuePtr::MemoryRef));
-            }
+        } else if let Some(RawStateChange { data, .. }) = self.last_committed_change(&key) {
+            return Ok(data.as_ref().map(TrieUpdateValuePtr::MemoryRef));
         }
         self.trie.get_ref(&self.root, &key).map(|option| {
             option.map(|(length, hash)| TrieUpdateValuePtr::HashAndSize(&self.trie, length, hash))
         })
     }
 
+    fn last_committed_change(&self, key: &[u8]) -> Option<&RawStateChange> {
+        self.committed
+            .get(key)
+            .and_then(|changes_with_trie_key| changes_with_trie_key.changes.last())
+            .or_else(|| {
+                self.base
+                    .get(key)
+                    .and_then(|changes_with_trie_key| changes_with_trie_key.changes.last())
+            })
+    }
+
     pub fn set(&mut self, trie_key: TrieKey, value: Vec<u8>) {
         // NOTE: Converting `TrieKey` to a `Vec<u8>` is useful here for 2 reasons:
         // - Using `Vec<u8>` for sorting `BTreeMap` in the same order as a `Trie` and
@@ -114,9 +129,51 @@ impl TrieUpdate {
         self.prospective.clear();
     }
 
+    /// Creates an independent update on top of the committed changes of this one, that can be used
+    /// from another thread. Changes committed to the fork are brought back with `merge_fork`.
+    ///
+    /// The committed changes are moved to the base shared by the update and its forks, so forking
+    /// doesn't copy them.
+    pub fn fork(&mut self) -> TrieUpdate {
+        assert!(self.prospective.is_empty(), "Fork cannot be created with uncommitted changes.");
+        if !self.committed.is_empty() {
+            let committed = std::mem::take(&mut self.committed);
+            let base = std::mem::take(&mut self.base);
+            self.base = Arc::new(append_changes(unwrap_or_clone(base), committed));
+        }
+        TrieUpdate {
+            trie: Arc::new(self.trie.with_separate_counter()),
+            root: self.root,
+            base: self.base.clone(),
+            committed: Default::default(),
+            prospective: Default::default(),
+        }
+    }
+
+    /// Appends the changes committed to the fork since it was created. Forks that are merged into
+    /// the same update must change disjoint keys, and the update itself must not be changed after
+    /// the forks were created.
+    pub fn merge_fork(&mut self, fork: TrieUpdate) {
+        assert!(fork.prospective.is_empty(), "Fork cannot be merged with uncommitted changes.");
+        debug_assert!(Arc::ptr_eq(&self.base, &fork.base), "Fork of another update is merged.");
+        let committed = std::mem::take(&mut self.committed);
+        self.committed = append_changes(committed, fork.committed);
+    }
+
+    /// All the committed changes, the ones made before the last fork first.
+    fn into_committed(self) -> (Arc<Trie>, CryptoHash, RawStateChanges) {
+        let TrieUpdate { trie, root, base, committed, .. } = self;
+        let committed = if base.is_empty() {
+            committed
+        } else {
+            append_changes(unwrap_or_clone(base), committed)
+        };
+        (trie, root, committed)
+    }
+
     pub fn finalize(self) -> Result<(TrieChanges, Vec<RawStateChangesWithTrieKey>), StorageError> {
         assert!(self.prospective.is_empty(), "Finalize cannot be called with uncommitted changes.");
-        let TrieUpdate { trie, root, committed, .. } = self;
+        let (trie, root, committed) = self.into_committed();
         let mut state_changes = Vec::with_capacity(committed.len());
         let trie_changes = trie.update(
             &root,
@@ -136,7 +193,7 @@ impl TrieUpdate {
 
     pub fn finalize_genesis(self) -> Result<TrieChanges, StorageError> {
         assert!(self.prospective.is_empty(), "Finalize cannot be called with uncommitted changes.");
-        let TrieUpdate { trie, root, committed, .. } = self;
+        let (trie, root, committed) = self.into_committed();
         let trie_changes = trie.update(
             &root,
             committed.into_iter().map(|(k, changes_with_trie_key)| {
@@ -171,6 +228,40 @@ impl TrieUpdate {
     }
 }
 
+/// Appends the changes of `other` to the changes of the same keys in `changes`.
+fn append_changes(mut changes: RawStateChanges, other: RawStateChanges) -> RawStateChanges {
+    for (raw_key, RawStateChangesWithTrieKey { trie_key, changes: other_changes }) in other {
+        changes
+            .entry(raw_key)
+            .or_insert_with(|| RawStateChangesWithTrieKey { trie_key, changes: Vec::new() })
+            .changes
+            .extend(other_changes);
+    }
+    changes
+}
+
+fn unwrap_or_clone(changes: Arc<RawStateChanges>) -> RawStateChanges {
+    Arc::try_unwrap(changes).unwrap_or_else(|changes| (*changes).clone())
+}
+
+/// Keys and last values of the committed changes starting from the given key.
+fn committed_values<'a>(
+    changes: &'a RawStateChanges,
+    start: &[u8],
+) -> Box<dyn Iterator<Item = (&'a Vec<u8>, &'a Option<Vec<u8>>)> + 'a> {
+    Box::new(changes.range(start.to_vec()..).map(|(raw_key, changes_with_trie_key)| {
+        (
+            raw_key,
+            &changes_with_trie_key
+                .changes
+                .last()
+                .as_ref()
+                .expect("Committed entry should have at least one change.")
+                .data,
+        )
+    }))
+}
+
 struct MergeIter<'a> {
     left: Peekable<Box<dyn Iterator<Item = (&'a Vec<u8>, &'a Option<Vec<u8>>)> + 'a>>,
     right: Peekable<Box<dyn Iterator<Item = (&'a Vec<u8>, &'a Option<Vec<u8>>)> + 'a>>,
@@ -227,19 +318,10 @@ impl<'a> TrieUpdateIterator<'a> {
             None => None,
         };
         trie_iter.seek(&start_offset)?;
-        let committed_iter = state_update.committed.range(start_offset.clone()..).map(
-            |(raw_key, changes_with_trie_key)| {
-                (
-                    raw_key,
-                    &changes_with_trie_key
-                        .changes
-                        .last()
-                        .as_ref()
-                        .expect("Committed entry should have at least one change.")
-                        .data,
-                )
-            },
-        );
+        let committed_iter = MergeIter {
+            left: committed_values(&state_update.base, &start_offset).peekable(),
+            right: committed_values(&state_update.committed, &start_offset).peekable(),
+        };
         let prospective_iter = state_update
             .prospective
             .range(start_offset..)
@@ -503,4 +585,39 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn trie_fork() {
+        let tries = create_tries();
+        let cause = StateChangeCause::TransactionProcessing { tx_hash: CryptoHash::default() };
+        let mut trie_update = tries.new_trie_update(0, CryptoHash::default());
+        trie_update.set(test_key(b"dog".to_vec()), b"puppy".to_vec());
+        trie_update.commit(cause.clone());
+
+        let mut fork1 = trie_update.fork();
+        let mut fork2 = trie_update.fork();
+        assert!(Arc::ptr_eq(&fork1.base, &fork2.base));
+        assert_eq!(fork1.get(&test_key(b"dog".to_vec())), Ok(Some(b"puppy".to_vec())));
+        fork1.set(test_key(b"dog".to_vec()), b"dog".to_vec());
+        fork1.commit(cause.clone());
+        fork2.set(test_key(b"cat".to_vec()), b"kitten".to_vec());
+        fork2.commit(cause.clone());
+        assert_eq!(fork2.get(&test_key(b"dog".to_vec())), Ok(Some(b"puppy".to_vec())));
+        trie_update.merge_fork(fork1);
+        trie_update.merge_fork(fork2);
+
+        assert_eq!(trie_update.get(&test_key(b"dog".to_vec())), Ok(Some(b"dog".to_vec())));
+        assert_eq!(trie_update.get(&test_key(b"cat".to_vec())), Ok(Some(b"kitten".to_vec())));
+        let keys: Result<Vec<_>, _> =
+            trie_update.iter(&test_key(vec![]).to_vec()).unwrap().collect();
+        assert_eq!(
+            keys.unwrap(),
+            vec![test_key(b"cat".to_vec()).to_vec(), test_key(b"dog".to_vec()).to_vec()]
+        );
+        let state_changes = trie_update.finalize().unwrap().1;
+        assert_eq!(
+            state_changes.iter().map(|changes| changes.changes.len()).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
 }
@@ -2,6 +2,7 @@ use std::convert::TryInto;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -9,18 +10,28 @@ use std::time::Duration;
 use actix;
 use actix_web;
 use chrono::Utc;
-use log::info;
+use log::{info, warn};
 use num_rational::Rational;
 use serde::{Deserialize, Serialize};
 
 use lazy_static::lazy_static;
-use near_chain_configs::{ClientConfig, Genesis, GenesisConfig, LogSummaryStyle};
+use near_chain::chain::NUM_EPOCHS_TO_KEEP_STORE_DATA;
+use near_chain_configs::{
+    ClientConfig, ExternalStorageLocation, Genesis, GenesisConfig, LogSummaryStyle,
+    TrustedCheckpoint,
+};
+use near_client::report_deprecated_usage;
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
+#[cfg(feature = "grpc")]
+use near_grpc::GrpcConfig;
 use near_jsonrpc::RpcConfig;
 use near_network::test_utils::open_port;
 use near_network::types::ROUTED_MESSAGE_TTL;
 use near_network::utils::blacklist_from_iter;
-use near_network::NetworkConfig;
+use near_network::{
+    BandwidthLimits, BootNodeHost, ConnectionLimits, EncryptionMode, NetworkConfig, PeerAccess,
+    PortMapping, ProxyConfig, ReputationConfig, SendQueueConfig, Transport,
+};
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::hash::CryptoHash;
 use near_primitives::state_record::StateRecord;
@@ -28,14 +39,17 @@ use near_primitives::types::{
     AccountId, AccountInfo, Balance, BlockHeightDelta, EpochHeight, Gas, NumBlocks, NumSeats,
     NumShards, ShardId,
 };
+use near_primitives::upgrade_schedule::ProtocolUpgradeVotingSchedule;
 use near_primitives::utils::{generate_random_string, get_num_seats_per_shard};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
-use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::version::{DeprecatedSurface, DEPRECATIONS, PROTOCOL_VERSION};
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
 use near_runtime_configs::RuntimeConfig;
 use near_telemetry::TelemetryConfig;
 
+use crate::metrics::MetricsConfig;
+
 /// Initial balance used in tests.
 pub const TESTING_INIT_BALANCE: Balance = 1_000_000_000 * NEAR_BASE;
 
@@ -194,12 +208,14 @@ fn default_peer_stats_period() -> Duration {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Network {
-    /// Address to listen for incoming connections.
+    /// Address to listen for incoming connections. Several comma separated addresses can be given
+    /// to listen on both IPv4 and IPv6, e.g. `0.0.0.0:24567,[::]:24567`.
     pub addr: String,
-    /// Address to advertise to peers for them to connect.
-    /// If empty, will use the same port as the addr, and will introspect on the listener.
+    /// Comma separated `ip:port` or `host:port` addresses to advertise to peers for them to
+    /// connect, signed in the peer record of the node. Host names are resolved at startup. If
+    /// empty, the addresses of `addr` with a specified IP are advertised.
     pub external_address: String,
-    /// Comma separated list of nodes to connect to.
+    /// Comma separated list of nodes to connect to, as `id@ip:port` or `id@host:port`.
     pub boot_nodes: String,
     /// Maximum number of active peers. Hard limit.
     #[serde(default = "default_max_num_peers")]
@@ -242,6 +258,50 @@ pub struct Network {
     /// Period to check on peer status
     #[serde(default = "default_peer_stats_period")]
     pub peer_stats_period: Duration,
+    /// Transport to connect to peers with, `quic` also listens on the UDP port of `addr` and
+    /// falls back to TCP for peers that don't support it.
+    #[serde(default)]
+    pub transport: Transport,
+    /// Misbehavior score at which peers get banned for `ban_window`, and how fast it goes down.
+    #[serde(default)]
+    pub reputation: ReputationConfig,
+    /// Bytes of chunk parts, sync messages and transactions queued for a slow peer before some
+    /// are dropped. Blocks, edges and control messages are never dropped.
+    #[serde(default)]
+    pub send_queue: SendQueueConfig,
+    /// SOCKS5 or HTTP proxy to connect to peers through. With `remote_dns` the host names of boot
+    /// nodes are resolved by the proxy.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Upload and download limits in bytes per second, per peer and for all peers together.
+    #[serde(default)]
+    pub bandwidth: BandwidthLimits,
+    /// Comma separated list of nodes, as `id@ip:port`, at which other validators connect directly
+    /// to this validator. Either this node itself, with its public address, or proxies connected
+    /// to it. Only used by validators.
+    #[serde(default)]
+    pub tier1_proxies: String,
+    /// Whether TCP connections are encrypted: `disabled`, `preferred` to encrypt connections to
+    /// peers that support it, or `required` to refuse plaintext connections. QUIC connections are
+    /// always encrypted.
+    #[serde(default)]
+    pub encryption: EncryptionMode,
+    /// Peers allowed and denied by node key or IP network, e.g. `10.0.0.0/8`. With `allow_only`
+    /// inbound connections are only accepted from allowed peers.
+    #[serde(default)]
+    pub access: PeerAccess,
+    /// Map the listening port on the router: `disabled`, `upnp`, `nat_pmp` or `auto` to try UPnP
+    /// and then NAT-PMP. The external address of the mapping is advertised to the peers.
+    #[serde(default)]
+    pub port_mapping: PortMapping,
+    /// Advertise the IP that the peers this node connects to see it at, with the listening port,
+    /// unless `external_address` is set.
+    #[serde(default)]
+    pub discover_external_address: bool,
+    /// Inbound connections per subnet, slots reserved for outbound and allowed peers, and how
+    /// long inbound peers wait to reconnect.
+    #[serde(default)]
+    pub connection_limits: ConnectionLimits,
 }
 
 impl Default for Network {
@@ -264,6 +324,17 @@ impl Default for Network {
             blacklist: vec![],
             ttl_account_id_router: default_ttl_account_id_router(),
             peer_stats_period: default_peer_stats_period(),
+            transport: Transport::default(),
+            reputation: ReputationConfig::default(),
+            send_queue: SendQueueConfig::default(),
+            proxy: None,
+            bandwidth: BandwidthLimits::default(),
+            tier1_proxies: "".to_string(),
+            encryption: EncryptionMode::default(),
+            access: PeerAccess::default(),
+            port_mapping: PortMapping::default(),
+            discover_external_address: false,
+            connection_limits: ConnectionLimits::default(),
         }
     }
 }
@@ -301,6 +372,14 @@ fn default_gc_blocks_limit() -> NumBlocks {
     2
 }
 
+fn default_gc_step_time_limit() -> Duration {
+    Duration::from_millis(100)
+}
+
+fn default_gc_num_epochs_to_keep() -> u64 {
+    NUM_EPOCHS_TO_KEEP_STORE_DATA
+}
+
 fn default_view_client_threads() -> usize {
     4
 }
@@ -309,6 +388,14 @@ fn default_doomslug_step_period() -> Duration {
     Duration::from_millis(100)
 }
 
+fn default_max_orphans() -> usize {
+    near_chain::MAX_ORPHAN_SIZE
+}
+
+fn default_max_orphan_age() -> Duration {
+    near_chain::MAX_ORPHAN_AGE
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Consensus {
     /// Minimum number of peers to start syncing.
@@ -321,8 +408,8 @@ pub struct Consensus {
     pub max_block_production_delay: Duration,
     /// Maximum duration before skipping given height.
     pub max_block_wait_delay: Duration,
-    /// Duration to reduce the wait for each missed block by validator.
-    #[serde(default = "default_reduce_wait_for_missing_block")]
+    /// Duration to reduce the wait for each missed block by validator. Deprecated, has no effect.
+    #[serde(default = "default_reduce_wait_for_missing_block", skip_serializing)]
     pub reduce_wait_for_missing_block: Duration,
     /// Produce empty blocks, use `false` for testing.
     pub produce_empty_blocks: bool,
@@ -357,6 +444,16 @@ pub struct Consensus {
     /// Time between running doomslug timer.
     #[serde(default = "default_doomslug_step_period")]
     pub doomslug_step_period: Duration,
+    /// Maximum number of blocks in the orphan pool, the highest ones are dropped first.
+    #[serde(default = "default_max_orphans")]
+    pub max_orphans: usize,
+    /// Time after which orphans are dropped from the pool.
+    #[serde(default = "default_max_orphan_age")]
+    pub max_orphan_age: Duration,
+    /// Apply the chunks of a block waiting for its other chunks as they arrive, so that the block
+    /// becomes the head sooner. Blocks with all their chunks are not applied any earlier.
+    #[serde(default)]
+    pub optimistic_block_application: bool,
 }
 
 impl Default for Consensus {
@@ -382,6 +479,9 @@ impl Default for Consensus {
             sync_check_period: default_sync_check_period(),
             sync_step_period: default_sync_step_period(),
             doomslug_step_period: default_doomslug_step_period(),
+            max_orphans: default_max_orphans(),
+            max_orphan_age: default_max_orphan_age(),
+            optimistic_block_application: false,
         }
     }
 }
@@ -397,17 +497,51 @@ pub struct Config {
     #[cfg(feature = "rosetta_rpc")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rosetta_rpc: Option<RosettaRpcConfig>,
+    #[cfg(feature = "grpc")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcConfig>,
+    /// Standalone Prometheus endpoint, the metrics are also served by the JSON RPC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsConfig>,
     pub telemetry: TelemetryConfig,
     pub network: Network,
     pub consensus: Consensus,
     pub tracked_accounts: Vec<AccountId>,
     pub tracked_shards: Vec<ShardId>,
     pub archive: bool,
+    /// Directory of the database that an archival node moves the data of the old epochs to,
+    /// relative to the home directory, so only the recent epochs stay in the main database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_store_path: Option<PathBuf>,
     pub log_summary_style: LogSummaryStyle,
     #[serde(default = "default_gc_blocks_limit")]
     pub gc_blocks_limit: NumBlocks,
+    /// Longest time garbage collection takes after a block, the heights left are collected
+    /// after the next blocks.
+    #[serde(default = "default_gc_step_time_limit")]
+    pub gc_step_time_limit: Duration,
+    /// Number of epochs whose data is not garbage collected, at least 3.
+    #[serde(default = "default_gc_num_epochs_to_keep")]
+    pub gc_num_epochs_to_keep: u64,
     #[serde(default = "default_view_client_threads")]
     pub view_client_threads: usize,
+    /// RFC 3339 timestamp after which the node votes for the new protocol version. Can be
+    /// overridden with `NEAR_PROTOCOL_UPGRADE_VOTING_SCHEDULE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_upgrade_voting_schedule: Option<ProtocolUpgradeVotingSchedule>,
+    /// Accounts whose transactions, receipts and balance changes are indexed in the local
+    /// database, starting from the first final block after they were added. The changes are
+    /// garbage collected with the blocks, unless the node is archival.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watched_accounts: Vec<AccountId>,
+    /// Filesystem directory or HTTP mirror of state dumps that state sync downloads state from,
+    /// next to the peers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_sync_external: Option<ExternalStorageLocation>,
+    /// Height, hash and state root of a block on the canonical chain. Sync doesn't check the
+    /// signatures of the headers and chunks up to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trusted_checkpoint: Option<TrustedCheckpoint>,
 }
 
 impl Default for Config {
@@ -420,15 +554,25 @@ impl Default for Config {
             rpc: RpcConfig::default(),
             #[cfg(feature = "rosetta_rpc")]
             rosetta_rpc: None,
+            #[cfg(feature = "grpc")]
+            grpc: None,
+            metrics: None,
             telemetry: TelemetryConfig::default(),
             network: Network::default(),
             consensus: Consensus::default(),
             tracked_accounts: vec![],
             tracked_shards: vec![],
             archive: false,
+            cold_store_path: None,
             log_summary_style: LogSummaryStyle::Colored,
             gc_blocks_limit: default_gc_blocks_limit(),
+            gc_step_time_limit: default_gc_step_time_limit(),
+            gc_num_epochs_to_keep: default_gc_num_epochs_to_keep(),
             view_client_threads: 4,
+            protocol_upgrade_voting_schedule: None,
+            watched_accounts: vec![],
+            state_sync_external: None,
+            trusted_checkpoint: None,
         }
     }
 }
@@ -438,7 +582,9 @@ impl Config {
         let mut file = File::open(path).expect("Could not open config file.");
         let mut content = String::new();
         file.read_to_string(&mut content).expect("Could not read from config file.");
-        Config::from(content.as_str())
+        let config = Config::from(content.as_str());
+        report_deprecated_config_fields(&content);
+        config
     }
 
     pub fn write_to_file(&self, path: &PathBuf) {
@@ -450,6 +596,23 @@ impl Config {
     }
 }
 
+/// Reports deprecated fields that are set in the config file, nested fields are looked up by
+/// their dotted path.
+fn report_deprecated_config_fields(content: &str) {
+    let value: serde_json::Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    for deprecation in DEPRECATIONS
+        .iter()
+        .filter(|deprecation| deprecation.surface == DeprecatedSurface::ConfigField)
+    {
+        if deprecation.name.split('.').try_fold(&value, |value, key| value.get(key)).is_some() {
+            report_deprecated_usage(DeprecatedSurface::ConfigField, deprecation.name);
+        }
+    }
+}
+
 impl From<&str> for Config {
     fn from(content: &str) -> Self {
         serde_json::from_str(content).expect("Failed to deserialize config")
@@ -544,6 +707,9 @@ pub struct NearConfig {
     pub rpc_config: RpcConfig,
     #[cfg(feature = "rosetta_rpc")]
     pub rosetta_rpc_config: Option<RosettaRpcConfig>,
+    #[cfg(feature = "grpc")]
+    pub grpc_config: Option<GrpcConfig>,
+    pub metrics_config: Option<MetricsConfig>,
     pub telemetry_config: TelemetryConfig,
     pub genesis: Genesis,
     pub validator_signer: Option<Arc<dyn ValidatorSigner>>,
@@ -556,6 +722,37 @@ impl NearConfig {
         network_key_pair: KeyFile,
         validator_signer: Option<Arc<dyn ValidatorSigner>>,
     ) -> Self {
+        let listen_addrs: Vec<SocketAddr> = config
+            .network
+            .addr
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(|addr| addr.parse().expect("Failed to parse network address"))
+            .collect();
+        let remote_dns = config.network.proxy.as_ref().map_or(false, |proxy| proxy.remote_dns);
+        let mut boot_nodes = vec![];
+        let mut boot_node_hosts = vec![];
+        for chunk in config.network.boot_nodes.split(',').filter(|chunk| !chunk.is_empty()) {
+            match chunk.parse::<BootNodeHost>() {
+                Ok(boot_node) if remote_dns => boot_node_hosts.push(boot_node),
+                Ok(boot_node) => match boot_node.resolve() {
+                    Ok(peer_info) => boot_nodes.push(peer_info),
+                    Err(err) => {
+                        warn!(target: "near", "Failed to resolve boot node {}: {}", boot_node, err)
+                    }
+                },
+                Err(_) => boot_nodes.push(chunk.try_into().expect("Failed to parse PeerInfo")),
+            }
+        }
+        let public_addrs = resolve_external_addrs(&config.network.external_address);
+        let tier1_proxies = config
+            .network
+            .tier1_proxies
+            .split(',')
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| chunk.try_into().expect("Failed to parse PeerInfo"))
+            .collect();
         NearConfig {
             config: config.clone(),
             client_config: ClientConfig {
@@ -594,29 +791,29 @@ impl NearConfig {
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
                 archive: config.archive,
+                cold_store_path: config.cold_store_path,
                 log_summary_style: config.log_summary_style,
                 gc_blocks_limit: config.gc_blocks_limit,
+                gc_step_time_limit: config.gc_step_time_limit,
+                gc_num_epochs_to_keep: config.gc_num_epochs_to_keep,
+                max_orphans: config.consensus.max_orphans,
+                max_orphan_age: config.consensus.max_orphan_age,
+                optimistic_block_application: config.consensus.optimistic_block_application,
                 view_client_threads: config.view_client_threads,
+                protocol_upgrade_voting_schedule: ProtocolUpgradeVotingSchedule::from_env()
+                    .unwrap_or_else(|err| panic!("{}", err))
+                    .or(config.protocol_upgrade_voting_schedule),
+                watched_accounts: config.watched_accounts,
+                state_sync_external: config.state_sync_external,
+                trusted_checkpoint: config.trusted_checkpoint,
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
                 secret_key: network_key_pair.secret_key,
                 account_id: validator_signer.as_ref().map(|vs| vs.validator_id().clone()),
-                addr: if config.network.addr.is_empty() {
-                    None
-                } else {
-                    Some(config.network.addr.parse().unwrap())
-                },
-                boot_nodes: if config.network.boot_nodes.is_empty() {
-                    vec![]
-                } else {
-                    config
-                        .network
-                        .boot_nodes
-                        .split(',')
-                        .map(|chunk| chunk.try_into().expect("Failed to parse PeerInfo"))
-                        .collect()
-                },
+                addr: listen_addrs.first().cloned(),
+                additional_addrs: listen_addrs.iter().skip(1).cloned().collect(),
+                boot_nodes,
                 handshake_timeout: config.network.handshake_timeout,
                 reconnect_delay: config.network.reconnect_delay,
                 bootstrap_peers_period: Duration::from_secs(60),
@@ -641,11 +838,27 @@ impl NearConfig {
                 blacklist: blacklist_from_iter(config.network.blacklist),
                 outbound_disabled: false,
                 archive: config.archive,
+                transport: config.network.transport,
+                reputation: config.network.reputation,
+                send_queue: config.network.send_queue,
+                proxy: config.network.proxy,
+                boot_node_hosts,
+                bandwidth: config.network.bandwidth,
+                tier1_proxies,
+                public_addrs,
+                encryption: config.network.encryption,
+                access: config.network.access,
+                port_mapping: config.network.port_mapping,
+                discover_external_addr: config.network.discover_external_address,
+                connection_limits: config.network.connection_limits,
             },
             telemetry_config: config.telemetry,
             rpc_config: config.rpc,
             #[cfg(feature = "rosetta_rpc")]
             rosetta_rpc_config: config.rosetta_rpc,
+            #[cfg(feature = "grpc")]
+            grpc_config: config.grpc,
+            metrics_config: config.metrics,
             genesis,
             validator_signer,
         }
@@ -672,6 +885,22 @@ impl NearConfig {
     }
 }
 
+/// Addresses of the comma separated `ip:port` or `host:port` external addresses. Host names are
+/// resolved once at startup, to every address they resolve to, the ones that can't be resolved are
+/// skipped with a warning.
+fn resolve_external_addrs(external_address: &str) -> Vec<SocketAddr> {
+    let mut addrs = vec![];
+    for addr in external_address.split(',').map(str::trim).filter(|addr| !addr.is_empty()) {
+        match addr.to_socket_addrs() {
+            Ok(resolved) => addrs.extend(resolved),
+            Err(err) => {
+                warn!(target: "near", "Failed to resolve external address {}: {}", addr, err)
+            }
+        }
+    }
+    addrs
+}
+
 fn add_protocol_account(records: &mut Vec<StateRecord>) {
     let signer = InMemorySigner::from_seed(
         PROTOCOL_TREASURY_ACCOUNT,
@@ -1047,3 +1276,41 @@ pub fn load_test_config(seed: &str, port: u16, genesis: Genesis) -> NearConfig {
     };
     NearConfig::new(config, genesis, signer.into(), validator_signer)
 }
+
+#[cfg(test)]
+mod tests {
+    use near_client::deprecated_usage;
+
+    use super::*;
+
+    #[test]
+    fn test_report_deprecated_config_fields() {
+        let name = "consensus.reduce_wait_for_missing_block";
+        let count = || {
+            deprecated_usage()
+                .into_iter()
+                .find(|usage| usage.name == name && usage.surface == DeprecatedSurface::ConfigField)
+                .map_or(0, |usage| usage.count)
+        };
+        let initial_count = count();
+        report_deprecated_config_fields(r#"{"consensus": {"min_num_peers": 3}}"#);
+        assert_eq!(count(), initial_count);
+        report_deprecated_config_fields(
+            r#"{"consensus": {"reduce_wait_for_missing_block": {"secs": 0, "nanos": 0}}}"#,
+        );
+        assert_eq!(count(), initial_count + 1);
+        // Deprecated fields are not written to the new config files.
+        let config = serde_json::to_value(&Config::default()).unwrap();
+        assert!(config["consensus"].get("reduce_wait_for_missing_block").is_none());
+    }
+
+    #[test]
+    fn test_resolve_external_addrs() {
+        let addrs = resolve_external_addrs("1.2.3.4:24567, [::1]:24568,localhost:24569,localhost,");
+        assert_eq!(addrs[0], "1.2.3.4:24567".parse().unwrap());
+        assert_eq!(addrs[1], "[::1]:24568".parse().unwrap());
+        // The host name resolves to at least one loopback address, the invalid one is skipped.
+        assert!(addrs.len() > 2);
+        assert!(addrs[2..].iter().all(|addr| addr.ip().is_loopback() && addr.port() == 24569));
+    }
+}
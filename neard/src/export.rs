@@ -0,0 +1,576 @@
+//! `neard export`: writes the chain history stored by the node into CSV or Parquet files, one file
+//! per table, so that it can be loaded into a data warehouse without running an indexer.
+//!
+//! The columns of every table are fixed by `Table::columns`. Nested data (actions, statuses, state
+//! change values) is stored as JSON of the corresponding RPC views, so its format follows the RPC.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use log::info;
+
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, ShardId, StateChangeKind, StateChangesRequest};
+use near_primitives::views::{ActionView, ExecutionStatusView, StateChangeWithCauseView};
+use near_store::Store;
+
+/// Number of blocks read before the rows are written out. Every batch becomes a row group in
+/// Parquet files.
+const BATCH_SIZE: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    #[cfg(feature = "parquet_export")]
+    Parquet,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            #[cfg(feature = "parquet_export")]
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            #[cfg(feature = "parquet_export")]
+            "parquet" => Ok(ExportFormat::Parquet),
+            #[cfg(not(feature = "parquet_export"))]
+            "parquet" => Err("neard is built without the parquet_export feature".to_string()),
+            _ => Err(format!("Unknown export format {}, expected csv or parquet", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKind {
+    Int64,
+    String,
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+}
+
+const fn int64(name: &'static str) -> Column {
+    Column { name, kind: ColumnKind::Int64 }
+}
+
+const fn string(name: &'static str) -> Column {
+    Column { name, kind: ColumnKind::String }
+}
+
+const BLOCK_COLUMNS: &[Column] = &[
+    int64("height"),
+    string("hash"),
+    string("prev_hash"),
+    string("epoch_id"),
+    int64("timestamp_nanosec"),
+    int64("chunks_included"),
+    string("gas_price"),
+    string("total_supply"),
+];
+
+const TRANSACTION_COLUMNS: &[Column] = &[
+    int64("block_height"),
+    string("block_hash"),
+    int64("shard_id"),
+    string("hash"),
+    string("signer_id"),
+    string("public_key"),
+    int64("nonce"),
+    string("receiver_id"),
+    string("actions"),
+];
+
+const EXECUTION_OUTCOME_COLUMNS: &[Column] = &[
+    int64("block_height"),
+    string("block_hash"),
+    int64("shard_id"),
+    string("id"),
+    string("executor_id"),
+    int64("gas_burnt"),
+    string("tokens_burnt"),
+    string("status"),
+    string("receipt_ids"),
+    string("logs"),
+];
+
+const STATE_CHANGE_COLUMNS: &[Column] = &[
+    int64("block_height"),
+    string("block_hash"),
+    string("cause"),
+    string("type"),
+    string("account_id"),
+    string("change"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    Blocks,
+    Transactions,
+    ExecutionOutcomes,
+    StateChanges,
+}
+
+impl Table {
+    pub const ALL: &'static [Table] =
+        &[Table::Blocks, Table::Transactions, Table::ExecutionOutcomes, Table::StateChanges];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::Blocks => "blocks",
+            Table::Transactions => "transactions",
+            Table::ExecutionOutcomes => "execution_outcomes",
+            Table::StateChanges => "state_changes",
+        }
+    }
+
+    pub fn columns(self) -> &'static [Column] {
+        match self {
+            Table::Blocks => BLOCK_COLUMNS,
+            Table::Transactions => TRANSACTION_COLUMNS,
+            Table::ExecutionOutcomes => EXECUTION_OUTCOME_COLUMNS,
+            Table::StateChanges => STATE_CHANGE_COLUMNS,
+        }
+    }
+}
+
+impl FromStr for Table {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Table::ALL
+            .iter()
+            .find(|table| table.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown table {}", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Int64(i64),
+    String(String),
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Int64(value as i64)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&CryptoHash> for Value {
+    fn from(value: &CryptoHash) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+type Row = Vec<Value>;
+
+pub struct ExportOptions {
+    pub from_height: BlockHeight,
+    /// Last height to export, inclusive. By default the height of the head.
+    pub to_height: Option<BlockHeight>,
+    pub output_dir: PathBuf,
+    pub format: ExportFormat,
+    pub tables: Vec<Table>,
+}
+
+trait TableWriter {
+    fn write_rows(&mut self, rows: Vec<Row>) -> Result<(), String>;
+
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+struct CsvWriter {
+    file: BufWriter<File>,
+}
+
+impl CsvWriter {
+    fn new(path: &Path, columns: &[Column]) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let mut writer = Self { file: BufWriter::new(file) };
+        let header = columns.iter().map(|column| column.name.to_string()).collect::<Vec<_>>();
+        writer.write_line(&header)?;
+        Ok(writer)
+    }
+
+    fn write_line(&mut self, fields: &[String]) -> Result<(), String> {
+        let line = fields.iter().map(|field| csv_escape(field)).collect::<Vec<_>>().join(",");
+        writeln!(self.file, "{}", line).map_err(|err| err.to_string())
+    }
+}
+
+impl TableWriter for CsvWriter {
+    fn write_rows(&mut self, rows: Vec<Row>) -> Result<(), String> {
+        for row in rows {
+            let fields = row
+                .into_iter()
+                .map(|value| match value {
+                    Value::Int64(value) => value.to_string(),
+                    Value::String(value) => value,
+                })
+                .collect::<Vec<_>>();
+            self.write_line(&fields)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.file.flush().map_err(|err| err.to_string())
+    }
+}
+
+/// Quotes the field if it contains a separator, a quote or a line break, as in RFC 4180.
+fn csv_escape(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(feature = "parquet_export")]
+mod parquet_writer {
+    use std::fs::File;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    use super::{Column, ColumnKind, Row, TableWriter, Value};
+
+    pub struct ParquetWriter {
+        writer: SerializedFileWriter<File>,
+    }
+
+    impl ParquetWriter {
+        pub fn new(path: &Path, name: &str, columns: &[Column]) -> Result<Self, String> {
+            let fields = columns
+                .iter()
+                .map(|column| match column.kind {
+                    ColumnKind::Int64 => format!("REQUIRED INT64 {};", column.name),
+                    ColumnKind::String => format!("REQUIRED BYTE_ARRAY {} (UTF8);", column.name),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let schema = parse_message_type(&format!("message {} {{ {} }}", name, fields))
+                .map_err(|err| err.to_string())?;
+            let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let writer = SerializedFileWriter::new(
+                file,
+                Rc::new(schema),
+                Rc::new(WriterProperties::builder().build()),
+            )
+            .map_err(|err| err.to_string())?;
+            Ok(Self { writer })
+        }
+    }
+
+    impl TableWriter for ParquetWriter {
+        fn write_rows(&mut self, rows: Vec<Row>) -> Result<(), String> {
+            if rows.is_empty() {
+                return Ok(());
+            }
+            let mut row_group_writer =
+                self.writer.next_row_group().map_err(|err| err.to_string())?;
+            let mut index = 0;
+            while let Some(mut column_writer) =
+                row_group_writer.next_column().map_err(|err| err.to_string())?
+            {
+                let values = rows.iter().map(|row| &row[index]);
+                match column_writer {
+                    ColumnWriter::Int64ColumnWriter(ref mut writer) => {
+                        let values = values
+                            .map(|value| match value {
+                                Value::Int64(value) => *value,
+                                Value::String(_) => unreachable!("column type is fixed"),
+                            })
+                            .collect::<Vec<_>>();
+                        writer.write_batch(&values, None, None).map_err(|err| err.to_string())?;
+                    }
+                    ColumnWriter::ByteArrayColumnWriter(ref mut writer) => {
+                        let values = values
+                            .map(|value| match value {
+                                Value::String(value) => ByteArray::from(value.as_str()),
+                                Value::Int64(_) => unreachable!("column type is fixed"),
+                            })
+                            .collect::<Vec<_>>();
+                        writer.write_batch(&values, None, None).map_err(|err| err.to_string())?;
+                    }
+                    _ => unreachable!("only INT64 and BYTE_ARRAY columns are used"),
+                }
+                row_group_writer.close_column(column_writer).map_err(|err| err.to_string())?;
+                index += 1;
+            }
+            self.writer.close_row_group(row_group_writer).map_err(|err| err.to_string())
+        }
+
+        fn finish(mut self: Box<Self>) -> Result<(), String> {
+            self.writer.close().map_err(|err| err.to_string())
+        }
+    }
+}
+
+fn create_writer(options: &ExportOptions, table: Table) -> Result<Box<dyn TableWriter>, String> {
+    let path = options.output_dir.join(format!("{}.{}", table.name(), options.format.extension()));
+    Ok(match options.format {
+        ExportFormat::Csv => Box::new(CsvWriter::new(&path, table.columns())?),
+        #[cfg(feature = "parquet_export")]
+        ExportFormat::Parquet => {
+            Box::new(parquet_writer::ParquetWriter::new(&path, table.name(), table.columns())?)
+        }
+    })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Failed to serialize")
+}
+
+/// Rows of the tables that are read per shard: transactions and execution outcomes of the shard
+/// in the given blocks.
+fn read_shard(
+    chain_store: &mut ChainStore,
+    shard_id: ShardId,
+    blocks: &[(BlockHeight, CryptoHash)],
+) -> Result<(Vec<Row>, Vec<Row>), String> {
+    let mut transactions = vec![];
+    let mut outcomes = vec![];
+    for (height, block_hash) in blocks {
+        let block = chain_store.get_block(block_hash).map_err(|err| err.to_string())?.clone();
+        let chunks = block.chunks();
+        if shard_id as usize >= chunks.len() {
+            continue;
+        }
+        let chunk_header = &chunks[shard_id as usize];
+        if chunk_header.height_included() == *height {
+            let chunk =
+                chain_store.get_chunk(&chunk_header.chunk_hash()).map_err(|err| err.to_string())?;
+            for tx in chunk.transactions() {
+                let actions = tx
+                    .transaction
+                    .actions
+                    .iter()
+                    .cloned()
+                    .map(ActionView::from)
+                    .collect::<Vec<_>>();
+                transactions.push(vec![
+                    (*height).into(),
+                    block_hash.into(),
+                    shard_id.into(),
+                    (&tx.get_hash()).into(),
+                    tx.transaction.signer_id.clone().into(),
+                    tx.transaction.public_key.to_string().into(),
+                    tx.transaction.nonce.into(),
+                    tx.transaction.receiver_id.clone().into(),
+                    to_json(&actions).into(),
+                ]);
+            }
+        }
+        let outcome_ids = chain_store
+            .get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)
+            .map_err(|err| err.to_string())?;
+        for outcome_id in outcome_ids {
+            let outcome = chain_store
+                .get_outcomes_by_id(&outcome_id)
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .find(|outcome| &outcome.block_hash == block_hash)
+                .ok_or_else(|| format!("Missing outcome {} in block {}", outcome_id, block_hash))?
+                .outcome_with_id
+                .outcome;
+            outcomes.push(vec![
+                (*height).into(),
+                block_hash.into(),
+                shard_id.into(),
+                (&outcome_id).into(),
+                outcome.executor_id.clone().into(),
+                outcome.gas_burnt.into(),
+                outcome.tokens_burnt.to_string().into(),
+                to_json(&ExecutionStatusView::from(outcome.status)).into(),
+                to_json(&outcome.receipt_ids).into(),
+                to_json(&outcome.logs).into(),
+            ]);
+        }
+    }
+    Ok((transactions, outcomes))
+}
+
+fn read_state_changes(
+    chain_store: &mut ChainStore,
+    height: BlockHeight,
+    block_hash: &CryptoHash,
+) -> Result<Vec<Row>, String> {
+    let kinds =
+        chain_store.get_state_changes_in_block(block_hash).map_err(|err| err.to_string())?;
+    let mut account_ids = vec![];
+    for kind in kinds {
+        let account_id = match kind {
+            StateChangeKind::AccountTouched { account_id }
+            | StateChangeKind::AccessKeyTouched { account_id }
+            | StateChangeKind::DataTouched { account_id }
+            | StateChangeKind::ContractCodeTouched { account_id } => account_id,
+        };
+        if !account_ids.contains(&account_id) {
+            account_ids.push(account_id);
+        }
+    }
+    let changes = chain_store
+        .get_state_changes(block_hash, &StateChangesRequest::AllChanges { account_ids })
+        .map_err(|err| err.to_string())?;
+    let mut rows = vec![];
+    for change in changes {
+        let view = StateChangeWithCauseView::from(change);
+        let value = serde_json::to_value(&view.value).expect("Failed to serialize");
+        let change = &value["change"];
+        rows.push(vec![
+            height.into(),
+            block_hash.into(),
+            to_json(&view.cause).into(),
+            value["type"].as_str().unwrap_or_default().to_string().into(),
+            change["account_id"].as_str().unwrap_or_default().to_string().into(),
+            to_json(change).into(),
+        ]);
+    }
+    Ok(rows)
+}
+
+/// Exports the blocks in the range of heights, reading the shards in parallel. Heights without a
+/// block on the canonical chain are skipped.
+pub fn export_chain(
+    store: Arc<Store>,
+    genesis_height: BlockHeight,
+    options: &ExportOptions,
+) -> Result<(), String> {
+    let mut chain_store = ChainStore::new(store.clone(), genesis_height);
+    let to_height = match options.to_height {
+        Some(to_height) => to_height,
+        None => chain_store.head().map_err(|err| err.to_string())?.height,
+    };
+    std::fs::create_dir_all(&options.output_dir).map_err(|err| err.to_string())?;
+    let mut writers = options
+        .tables
+        .iter()
+        .map(|table| Ok((*table, create_writer(options, *table)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let exports = |table| options.tables.contains(&table);
+
+    let mut batch_start = options.from_height;
+    while batch_start <= to_height {
+        let batch_end = std::cmp::min(batch_start + BATCH_SIZE - 1, to_height);
+        let mut blocks = vec![];
+        let mut block_rows = vec![];
+        let mut state_change_rows = vec![];
+        let mut num_shards = 0;
+        for height in batch_start..=batch_end {
+            let block_hash = match chain_store.get_block_hash_by_height(height) {
+                Ok(block_hash) => block_hash,
+                Err(_) => continue,
+            };
+            let block = chain_store.get_block(&block_hash).map_err(|err| err.to_string())?;
+            let header = block.header();
+            num_shards = std::cmp::max(num_shards, block.chunks().len() as ShardId);
+            block_rows.push(vec![
+                height.into(),
+                (&block_hash).into(),
+                header.prev_hash().into(),
+                (&header.epoch_id().0).into(),
+                header.raw_timestamp().into(),
+                header.chunks_included().into(),
+                header.gas_price().to_string().into(),
+                header.total_supply().to_string().into(),
+            ]);
+            if exports(Table::StateChanges) {
+                state_change_rows.extend(read_state_changes(
+                    &mut chain_store,
+                    height,
+                    &block_hash,
+                )?);
+            }
+            blocks.push((height, block_hash));
+        }
+
+        let mut transaction_rows = vec![];
+        let mut outcome_rows = vec![];
+        if exports(Table::Transactions) || exports(Table::ExecutionOutcomes) {
+            let blocks = Arc::new(blocks);
+            let readers = (0..num_shards)
+                .map(|shard_id| {
+                    let store = store.clone();
+                    let blocks = blocks.clone();
+                    thread::spawn(move || {
+                        let mut chain_store = ChainStore::new(store, genesis_height);
+                        read_shard(&mut chain_store, shard_id, &blocks)
+                    })
+                })
+                .collect::<Vec<_>>();
+            for reader in readers {
+                let (transactions, outcomes) =
+                    reader.join().map_err(|_| "Shard reader panicked".to_string())??;
+                transaction_rows.extend(transactions);
+                outcome_rows.extend(outcomes);
+            }
+        }
+
+        for (table, writer) in writers.iter_mut() {
+            let rows = match table {
+                Table::Blocks => std::mem::take(&mut block_rows),
+                Table::Transactions => std::mem::take(&mut transaction_rows),
+                Table::ExecutionOutcomes => std::mem::take(&mut outcome_rows),
+                Table::StateChanges => std::mem::take(&mut state_change_rows),
+            };
+            writer.write_rows(rows)?;
+        }
+        info!(target: "export", "Exported blocks #{} to #{}", batch_start, batch_end);
+        batch_start = batch_end + 1;
+    }
+
+    for (_, writer) in writers {
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("alice.near"), "alice.near");
+        assert_eq!(csv_escape("[\"a\",\"b\"]"), "\"[\"\"a\"\",\"\"b\"\"]\"");
+        assert_eq!(csv_escape("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn test_parse_table() {
+        for table in Table::ALL {
+            assert_eq!(table.name().parse::<Table>(), Ok(*table));
+        }
+        assert!("receipts".parse::<Table>().is_err());
+    }
+}
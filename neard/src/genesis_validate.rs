@@ -1,6 +1,7 @@
 use near_chain_configs::Genesis;
 use near_crypto::key_conversion::is_valid_staking_key;
 use near_primitives::state_record::StateRecord;
+use near_primitives::utils::is_valid_account_id;
 use num_rational::Rational;
 use std::collections::{HashMap, HashSet};
 
@@ -99,13 +100,31 @@ pub fn validate_genesis(genesis: &Genesis) {
         genesis.config.gas_price_adjustment_rate < Rational::from_integer(1),
         "Gas price adjustment rate must be less than 1"
     );
+    let mut prev_protocol_version = genesis.config.protocol_version;
+    for upgrade in genesis.config.block_limits_upgrades.iter() {
+        assert!(
+            upgrade.protocol_version > prev_protocol_version,
+            "Block limits upgrades must be ordered by protocol version and be above genesis protocol version"
+        );
+        assert!(
+            upgrade.gas_limit > 0 && upgrade.max_block_size > 0,
+            "Block limits must be positive"
+        );
+        prev_protocol_version = upgrade.protocol_version;
+    }
+    assert!(
+        is_valid_account_id(
+            &genesis.config.runtime_config.account_creation_config.registrar_account_id
+        ),
+        "Registrar account id is not valid"
+    );
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use near_chain_configs::GenesisRecords;
+    use near_chain_configs::{BlockLimitsUpgrade, GenesisRecords};
     use near_crypto::{KeyType, PublicKey};
     use near_primitives::account::{AccessKey, Account};
     use near_primitives::types::AccountInfo;
@@ -237,4 +256,31 @@ mod test {
         ]);
         validate_genesis(&genesis);
     }
+
+    #[test]
+    #[should_panic(expected = "Block limits upgrades must be ordered by protocol version")]
+    fn test_unordered_block_limits_upgrades() {
+        let mut genesis = Genesis::default();
+        genesis.config.validators = vec![AccountInfo {
+            account_id: "test".to_string(),
+            public_key: VALID_ED25519_RISTRETTO_KEY.parse().unwrap(),
+            amount: 10,
+        }];
+        genesis.config.total_supply = 110;
+        genesis.records = GenesisRecords(vec![StateRecord::Account {
+            account_id: "test".to_string(),
+            account: Account {
+                amount: 100,
+                locked: 10,
+                code_hash: Default::default(),
+                storage_usage: 0,
+            },
+        }]);
+        genesis.config.protocol_version = 40;
+        genesis.config.block_limits_upgrades = vec![
+            BlockLimitsUpgrade { protocol_version: 42, gas_limit: 2, max_block_size: 100 },
+            BlockLimitsUpgrade { protocol_version: 41, gas_limit: 3, max_block_size: 100 },
+        ];
+        validate_genesis(&genesis);
+    }
 }
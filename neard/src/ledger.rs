@@ -0,0 +1,294 @@
+//! Minimal client for the NEAR Ledger app, used by `neard tx sign --ledger`.
+//!
+//! Talks the app's APDU protocol over the Ledger HID framing: the transaction is sent in chunks
+//! together with the BIP32 derivation path and the device returns an ED25519 signature.
+use std::ffi::CString;
+
+use borsh::BorshSerialize;
+
+use near_crypto::{KeyType, PublicKey, Signature};
+use near_primitives::transaction::{SignedTransaction, Transaction};
+
+/// Default derivation path used by NEAR wallets.
+pub const DEFAULT_HD_PATH: &str = "44'/397'/0'/0'/1'";
+
+const LEDGER_VENDOR_ID: u16 = 0x2c97;
+const LEDGER_USAGE_PAGE: u16 = 0xffa0;
+const HID_PACKET_SIZE: usize = 64;
+const HID_CHANNEL: u16 = 0x0101;
+const HID_TAG_APDU: u8 = 0x05;
+const HID_READ_TIMEOUT_MS: i32 = 60_000;
+
+const CLA: u8 = 0x80;
+const INS_SIGN: u8 = 0x02;
+const INS_GET_PUBLIC_KEY: u8 = 0x04;
+const P1_MORE: u8 = 0x00;
+const P1_LAST: u8 = 0x80;
+/// Network id passed as P2, `W` is used by all NEAR networks.
+const NETWORK_ID: u8 = b'W';
+/// Max size of the payload of a single sign APDU.
+const SIGN_CHUNK_SIZE: usize = 128;
+const SW_OK: u16 = 0x9000;
+const SW_REJECTED: u16 = 0x6985;
+
+#[derive(Debug)]
+pub enum LedgerError {
+    DeviceNotFound,
+    Hid(String),
+    InvalidPath(String),
+    InvalidResponse(String),
+    /// User rejected the operation on the device.
+    Rejected,
+    /// Device returned a non-success status word.
+    Status(u16),
+}
+
+impl std::fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LedgerError::DeviceNotFound => {
+                write!(f, "Ledger device not found, make sure the NEAR app is open")
+            }
+            LedgerError::Hid(err) => write!(f, "HID error: {}", err),
+            LedgerError::InvalidPath(path) => write!(f, "Invalid derivation path: {}", path),
+            LedgerError::InvalidResponse(err) => write!(f, "Invalid response: {}", err),
+            LedgerError::Rejected => write!(f, "Operation was rejected on the device"),
+            LedgerError::Status(status) => write!(f, "Device returned status {:#06x}", status),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+/// Exchanges raw APDUs with the device.
+pub trait LedgerTransport {
+    /// Sends the APDU and returns the response including the trailing status word.
+    fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, LedgerError>;
+}
+
+/// Parses `44'/397'/0'/0'/1'` into its BIP32 serialization: big endian u32 per component.
+pub fn hd_path_to_bytes(path: &str) -> Result<Vec<u8>, LedgerError> {
+    let mut result = vec![];
+    for component in path.split('/') {
+        let hardened = component.ends_with('\'');
+        let number = component.trim_end_matches('\'');
+        let mut index: u32 =
+            number.parse().map_err(|_| LedgerError::InvalidPath(path.to_string()))?;
+        if index >= 0x8000_0000 {
+            return Err(LedgerError::InvalidPath(path.to_string()));
+        }
+        if hardened {
+            index |= 0x8000_0000;
+        }
+        result.extend_from_slice(&index.to_be_bytes());
+    }
+    Ok(result)
+}
+
+fn build_apdu(ins: u8, p1: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![CLA, ins, p1, NETWORK_ID, data.len() as u8];
+    apdu.extend_from_slice(data);
+    apdu
+}
+
+/// Strips and checks the status word of the response.
+fn check_response(mut response: Vec<u8>) -> Result<Vec<u8>, LedgerError> {
+    if response.len() < 2 {
+        return Err(LedgerError::InvalidResponse("response is too short".to_string()));
+    }
+    let status_word = response.split_off(response.len() - 2);
+    match u16::from_be_bytes([status_word[0], status_word[1]]) {
+        SW_OK => Ok(response),
+        SW_REJECTED => Err(LedgerError::Rejected),
+        status => Err(LedgerError::Status(status)),
+    }
+}
+
+/// Returns the public key for the given derivation path.
+pub fn get_public_key(
+    transport: &dyn LedgerTransport,
+    hd_path: &str,
+) -> Result<PublicKey, LedgerError> {
+    let apdu = build_apdu(INS_GET_PUBLIC_KEY, 0, &hd_path_to_bytes(hd_path)?);
+    let response = check_response(transport.exchange(&apdu)?)?;
+    PublicKey::from_parts(KeyType::ED25519, &response)
+        .map_err(|_| LedgerError::InvalidResponse("invalid public key length".to_string()))
+}
+
+/// Signs the borsh-serialized transaction on the device.
+pub fn sign_transaction(
+    transport: &dyn LedgerTransport,
+    hd_path: &str,
+    transaction: Transaction,
+) -> Result<SignedTransaction, LedgerError> {
+    let mut data = hd_path_to_bytes(hd_path)?;
+    data.extend(transaction.try_to_vec().expect("Failed to serialize"));
+
+    let chunks: Vec<_> = data.chunks(SIGN_CHUNK_SIZE).collect();
+    let mut response = vec![];
+    for (i, chunk) in chunks.iter().enumerate() {
+        let p1 = if i + 1 == chunks.len() { P1_LAST } else { P1_MORE };
+        response = check_response(transport.exchange(&build_apdu(INS_SIGN, p1, chunk))?)?;
+    }
+    let signature = Signature::from_parts(near_crypto::KeyType::ED25519, &response)
+        .map_err(|err| LedgerError::InvalidResponse(err.to_string()))?;
+    Ok(SignedTransaction::new(signature, transaction))
+}
+
+/// Splits the APDU into HID packets: channel, tag, sequence index and, in the first packet,
+/// the total APDU length, followed by the payload padded with zeroes.
+fn wrap_apdu(apdu: &[u8]) -> Vec<[u8; HID_PACKET_SIZE]> {
+    let mut data = (apdu.len() as u16).to_be_bytes().to_vec();
+    data.extend_from_slice(apdu);
+    data.chunks(HID_PACKET_SIZE - 5)
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut packet = [0u8; HID_PACKET_SIZE];
+            packet[0..2].copy_from_slice(&HID_CHANNEL.to_be_bytes());
+            packet[2] = HID_TAG_APDU;
+            packet[3..5].copy_from_slice(&(sequence as u16).to_be_bytes());
+            packet[5..5 + chunk.len()].copy_from_slice(chunk);
+            packet
+        })
+        .collect()
+}
+
+/// Transport over USB HID.
+pub struct HidTransport {
+    device: hidapi::HidDevice,
+}
+
+impl HidTransport {
+    /// Opens the first connected Ledger device.
+    pub fn open() -> Result<Self, LedgerError> {
+        let api = hidapi::HidApi::new().map_err(|err| LedgerError::Hid(err.to_string()))?;
+        let path: CString = api
+            .device_list()
+            .find(|device| {
+                device.vendor_id() == LEDGER_VENDOR_ID
+                    && (device.usage_page() == LEDGER_USAGE_PAGE || device.interface_number() == 0)
+            })
+            .map(|device| device.path().to_owned())
+            .ok_or(LedgerError::DeviceNotFound)?;
+        let device = api.open_path(&path).map_err(|err| LedgerError::Hid(err.to_string()))?;
+        Ok(Self { device })
+    }
+
+    fn read_packet(&self, sequence: u16) -> Result<[u8; HID_PACKET_SIZE], LedgerError> {
+        let mut packet = [0u8; HID_PACKET_SIZE];
+        let size = self
+            .device
+            .read_timeout(&mut packet, HID_READ_TIMEOUT_MS)
+            .map_err(|err| LedgerError::Hid(err.to_string()))?;
+        if size < 5
+            || packet[0..2] != HID_CHANNEL.to_be_bytes()
+            || packet[2] != HID_TAG_APDU
+            || packet[3..5] != sequence.to_be_bytes()
+        {
+            return Err(LedgerError::InvalidResponse("unexpected HID packet".to_string()));
+        }
+        Ok(packet)
+    }
+}
+
+impl LedgerTransport for HidTransport {
+    fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, LedgerError> {
+        for packet in wrap_apdu(apdu) {
+            // The first byte is the HID report id.
+            let mut report = vec![0u8];
+            report.extend_from_slice(&packet);
+            self.device.write(&report).map_err(|err| LedgerError::Hid(err.to_string()))?;
+        }
+
+        let first = self.read_packet(0)?;
+        let length = u16::from_be_bytes([first[5], first[6]]) as usize;
+        let mut response = first[7..].to_vec();
+        let mut sequence = 1;
+        while response.len() < length {
+            response.extend_from_slice(&self.read_packet(sequence)?[5..]);
+            sequence += 1;
+        }
+        response.truncate(length);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::{Action, TransferAction};
+
+    use super::*;
+
+    /// Emulates the NEAR app with an in-memory key.
+    struct MockTransport {
+        signer: InMemorySigner,
+        buffer: RefCell<Vec<u8>>,
+    }
+
+    impl LedgerTransport for MockTransport {
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, LedgerError> {
+            assert_eq!(apdu[0], CLA);
+            assert_eq!(apdu[4] as usize, apdu.len() - 5);
+            let mut response = match apdu[1] {
+                INS_GET_PUBLIC_KEY => self.signer.public_key().key_data().to_vec(),
+                INS_SIGN => {
+                    self.buffer.borrow_mut().extend_from_slice(&apdu[5..]);
+                    if apdu[2] == P1_LAST {
+                        let data = self.buffer.borrow();
+                        let hash = near_primitives::hash::hash(&data[20..]);
+                        match self.signer.sign(hash.as_ref()) {
+                            Signature::ED25519(signature) => signature.to_bytes().to_vec(),
+                            _ => unreachable!(),
+                        }
+                    } else {
+                        vec![]
+                    }
+                }
+                _ => unreachable!(),
+            };
+            response.extend_from_slice(&SW_OK.to_be_bytes());
+            Ok(response)
+        }
+    }
+
+    #[test]
+    fn test_hd_path_to_bytes() {
+        assert_eq!(
+            hd_path_to_bytes("44'/397'/0'/0'/1'").unwrap(),
+            vec![0x80, 0, 0, 44, 0x80, 0, 0x01, 0x8d, 0x80, 0, 0, 0, 0x80, 0, 0, 0, 0x80, 0, 0, 1]
+        );
+        assert_eq!(hd_path_to_bytes("1/2").unwrap(), vec![0, 0, 0, 1, 0, 0, 0, 2]);
+        assert!(hd_path_to_bytes("44'/abc").is_err());
+    }
+
+    #[test]
+    fn test_wrap_apdu() {
+        let packets = wrap_apdu(&[7u8; 100]);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(&packets[0][..7], &[0x01, 0x01, 0x05, 0, 0, 0, 100]);
+        assert_eq!(&packets[1][..5], &[0x01, 0x01, 0x05, 0, 1]);
+    }
+
+    #[test]
+    fn test_sign_transaction() {
+        let transport = MockTransport {
+            signer: InMemorySigner::from_seed("test", KeyType::ED25519, "test"),
+            buffer: RefCell::new(vec![]),
+        };
+        let public_key = get_public_key(&transport, DEFAULT_HD_PATH).unwrap();
+        let transaction = Transaction {
+            signer_id: "test".to_string(),
+            public_key: public_key.clone(),
+            nonce: 1,
+            receiver_id: "other".to_string(),
+            block_hash: CryptoHash::default(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 1 }); 20],
+        };
+        let signed = sign_transaction(&transport, DEFAULT_HD_PATH, transaction).unwrap();
+        assert!(signed.signature.verify(signed.get_hash().as_ref(), &public_key));
+    }
+}
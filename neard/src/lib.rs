@@ -2,38 +2,52 @@ use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 
-use actix::{Actor, Addr, Arbiter};
-use log::{error, info};
+use actix::{Actor, Addr, Arbiter, System};
+use actix_web::dev::Server;
+use log::{error, info, warn};
 use tracing::trace;
 
-use near_chain::ChainGenesis;
+use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode, RuntimeAdapter};
 #[cfg(feature = "adversarial")]
 use near_client::AdversarialControls;
 use near_client::{start_client, start_view_client, ClientActor, ViewClientActor};
+#[cfg(feature = "grpc")]
+use near_grpc::start_grpc;
 use near_jsonrpc::start_http;
 use near_network::{NetworkRecipient, PeerManagerActor};
+use near_primitives::block::Tip;
+use near_primitives::types::NumBlocks;
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::start_rosetta_rpc;
-use near_store::{create_store, Store};
+use near_store::{create_split_store, create_store, Store};
 use near_telemetry::TelemetryActor;
 
 pub use crate::config::{init_configs, load_config, load_test_config, NearConfig, NEAR_BASE};
-use crate::migrations::migrate_12_to_13;
+use crate::metrics::{register_db_metrics, start_metrics_server};
+use crate::migrations::migrate_store;
+pub use crate::migrations::MigrationOptions;
 pub use crate::runtime::NightshadeRuntime;
-use near_store::migrations::{
-    fill_col_outcomes_by_hash, fill_col_transaction_refcount, get_store_version, migrate_10_to_11,
-    migrate_11_to_12, migrate_13_to_14, migrate_14_to_15, migrate_6_to_7, migrate_7_to_8,
-    migrate_8_to_9, migrate_9_to_10, set_store_version,
-};
+pub use crate::shard_tracker::account_id_to_shard_id;
+use near_primitives::version::DbVersion;
+use near_store::migrations::{get_store_version, set_store_version, set_store_version_if_missing};
 
 pub mod config;
+pub mod export;
 pub mod genesis_validate;
+#[cfg(feature = "ledger")]
+pub mod ledger;
+pub mod metrics;
 mod migrations;
 mod runtime;
 mod shard_tracker;
+pub mod status;
 
 const STORE_PATH: &str = "data";
 
+/// Database version of the first cold databases, which were not versioned yet. Cold databases
+/// never existed before it, so only the later migration steps run on them.
+const COLD_STORE_MIN_VERSION: DbVersion = 20;
+
 pub fn store_path_exists<P: AsRef<Path>>(path: P) -> bool {
     fs::canonicalize(path).is_ok()
 }
@@ -63,7 +77,7 @@ pub fn get_default_home() -> String {
 }
 
 /// Function checks current version of the database and applies migrations to the database.
-pub fn apply_store_migrations(path: &String, near_config: &NearConfig) {
+pub fn apply_store_migrations(path: &String, near_config: &NearConfig, options: &MigrationOptions) {
     let db_version = get_store_version(path);
     if db_version > near_primitives::version::DB_VERSION {
         error!(target: "near", "DB version {} is created by a newer version of neard, please update neard or delete data", db_version);
@@ -73,141 +87,159 @@ pub fn apply_store_migrations(path: &String, near_config: &NearConfig) {
         return;
     }
 
-    // Add migrations here based on `db_version`.
-    if db_version <= 1 {
-        // version 1 => 2: add gc column
-        // Does not need to do anything since open db with option `create_missing_column_families`
-        // Nevertheless need to bump db version, because db_version 1 binary can't open db_version 2 db
-        info!(target: "near", "Migrate DB from version 1 to 2");
-        let store = create_store(&path);
-        set_store_version(&store, 2);
-    }
-    if db_version <= 2 {
-        // version 2 => 3: add ColOutcomesByBlockHash + rename LastComponentNonce -> ColLastComponentNonce
-        // The column number is the same, so we don't need additional updates
-        info!(target: "near", "Migrate DB from version 2 to 3");
-        let store = create_store(&path);
-        fill_col_outcomes_by_hash(&store);
-        set_store_version(&store, 3);
-    }
-    if db_version <= 3 {
-        // version 3 => 4: add ColTransactionRefCount
-        info!(target: "near", "Migrate DB from version 3 to 4");
-        let store = create_store(&path);
-        fill_col_transaction_refcount(&store);
-        set_store_version(&store, 4);
-    }
-    if db_version <= 4 {
-        info!(target: "near", "Migrate DB from version 4 to 5");
-        // version 4 => 5: add ColProcessedBlockHeights
-        // we don't need to backfill the old heights since at worst we will just process some heights
-        // again.
-        let store = create_store(&path);
-        set_store_version(&store, 5);
-    }
-    if db_version <= 5 {
-        info!(target: "near", "Migrate DB from version 5 to 6");
-        // version 5 => 6: add merge operator to ColState
-        // we don't have merge records before so old storage works
-        let store = create_store(&path);
-        set_store_version(&store, 6);
-    }
-    if db_version <= 6 {
-        info!(target: "near", "Migrate DB from version 6 to 7");
-        // version 6 => 7:
-        // - make ColState use 8 bytes for refcount (change to merge operator)
-        // - move ColTransactionRefCount into ColTransactions
-        // - make ColReceiptIdToShardId refcounted
-        migrate_6_to_7(path);
-    }
-    if db_version <= 7 {
-        info!(target: "near", "Migrate DB from version 7 to 8");
-        // version 7 => 8:
-        // delete values in column `StateColParts`
-        migrate_7_to_8(path);
-    }
-    if db_version <= 8 {
-        info!(target: "near", "Migrate DB from version 8 to 9");
-        // version 8 => 9:
-        // Repair `ColTransactions`, `ColReceiptIdToShardId`
-        migrate_8_to_9(path);
-    }
-    if db_version <= 9 {
-        info!(target: "near", "Migrate DB from version 9 to 10");
-        // version 9 => 10;
-        // populate partial encoded chunks for chunks that exist in storage
-        migrate_9_to_10(path, near_config.client_config.archive);
-    }
-    if db_version <= 10 {
-        info!(target: "near", "Migrate DB from version 10 to 11");
-        // version 10 => 11
-        // Add final head
-        migrate_10_to_11(path);
-    }
-    if db_version <= 11 {
-        info!(target: "near", "Migrate DB from version 11 to 12");
-        // version 11 => 12;
-        // populate ColReceipts with existing receipts
-        migrate_11_to_12(path);
-    }
-    if db_version <= 12 {
-        info!(target: "near", "Migrate DB from version 12 to 13");
-        // version 12 => 13;
-        // migrate ColTransactionResult to fix the inconsistencies there
-        migrate_12_to_13(path, near_config);
-    }
-    if db_version <= 13 {
-        info!(target: "near", "Migrate DB from version 13 to 14");
-        // version 13 => 14;
-        // store versioned enums for shard chunks
-        migrate_13_to_14(path);
-    }
-    if db_version <= 14 {
-        info!(target: "near", "Migrate DB from version 14 to 15");
-        // version 14 => 15;
-        // Change ColOutcomesByBlockHash to be ordered within each shard
-        migrate_14_to_15(path);
+    migrate_store(path, near_config, options);
+
+    if !options.dry_run {
+        let db_version = get_store_version(path);
+        debug_assert_eq!(db_version, near_primitives::version::DB_VERSION);
     }
-    if db_version <= 15 {
-        info!(target: "near", "Migrate DB from version 15 to 16");
-        // version 15 => 16: add column for compiled contracts
-        let store = create_store(&path);
-        set_store_version(&store, 16);
+}
+
+/// Path of the cold database of an archival node with split storage, if it has one.
+pub fn get_cold_store_path(home_dir: &Path, near_config: &NearConfig) -> Option<String> {
+    let client_config = &near_config.client_config;
+    match &client_config.cold_store_path {
+        Some(cold_store_path) if client_config.archive => {
+            Some(home_dir.join(cold_store_path).to_str().unwrap().to_owned())
+        }
+        Some(_) => {
+            warn!(target: "near", "cold_store_path is only used by archival nodes, ignoring it");
+            None
+        }
+        None => None,
     }
+}
 
-    let db_version = get_store_version(path);
-    debug_assert_eq!(db_version, near_primitives::version::DB_VERSION);
+/// Applies the migrations to the cold database of a split storage. It has its own version and is
+/// migrated separately from the hot one.
+pub fn apply_cold_store_migrations(
+    cold_path: &String,
+    near_config: &NearConfig,
+    options: &MigrationOptions,
+) {
+    set_store_version_if_missing(cold_path, COLD_STORE_MIN_VERSION);
+    apply_store_migrations(cold_path, near_config, options);
 }
 
 pub fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> Arc<Store> {
     let path = get_store_path(home_dir);
     let store_exists = store_path_exists(&path);
     if store_exists {
-        apply_store_migrations(&path, near_config);
+        apply_store_migrations(&path, near_config, &MigrationOptions::default());
     }
-    let store = create_store(&path);
+    let store = match get_cold_store_path(home_dir, near_config) {
+        Some(cold_path) => {
+            info!(target: "near", "Opening cold store database at {:?}", cold_path);
+            if store_path_exists(&cold_path) {
+                apply_cold_store_migrations(&cold_path, near_config, &MigrationOptions::default());
+            } else {
+                set_store_version(&create_store(&cold_path), near_primitives::version::DB_VERSION);
+            }
+            create_split_store(&path, &cold_path)
+        }
+        None => create_store(&path),
+    };
     if !store_exists {
         set_store_version(&store, near_primitives::version::DB_VERSION);
     }
     store
 }
 
-pub fn start_with_config(
+/// Rewinds the head of a stopped node by up to `num_blocks` blocks, past the last final block if
+/// needed, which the node syncs again once started. See `Chain::undo_blocks`.
+pub fn undo_blocks(
     home_dir: &Path,
-    config: NearConfig,
-) -> (Addr<ClientActor>, Addr<ViewClientActor>, Vec<Arbiter>) {
+    near_config: &NearConfig,
+    num_blocks: NumBlocks,
+) -> Result<Tip, near_chain::Error> {
+    let store = init_and_migrate_store(home_dir, near_config);
+    let runtime = Arc::new(NightshadeRuntime::new(
+        home_dir,
+        store,
+        &near_config.genesis,
+        near_config.client_config.tracked_accounts.clone(),
+        near_config.client_config.tracked_shards.clone(),
+    ));
+    let chain_genesis = ChainGenesis::from(&near_config.genesis);
+    let mut chain = Chain::new(runtime.clone(), &chain_genesis, DoomslugThresholdMode::TwoThirds)?;
+    chain.undo_blocks(runtime.get_tries(), num_blocks, true)
+}
+
+/// Options of a node started with `start`.
+pub struct NodeOptions {
+    /// Whether to serve JSON RPC at `NearConfig::rpc_config`.
+    pub enable_rpc: bool,
+    /// Telemetry actor to report to. By default a new one is started with
+    /// `NearConfig::telemetry_config`.
+    pub telemetry: Option<Addr<TelemetryActor>>,
+}
+
+impl Default for NodeOptions {
+    fn default() -> Self {
+        Self { enable_rpc: true, telemetry: None }
+    }
+}
+
+/// Handles of a node running in the current actix system.
+pub struct NodeHandle {
+    pub client: Addr<ClientActor>,
+    pub view_client: Addr<ViewClientActor>,
+    pub network: Addr<PeerManagerActor>,
+    /// JSON RPC server, if it's enabled.
+    pub rpc_server: Option<Server>,
+    /// Prometheus server, if it's configured.
+    pub metrics_server: Option<Server>,
+    client_arbiter: Arbiter,
+    network_arbiter: Arbiter,
+}
+
+impl NodeHandle {
+    /// Stops the JSON RPC and metrics servers, waiting for the requests in flight, disconnects
+    /// from the network and stops the actix system the node was started in, along with the client
+    /// and the view client. Threads of the node can then be waited for with `join`.
+    pub async fn shutdown(&mut self) {
+        if let Some(rpc_server) = self.rpc_server.take() {
+            rpc_server.stop(true).await;
+        }
+        if let Some(metrics_server) = self.metrics_server.take() {
+            metrics_server.stop(true).await;
+        }
+        self.network_arbiter.stop();
+        System::current().stop();
+    }
+
+    /// Waits for the threads of the node to finish, after the actix system was stopped.
+    pub fn join(self) {
+        for mut arbiter in vec![self.client_arbiter, self.network_arbiter] {
+            arbiter.join().unwrap();
+        }
+    }
+}
+
+/// Starts a node in the current actix system, opening or creating its database in `home_dir`.
+///
+/// The node can be embedded into another process: it logs through the `log` and `tracing`
+/// subscribers installed by the caller and registers its metrics in the default prometheus
+/// registry, see `near_metrics::gather`. The only process-wide state it installs is a panic hook
+/// that stops the current actix system with exit code 1 when any thread panics.
+pub fn start(home_dir: &Path, config: NearConfig, options: NodeOptions) -> NodeHandle {
     let store = init_and_migrate_store(home_dir, &config);
     near_actix_utils::init_stop_on_panic();
+    register_db_metrics(&store);
 
-    let runtime = Arc::new(NightshadeRuntime::new(
+    let mut runtime = NightshadeRuntime::new(
         home_dir,
         Arc::clone(&store),
         &config.genesis,
         config.client_config.tracked_accounts.clone(),
         config.client_config.tracked_shards.clone(),
-    ));
+    );
+    runtime.set_gc_num_epochs_to_keep(config.client_config.gc_num_epochs_to_keep);
+    let runtime = Arc::new(runtime);
 
-    let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
+    let telemetry = options
+        .telemetry
+        .unwrap_or_else(|| TelemetryActor::new(config.telemetry_config.clone()).start());
     let chain_genesis = ChainGenesis::from(&config.genesis);
 
     let node_id = config.network_config.public_key.clone().into();
@@ -235,12 +267,16 @@ pub fn start_with_config(
         #[cfg(feature = "adversarial")]
         adv.clone(),
     );
-    start_http(
-        config.rpc_config,
-        config.genesis.config.clone(),
-        client_actor.clone(),
-        view_client.clone(),
-    );
+    let rpc_server = if options.enable_rpc {
+        Some(start_http(
+            config.rpc_config,
+            config.genesis.config.clone(),
+            client_actor.clone(),
+            view_client.clone(),
+        ))
+    } else {
+        None
+    };
     #[cfg(feature = "rosetta_rpc")]
     if let Some(rosetta_rpc_config) = config.rosetta_rpc_config {
         start_rosetta_rpc(
@@ -250,22 +286,44 @@ pub fn start_with_config(
             view_client.clone(),
         );
     }
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_config) = config.grpc_config {
+        start_grpc(grpc_config, view_client.clone());
+    }
+    let metrics_server = config.metrics_config.map(start_metrics_server);
 
     config.network_config.verify();
 
-    let arbiter = Arbiter::new();
+    let network_arbiter = Arbiter::new();
 
     let client_actor1 = client_actor.clone().recipient();
     let view_client1 = view_client.clone().recipient();
     let network_config = config.network_config;
 
-    let network_actor = PeerManagerActor::start_in_arbiter(&arbiter, move |_ctx| {
+    let network_actor = PeerManagerActor::start_in_arbiter(&network_arbiter, move |_ctx| {
         PeerManagerActor::new(store, network_config, client_actor1, view_client1).unwrap()
     });
 
-    network_adapter.set_recipient(network_actor.recipient());
+    network_adapter.set_recipient(network_actor.clone().recipient());
 
     trace!(target: "diagnostic", key="log", "Starting NEAR node with diagnostic activated");
 
-    (client_actor, view_client, vec![client_arbiter, arbiter])
+    NodeHandle {
+        client: client_actor,
+        view_client,
+        network: network_actor,
+        rpc_server,
+        metrics_server,
+        client_arbiter,
+        network_arbiter,
+    }
+}
+
+pub fn start_with_config(
+    home_dir: &Path,
+    config: NearConfig,
+) -> (Addr<ClientActor>, Addr<ViewClientActor>, Vec<Arbiter>) {
+    let NodeHandle { client, view_client, client_arbiter, network_arbiter, .. } =
+        start(home_dir, config, NodeOptions::default());
+    (client, view_client, vec![client_arbiter, network_arbiter])
 }
@@ -5,18 +5,33 @@ use std::io;
 use std::path::Path;
 
 use actix::System;
-use clap::{crate_version, App, AppSettings, Arg, SubCommand};
-#[cfg(feature = "adversarial")]
-use log::error;
-use log::info;
+use borsh::BorshSerialize;
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::{error, info};
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 use git_version::git_version;
-use near_primitives::version::{Version, PROTOCOL_VERSION};
+use near_crypto::{InMemorySigner, PublicKey, Signer};
+use near_jsonrpc_client::new_client;
+use near_primitives::account::AccessKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::to_base64;
+use near_primitives::transaction::{
+    Action, AddKeyAction, DeleteKeyAction, SignedTransaction, StakeAction, Transaction,
+    TransferAction,
+};
+use near_primitives::version::{ProtocolFeature, Version, PROTOCOL_VERSION};
+use near_store::create_store;
 use neard::config::init_testnet_configs;
+use neard::export::{export_chain, ExportOptions, Table};
 use neard::genesis_validate::validate_genesis;
-use neard::{get_default_home, get_store_path, init_configs, load_config, start_with_config};
+use neard::status::print_rich_status;
+use neard::{
+    apply_cold_store_migrations, apply_store_migrations, get_cold_store_path, get_default_home,
+    get_store_path, init_configs, load_config, start, store_path_exists, undo_blocks,
+    MigrationOptions, NodeOptions,
+};
 
 fn init_logging(verbose: Option<&str>) {
     let mut env_filter = EnvFilter::new(
@@ -59,6 +74,129 @@ fn init_logging(verbose: Option<&str>) {
         .init();
 }
 
+fn parse_public_key(value: &str) -> PublicKey {
+    value.parse().expect("Failed to parse public key")
+}
+
+fn parse_actions(args: &ArgMatches) -> Vec<Action> {
+    let mut actions = vec![];
+    if let Some(amount) = args.value_of("transfer") {
+        let deposit = amount.parse().expect("Failed to parse transfer amount");
+        actions.push(Action::Transfer(TransferAction { deposit }));
+    }
+    if let Some(amount) = args.value_of("stake") {
+        actions.push(Action::Stake(StakeAction {
+            stake: amount.parse().expect("Failed to parse stake amount"),
+            public_key: parse_public_key(args.value_of("stake-key").unwrap()),
+        }));
+    }
+    if let Some(public_key) = args.value_of("add-key") {
+        actions.push(Action::AddKey(AddKeyAction {
+            public_key: parse_public_key(public_key),
+            access_key: AccessKey::full_access(),
+        }));
+    }
+    if let Some(public_key) = args.value_of("delete-key") {
+        actions
+            .push(Action::DeleteKey(DeleteKeyAction { public_key: parse_public_key(public_key) }));
+    }
+    if actions.is_empty() {
+        panic!("At least one of --transfer, --stake, --add-key or --delete-key must be specified");
+    }
+    actions
+}
+
+#[cfg(feature = "ledger")]
+fn sign_with_ledger(
+    args: &ArgMatches,
+    build_transaction: impl FnOnce(PublicKey) -> Transaction,
+) -> SignedTransaction {
+    use neard::ledger::{get_public_key, sign_transaction, HidTransport, DEFAULT_HD_PATH};
+
+    let hd_path = args.value_of("hd-path").unwrap_or(DEFAULT_HD_PATH);
+    let transport = HidTransport::open().unwrap_or_else(|err| panic!("{}", err));
+    let public_key = get_public_key(&transport, hd_path).unwrap_or_else(|err| panic!("{}", err));
+    eprintln!("Please review and confirm the transaction on your Ledger device");
+    sign_transaction(&transport, hd_path, build_transaction(public_key))
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[cfg(not(feature = "ledger"))]
+fn sign_with_ledger(
+    _args: &ArgMatches,
+    _build_transaction: impl FnOnce(PublicKey) -> Transaction,
+) -> SignedTransaction {
+    panic!("neard was built without Ledger support, rebuild it with `--features ledger`");
+}
+
+/// Builds the transaction from the command line, signs it with the Ledger device or the key file
+/// and either prints it in base64 or broadcasts it to the node.
+fn sign_and_send_transaction(home_dir: &Path, args: &ArgMatches) {
+    let signer_id = args.value_of("signer-id").unwrap().to_string();
+    let receiver_id = args.value_of("receiver-id").unwrap_or(&signer_id).to_string();
+    let nonce = args.value_of("nonce").unwrap().parse().expect("Failed to parse nonce");
+    let block_hash: CryptoHash =
+        args.value_of("block-hash").unwrap().try_into().expect("Failed to parse block hash");
+    let actions = parse_actions(args);
+    let build_transaction = |public_key| Transaction {
+        signer_id: signer_id.clone(),
+        public_key,
+        nonce,
+        receiver_id,
+        block_hash,
+        actions,
+    };
+
+    let signed_transaction = if args.is_present("ledger") {
+        sign_with_ledger(args, build_transaction)
+    } else {
+        let key_file = args
+            .value_of("key-file")
+            .expect("Either --ledger or --key-file must be specified to sign the transaction");
+        let signer = InMemorySigner::from_file(Path::new(key_file));
+        if signer.account_id != signer_id {
+            panic!("Key file is for {}, not for {}", signer.account_id, signer_id);
+        }
+        let transaction = build_transaction(signer.public_key());
+        let signature = signer.sign(transaction.get_hash().as_ref());
+        SignedTransaction::new(signature, transaction)
+    };
+
+    let encoded = to_base64(&signed_transaction.try_to_vec().expect("Failed to serialize"));
+    if !args.is_present("broadcast") {
+        println!("{}", encoded);
+        return;
+    }
+    let near_config = load_config(home_dir);
+    let rpc_addr = args.value_of("rpc-addr").unwrap_or(&near_config.rpc_config.addr);
+    let url = format!("http://{}", rpc_addr.replace("0.0.0.0", "127.0.0.1"));
+    let outcome = System::new("tx")
+        .block_on(async move { new_client(&url).broadcast_tx_commit(encoded).await })
+        .unwrap_or_else(|err| panic!("Failed to broadcast transaction: {}", err.to_string()));
+    println!("{}", serde_json::to_string_pretty(&outcome).unwrap());
+}
+
+/// Prints the protocol features compiled into the binary. Whether they are enabled in the current
+/// epoch is reported by the `EXPERIMENTAL_protocol_features` RPC of a running node.
+fn print_protocol_features() {
+    println!("Protocol version: {}", PROTOCOL_VERSION);
+    let features = ProtocolFeature::all();
+    if features.is_empty() {
+        println!("No protocol features are compiled in");
+    }
+    for feature in features {
+        match feature.nightly_feature() {
+            Some(nightly_feature) => println!(
+                "{} => {} (nightly, `{}`)",
+                feature.name(),
+                feature.protocol_version(),
+                nightly_feature
+            ),
+            None => println!("{} => {}", feature.name(), feature.protocol_version()),
+        }
+    }
+}
+
 fn main() {
     // We use it to automatically search the for root certificates to perform HTTPS calls
     // (sending telemetry and downloading genesis)
@@ -70,7 +208,7 @@ fn main() {
         build: git_version!(fallback = "unknown").to_string(),
     };
     let matches = App::new("NEAR Protocol Node")
-        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::ArgRequiredElseHelp)
         .version(format!("{} (build {})", version.version, version.build).as_str())
         .arg(Arg::with_name("verbose").long("verbose").help("Verbose logging").takes_value(true))
         .arg(
@@ -80,6 +218,11 @@ fn main() {
                 .help("Directory for config and data (default \"~/.near\")")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("print-features")
+                .long("print-features")
+                .help("Print the protocol features compiled into the binary and exit"),
+        )
         .subcommand(SubCommand::with_name("init").about("Initializes NEAR configuration")
             .arg(Arg::with_name("chain-id").long("chain-id").takes_value(true).help("Chain ID, by default creates new random"))
             .arg(Arg::with_name("account-id").long("account-id").takes_value(true).help("Account ID for the validator key"))
@@ -105,10 +248,52 @@ fn main() {
             .arg(Arg::with_name("telemetry-url").long("telemetry-url").help("Customize telemetry url").takes_value(true))
             .arg(Arg::with_name("archive").long("archive").help("Keep old blocks in the storage (default false)").takes_value(false))
         )
+        .subcommand(SubCommand::with_name("status").about("Queries the local node and prints its status")
+            .arg(Arg::with_name("rich").long("rich").takes_value(false).help("Print a colored health summary with actionable diagnostics"))
+            .arg(Arg::with_name("rpc-addr").long("rpc-addr").takes_value(true).help("RPC address of the node (default is taken from config)"))
+        )
+        .subcommand(SubCommand::with_name("tx").about("Transaction tools")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("sign").about("Signs a transaction and prints it in base64 or broadcasts it")
+                .arg(Arg::with_name("signer-id").long("signer-id").takes_value(true).required(true).help("Account that signs the transaction"))
+                .arg(Arg::with_name("receiver-id").long("receiver-id").takes_value(true).help("Receiver of the transaction (default is signer)"))
+                .arg(Arg::with_name("nonce").long("nonce").takes_value(true).required(true).help("Nonce of the access key"))
+                .arg(Arg::with_name("block-hash").long("block-hash").takes_value(true).required(true).help("Hash of a recent block"))
+                .arg(Arg::with_name("transfer").long("transfer").takes_value(true).help("Transfer given amount of yoctoNEAR"))
+                .arg(Arg::with_name("stake").long("stake").takes_value(true).requires("stake-key").help("Stake given amount of yoctoNEAR"))
+                .arg(Arg::with_name("stake-key").long("stake-key").takes_value(true).help("Validator public key to stake with"))
+                .arg(Arg::with_name("add-key").long("add-key").takes_value(true).help("Add public key with full access"))
+                .arg(Arg::with_name("delete-key").long("delete-key").takes_value(true).help("Delete public key"))
+                .arg(Arg::with_name("ledger").long("ledger").takes_value(false).help("Sign with the NEAR app on a Ledger device"))
+                .arg(Arg::with_name("hd-path").long("hd-path").takes_value(true).help("Ledger derivation path (default \"44'/397'/0'/0'/1'\")"))
+                .arg(Arg::with_name("key-file").long("key-file").takes_value(true).conflicts_with("ledger").help("Sign with the key from the given file"))
+                .arg(Arg::with_name("broadcast").long("broadcast").takes_value(false).help("Broadcast the signed transaction and wait for the outcome"))
+                .arg(Arg::with_name("rpc-addr").long("rpc-addr").takes_value(true).help("RPC address to broadcast to (default is taken from config)"))
+            )
+        )
+        .subcommand(SubCommand::with_name("migrate").about("Migrates the database to the current version, also done on run")
+            .arg(Arg::with_name("dry-run").long("dry-run").takes_value(false).help("Migrate a temporary checkpoint of the database instead, leaving the database unchanged"))
+            .arg(Arg::with_name("no-snapshot").long("no-snapshot").takes_value(false).help("Don't snapshot the database before steps that rewrite data"))
+        )
+        .subcommand(SubCommand::with_name("export").about("Exports blocks, transactions, execution outcomes and state changes into CSV or Parquet files, the node must be stopped")
+            .arg(Arg::with_name("output-dir").long("output-dir").takes_value(true).required(true).help("Directory to write a file per table to"))
+            .arg(Arg::with_name("from-height").long("from-height").takes_value(true).help("First height to export (default is genesis height)"))
+            .arg(Arg::with_name("to-height").long("to-height").takes_value(true).help("Last height to export (default is the head)"))
+            .arg(Arg::with_name("format").long("format").takes_value(true).default_value("csv").help("csv or parquet (requires the parquet_export feature)"))
+            .arg(Arg::with_name("tables").long("tables").takes_value(true).help("Comma separated tables to export: blocks, transactions, execution_outcomes, state_changes (default all)"))
+        )
+        .subcommand(SubCommand::with_name("undo-blocks").about("Rewinds the head by the given number of blocks, also past the last final block, the node must be stopped and syncs them again when started")
+            .arg(Arg::with_name("num-blocks").long("num-blocks").takes_value(true).required(true).help("Number of blocks to remove from the head"))
+        )
         .subcommand(SubCommand::with_name("unsafe_reset_data").about("(unsafe) Remove all the data, effectively resetting node to genesis state (keeps genesis and config)"))
         .subcommand(SubCommand::with_name("unsafe_reset_all").about("(unsafe) Remove all the config, keys, data and effectively removing all information about the network"))
         .get_matches();
 
+    if matches.is_present("print-features") {
+        print_protocol_features();
+        return;
+    }
+
     init_logging(matches.value_of("verbose"));
     info!(target: "near", "Version: {}, Build: {}, Latest Protocol: {}", version.version, version.build, PROTOCOL_VERSION);
 
@@ -231,9 +416,94 @@ fn main() {
             }
 
             let system = System::new("NEAR");
-            let (_, _, arbiters) = start_with_config(home_dir, near_config);
+            let node = start(home_dir, near_config, NodeOptions::default());
             system.run().unwrap();
-            arbiters.into_iter().for_each(|mut a| a.join().unwrap());
+            node.join();
+        }
+        ("status", Some(args)) => {
+            let near_config = load_config(home_dir);
+            if args.is_present("rich") {
+                print_rich_status(home_dir, &near_config, args.value_of("rpc-addr"));
+            } else {
+                let rpc_addr = args.value_of("rpc-addr").unwrap_or(&near_config.rpc_config.addr);
+                println!("RPC server: {}, use --rich for a health summary", rpc_addr);
+            }
+        }
+        ("tx", Some(args)) => match args.subcommand() {
+            ("sign", Some(args)) => sign_and_send_transaction(home_dir, args),
+            (_, _) => unreachable!(),
+        },
+        ("migrate", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let store_path = get_store_path(home_dir);
+            if !store_path_exists(&store_path) {
+                error!(target: "near", "No database at {}", store_path);
+                std::process::exit(1);
+            }
+            let options = MigrationOptions {
+                dry_run: args.is_present("dry-run"),
+                snapshot: !args.is_present("no-snapshot"),
+            };
+            apply_store_migrations(&store_path, &near_config, &options);
+            if let Some(cold_path) = get_cold_store_path(home_dir, &near_config) {
+                if store_path_exists(&cold_path) {
+                    apply_cold_store_migrations(&cold_path, &near_config, &options);
+                }
+            }
+        }
+        ("export", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let store_path = get_store_path(home_dir);
+            if !store_path_exists(&store_path) {
+                error!(target: "near", "No database at {}", store_path);
+                std::process::exit(1);
+            }
+            let genesis_height = near_config.genesis.config.genesis_height;
+            let options = ExportOptions {
+                from_height: args
+                    .value_of("from-height")
+                    .map(|x| x.parse().expect("Failed to parse from-height"))
+                    .unwrap_or(genesis_height),
+                to_height: args
+                    .value_of("to-height")
+                    .map(|x| x.parse().expect("Failed to parse to-height")),
+                output_dir: args.value_of("output-dir").unwrap().into(),
+                format: args
+                    .value_of("format")
+                    .unwrap()
+                    .parse()
+                    .unwrap_or_else(|err| panic!("{}", err)),
+                tables: match args.value_of("tables") {
+                    Some(tables) => tables
+                        .split(',')
+                        .map(|table| table.parse().unwrap_or_else(|err| panic!("{}", err)))
+                        .collect(),
+                    None => Table::ALL.to_vec(),
+                },
+            };
+            if let Err(err) = export_chain(create_store(&store_path), genesis_height, &options) {
+                error!(target: "near", "Export failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        ("undo-blocks", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let store_path = get_store_path(home_dir);
+            if !store_path_exists(&store_path) {
+                error!(target: "near", "No database at {}", store_path);
+                std::process::exit(1);
+            }
+            let num_blocks =
+                args.value_of("num-blocks").unwrap().parse().expect("Failed to parse num-blocks");
+            match undo_blocks(home_dir, &near_config, num_blocks) {
+                Ok(head) => {
+                    info!(target: "near", "Head is now {} at {}", head.last_block_hash, head.height)
+                }
+                Err(err) => {
+                    error!(target: "near", "Undoing blocks failed: {}", err);
+                    std::process::exit(1);
+                }
+            }
         }
         ("unsafe_reset_data", Some(_args)) => {
             let store_path = get_store_path(home_dir);
@@ -244,6 +514,10 @@ fn main() {
             info!(target: "near", "Removing all data and config from {}", home_dir.to_str().unwrap());
             fs::remove_dir_all(home_dir).expect("Removing data and config failed.");
         }
+        (_, None) => {
+            eprintln!("{}", matches.usage());
+            std::process::exit(1);
+        }
         (_, _) => unreachable!(),
     }
 }
@@ -0,0 +1,82 @@
+//! Prometheus endpoint of the node, separate from the JSON RPC so that metrics can be scraped
+//! from nodes that don't serve RPC, and metrics that aren't tracked by a single subsystem.
+use std::sync::{Arc, Weak};
+
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use near_metrics::{try_create_int_gauge_vec, IntGaugeVec};
+use near_store::{DBCol, Store};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MetricsConfig {
+    /// Address to serve the metrics on, at `/metrics`.
+    pub addr: String,
+}
+
+lazy_static! {
+    static ref DB_ESTIMATED_KEYS: near_metrics::Result<IntGaugeVec> = try_create_int_gauge_vec(
+        "near_db_estimated_keys",
+        "Estimated number of keys in the database, by column",
+        &["column"]
+    );
+    static ref DB_LIVE_DATA_SIZE: near_metrics::Result<IntGaugeVec> = try_create_int_gauge_vec(
+        "near_db_live_data_size_bytes",
+        "Estimated size of the live data in the database, by column",
+        &["column"]
+    );
+}
+
+/// Reports the statistics of the database columns, read from RocksDB on every scrape.
+pub fn register_db_metrics(store: &Arc<Store>) {
+    let store: Weak<Store> = Arc::downgrade(store);
+    near_metrics::register_gather_hook(move || {
+        let store = match store.upgrade() {
+            Some(store) => store,
+            None => return,
+        };
+        let db = match store.get_rocksdb() {
+            Some(db) => db,
+            None => return,
+        };
+        for col in DBCol::iter() {
+            let column = format!("{:?}", col);
+            match (db.estimate_num_keys(col), db.estimate_live_data_size(col)) {
+                (Ok(num_keys), Ok(live_data_size)) => {
+                    if let (Ok(keys_gauge), Ok(size_gauge)) =
+                        (&*DB_ESTIMATED_KEYS, &*DB_LIVE_DATA_SIZE)
+                    {
+                        keys_gauge.with_label_values(&[&column]).set(num_keys as i64);
+                        size_gauge.with_label_values(&[&column]).set(live_data_size as i64);
+                    }
+                }
+                (Err(err), _) | (_, Err(err)) => {
+                    warn!(target: "metrics", "Failed to read statistics of {}: {:?}", column, err);
+                }
+            }
+        }
+    });
+}
+
+async fn metrics_handler() -> HttpResponse {
+    match near_metrics::gather_text() {
+        Ok(text) => HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(text),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+pub fn start_metrics_server(config: MetricsConfig) -> Server {
+    info!(target: "metrics", "Serving metrics at {}", config.addr);
+    HttpServer::new(|| {
+        App::new().service(web::resource("/metrics").route(web::get().to(metrics_handler)))
+    })
+    .bind(config.addr)
+    .unwrap()
+    .workers(1)
+    .shutdown_timeout(5)
+    .run()
+}
@@ -1,14 +1,22 @@
 use crate::{NearConfig, NightshadeRuntime};
 use borsh::BorshDeserialize;
+use log::{info, warn};
 use near_chain::chain::collect_receipts_from_response;
 use near_chain::types::ApplyTransactionResult;
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
 use near_primitives::sharding::{ChunkHash, ShardChunkHeader, ShardChunkV1};
 use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
 use near_primitives::types::{BlockHeight, ShardId};
-use near_store::migrations::set_store_version;
+use near_primitives::version::DbVersion;
+use near_store::migrations::{
+    create_snapshot, estimate_num_keys, fill_col_outcomes_by_hash, fill_col_transaction_refcount,
+    get_store_version, migrate_10_to_11, migrate_11_to_12, migrate_13_to_14, migrate_14_to_15,
+    migrate_20_to_21, migrate_6_to_7, migrate_7_to_8, migrate_8_to_9, migrate_9_to_10,
+    set_store_version,
+};
 use near_store::{create_store, DBCol, StoreUpdate};
 use std::path::Path;
+use std::time::Instant;
 
 fn get_chunk(chain_store: &ChainStore, chunk_hash: ChunkHash) -> ShardChunkV1 {
     let store = chain_store.store();
@@ -122,3 +130,250 @@ pub fn migrate_12_to_13(path: &String, near_config: &NearConfig) {
     }
     set_store_version(&store, 13);
 }
+
+/// Step that upgrades the database from `from_version` to `from_version + 1`. Steps are forward
+/// only and each of them writes the new version when it's done, so an interrupted upgrade resumes
+/// from the first unfinished step.
+pub struct Migration {
+    pub from_version: DbVersion,
+    pub description: &'static str,
+    /// Columns written by the step, progress is reported for each of them.
+    pub columns: &'static [DBCol],
+    /// Whether the step deletes or rewrites existing data. The database is snapshotted first.
+    pub destructive: bool,
+    /// Migrates the data, the new version is written after it returns.
+    run: fn(&String, &NearConfig),
+}
+
+impl Migration {
+    /// Step that only adds columns, which are created when the database is opened.
+    fn bump_version(from_version: DbVersion, description: &'static str) -> Self {
+        Self { from_version, description, columns: &[], destructive: false, run: |_, _| {} }
+    }
+
+    /// Applies the step, returns the path of the snapshot taken before it, if any.
+    fn apply(&self, path: &String, near_config: &NearConfig, snapshot: bool) -> Option<String> {
+        info!(target: "near", "Migrate DB from version {} to {}: {}", self.from_version, self.from_version + 1, self.description);
+        let snapshot_path = if self.destructive && snapshot {
+            let snapshot_path = snapshot_path(path, self.from_version);
+            if Path::new(&snapshot_path).exists() {
+                // Left by an interrupted run of the same step, it still has the data before it.
+                info!(target: "near", "Keeping existing snapshot {}", snapshot_path);
+            } else {
+                info!(target: "near", "Snapshotting DB to {}", snapshot_path);
+                create_snapshot(path, self.from_version, &snapshot_path);
+            }
+            Some(snapshot_path)
+        } else {
+            None
+        };
+        let keys_before = estimate_num_keys(path, self.from_version, self.columns);
+        let started = Instant::now();
+        (self.run)(path, near_config);
+        set_store_version(&create_store(path), self.from_version + 1);
+        let keys_after = estimate_num_keys(path, self.from_version + 1, self.columns);
+        for ((col, before), (_, after)) in keys_before.into_iter().zip(keys_after) {
+            info!(target: "near", "Migrated column {}: ~{} keys before, ~{} keys after", col, before, after);
+        }
+        info!(target: "near", "Migrated DB to version {} in {:?}", self.from_version + 1, started.elapsed());
+        snapshot_path
+    }
+}
+
+pub struct MigrationOptions {
+    /// Apply the steps to a checkpoint of the database, which is removed afterwards, and leave
+    /// the database itself unchanged.
+    pub dry_run: bool,
+    /// Snapshot the database before destructive steps. The snapshots are removed once all the
+    /// steps succeed, and kept to restore from otherwise.
+    pub snapshot: bool,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self { dry_run: false, snapshot: true }
+    }
+}
+
+/// Directory of the snapshot taken before migrating from `from_version`, next to the database.
+pub fn snapshot_path(path: &str, from_version: DbVersion) -> String {
+    format!("{}-snapshot-v{}", path.trim_end_matches('/'), from_version)
+}
+
+/// Directory of the checkpoint that a dry run migrates, next to the database.
+fn dry_run_path(path: &str) -> String {
+    format!("{}-dry-run", path.trim_end_matches('/'))
+}
+
+fn remove_dir(path: &str) {
+    if let Err(err) = std::fs::remove_dir_all(path) {
+        warn!(target: "near", "Failed to remove {}: {}", path, err);
+    }
+}
+
+/// All migration steps in order. Add a step here when bumping `DB_VERSION`.
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        Migration::bump_version(1, "add gc column"),
+        Migration {
+            from_version: 2,
+            // Also renames LastComponentNonce to ColLastComponentNonce, the column number is the same.
+            description: "add ColOutcomesByBlockHash",
+            columns: &[DBCol::ColOutcomeIds],
+            destructive: false,
+            run: |path, _| fill_col_outcomes_by_hash(&create_store(path)),
+        },
+        Migration {
+            from_version: 3,
+            description: "add ColTransactionRefCount",
+            columns: &[DBCol::_ColTransactionRefCount],
+            destructive: false,
+            run: |path, _| fill_col_transaction_refcount(&create_store(path)),
+        },
+        // Old heights are not backfilled, at worst some heights are processed again.
+        Migration::bump_version(4, "add ColProcessedBlockHeights"),
+        // There were no merge records before, so the old storage works.
+        Migration::bump_version(5, "add merge operator to ColState"),
+        Migration {
+            from_version: 6,
+            description: "use 8 bytes refcount in ColState, move ColTransactionRefCount into \
+                          ColTransactions, make ColReceiptIdToShardId refcounted",
+            columns: &[
+                DBCol::ColState,
+                DBCol::ColTransactions,
+                DBCol::_ColTransactionRefCount,
+                DBCol::ColReceiptIdToShardId,
+            ],
+            destructive: true,
+            run: |path, _| migrate_6_to_7(path),
+        },
+        Migration {
+            from_version: 7,
+            description: "delete state parts",
+            columns: &[DBCol::ColStateParts],
+            destructive: true,
+            run: |path, _| migrate_7_to_8(path),
+        },
+        Migration {
+            from_version: 8,
+            description: "repair ColTransactions and ColReceiptIdToShardId",
+            columns: &[DBCol::ColTransactions, DBCol::ColReceiptIdToShardId],
+            destructive: true,
+            run: |path, _| migrate_8_to_9(path),
+        },
+        Migration {
+            from_version: 9,
+            description: "populate partial encoded chunks for stored chunks",
+            columns: &[DBCol::ColPartialChunks],
+            destructive: false,
+            run: |path, near_config| migrate_9_to_10(path, near_config.client_config.archive),
+        },
+        Migration {
+            from_version: 10,
+            description: "add final head",
+            columns: &[DBCol::ColBlockMisc],
+            destructive: false,
+            run: |path, _| migrate_10_to_11(path),
+        },
+        Migration {
+            from_version: 11,
+            description: "populate ColReceipts with existing receipts",
+            columns: &[DBCol::ColReceipts],
+            destructive: false,
+            run: |path, _| migrate_11_to_12(path),
+        },
+        Migration {
+            from_version: 12,
+            description: "fix inconsistencies in ColTransactionResult",
+            columns: &[DBCol::ColTransactionResult],
+            destructive: true,
+            run: |path, near_config| migrate_12_to_13(path, near_config),
+        },
+        Migration {
+            from_version: 13,
+            description: "store versioned enums for shard chunks",
+            columns: &[
+                DBCol::ColPartialChunks,
+                DBCol::ColInvalidChunks,
+                DBCol::ColChunks,
+                DBCol::ColStateHeaders,
+            ],
+            destructive: true,
+            run: |path, _| migrate_13_to_14(path),
+        },
+        Migration {
+            from_version: 14,
+            description: "order ColOutcomeIds within each shard",
+            columns: &[DBCol::ColOutcomeIds],
+            destructive: true,
+            run: |path, _| migrate_14_to_15(path),
+        },
+        Migration::bump_version(15, "add column for compiled contracts"),
+        // Fee history is only available for blocks processed after the migration.
+        Migration::bump_version(16, "add ColBlockFeeInfo"),
+        // Idempotency keys are only indexed for blocks processed after the migration.
+        Migration::bump_version(17, "add ColIdempotencyKeys"),
+        Migration::bump_version(18, "add ColWatchedAccountChanges"),
+        // Peers start without history, as on a new node.
+        Migration::bump_version(19, "add ColPeerStats"),
+        Migration {
+            from_version: 20,
+            description: "key ColWatchedAccountChanges by block height",
+            columns: &[DBCol::ColWatchedAccountChanges],
+            destructive: true,
+            run: |path, _| migrate_20_to_21(path),
+        },
+    ]
+}
+
+/// Applies the migration steps from the current version of the database, returns the paths of
+/// the snapshots taken before them.
+fn apply_migrations(path: &String, near_config: &NearConfig, snapshot: bool) -> Vec<String> {
+    let db_version = get_store_version(path);
+    migrations()
+        .iter()
+        .filter(|migration| migration.from_version >= db_version)
+        .filter_map(|migration| migration.apply(path, near_config, snapshot))
+        .collect()
+}
+
+/// Applies the migration steps from the current version of the database.
+pub fn migrate_store(path: &String, near_config: &NearConfig, options: &MigrationOptions) {
+    if options.dry_run {
+        let dry_run_path = dry_run_path(path);
+        if Path::new(&dry_run_path).exists() {
+            // Left by an interrupted dry run.
+            remove_dir(&dry_run_path);
+        }
+        info!(target: "near", "Dry run: migrating a checkpoint of the DB in {}", dry_run_path);
+        create_snapshot(path, get_store_version(path), &dry_run_path);
+        apply_migrations(&dry_run_path, near_config, false);
+        remove_dir(&dry_run_path);
+        info!(target: "near", "Dry run succeeded, the DB at {} is unchanged", path);
+        return;
+    }
+    for snapshot_path in apply_migrations(path, near_config, options.snapshot) {
+        info!(target: "near", "Migration succeeded, removing snapshot {}", snapshot_path);
+        remove_dir(&snapshot_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::version::DB_VERSION;
+
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_contiguous() {
+        let versions: Vec<_> =
+            migrations().iter().map(|migration| migration.from_version).collect();
+        assert_eq!(versions, (1..DB_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_snapshot_path() {
+        assert_eq!(snapshot_path("/home/near/data/", 12), "/home/near/data-snapshot-v12");
+        assert_eq!(dry_run_path("/home/near/data/"), "/home/near/data-dry-run");
+    }
+}
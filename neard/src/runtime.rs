@@ -10,16 +10,19 @@ use borsh::ser::BorshSerialize;
 use borsh::BorshDeserialize;
 use log::{debug, error, info, warn};
 
-use near_chain::chain::NUM_EPOCHS_TO_KEEP_STORE_DATA;
-use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
+use near_chain::chain::{MIN_EPOCHS_TO_KEEP_STORE_DATA, NUM_EPOCHS_TO_KEEP_STORE_DATA};
+use near_chain::types::{
+    ApplyTransactionResult, BlockEconomicsConfig, BlockHeaderInfo, ChainGenesis,
+};
 use near_chain::{BlockHeader, Error, ErrorKind, RuntimeAdapter};
-use near_chain_configs::{Genesis, GenesisConfig};
-use near_crypto::{PublicKey, Signature};
+use near_chain_configs::{Genesis, GenesisConfig, ProtocolConfigView};
+use near_crypto::{verify_batch_parallel, PublicKey, Signature, SignatureVerificationCache};
 use near_epoch_manager::{EpochManager, RewardCalculator};
 use near_pool::types::PoolIterator;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::block::{Approval, ApprovalInner};
 use near_primitives::challenge::ChallengesResult;
+use near_primitives::contract::MethodMetadata;
 use near_primitives::epoch_manager::{BlockInfo, EpochConfig};
 use near_primitives::errors::{EpochError, InvalidTxError, RuntimeError};
 use near_primitives::hash::{hash, CryptoHash};
@@ -27,30 +30,31 @@ use near_primitives::receipt::Receipt;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::state_record::StateRecord;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::trie_key::trie_key_parsers;
+use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::{
     AccountId, ApprovalStake, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas,
     MerkleHash, NumShards, ShardId, StateChangeCause, StateRoot, StateRootNode, ValidatorStake,
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
-    AccessKeyInfoView, CallResult, EpochValidatorInfo, QueryError, QueryRequest, QueryResponse,
-    QueryResponseKind, ViewApplyState, ViewStateResult,
+    AccessKeyInfoView, AccessKeyList, AccountList, CallResult, ContractMetadataView,
+    EpochValidatorInfo, QueryError, QueryRequest, QueryResponse, QueryResponseKind, ViewApplyState,
+    ViewStateResult, MAX_ACCESS_KEYS_PER_QUERY, MAX_ACCOUNTS_PER_QUERY, MAX_STATE_VALUES_PER_QUERY,
 };
 use near_store::{
     get_access_key_raw, get_genesis_hash, get_genesis_state_roots, set_genesis_hash,
     set_genesis_state_roots, ColState, PartialStorage, ShardTries, Store,
-    StoreCompiledContractCache, Trie, WrappedTrieChanges,
+    StoreCompiledContractCache, Trie, TrieUpdateIterator, WrappedTrieChanges,
 };
 use node_runtime::adapter::ViewRuntimeAdapter;
 use node_runtime::state_viewer::TrieViewer;
 use node_runtime::{
     validate_transaction, verify_and_charge_transaction, ApplyState, Runtime,
-    ValidatorAccountsUpdate,
+    ValidatorAccountsUpdate, VerifiedSignaturesCache,
 };
 
 use crate::shard_tracker::{account_id_to_shard_id, ShardTracker};
-use near_runtime_configs::RuntimeConfig;
+use near_runtime_configs::RuntimeConfigStore;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 const STATE_DUMP_FILE: &str = "state_dump";
@@ -116,7 +120,8 @@ impl EpochInfoProvider for SafeEpochManager {
 /// TODO: this possibly should be merged with the runtime cargo or at least reconciled on the interfaces.
 pub struct NightshadeRuntime {
     genesis_config: GenesisConfig,
-    genesis_runtime_config: Arc<RuntimeConfig>,
+    runtime_config_store: RuntimeConfigStore,
+    block_economics_config: BlockEconomicsConfig,
 
     store: Arc<Store>,
     tries: ShardTries,
@@ -125,6 +130,13 @@ pub struct NightshadeRuntime {
     epoch_manager: SafeEpochManager,
     shard_tracker: ShardTracker,
     genesis_state_roots: Vec<StateRoot>,
+    /// Signatures verified at the transaction admission, reused when applying chunks.
+    verified_signatures: Arc<VerifiedSignaturesCache>,
+    /// Approval signatures verified with the block headers, so that the header of the same block
+    /// received again, e.g. at the header sync and then with the block, is not reverified.
+    verified_approvals: SignatureVerificationCache,
+    /// Number of epochs whose data is not garbage collected.
+    gc_num_epochs_to_keep: u64,
 }
 
 impl NightshadeRuntime {
@@ -138,7 +150,8 @@ impl NightshadeRuntime {
         let runtime = Runtime::new();
         let trie_viewer = TrieViewer::new();
         let genesis_config = genesis.config.clone();
-        let genesis_runtime_config = Arc::new(genesis_config.runtime_config.clone());
+        let runtime_config_store = RuntimeConfigStore::new(&genesis_config.runtime_config);
+        let block_economics_config = BlockEconomicsConfig::from(&ChainGenesis::from(genesis));
         let num_shards = genesis.config.num_block_producer_seats_per_shard.len() as NumShards;
         let initial_epoch_config = EpochConfig {
             epoch_length: genesis.config.epoch_length,
@@ -148,6 +161,7 @@ impl NightshadeRuntime {
                 .config
                 .num_block_producer_seats_per_shard
                 .clone(),
+            num_chunk_only_producer_seats: genesis.config.num_chunk_only_producer_seats,
             avg_hidden_validator_seats_per_shard: genesis
                 .config
                 .avg_hidden_validator_seats_per_shard
@@ -207,7 +221,8 @@ impl NightshadeRuntime {
         );
         NightshadeRuntime {
             genesis_config,
-            genesis_runtime_config,
+            runtime_config_store,
+            block_economics_config,
             store,
             tries,
             runtime,
@@ -215,9 +230,18 @@ impl NightshadeRuntime {
             epoch_manager: SafeEpochManager(epoch_manager),
             shard_tracker,
             genesis_state_roots: state_roots,
+            verified_signatures: Arc::new(VerifiedSignaturesCache::default()),
+            verified_approvals: SignatureVerificationCache::default(),
+            gc_num_epochs_to_keep: NUM_EPOCHS_TO_KEEP_STORE_DATA,
         }
     }
 
+    /// Sets the number of epochs whose data is not garbage collected, at least
+    /// `MIN_EPOCHS_TO_KEEP_STORE_DATA`.
+    pub fn set_gc_num_epochs_to_keep(&mut self, gc_num_epochs_to_keep: u64) {
+        self.gc_num_epochs_to_keep = gc_num_epochs_to_keep.max(MIN_EPOCHS_TO_KEEP_STORE_DATA);
+    }
+
     fn get_epoch_height_from_prev_block(
         &self,
         prev_block_hash: &CryptoHash,
@@ -442,11 +466,9 @@ impl NightshadeRuntime {
             gas_limit: Some(gas_limit),
             random_seed,
             current_protocol_version,
-            config: RuntimeConfig::from_protocol_version(
-                &self.genesis_runtime_config,
-                current_protocol_version,
-            ),
+            config: self.runtime_config_store.get_config(current_protocol_version).clone(),
             cache: Some(Arc::new(StoreCompiledContractCache { store: self.store.clone() })),
+            verified_signatures: Some(self.verified_signatures.clone()),
         };
 
         let apply_result = self
@@ -513,6 +535,50 @@ impl NightshadeRuntime {
 
         Ok(result)
     }
+
+    /// Returns up to `limit` access keys after `cursor`, all of them if there is no limit.
+    fn query_access_key_list(
+        &self,
+        shard_id: ShardId,
+        state_root: &StateRoot,
+        block_height: BlockHeight,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+        cursor: Option<&PublicKey>,
+        limit: Option<usize>,
+    ) -> QueryResponse {
+        // One more key is read to know whether there is a next page.
+        let kind = match self.view_access_keys(
+            shard_id,
+            *state_root,
+            account_id,
+            cursor,
+            limit.map(|limit| limit + 1),
+        ) {
+            Ok(mut result) => {
+                let next_cursor = match limit {
+                    Some(limit) if result.len() > limit => {
+                        result.truncate(limit);
+                        result.last().map(|(public_key, _)| public_key.clone())
+                    }
+                    _ => None,
+                };
+                let mut access_keys: AccessKeyList = result
+                    .into_iter()
+                    .map(|(public_key, access_key)| AccessKeyInfoView {
+                        public_key,
+                        access_key: access_key.into(),
+                    })
+                    .collect();
+                access_keys.next_cursor = next_cursor;
+                QueryResponseKind::AccessKeyList(access_keys)
+            }
+            Err(err) => {
+                QueryResponseKind::Error(QueryError { error: err.to_string(), logs: vec![] })
+            }
+        };
+        QueryResponse { kind, block_height, block_hash: *block_hash }
+    }
 }
 
 pub fn state_record_to_shard_id(state_record: &StateRecord, num_shards: NumShards) -> ShardId {
@@ -574,10 +640,8 @@ impl RuntimeAdapter for NightshadeRuntime {
         verify_signature: bool,
         current_protocol_version: ProtocolVersion,
     ) -> Result<Option<InvalidTxError>, Error> {
-        let runtime_config = RuntimeConfig::from_protocol_version(
-            &self.genesis_runtime_config,
-            current_protocol_version,
-        );
+        let runtime_config = self.runtime_config_store.get_config(current_protocol_version);
+        let verify_signature = verify_signature && !self.verified_signatures.contains(transaction);
 
         if let Some(state_root) = state_root {
             let shard_id = self.account_id_to_shard_id(&transaction.transaction.signer_id);
@@ -591,7 +655,12 @@ impl RuntimeAdapter for NightshadeRuntime {
                 verify_signature,
                 current_protocol_version,
             ) {
-                Ok(_) => Ok(None),
+                Ok(_) => {
+                    if verify_signature {
+                        self.verified_signatures.insert(transaction);
+                    }
+                    Ok(None)
+                }
                 Err(RuntimeError::InvalidTxError(err)) => {
                     debug!(target: "runtime", "Tx {:?} validation failed: {:?}", transaction, err);
                     Ok(Some(err))
@@ -610,7 +679,12 @@ impl RuntimeAdapter for NightshadeRuntime {
                 verify_signature,
                 current_protocol_version,
             ) {
-                Ok(_) => Ok(None),
+                Ok(_) => {
+                    if verify_signature {
+                        self.verified_signatures.insert(transaction);
+                    }
+                    Ok(None)
+                }
                 Err(RuntimeError::InvalidTxError(err)) => {
                     debug!(target: "runtime", "Tx {:?} validation failed: {:?}", transaction, err);
                     Ok(Some(err))
@@ -623,6 +697,10 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn verify_transaction_signature(&self, transaction: &SignedTransaction) -> bool {
+        self.verified_signatures.verify(transaction)
+    }
+
     fn prepare_transactions(
         &self,
         gas_price: Balance,
@@ -642,10 +720,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         let mut transactions = vec![];
         let mut num_checked_transactions = 0;
 
-        let runtime_config = RuntimeConfig::from_protocol_version(
-            &self.genesis_runtime_config,
-            current_protocol_version,
-        );
+        let runtime_config = self.runtime_config_store.get_config(current_protocol_version);
 
         while total_gas_burnt < transactions_gas_limit {
             if let Some(iter) = pool_iterator.next() {
@@ -773,9 +848,10 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_height: BlockHeight,
         approvals: &[Option<Signature>],
     ) -> Result<bool, Error> {
-        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
-        let info =
-            epoch_manager.get_all_block_approvers_ordered(prev_block_hash).map_err(Error::from)?;
+        let info = {
+            let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+            epoch_manager.get_all_block_approvers_ordered(prev_block_hash).map_err(Error::from)?
+        };
         if approvals.len() > info.len() {
             return Ok(false);
         }
@@ -789,12 +865,23 @@ impl RuntimeAdapter for NightshadeRuntime {
             block_height,
         );
 
-        for (validator, may_be_signature) in info.into_iter().zip(approvals.iter()) {
-            if let Some(signature) = may_be_signature {
-                if !signature.verify(message_to_sign.as_ref(), &validator.public_key) {
-                    return Ok(false);
-                }
-            }
+        let signatures_to_verify: Vec<_> = info
+            .iter()
+            .zip(approvals.iter())
+            .filter_map(|(validator, may_be_signature)| {
+                may_be_signature
+                    .as_ref()
+                    .map(|signature| (message_to_sign.as_slice(), signature, &validator.public_key))
+            })
+            .filter(|(data, signature, public_key)| {
+                !self.verified_approvals.contains(data, signature, public_key)
+            })
+            .collect();
+        if !verify_batch_parallel(&signatures_to_verify) {
+            return Ok(false);
+        }
+        for (data, signature, public_key) in signatures_to_verify {
+            self.verified_approvals.insert(data, signature, public_key);
         }
         Ok(true)
     }
@@ -810,6 +897,11 @@ impl RuntimeAdapter for NightshadeRuntime {
             .map_err(Error::from)
     }
 
+    fn get_epoch_chunk_producers(&self, epoch_id: &EpochId) -> Result<Vec<ValidatorStake>, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        epoch_manager.get_all_chunk_producers(epoch_id).map_err(Error::from)
+    }
+
     fn get_epoch_block_approvers_ordered(
         &self,
         parent_hash: &CryptoHash,
@@ -969,7 +1061,7 @@ impl RuntimeAdapter for NightshadeRuntime {
             // maintain pointers to avoid cloning.
             let mut last_block_in_prev_epoch = epoch_first_block_info.prev_hash;
             let mut epoch_start_height = epoch_first_block_info.height;
-            for _ in 0..NUM_EPOCHS_TO_KEEP_STORE_DATA - 1 {
+            for _ in 0..self.gc_num_epochs_to_keep - 1 {
                 let epoch_first_block =
                     epoch_manager.get_block_info(&last_block_in_prev_epoch)?.epoch_first_block;
                 let epoch_first_block_info = epoch_manager.get_block_info(&epoch_first_block)?;
@@ -996,6 +1088,21 @@ impl RuntimeAdapter for NightshadeRuntime {
         Ok(epoch_manager.get_epoch_info(epoch_id)?.protocol_version)
     }
 
+    fn get_protocol_config(&self, epoch_id: &EpochId) -> Result<ProtocolConfigView, Error> {
+        let protocol_version = self.get_epoch_protocol_version(epoch_id)?;
+        let economics = &self.block_economics_config;
+        Ok(ProtocolConfigView {
+            protocol_version,
+            gas_limit: economics.gas_limit(protocol_version),
+            max_block_size: economics.max_block_size(protocol_version),
+            min_gas_price: economics.min_gas_price(protocol_version),
+            max_gas_price: economics.max_gas_price(protocol_version),
+            gas_price_adjustment_rate: economics.gas_price_adjustment_rate(protocol_version),
+            runtime_config: self.runtime_config_store.get_config(protocol_version).as_ref().clone(),
+            ..ProtocolConfigView::from(&self.genesis_config)
+        })
+    }
+
     fn add_validator_proposals(&self, block_header_info: BlockHeaderInfo) -> Result<(), Error> {
         // Check that genesis block doesn't have any proposals.
         assert!(
@@ -1164,7 +1271,15 @@ impl RuntimeAdapter for NightshadeRuntime {
                 }
             }
             QueryRequest::ViewState { account_id, prefix } => {
-                match self.view_state(shard_id, *state_root, account_id, prefix.as_ref()) {
+                match self.view_state(
+                    shard_id,
+                    *state_root,
+                    account_id,
+                    prefix.as_ref(),
+                    None,
+                    None,
+                    false,
+                ) {
                     Ok(result) => Ok(QueryResponse {
                         kind: QueryResponseKind::ViewState(result),
                         block_height,
@@ -1180,18 +1295,31 @@ impl RuntimeAdapter for NightshadeRuntime {
                     }),
                 }
             }
-            QueryRequest::ViewAccessKeyList { account_id } => {
-                match self.view_access_keys(shard_id, *state_root, account_id) {
+            QueryRequest::ViewStatePaginated {
+                account_id,
+                prefix,
+                cursor,
+                limit,
+                include_proof,
+            } => {
+                if *limit == Some(0) {
+                    return Err("Limit must be positive".into());
+                }
+                let limit = std::cmp::min(
+                    limit.unwrap_or(MAX_STATE_VALUES_PER_QUERY),
+                    MAX_STATE_VALUES_PER_QUERY,
+                ) as usize;
+                match self.view_state(
+                    shard_id,
+                    *state_root,
+                    account_id,
+                    prefix.as_ref(),
+                    cursor.as_deref(),
+                    Some(limit),
+                    *include_proof,
+                ) {
                     Ok(result) => Ok(QueryResponse {
-                        kind: QueryResponseKind::AccessKeyList(
-                            result
-                                .into_iter()
-                                .map(|(public_key, access_key)| AccessKeyInfoView {
-                                    public_key,
-                                    access_key: access_key.into(),
-                                })
-                                .collect(),
-                        ),
+                        kind: QueryResponseKind::ViewState(result),
                         block_height,
                         block_hash: *block_hash,
                     }),
@@ -1205,6 +1333,33 @@ impl RuntimeAdapter for NightshadeRuntime {
                     }),
                 }
             }
+            QueryRequest::ViewAccessKeyList { account_id } => Ok(self.query_access_key_list(
+                shard_id,
+                state_root,
+                block_height,
+                block_hash,
+                account_id,
+                None,
+                None,
+            )),
+            QueryRequest::ViewAccessKeyListPaginated { account_id, cursor, limit } => {
+                if *limit == Some(0) {
+                    return Err("Limit must be positive".into());
+                }
+                let limit = std::cmp::min(
+                    limit.unwrap_or(MAX_ACCESS_KEYS_PER_QUERY),
+                    MAX_ACCESS_KEYS_PER_QUERY,
+                ) as usize;
+                Ok(self.query_access_key_list(
+                    shard_id,
+                    state_root,
+                    block_height,
+                    block_hash,
+                    account_id,
+                    cursor.as_ref(),
+                    Some(limit),
+                ))
+            }
             QueryRequest::ViewAccessKey { account_id, public_key } => {
                 match self.view_access_key(shard_id, *state_root, account_id, public_key) {
                     Ok(access_key) => Ok(QueryResponse {
@@ -1222,6 +1377,60 @@ impl RuntimeAdapter for NightshadeRuntime {
                     }),
                 }
             }
+            QueryRequest::ViewContractMetadata { account_id } => {
+                match self.view_contract_metadata(shard_id, *state_root, account_id) {
+                    Ok(methods) => Ok(QueryResponse {
+                        kind: QueryResponseKind::ContractMetadata(ContractMetadataView {
+                            methods: methods.into_iter().map(Into::into).collect(),
+                        }),
+                        block_height,
+                        block_hash: *block_hash,
+                    }),
+                    Err(err) => Ok(QueryResponse {
+                        kind: QueryResponseKind::Error(QueryError {
+                            error: err.to_string(),
+                            logs: vec![],
+                        }),
+                        block_height,
+                        block_hash: *block_hash,
+                    }),
+                }
+            }
+            QueryRequest::ViewAccountsByPrefix { prefix, cursor, limit } => {
+                if *limit == Some(0) {
+                    return Err("Limit must be positive".into());
+                }
+                let limit =
+                    std::cmp::min(limit.unwrap_or(MAX_ACCOUNTS_PER_QUERY), MAX_ACCOUNTS_PER_QUERY)
+                        as usize;
+                match self.view_accounts(shard_id, *state_root, prefix, cursor.as_ref(), limit + 1)
+                {
+                    Ok(mut accounts) => {
+                        let next_cursor = if accounts.len() > limit {
+                            accounts.truncate(limit);
+                            accounts.last().cloned()
+                        } else {
+                            None
+                        };
+                        Ok(QueryResponse {
+                            kind: QueryResponseKind::AccountList(AccountList {
+                                accounts,
+                                next_cursor,
+                            }),
+                            block_height,
+                            block_hash: *block_hash,
+                        })
+                    }
+                    Err(err) => Ok(QueryResponse {
+                        kind: QueryResponseKind::Error(QueryError {
+                            error: err.to_string(),
+                            logs: vec![],
+                        }),
+                        block_height,
+                        block_hash: *block_hash,
+                    }),
+                }
+            }
         }
     }
 
@@ -1433,12 +1642,22 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         shard_id: ShardId,
         state_root: MerkleHash,
         account_id: &AccountId,
+        cursor: Option<&PublicKey>,
+        limit: Option<usize>,
     ) -> Result<Vec<(PublicKey, AccessKey)>, Box<dyn std::error::Error>> {
         let state_update = self.get_tries().new_trie_update_view(shard_id, state_root);
         let prefix = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
         let raw_prefix: &[u8] = prefix.as_ref();
-        let access_keys = match state_update.iter(&prefix) {
+        let start =
+            cursor.map(|public_key| public_key.try_to_vec()).transpose()?.unwrap_or_default();
+        let access_keys = match TrieUpdateIterator::new(&state_update, &prefix, &start, None) {
             Ok(iter) => iter
+                // The cursor itself was returned with the previous page.
+                .filter(|key| {
+                    cursor.is_none()
+                        || key.as_ref().map_or(true, |key| key[raw_prefix.len()..] != start[..])
+                })
+                .take(limit.unwrap_or(usize::MAX))
                 .map(|key| {
                     let key = key?;
                     let public_key = &key[raw_prefix.len()..];
@@ -1454,15 +1673,58 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         access_keys
     }
 
+    fn view_accounts(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+        prefix: &str,
+        cursor: Option<&AccountId>,
+        limit: usize,
+    ) -> Result<Vec<AccountId>, Box<dyn std::error::Error>> {
+        let state_update = self.get_tries().new_trie_update_view(shard_id, state_root);
+        let raw_prefix = TrieKey::Account { account_id: prefix.to_string() }.to_vec();
+        let start: &[u8] = match cursor {
+            Some(cursor) if cursor.starts_with(prefix) => &cursor.as_bytes()[prefix.len()..],
+            Some(cursor) => {
+                return Err(
+                    format!("Cursor {} doesn't start with the prefix {}", cursor, prefix).into()
+                )
+            }
+            None => &[],
+        };
+        TrieUpdateIterator::new(&state_update, &raw_prefix, start, None)?
+            // The cursor itself was returned with the previous page.
+            .filter(|key| {
+                cursor.is_none()
+                    || key.as_ref().map_or(true, |key| key[raw_prefix.len()..] != start[..])
+            })
+            .take(limit)
+            .map(|key| Ok(trie_key_parsers::parse_account_id_from_account_key(&key?)?))
+            .collect()
+    }
+
     fn view_state(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
         account_id: &AccountId,
         prefix: &[u8],
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+        include_proof: bool,
     ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
         let state_update = self.get_tries().new_trie_update_view(shard_id, state_root);
-        self.trie_viewer.view_state(&state_update, account_id, prefix)
+        self.trie_viewer.view_state(&state_update, account_id, prefix, cursor, limit, include_proof)
+    }
+
+    fn view_contract_metadata(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+    ) -> Result<Vec<MethodMetadata>, Box<dyn std::error::Error>> {
+        let state_update = self.get_tries().new_trie_update_view(shard_id, state_root);
+        self.trie_viewer.view_contract_metadata(&state_update, account_id)
     }
 }
 
@@ -2207,6 +2469,8 @@ mod test {
                 shards: vec![0],
                 num_produced_blocks: 1,
                 num_expected_blocks: 1,
+                num_produced_chunks: 1,
+                num_expected_chunks: 1,
             },
             CurrentEpochValidatorInfo {
                 account_id: "test2".to_string(),
@@ -2216,6 +2480,8 @@ mod test {
                 shards: vec![0],
                 num_produced_blocks: 1,
                 num_expected_blocks: 1,
+                num_produced_chunks: 1,
+                num_expected_chunks: 1,
             },
         ];
         let next_epoch_validator_info = vec![
@@ -2247,6 +2513,10 @@ mod test {
                 }
                 .into()],
                 prev_epoch_kickout: Default::default(),
+                current_epoch_kickout: Some(vec![ValidatorKickoutView {
+                    account_id: "test1".to_string(),
+                    reason: ValidatorKickoutReason::Unstaked
+                }]),
                 epoch_start_height: 1
             }
         );
@@ -2255,6 +2525,8 @@ mod test {
 
         current_epoch_validator_info[1].num_produced_blocks = 0;
         current_epoch_validator_info[1].num_expected_blocks = 0;
+        current_epoch_validator_info[1].num_produced_chunks = 0;
+        current_epoch_validator_info[1].num_expected_chunks = 0;
         assert_eq!(response.current_validators, current_epoch_validator_info);
         assert_eq!(
             response.next_validators,
@@ -2274,6 +2546,7 @@ mod test {
                 reason: ValidatorKickoutReason::Unstaked
             }]
         );
+        assert_eq!(response.current_epoch_kickout, None);
         assert_eq!(response.epoch_start_height, 3);
     }
 
@@ -2823,4 +3096,28 @@ mod test {
         assert_eq!(env.last_proposals.len(), 1);
         assert_eq!(env.last_proposals[0].stake, 0);
     }
+
+    /// Fewer epochs than `MIN_EPOCHS_TO_KEEP_STORE_DATA` are never garbage collected.
+    #[test]
+    fn test_gc_num_epochs_to_keep() {
+        let epoch_length = 5;
+        let mut env = TestEnv::new(
+            "test_gc_num_epochs_to_keep",
+            vec![vec!["test1".to_string()]],
+            epoch_length,
+            vec![],
+            vec![],
+            false,
+        );
+        for _ in 0..epoch_length * (NUM_EPOCHS_TO_KEEP_STORE_DATA + 1) {
+            env.step_default(vec![]);
+        }
+        let head_hash = env.head.last_block_hash;
+        let default_stop_height = env.runtime.get_gc_stop_height(&head_hash);
+        env.runtime.set_gc_num_epochs_to_keep(MIN_EPOCHS_TO_KEEP_STORE_DATA);
+        let min_stop_height = env.runtime.get_gc_stop_height(&head_hash);
+        assert!(min_stop_height > default_stop_height);
+        env.runtime.set_gc_num_epochs_to_keep(1);
+        assert_eq!(env.runtime.get_gc_stop_height(&head_hash), min_stop_height);
+    }
 }
@@ -250,6 +250,7 @@ mod tests {
             num_shards,
             num_block_producer_seats: 1,
             num_block_producer_seats_per_shard: vec![1],
+            num_chunk_only_producer_seats: 0,
             avg_hidden_validator_seats_per_shard: vec![],
             block_producer_kickout_threshold: 90,
             chunk_producer_kickout_threshold: 60,
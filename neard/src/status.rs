@@ -0,0 +1,404 @@
+//! `neard status --rich`: queries the local node over JSON-RPC and prints a human readable
+//! health summary for operators that don't run a monitoring stack.
+use std::fs;
+use std::path::Path;
+
+use actix::System;
+use chrono::Utc;
+
+use near_jsonrpc_client::new_client;
+use near_primitives::views::{EpochValidatorInfo, StatusResponse};
+
+use crate::config::NearConfig;
+
+/// A block older than this is considered stale and the node is likely stuck.
+const STALE_BLOCK_SECS: i64 = 60;
+/// Warn when the disk holding the data directory has less free space than this.
+const MIN_FREE_DISK_BYTES: u64 = 50 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn colored_label(&self) -> &'static str {
+        match self {
+            Severity::Ok => "\x1b[32m  OK \x1b[0m",
+            Severity::Warning => "\x1b[33m WARN\x1b[0m",
+            Severity::Error => "\x1b[31mERROR\x1b[0m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: String) -> Self {
+        Self { severity, message }
+    }
+}
+
+/// Everything `--rich` knows about the node, collected before any diagnostics are run.
+pub struct NodeReport {
+    pub status: StatusResponse,
+    pub validators: Option<EpochValidatorInfo>,
+    pub num_active_peers: Option<u64>,
+    pub data_dir_size: Option<u64>,
+    pub free_disk_space: Option<u64>,
+}
+
+/// Thresholds taken from the local config and genesis.
+pub struct DiagnosticThresholds {
+    pub min_num_peers: usize,
+    pub block_producer_kickout_threshold: u8,
+}
+
+impl From<&NearConfig> for DiagnosticThresholds {
+    fn from(near_config: &NearConfig) -> Self {
+        Self {
+            min_num_peers: near_config.client_config.min_num_peers,
+            block_producer_kickout_threshold: near_config
+                .genesis
+                .config
+                .block_producer_kickout_threshold,
+        }
+    }
+}
+
+/// Turns a node report into a list of actionable diagnostics.
+pub fn diagnose(report: &NodeReport, thresholds: &DiagnosticThresholds) -> Vec<Diagnostic> {
+    let mut result = vec![];
+    let status = &report.status;
+
+    if status.latest_protocol_version < status.protocol_version {
+        result.push(Diagnostic::new(
+            Severity::Error,
+            format!(
+                "Binary supports protocol version {} but the chain is at {}: upgrade neard",
+                status.latest_protocol_version, status.protocol_version
+            ),
+        ));
+    } else if status.latest_protocol_version > status.protocol_version {
+        result.push(Diagnostic::new(
+            Severity::Ok,
+            format!(
+                "Binary votes for protocol version {} (chain is at {})",
+                status.latest_protocol_version, status.protocol_version
+            ),
+        ));
+    } else {
+        result.push(Diagnostic::new(
+            Severity::Ok,
+            format!("Protocol version {}", status.protocol_version),
+        ));
+    }
+
+    let block_age = (Utc::now() - status.sync_info.latest_block_time).num_seconds();
+    if status.sync_info.syncing {
+        result.push(Diagnostic::new(
+            Severity::Warning,
+            format!(
+                "Node is syncing, head is at #{} ({}s behind)",
+                status.sync_info.latest_block_height, block_age
+            ),
+        ));
+    } else if block_age > STALE_BLOCK_SECS {
+        result.push(Diagnostic::new(
+            Severity::Error,
+            format!(
+                "Latest block #{} is {}s old: node is not receiving blocks",
+                status.sync_info.latest_block_height, block_age
+            ),
+        ));
+    } else {
+        result.push(Diagnostic::new(
+            Severity::Ok,
+            format!("Synced, head is at #{}", status.sync_info.latest_block_height),
+        ));
+    }
+
+    match report.num_active_peers {
+        Some(peers) if (peers as usize) < thresholds.min_num_peers => {
+            result.push(Diagnostic::new(
+                Severity::Error,
+                format!(
+                    "Only {} active peers, at least {} are required to sync and produce blocks",
+                    peers, thresholds.min_num_peers
+                ),
+            ));
+        }
+        Some(peers) => {
+            result.push(Diagnostic::new(Severity::Ok, format!("{} active peers", peers)));
+        }
+        None => {
+            result.push(Diagnostic::new(
+                Severity::Warning,
+                "Failed to fetch network info".to_string(),
+            ));
+        }
+    }
+
+    if let Some(free) = report.free_disk_space {
+        if free < MIN_FREE_DISK_BYTES {
+            result.push(Diagnostic::new(
+                Severity::Warning,
+                format!("Only {} GiB of free disk space left", free / (1024 * 1024 * 1024)),
+            ));
+        }
+    }
+
+    if let Some(account_id) = &status.validator_account_id {
+        result.extend(diagnose_validator(account_id, report.validators.as_ref(), thresholds));
+    }
+    result
+}
+
+fn diagnose_validator(
+    account_id: &str,
+    validators: Option<&EpochValidatorInfo>,
+    thresholds: &DiagnosticThresholds,
+) -> Vec<Diagnostic> {
+    let mut result = vec![];
+    let validators = match validators {
+        Some(validators) => validators,
+        None => {
+            return vec![Diagnostic::new(
+                Severity::Warning,
+                "Failed to fetch validators info".to_string(),
+            )]
+        }
+    };
+
+    if let Some(kickout) = validators.prev_epoch_kickout.iter().find(|k| k.account_id == account_id)
+    {
+        result.push(Diagnostic::new(
+            Severity::Error,
+            format!("{} was kicked out in the previous epoch: {:?}", account_id, kickout.reason),
+        ));
+    }
+
+    match validators.current_validators.iter().find(|v| v.account_id == account_id) {
+        Some(info) => {
+            if info.is_slashed {
+                result.push(Diagnostic::new(
+                    Severity::Error,
+                    format!("{} is slashed and will be kicked out", account_id),
+                ));
+            }
+            let threshold = u64::from(thresholds.block_producer_kickout_threshold);
+            if info.num_expected_blocks > 0
+                && info.num_produced_blocks * 100 < info.num_expected_blocks * threshold
+            {
+                result.push(Diagnostic::new(
+                    Severity::Error,
+                    format!(
+                        "You will be kicked out because {} produced {}/{} expected blocks, \
+                         below the {}% threshold",
+                        account_id, info.num_produced_blocks, info.num_expected_blocks, threshold
+                    ),
+                ));
+            } else {
+                result.push(Diagnostic::new(
+                    Severity::Ok,
+                    format!(
+                        "{} is validating, produced {}/{} expected blocks",
+                        account_id, info.num_produced_blocks, info.num_expected_blocks
+                    ),
+                ));
+            }
+        }
+        None => result.push(Diagnostic::new(
+            Severity::Warning,
+            format!("{} is not a validator in the current epoch", account_id),
+        )),
+    }
+
+    if validators.next_validators.iter().all(|v| v.account_id != account_id) {
+        let reason = if validators.current_proposals.iter().any(|p| p.account_id == account_id) {
+            "proposal is pending"
+        } else {
+            "no stake proposal was found, stake to get a seat"
+        };
+        result.push(Diagnostic::new(
+            Severity::Warning,
+            format!("{} is not a validator in the next epoch: {}", account_id, reason),
+        ));
+    }
+    result
+}
+
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(size)
+}
+
+fn free_disk_space(path: &Path) -> Option<u64> {
+    let path = std::ffi::CString::new(path.to_str()?).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+fn rpc_url(rpc_addr: &str) -> String {
+    format!("http://{}", rpc_addr.replace("0.0.0.0", "127.0.0.1"))
+}
+
+/// Queries the node running from `home_dir` and prints a colored summary to stdout.
+pub fn print_rich_status(home_dir: &Path, near_config: &NearConfig, rpc_addr: Option<&str>) {
+    let url = rpc_url(rpc_addr.unwrap_or(&near_config.rpc_config.addr));
+    let store_path = home_dir.join("data");
+
+    let mut system = System::new("status");
+    let report = system.block_on(async move {
+        let client = new_client(&url);
+        let status = client.status().await.map_err(|err| err.to_string())?;
+        let validators = client.validators(None).await.ok();
+        let num_active_peers = client
+            .network_info()
+            .await
+            .ok()
+            .and_then(|info| info.get("num_active_peers").and_then(|peers| peers.as_u64()));
+        Ok::<_, String>(NodeReport {
+            status,
+            validators,
+            num_active_peers,
+            data_dir_size: dir_size(&store_path).ok(),
+            free_disk_space: free_disk_space(&store_path),
+        })
+    });
+
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            println!("{} Failed to query local node: {}", Severity::Error.colored_label(), err);
+            println!("        Is neard running and is the RPC server enabled?");
+            std::process::exit(1);
+        }
+    };
+
+    let status = &report.status;
+    println!(
+        "Node {} (build {}) on chain {}",
+        status.version.version, status.version.build, status.chain_id
+    );
+    if let Some(account_id) = &status.validator_account_id {
+        println!("Validator account: {}", account_id);
+    }
+    if let Some(size) = report.data_dir_size {
+        println!("Data directory: {} MiB", size / (1024 * 1024));
+    }
+    println!();
+    for diagnostic in diagnose(&report, &DiagnosticThresholds::from(near_config)) {
+        println!("{} {}", diagnostic.severity.colored_label(), diagnostic.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use near_crypto::{KeyType, PublicKey};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::version::{Version, PROTOCOL_VERSION};
+    use near_primitives::views::{CurrentEpochValidatorInfo, StatusSyncInfo};
+
+    use super::*;
+
+    fn report(validators: Option<EpochValidatorInfo>, peers: u64) -> NodeReport {
+        NodeReport {
+            status: StatusResponse {
+                version: Version { version: "test".to_string(), build: "test".to_string() },
+                chain_id: "test".to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                latest_protocol_version: PROTOCOL_VERSION,
+                rpc_addr: "0.0.0.0:3030".to_string(),
+                validators: vec![],
+                sync_info: StatusSyncInfo {
+                    latest_block_hash: CryptoHash::default(),
+                    latest_block_height: 10,
+                    latest_state_root: CryptoHash::default(),
+                    latest_block_time: Utc::now(),
+                    syncing: false,
+                },
+                validator_account_id: Some("test0".to_string()),
+                deprecated_usage: vec![],
+            },
+            validators,
+            num_active_peers: Some(peers),
+            data_dir_size: None,
+            free_disk_space: None,
+        }
+    }
+
+    fn validators(produced: u64, expected: u64) -> EpochValidatorInfo {
+        EpochValidatorInfo {
+            current_validators: vec![CurrentEpochValidatorInfo {
+                account_id: "test0".to_string(),
+                public_key: PublicKey::empty(KeyType::ED25519),
+                is_slashed: false,
+                stake: 1,
+                shards: vec![0],
+                num_produced_blocks: produced,
+                num_expected_blocks: expected,
+                num_produced_chunks: produced,
+                num_expected_chunks: expected,
+            }],
+            next_validators: vec![],
+            current_fishermen: vec![],
+            next_fishermen: vec![],
+            current_proposals: vec![],
+            prev_epoch_kickout: vec![],
+            current_epoch_kickout: None,
+            epoch_start_height: 1,
+        }
+    }
+
+    fn thresholds() -> DiagnosticThresholds {
+        DiagnosticThresholds { min_num_peers: 3, block_producer_kickout_threshold: 90 }
+    }
+
+    fn errors(diagnostics: Vec<Diagnostic>) -> Vec<String> {
+        diagnostics
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_healthy_validator() {
+        let diagnostics = diagnose(&report(Some(validators(10, 10)), 5), &thresholds());
+        assert!(errors(diagnostics).is_empty());
+    }
+
+    #[test]
+    fn test_kickout_and_peers() {
+        let errors = errors(diagnose(&report(Some(validators(5, 10)), 1), &thresholds()));
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("You will be kicked out")));
+        assert!(errors.iter().any(|e| e.starts_with("Only 1 active peers")));
+    }
+
+    #[test]
+    fn test_outdated_binary() {
+        let mut report = report(None, 5);
+        report.status.protocol_version = report.status.latest_protocol_version + 1;
+        let errors = errors(diagnose(&report, &thresholds()));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with("upgrade neard"));
+    }
+}
@@ -0,0 +1,57 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use actix::{Actor, System};
+use futures::{future, FutureExt};
+
+use near_chain_configs::Genesis;
+use near_client::GetBlock;
+use near_logger_utils::init_integration_logger;
+use near_network::test_utils::{convert_boot_nodes, open_port, WaitOrTimeout};
+use neard::{config::GenesisExt, load_test_config, start, NodeOptions};
+use testlib::test_helpers::heavy_test;
+
+/// Starts a node without RPC through the library API, waits for a few blocks and shuts it down.
+#[test]
+fn embedded_node_shutdown() {
+    heavy_test(|| {
+        init_integration_logger();
+
+        let genesis = Genesis::test(vec!["test1"], 1);
+        let mut near = load_test_config("test1", open_port(), genesis);
+        near.network_config.boot_nodes = convert_boot_nodes(vec![]);
+        near.client_config.min_num_peers = 0;
+        let system = System::new("NEAR");
+
+        let dir = tempfile::Builder::new().prefix("embedded_node").tempdir().unwrap();
+        let node = start(dir.path(), near, NodeOptions { enable_rpc: false, ..Default::default() });
+        assert!(node.rpc_server.is_none());
+        let view_client = node.view_client.clone();
+        let node = Rc::new(RefCell::new(Some(node)));
+
+        WaitOrTimeout::new(
+            Box::new(move |_ctx| {
+                let node = node.clone();
+                actix::spawn(view_client.send(GetBlock::latest()).then(move |res| {
+                    match &res {
+                        Ok(Ok(b)) if b.header.height >= 5 => {
+                            if let Some(mut node) = node.borrow_mut().take() {
+                                // Shutting the node down stops the system, otherwise the test
+                                // times out.
+                                actix::spawn(async move { node.shutdown().await });
+                            }
+                        }
+                        Err(_) => return future::ready(()),
+                        _ => {}
+                    };
+                    future::ready(())
+                }));
+            }),
+            100,
+            60000,
+        )
+        .start();
+
+        system.run().unwrap();
+    });
+}
@@ -113,6 +113,21 @@ pub struct ActionCreationConfig {
 
     /// Base cost of deleting an account.
     pub delete_account_cost: Fee,
+
+    /// Base cost of an idempotency key.
+    #[serde(default = "default_idempotency_key_cost")]
+    pub idempotency_key_cost: Fee,
+    /// Cost per byte of an idempotency key.
+    #[serde(default = "default_idempotency_key_cost_per_byte")]
+    pub idempotency_key_cost_per_byte: Fee,
+}
+
+fn default_idempotency_key_cost() -> Fee {
+    Fee { send_sir: 94946625000, send_not_sir: 94946625000, execution: 94946625000 }
+}
+
+fn default_idempotency_key_cost_per_byte() -> Fee {
+    Fee { send_sir: 2235934, send_not_sir: 2235934, execution: 2235934 }
 }
 
 /// Describes the cost of creating an access key.
@@ -219,6 +234,8 @@ impl Default for RuntimeFeesConfig {
                     send_not_sir: 147489000000,
                     execution: 147489000000,
                 },
+                idempotency_key_cost: default_idempotency_key_cost(),
+                idempotency_key_cost_per_byte: default_idempotency_key_cost_per_byte(),
             },
             storage_usage_config: StorageUsageConfig {
                 // See Account in core/primitives/src/account.rs for the data structure.
@@ -255,7 +272,9 @@ impl RuntimeFeesConfig {
                     function_call_cost_per_byte: free.clone(),
                 },
                 delete_key_cost: free.clone(),
-                delete_account_cost: free,
+                delete_account_cost: free.clone(),
+                idempotency_key_cost: free.clone(),
+                idempotency_key_cost_per_byte: free,
             },
             storage_usage_config: StorageUsageConfig {
                 num_bytes_account: 0,
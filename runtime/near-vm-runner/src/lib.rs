@@ -2,11 +2,13 @@ mod cache;
 mod errors;
 mod imports;
 mod memory;
+mod metadata;
 pub mod prepare;
 mod runner;
 mod wasmer_runner;
 #[cfg(feature = "wasmtime_vm")]
 mod wasmtime_runner;
+pub use metadata::{method_metadata, METHOD_METADATA_SECTION};
 pub use near_vm_errors::VMError;
 pub use runner::compile_module;
 pub use runner::run;
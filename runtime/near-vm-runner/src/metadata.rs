@@ -0,0 +1,59 @@
+//! Metadata that contracts store in custom sections of their code.
+
+use borsh::BorshDeserialize;
+use parity_wasm::elements;
+
+use near_primitives::contract::MethodMetadata;
+
+/// Name of the custom section with borsh serialized `Vec<MethodMetadata>`, the recommended gas
+/// and deposit for calls of the contract methods.
+pub const METHOD_METADATA_SECTION: &str = "near_method_metadata";
+
+/// Reads the method metadata from the contract code. Code without the section has no metadata.
+pub fn method_metadata(code: &[u8]) -> Result<Vec<MethodMetadata>, String> {
+    let module: elements::Module = elements::deserialize_buffer(code)
+        .map_err(|err| format!("Failed to deserialize contract code: {}", err))?;
+    match module.custom_sections().find(|section| section.name() == METHOD_METADATA_SECTION) {
+        Some(section) => Vec::<MethodMetadata>::try_from_slice(section.payload())
+            .map_err(|err| format!("Invalid {} section: {}", METHOD_METADATA_SECTION, err)),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use parity_wasm::elements::{CustomSection, Section};
+    use wabt;
+
+    use super::*;
+
+    fn code_with_section(payload: Vec<u8>) -> Vec<u8> {
+        let wasm = wabt::wat2wasm("(module)").unwrap();
+        let mut module: elements::Module = elements::deserialize_buffer(&wasm).unwrap();
+        module.sections_mut().push(Section::Custom(CustomSection::new(
+            METHOD_METADATA_SECTION.to_string(),
+            payload,
+        )));
+        elements::serialize(module).unwrap()
+    }
+
+    #[test]
+    fn test_method_metadata() {
+        let metadata = vec![MethodMetadata {
+            method_name: "transfer".to_string(),
+            gas: 10_000_000_000_000,
+            deposit: 1,
+        }];
+        let code = code_with_section(metadata.try_to_vec().unwrap());
+        assert_eq!(method_metadata(&code).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_no_method_metadata() {
+        let code = wabt::wat2wasm("(module)").unwrap();
+        assert_eq!(method_metadata(&code).unwrap(), vec![]);
+        assert!(method_metadata(&code_with_section(vec![1, 2, 3])).is_err());
+        assert!(method_metadata(b"not wasm").is_err());
+    }
+}
@@ -13,6 +13,7 @@ use near_primitives::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
 };
+use near_primitives::version::PROTOCOL_VERSION;
 
 use crate::ext_costs_generator::ExtCostsGenerator;
 use crate::runtime_fees_generator::RuntimeFeesGenerator;
@@ -26,7 +27,7 @@ use near_runtime_fees::{
     RuntimeFeesConfig,
 };
 use near_vm_logic::{ExtCosts, ExtCostsConfig, VMConfig, VMLimitConfig};
-use node_runtime::config::RuntimeConfig;
+use node_runtime::config::{RuntimeConfig, RuntimeConfigStore};
 
 /// How much gas there is in a nanosecond worth of computation.
 const GAS_IN_MEASURE_UNIT: u128 = 1_000_000u128;
@@ -563,6 +564,7 @@ fn get_runtime_fees_config(measurement: &Measurements) -> RuntimeFeesConfig {
     let generator = RuntimeFeesGenerator::new(measurement);
     let measured = generator.compute();
     let metric = measurement.gas_metric;
+    let default_fees = RuntimeFeesConfig::default();
     let function_call_total_cost = ratio_to_gas(metric, measured[&ActionFunctionCallBase]);
     let function_call_cost = Fee {
         send_sir: function_call_total_cost / 2,
@@ -602,6 +604,12 @@ fn get_runtime_fees_config(measurement: &Measurements) -> RuntimeFeesConfig {
             },
             delete_key_cost: measured_to_fee(metric, measured[&ActionDeleteKey]),
             delete_account_cost: measured_to_fee(metric, measured[&ActionDeleteAccount]),
+            // Not measured, the idempotency key is only indexed off the state.
+            idempotency_key_cost: default_fees.action_creation_config.idempotency_key_cost.clone(),
+            idempotency_key_cost_per_byte: default_fees
+                .action_creation_config
+                .idempotency_key_cost_per_byte
+                .clone(),
         },
         ..Default::default()
     }
@@ -688,7 +696,10 @@ fn get_vm_config(measurement: &Measurements, config: &Config) -> VMConfig {
 }
 
 fn get_runtime_config(measurement: &Measurements, config: &Config) -> RuntimeConfig {
-    let mut runtime_config = RuntimeConfig::default();
+    let mut runtime_config = RuntimeConfigStore::new(&RuntimeConfig::default())
+        .get_config(PROTOCOL_VERSION)
+        .as_ref()
+        .clone();
     runtime_config.wasm_config = get_vm_config(measurement, config);
 
     // Compiling small test contract that was used for `noop` function call estimation.
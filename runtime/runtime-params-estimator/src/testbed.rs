@@ -12,7 +12,7 @@ use near_primitives::version::PROTOCOL_VERSION;
 use near_store::{create_store, ColState, ShardTries, StoreCompiledContractCache};
 use near_vm_logic::VMLimitConfig;
 use neard::get_store_path;
-use node_runtime::config::RuntimeConfig;
+use node_runtime::config::{RuntimeConfig, RuntimeConfigStore};
 use node_runtime::{ApplyState, Runtime};
 use std::sync::Arc;
 
@@ -53,7 +53,10 @@ impl RuntimeTestbed {
         assert!(!state_roots.is_empty(), "No state roots found.");
         let root = state_roots[0];
 
-        let mut runtime_config = RuntimeConfig::default();
+        let mut runtime_config = RuntimeConfigStore::new(&RuntimeConfig::default())
+            .get_config(PROTOCOL_VERSION)
+            .as_ref()
+            .clone();
 
         runtime_config.wasm_config.limit_config = VMLimitConfig {
             max_total_log_length: u64::max_value(),
@@ -89,6 +92,7 @@ impl RuntimeTestbed {
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(runtime_config),
             cache: Some(Arc::new(StoreCompiledContractCache { store: tries.get_store() })),
+            verified_signatures: None,
         };
         Self {
             workdir,
@@ -229,6 +229,7 @@ impl RuntimeStandalone {
             config: self.runtime_config.clone(),
             // TODO: shall we use compiled contracts cache in standalone runtime?
             cache: None,
+            verified_signatures: None,
         };
 
         let apply_result = self.runtime.apply(
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshSerialize;
 use log::debug;
 
 use near_primitives::account::{AccessKey, AccessKeyPermission, Account};
@@ -27,7 +27,7 @@ use near_vm_logic::VMContext;
 use crate::config::{safe_add_gas, RuntimeConfig};
 use crate::ext::RuntimeExt;
 use crate::{ActionResult, ApplyState};
-use near_crypto::PublicKey;
+use near_crypto::{KeyType, PublicKey};
 use near_primitives::errors::{ActionError, ActionErrorKind, ExternalError, RuntimeError};
 use near_primitives::version::{
     ProtocolVersion, DELETE_KEY_STORAGE_USAGE_PROTOCOL_VERSION,
@@ -294,12 +294,15 @@ pub(crate) fn action_create_account(
     account_id: &AccountId,
     predecessor_id: &AccountId,
     result: &mut ActionResult,
+    current_protocol_version: ProtocolVersion,
 ) {
     // NOTE: The account_id is valid, because the Receipt is validated before.
     debug_assert!(is_valid_account_id(account_id));
 
     if is_valid_top_level_account_id(account_id) {
-        if account_id.len() < account_creation_config.min_allowed_top_level_account_length as usize
+        if current_protocol_version >= account_creation_config.registrar_only_from_protocol_version
+            && account_id.len()
+                < account_creation_config.min_allowed_top_level_account_length as usize
             && predecessor_id != &account_creation_config.registrar_account_id
         {
             // A short top-level account ID can only be created registrar account.
@@ -348,15 +351,9 @@ pub(crate) fn action_implicit_account_creation_transfer(
     *actor_id = account_id.clone();
 
     let access_key = AccessKey::full_access();
-    // 0 for ED25519
-    let mut public_key_data = Vec::with_capacity(33);
-    public_key_data.push(0u8);
-    public_key_data.extend(
-        hex::decode(account_id.as_bytes())
-            .expect("account id was a valid hex of length 64 resulting in 32 bytes"),
-    );
-    debug_assert_eq!(public_key_data.len(), 33);
-    let public_key = PublicKey::try_from_slice(&public_key_data)
+    let public_key_data = hex::decode(account_id.as_bytes())
+        .expect("account id was a valid hex of length 64 resulting in 32 bytes");
+    let public_key = PublicKey::from_parts(KeyType::ED25519, &public_key_data)
         .expect("we should be able to deserialize ED25519 public key");
 
     *account = Some(Account {
@@ -522,7 +519,10 @@ pub(crate) fn check_actor_permissions(
                 .into());
             }
         }
-        Action::CreateAccount(_) | Action::FunctionCall(_) | Action::Transfer(_) => (),
+        Action::CreateAccount(_)
+        | Action::FunctionCall(_)
+        | Action::Transfer(_)
+        | Action::IdempotencyKey(_) => (),
     };
     Ok(())
 }
@@ -591,7 +591,8 @@ pub(crate) fn check_account_existence(
         | Action::Stake(_)
         | Action::AddKey(_)
         | Action::DeleteKey(_)
-        | Action::DeleteAccount(_) => {
+        | Action::DeleteAccount(_)
+        | Action::IdempotencyKey(_) => {
             if account.is_none() {
                 return Err(ActionErrorKind::AccountDoesNotExist {
                     account_id: account_id.clone(),
@@ -605,12 +606,30 @@ pub(crate) fn check_account_existence(
 
 #[cfg(test)]
 mod tests {
+    use near_primitives::version::PROTOCOL_VERSION;
+
     use super::*;
 
     fn test_action_create_account(
         account_id: AccountId,
         predecessor_id: AccountId,
         length: u8,
+    ) -> ActionResult {
+        test_action_create_account_with_protocol_version(
+            account_id,
+            predecessor_id,
+            length,
+            0,
+            PROTOCOL_VERSION,
+        )
+    }
+
+    fn test_action_create_account_with_protocol_version(
+        account_id: AccountId,
+        predecessor_id: AccountId,
+        length: u8,
+        registrar_only_from_protocol_version: ProtocolVersion,
+        current_protocol_version: ProtocolVersion,
     ) -> ActionResult {
         let mut account = None;
         let mut actor_id = predecessor_id.clone();
@@ -620,12 +639,14 @@ mod tests {
             &AccountCreationConfig {
                 min_allowed_top_level_account_length: length,
                 registrar_account_id: AccountId::from("registrar"),
+                registrar_only_from_protocol_version,
             },
             &mut account,
             &mut actor_id,
             &account_id,
             &predecessor_id,
             &mut action_result,
+            current_protocol_version,
         );
         if action_result.result.is_ok() {
             assert!(account.is_some());
@@ -705,4 +726,44 @@ mod tests {
             test_action_create_account(account_id.clone(), predecessor_id.clone(), 0);
         assert!(action_result.result.is_ok());
     }
+
+    #[test]
+    fn test_create_account_short_top_level_registrar_only_from_protocol_version() {
+        let account_id = AccountId::from("bob");
+        let predecessor_id = AccountId::from("near");
+        let action_result = test_action_create_account_with_protocol_version(
+            account_id.clone(),
+            predecessor_id.clone(),
+            11,
+            40,
+            39,
+        );
+        assert!(action_result.result.is_ok());
+        let action_result = test_action_create_account_with_protocol_version(
+            account_id.clone(),
+            predecessor_id.clone(),
+            11,
+            40,
+            40,
+        );
+        assert_eq!(
+            action_result.result,
+            Err(ActionError {
+                index: None,
+                kind: ActionErrorKind::CreateAccountOnlyByRegistrar {
+                    account_id: account_id.clone(),
+                    registrar_account_id: AccountId::from("registrar"),
+                    predecessor_id: predecessor_id.clone(),
+                }
+            })
+        );
+        let action_result = test_action_create_account_with_protocol_version(
+            account_id,
+            AccountId::from("registrar"),
+            11,
+            40,
+            40,
+        );
+        assert!(action_result.result.is_ok());
+    }
 }
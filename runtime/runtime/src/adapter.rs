@@ -1,5 +1,6 @@
 use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, Account};
+use near_primitives::contract::MethodMetadata;
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::{
     AccountId, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, MerkleHash, ShardId,
@@ -41,18 +42,43 @@ pub trait ViewRuntimeAdapter {
         public_key: &PublicKey,
     ) -> Result<AccessKey, Box<dyn std::error::Error>>;
 
+    /// Returns up to `limit` access keys of the account ordered by public key, starting after
+    /// `cursor`.
     fn view_access_keys(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
         account_id: &AccountId,
+        cursor: Option<&PublicKey>,
+        limit: Option<usize>,
     ) -> Result<Vec<(PublicKey, AccessKey)>, Box<dyn std::error::Error>>;
 
+    /// Returns up to `limit` sorted ids of the accounts of the shard that start with `prefix`,
+    /// starting after `cursor`.
+    fn view_accounts(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+        prefix: &str,
+        cursor: Option<&AccountId>,
+        limit: usize,
+    ) -> Result<Vec<AccountId>, Box<dyn std::error::Error>>;
+
     fn view_state(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
         account_id: &AccountId,
         prefix: &[u8],
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+        include_proof: bool,
     ) -> Result<ViewStateResult, Box<dyn std::error::Error>>;
+
+    fn view_contract_metadata(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+    ) -> Result<Vec<MethodMetadata>, Box<dyn std::error::Error>>;
 }
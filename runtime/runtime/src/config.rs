@@ -2,13 +2,14 @@
 use near_primitives::account::AccessKeyPermission;
 use near_primitives::errors::IntegerOverflowError;
 use near_primitives::transaction::{
-    Action, AddKeyAction, DeployContractAction, FunctionCallAction, Transaction,
+    Action, AddKeyAction, DeployContractAction, FunctionCallAction, IdempotencyKeyAction,
+    Transaction,
 };
 use near_primitives::types::{AccountId, Balance, Gas};
 use near_runtime_fees::RuntimeFeesConfig;
 
 // Just re-exporting RuntimeConfig for backwards compatibility.
-pub use near_runtime_configs::RuntimeConfig;
+pub use near_runtime_configs::{RuntimeConfig, RuntimeConfigStore};
 
 use near_primitives::version::{ProtocolVersion, IMPLICIT_ACCOUNT_CREATION_PROTOCOL_VERSION};
 use near_runtime_utils::is_account_id_64_len_hex;
@@ -128,6 +129,11 @@ pub fn total_send_fees(
             },
             DeleteKey(_) => cfg.delete_key_cost.send_fee(sender_is_receiver),
             DeleteAccount(_) => cfg.delete_account_cost.send_fee(sender_is_receiver),
+            IdempotencyKey(IdempotencyKeyAction { key }) => {
+                let num_bytes = key.len() as u64;
+                cfg.idempotency_key_cost.send_fee(sender_is_receiver)
+                    + num_bytes * cfg.idempotency_key_cost_per_byte.send_fee(sender_is_receiver)
+            }
         };
         result = safe_add_gas(result, delta)?;
     }
@@ -182,6 +188,11 @@ pub fn exec_fee(
         },
         DeleteKey(_) => cfg.delete_key_cost.exec_fee(),
         DeleteAccount(_) => cfg.delete_account_cost.exec_fee(),
+        IdempotencyKey(IdempotencyKeyAction { key }) => {
+            let num_bytes = key.len() as u64;
+            cfg.idempotency_key_cost.exec_fee()
+                + cfg.idempotency_key_cost_per_byte.exec_fee() * num_bytes
+        }
     }
 }
 /// Returns transaction costs for a given transaction.
@@ -43,12 +43,13 @@ use crate::config::{
     exec_fee, safe_add_balance, safe_add_gas, safe_gas_to_balance, total_deposit, total_exec_fees,
     total_prepaid_gas, RuntimeConfig,
 };
+use crate::parallel::independent_receipt_groups;
+pub use crate::signature_cache::VerifiedSignaturesCache;
 use crate::verifier::validate_receipt;
 pub use crate::verifier::{validate_transaction, verify_and_charge_transaction};
 use near_primitives::version::{ProtocolVersion, IMPLICIT_ACCOUNT_CREATION_PROTOCOL_VERSION};
 use near_runtime_fees::RuntimeFeesConfig;
 use std::borrow::Borrow;
-use std::rc::Rc;
 use std::sync::Arc;
 
 mod actions;
@@ -58,6 +59,8 @@ pub mod cache;
 pub mod config;
 pub mod ext;
 mod metrics;
+mod parallel;
+pub mod signature_cache;
 pub mod state_viewer;
 mod verifier;
 
@@ -89,6 +92,8 @@ pub struct ApplyState {
     pub config: Arc<RuntimeConfig>,
     /// Cache for compiled contracts.
     pub cache: Option<Arc<dyn CompiledContractCache>>,
+    /// Transaction signatures that were already verified by this node.
+    pub verified_signatures: Option<Arc<VerifiedSignaturesCache>>,
 }
 
 /// Contains information to update validators accounts at the first block of a new epoch.
@@ -234,12 +239,16 @@ impl Runtime {
         stats: &mut ApplyStats,
     ) -> Result<(Receipt, ExecutionOutcomeWithId), RuntimeError> {
         near_metrics::inc_counter(&metrics::TRANSACTION_PROCESSED_TOTAL);
+        let verify_signature = match &apply_state.verified_signatures {
+            Some(verified_signatures) => !verified_signatures.contains(signed_transaction),
+            None => true,
+        };
         match verify_and_charge_transaction(
             &apply_state.config,
             state_update,
             apply_state.gas_price,
             signed_transaction,
-            true,
+            verify_signature,
             apply_state.current_protocol_version,
         ) {
             Ok(verification_result) => {
@@ -344,6 +353,7 @@ impl Runtime {
                     &receipt.receiver_id,
                     &receipt.predecessor_id,
                     &mut result,
+                    apply_state.current_protocol_version,
                 );
             }
             Action::DeployContract(deploy_contract) => {
@@ -449,6 +459,9 @@ impl Runtime {
                     delete_account,
                 )?;
             }
+            Action::IdempotencyKey(_) => {
+                // The key is indexed from the transaction by the chain, there is nothing to apply.
+            }
         };
         Ok(result)
     }
@@ -1092,7 +1105,7 @@ impl Runtime {
         transactions: &[SignedTransaction],
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<ApplyResult, RuntimeError> {
-        let trie = Rc::new(trie);
+        let trie = Arc::new(trie);
         let initial_state = TrieUpdate::new(trie.clone(), root);
         let mut state_update = TrieUpdate::new(trie.clone(), root);
 
@@ -1112,6 +1125,12 @@ impl Runtime {
         let mut outcomes = vec![];
         let mut total_gas_burnt = 0;
 
+        if let Some(verified_signatures) = &apply_state.verified_signatures {
+            // Verifies the signatures that weren't seen at admission in parallel. Invalid ones are
+            // not cached and will be reported by `process_transaction` below.
+            verified_signatures.verify_batch(transactions);
+        }
+
         for signed_transaction in transactions {
             let (receipt, outcome_with_id) = self.process_transaction(
                 &mut state_update,
@@ -1202,15 +1221,48 @@ impl Runtime {
         }
 
         // And then we process the new incoming receipts. These are receipts from other shards.
-        for receipt in incoming_receipts.iter() {
-            // Validating new incoming no matter whether we have available gas or not. We don't
-            // want to store invalid receipts in state as delayed.
-            validate_receipt(&apply_state.config.wasm_config.limit_config, &receipt)
-                .map_err(RuntimeError::ReceiptValidationError)?;
-            if total_gas_burnt < gas_limit {
-                process_receipt(&receipt, &mut state_update, &mut total_gas_burnt)?;
-            } else {
-                Self::delay_receipt(&mut state_update, &mut delayed_receipts_indices, receipt)?;
+        // If all of them fit into the gas limit, receipts for different accounts are applied in
+        // parallel. There are no uncommitted changes at this point, because receipts are only
+        // delayed once the gas limit is reached.
+        let parallel_groups = independent_receipt_groups(
+            apply_state,
+            incoming_receipts,
+            gas_limit.saturating_sub(total_gas_burnt),
+        )
+        .filter(|_| {
+            incoming_receipts.iter().all(|receipt| {
+                validate_receipt(&apply_state.config.wasm_config.limit_config, receipt).is_ok()
+            })
+        });
+        if let Some(groups) = parallel_groups {
+            let processed_receipts = self.apply_receipts_in_parallel(
+                &mut state_update,
+                apply_state,
+                incoming_receipts,
+                &groups,
+                &mut stats,
+                epoch_info_provider,
+            )?;
+            for processed in processed_receipts {
+                outgoing_receipts.extend(processed.outgoing_receipts);
+                validator_proposals.extend(processed.validator_proposals);
+                if let Some(outcome_with_id) = processed.outcome {
+                    total_gas_burnt =
+                        safe_add_gas(total_gas_burnt, outcome_with_id.outcome.gas_burnt)?;
+                    outcomes.push(outcome_with_id);
+                }
+            }
+        } else {
+            for receipt in incoming_receipts.iter() {
+                // Validating new incoming no matter whether we have available gas or not. We don't
+                // want to store invalid receipts in state as delayed.
+                validate_receipt(&apply_state.config.wasm_config.limit_config, &receipt)
+                    .map_err(RuntimeError::ReceiptValidationError)?;
+                if total_gas_burnt < gas_limit {
+                    process_receipt(&receipt, &mut state_update, &mut total_gas_burnt)?;
+                } else {
+                    Self::delay_receipt(&mut state_update, &mut delayed_receipts_indices, receipt)?;
+                }
             }
         }
 
@@ -1536,6 +1588,7 @@ mod tests {
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(RuntimeConfig::default()),
             cache: Some(Arc::new(StoreCompiledContractCache { store: tries.get_store() })),
+            verified_signatures: None,
         };
 
         (runtime, tries, root, apply_state, signer, MockEpochInfoProvider::default())
@@ -1979,6 +2032,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_receipts_in_parallel() {
+        let (runtime, tries, root, mut apply_state, _, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 1);
+
+        // Transfers to accounts that don't exist fail and generate refunds.
+        let receivers = vec![alice_account(), "carol.near".to_string(), "dave.near".to_string()];
+        let receipts: Vec<Receipt> = generate_receipts(to_yocto(10_000), 9)
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut receipt)| {
+                receipt.receiver_id = receivers[i % receivers.len()].clone();
+                receipt
+            })
+            .collect();
+        let max_gas: Gas = receipts
+            .iter()
+            .map(|receipt| parallel::max_gas_burnt(&apply_state, receipt).unwrap())
+            .sum();
+        let apply = |apply_state: &ApplyState| {
+            runtime
+                .apply(
+                    tries.get_trie_for_shard(0),
+                    root,
+                    &None,
+                    apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                )
+                .unwrap()
+        };
+
+        // All receipts fit into the gas limit, but only the sequential application checks it
+        // precisely.
+        apply_state.gas_limit = Some(max_gas);
+        assert!(independent_receipt_groups(&apply_state, &receipts, max_gas).is_none());
+        let sequential = apply(&apply_state);
+        apply_state.gas_limit = Some(max_gas + 1);
+        assert_eq!(
+            independent_receipt_groups(&apply_state, &receipts, max_gas + 1),
+            Some(vec![vec![0, 3, 6], vec![1, 4, 7], vec![2, 5, 8]])
+        );
+        let parallel = apply(&apply_state);
+
+        assert_eq!(sequential.outcomes.len(), 9);
+        assert_eq!(parallel.state_root, sequential.state_root);
+        assert_eq!(parallel.outcomes, sequential.outcomes);
+        assert_eq!(parallel.outgoing_receipts, sequential.outgoing_receipts);
+        assert_eq!(
+            parallel.state_changes.try_to_vec().unwrap(),
+            sequential.state_changes.try_to_vec().unwrap()
+        );
+        assert_eq!(format!("{:?}", parallel.stats), format!("{:?}", sequential.stats));
+    }
+
     #[test]
     fn test_apply_invalid_incoming_receipts() {
         let initial_balance = to_yocto(1_000_000);
@@ -0,0 +1,166 @@
+//! Parallel application of incoming receipts.
+//!
+//! Everything a receipt reads or writes in the state is keyed by its receiver, so receipts with
+//! different receivers never touch the same keys. Receipts are grouped by receiver, each group is
+//! applied in order on its own fork of the state update and the forks are merged back in the order
+//! of the groups. The resulting state, state changes, outcomes and outgoing receipts are the same
+//! as if the receipts were applied one by one.
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use near_primitives::errors::RuntimeError;
+use near_primitives::receipt::{Receipt, ReceiptEnum};
+use near_primitives::transaction::ExecutionOutcomeWithId;
+use near_primitives::types::{AccountId, EpochInfoProvider, Gas, ValidatorStake};
+use near_store::TrieUpdate;
+
+use crate::config::{safe_add_balance, safe_add_gas, total_exec_fees, total_prepaid_gas};
+use crate::{ApplyState, ApplyStats, Runtime};
+
+/// Result of applying a single receipt.
+pub(crate) struct ProcessedReceipt {
+    pub outcome: Option<ExecutionOutcomeWithId>,
+    pub outgoing_receipts: Vec<Receipt>,
+    pub validator_proposals: Vec<ValidatorStake>,
+}
+
+struct ProcessedGroup {
+    state_update: TrieUpdate,
+    receipts: Vec<(usize, ProcessedReceipt)>,
+    stats: ApplyStats,
+}
+
+/// Upper bound of the gas that can be burnt by the receipt. It's unknown for data receipts,
+/// because they can trigger execution of a postponed receipt.
+pub(crate) fn max_gas_burnt(apply_state: &ApplyState, receipt: &Receipt) -> Option<Gas> {
+    match &receipt.receipt {
+        ReceiptEnum::Action(action_receipt) => {
+            let transaction_costs = &apply_state.config.transaction_costs;
+            let exec_fees = total_exec_fees(
+                transaction_costs,
+                &action_receipt.actions,
+                &receipt.receiver_id,
+                apply_state.current_protocol_version,
+            )
+            .ok()?;
+            let exec_gas = safe_add_gas(
+                exec_fees,
+                transaction_costs.action_receipt_creation_config.exec_fee(),
+            )
+            .ok()?;
+            safe_add_gas(exec_gas, total_prepaid_gas(&action_receipt.actions).ok()?).ok()
+        }
+        ReceiptEnum::Data(_) => None,
+    }
+}
+
+/// Splits receipts into groups of indices with the same receiver, in the order of the first
+/// receipt of every group. Returns `None` if the receipts can't be applied in parallel: there are
+/// less than 2 groups, the gas of some receipt is not bounded, or the receipts may exceed
+/// `available_gas` in which case some of them have to be delayed.
+pub(crate) fn independent_receipt_groups(
+    apply_state: &ApplyState,
+    receipts: &[Receipt],
+    available_gas: Gas,
+) -> Option<Vec<Vec<usize>>> {
+    let mut total_gas: Gas = 0;
+    let mut group_indices: HashMap<&AccountId, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (index, receipt) in receipts.iter().enumerate() {
+        total_gas = safe_add_gas(total_gas, max_gas_burnt(apply_state, receipt)?).ok()?;
+        let group_index = *group_indices.entry(&receipt.receiver_id).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group_index].push(index);
+    }
+    if total_gas >= available_gas || groups.len() < 2 {
+        return None;
+    }
+    Some(groups)
+}
+
+impl Runtime {
+    /// Applies the groups of receipts returned by `independent_receipt_groups` concurrently.
+    /// Returns results in the order of `receipts`. If some receipts fail, the error of the first
+    /// one is returned, same as for sequential application.
+    pub(crate) fn apply_receipts_in_parallel(
+        &self,
+        state_update: &mut TrieUpdate,
+        apply_state: &ApplyState,
+        receipts: &[Receipt],
+        groups: &[Vec<usize>],
+        stats: &mut ApplyStats,
+        epoch_info_provider: &dyn EpochInfoProvider,
+    ) -> Result<Vec<ProcessedReceipt>, RuntimeError> {
+        let forks: Vec<_> = groups.iter().map(|group| (state_update.fork(), group)).collect();
+        let results: Vec<Result<ProcessedGroup, (usize, RuntimeError)>> = forks
+            .into_par_iter()
+            .map(|(mut fork, group)| {
+                let mut group_stats = ApplyStats::default();
+                let mut processed_receipts = Vec::with_capacity(group.len());
+                for &index in group {
+                    let mut outgoing_receipts = vec![];
+                    let mut validator_proposals = vec![];
+                    let outcome = self
+                        .process_receipt(
+                            &mut fork,
+                            apply_state,
+                            &receipts[index],
+                            &mut outgoing_receipts,
+                            &mut validator_proposals,
+                            &mut group_stats,
+                            epoch_info_provider,
+                        )
+                        .map_err(|err| (index, err))?;
+                    processed_receipts.push((
+                        index,
+                        ProcessedReceipt { outcome, outgoing_receipts, validator_proposals },
+                    ));
+                }
+                Ok(ProcessedGroup {
+                    state_update: fork,
+                    receipts: processed_receipts,
+                    stats: group_stats,
+                })
+            })
+            .collect();
+
+        let mut processed_receipts: Vec<Option<ProcessedReceipt>> =
+            receipts.iter().map(|_| None).collect();
+        let mut first_error: Option<(usize, RuntimeError)> = None;
+        for result in results {
+            match result {
+                Ok(group) => {
+                    state_update.merge_fork(group.state_update);
+                    stats.tx_burnt_amount =
+                        safe_add_balance(stats.tx_burnt_amount, group.stats.tx_burnt_amount)?;
+                    stats.slashed_burnt_amount = safe_add_balance(
+                        stats.slashed_burnt_amount,
+                        group.stats.slashed_burnt_amount,
+                    )?;
+                    stats.other_burnt_amount =
+                        safe_add_balance(stats.other_burnt_amount, group.stats.other_burnt_amount)?;
+                    stats.gas_deficit_amount =
+                        safe_add_balance(stats.gas_deficit_amount, group.stats.gas_deficit_amount)?;
+                    for (index, processed) in group.receipts {
+                        processed_receipts[index] = Some(processed);
+                    }
+                }
+                Err((index, err)) => {
+                    if first_error.as_ref().map_or(true, |(first_index, _)| index < *first_index) {
+                        first_error = Some((index, err));
+                    }
+                }
+            }
+        }
+        if let Some((_, err)) = first_error {
+            return Err(err);
+        }
+        Ok(processed_receipts
+            .into_iter()
+            .map(|processed| processed.expect("Every receipt belongs to a group"))
+            .collect())
+    }
+}
@@ -0,0 +1,122 @@
+use rayon::prelude::*;
+
+use near_crypto::SignatureVerificationCache;
+use near_primitives::transaction::SignedTransaction;
+
+/// Default number of verified signatures to remember.
+pub const DEFAULT_VERIFIED_SIGNATURES_CACHE_SIZE: usize = 100_000;
+
+/// LRU of transaction signatures that were already verified by this node, e.g. at the pool
+/// admission, so that applying a chunk with the same transactions skips redundant signature
+/// verification.
+///
+/// Transaction signatures are stored in a `SignatureVerificationCache` of the signed transaction
+/// hash, which is keyed by the signature too: the transaction hash does not cover the signature,
+/// so the same transaction may come with a different (invalid) one.
+pub struct VerifiedSignaturesCache {
+    cache: SignatureVerificationCache,
+}
+
+impl VerifiedSignaturesCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { cache: SignatureVerificationCache::new(capacity) }
+    }
+
+    /// Returns true if the signature of the given transaction is known to be valid.
+    pub fn contains(&self, transaction: &SignedTransaction) -> bool {
+        self.cache.contains(
+            transaction.get_hash().as_ref(),
+            &transaction.signature,
+            &transaction.transaction.public_key,
+        )
+    }
+
+    /// Remembers the signature of the given transaction as valid. The caller must have verified it.
+    pub fn insert(&self, transaction: &SignedTransaction) {
+        self.cache.insert(
+            transaction.get_hash().as_ref(),
+            &transaction.signature,
+            &transaction.transaction.public_key,
+        )
+    }
+
+    /// Verifies the signature of the transaction unless it is in the cache already, and remembers
+    /// it if valid.
+    pub fn verify(&self, transaction: &SignedTransaction) -> bool {
+        self.cache.verify(
+            transaction.get_hash().as_ref(),
+            &transaction.signature,
+            &transaction.transaction.public_key,
+        )
+    }
+
+    /// Verifies signatures of the transactions that are not in the cache yet on the global rayon
+    /// thread pool and remembers the valid ones. Returns false if any of the signatures is invalid.
+    pub fn verify_batch(&self, transactions: &[SignedTransaction]) -> bool {
+        let unverified: Vec<_> =
+            transactions.iter().filter(|transaction| !self.contains(transaction)).collect();
+        unverified
+            .par_iter()
+            .map(|transaction| self.verify(transaction))
+            .collect::<Vec<bool>>()
+            .into_iter()
+            .all(|is_valid| is_valid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for VerifiedSignaturesCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_VERIFIED_SIGNATURES_CACHE_SIZE)
+    }
+}
+
+impl std::fmt::Debug for VerifiedSignaturesCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VerifiedSignaturesCache").field("len", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_primitives::hash::CryptoHash;
+
+    use super::*;
+
+    fn transaction(signer: &dyn Signer, nonce: u64) -> SignedTransaction {
+        SignedTransaction::send_money(
+            nonce,
+            "alice.near".to_string(),
+            "bob.near".to_string(),
+            signer,
+            1,
+            CryptoHash::default(),
+        )
+    }
+
+    #[test]
+    fn test_verified_signatures_cache() {
+        let signer = InMemorySigner::from_seed("alice.near", KeyType::ED25519, "alice");
+        let cache = VerifiedSignaturesCache::new(10);
+        let transactions: Vec<_> = (1..5).map(|nonce| transaction(&signer, nonce)).collect();
+        assert!(!cache.contains(&transactions[0]));
+        assert!(cache.verify_batch(&transactions));
+        assert_eq!(cache.len(), 4);
+        assert!(transactions.iter().all(|tx| cache.contains(tx)));
+
+        let mut forged = transactions[0].clone();
+        forged.signature = transactions[1].signature.clone();
+        assert!(!cache.contains(&forged));
+        assert!(!cache.verify_batch(&[forged.clone()]));
+        assert!(!cache.verify(&forged));
+        assert!(!cache.contains(&forged));
+    }
+}
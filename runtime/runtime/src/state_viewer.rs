@@ -1,4 +1,5 @@
 use std::str;
+use std::sync::Arc;
 use std::time::Instant;
 
 use borsh::BorshSerialize;
@@ -6,6 +7,7 @@ use log::debug;
 
 use near_crypto::{KeyType, PublicKey};
 use near_primitives::account::{AccessKey, Account};
+use near_primitives::contract::MethodMetadata;
 use near_primitives::hash::CryptoHash;
 use near_primitives::serialize::to_base64;
 use near_primitives::trie_key::trie_key_parsers;
@@ -53,33 +55,77 @@ impl TrieViewer {
             .ok_or_else(|| format!("access key {} does not exist while viewing", public_key).into())
     }
 
+    /// Returns the contract data of `account_id` starting with `prefix`, after `cursor` and up to
+    /// `limit` values. With `include_proof`, the trie nodes read are returned as the proof.
     pub fn view_state(
         &self,
         state_update: &TrieUpdate,
         account_id: &AccountId,
         prefix: &[u8],
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+        include_proof: bool,
     ) -> Result<ViewStateResult, Box<dyn std::error::Error>> {
         if !is_valid_account_id(account_id) {
             return Err(format!("Account ID '{}' is not valid", account_id).into());
         }
+        let trie = if include_proof {
+            Arc::new(state_update.trie.recording_reads())
+        } else {
+            Arc::clone(&state_update.trie)
+        };
         let mut values = vec![];
+        let mut next_cursor = None;
         let query = trie_key_parsers::get_raw_prefix_for_contract_data(account_id, prefix);
         let acc_sep_len = query.len() - prefix.len();
-        let mut iter = state_update.trie.iter(&state_update.get_root())?;
-        iter.seek(&query)?;
+        let mut iter = trie.iter(&state_update.get_root())?;
+        match cursor {
+            Some(cursor) if cursor > prefix => {
+                let mut start = query[..acc_sep_len].to_vec();
+                start.extend_from_slice(cursor);
+                iter.seek(&start)?;
+            }
+            _ => iter.seek(&query)?,
+        }
         for item in iter {
             let (key, value) = item?;
             if !key.starts_with(&query.as_ref()) {
                 break;
             }
+            // The cursor itself was returned with the previous page.
+            if cursor.map_or(false, |cursor| key[acc_sep_len..] == cursor[..]) {
+                continue;
+            }
+            if limit.map_or(false, |limit| values.len() >= limit) {
+                next_cursor = values.last().map(|item: &StateItem| item.key.clone());
+                break;
+            }
             values.push(StateItem {
                 key: to_base64(&key[acc_sep_len..]),
                 value: to_base64(&value),
                 proof: vec![],
             });
         }
-        // TODO(2076): Add proofs for the storage items.
-        Ok(ViewStateResult { values, proof: vec![] })
+        let proof = trie
+            .recorded_storage()
+            .map(|storage| storage.nodes.0.iter().map(to_base64).collect())
+            .unwrap_or_default();
+        Ok(ViewStateResult { values, proof, next_cursor })
+    }
+
+    pub fn view_contract_metadata(
+        &self,
+        state_update: &TrieUpdate,
+        account_id: &AccountId,
+    ) -> Result<Vec<MethodMetadata>, Box<dyn std::error::Error>> {
+        if !is_valid_account_id(account_id) {
+            return Err(format!("Account ID '{}' is not valid", account_id).into());
+        }
+        let account = get_account(state_update, account_id)?
+            .ok_or_else(|| format!("Account {:?} doesn't exist", account_id))?;
+        let code = get_code_with_cache(state_update, account_id, &account)?
+            .ok_or_else(|| format!("cannot find contract code for account {}", account_id))?;
+        Ok(near_vm_runner::method_metadata(&code.code)?)
     }
 
     pub fn call_function(
@@ -182,12 +228,15 @@ impl TrieViewer {
 mod tests {
     use crate::state_viewer::TrieViewer;
     use crate::AccountId;
+    use near_primitives::challenge::PartialState;
     use near_primitives::hash::CryptoHash;
+    use near_primitives::serialize::{from_base64, to_base64};
     use near_primitives::test_utils::MockEpochInfoProvider;
     use near_primitives::trie_key::TrieKey;
     use near_primitives::types::{EpochId, StateChangeCause};
     use near_primitives::version::PROTOCOL_VERSION;
     use near_primitives::views::{StateItem, ViewApplyState};
+    use near_store::{PartialStorage, Trie};
     use testlib::runtime_utils::{
         alice_account, encode_int, get_runtime_and_trie, get_test_trie_viewer,
     };
@@ -333,7 +382,9 @@ mod tests {
 
         let state_update = tries.new_trie_update(0, new_root);
         let trie_viewer = TrieViewer::new();
-        let result = trie_viewer.view_state(&state_update, &alice_account(), b"").unwrap();
+        let result = trie_viewer
+            .view_state(&state_update, &alice_account(), b"", None, None, false)
+            .unwrap();
         assert_eq!(result.proof, Vec::<String>::new());
         assert_eq!(
             result.values,
@@ -350,9 +401,13 @@ mod tests {
                 }
             ]
         );
-        let result = trie_viewer.view_state(&state_update, &alice_account(), b"xyz").unwrap();
+        let result = trie_viewer
+            .view_state(&state_update, &alice_account(), b"xyz", None, None, false)
+            .unwrap();
         assert_eq!(result.values, []);
-        let result = trie_viewer.view_state(&state_update, &alice_account(), b"test123").unwrap();
+        let result = trie_viewer
+            .view_state(&state_update, &alice_account(), b"test123", None, None, false)
+            .unwrap();
         assert_eq!(
             result.values,
             [StateItem {
@@ -390,4 +445,50 @@ mod tests {
 
         assert_eq!(logs, vec!["hello".to_string()]);
     }
+
+    #[test]
+    fn test_view_state_paginated_with_proof() {
+        let (_, tries, root) = get_runtime_and_trie();
+        let mut state_update = tries.new_trie_update(0, root);
+        for key in &[b"test123", b"test321"] {
+            state_update.set(
+                TrieKey::ContractData { account_id: alice_account(), key: key.to_vec() },
+                b"123".to_vec(),
+            );
+        }
+        state_update.commit(StateChangeCause::InitialState);
+        let trie_changes = state_update.finalize().unwrap().0;
+        let (db_changes, new_root) = tries.apply_all(&trie_changes, 0).unwrap();
+        db_changes.commit().unwrap();
+
+        let state_update = tries.new_trie_update(0, new_root);
+        let trie_viewer = TrieViewer::new();
+        let result = trie_viewer
+            .view_state(&state_update, &alice_account(), b"test", None, Some(1), true)
+            .unwrap();
+        assert_eq!(result.values.len(), 1);
+        assert_eq!(result.values[0].key, to_base64(b"test123"));
+        assert_eq!(result.next_cursor, Some(to_base64(b"test123")));
+        // The proof is enough to read the returned value without the rest of the state.
+        let nodes = result.proof.iter().map(|node| from_base64(node).unwrap()).collect();
+        let partial_trie =
+            Trie::from_recorded_storage(PartialStorage { nodes: PartialState(nodes) });
+        let key = TrieKey::ContractData { account_id: alice_account(), key: b"test123".to_vec() };
+        assert_eq!(partial_trie.get(&new_root, &key.to_vec()).unwrap(), Some(b"123".to_vec()));
+
+        let result = trie_viewer
+            .view_state(
+                &state_update,
+                &alice_account(),
+                b"test",
+                Some(&b"test123"[..]),
+                Some(1),
+                false,
+            )
+            .unwrap();
+        assert_eq!(result.values.len(), 1);
+        assert_eq!(result.values[0].key, to_base64(b"test321"));
+        assert_eq!(result.next_cursor, None);
+        assert!(result.proof.is_empty());
+    }
 }
@@ -1,5 +1,6 @@
 use near_crypto::key_conversion::is_valid_staking_key;
 use near_primitives::account::AccessKeyPermission;
+use near_primitives::checked_feature;
 use near_primitives::errors::{
     ActionsValidationError, InvalidAccessKeyError, InvalidTxError, ReceiptValidationError,
     RuntimeError,
@@ -7,7 +8,7 @@ use near_primitives::errors::{
 use near_primitives::receipt::{ActionReceipt, DataReceipt, Receipt, ReceiptEnum};
 use near_primitives::transaction::{
     Action, AddKeyAction, DeleteAccountAction, DeployContractAction, FunctionCallAction,
-    SignedTransaction, StakeAction,
+    IdempotencyKeyAction, SignedTransaction, StakeAction, MAX_IDEMPOTENCY_KEY_LENGTH,
 };
 use near_primitives::utils::is_valid_account_id;
 use near_primitives::version::ProtocolVersion;
@@ -50,6 +51,22 @@ pub fn validate_transaction(
         return Err(InvalidTxError::InvalidSignature.into());
     }
 
+    if transaction.actions.iter().any(|action| matches!(action, Action::IdempotencyKey(_)))
+        && !checked_feature!(
+            "protocol_feature_idempotency_key",
+            IdempotencyKey,
+            current_protocol_version
+        )
+    {
+        return Err(InvalidTxError::ActionsValidation(
+            ActionsValidationError::UnsupportedProtocolFeature {
+                protocol_feature: "IdempotencyKey".to_string(),
+                version: current_protocol_version,
+            },
+        )
+        .into());
+    }
+
     validate_actions(&config.wasm_config.limit_config, &transaction.actions)
         .map_err(|e| InvalidTxError::ActionsValidation(e))?;
 
@@ -283,6 +300,15 @@ pub(crate) fn validate_actions(
         });
     }
 
+    if actions.iter().skip(1).any(|action| matches!(action, Action::IdempotencyKey(_))) {
+        return Err(ActionsValidationError::IdempotencyKeyMustBeFirst);
+    }
+    if let Some(Action::IdempotencyKey(_)) = actions.first() {
+        if !actions.iter().any(|action| matches!(action, Action::FunctionCall(_))) {
+            return Err(ActionsValidationError::IdempotencyKeyWithoutFunctionCall);
+        }
+    }
+
     let mut iter = actions.iter().peekable();
     while let Some(action) = iter.next() {
         if let Action::DeleteAccount(_) = action {
@@ -319,6 +345,7 @@ pub fn validate_action(
         Action::AddKey(a) => validate_add_key_action(limit_config, a),
         Action::DeleteKey(_) => Ok(()),
         Action::DeleteAccount(a) => validate_delete_account_action(a),
+        Action::IdempotencyKey(a) => validate_idempotency_key_action(a),
     }
 }
 
@@ -425,6 +452,20 @@ fn validate_delete_account_action(
     Ok(())
 }
 
+/// Validates `IdempotencyKeyAction`. Checks that the key length doesn't exceed the limit.
+fn validate_idempotency_key_action(
+    action: &IdempotencyKeyAction,
+) -> Result<(), ActionsValidationError> {
+    if action.key.len() as u64 > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Err(ActionsValidationError::IdempotencyKeyLengthExceeded {
+            length: action.key.len() as u64,
+            limit: MAX_IDEMPOTENCY_KEY_LENGTH,
+        });
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -1407,8 +1448,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_idempotency_key_must_be_first() {
+        let limit_config = VMLimitConfig::default();
+        let function_call = Action::FunctionCall(FunctionCallAction {
+            method_name: "hello".to_string(),
+            args: b"abc".to_vec(),
+            gas: 100,
+            deposit: 0,
+        });
+        let idempotency_key = Action::IdempotencyKey(IdempotencyKeyAction { key: b"key".to_vec() });
+        assert_eq!(
+            validate_actions(&limit_config, &vec![idempotency_key.clone(), function_call.clone()]),
+            Ok(()),
+        );
+        assert_eq!(
+            validate_actions(&limit_config, &vec![function_call.clone(), idempotency_key.clone()])
+                .expect_err("Expected an error"),
+            ActionsValidationError::IdempotencyKeyMustBeFirst,
+        );
+        assert_eq!(
+            validate_actions(
+                &limit_config,
+                &vec![idempotency_key, Action::CreateAccount(CreateAccountAction {})]
+            )
+            .expect_err("Expected an error"),
+            ActionsValidationError::IdempotencyKeyWithoutFunctionCall,
+        );
+    }
+
     // Individual actions
 
+    #[test]
+    fn test_validate_action_idempotency_key_too_long() {
+        assert_eq!(
+            validate_action(
+                &VMLimitConfig::default(),
+                &Action::IdempotencyKey(IdempotencyKeyAction {
+                    key: vec![0; MAX_IDEMPOTENCY_KEY_LENGTH as usize + 1]
+                })
+            )
+            .expect_err("Expected an error"),
+            ActionsValidationError::IdempotencyKeyLengthExceeded {
+                length: MAX_IDEMPOTENCY_KEY_LENGTH + 1,
+                limit: MAX_IDEMPOTENCY_KEY_LENGTH
+            },
+        );
+    }
+
     #[test]
     fn test_validate_action_valid_create_account() {
         validate_action(&VMLimitConfig::default(), &Action::CreateAccount(CreateAccountAction {}))
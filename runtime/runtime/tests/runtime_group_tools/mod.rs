@@ -68,6 +68,7 @@ impl StandaloneRuntime {
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(runtime_config),
             cache: None,
+            verified_signatures: None,
         };
 
         Self {
@@ -18,8 +18,10 @@ use near_primitives::types::{BlockHeight, ChunkExtra, ShardId, StateRoot};
 use near_store::test_utils::create_test_store;
 use near_store::{create_store, Store, TrieIterator};
 use neard::{get_default_home, get_store_path, load_config, NearConfig, NightshadeRuntime};
+use state_diff::diff_tries;
 use state_dump::state_dump;
 
+mod state_diff;
 mod state_dump;
 
 #[allow(unused)]
@@ -355,6 +357,62 @@ fn check_block_chunk_existence(store: Arc<Store>, near_config: &NearConfig) {
     println!("Block check succeed");
 }
 
+fn diff_state(
+    store: Arc<Store>,
+    home_dir: &Path,
+    near_config: &NearConfig,
+    other_home_dir: &Path,
+    height: Option<BlockHeight>,
+    all: bool,
+) {
+    let mode = match height {
+        Some(height) => LoadTrieMode::Height(height),
+        None => LoadTrieMode::Latest,
+    };
+    let (runtime, state_roots, header) =
+        load_trie_stop_at_height(store, home_dir, near_config, mode);
+    let other_near_config = load_config(other_home_dir);
+    let other_store = create_store(&get_store_path(other_home_dir));
+    let (other_runtime, other_state_roots, other_header) = load_trie_stop_at_height(
+        other_store,
+        other_home_dir,
+        &other_near_config,
+        LoadTrieMode::Height(header.height()),
+    );
+    if header.hash() != other_header.hash() {
+        panic!(
+            "Nodes have different blocks at height {}: {} and {}",
+            header.height(),
+            header.hash(),
+            other_header.hash()
+        );
+    }
+    println!("Comparing state before block #{} {}", header.height(), header.hash());
+
+    let mut num_differences = 0;
+    for (shard_id, (state_root, other_state_root)) in
+        state_roots.iter().zip(other_state_roots.iter()).enumerate()
+    {
+        if state_root == other_state_root {
+            println!("shard {}: state roots match {}", shard_id, state_root);
+            continue;
+        }
+        println!("shard {}: state roots differ {} and {}", shard_id, state_root, other_state_root);
+        let trie = runtime.get_trie_for_shard(shard_id as ShardId);
+        let other_trie = other_runtime.get_trie_for_shard(shard_id as ShardId);
+        let differences =
+            diff_tries(&trie, state_root, &other_trie, other_state_root, all).unwrap();
+        for difference in differences.iter() {
+            println!("{}", difference.describe());
+        }
+        num_differences += differences.len();
+        if !all && num_differences > 0 {
+            break;
+        }
+    }
+    println!("Found {} differences", num_differences);
+}
+
 fn main() {
     init_integration_logger();
 
@@ -452,6 +510,29 @@ fn main() {
                 )
                 .help("View head of the storage"),
         )
+        .subcommand(
+            SubCommand::with_name("diff_state")
+                .arg(
+                    Arg::with_name("other")
+                        .long("other")
+                        .required(true)
+                        .help("Home directory of the node to compare with")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .help("Height of the block to compare the state before, head by default")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Whether to print all differences instead of the first one")
+                        .takes_value(false),
+                )
+                .help("Find differences of the state with another node at the same block"),
+        )
         .subcommand(
             SubCommand::with_name("check_block")
                 .help("Check whether the node has all the blocks up to its head"),
@@ -531,6 +612,12 @@ fn main() {
             let view_chunks = args.is_present("chunk");
             view_chain(store, &near_config, height, view_block, view_chunks);
         }
+        ("diff_state", Some(args)) => {
+            let other_home_dir = args.value_of("other").map(|dir| Path::new(dir)).unwrap();
+            let height = args.value_of("height").map(|s| s.parse::<u64>().unwrap());
+            let all = args.is_present("all");
+            diff_state(store, home_dir, &near_config, other_home_dir, height, all);
+        }
         ("check_block", Some(_)) => {
             check_block_chunk_existence(store, &near_config);
         }
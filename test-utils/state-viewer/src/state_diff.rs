@@ -0,0 +1,137 @@
+use std::iter::Peekable;
+
+use near_primitives::serialize::to_base;
+use near_primitives::state_record::StateRecord;
+use near_primitives::types::StateRoot;
+use near_store::{StorageError, Trie, TrieIterator};
+
+/// Key-value pair of the state that differs between two tries.
+#[derive(Debug, PartialEq)]
+pub struct StateDifference {
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+    pub other_value: Option<Vec<u8>>,
+}
+
+impl StateDifference {
+    /// Human readable description of the difference, with the account it belongs to if the key
+    /// can be parsed.
+    pub fn describe(&self) -> String {
+        let describe_value = |value: &Option<Vec<u8>>| match value {
+            Some(value) => StateRecord::from_raw_key_value(self.key.clone(), value.clone())
+                .map(|record| record.to_string())
+                .unwrap_or_else(|| format!("raw value {}", to_base(value))),
+            None => "missing".to_string(),
+        };
+        format!(
+            "key {}\n  this:  {}\n  other: {}",
+            to_base(&self.key),
+            describe_value(&self.value),
+            describe_value(&self.other_value)
+        )
+    }
+}
+
+/// Walks both tries in key order and returns the differences. Stops at the first difference
+/// unless `all` is set.
+pub fn diff_tries(
+    trie: &Trie,
+    state_root: &StateRoot,
+    other_trie: &Trie,
+    other_state_root: &StateRoot,
+    all: bool,
+) -> Result<Vec<StateDifference>, StorageError> {
+    let mut differences = vec![];
+    if state_root == other_state_root {
+        return Ok(differences);
+    }
+    let mut iter = TrieIterator::new(trie, state_root)?.peekable();
+    let mut other_iter = TrieIterator::new(other_trie, other_state_root)?.peekable();
+    loop {
+        let difference = match (peek_key(&mut iter)?, peek_key(&mut other_iter)?) {
+            (None, None) => break,
+            (Some(key), Some(other_key)) if key == other_key => {
+                let (key, value) = iter.next().unwrap()?;
+                let (_, other_value) = other_iter.next().unwrap()?;
+                if value == other_value {
+                    continue;
+                }
+                StateDifference { key, value: Some(value), other_value: Some(other_value) }
+            }
+            (Some(key), other_key) if other_key.map_or(true, |other_key| key < other_key) => {
+                let (key, value) = iter.next().unwrap()?;
+                StateDifference { key, value: Some(value), other_value: None }
+            }
+            _ => {
+                let (key, other_value) = other_iter.next().unwrap()?;
+                StateDifference { key, value: None, other_value: Some(other_value) }
+            }
+        };
+        differences.push(difference);
+        if !all {
+            break;
+        }
+    }
+    Ok(differences)
+}
+
+fn peek_key<'a>(iter: &'a mut Peekable<TrieIterator>) -> Result<Option<&'a [u8]>, StorageError> {
+    match iter.peek() {
+        Some(Ok((key, _))) => Ok(Some(key)),
+        Some(Err(err)) => Err(err.clone()),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_store::test_utils::{create_tries, test_populate_trie};
+
+    use super::*;
+
+    fn changes(items: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+        items.iter().map(|(key, value)| (key.to_vec(), Some(value.to_vec()))).collect()
+    }
+
+    #[test]
+    fn test_diff_tries() {
+        let tries = create_tries();
+        let other_tries = create_tries();
+        let root = test_populate_trie(
+            &tries,
+            &Trie::empty_root(),
+            0,
+            changes(&[(b"aa", b"1"), (b"ab", b"2"), (b"b", b"3"), (b"c", b"4")]),
+        );
+        let other_root = test_populate_trie(
+            &other_tries,
+            &Trie::empty_root(),
+            0,
+            changes(&[(b"aa", b"1"), (b"ab", b"5"), (b"ac", b"6"), (b"c", b"4")]),
+        );
+        let trie = tries.get_trie_for_shard(0);
+        let other_trie = other_tries.get_trie_for_shard(0);
+
+        assert_eq!(diff_tries(&trie, &root, &trie, &root, true).unwrap(), vec![]);
+        assert_eq!(
+            diff_tries(&trie, &root, &other_trie, &other_root, false).unwrap(),
+            vec![StateDifference {
+                key: b"ab".to_vec(),
+                value: Some(b"2".to_vec()),
+                other_value: Some(b"5".to_vec())
+            }]
+        );
+        let differences = diff_tries(&trie, &root, &other_trie, &other_root, true).unwrap();
+        assert_eq!(
+            differences
+                .iter()
+                .map(|difference| (
+                    difference.key.as_slice(),
+                    difference.value.is_some(),
+                    difference.other_value.is_some()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(&b"ab"[..], true, true), (&b"ac"[..], false, true), (&b"b"[..], true, false)]
+        );
+    }
+}
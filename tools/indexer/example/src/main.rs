@@ -207,6 +207,7 @@ fn main() {
                 home_dir,
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
                 await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
+                filter: None,
             };
             let indexer = near_indexer::Indexer::new(indexer_config);
             let stream = indexer.streamer();